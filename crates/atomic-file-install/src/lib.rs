@@ -2,10 +2,15 @@
 //! can be either noclobber (fail if destination already exists) or
 //! replacing it atomically if it exists.
 
-use std::{fs, io, path::Path};
+use std::{
+    cell::RefCell,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering::Relaxed},
+};
 
 use reflink_copy::reflink_or_copy;
-use tempfile::{NamedTempFile, TempPath};
+use tempfile::{NamedTempFile, PathPersistError, TempPath};
 use tracing::{debug, warn};
 
 #[cfg(unix)]
@@ -14,6 +19,124 @@ use std::os::unix::fs::symlink as symlink_file_inner;
 #[cfg(windows)]
 use std::os::windows::fs::symlink_file as symlink_file_inner;
 
+/// A fallback taken because the destination filesystem does not support
+/// an operation, e.g. vfat does not support symlinks and permissions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Degradation {
+    /// `link` is created as a copy instead of a symlink.
+    SymlinkCopied { link: PathBuf },
+    /// `src` is copied to `dst` then removed instead of being renamed,
+    /// e.g. because they are on different filesystems.
+    RenameCopied { src: PathBuf, dst: PathBuf },
+    /// Permissions of `path` are not set.
+    PermissionsSkipped { path: PathBuf },
+}
+
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SymlinkCopied { link } => {
+                write!(
+                    f,
+                    "Created '{}' as a copy instead of a symlink",
+                    link.display()
+                )
+            }
+            Self::RenameCopied { src, dst } => write!(
+                f,
+                "Copied '{}' to '{}' instead of renaming it",
+                src.display(),
+                dst.display()
+            ),
+            Self::PermissionsSkipped { path } => {
+                write!(f, "Skipped setting permissions of '{}'", path.display())
+            }
+        }
+    }
+}
+
+thread_local! {
+    static DEGRADATIONS: RefCell<Vec<Degradation>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Take the [`Degradation`]s that occurred on the current thread since the
+/// last call.
+pub fn take_degradations() -> Vec<Degradation> {
+    DEGRADATIONS.with(|degradations| degradations.take())
+}
+
+fn degrade(degradation: Degradation, reason: &io::Error) {
+    static PERMISSIONS_WARNED: AtomicBool = AtomicBool::new(false);
+
+    if matches!(degradation, Degradation::PermissionsSkipped { .. })
+        && PERMISSIONS_WARNED.swap(true, Relaxed)
+    {
+        // Only warn once since it is likely to happen for every file.
+        debug!("{degradation}: {reason}");
+    } else {
+        warn!("{degradation}: {reason}");
+    }
+
+    DEGRADATIONS.with(|degradations| degradations.borrow_mut().push(degradation));
+}
+
+/// `EPERM`, returned by e.g. vfat on Linux for symlinks and permissions.
+#[cfg(unix)]
+const EPERM: i32 = 1;
+
+/// `EXDEV` on unix, `ERROR_NOT_SAME_DEVICE` on Windows.
+#[cfg(unix)]
+const EXDEV: i32 = 18;
+#[cfg(windows)]
+const EXDEV: i32 = 17;
+
+/// Return true if `err` indicates that the filesystem does not support
+/// the operation.
+///
+/// `PermissionDenied` is only taken as such for `EPERM`, since `EACCES` is
+/// returned for files the user may not write, which must not be skipped.
+fn is_unsupported(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(EPERM) {
+        return true;
+    }
+
+    err.kind() == io::ErrorKind::Unsupported
+}
+
+/// Return true if `src` could not be renamed because the destination is on
+/// another filesystem, or because it does not support renaming, so that it
+/// has to be copied instead.
+fn should_copy_rename(err: &io::Error) -> bool {
+    #[cfg(any(unix, windows))]
+    if err.raw_os_error() == Some(EXDEV) {
+        return true;
+    }
+
+    err.kind() == io::ErrorKind::Unsupported
+}
+
+/// Filesystem operations that might not be supported by the destination,
+/// abstracted so that failures can be injected in tests.
+trait Fs {
+    fn rename(&self, src: &Path, dst: &Path) -> io::Result<()> {
+        fs::rename(src, dst)
+    }
+
+    fn symlink_file(&self, dest: &Path, link: &Path) -> io::Result<()> {
+        symlink_file_inner(dest, link)
+    }
+
+    fn set_permissions(&self, file: &fs::File, permissions: fs::Permissions) -> io::Result<()> {
+        file.set_permissions(permissions)
+    }
+}
+
+struct StdFs;
+
+impl Fs for StdFs {}
+
 fn parent(p: &Path) -> io::Result<&Path> {
     p.parent().ok_or_else(|| {
         io::Error::new(
@@ -23,7 +146,7 @@ fn parent(p: &Path) -> io::Result<&Path> {
     })
 }
 
-fn copy_to_tempfile(src: &Path, dst: &Path) -> io::Result<NamedTempFile> {
+fn copy_to_tempfile(ops: &dyn Fs, src: &Path, dst: &Path) -> io::Result<NamedTempFile> {
     let parent = parent(dst)?;
     debug!("Creating named tempfile at '{}'", parent.display());
     let tempfile = NamedTempFile::new_in(parent)?;
@@ -46,7 +169,17 @@ fn copy_to_tempfile(src: &Path, dst: &Path) -> io::Result<NamedTempFile> {
         "Setting permissions of '{}' to '{permissions:#?}'",
         tempfile.path().display()
     );
-    tempfile.as_file().set_permissions(permissions)?;
+    if let Err(err) = ops.set_permissions(tempfile.as_file(), permissions) {
+        if !is_unsupported(&err) {
+            return Err(err);
+        }
+        degrade(
+            Degradation::PermissionsSkipped {
+                path: dst.to_owned(),
+            },
+            &err,
+        );
+    }
 
     Ok(tempfile)
 }
@@ -55,13 +188,17 @@ fn copy_to_tempfile(src: &Path, dst: &Path) -> io::Result<NamedTempFile> {
 ///
 /// This is a blocking function, must be called in `block_in_place` mode.
 pub fn atomic_install_noclobber(src: &Path, dst: &Path) -> io::Result<()> {
+    atomic_install_noclobber_inner(&StdFs, src, dst)
+}
+
+fn atomic_install_noclobber_inner(ops: &dyn Fs, src: &Path, dst: &Path) -> io::Result<()> {
     debug!(
         "Attempting to rename from '{}' to '{}'.",
         src.display(),
         dst.display()
     );
 
-    let tempfile = copy_to_tempfile(src, dst)?;
+    let tempfile = copy_to_tempfile(ops, src, dst)?;
 
     debug!(
         "Persisting '{}' to '{}', fail if dst already exists",
@@ -76,15 +213,34 @@ pub fn atomic_install_noclobber(src: &Path, dst: &Path) -> io::Result<()> {
 /// Atomically install a file, this atomically replace `dst` if it exists.
 ///
 /// This is a blocking function, must be called in `block_in_place` mode.
+///
+/// If `src` cannot be renamed to `dst`, it is copied then removed.
 pub fn atomic_install(src: &Path, dst: &Path) -> io::Result<()> {
+    atomic_install_inner(&StdFs, src, dst)
+}
+
+fn atomic_install_inner(ops: &dyn Fs, src: &Path, dst: &Path) -> io::Result<()> {
     debug!(
         "Attempting to atomically rename from '{}' to '{}'",
         src.display(),
         dst.display()
     );
 
-    if let Err(err) = fs::rename(src, dst) {
-        warn!("Attempting at atomic rename failed: {err}, fallback to other methods.");
+    if cfg!(windows) {
+        remove_old_files(dst);
+    }
+
+    let res = if cfg!(windows) && is_current_exe(dst) {
+        // Replacing it would fail, and so would `ReplaceFileW`.
+        debug!("'{}' is the running executable", dst.display());
+        replace_aside(ops, dst, || ops.rename(src, dst))
+    } else {
+        ops.rename(src, dst)
+            .or_else(|err| replace_in_use(ops, dst, err, || ops.rename(src, dst)))
+    };
+
+    if let Err(err) = res {
+        debug!("Attempting at atomic rename failed: {err}, fallback to other methods.");
 
         #[cfg(windows)]
         {
@@ -99,11 +255,31 @@ pub fn atomic_install(src: &Path, dst: &Path) -> io::Result<()> {
             }
         }
 
+        if !should_copy_rename(&err) {
+            return Err(err);
+        }
+
         // src and dst is not on the same filesystem/mountpoint.
         // Fallback to creating NamedTempFile on the parent dir of
         // dst.
 
-        persist(copy_to_tempfile(src, dst)?.into_temp_path(), dst)?;
+        let tempfile = copy_to_tempfile(ops, src, dst)?;
+        tempfile.as_file().sync_all()?;
+        persist(tempfile.into_temp_path(), dst).or_else(|PathPersistError { error, path }| {
+            replace_in_use(ops, dst, error, || Ok(persist(path, dst)?))
+        })?;
+
+        degrade(
+            Degradation::RenameCopied {
+                src: src.to_owned(),
+                dst: dst.to_owned(),
+            },
+            &err,
+        );
+
+        if let Err(err) = fs::remove_file(src) {
+            debug!("Failed to remove '{}': {err}", src.display());
+        }
     } else {
         debug!("Attempting at atomically succeeded.");
     }
@@ -111,20 +287,163 @@ pub fn atomic_install(src: &Path, dst: &Path) -> io::Result<()> {
     Ok(())
 }
 
+/// Suffix of the files moved aside because they were in use, see
+/// [`remove_old_files`].
+const OLD_SUFFIX: &str = ".__old__";
+
+/// Return true if `dst` cannot be replaced because it is in use, e.g. it is
+/// an executable running on Windows, which can be renamed but not replaced.
+fn is_in_use(err: &io::Error, dst: &Path) -> bool {
+    err.kind() == io::ErrorKind::PermissionDenied && fs::symlink_metadata(dst).is_ok()
+}
+
+/// Return true if `dst` is the executable of the current process.
+fn is_current_exe(dst: &Path) -> bool {
+    let current_exe = env::current_exe().and_then(fs::canonicalize);
+    match (current_exe, fs::canonicalize(dst)) {
+        (Ok(current_exe), Ok(dst)) => current_exe == dst,
+        _ => false,
+    }
+}
+
+/// Call `install` again after moving `dst` aside if it failed with `err`
+/// because `dst` is in use, otherwise return `err`.
+///
+/// `dst` is moved back if `install` fails again.
+fn replace_in_use(
+    ops: &dyn Fs,
+    dst: &Path,
+    err: io::Error,
+    install: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    if !is_in_use(&err, dst) {
+        return Err(err);
+    }
+    debug!(
+        "Failed to replace '{}': {err}, moving it aside as it may be in use",
+        dst.display()
+    );
+
+    replace_aside(ops, dst, install)
+}
+
+/// Call `install` after moving `dst` aside, `dst` is moved back if it
+/// fails.
+fn replace_aside(
+    ops: &dyn Fs,
+    dst: &Path,
+    install: impl FnOnce() -> io::Result<()>,
+) -> io::Result<()> {
+    let aside = move_aside(ops, dst)?;
+
+    match install() {
+        Ok(()) => {
+            let aside_path = aside.to_path_buf();
+            // A file in use on Windows cannot be removed until it is not
+            // used anymore, it is then left behind for the next install or
+            // removed on reboot.
+            if let Err(err) = aside.close() {
+                debug!("Failed to remove '{}': {err}", aside_path.display());
+
+                #[cfg(windows)]
+                if let Err(err) = win::remove_on_reboot(&aside_path) {
+                    debug!(
+                        "Failed to schedule the removal of '{}' on reboot: {err}",
+                        aside_path.display()
+                    );
+                }
+            }
+            Ok(())
+        }
+        Err(err) => {
+            if let Err(restore_err) = ops.rename(&aside, dst) {
+                warn!(
+                    "Failed to move '{}' back to '{}': {restore_err}",
+                    aside.display(),
+                    dst.display()
+                );
+                // Keep the original file rather than removing it on drop.
+                aside.keep().ok();
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Move `dst` to a temporary path next to it ending with [`OLD_SUFFIX`],
+/// which is removed on drop.
+fn move_aside(ops: &dyn Fs, dst: &Path) -> io::Result<TempPath> {
+    let file_name = dst.file_name().unwrap_or_default().to_string_lossy();
+    let aside = tempfile::Builder::new()
+        .prefix(&format!("{file_name}."))
+        .suffix(OLD_SUFFIX)
+        .tempfile_in(parent(dst)?)?
+        .into_temp_path();
+
+    debug!("Moving '{}' aside to '{}'", dst.display(), aside.display());
+    ops.rename(dst, &aside)?;
+
+    Ok(aside)
+}
+
+/// Remove the files left next to `path` by previous installs, which moved
+/// it aside as it was in use, e.g. an executable running on Windows.
+///
+/// Failures are ignored since the files may still be in use.
+///
+/// This is a blocking function, must be called in `block_in_place` mode.
+pub fn remove_old_files(path: &Path) {
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let prefix = format!("{}.", file_name.to_string_lossy());
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let is_old = name.to_str().map_or(false, |name| {
+            name.starts_with(&prefix) && name.ends_with(OLD_SUFFIX)
+        });
+        if !is_old {
+            continue;
+        }
+
+        let old = entry.path();
+        match fs::remove_file(&old) {
+            Ok(()) => debug!("Removed '{}' left by a previous install", old.display()),
+            Err(err) => debug!("Failed to remove '{}': {err}", old.display()),
+        }
+    }
+}
+
 /// Create a symlink at `link` to `dest`, this fails if the `link`
 /// already exists.
 ///
 /// This is a blocking function, must be called in `block_in_place` mode.
+///
+/// If the filesystem does not support symlinks, `link` is created as a copy.
 pub fn atomic_symlink_file_noclobber(dest: &Path, link: &Path) -> io::Result<()> {
-    match symlink_file_inner(dest, link) {
-        Ok(_) => Ok(()),
+    atomic_symlink_file_noclobber_inner(&StdFs, dest, link)
+}
 
-        #[cfg(windows)]
-        // Symlinks on Windows are disabled in some editions, so creating one is unreliable.
-        // Fallback to copy if it fails.
-        Err(_) => atomic_install_noclobber(dest, link),
+fn atomic_symlink_file_noclobber_inner(ops: &dyn Fs, dest: &Path, link: &Path) -> io::Result<()> {
+    match ops.symlink_file(dest, link) {
+        Ok(_) => Ok(()),
+        Err(err) if should_copy_symlink(&err) => {
+            let tempfile = copy_to_tempfile(ops, &symlink_target(dest, link)?, link)?;
+            tempfile.persist_noclobber(link)?;
+
+            degrade(
+                Degradation::SymlinkCopied {
+                    link: link.to_owned(),
+                },
+                &err,
+            );
 
-        #[cfg(not(windows))]
+            Ok(())
+        }
         Err(err) => Err(err),
     }
 }
@@ -133,7 +452,13 @@ pub fn atomic_symlink_file_noclobber(dest: &Path, link: &Path) -> io::Result<()>
 /// `link` if it already exists.
 ///
 /// This is a blocking function, must be called in `block_in_place` mode.
+///
+/// If the filesystem does not support symlinks, `link` is created as a copy.
 pub fn atomic_symlink_file(dest: &Path, link: &Path) -> io::Result<()> {
+    atomic_symlink_file_inner(&StdFs, dest, link)
+}
+
+fn atomic_symlink_file_inner(ops: &dyn Fs, dest: &Path, link: &Path) -> io::Result<()> {
     let parent = parent(link)?;
 
     debug!("Creating tempPath at '{}'", parent.display());
@@ -148,25 +473,117 @@ pub fn atomic_symlink_file(dest: &Path, link: &Path) -> io::Result<()> {
         dest.display()
     );
 
-    match symlink_file_inner(dest, &temp_path) {
-        Ok(_) => persist(temp_path, link),
-
-        #[cfg(windows)]
-        // Symlinks on Windows are disabled in some editions, so creating one is unreliable.
-        // Fallback to copy if it fails.
-        Err(_) => atomic_install(dest, link),
+    match ops.symlink_file(dest, &temp_path) {
+        Ok(_) => Ok(persist(temp_path, link)?),
+        Err(err) if should_copy_symlink(&err) => {
+            let tempfile = copy_to_tempfile(ops, &symlink_target(dest, link)?, link)?;
+            persist(tempfile.into_temp_path(), link)?;
+
+            degrade(
+                Degradation::SymlinkCopied {
+                    link: link.to_owned(),
+                },
+                &err,
+            );
 
-        #[cfg(not(windows))]
+            Ok(())
+        }
         Err(err) => Err(err),
     }
 }
 
-fn persist(temp_path: TempPath, to: &Path) -> io::Result<()> {
+fn should_copy_symlink(err: &io::Error) -> bool {
+    // Symlinks on Windows are disabled in some editions, so creating one is unreliable.
+    cfg!(windows) || is_unsupported(err)
+}
+
+/// Return the path of the file the symlink `link` pointing to `dest`
+/// would resolve to.
+fn symlink_target(dest: &Path, link: &Path) -> io::Result<PathBuf> {
+    Ok(parent(link)?.join(dest))
+}
+
+/// A copy of a file that is about to be replaced, kept next to it
+/// so that it can be restored if the surrounding operation fails.
+///
+/// A symlink is backed up as the path it points to, not as a copy of the
+/// file it resolves to.
+///
+/// The copy is removed on drop unless [`Backup::restore`] is called.
+#[derive(Debug)]
+pub struct Backup {
+    original: PathBuf,
+    backup: BackupKind,
+}
+
+#[derive(Debug)]
+enum BackupKind {
+    File(TempPath),
+    Symlink(PathBuf),
+}
+
+impl Backup {
+    /// Back up `dst` if it exists, even as a dangling symlink, return
+    /// `None` if there is nothing to back up.
+    ///
+    /// This is a blocking function, must be called in `block_in_place` mode.
+    pub fn create(dst: &Path) -> io::Result<Option<Self>> {
+        let metadata = match fs::symlink_metadata(dst) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        debug!("Backing up '{}' before replacing it", dst.display());
+
+        let backup = if metadata.is_symlink() {
+            BackupKind::Symlink(fs::read_link(dst)?)
+        } else {
+            BackupKind::File(copy_to_tempfile(&StdFs, dst, dst)?.into_temp_path())
+        };
+
+        Ok(Some(Self {
+            original: dst.to_owned(),
+            backup,
+        }))
+    }
+
+    /// Path of the file being backed up.
+    pub fn original(&self) -> &Path {
+        &self.original
+    }
+
+    /// Atomically move the backup back to its original location.
+    ///
+    /// This is a blocking function, must be called in `block_in_place` mode.
+    pub fn restore(self) -> io::Result<()> {
+        match self.backup {
+            BackupKind::File(backup) => {
+                debug!(
+                    "Restoring '{}' from backup '{}'",
+                    self.original.display(),
+                    backup.display()
+                );
+                Ok(persist(backup, &self.original)?)
+            }
+            BackupKind::Symlink(dest) => {
+                debug!(
+                    "Restoring symlink '{}' pointing to '{}'",
+                    self.original.display(),
+                    dest.display()
+                );
+                atomic_symlink_file(&dest, &self.original)
+            }
+        }
+    }
+}
+
+fn persist(temp_path: TempPath, to: &Path) -> Result<(), PathPersistError> {
     debug!("Persisting '{}' to '{}'", temp_path.display(), to.display());
     match temp_path.persist(to) {
         Ok(()) => Ok(()),
         #[cfg(windows)]
-        Err(tempfile::PathPersistError {
+        Err(PathPersistError {
             error,
             path: temp_path,
         }) => {
@@ -175,10 +592,13 @@ fn persist(temp_path: TempPath, to: &Path) -> io::Result<()> {
                 temp_path.display(),
                 to.display(),
             );
-            win::replace_file(&temp_path, to).map_err(io::Error::from)
+            win::replace_file(&temp_path, to).map_err(|err| PathPersistError {
+                error: err.into(),
+                path: temp_path,
+            })
         }
         #[cfg(not(windows))]
-        Err(err) => Err(err.into()),
+        Err(err) => Err(err),
     }
 }
 
@@ -188,9 +608,28 @@ mod win {
 
     use windows::{
         core::{Error, PCWSTR},
-        Win32::Storage::FileSystem::{ReplaceFileW, REPLACE_FILE_FLAGS},
+        Win32::Storage::FileSystem::{
+            MoveFileExW, ReplaceFileW, MOVEFILE_DELAY_UNTIL_REBOOT, REPLACE_FILE_FLAGS,
+        },
     };
 
+    /// Schedule the removal of `path` on the next reboot, which usually
+    /// requires administrator privileges.
+    pub(super) fn remove_on_reboot(path: &Path) -> Result<(), Error> {
+        let mut path: Vec<_> = path.as_os_str().encode_wide().collect();
+        path.push(0);
+
+        // SAFETY: We use it according its doc
+        // https://learn.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-movefileexw
+        unsafe {
+            MoveFileExW(
+                PCWSTR::from_raw(path.as_ptr()), // lpexistingfilename
+                PCWSTR::null(),                  // lpnewfilename, null to remove it
+                MOVEFILE_DELAY_UNTIL_REBOOT,     // dwflags
+            )
+        }
+    }
+
     pub(super) fn replace_file(src: &Path, dst: &Path) -> Result<(), Error> {
         let mut src: Vec<_> = src.as_os_str().encode_wide().collect();
         let mut dst: Vec<_> = dst.as_os_str().encode_wide().collect();
@@ -216,3 +655,352 @@ mod win {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The error of vfat on Linux for operations it does not support.
+    fn operation_not_permitted() -> io::Error {
+        #[cfg(unix)]
+        return io::Error::from_raw_os_error(EPERM);
+        #[cfg(not(unix))]
+        return io::ErrorKind::Unsupported.into();
+    }
+
+    /// Simulate a filesystem without support for rename across devices,
+    /// symlinks and permissions, e.g. vfat.
+    struct LimitedFs;
+
+    impl Fs for LimitedFs {
+        fn rename(&self, _src: &Path, _dst: &Path) -> io::Result<()> {
+            Err(io::Error::from_raw_os_error(EXDEV))
+        }
+
+        fn symlink_file(&self, _dest: &Path, _link: &Path) -> io::Result<()> {
+            Err(operation_not_permitted())
+        }
+
+        fn set_permissions(
+            &self,
+            _file: &fs::File,
+            _permissions: fs::Permissions,
+        ) -> io::Result<()> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn test_degraded_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        let link = dir.path().join("link");
+        fs::write(&src, "binary").unwrap();
+
+        take_degradations();
+
+        atomic_install_inner(&LimitedFs, &src, &dst).unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "binary");
+        assert!(!src.exists());
+
+        atomic_symlink_file_inner(&LimitedFs, Path::new("dst"), &link).unwrap();
+        assert!(!fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "binary");
+
+        fs::remove_file(&link).unwrap();
+        atomic_symlink_file_noclobber_inner(&LimitedFs, &dst, &link).unwrap();
+        assert_eq!(fs::read_to_string(&link).unwrap(), "binary");
+
+        assert_eq!(
+            take_degradations(),
+            [
+                Degradation::PermissionsSkipped { path: dst.clone() },
+                Degradation::RenameCopied {
+                    src,
+                    dst: dst.clone()
+                },
+                Degradation::PermissionsSkipped { path: link.clone() },
+                Degradation::SymlinkCopied { link: link.clone() },
+                Degradation::PermissionsSkipped { path: link.clone() },
+                Degradation::SymlinkCopied { link },
+            ]
+        );
+        assert_eq!(take_degradations(), []);
+    }
+
+    /// Simulate a file in use on Windows, which can be renamed but not
+    /// replaced.
+    struct InUseFs(PathBuf);
+
+    impl Fs for InUseFs {
+        fn rename(&self, src: &Path, dst: &Path) -> io::Result<()> {
+            if dst == self.0 && dst.exists() {
+                Err(io::ErrorKind::PermissionDenied.into())
+            } else {
+                fs::rename(src, dst)
+            }
+        }
+    }
+
+    #[test]
+    fn test_install_in_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        fs::write(&src, "new").unwrap();
+        fs::write(&dst, "old").unwrap();
+
+        take_degradations();
+
+        atomic_install_inner(&InUseFs(dst.clone()), &src, &dst).unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "new");
+        assert!(!src.exists());
+        // The file moved aside is removed once it is not in use.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(take_degradations(), []);
+
+        // Any other error is not handled as a file in use.
+        let err = replace_in_use(
+            &InUseFs(dst.clone()),
+            &dst,
+            io::ErrorKind::NotFound.into(),
+            || unreachable!(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_replace_in_use_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let dst = dir.path().join("dst");
+        fs::write(&dst, "old").unwrap();
+
+        let err = replace_in_use(
+            &InUseFs(dst.clone()),
+            &dst,
+            io::ErrorKind::PermissionDenied.into(),
+            || Err(io::Error::new(io::ErrorKind::Other, "still failing")),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "still failing");
+
+        // The original file is moved back.
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "old");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_remove_old_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let dst = dir.path().join("dst");
+        for name in [
+            "dst",
+            "dst.1a2b3c.__old__",
+            "dst.1a2b3c",
+            "other.1a2b3c.__old__",
+        ] {
+            fs::write(dir.path().join(name), "old").unwrap();
+        }
+
+        remove_old_files(&dst);
+
+        let mut names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["dst", "dst.1a2b3c", "other.1a2b3c.__old__"]);
+    }
+
+    /// Replace a copy of `cmd.exe` while it runs, which can be renamed but
+    /// not replaced.
+    #[cfg(windows)]
+    #[test]
+    fn test_install_running_exe() {
+        use std::process::{Command, Stdio};
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src.exe");
+        let dst = dir.path().join("dst.exe");
+        fs::write(&src, "new").unwrap();
+        fs::copy(env::var_os("ComSpec").unwrap(), &dst).unwrap();
+
+        let mut child = Command::new(&dst)
+            .args(["/C", "ping -n 30 127.0.0.1"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let res = atomic_install(&src, &dst);
+        child.kill().unwrap();
+        child.wait().unwrap();
+        res.unwrap();
+
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "new");
+        assert!(!src.exists());
+
+        // The running executable is left behind, and removed by the next
+        // install once it has exited.
+        let old_files = || {
+            fs::read_dir(dir.path())
+                .unwrap()
+                .filter(|entry| {
+                    let name = entry.as_ref().unwrap().file_name();
+                    name.to_string_lossy().ends_with(OLD_SUFFIX)
+                })
+                .count()
+        };
+        assert_eq!(old_files(), 1);
+
+        fs::write(&src, "newer").unwrap();
+        atomic_install(&src, &dst).unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "newer");
+        assert_eq!(old_files(), 0);
+    }
+
+    // Symlinks fall back to copy on any error on Windows.
+    #[cfg(not(windows))]
+    #[test]
+    fn test_unexpected_errors_are_not_degraded() {
+        struct FailingFs;
+
+        impl Fs for FailingFs {
+            fn symlink_file(&self, _dest: &Path, _link: &Path) -> io::Result<()> {
+                Err(io::ErrorKind::NotFound.into())
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("link");
+
+        take_degradations();
+
+        atomic_symlink_file_noclobber_inner(&FailingFs, Path::new("dst"), &link).unwrap_err();
+        assert_eq!(take_degradations(), []);
+    }
+
+    /// Only a rename across filesystems, or not supported, is replaced by a
+    /// copy, and only `EPERM` means that permissions are not supported.
+    #[cfg(unix)]
+    #[test]
+    fn test_access_errors_are_not_degraded() {
+        /// `EACCES`, e.g. for a directory the user may not write.
+        const EACCES: i32 = 13;
+
+        struct DeniedFs;
+
+        impl Fs for DeniedFs {
+            fn rename(&self, _src: &Path, _dst: &Path) -> io::Result<()> {
+                Err(io::Error::from_raw_os_error(EACCES))
+            }
+
+            fn set_permissions(
+                &self,
+                _file: &fs::File,
+                _permissions: fs::Permissions,
+            ) -> io::Result<()> {
+                Err(io::Error::from_raw_os_error(EACCES))
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dst = dir.path().join("dst");
+        fs::write(&src, "binary").unwrap();
+
+        take_degradations();
+
+        let err = atomic_install_inner(&DeniedFs, &src, &dst).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(EACCES));
+        let err = copy_to_tempfile(&DeniedFs, &src, &dst).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(EACCES));
+
+        assert_eq!(take_degradations(), []);
+        assert_eq!(fs::read_to_string(&src).unwrap(), "binary");
+        assert!(!dst.exists());
+    }
+
+    /// `/dev/shm` is usually a tmpfs, which is on a different filesystem
+    /// from the default temp dir.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_install_across_filesystems() {
+        use std::os::unix::fs::MetadataExt;
+
+        let Ok(src_dir) = tempfile::tempdir_in("/dev/shm") else {
+            return;
+        };
+        let dst_dir = tempfile::tempdir().unwrap();
+
+        let dev = |path: &Path| fs::metadata(path).unwrap().dev();
+        if dev(src_dir.path()) == dev(dst_dir.path()) {
+            return;
+        }
+
+        let src = src_dir.path().join("src");
+        let dst = dst_dir.path().join("dst");
+        fs::write(&src, "binary").unwrap();
+
+        take_degradations();
+
+        atomic_install(&src, &dst).unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "binary");
+        assert!(!src.exists());
+
+        assert_eq!(
+            take_degradations(),
+            [Degradation::RenameCopied { src, dst }]
+        );
+    }
+
+    #[test]
+    fn test_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let dst = dir.path().join("dst");
+        fs::write(&dst, "old").unwrap();
+
+        assert!(Backup::create(&dir.path().join("missing"))
+            .unwrap()
+            .is_none());
+
+        let backup = Backup::create(&dst).unwrap().unwrap();
+        fs::write(&dst, "new").unwrap();
+        backup.restore().unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "old");
+
+        // A backup which is not restored is removed.
+        drop(Backup::create(&dst).unwrap());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_backup_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real");
+        let link = dir.path().join("link");
+        let src = dir.path().join("src");
+        fs::write(&real, "real").unwrap();
+        fs::write(&src, "new").unwrap();
+        std::os::unix::fs::symlink("real", &link).unwrap();
+
+        let backup = Backup::create(&link).unwrap().unwrap();
+        atomic_install(&src, &link).unwrap();
+        assert!(!fs::symlink_metadata(&link).unwrap().is_symlink());
+
+        backup.restore().unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("real"));
+        assert_eq!(fs::read_to_string(&real).unwrap(), "real");
+
+        // A dangling symlink is backed up as well.
+        fs::remove_file(&real).unwrap();
+        let backup = Backup::create(&link).unwrap().unwrap();
+        fs::remove_file(&link).unwrap();
+        backup.restore().unwrap();
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("real"));
+    }
+}
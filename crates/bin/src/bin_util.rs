@@ -4,7 +4,7 @@ use std::{
 };
 
 use binstalk::errors::BinstallError;
-use binstalk::helpers::tasks::AutoAbortJoinHandle;
+use binstalk::helpers::{remote::CancellationToken, tasks::AutoAbortJoinHandle};
 use miette::Result;
 use tokio::runtime::Runtime;
 use tracing::{error, info};
@@ -49,16 +49,18 @@ impl MainExit {
 /// This function would start a tokio multithreading runtime,
 /// then `block_on` the task it returns.
 ///
-/// It will cancel the future if user requested cancellation
-/// via signal.
+/// It will cancel the token passed to `f` if user requested cancellation
+/// via signal, and return once the task is done.
 pub fn run_tokio_main(
-    f: impl FnOnce() -> Result<Option<AutoAbortJoinHandle<Result<()>>>>,
+    f: impl FnOnce(CancellationToken) -> Result<Option<AutoAbortJoinHandle<Result<()>>>>,
 ) -> Result<()> {
     let rt = Runtime::new().map_err(BinstallError::from)?;
     let _guard = rt.enter();
 
-    if let Some(handle) = f()? {
-        rt.block_on(cancel_on_user_sig_term(handle))?
+    let cancellation_token = CancellationToken::new();
+
+    if let Some(handle) = f(cancellation_token.clone())? {
+        rt.block_on(cancel_on_user_sig_term(handle, cancellation_token))?
     } else {
         Ok(())
     }
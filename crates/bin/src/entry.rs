@@ -1,48 +1,97 @@
 use std::{
+    borrow::Cow,
+    collections::BTreeMap,
     env, fs,
+    future::Future,
+    io::{self, IsTerminal},
+    iter,
+    num::NonZeroU64,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
+#[cfg(feature = "git")]
+use binstalk::helpers::git::GitReference;
 use binstalk::{
     errors::{BinstallError, CrateContextError},
-    fetchers::{Fetcher, GhCrateMeta, QuickInstall, SignaturePolicy},
+    fetchers::{
+        ArtifactCache, ChecksumPolicy, Fetcher, GhCrateMeta, QuickInstall, SignaturePolicy,
+        UrlRewrite, UrlRewrites,
+    },
     get_desired_targets,
     helpers::{
-        gh_api_client::GhApiClient,
+        gh_api_client::{GhApiClient, GhApiHost, GhAuthCheck, GhReleaseCache},
+        gitea_api_client::GiteaApiClient,
+        gl_api_client::{GlApiClient, GlAuthToken},
         jobserver_client::LazyJobserverClient,
-        remote::{Certificate, Client},
+        remote::{self, CancellationToken, Certificate, Client, SpkiPins},
         tasks::AutoAbortJoinHandle,
     },
     ops::{
         self,
-        resolve::{CrateName, Resolution, ResolutionFetch, VersionReqExt},
-        CargoTomlFetchOverride, Options, Resolver,
+        install_path::InstallPathTemplate,
+        outdated::OutdatedCrate,
+        progress::ProgressSink,
+        resolve::{
+            format_size, CrateName, CrateSpec, DeniedVersion, Denylist, Resolution,
+            ResolutionFetch, ResolutionSource, VersionReqExt,
+        },
+        run_report::{CrateReport, RunReport, RunReporter},
+        stats::RunStats,
+        usage_report::{HttpUsageReporter, UsageReporter},
+        CargoTomlFetchOverride, Options, Resolver, VersionMatchPolicy,
     },
+    registry::{CrateCache, Registry},
+    TARGET,
 };
 use binstalk_manifests::{
-    cargo_config::Config, cargo_toml_binstall::PkgOverride, crates_manifests::Manifests,
+    cargo_config::{Config, DenylistEntry},
+    cargo_toml_binstall::PkgOverride,
+    crate_info::{CrateInfo, LockedCrate, SourceType},
+    crates_manifests::Manifests,
+    lockfile::{Lockfile, LockfileError},
 };
+use clap::ValueEnum;
+use compact_str::{format_compact, CompactString};
 use file_format::FileFormat;
 use home::cargo_home;
 use log::LevelFilter;
-use miette::{miette, Report, Result, WrapErr};
-use tokio::task::block_in_place;
-use tracing::{debug, error, info, warn};
+use miette::{miette, IntoDiagnostic, Report, Result, WrapErr};
+use tokio::{
+    sync::Semaphore,
+    task::block_in_place,
+    time::{timeout_at, Instant},
+};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
 
 use crate::{
-    args::{Args, Strategy},
-    gh_token, git_credentials, install_path,
-    ui::confirm,
+    args::{Args, HostLimit, LogFormat, LogProgress, Strategy},
+    github_token, install_path,
+    progress::{BarProgress, SummaryProgress},
+    ui::{confirm, PendingCrate},
 };
 
 pub fn install_crates(
     args: Args,
     jobserver_client: LazyJobserverClient,
+    cancellation_token: CancellationToken,
 ) -> Result<Option<AutoAbortJoinHandle<Result<()>>>> {
+    // The deadline starts with the invocation.
+    let deadline = args.deadline.map(|deadline| {
+        let deadline = Duration::from_secs(deadline.get());
+        (deadline, Instant::now() + deadline)
+    });
+
     // Compute Resolvers
     let mut cargo_install_fallback = false;
 
+    let disabled_strategies = Strategy::value_variants()
+        .iter()
+        .filter(|strategy| !args.strategies.contains(strategy))
+        .map(|strategy| (*strategy).into())
+        .collect();
+
     let resolvers: Vec<_> = args
         .strategies
         .into_iter()
@@ -56,26 +105,193 @@ pub fn install_crates(
         })
         .collect();
 
-    // Load .cargo/config.toml
+    // Load .cargo/config.toml, the way cargo discovers it
     let cargo_home = cargo_home().map_err(BinstallError::from)?;
-    let mut config = Config::load_from_path(cargo_home.join("config.toml"))?;
+    let cwd = env::current_dir().map_err(BinstallError::from)?;
+    let mut config = Config::load_hierarchy(&cwd, &cargo_home)?;
+    config.apply_env(|name| env::var(name).ok())?;
+
+    let offline = args.offline;
+    let net_offline = config.net.as_ref().and_then(|net| net.offline) == Some(true);
+    if net_offline && !offline {
+        warn!(
+            "`net.offline` is set in cargo config, but binstall still needs network access \
+            to fetch prebuilt binaries and the registry index, only `.crate` files are taken \
+            from the cache, use `--offline` to only install from the caches"
+        );
+    }
+    // `.crate` files are cached alongside the artifacts saved by `--prefetch`.
+    let crate_cache =
+        CrateCache::new(cargo_home.join("binstall").join("cache")).offline(net_offline || offline);
+    // Packages downloaded by earlier runs.
+    let artifact_cache = (!args.no_cache).then(|| {
+        let dir = match dirs::cache_dir() {
+            Some(cache_dir) => cache_dir.join("cargo-binstall").join("artifacts"),
+            None => cargo_home.join("binstall").join("cache").join("artifacts"),
+        };
+        ArtifactCache::new(dir)
+    });
+    // Releases of earlier runs, so that CI jobs don't run into the rate limit.
+    let gh_release_cache_path = cargo_home
+        .join("binstall")
+        .join("cache")
+        .join("gh-releases.json");
+
+    let stats_path = cargo_home
+        .join("binstall")
+        .join("cache")
+        .join("last-run-stats.json");
+    let json_output = args.json_output;
+    let json_report = args.json_report;
+    if args.crate_names.is_empty()
+        && args.from_lockfile.is_none()
+        && !args.upgrade
+        && !args.outdated
+    {
+        // Only `--stats` is passed.
+        match load_last_run_stats(&stats_path)? {
+            Some(stats) => print_stats(&stats, json_output),
+            None if json_output => println!("{}", serde_json::json!({ "stats": null })),
+            None => info!("No run recorded yet"),
+        }
+        return Ok(None);
+    }
+    // `:bin` specs install the binary from the crate providing it, once found.
+    let (arg_crate_names, bin_specs) = CrateSpec::partition(args.crate_names);
+    let print_run_stats = args.stats
+        || args
+            .log_level
+            .is_some_and(|log_level| log_level >= LevelFilter::Debug);
+
+    // Skip the steps that only make sense when installing for the host,
+    // e.g. when provisioning an image for another architecture.
+    let foreign_target = is_foreign_target(args.targets.as_deref());
+    if foreign_target {
+        debug!("Installing for a foreign target, skipping host-only steps");
+    }
 
     // Compute paths
-    let cargo_root = args.root;
-    let (install_path, mut manifests, temp_dir) = compute_paths_and_load_manifests(
-        cargo_root.clone(),
-        args.install_path,
-        args.no_track,
-        cargo_home,
-        &mut config,
-    )?;
+    let cargo_root = args.root.map(install_path::expand_path).transpose()?;
+    let mut custom_install_path = args
+        .install_path
+        .map(install_path::expand_path)
+        .transpose()?;
+    let install_path_template = custom_install_path
+        .as_deref()
+        .map(InstallPathTemplate::new)
+        .transpose()?
+        .flatten();
+    if let Some(template) = &install_path_template {
+        custom_install_path = Some(template.base().to_owned());
+    }
+    let link_dir = args.link_dir.map(install_path::expand_path).transpose()?;
+    // The content-addressed cache `--prefetch` saves the artifacts into.
+    let prefetch = args
+        .prefetch
+        .then(|| cargo_home.join("binstall").join("cache"));
+    let download_only = args.download_only.then_some(args.output_dir);
+    let (install_path, mut manifests, temp_dir) = if let Some(cache_dir) = &prefetch {
+        compute_prefetch_paths(cache_dir)?
+    } else if let Some(output_dir) = &download_only {
+        compute_download_only_paths(output_dir)?
+    } else {
+        compute_paths_and_load_manifests(
+            cargo_root.clone(),
+            custom_install_path,
+            args.no_track,
+            foreign_target,
+            cargo_home,
+            &mut config,
+        )?
+    };
 
-    // Remove installed crates
-    let mut crate_names =
-        filter_out_installed_crates(args.crate_names, args.force, manifests.as_mut())?.peekable();
+    if args.uninstall {
+        uninstall_crates(
+            arg_crate_names,
+            manifests,
+            &install_path,
+            install_path_template.as_ref(),
+            args.dry_run,
+        )?;
+        return Ok(None);
+    }
 
-    if crate_names.peek().is_none() {
+    let tracked_bins = manifests
+        .as_mut()
+        .map(Manifests::load_tracked_bins)
+        .transpose()?;
+    let tracked_extra_files = manifests.as_ref().map(Manifests::load_tracked_extra_files);
+
+    let completion_shells = match args.install_completions {
+        Some(shells) if shells.is_empty() => ops::Shell::ALL.to_vec(),
+        Some(shells) => shells.into_iter().map(ops::Shell::from).collect(),
+        None => Vec::new(),
+    };
+
+    let extra_files_dir = if !completion_shells.is_empty() || args.install_man {
+        let dir = if foreign_target && args.extra_files_prefix.is_none() {
+            None
+        } else {
+            install_path::get_extra_files_dir(args.extra_files_prefix)
+        };
+        if dir.is_none() {
+            warn!("No viable directory to install completions and man pages, try `--extra-files-prefix`");
+        }
+        dir
+    } else {
+        None
+    };
+
+    let source_type = if args.manifest_path.is_some() {
+        SourceType::Path
+    } else {
+        #[cfg(feature = "git")]
+        if args.git.is_some() {
+            SourceType::Git
+        } else {
+            SourceType::Registry
+        }
+
+        #[cfg(not(feature = "git"))]
+        SourceType::Registry
+    };
+
+    // The entries of the lockfile by crate, the crates of which are installed
+    // instead of `args.crate_names`.
+    let mut locked_crates = args
+        .from_lockfile
+        .as_deref()
+        .map(load_locked_crates)
+        .transpose()?;
+
+    let upgrade = args.upgrade;
+    let outdated = args.outdated;
+    let (crate_names, up_to_date) = if upgrade || outdated {
+        (
+            installed_crates_to_upgrade(arg_crate_names, args.include_pinned, manifests.as_ref())?,
+            0,
+        )
+    } else {
+        // Remove installed crates
+        filter_out_installed_crates(
+            match &locked_crates {
+                Some(locked_crates) => locked_crate_names(locked_crates),
+                None => arg_crate_names,
+            },
+            args.force,
+            source_type,
+            &install_path,
+            install_path_template.as_ref(),
+            manifests.as_mut(),
+        )?
+    };
+
+    if crate_names.is_empty() && bin_specs.is_empty() {
         debug!("Nothing to do");
+        print_summary(up_to_date, 0);
+        if let Some(path) = &json_report {
+            write_report(&RunReport::default(), path)?;
+        }
         return Ok(None);
     }
 
@@ -84,10 +300,16 @@ pub fn install_crates(
 
     // Computer cli_overrides
     let cli_overrides = PkgOverride {
-        pkg_url: args.pkg_url,
+        pkg_url: args.pkg_url.into_iter().collect(),
+        pkg_tag: None,
         pkg_fmt: args.pkg_fmt,
         bin_dir: args.bin_dir,
+        binary_ext: None,
+        completions_dir: None,
+        man_dir: None,
         signing: None,
+        pkg_checksum_url: None,
+        pkg_checksum_algo: None,
     };
 
     // Initialize reqwest client
@@ -95,50 +317,294 @@ pub fn install_crates(
 
     let mut http = config.http.take();
 
+    let proxy = match args.proxy {
+        Some(proxy) => {
+            Some(parse_proxy(&proxy).map_err(|err| miette!("Invalid `--proxy` {proxy:?}: {err}"))?)
+        }
+        None => http
+            .as_mut()
+            .and_then(|http| http.proxy.take())
+            .map(|proxy| {
+                parse_proxy(&proxy)
+                    .map_err(|err| miette!("Invalid `http.proxy` {proxy:?} in cargo config: {err}"))
+            })
+            .transpose()?,
+    };
+
+    let mut binstall_config = config.binstall.take();
+
+    let user_agent = user_agent(
+        args.user_agent_suffix.as_deref().or(binstall_config
+            .as_ref()
+            .and_then(|binstall| binstall.user_agent_suffix.as_deref())),
+    )?;
+
+    let disable_telemetry = args.no_telemetry
+        || offline
+        || binstall_config
+            .as_ref()
+            .and_then(|binstall| binstall.disable_telemetry)
+            .unwrap_or(false);
+    let usage_report_endpoint = match args.usage_report_endpoint {
+        Some(url) => Some(url),
+        None => binstall_config
+            .as_mut()
+            .and_then(|binstall| binstall.usage_report_endpoint.take())
+            .map(|url| {
+                url.parse().map_err(|err| {
+                    miette!("Invalid `binstall.usage-report-endpoint` in cargo config: {err}")
+                })
+            })
+            .transpose()?,
+    };
+
+    let mut spki_pins = SpkiPins::default();
+    for (host, pins) in binstall_config
+        .as_mut()
+        .and_then(|binstall| binstall.spki_pins.take())
+        .into_iter()
+        .flatten()
+    {
+        for pin in pins {
+            spki_pins
+                .add(&host, &pin)
+                .map_err(|err| miette!("Invalid `binstall.spki-pins` in cargo config: {err}"))?;
+        }
+    }
+
+    let mut denylist = Denylist::default();
+    for (crate_name, entries) in binstall_config
+        .as_mut()
+        .and_then(|binstall| binstall.denylist.take())
+        .into_iter()
+        .flatten()
+    {
+        for entry in entries {
+            let (version, target, reason) = match entry {
+                DenylistEntry::Version(version) => (version, None, None),
+                DenylistEntry::WithOptions {
+                    version,
+                    target,
+                    reason,
+                } => (version, target, reason),
+            };
+            let version = version.parse().map_err(|err| {
+                miette!("Invalid version {version} of {crate_name} in `binstall.denylist` in cargo config: {err}")
+            })?;
+
+            denylist.add(
+                crate_name.clone(),
+                DeniedVersion {
+                    version,
+                    target,
+                    reason,
+                },
+            );
+        }
+    }
+
+    let mut url_rewrites = args.url_rewrite;
+    for (from, to) in binstall_config
+        .as_mut()
+        .and_then(|binstall| binstall.url_rewrite.take())
+        .into_iter()
+        .flatten()
+    {
+        url_rewrites.push(
+            UrlRewrite::new(&from, &to)
+                .map_err(|err| miette!("Invalid `binstall.url-rewrite` in cargo config: {err}"))?,
+        );
+    }
+    let url_rewrites = UrlRewrites::new(url_rewrites);
+
     let client = Client::new(
-        concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+        user_agent,
         args.min_tls_version.map(|v| v.into()),
         rate_limit.duration,
         rate_limit.request_count,
         read_root_certs(
             args.root_certificates,
             http.as_mut().and_then(|http| http.cainfo.take()),
-        ),
+        )?,
+        spki_pins,
+        proxy,
     )
-    .map_err(BinstallError::from)?;
-
-    let gh_api_client = GhApiClient::new(
-        client.clone(),
-        args.github_token.or_else(|| {
-            if args.no_discover_github_token {
-                None
-            } else {
-                git_credentials::try_from_home().or_else(|| match gh_token::get() {
-                    Ok(token) => Some(token),
-                    Err(err) => {
-                        warn!(?err, "Failed to retrieve token from `gh auth token`");
-                        warn!("Failed to read git credential file");
-                        None
-                    }
-                })
+    .map_err(BinstallError::from)?
+    .offline(offline)
+    .timeout(
+        args.timeout
+            .map(NonZeroU64::get)
+            .or(http.as_ref().and_then(|http| http.timeout))
+            .filter(|timeout| *timeout > 0)
+            .map(Duration::from_secs),
+    )
+    .retries(args.retries)
+    .space_expansion_factor(args.space_expansion_factor)
+    .cancellation_token(cancellation_token.clone())
+    .host_limits({
+        let mut host_limits = remote::HostLimits::default();
+        for HostLimit { host, limit } in args.host_limit {
+            match limit {
+                Some(limit) => host_limits.insert(host, limit),
+                None => host_limits.remove(&host),
             }
-        }),
+        }
+        host_limits
+    });
+
+    let client = match (args.record_fixture, args.replay_fixture) {
+        (Some(dir), _) => {
+            info!("Recording responses to fixture {}", dir.display());
+            client.with_fixture(
+                remote::Fixture::record(dir)
+                    .map_err(|err| miette!("Invalid `--record-fixture`: {err}"))?,
+            )
+        }
+        (None, Some(dir)) => {
+            info!("Replaying responses from fixture {}", dir.display());
+            client.with_fixture(
+                remote::Fixture::replay(dir)
+                    .map_err(|err| miette!("Invalid `--replay-fixture`: {err}"))?,
+            )
+        }
+        (None, None) => client,
+    };
+
+    let usage_reporter = usage_report_endpoint
+        .filter(|_| !disable_telemetry)
+        .map(|endpoint| {
+            debug!("Reporting installed crates to {endpoint}");
+            Arc::new(HttpUsageReporter::new(client.clone(), endpoint))
+        });
+
+    let gh_api_host = args
+        .github_api_url
+        .or_else(GhApiHost::from_env)
+        .unwrap_or_default();
+    if !gh_api_host.is_github_com() {
+        info!("Using the GitHub API of {gh_api_host}");
+    }
+
+    let github_tokens = if !args.github_token.is_empty() || args.no_discover_github_token {
+        args.github_token
+    } else {
+        github_token::discover(gh_api_host.host())
+            .into_iter()
+            .collect()
+    };
+
+    let has_github_token = !github_tokens.is_empty();
+    let gh_api_client =
+        GhApiClient::with_request_budget(client.clone(), github_tokens, args.github_api_budget)
+            .with_host(gh_api_host)
+            .with_release_cache(GhReleaseCache::load(gh_release_cache_path));
+
+    // Tell early if the token is rejected, while the crates are resolved.
+    if has_github_token && !offline {
+        let gh_api_client = gh_api_client.clone();
+        tokio::spawn(async move { report_github_auth(&gh_api_client).await });
+    }
+
+    let gitlab_token = args
+        .gitlab_token
+        .map(GlAuthToken::Private)
+        .or_else(GlAuthToken::from_env);
+    let mut gl_api_client = GlApiClient::new(client.clone(), gitlab_token);
+    if let Some(gitlab_host) = args.gitlab_host {
+        gl_api_client = gl_api_client.with_host(&gitlab_host);
+        info!("Using the GitLab API of {}", gl_api_client.host());
+    }
+
+    let mut gitea_api_client = GiteaApiClient::new(client.clone(), args.gitea_token);
+    if !args.gitea_host.is_empty() {
+        gitea_api_client = gitea_api_client.with_hosts(&args.gitea_host);
+    }
+
+    let quiet = args.log_level == Some(LevelFilter::Off);
+    let log_progress = resolve_log_progress(
+        args.log_progress,
+        args.log_format,
+        io::stdout().is_terminal(),
+        io::stderr().is_terminal(),
     );
 
     // Create binstall_opts
+    let registry: Registry = if let Some(index) = args.index {
+        index
+    } else if let Some(registry_name) = args
+        .registry
+        .or_else(|| config.registry.and_then(|registry| registry.default))
+    {
+        let registry_name_lowercase = registry_name.to_lowercase();
+        let registry_env = |suffix: &str| {
+            env::vars().find_map(|(k, v)| {
+                let name_lowercase = k
+                    .strip_prefix("CARGO_REGISTRIES_")?
+                    .strip_suffix(suffix)?
+                    .to_lowercase();
+
+                (name_lowercase == registry_name_lowercase).then_some(v)
+            })
+        };
+        let registry_config = config
+            .registries
+            .as_ref()
+            .and_then(|registries| registries.get(&registry_name));
+
+        let v = registry_env("_INDEX");
+        let registry: Registry = if let Some(v) = &v {
+            v
+        } else {
+            registry_config
+                .and_then(|registry| registry.index.as_deref())
+                .ok_or_else(|| BinstallError::UnknownRegistryName(registry_name.clone()))?
+        }
+        .parse()
+        .map_err(BinstallError::from)?;
+
+        let token = registry_env("_TOKEN")
+            .map(CompactString::from)
+            .or_else(|| registry_config.and_then(|registry| registry.token.clone()));
+        match token {
+            Some(token) => registry.with_token(token),
+            None => registry,
+        }
+    } else {
+        Default::default()
+    };
+    let registry = registry.with_crate_cache(crate_cache);
+
+    #[cfg(feature = "git")]
+    let git_reference = args
+        .branch
+        .map(GitReference::Branch)
+        .or(args.tag.map(GitReference::Tag))
+        .or(args.rev.map(GitReference::Rev));
+
     let binstall_opts = Arc::new(Options {
         no_symlinks: args.no_symlinks,
         dry_run: args.dry_run,
         force: args.force,
-        quiet: args.log_level == Some(LevelFilter::Off),
+        quiet,
         locked: args.locked,
         no_track: args.no_track,
 
+        tracked_bins,
+        // The package manager of the host does not own files of foreign targets.
+        query_package_owner: args.query_package_owner && !foreign_target,
+
+        completion_shells,
+        install_man: args.install_man,
+        extra_files_dir,
+        tracked_extra_files,
+
         version_req: args.version_req,
+        strict: args.strict,
+        denylist,
         #[cfg(feature = "git")]
         cargo_toml_fetch_override: match (args.manifest_path, args.git) {
             (Some(manifest_path), None) => Some(CargoTomlFetchOverride::Path(manifest_path)),
-            (None, Some(git_url)) => Some(CargoTomlFetchOverride::Git(git_url)),
+            (None, Some(git_url)) => Some(CargoTomlFetchOverride::Git(git_url, git_reference)),
             (None, None) => None,
             _ => unreachable!("manifest_path and git cannot be specified at the same time"),
         },
@@ -150,46 +616,28 @@ pub fn install_crates(
         desired_targets,
         resolvers,
         cargo_install_fallback,
+        disabled_strategies,
 
         temp_dir: temp_dir.path().to_owned(),
         install_path,
+        install_path_template,
+        link_dir,
+        // The artifacts are staged in the temporary directory, and only
+        // moved into the cache once complete.
+        download_only: match &prefetch {
+            Some(_) => Some(temp_dir.path().to_owned()),
+            None => download_only,
+        },
         cargo_root,
+        artifact_cache,
 
         client,
         gh_api_client,
+        gl_api_client,
+        gitea_api_client,
         jobserver_client,
-        registry: if let Some(index) = args.index {
-            index
-        } else if let Some(registry_name) = args
-            .registry
-            .or_else(|| config.registry.and_then(|registry| registry.default))
-        {
-            let registry_name_lowercase = registry_name.to_lowercase();
-
-            let v = env::vars().find_map(|(k, v)| {
-                let name_lowercase = k
-                    .strip_prefix("CARGO_REGISTRIES_")?
-                    .strip_suffix("_INDEX")?
-                    .to_lowercase();
-
-                (name_lowercase == registry_name_lowercase).then_some(v)
-            });
-
-            if let Some(v) = &v {
-                v
-            } else {
-                config
-                    .registries
-                    .as_ref()
-                    .and_then(|registries| registries.get(&registry_name))
-                    .and_then(|registry| registry.index.as_deref())
-                    .ok_or_else(|| BinstallError::UnknownRegistryName(registry_name))?
-            }
-            .parse()
-            .map_err(BinstallError::from)?
-        } else {
-            Default::default()
-        },
+        cancellation_token,
+        registry,
 
         signature_policy: if args.only_signed {
             SignaturePolicy::Require
@@ -198,42 +646,199 @@ pub fn install_crates(
         } else {
             SignaturePolicy::IfPresent
         },
+        checksum_policy: if args.require_checksum {
+            ChecksumPolicy::Require
+        } else {
+            ChecksumPolicy::IfPresent
+        },
+        version_match_policy: if args.strict_version_match {
+            VersionMatchPolicy::Strict
+        } else if args.check_version_match {
+            VersionMatchPolicy::Warn
+        } else {
+            VersionMatchPolicy::Ignore
+        },
+        allow_insecure_http: args.allow_insecure_http,
+        url_rewrites,
+
+        progress_sink: match log_progress {
+            _ if quiet => None,
+            LogProgress::Bar => Some(Arc::new(BarProgress::stdout()) as Arc<dyn ProgressSink>),
+            LogProgress::Summary => Some(Arc::new(SummaryProgress::stdout()) as _),
+            LogProgress::Auto | LogProgress::Off => None,
+        },
+        stats: Default::default(),
+        run_reporter: json_report.is_some().then(RunReporter::default),
+
+        usage_reporter: usage_reporter
+            .clone()
+            .map(|usage_reporter| usage_reporter as Arc<dyn UsageReporter>),
+        disable_telemetry,
     });
 
     // Destruct args before any async function to reduce size of the future
     let dry_run = args.dry_run;
     let no_confirm = args.no_confirm;
+    let confirm_third_party_only = args.confirm_third_party_only;
     let no_cleanup = args.no_cleanup;
+    let write_lockfile = args.write_lockfile;
+    let concurrent = crate_names.len() + bin_specs.len() > 1;
+
+    let jobs = Arc::new(Semaphore::new(args.jobs.get()));
+
+    if outdated {
+        return Ok(Some(check_outdated(
+            binstall_opts,
+            crate_names,
+            jobs,
+            json_output,
+        )));
+    }
+
+    // The crates providing the binaries are only known once found, so the
+    // installed versions are looked up then.
+    let installed_crates = match manifests.as_mut() {
+        Some(manifests) if !bin_specs.is_empty() && !args.force => {
+            Some(manifests.load_installed_crates()?)
+        }
+        _ => None,
+    };
+    let bin_tasks: Vec<_> = bin_specs
+        .into_iter()
+        .map(|bin_spec| {
+            let opts = binstall_opts.clone();
+            let jobs = jobs.clone();
+            let name = format_compact!(":{}", bin_spec.bin);
+            let span = crate_span(&name, concurrent);
+            let prefetch = prefetch.clone();
+            let installed_crates = installed_crates.clone();
+            let context = name.clone();
+            let resolve = async move {
+                let _permit = jobs.acquire().await;
+
+                let crate_name = ops::resolve::find_crate_providing_bin(&opts, &bin_spec.bin)
+                    .await
+                    .map_err(|err| err.crate_context(context))?;
+                info!("Installing binary {} from crate {crate_name}", bin_spec.bin);
+                let current_version = installed_crates
+                    .and_then(|mut installed_crates| installed_crates.remove(&crate_name));
+                let crate_name = bin_spec.with_crate(crate_name);
+
+                if let Some(cache_dir) = &prefetch {
+                    return ops::prefetch::prefetch(opts, crate_name, cache_dir)
+                        .await
+                        .map(Resolution::Downloaded);
+                }
+
+                ops::resolve::resolve(opts, crate_name, current_version).await
+            };
+            AutoAbortJoinHandle::spawn(with_deadline(deadline, name, resolve).instrument(span))
+        })
+        .collect();
 
     // Resolve crates
     let tasks: Vec<_> = crate_names
-        .map(|(crate_name, current_version)| {
-            AutoAbortJoinHandle::spawn(ops::resolve::resolve(
-                binstall_opts.clone(),
-                crate_name,
-                current_version,
-            ))
+        .into_iter()
+        .map(|(crate_name, current_version, crate_info)| {
+            let opts = binstall_opts.clone();
+            let jobs = jobs.clone();
+            let span = crate_span(&crate_name.name, concurrent);
+            let name = crate_name.name.clone();
+            let prefetch = prefetch.clone();
+            let locked = locked_crates
+                .as_mut()
+                .and_then(|locked_crates| locked_crates.remove(&crate_name.name));
+            let resolve = async move {
+                let _permit = jobs.acquire().await;
+
+                if let Some(entries) = locked {
+                    return ops::resolve::resolve_locked(
+                        opts,
+                        crate_name.name,
+                        entries,
+                        current_version,
+                    )
+                    .await;
+                }
+
+                if let Some(cache_dir) = &prefetch {
+                    return ops::prefetch::prefetch(opts, crate_name, cache_dir)
+                        .await
+                        .map(Resolution::Downloaded);
+                }
+
+                // Only resolve the crates which are behind.
+                if let (true, Some(crate_info)) = (upgrade, &crate_info) {
+                    match ops::outdated::newer_version(&opts, crate_info).await? {
+                        Some(latest) => info!(
+                            "{} v{} can be upgraded to v{latest}",
+                            crate_info.name, crate_info.current_version
+                        ),
+                        None => {
+                            debug!(
+                                "{} v{} is the latest version",
+                                crate_info.name, crate_info.current_version
+                            );
+                            return Ok(Resolution::AlreadyUpToDate);
+                        }
+                    }
+                }
+
+                let fetch_source = crate_info
+                    .as_ref()
+                    .and_then(|crate_info| crate_info.fetch_source.clone());
+                match ops::resolve::resolve_with_fetch_source(
+                    opts.clone(),
+                    crate_name,
+                    current_version,
+                    fetch_source,
+                )
+                .await?
+                {
+                    // No newer version, but the installed binaries might be
+                    // missing or corrupt.
+                    Resolution::AlreadyUpToDate => match crate_info {
+                        // Errors are already in the context of the crate.
+                        Some(crate_info) => ops::repair::repair(opts, crate_info).await,
+                        None => Ok(Resolution::AlreadyUpToDate),
+                    },
+                    resolution => Ok(resolution),
+                }
+            };
+            AutoAbortJoinHandle::spawn(with_deadline(deadline, name, resolve).instrument(span))
         })
+        .chain(bin_tasks)
         .collect();
 
-    Ok(Some(if args.continue_on_failure {
+    let stats_opts = binstall_opts.clone();
+
+    let handle = if !args.fail_fast {
         AutoAbortJoinHandle::spawn(async move {
             // Collect results
             let mut resolution_fetchs = Vec::new();
             let mut resolution_sources = Vec::new();
             let mut errors = Vec::new();
 
+            let mut up_to_date = up_to_date;
+
             for task in tasks {
                 match task.flattened_join().await {
-                    Ok(Resolution::AlreadyUpToDate) => {}
+                    Ok(Resolution::AlreadyUpToDate) => up_to_date += 1,
                     Ok(Resolution::Fetch(fetch)) => {
                         fetch.print(&binstall_opts);
+                        report_crate(&binstall_opts, || {
+                            CrateReport::from_fetch(&fetch, &binstall_opts)
+                        });
                         resolution_fetchs.push(fetch)
                     }
                     Ok(Resolution::InstallFromSource(source)) => {
                         source.print();
+                        report_crate(&binstall_opts, || {
+                            CrateReport::from_source(&source, &binstall_opts)
+                        });
                         resolution_sources.push(source)
                     }
+                    Ok(Resolution::Downloaded(downloaded)) => downloaded.print(),
                     Err(BinstallError::CrateContext(err)) => errors.push(err),
                     Err(e) => panic!("Expected BinstallError::CrateContext(_), got {}", e),
                 }
@@ -244,13 +849,23 @@ pub fn install_crates(
                     Err(err.into())
                 } else {
                     debug!("Nothing to do");
+                    print_summary(up_to_date, 0);
                     Ok(())
                 };
             }
 
+            print_download_size(&resolution_fetchs);
+
             // Confirm
             if !dry_run && !no_confirm {
-                if let Err(abort_err) = confirm().await {
+                if let Err(abort_err) = confirm_crates(
+                    &binstall_opts.cancellation_token,
+                    &mut resolution_fetchs,
+                    &mut resolution_sources,
+                    confirm_third_party_only,
+                )
+                .await
+                {
                     return if let Some(err) = BinstallError::crate_errors(errors) {
                         Err(Report::new(abort_err).wrap_err(err))
                     } else {
@@ -259,6 +874,12 @@ pub fn install_crates(
                 }
             }
 
+            if let Some(path) = &write_lockfile {
+                block_in_place(|| {
+                    write_lockfile_to(path, &resolution_fetchs, &resolution_sources)
+                })?;
+            }
+
             let manifest_update_res = do_install_fetches_continue_on_failure(
                 resolution_fetchs,
                 manifests,
@@ -271,7 +892,12 @@ pub fn install_crates(
 
             let tasks: Vec<_> = resolution_sources
                 .into_iter()
-                .map(|source| AutoAbortJoinHandle::spawn(source.install(binstall_opts.clone())))
+                .map(|source| {
+                    let span = crate_span(&source.name, concurrent);
+                    AutoAbortJoinHandle::spawn(
+                        source.install(binstall_opts.clone()).instrument(span),
+                    )
+                })
                 .collect();
 
             for task in tasks {
@@ -282,10 +908,14 @@ pub fn install_crates(
                 }
             }
 
+            if let Ok(repaired) = manifest_update_res {
+                print_summary(up_to_date, repaired);
+            }
+
             match (BinstallError::crate_errors(errors), manifest_update_res) {
-                (None, Ok(())) => Ok(()),
+                (None, Ok(_)) => Ok(()),
                 (None, Err(err)) => Err(err),
-                (Some(err), Ok(())) => Err(err.into()),
+                (Some(err), Ok(_)) => Err(err.into()),
                 (Some(err), Err(manifest_update_err)) => {
                     Err(Report::new(err).wrap_err(manifest_update_err))
                 }
@@ -297,31 +927,55 @@ pub fn install_crates(
             let mut resolution_fetchs = Vec::new();
             let mut resolution_sources = Vec::new();
 
+            let mut up_to_date = up_to_date;
+
             for task in tasks {
                 match task.await?? {
-                    Resolution::AlreadyUpToDate => {}
+                    Resolution::AlreadyUpToDate => up_to_date += 1,
                     Resolution::Fetch(fetch) => {
                         fetch.print(&binstall_opts);
+                        report_crate(&binstall_opts, || {
+                            CrateReport::from_fetch(&fetch, &binstall_opts)
+                        });
                         resolution_fetchs.push(fetch)
                     }
                     Resolution::InstallFromSource(source) => {
                         source.print();
+                        report_crate(&binstall_opts, || {
+                            CrateReport::from_source(&source, &binstall_opts)
+                        });
                         resolution_sources.push(source)
                     }
+                    Resolution::Downloaded(downloaded) => downloaded.print(),
                 }
             }
 
             if resolution_fetchs.is_empty() && resolution_sources.is_empty() {
                 debug!("Nothing to do");
+                print_summary(up_to_date, 0);
                 return Ok(());
             }
 
+            print_download_size(&resolution_fetchs);
+
             // Confirm
             if !dry_run && !no_confirm {
-                confirm().await?;
+                confirm_crates(
+                    &binstall_opts.cancellation_token,
+                    &mut resolution_fetchs,
+                    &mut resolution_sources,
+                    confirm_third_party_only,
+                )
+                .await?;
+            }
+
+            if let Some(path) = &write_lockfile {
+                block_in_place(|| {
+                    write_lockfile_to(path, &resolution_fetchs, &resolution_sources)
+                })?;
             }
 
-            do_install_fetches(
+            let repaired = do_install_fetches(
                 resolution_fetchs,
                 manifests,
                 &binstall_opts,
@@ -332,70 +986,158 @@ pub fn install_crates(
 
             let tasks: Vec<_> = resolution_sources
                 .into_iter()
-                .map(|source| AutoAbortJoinHandle::spawn(source.install(binstall_opts.clone())))
+                .map(|source| {
+                    let span = crate_span(&source.name, concurrent);
+                    AutoAbortJoinHandle::spawn(
+                        source.install(binstall_opts.clone()).instrument(span),
+                    )
+                })
                 .collect();
 
             for task in tasks {
                 task.await??;
             }
 
+            print_summary(up_to_date, repaired);
+
             Ok(())
         })
-    }))
-}
+    };
+
+    Ok(Some(AutoAbortJoinHandle::spawn(async move {
+        let res = handle.await?;
 
-fn do_read_root_cert(path: &Path) -> Result<Option<Certificate>, BinstallError> {
-    use std::io::{Read, Seek};
+        // Only report the crates installed once their receipts are written.
+        if let Some(usage_reporter) = &usage_reporter {
+            usage_reporter.flush(USAGE_REPORT_TIMEOUT).await;
+        }
 
-    let mut file = fs::File::open(path)?;
-    let file_format = FileFormat::from_reader(&mut file)?;
+        let gh_api_client = &stats_opts.gh_api_client;
+        if gh_api_client.requests_used() > 0 || gh_api_client.requests_remaining().is_some() {
+            info!("{}", gh_api_client.budget_usage());
+        }
 
-    let open_cert = match file_format {
-        FileFormat::PemCertificate => Certificate::from_pem,
-        FileFormat::DerCertificate => Certificate::from_der,
-        _ => {
+        let malformed_responses = gh_api_client.malformed_responses();
+        if malformed_responses > 0 {
             warn!(
-                "Unable to load {}: Expected pem or der ceritificate but found {file_format}",
-                path.display()
+                "Received {malformed_responses} malformed GitHub API response{}, \
+the GitHub API was not used after the first one",
+                if malformed_responses == 1 { "" } else { "s" }
             );
+        }
 
-            return Ok(None);
+        // It may wait for the lock of the file held by another process.
+        if let Err(err) = block_in_place(|| gh_api_client.flush_release_cache()) {
+            warn!("Failed to save the GitHub release cache: {err}");
         }
-    };
 
-    // Move file back to its head
-    file.rewind()?;
+        let stats = RunStats::collect(&stats_opts);
+        if let Err(err) = stats.save(&stats_path) {
+            warn!(
+                "Failed to save the statistics of this run to '{}': {err}",
+                stats_path.display()
+            );
+        }
+        if print_run_stats {
+            print_stats(&stats, json_output);
+        }
+
+        if let (Some(path), Some(run_reporter)) = (&json_report, &stats_opts.run_reporter) {
+            if let Err(err) = &res {
+                match err.downcast_ref::<BinstallError>() {
+                    Some(err) => run_reporter.add_error(err),
+                    None => run_reporter.add_other_error(err.to_string()),
+                }
+            }
+            let written = write_report(&run_reporter.report(), path);
+            return res.and(written);
+        }
+
+        res
+    })))
+}
+
+fn do_read_root_cert(path: &Path) -> Result<Vec<Certificate>> {
+    let buffer = fs::read(path).map_err(BinstallError::from)?;
+
+    match FileFormat::from_bytes(&buffer) {
+        FileFormat::PemCertificate => {
+            Ok(Certificate::from_pem_bundle(&buffer).map_err(BinstallError::from)?)
+        }
+        FileFormat::DerCertificate => Ok(vec![
+            Certificate::from_der(&buffer).map_err(BinstallError::from)?
+        ]),
+        file_format => Err(miette!(
+            "Expected pem or der certificate but found {file_format}"
+        )),
+    }
+}
+
+/// Fail `task` with [`BinstallError::DeadlineExceeded`] if it is not done
+/// by the deadline, dropping it cancels the downloads in flight.
+async fn with_deadline<T>(
+    deadline: Option<(Duration, Instant)>,
+    crate_name: CompactString,
+    task: impl Future<Output = Result<T, BinstallError>>,
+) -> Result<T, BinstallError> {
+    match deadline {
+        Some((deadline, instant)) => timeout_at(instant, task).await.unwrap_or_else(|_| {
+            Err(BinstallError::DeadlineExceeded(deadline).crate_context(crate_name))
+        }),
+        None => task.await,
+    }
+}
 
-    let mut buffer = Vec::with_capacity(200);
-    file.read_to_end(&mut buffer)?;
+/// Parse a proxy in libcurl format, like `http.proxy` of cargo config, i.e.
+/// `[scheme://]host:port` with `http` as the default scheme.
+fn parse_proxy(proxy: &str) -> Result<remote::Url, remote::UrlParseError> {
+    let url = if proxy.contains("://") {
+        Cow::Borrowed(proxy)
+    } else {
+        Cow::Owned(format!("http://{proxy}"))
+    };
 
-    open_cert(&buffer).map_err(From::from).map(Some)
+    remote::Url::parse(&url)
 }
 
+/// Load the certificates of `root_certificate_paths` and `config_cainfo`,
+/// failing on the first one that cannot be loaded.
 fn read_root_certs(
     root_certificate_paths: Vec<PathBuf>,
     config_cainfo: Option<PathBuf>,
-) -> impl Iterator<Item = Certificate> {
-    root_certificate_paths
-        .into_iter()
-        .chain(config_cainfo)
-        .filter_map(|path| match do_read_root_cert(&path) {
-            Ok(optional_cert) => optional_cert,
-            Err(err) => {
-                warn!(
-                    "Failed to load root certificate at {}: {err}",
-                    path.display()
-                );
-                None
-            }
-        })
+) -> Result<Vec<Certificate>> {
+    let mut certificates = Vec::new();
+    for path in root_certificate_paths.into_iter().chain(config_cainfo) {
+        let certs = do_read_root_cert(&path)
+            .wrap_err_with(|| format!("Failed to load root certificate at {}", path.display()))?;
+        certificates.extend(certs);
+    }
+
+    Ok(certificates)
+}
+
+/// Return true if `targets` are specified and none of them is the host target.
+fn is_foreign_target(targets: Option<&[String]>) -> bool {
+    targets.is_some_and(|targets| !targets.is_empty() && !targets.iter().any(|t| t == TARGET))
 }
 
+/// Directory under a custom install path to keep the manifests of the
+/// crates installed for a foreign target in.
+const FOREIGN_MANIFESTS_DIR: &str = ".binstall";
+
+/// How long to wait for the usage reports at exit.
+const USAGE_REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Return (install_path, manifests, temp_dir)
+///
+/// Manifests are only loaded for the cargo roots, or for a custom install
+/// path with a foreign target, in which case they are stored under the
+/// install path itself so that it is self-contained.
 fn compute_paths_and_load_manifests(
     roots: Option<PathBuf>,
     install_path: Option<PathBuf>,
     no_track: bool,
+    foreign_target: bool,
     cargo_home: PathBuf,
     config: &mut Config,
 ) -> Result<(PathBuf, Option<Manifests>, tempfile::TempDir)> {
@@ -416,11 +1158,15 @@ fn compute_paths_and_load_manifests(
     fs::create_dir_all(&install_path).map_err(BinstallError::Io)?;
     debug!("Using install path: {}", install_path.display());
 
-    let no_manifests = no_track || custom_install_path;
-
     // Load manifests
-    let manifests = if !no_manifests {
+    let manifests = if no_track {
+        None
+    } else if !custom_install_path {
         Some(Manifests::open_exclusive(&cargo_roots)?)
+    } else if foreign_target {
+        Some(Manifests::open_exclusive(
+            &install_path.join(FOREIGN_MANIFESTS_DIR),
+        )?)
     } else {
         None
     };
@@ -439,18 +1185,92 @@ fn compute_paths_and_load_manifests(
     Ok((install_path, manifests, temp_dir))
 }
 
-/// Return vec of (crate_name, current_version)
+/// Return the User-Agent of the requests, with `suffix` appended.
+fn user_agent(suffix: Option<&str>) -> Result<String> {
+    let mut user_agent =
+        concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")).to_string();
+
+    if let Some(suffix) = suffix.map(str::trim).filter(|suffix| !suffix.is_empty()) {
+        if !suffix.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+            return Err(miette!(
+                "Invalid user agent suffix {suffix:?}: only printable ASCII characters are allowed"
+            ));
+        }
+
+        user_agent.push(' ');
+        user_agent.push_str(suffix);
+    }
+
+    Ok(user_agent)
+}
+
+/// Return (install_path, manifests, temp_dir) for `--download-only`, which
+/// never touches the install root.
+///
+/// The temporary directory is a hidden sibling of `output_dir`, so that
+/// only the artifacts are ever saved into it.
+fn compute_download_only_paths(
+    output_dir: &Path,
+) -> Result<(PathBuf, Option<Manifests>, tempfile::TempDir)> {
+    fs::create_dir_all(output_dir).map_err(BinstallError::Io)?;
+    debug!("Saving artifacts to: {}", output_dir.display());
+
+    let temp_dir = sibling_temp_dir(output_dir, ".cargo-binstall-download")?;
+
+    Ok((output_dir.to_owned(), None, temp_dir))
+}
+
+/// Return (install_path, manifests, temp_dir) for `--prefetch`, with the
+/// temporary directory next to `cache_dir`, so that the artifacts staged in
+/// it are renamed into the cache atomically.
+fn compute_prefetch_paths(
+    cache_dir: &Path,
+) -> Result<(PathBuf, Option<Manifests>, tempfile::TempDir)> {
+    fs::create_dir_all(cache_dir).map_err(BinstallError::Io)?;
+    debug!("Saving artifacts to the cache at: {}", cache_dir.display());
+
+    let temp_dir = sibling_temp_dir(cache_dir, ".cargo-binstall-prefetch")?;
+
+    Ok((cache_dir.to_owned(), None, temp_dir))
+}
+
+/// Create a temporary directory in the parent of the existing `dir`, or in
+/// `dir` itself if it is a root.
+fn sibling_temp_dir(dir: &Path, prefix: &str) -> Result<tempfile::TempDir> {
+    // `dir` may be relative, e.g. `.` has no parent.
+    let dir = fs::canonicalize(dir).map_err(BinstallError::Io)?;
+
+    tempfile::Builder::new()
+        .prefix(prefix)
+        .tempdir_in(dir.parent().unwrap_or(&dir))
+        .map_err(BinstallError::from)
+        .wrap_err("Creating a temporary directory failed.")
+}
+
+/// Return vec of (crate_name, current_version, crate_info), and the number
+/// of crates already installed and intact.
+///
+/// `crate_info` is the record of the installed crate, to repair its
+/// binaries if there is no newer version.
 fn filter_out_installed_crates(
     crate_names: Vec<CrateName>,
     force: bool,
+    source_type: SourceType,
+    install_path: &Path,
+    install_path_template: Option<&InstallPathTemplate>,
     manifests: Option<&mut Manifests>,
-) -> Result<impl Iterator<Item = (CrateName, Option<semver::Version>)> + '_> {
+) -> Result<(CratesToResolve, usize)> {
+    let mut crate_infos = manifests
+        .as_ref()
+        .map(|manifests| manifests.load_crate_infos());
     let mut installed_crates = manifests
         .map(Manifests::load_installed_crates)
         .transpose()?;
 
-    Ok(CrateName::dedup(crate_names)
-    .filter_map(move |crate_name| {
+    let mut up_to_date = 0;
+
+    let crate_names = CrateName::dedup(crate_names)
+    .filter_map(|crate_name| {
         let name = &crate_name.name;
 
         let curr_version = installed_crates
@@ -460,6 +1280,9 @@ fn filter_out_installed_crates(
             //
             // So here we take ownership of the version stored to avoid cloning.
             .and_then(|crates| crates.remove(name));
+        let crate_info = crate_infos
+            .as_mut()
+            .and_then(|crate_infos| crate_infos.remove(name));
 
         match (
             force,
@@ -469,48 +1292,576 @@ fn filter_out_installed_crates(
             (false, Some(curr_version), Some(version_req))
                 if version_req.is_latest_compatible(&curr_version) =>
             {
-                debug!("Bailing out early because we can assume wanted is already installed from metafile");
-                info!("{name} v{curr_version} is already installed, use --force to override");
-                None
+                match crate_info {
+                    Some(crate_info) if crate_info.source.source_type != source_type => {
+                        info!(
+                            "{name} v{curr_version} is installed from {:?} source, reinstalling it from {source_type:?} source",
+                            crate_info.source.source_type,
+                        );
+                        Some((crate_name, None, Some(crate_info)))
+                    }
+                    Some(crate_info)
+                        if !ops::repair::broken_bins(
+                            &crate_info,
+                            &install_path_template.map_or(Cow::Borrowed(install_path), |template| {
+                                Cow::Owned(template.render_for(&crate_info))
+                            }),
+                        )
+                        .is_empty() =>
+                    {
+                        Some((crate_name, Some(curr_version), Some(crate_info)))
+                    }
+                    _ => {
+                        debug!("Bailing out early because we can assume wanted is already installed from metafile");
+                        info!("{name} v{curr_version} is already installed, use --force to override");
+                        up_to_date += 1;
+                        None
+                    }
+                }
             }
 
             // The version req is "*" thus a remote upgraded version could exist
             (false, Some(curr_version), None) => {
-                Some((crate_name, Some(curr_version)))
+                let mut crate_name = crate_name;
+                // Upgrade the binaries selected when it was installed.
+                if crate_name.bins.is_empty() {
+                    if let Some(crate_info) = &crate_info {
+                        crate_name.bins = crate_info.selected_bins.clone();
+                    }
+                }
+                Some((crate_name, Some(curr_version), crate_info))
+            }
+
+            _ => Some((crate_name, None, None)),
+        }
+    })
+    .collect();
+
+    Ok((crate_names, up_to_date))
+}
+
+/// The crates to resolve, with their installed version and record if any.
+type CratesToResolve = Vec<(CrateName, Option<semver::Version>, Option<CrateInfo>)>;
+
+/// Load the lockfile at `path`, return its entries by crate.
+fn load_locked_crates(path: &Path) -> Result<BTreeMap<CompactString, Vec<LockedCrate>>> {
+    let lockfile = Lockfile::load_from_path(path)
+        .wrap_err_with(|| format!("Failed to load the lockfile '{}'", path.display()))?;
+
+    let mut locked_crates: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for locked in lockfile.into_crates() {
+        locked_crates
+            .entry(locked.name.clone())
+            .or_default()
+            .push(locked);
+    }
+    Ok(locked_crates)
+}
+
+/// Return the crates of the lockfile to install, with the version recorded
+/// if it is the same on all the targets, so that the crates already
+/// installed with it are skipped.
+fn locked_crate_names(locked_crates: &BTreeMap<CompactString, Vec<LockedCrate>>) -> Vec<CrateName> {
+    locked_crates
+        .iter()
+        .map(|(name, entries)| {
+            let version = &entries[0].version;
+            CrateName {
+                name: name.clone(),
+                version_req: entries
+                    .iter()
+                    .all(|entry| entry.version == *version)
+                    .then(|| semver::VersionReq::parse_from_cli(&version.to_string()).ok())
+                    .flatten(),
+                bins: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Record the artifacts of `fetches` in the lockfile at `path`, keeping the
+/// entries of the other crates and targets already in it.
+fn write_lockfile_to(
+    path: &Path,
+    fetches: &[Box<ResolutionFetch>],
+    sources: &[ResolutionSource],
+) -> Result<()> {
+    let mut lockfile = match Lockfile::load_from_path(path) {
+        Ok(lockfile) => lockfile,
+        Err(LockfileError::Io(err)) if err.kind() == io::ErrorKind::NotFound => Lockfile::default(),
+        Err(err) => {
+            return Err(Report::new(err)
+                .wrap_err(format!("Failed to load the lockfile '{}'", path.display())))
+        }
+    };
+
+    for locked in fetches.iter().filter_map(|fetch| fetch.lock()) {
+        lockfile.insert(locked);
+    }
+    for source in sources {
+        warn!(
+            "{} is built from source, it is not recorded in the lockfile",
+            source.name
+        );
+    }
+
+    lockfile
+        .write_to_path(path)
+        .wrap_err_with(|| format!("Failed to write the lockfile '{}'", path.display()))
+}
+
+/// Return the installed crates to check with `--upgrade` and `--outdated`:
+/// the ones in `crate_names`, or all of them if it is empty.
+///
+/// Crates installed from a git repository or a local path are skipped, as
+/// well as the pinned ones unless `include_pinned` is set.
+fn installed_crates_to_upgrade(
+    crate_names: Vec<CrateName>,
+    include_pinned: bool,
+    manifests: Option<&Manifests>,
+) -> Result<CratesToResolve> {
+    let Some(manifests) = manifests else {
+        return Err(miette!(
+            "--upgrade and --outdated need the records of the installed crates, \
+which are not kept with --install-path"
+        ));
+    };
+    let mut crate_infos = manifests.load_crate_infos();
+
+    let crate_infos: Vec<CrateInfo> = if crate_names.is_empty() {
+        crate_infos.into_values().collect()
+    } else {
+        CrateName::dedup(crate_names)
+            .filter_map(|crate_name| {
+                let crate_info = crate_infos.remove(&crate_name.name);
+                if crate_info.is_none() {
+                    warn!(
+                        "{} is not installed by binstall, skipping it",
+                        crate_name.name
+                    );
+                }
+                crate_info
+            })
+            .collect()
+    };
+
+    Ok(crate_infos
+        .into_iter()
+        .filter(|crate_info| {
+            let name = &crate_info.name;
+            let source = match crate_info.source.source_type {
+                SourceType::Registry if include_pinned || !ops::outdated::is_pinned(crate_info) => {
+                    return true
+                }
+                SourceType::Registry => {
+                    info!(
+                        "{name} is pinned to v{}, use --include-pinned to upgrade it",
+                        crate_info.current_version
+                    );
+                    return false;
+                }
+                SourceType::Git => "a git repository",
+                SourceType::Path => "a local path",
+            };
+            info!("{name} is installed from {source}, skipping it");
+            false
+        })
+        .map(|crate_info| {
+            let version_req = ops::outdated::upgrade_version_req(&crate_info);
+            let crate_name = CrateName {
+                name: crate_info.name.clone(),
+                version_req: (version_req != semver::VersionReq::STAR).then_some(version_req),
+                bins: crate_info.selected_bins.clone(),
+            };
+            (
+                crate_name,
+                Some(crate_info.current_version.clone()),
+                Some(crate_info),
+            )
+        })
+        .collect())
+}
+
+/// Remove `crate_names` and the files recorded for them, for `--uninstall`.
+///
+/// Fails before removing anything if one of them is not installed by
+/// binstall, listing the crates which are.
+fn uninstall_crates(
+    crate_names: Vec<CrateName>,
+    manifests: Option<Manifests>,
+    install_path: &Path,
+    install_path_template: Option<&InstallPathTemplate>,
+    dry_run: bool,
+) -> Result<()> {
+    let Some(mut manifests) = manifests else {
+        return Err(miette!(
+            "--uninstall needs the records of the installed crates, \
+which are not kept with --install-path"
+        ));
+    };
+    let mut crate_infos = manifests.load_crate_infos();
+    let installed: Vec<CompactString> = crate_infos.keys().cloned().collect();
+
+    let mut unknown = Vec::new();
+    let mut to_uninstall = Vec::new();
+    for crate_name in CrateName::dedup(crate_names) {
+        match crate_infos.remove(&crate_name.name) {
+            Some(crate_info) => match &crate_name.version_req {
+                Some(version_req) if !version_req.matches(&crate_info.current_version) => {
+                    return Err(miette!(
+                        "{} v{} is installed, which does not match {version_req}",
+                        crate_info.name,
+                        crate_info.current_version
+                    ));
+                }
+                _ => to_uninstall.push(crate_info),
+            },
+            None => unknown.push(crate_name.name),
+        }
+    }
+
+    if !unknown.is_empty() {
+        let help = if installed.is_empty() {
+            "No crate is installed by binstall".to_string()
+        } else {
+            format!(
+                "The crates installed by binstall are: {}",
+                installed.join(", ")
+            )
+        };
+        return Err(miette!(
+            help = help,
+            "{} not installed by binstall",
+            unknown.join(", ")
+        ));
+    }
+
+    // The binaries of the other crates, which must not be removed.
+    let other_bins = crate_infos
+        .values()
+        .flat_map(|crate_info| crate_info.bins.iter().cloned())
+        .collect();
+
+    for crate_info in to_uninstall {
+        let install_path = match install_path_template {
+            Some(template) => Cow::Owned(template.render_for(&crate_info)),
+            None => Cow::Borrowed(install_path),
+        };
+        let files = ops::uninstall::installed_files(&crate_info, &install_path, &other_bins);
+
+        if dry_run {
+            info!(
+                "Dry-run: {} v{} would be uninstalled, removing the following files:",
+                crate_info.name, crate_info.current_version
+            );
+            for file in &files {
+                info!("  - {}", file.display());
+            }
+        } else {
+            ops::uninstall::remove_files(&files)?;
+            manifests.remove(&crate_info.name)?;
+            info!(
+                "Uninstalled {} v{}",
+                crate_info.name, crate_info.current_version
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Check which of `crate_names` are behind the latest version, and print
+/// them without installing anything, for `--outdated`.
+fn check_outdated(
+    opts: Arc<Options>,
+    crate_names: CratesToResolve,
+    jobs: Arc<Semaphore>,
+    json_output: bool,
+) -> AutoAbortJoinHandle<Result<()>> {
+    let concurrent = crate_names.len() > 1;
+    let tasks: Vec<_> = crate_names
+        .into_iter()
+        .filter_map(|(_crate_name, _current_version, crate_info)| crate_info)
+        .map(|crate_info| {
+            let opts = opts.clone();
+            let jobs = jobs.clone();
+            let span = crate_span(&crate_info.name, concurrent);
+            AutoAbortJoinHandle::spawn(
+                async move {
+                    let _permit = jobs.acquire().await;
+                    ops::outdated::check(opts, crate_info).await
+                }
+                .instrument(span),
+            )
+        })
+        .collect();
+
+    AutoAbortJoinHandle::spawn(async move {
+        let mut outdated = Vec::new();
+        let mut errors = Vec::new();
+
+        for task in tasks {
+            match task.flattened_join().await {
+                Ok(Some(outdated_crate)) => outdated.push(outdated_crate),
+                Ok(None) => (),
+                Err(BinstallError::CrateContext(err)) => errors.push(err),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        print_outdated(&outdated, json_output);
+
+        match BinstallError::crate_errors(errors) {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    })
+}
+
+/// Print `outdated` as a table, or as an `outdated` array with
+/// `--json-output`.
+fn print_outdated(outdated: &[OutdatedCrate], json_output: bool) {
+    if json_output {
+        let outdated: Vec<_> = outdated
+            .iter()
+            .map(|outdated_crate| {
+                serde_json::json!({
+                    "name": outdated_crate.name,
+                    "installed": outdated_crate.installed.to_string(),
+                    "latest": outdated_crate.latest.to_string(),
+                    "prebuilt": outdated_crate.prebuilt,
+                    "source": outdated_crate.source.url.as_str(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::json!({ "outdated": outdated }));
+    } else if outdated.is_empty() {
+        info!("All the crates checked are up to date");
+    } else {
+        print!("{}", format_outdated_table(outdated));
+    }
+}
+
+fn format_outdated_table(outdated: &[OutdatedCrate]) -> String {
+    let header = ["Name", "Installed", "Latest", "Source"];
+    let rows: Vec<[String; 4]> = outdated
+        .iter()
+        .map(|outdated_crate| {
+            [
+                outdated_crate.name.to_string(),
+                outdated_crate.installed.to_string(),
+                if outdated_crate.prebuilt {
+                    outdated_crate.latest.to_string()
+                } else {
+                    format!("{} (from source)", outdated_crate.latest)
+                },
+                outdated_crate.source.url.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    for row in iter::once(header.map(String::from)).chain(rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Check the GitHub token up front, and report whether it is rejected.
+async fn report_github_auth(gh_api_client: &GhApiClient) {
+    let host = gh_api_client.host();
+
+    match gh_api_client.check_auth().await {
+        Ok(GhAuthCheck::Authenticated { login, scopes }) => {
+            info!("Authenticated to the GitHub API of {host} as {login}");
+            if let Some(scopes) = scopes {
+                debug!("GitHub token scopes: {}", scopes.join(", "));
             }
+        }
+        Ok(GhAuthCheck::Rejected) => warn!(
+            "The GitHub token was rejected by {host} (401), it may be expired or revoked, requests will be sent without it"
+        ),
+        Ok(GhAuthCheck::NoToken) => (),
+        Err(err) => debug!("Failed to check the GitHub token: {err}"),
+    }
+}
 
-            _ => Some((crate_name, None)),
+/// Resolve [`LogProgress::Auto`] from whether stdout and stderr are
+/// terminals.
+fn resolve_log_progress(
+    log_progress: LogProgress,
+    log_format: LogFormat,
+    stdout_is_terminal: bool,
+    stderr_is_terminal: bool,
+) -> LogProgress {
+    match log_progress {
+        // Keep stdout parsable.
+        LogProgress::Auto if log_format == LogFormat::Json => LogProgress::Off,
+        LogProgress::Auto if stdout_is_terminal && stderr_is_terminal => LogProgress::Bar,
+        LogProgress::Auto => LogProgress::Summary,
+        log_progress => log_progress,
+    }
+}
+
+/// Load the statistics saved by the last run, `None` if no run is recorded
+/// yet.
+fn load_last_run_stats(stats_path: &Path) -> Result<Option<RunStats>> {
+    match RunStats::load(stats_path) {
+        Ok(stats) => Ok(Some(stats)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(miette!(
+            "Failed to load the statistics of the last run from '{}': {err}",
+            stats_path.display()
+        )),
+    }
+}
+
+/// Print `stats` as a `stats` object with `--json-output`, next to the
+/// json logs on stdout.
+fn print_stats(stats: &RunStats, json_output: bool) {
+    if json_output {
+        println!("{}", serde_json::json!({ "stats": stats }));
+    } else {
+        for line in stats.to_string().lines() {
+            info!("{line}");
         }
-    }))
+    }
+}
+
+/// Add the crate returned by `report` to the report of the run, if any.
+fn report_crate(opts: &Options, report: impl FnOnce() -> CrateReport) {
+    if let Some(run_reporter) = &opts.run_reporter {
+        run_reporter.add_crate(report());
+    }
+}
+
+fn write_report(report: &RunReport, path: &Path) -> Result<()> {
+    report.write(path).into_diagnostic().wrap_err_with(|| {
+        format!(
+            "Failed to write the report of this run to '{}'",
+            path.display()
+        )
+    })
+}
+
+/// Print how many crates were already up to date, and how many had some
+/// binaries reinstalled.
+fn print_summary(up_to_date: usize, repaired: usize) {
+    if up_to_date + repaired > 0 {
+        info!("Already up to date: {up_to_date}, repaired: {repaired}");
+    }
+}
+
+/// Ask the user to confirm the installation, and drop the crates they
+/// declined.
+#[allow(clippy::vec_box)]
+async fn confirm_crates(
+    cancellation_token: &CancellationToken,
+    resolution_fetchs: &mut Vec<Box<ResolutionFetch>>,
+    resolution_sources: &mut Vec<ResolutionSource>,
+    third_party_only: bool,
+) -> Result<(), BinstallError> {
+    let crates = resolution_fetchs
+        .iter()
+        .map(|fetch| PendingCrate::from_fetch(fetch))
+        .chain(resolution_sources.iter().map(PendingCrate::from_source))
+        .collect();
+
+    let declined = confirm(cancellation_token, crates, third_party_only).await?;
+    for name in &declined {
+        info!("Skipping {name}");
+    }
+
+    resolution_fetchs.retain(|fetch| !declined.contains(&fetch.name));
+    resolution_sources.retain(|source| !declined.contains(&source.name));
+
+    Ok(())
+}
+
+/// Print the total size of the packages of `resolution_fetchs`, if there are
+/// several of them.
+fn print_download_size(resolution_fetchs: &[Box<ResolutionFetch>]) {
+    if resolution_fetchs.len() < 2 {
+        return;
+    }
+
+    let total: u64 = resolution_fetchs
+        .iter()
+        .filter_map(|fetch| fetch.download_size)
+        .sum();
+    let unknown = resolution_fetchs
+        .iter()
+        .filter(|fetch| fetch.download_size.is_none())
+        .count();
+
+    if unknown == 0 {
+        info!("Total download size: {}", format_size(total));
+    } else if unknown < resolution_fetchs.len() {
+        info!(
+            "Total download size: {}, not counting {unknown} packages of unknown size",
+            format_size(total)
+        );
+    }
+}
+
+/// Return the span prefixing the logs of `crate_name` with it, if crates are
+/// installed concurrently.
+fn crate_span(crate_name: &str, concurrent: bool) -> Span {
+    if concurrent {
+        info_span!("crate", name = crate_name)
+    } else {
+        Span::none()
+    }
 }
 
 #[allow(clippy::vec_box)]
 fn do_install_fetches(
     resolution_fetchs: Vec<Box<ResolutionFetch>>,
     // Take manifests by value to drop the `FileLock`.
-    manifests: Option<Manifests>,
+    mut manifests: Option<Manifests>,
     binstall_opts: &Options,
     dry_run: bool,
     temp_dir: tempfile::TempDir,
     no_cleanup: bool,
-) -> Result<()> {
+) -> Result<usize> {
     if resolution_fetchs.is_empty() {
-        return Ok(());
+        return Ok(0);
     }
 
     if dry_run {
         info!("Dry-run: Not proceeding to install fetched binaries");
-        return Ok(());
+        return Ok(0);
     }
 
+    let concurrent = resolution_fetchs.len() > 1;
+
     block_in_place(|| {
-        let metadata_vec = resolution_fetchs
-            .into_iter()
-            .map(|fetch| fetch.install(binstall_opts))
-            .collect::<Result<Vec<_>, BinstallError>>()?;
+        let mut repaired = 0;
+
+        for fetch in resolution_fetchs {
+            let _span = crate_span(&fetch.name, concurrent).entered();
+            repaired += usize::from(fetch.is_repair());
+            let crate_info = fetch.install(binstall_opts)?;
 
-        if let Some(manifests) = manifests {
-            manifests.update(metadata_vec)?;
+            // Record each crate as soon as it is installed, so that it is
+            // still tracked if the process dies before the rest is done.
+            if let Some(manifests) = manifests.as_mut() {
+                manifests.record(crate_info)?;
+            }
         }
 
         if no_cleanup {
@@ -522,7 +1873,7 @@ fn do_install_fetches(
             });
         }
 
-        Ok(())
+        Ok(repaired)
     })
 }
 
@@ -530,37 +1881,45 @@ fn do_install_fetches(
 fn do_install_fetches_continue_on_failure(
     resolution_fetchs: Vec<Box<ResolutionFetch>>,
     // Take manifests by value to drop the `FileLock`.
-    manifests: Option<Manifests>,
+    mut manifests: Option<Manifests>,
     binstall_opts: &Options,
     dry_run: bool,
     temp_dir: tempfile::TempDir,
     no_cleanup: bool,
     errors: &mut Vec<Box<CrateContextError>>,
-) -> Result<()> {
+) -> Result<usize> {
     if resolution_fetchs.is_empty() {
-        return Ok(());
+        return Ok(0);
     }
 
     if dry_run {
         info!("Dry-run: Not proceeding to install fetched binaries");
-        return Ok(());
+        return Ok(0);
     }
 
+    let concurrent = resolution_fetchs.len() > 1;
+
     block_in_place(|| {
-        let metadata_vec = resolution_fetchs
-            .into_iter()
-            .filter_map(|fetch| match fetch.install(binstall_opts) {
-                Ok(crate_info) => Some(crate_info),
+        let mut repaired = 0;
+
+        for fetch in resolution_fetchs {
+            let _span = crate_span(&fetch.name, concurrent).entered();
+            let is_repair = fetch.is_repair();
+            let crate_info = match fetch.install(binstall_opts) {
+                Ok(crate_info) => crate_info,
                 Err(BinstallError::CrateContext(err)) => {
                     errors.push(err);
-                    None
+                    continue;
                 }
                 Err(e) => panic!("Expected BinstallError::CrateContext(_), got {}", e),
-            })
-            .collect::<Vec<_>>();
+            };
+            repaired += usize::from(is_repair);
 
-        if let Some(manifests) = manifests {
-            manifests.update(metadata_vec)?;
+            // Record each crate as soon as it is installed, so that it is
+            // still tracked if the process dies before the rest is done.
+            if let Some(manifests) = manifests.as_mut() {
+                manifests.record(crate_info)?;
+            }
         }
 
         if no_cleanup {
@@ -572,6 +1931,271 @@ fn do_install_fetches_continue_on_failure(
             });
         }
 
-        Ok(())
+        Ok(repaired)
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use binstalk_manifests::crate_info::CrateSource;
+
+    #[test]
+    fn test_is_foreign_target() {
+        let foreign = if TARGET == "aarch64-unknown-linux-musl" {
+            "x86_64-unknown-linux-musl"
+        } else {
+            "aarch64-unknown-linux-musl"
+        };
+
+        assert!(!is_foreign_target(None));
+        assert!(!is_foreign_target(Some(&[])));
+        assert!(!is_foreign_target(Some(&[TARGET.to_string()])));
+        assert!(!is_foreign_target(Some(&[
+            foreign.to_string(),
+            TARGET.to_string()
+        ])));
+        assert!(is_foreign_target(Some(&[foreign.to_string()])));
+    }
+
+    #[test]
+    fn test_parse_proxy() {
+        assert_eq!(
+            parse_proxy("proxy.example.com:3128").unwrap().as_str(),
+            "http://proxy.example.com:3128/"
+        );
+        assert_eq!(
+            parse_proxy("socks5://127.0.0.1:1080").unwrap().as_str(),
+            "socks5://127.0.0.1:1080"
+        );
+        assert!(parse_proxy("http://[::1").is_err());
+    }
+
+    #[test]
+    fn test_read_root_certs_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.pem");
+        let not_a_cert = dir.path().join("not-a-cert.pem");
+        fs::write(&not_a_cert, "not a certificate").unwrap();
+
+        for path in [missing, not_a_cert] {
+            let err = read_root_certs(vec![], Some(path.clone())).unwrap_err();
+            assert!(
+                err.to_string().contains(&*path.to_string_lossy()),
+                "{err:?}"
+            );
+        }
+
+        assert!(read_root_certs(vec![], None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let default = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+        assert_eq!(user_agent(None).unwrap(), default);
+        assert_eq!(user_agent(Some(" ")).unwrap(), default);
+        assert_eq!(
+            user_agent(Some("corp-ci/7")).unwrap(),
+            format!("{default} corp-ci/7")
+        );
+        assert!(user_agent(Some("corp-ci\r\nX-Injected: 1")).is_err());
+    }
+
+    fn crate_info(name: &str, version_req: &str, source: CrateSource) -> CrateInfo {
+        CrateInfo {
+            version_req: version_req.into(),
+            source,
+            ..CrateInfo::for_test(name, semver::Version::new(1, 0, 0))
+        }
+    }
+
+    #[test]
+    fn test_installed_crates_to_upgrade() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manifests = Manifests::open_exclusive(dir.path()).unwrap();
+        manifests
+            .record(crate_info("a", "*", CrateSource::cratesio_registry()))
+            .unwrap();
+        manifests
+            .record(crate_info("b", "=1.0.0", CrateSource::cratesio_registry()))
+            .unwrap();
+        manifests
+            .record(crate_info("c", "~1.0", CrateSource::cratesio_registry()))
+            .unwrap();
+        manifests
+            .record(crate_info(
+                "d",
+                "*",
+                CrateSource {
+                    source_type: SourceType::Path,
+                    url: remote::Url::parse("file:///src/d").unwrap().into(),
+                },
+            ))
+            .unwrap();
+
+        let names = |include_pinned, crate_names: &[&str]| {
+            installed_crates_to_upgrade(
+                crate_names
+                    .iter()
+                    .map(|crate_name| crate_name.parse().unwrap())
+                    .collect(),
+                include_pinned,
+                Some(&manifests),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|(crate_name, current_version, _)| {
+                assert_eq!(current_version, Some(semver::Version::new(1, 0, 0)));
+                crate_name.to_string()
+            })
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(false, &[]), ["a", "c@~1.0"]);
+        assert_eq!(names(true, &[]), ["a", "b", "c@~1.0"]);
+        assert_eq!(names(false, &["b", "c", "missing"]), ["c@~1.0"]);
+
+        assert!(installed_crates_to_upgrade(vec![], false, None).is_err());
+    }
+
+    #[test]
+    fn test_format_outdated_table() {
+        let outdated = [
+            OutdatedCrate {
+                name: "cargo-binstall".into(),
+                installed: semver::Version::new(1, 0, 0),
+                latest: semver::Version::new(1, 10, 0),
+                prebuilt: true,
+                source: CrateSource::cratesio_registry(),
+            },
+            OutdatedCrate {
+                name: "tool".into(),
+                installed: semver::Version::new(0, 1, 0),
+                latest: semver::Version::new(0, 2, 0),
+                prebuilt: false,
+                source: CrateSource::cratesio_registry(),
+            },
+        ];
+
+        assert_eq!(
+            format_outdated_table(&outdated),
+            "\
+Name            Installed  Latest               Source
+cargo-binstall  1.0.0      1.10.0               https://github.com/rust-lang/crates.io-index
+tool            0.1.0      0.2.0 (from source)  https://github.com/rust-lang/crates.io-index
+"
+        );
+    }
+
+    #[test]
+    fn test_manifests_of_foreign_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let cargo_home = dir.path().join("cargo");
+        let install_path = dir.path().join("rootfs/usr/local/bin");
+
+        let compute = |install_path: Option<&Path>, foreign_target| {
+            compute_paths_and_load_manifests(
+                None,
+                install_path.map(Path::to_owned),
+                false,
+                foreign_target,
+                cargo_home.clone(),
+                &mut Config::default(),
+            )
+            .unwrap()
+        };
+
+        // Custom install path for the host is not tracked.
+        let (_, manifests, _) = compute(Some(&install_path), false);
+        assert!(manifests.is_none());
+        assert!(!install_path.join(FOREIGN_MANIFESTS_DIR).exists());
+
+        // Manifests of a foreign target are kept in the install path.
+        let (path, manifests, _) = compute(Some(&install_path), true);
+        assert_eq!(path, install_path);
+        assert!(manifests.is_some());
+        drop(manifests);
+        assert!(install_path
+            .join(FOREIGN_MANIFESTS_DIR)
+            .join(".crates.toml")
+            .is_file());
+        assert!(!cargo_home.join(".crates.toml").exists());
+
+        // The global install path always uses the cargo roots.
+        let (path, manifests, _) = compute(None, true);
+        assert_eq!(path, cargo_home.join("bin"));
+        assert!(manifests.is_some());
+        drop(manifests);
+        assert!(cargo_home.join(".crates.toml").is_file());
+    }
+
+    #[test]
+    fn test_download_only_temp_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("artifacts");
+
+        let (path, manifests, temp_dir) = compute_download_only_paths(&output_dir).unwrap();
+        assert_eq!(path, output_dir);
+        assert!(manifests.is_none());
+
+        // Nothing but the artifacts is saved into the output directory.
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 0);
+        assert_eq!(
+            temp_dir.path().parent(),
+            Some(fs::canonicalize(dir.path()).unwrap().as_path())
+        );
+        assert!(temp_dir
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with('.'));
+    }
+
+    #[test]
+    fn test_resolve_log_progress() {
+        let auto = |stdout_is_terminal, stderr_is_terminal| {
+            resolve_log_progress(
+                LogProgress::Auto,
+                LogFormat::Text,
+                stdout_is_terminal,
+                stderr_is_terminal,
+            )
+        };
+
+        assert_eq!(auto(true, true), LogProgress::Bar);
+        // e.g. `cargo binstall foo 2>ci.log`
+        assert_eq!(auto(true, false), LogProgress::Summary);
+        assert_eq!(auto(false, true), LogProgress::Summary);
+        assert_eq!(auto(false, false), LogProgress::Summary);
+
+        assert_eq!(
+            resolve_log_progress(LogProgress::Auto, LogFormat::Json, true, true),
+            LogProgress::Off
+        );
+        assert_eq!(
+            resolve_log_progress(LogProgress::Bar, LogFormat::Json, false, false),
+            LogProgress::Bar
+        );
+    }
+
+    #[test]
+    fn test_load_last_run_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let stats_path = dir.path().join("cache").join("last-run-stats.json");
+
+        // No run recorded yet.
+        assert!(load_last_run_stats(&stats_path).unwrap().is_none());
+
+        RunStats::default().save(&stats_path).unwrap();
+        assert_eq!(
+            load_last_run_stats(&stats_path).unwrap(),
+            Some(RunStats::default())
+        );
+
+        // Other errors are still reported.
+        assert!(load_last_run_stats(dir.path()).is_err());
+    }
+}
@@ -1,10 +1,12 @@
-use std::{io, process};
+use std::{env, fs, io, path::PathBuf, process};
 
 use compact_str::CompactString;
+use dirs::home_dir;
 
-pub(super) fn get() -> io::Result<CompactString> {
+/// Return the token of `gh` for `host`, e.g. `github.com`.
+pub(super) fn get(host: &str) -> io::Result<CompactString> {
     let process::Output { status, stdout, .. } = process::Command::new("gh")
-        .args(["auth", "token"])
+        .args(["auth", "token", "--hostname", host])
         .stdin(process::Stdio::null())
         .stdout(process::Stdio::piped())
         .stderr(process::Stdio::null())
@@ -25,3 +27,139 @@ pub(super) fn get() -> io::Result<CompactString> {
 
     Ok(s.trim().into())
 }
+
+/// Return the token of `host` stored in plain text in the `hosts.yml` of
+/// `gh`, which is the case when `gh` has no access to a keyring.
+pub(super) fn from_hosts_file(host: &str) -> Option<CompactString> {
+    let content = fs::read_to_string(config_dir()?.join("hosts.yml")).ok()?;
+    from_hosts_yml(&content, host)
+}
+
+/// Return the config dir of `gh`, following the same order as `gh`.
+fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("GH_CONFIG_DIR").filter(|dir| !dir.is_empty()) {
+        return Some(dir.into());
+    }
+
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("gh"));
+    }
+
+    if cfg!(windows) {
+        if let Some(dir) = env::var_os("AppData").filter(|dir| !dir.is_empty()) {
+            return Some(PathBuf::from(dir).join("GitHub CLI"));
+        }
+    }
+
+    Some(home_dir()?.join(".config/gh"))
+}
+
+/// Extract the `oauth_token` of `host` from the content of `hosts.yml`.
+///
+/// Only the subset of yaml written by `gh` is supported: a mapping from
+/// the hosts to mappings of plain scalars.
+fn from_hosts_yml(content: &str, host: &str) -> Option<CompactString> {
+    let mut in_host = false;
+    let mut host_indent = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        // Skip the lines which are not a key: the items of a sequence, which
+        // may contain a `:` themselves, and the lines without `:`.
+        if trimmed.starts_with("- ") {
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (unquote(key.trim()), unquote(value.trim()));
+
+        let indent = line.len() - trimmed.len();
+        if indent == 0 {
+            in_host = key.eq_ignore_ascii_case(host);
+            host_indent = None;
+            continue;
+        }
+
+        // Skip the tokens of the `users` of the host, the one of the
+        // active user is a direct child of the host.
+        if !in_host || *host_indent.get_or_insert(indent) != indent {
+            continue;
+        }
+
+        if key == "oauth_token" && !value.is_empty() {
+            return Some(value.into());
+        }
+    }
+
+    None
+}
+
+fn unquote(s: &str) -> &str {
+    let s = match s.split_once(" #") {
+        Some((s, _comment)) => s.trim_end(),
+        None => s,
+    };
+
+    ['"', '\'']
+        .into_iter()
+        .find_map(|quote| s.strip_prefix(quote)?.strip_suffix(quote))
+        .unwrap_or(s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_hosts_yml() {
+        let hosts = include_str!("../tests/fixtures/gh-hosts/hosts.yml");
+        assert_eq!(
+            from_hosts_yml(hosts, "github.com").as_deref(),
+            Some("gho_github")
+        );
+        assert_eq!(
+            from_hosts_yml(hosts, "github.corp.example.com").as_deref(),
+            Some("ghp_corp")
+        );
+        assert_eq!(from_hosts_yml(hosts, "gitlab.com"), None);
+    }
+
+    #[test]
+    fn test_from_hosts_yml_skips_lines_without_key() {
+        let hosts = "\
+github.com:
+    scopes:
+- repo
+- read:org
+    oauth_token: gho_github
+";
+        assert_eq!(
+            from_hosts_yml(hosts, "github.com").as_deref(),
+            Some("gho_github")
+        );
+    }
+
+    #[test]
+    fn test_from_hosts_yml_multi_account() {
+        let hosts = include_str!("../tests/fixtures/gh-hosts/multi-account.yml");
+        assert_eq!(
+            from_hosts_yml(hosts, "github.com").as_deref(),
+            Some("gho_active")
+        );
+    }
+
+    #[test]
+    fn test_from_hosts_yml_keyring() {
+        // The token is stored in the keyring, only `gh auth token` can
+        // retrieve it.
+        let hosts = include_str!("../tests/fixtures/gh-hosts/keyring.yml");
+        assert_eq!(from_hosts_yml(hosts, "github.com"), None);
+        assert_eq!(from_hosts_yml("not: [yaml", "github.com"), None);
+        assert_eq!(from_hosts_yml("", "github.com"), None);
+    }
+}
@@ -0,0 +1,48 @@
+//! Discovery of the GitHub token when none is passed with `--github-token`,
+//! `GITHUB_TOKEN` or `GH_TOKEN`.
+
+use compact_str::CompactString;
+use tracing::debug;
+
+use crate::{gh_token, git_credentials};
+
+/// Return the token of `host`, e.g. `github.com`, from the first source
+/// that has one:
+///  - the `hosts.yml` of `gh`
+///  - the credential files of git
+///  - `gh auth token`, for tokens stored in a keyring
+///  - `git credential fill`, for the credential helpers of git
+///
+/// Discovery is best-effort, `None` is returned if every source fails so
+/// that the GitHub API is used anonymously.
+pub(crate) fn discover(host: &str) -> Option<CompactString> {
+    if let Some(token) = gh_token::from_hosts_file(host) {
+        debug!("Using the GitHub token of {host} from the hosts.yml of gh");
+        return Some(token);
+    }
+
+    if let Some(token) = git_credentials::try_from_home(host) {
+        debug!("Using the GitHub token of {host} from the git credential file");
+        return Some(token);
+    }
+
+    match gh_token::get(host) {
+        Ok(token) if !token.is_empty() => {
+            debug!("Using the GitHub token of {host} from `gh auth token`");
+            return Some(token);
+        }
+        Ok(_) => debug!("`gh auth token` returned no token"),
+        Err(err) => debug!(?err, "Failed to retrieve token from `gh auth token`"),
+    }
+
+    match git_credentials::from_helper(host) {
+        Ok(token) => {
+            debug!("Using the GitHub token of {host} from `git credential fill`");
+            return Some(token);
+        }
+        Err(err) => debug!(?err, "Failed to retrieve token from `git credential fill`"),
+    }
+
+    debug!("No GitHub token found for {host}, using the API anonymously");
+    None
+}
@@ -1,11 +1,68 @@
 use std::{
     env::var_os,
-    path::{Path, PathBuf},
+    ffi::OsString,
+    path::{is_separator, Path, PathBuf},
 };
 
 use binstalk_manifests::cargo_config::Config;
+use miette::{miette, Result};
 use tracing::debug;
 
+/// Expand a leading `~` to the home directory, and `$VAR` or `${VAR}` to
+/// the value of the environment variable, in a path given on the command
+/// line.
+///
+/// Paths which are not valid UTF-8 are returned as is.
+pub fn expand_path(path: PathBuf) -> Result<PathBuf> {
+    let Some(source) = path.to_str() else {
+        return Ok(path);
+    };
+
+    let mut expanded = OsString::with_capacity(source.len());
+    let mut rest = source;
+
+    if let Some(after) = rest.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(is_separator) {
+            let home = dirs::home_dir().ok_or_else(|| {
+                miette!("Cannot expand `~` in {source}: the home directory is unknown")
+            })?;
+            expanded.push(home);
+            rest = after;
+        }
+    }
+
+    while let Some(pos) = rest.find('$') {
+        expanded.push(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        let (name, next) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+
+        if name.is_empty() {
+            expanded.push("$");
+        } else {
+            let value = var_os(name).ok_or_else(|| {
+                miette!("Cannot expand `${name}` in {source}: the environment variable is not set")
+            })?;
+            expanded.push(value);
+        }
+        rest = next;
+    }
+    expanded.push(rest);
+
+    Ok(expanded.into())
+}
+
 pub fn get_cargo_roots_path(
     cargo_roots: Option<PathBuf>,
     cargo_home: PathBuf,
@@ -54,3 +111,52 @@ pub fn get_install_path(
 
     (dir, true)
 }
+
+/// Return the directory to install shell completions and man pages into.
+///
+/// Uses `$prefix/share` if `prefix` is specified, otherwise fallback to
+/// `$XDG_DATA_HOME` (default to `$HOME/.local/share`) on unix.
+pub fn get_extra_files_dir(prefix: Option<PathBuf>) -> Option<PathBuf> {
+    if let Some(prefix) = prefix {
+        return Some(prefix.join("share"));
+    }
+
+    if !cfg!(unix) {
+        return None;
+    }
+
+    var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(".local/share")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expand() {
+        std::env::set_var("BINSTALL_TEST_EXPAND_PATH", "/opt/tools");
+
+        let expand = |path: &str| expand_path(path.into()).unwrap();
+
+        assert_eq!(
+            expand("$BINSTALL_TEST_EXPAND_PATH/bin"),
+            Path::new("/opt/tools/bin")
+        );
+        assert_eq!(
+            expand("${BINSTALL_TEST_EXPAND_PATH}-v1/{ name }"),
+            Path::new("/opt/tools-v1/{ name }")
+        );
+        assert_eq!(expand("/opt/$/bin"), Path::new("/opt/$/bin"));
+        assert_eq!(expand("/opt/~/bin"), Path::new("/opt/~/bin"));
+
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(expand("~"), home);
+            assert_eq!(expand("~/tools"), home.join("tools"));
+        }
+
+        assert!(expand_path("$BINSTALL_TEST_EXPAND_PATH_UNSET/bin".into()).is_err());
+    }
+}
@@ -5,9 +5,11 @@ mod bin_util;
 mod entry;
 mod gh_token;
 mod git_credentials;
+mod github_token;
 mod install_path;
 mod logging;
 mod main_impl;
+mod progress;
 mod signal;
 mod ui;
 
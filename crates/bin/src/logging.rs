@@ -6,7 +6,10 @@ use std::{
 
 use log::{LevelFilter, Log, STATIC_MAX_LEVEL};
 use once_cell::sync::Lazy;
-use supports_color::{on as supports_color_on_stream, Stream::Stdout};
+use supports_color::{
+    on as supports_color_on_stream,
+    Stream::{Stderr, Stdout},
+};
 use tracing::{
     callsite::Callsite,
     dispatcher, field,
@@ -21,6 +24,8 @@ use tracing_subscriber::{
     layer::SubscriberExt,
 };
 
+use crate::args::LogFormat;
+
 // Shamelessly taken from tracing-log
 
 struct Fields {
@@ -137,16 +142,35 @@ impl Log for Logger {
     fn flush(&self) {}
 }
 
-struct ErrorFreeWriter;
+/// Write to stdout or stderr, ignoring the errors so that the logging
+/// system keeps working.
+#[derive(Copy, Clone)]
+enum ErrorFreeWriter {
+    Stdout,
+    Stderr,
+}
+
+impl ErrorFreeWriter {
+    fn report_err(self, err: io::Error) {
+        let stream = match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        };
+        writeln!(io::stderr(), "Failed to write to {stream}: {err}").ok();
+    }
 
-fn report_err(err: io::Error) {
-    writeln!(io::stderr(), "Failed to write to stdout: {err}").ok();
+    fn with_stream<T>(self, f: impl FnOnce(&mut dyn Write) -> io::Result<T>) -> io::Result<T> {
+        match self {
+            Self::Stdout => f(&mut io::stdout()),
+            Self::Stderr => f(&mut io::stderr()),
+        }
+    }
 }
 
-impl io::Write for &ErrorFreeWriter {
+impl io::Write for ErrorFreeWriter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        io::stdout().write(buf).or_else(|err| {
-            report_err(err);
+        self.with_stream(|stream| stream.write(buf)).or_else(|err| {
+            self.report_err(err);
             // Behave as if writing to /dev/null so that logging system
             // would keep working.
             Ok(buf.len())
@@ -154,26 +178,28 @@ impl io::Write for &ErrorFreeWriter {
     }
 
     fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        io::stdout().write_all(buf).or_else(|err| {
-            report_err(err);
-            // Behave as if writing to /dev/null so that logging system
-            // would keep working.
-            Ok(())
-        })
+        self.with_stream(|stream| stream.write_all(buf))
+            .or_else(|err| {
+                self.report_err(err);
+                // Behave as if writing to /dev/null so that logging system
+                // would keep working.
+                Ok(())
+            })
     }
 
     fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-        io::stdout().write_vectored(bufs).or_else(|err| {
-            report_err(err);
-            // Behave as if writing to /dev/null so that logging system
-            // would keep working.
-            Ok(bufs.iter().map(|io_slice| io_slice.len()).sum())
-        })
+        self.with_stream(|stream| stream.write_vectored(bufs))
+            .or_else(|err| {
+                self.report_err(err);
+                // Behave as if writing to /dev/null so that logging system
+                // would keep working.
+                Ok(bufs.iter().map(|io_slice| io_slice.len()).sum())
+            })
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        io::stdout().flush().or_else(|err| {
-            report_err(err);
+        self.with_stream(|stream| stream.flush()).or_else(|err| {
+            self.report_err(err);
             // Behave as if writing to /dev/null so that logging system
             // would keep working.
             Ok(())
@@ -181,15 +207,30 @@ impl io::Write for &ErrorFreeWriter {
     }
 }
 
-impl<'a> MakeWriter<'a> for ErrorFreeWriter {
-    type Writer = &'a Self;
+/// Print the info logs meant for the user to stdout and the diagnostics,
+/// i.e. everything else, to stderr so that they can be separated.
+///
+/// json logs are all printed to stderr.
+struct StdioWriter {
+    log_format: LogFormat,
+}
+
+impl<'a> MakeWriter<'a> for StdioWriter {
+    type Writer = ErrorFreeWriter;
 
     fn make_writer(&'a self) -> Self::Writer {
-        self
+        ErrorFreeWriter::Stderr
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        match self.log_format {
+            LogFormat::Text if *meta.level() == Level::INFO => ErrorFreeWriter::Stdout,
+            _ => ErrorFreeWriter::Stderr,
+        }
     }
 }
 
-pub fn logging(log_level: LevelFilter, json_output: bool) {
+pub fn logging(log_level: LevelFilter, log_format: LogFormat) {
     // Calculate log_level
     let log_level = min(log_level, STATIC_MAX_LEVEL);
 
@@ -211,28 +252,33 @@ pub fn logging(log_level: LevelFilter, json_output: bool) {
 
     // Build fmt subscriber
     let log_level = log_level.as_trace();
-    let subscriber_builder = fmt().with_max_level(log_level).with_writer(ErrorFreeWriter);
-
-    let subscriber: Box<dyn Subscriber + Send + Sync> = if json_output {
-        Box::new(subscriber_builder.json().finish())
-    } else {
-        // Disable time, target, file, line_num, thread name/ids to make the
-        // output more readable
-        let subscriber_builder = subscriber_builder
-            .without_time()
-            .with_target(false)
-            .with_file(false)
-            .with_line_number(false)
-            .with_thread_names(false)
-            .with_thread_ids(false);
-
-        // subscriber_builder defaults to write to io::stdout(),
-        // so tests whether it supports color.
-        let stdout_supports_color = supports_color_on_stream(Stdout)
-            .map(|color_level| color_level.has_basic)
-            .unwrap_or_default();
-
-        Box::new(subscriber_builder.with_ansi(stdout_supports_color).finish())
+    let subscriber_builder = fmt()
+        .with_max_level(log_level)
+        .with_writer(StdioWriter { log_format });
+
+    let subscriber: Box<dyn Subscriber + Send + Sync> = match log_format {
+        LogFormat::Json => Box::new(subscriber_builder.json().finish()),
+        LogFormat::Text => {
+            // Disable time, target, file, line_num, thread name/ids to make the
+            // output more readable
+            let subscriber_builder = subscriber_builder
+                .without_time()
+                .with_target(false)
+                .with_file(false)
+                .with_line_number(false)
+                .with_thread_names(false)
+                .with_thread_ids(false);
+
+            // Logs are written to both stdout and stderr, so tests whether
+            // both of them support color.
+            let supports_color = [Stdout, Stderr].into_iter().all(|stream| {
+                supports_color_on_stream(stream)
+                    .map(|color_level| color_level.has_basic)
+                    .unwrap_or_default()
+            });
+
+            Box::new(subscriber_builder.with_ansi(supports_color).finish())
+        }
     };
 
     // Builder layer for filtering
@@ -1,6 +1,11 @@
-use std::{process::Termination, time::Instant};
+use std::{env, process::Termination, time::Instant};
 
-use binstalk::{helpers::jobserver_client::LazyJobserverClient, TARGET};
+use binstalk::{
+    errors::EXIT_CODES,
+    get_desired_targets,
+    helpers::{jobserver_client::LazyJobserverClient, remove_old_files},
+    TARGET,
+};
 use log::LevelFilter;
 use tracing::debug;
 
@@ -31,10 +36,13 @@ pub fn do_main() -> impl Termination {
             let rustc_commit_hash = env!("VERGEN_RUSTC_COMMIT_HASH");
             let rustc_llvm_version = env!("VERGEN_RUSTC_LLVM_VERSION");
 
+            let targets = desired_targets(args.targets);
+
             println!(
                 r#"cargo-binstall: {cargo_binstall_version}
 build-date: {build_date}
 build-target: {TARGET}
+targets: {targets}
 build-features: {features}
 build-commit-hash: {git_sha}
 build-commit-date: {git_commit_date}
@@ -46,15 +54,27 @@ rustc-llvm-version: {rustc_llvm_version}"#
             println!("{cargo_binstall_version}");
         }
         MainExit::Success(None)
+    } else if args.list_exit_codes {
+        for (code, kind, description) in EXIT_CODES {
+            println!("{code:>3}  {:<17}  {description}", kind.to_string());
+        }
+        MainExit::Success(None)
     } else {
-        logging(
-            args.log_level.unwrap_or(LevelFilter::Info),
-            args.json_output,
-        );
+        logging(args.log_level.unwrap_or(LevelFilter::Info), args.log_format);
+
+        // A previous self-upgrade moves the running executable aside on
+        // Windows, it can be removed now that it has exited.
+        if cfg!(windows) {
+            if let Ok(current_exe) = env::current_exe() {
+                remove_old_files(&current_exe);
+            }
+        }
 
         let start = Instant::now();
 
-        let result = run_tokio_main(|| entry::install_crates(args, jobserver_client));
+        let result = run_tokio_main(|cancellation_token| {
+            entry::install_crates(args, jobserver_client, cancellation_token)
+        });
 
         let done = start.elapsed();
         debug!("run time: {done:?}");
@@ -62,3 +82,12 @@ rustc-llvm-version: {rustc_llvm_version}"#
         MainExit::new(result, done)
     }
 }
+
+/// Return the targets binaries would be looked up for, as detected or
+/// overridden by `--targets` or `BINSTALL_HOST_TARGETS`.
+fn desired_targets(targets: Option<Vec<String>>) -> String {
+    match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt.block_on(async { get_desired_targets(targets).get().await.join(",") }),
+        Err(_) => "UNKNOWN".to_string(),
+    }
+}
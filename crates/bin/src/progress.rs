@@ -0,0 +1,511 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Write as _},
+    io::{self, Write},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use binstalk::ops::{
+    progress::{Progress, ProgressSink},
+    resolve::format_size,
+};
+use compact_str::CompactString;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+/// Lines longer than this are truncated.
+const MAX_LINE_LEN: usize = 160;
+
+/// Print one line per state transition per crate, suitable for CI logs.
+pub struct SummaryProgress<W> {
+    writer: Mutex<W>,
+}
+
+impl SummaryProgress<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W> SummaryProgress<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W> fmt::Debug for SummaryProgress<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SummaryProgress")
+    }
+}
+
+impl<W: Write + Send> ProgressSink for SummaryProgress<W> {
+    fn report(&self, crate_name: &str, progress: Progress<'_>) {
+        let mut line = String::with_capacity(64);
+
+        write!(line, "[{}] {crate_name}: ", WallClock(SystemTime::now())).unwrap();
+
+        match progress {
+            Progress::Resolving => line.push_str("resolving"),
+            Progress::Found {
+                source,
+                target,
+                size,
+            } => {
+                write!(line, "found artifact at {source} ({target}").unwrap();
+                if let Some(size) = size {
+                    write!(line, ", {}", format_size(size)).unwrap();
+                }
+                line.push(')');
+            }
+            Progress::Downloaded { elapsed } => {
+                write!(line, "downloaded in {:.1}s", elapsed.as_secs_f64()).unwrap()
+            }
+            Progress::Installed { bins, install_path } => write!(
+                line,
+                "installed {bins} {} to {}",
+                if bins == 1 { "binary" } else { "binaries" },
+                install_path.display()
+            )
+            .unwrap(),
+            Progress::Failed { error } => write!(line, "failed: {error}").unwrap(),
+            _ => return,
+        }
+
+        let mut line = sanitize_line(line);
+        line.push('\n');
+
+        let mut writer = self.writer.lock().unwrap();
+        // Progress is best-effort, errors are ignored just like the logger.
+        writer.write_all(line.as_bytes()).ok();
+        writer.flush().ok();
+    }
+}
+
+/// Draw a progress bar per artifact being downloaded, or a spinner with a
+/// byte counter if its size is unknown, for terminals.
+pub struct BarProgress {
+    bars: MultiProgress,
+    downloads: Mutex<HashMap<CompactString, ProgressBar>>,
+}
+
+impl BarProgress {
+    /// Draw to stdout, nothing is drawn if it is not a terminal.
+    pub fn stdout() -> Self {
+        Self::new(ProgressDrawTarget::stdout())
+    }
+
+    fn new(target: ProgressDrawTarget) -> Self {
+        Self {
+            bars: MultiProgress::with_draw_target(target),
+            downloads: Mutex::default(),
+        }
+    }
+
+    fn new_bar(&self, crate_name: &str, total: Option<u64>) -> ProgressBar {
+        let bar = match total {
+            Some(total) => ProgressBar::new(total).with_style(
+                ProgressStyle::with_template(
+                    "{prefix} [{bar:30}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+                )
+                .unwrap()
+                .progress_chars("=> "),
+            ),
+            None => ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{prefix} {spinner} {bytes} ({bytes_per_sec})")
+                    .unwrap(),
+            ),
+        };
+        let bar = self
+            .bars
+            .add(bar.with_prefix(sanitize_line(crate_name.to_owned())));
+
+        if total.is_none() {
+            bar.enable_steady_tick(Duration::from_millis(100));
+        }
+
+        bar
+    }
+}
+
+impl fmt::Debug for BarProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BarProgress")
+    }
+}
+
+impl ProgressSink for BarProgress {
+    fn report(&self, crate_name: &str, progress: Progress<'_>) {
+        let mut downloads = self.downloads.lock().unwrap();
+
+        match progress {
+            Progress::Downloading { downloaded, total } => {
+                let bar = downloads
+                    .entry(crate_name.into())
+                    .or_insert_with(|| self.new_bar(crate_name, total));
+                bar.set_position(downloaded);
+            }
+            // The download is over, or another artifact is about to be
+            // downloaded after it failed.
+            _ => {
+                if let Some(bar) = downloads.remove(crate_name) {
+                    bar.finish_and_clear();
+                }
+            }
+        }
+    }
+}
+
+/// Remove control characters and truncate the line to at most
+/// [`MAX_LINE_LEN`] characters.
+fn sanitize_line(line: String) -> String {
+    let mut chars = line.chars().map(|c| if c.is_control() { ' ' } else { c });
+
+    let mut sanitized: String = chars.by_ref().take(MAX_LINE_LEN).collect();
+    if chars.next().is_some() {
+        sanitized.pop();
+        sanitized.push('…');
+    }
+
+    sanitized
+}
+
+/// Format time of the day as `HH:MM:SS` in UTC.
+struct WallClock(SystemTime);
+
+impl fmt::Display for WallClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default()
+            % (24 * 60 * 60);
+
+        write!(
+            f,
+            "{:02}:{:02}:{:02}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{
+        fs,
+        num::{NonZeroU16, NonZeroU64},
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use binstalk::{
+        fetchers::{
+            ArtifactCache, ChecksumPolicy, Data, FetchError, Fetcher, SignaturePolicy,
+            TargetDataErased,
+        },
+        get_desired_targets,
+        helpers::{
+            download::{
+                DownloadError, DownloadProgress, ExtractFilter, ExtractedFiles, SavedArtifact,
+            },
+            gh_api_client::GhApiClient,
+            gitea_api_client::GiteaApiClient,
+            gl_api_client::GlApiClient,
+            jobserver_client::LazyJobserverClient,
+            remote::{Client, SpkiPins},
+        },
+        manifests::{
+            cargo_toml_binstall::{PkgFmt, PkgMeta, PkgOverride, Strategy},
+            crate_info::{FetchSource, SignatureCheck, Verification},
+        },
+        ops::{
+            resolve::{resolve, CrateName, Resolution},
+            CargoTomlFetchOverride, Options, VersionMatchPolicy,
+        },
+    };
+    use tokio::task::JoinHandle;
+
+    const TARGET: &str = "x86_64-unknown-linux-gnu";
+
+    /// Provide the binary `cargo-binstall` of `cargo-binstall-test`.
+    struct MockFetcher {
+        target_data: Arc<TargetDataErased>,
+    }
+
+    #[async_trait::async_trait]
+    impl Fetcher for MockFetcher {
+        fn new(
+            _client: Client,
+            _gh_api_client: GhApiClient,
+            _gl_api_client: GlApiClient,
+            _gitea_api_client: GiteaApiClient,
+            _data: Arc<Data>,
+            target_data: Arc<TargetDataErased>,
+            _signature_policy: SignaturePolicy,
+            _checksum_policy: ChecksumPolicy,
+            _artifact_cache: Option<ArtifactCache>,
+        ) -> Arc<dyn Fetcher> {
+            Arc::new(Self { target_data })
+        }
+
+        async fn fetch_and_extract(
+            &self,
+            dst: &Path,
+            _filter: Option<&ExtractFilter>,
+            _progress: &dyn DownloadProgress,
+        ) -> Result<(ExtractedFiles, Verification), FetchError> {
+            fs::create_dir_all(dst).map_err(DownloadError::from)?;
+            fs::write(dst.join("cargo-binstall"), "#!/bin/sh\n").map_err(DownloadError::from)?;
+
+            let mut extracted_files = ExtractedFiles::new();
+            extracted_files.add_file(Path::new("cargo-binstall"));
+            Ok((
+                extracted_files,
+                Verification {
+                    sha256: "".into(),
+                    signature: SignatureCheck::NotPublished,
+                },
+            ))
+        }
+
+        async fn fetch_and_save(
+            &self,
+            _dir: &Path,
+            _progress: &dyn DownloadProgress,
+        ) -> Result<(SavedArtifact, Verification), FetchError> {
+            unimplemented!()
+        }
+
+        fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>> {
+            tokio::spawn(async { Ok(true) })
+        }
+
+        fn report_to_upstream(self: Arc<Self>) {}
+
+        async fn report_size(&self) -> Result<Option<u64>, FetchError> {
+            Ok(Some(12_345_678))
+        }
+
+        fn pkg_fmt(&self) -> PkgFmt {
+            PkgFmt::Tgz
+        }
+
+        fn target_meta(&self) -> PkgMeta {
+            let mut meta = self.target_data.meta.clone();
+            meta.pkg_fmt = Some(self.pkg_fmt());
+            meta
+        }
+
+        fn source_name(&self) -> CompactString {
+            "github.com".into()
+        }
+
+        fn fetch_source(&self) -> FetchSource {
+            FetchSource::GhCrateMeta {
+                url: "https://github.com/cargo-binstall-test.tgz"
+                    .parse()
+                    .unwrap(),
+                repo: None,
+            }
+        }
+
+        fn fetcher_name(&self) -> &'static str {
+            "MockFetcher"
+        }
+
+        fn is_third_party(&self) -> bool {
+            false
+        }
+
+        fn strategy(&self) -> Strategy {
+            Strategy::CrateMetaData
+        }
+
+        fn target(&self) -> &str {
+            &self.target_data.target
+        }
+
+        fn target_data(&self) -> &Arc<TargetDataErased> {
+            &self.target_data
+        }
+    }
+
+    fn options(dir: &Path, progress_sink: Arc<dyn ProgressSink>) -> Options {
+        let client = Client::new(
+            "binstall-test",
+            None,
+            NonZeroU16::new(10).unwrap(),
+            NonZeroU64::new(1).unwrap(),
+            [],
+            SpkiPins::default(),
+            None,
+        )
+        .unwrap();
+
+        let manifest_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/summary-progress.Cargo.toml");
+
+        Options {
+            no_symlinks: true,
+            dry_run: false,
+            force: false,
+            quiet: true,
+            locked: false,
+            no_track: true,
+
+            tracked_bins: None,
+            query_package_owner: false,
+
+            completion_shells: Vec::new(),
+            install_man: false,
+            extra_files_dir: None,
+            tracked_extra_files: None,
+
+            version_req: None,
+            strict: false,
+            denylist: Default::default(),
+            cargo_toml_fetch_override: Some(CargoTomlFetchOverride::Path(manifest_path)),
+            cli_overrides: PkgOverride::default(),
+
+            desired_targets: get_desired_targets(Some(vec![TARGET.to_string()])),
+            resolvers: vec![MockFetcher::new],
+            cargo_install_fallback: false,
+            disabled_strategies: Vec::new(),
+
+            temp_dir: dir.join("tmp"),
+            install_path: dir.join("bin"),
+            install_path_template: None,
+            link_dir: None,
+            download_only: None,
+            cargo_root: None,
+            artifact_cache: None,
+
+            gh_api_client: GhApiClient::new(client.clone(), None),
+            gl_api_client: GlApiClient::new(client.clone(), None),
+            gitea_api_client: GiteaApiClient::new(client.clone(), None),
+            client,
+            jobserver_client: LazyJobserverClient::new(),
+            registry: Default::default(),
+            cancellation_token: Default::default(),
+
+            signature_policy: SignaturePolicy::IfPresent,
+            checksum_policy: ChecksumPolicy::IfPresent,
+            version_match_policy: VersionMatchPolicy::Ignore,
+            allow_insecure_http: false,
+            url_rewrites: Default::default(),
+
+            progress_sink: Some(progress_sink),
+            stats: Default::default(),
+            run_reporter: None,
+
+            usage_reporter: None,
+            disable_telemetry: true,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_summary_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("tmp")).unwrap();
+        fs::create_dir_all(dir.path().join("bin")).unwrap();
+
+        let sink = Arc::new(SummaryProgress::new(Vec::new()));
+        let opts = Arc::new(options(dir.path(), sink.clone()));
+
+        let crate_name = CrateName {
+            name: "cargo-binstall-test".into(),
+            version_req: None,
+            bins: Vec::new(),
+        };
+        let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name, None).await else {
+            panic!("Expected the crate to be fetched");
+        };
+        fetch.install(&opts).unwrap();
+
+        let output = String::from_utf8(sink.writer.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output
+            .lines()
+            .map(|line| {
+                let (time, rest) = line.split_once(' ').unwrap();
+                assert_eq!(time.len(), "[00:00:00]".len());
+                rest
+            })
+            .collect();
+
+        assert_eq!(lines.len(), 4, "{output}");
+        assert_eq!(
+            lines[..2],
+            [
+                "cargo-binstall-test: resolving",
+                "cargo-binstall-test: found artifact at github.com (x86_64-unknown-linux-gnu, 12.3 MB)",
+            ]
+        );
+        assert!(
+            lines[2].starts_with("cargo-binstall-test: downloaded in ") && lines[2].ends_with('s'),
+            "{output}"
+        );
+        assert_eq!(
+            lines[3],
+            format!(
+                "cargo-binstall-test: installed 1 binary to {}",
+                dir.path().join("bin").display()
+            )
+        );
+    }
+
+    #[test]
+    fn test_sanitize_line() {
+        assert_eq!(sanitize_line("a\rb\n".to_owned()), "a b ");
+
+        let line = sanitize_line("x".repeat(200));
+        assert_eq!(line.chars().count(), MAX_LINE_LEN);
+        assert!(line.ends_with('…'));
+
+        assert_eq!(
+            sanitize_line("x".repeat(MAX_LINE_LEN)),
+            "x".repeat(MAX_LINE_LEN)
+        );
+    }
+
+    #[test]
+    fn test_bar_progress() {
+        let sink = BarProgress::new(ProgressDrawTarget::hidden());
+        let downloading = |downloaded, total| Progress::Downloading { downloaded, total };
+
+        sink.report("a", downloading(10, Some(100)));
+        sink.report("a", downloading(50, Some(100)));
+        sink.report("b", downloading(10, None));
+
+        {
+            let downloads = sink.downloads.lock().unwrap();
+            assert_eq!(downloads["a"].position(), 50);
+            assert_eq!(downloads["a"].length(), Some(100));
+            assert_eq!(downloads["b"].position(), 10);
+            assert_eq!(downloads["b"].length(), None);
+        }
+
+        sink.report(
+            "a",
+            Progress::Downloaded {
+                elapsed: Duration::from_secs(1),
+            },
+        );
+        sink.report("b", Progress::Resolving);
+
+        assert!(sink.downloads.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_wall_clock() {
+        let time = UNIX_EPOCH + Duration::from_secs(3 * 24 * 3600 + 13 * 3600 + 4 * 60 + 5);
+        assert_eq!(WallClock(time).to_string(), "13:04:05");
+    }
+}
@@ -1,30 +1,50 @@
 use std::io;
 
-use binstalk::{errors::BinstallError, helpers::tasks::AutoAbortJoinHandle};
-use tokio::signal;
+use binstalk::{
+    errors::BinstallError,
+    helpers::{remote::CancellationToken, tasks::AutoAbortJoinHandle},
+};
+use tracing::warn;
 
 /// This function will poll the handle while listening for ctrl_c,
-/// `SIGINT`, `SIGHUP`, `SIGTERM` and `SIGQUIT`.
+/// `SIGINT`, `SIGHUP`, `SIGTERM` and `SIGQUIT` (ctrl_c and ctrl_break on
+/// windows).
 ///
-/// When signal is received, [`BinstallError::UserAbort`] will be returned.
+/// On the first signal, `cancellation_token` is cancelled so that the
+/// handle can stop and clean up its temporary files, then
+/// [`BinstallError::UserAbort`] is returned once it is done.
+/// On the second one, the process exits immediately.
 ///
 /// It would also ignore `SIGUSER1` and `SIGUSER2` on unix.
 ///
 /// This function uses [`tokio::signal`] and once exit, does not reset the default
 /// signal handler, so be careful when using it.
 pub async fn cancel_on_user_sig_term<T>(
-    handle: AutoAbortJoinHandle<T>,
+    mut handle: AutoAbortJoinHandle<T>,
+    cancellation_token: CancellationToken,
 ) -> Result<T, BinstallError> {
     ignore_signals()?;
 
+    let mut signals = CancellationSignals::new()?;
+
+    tokio::select! {
+        biased;
+
+        () = signals.recv() => (),
+        res = &mut handle => return res,
+    }
+
+    warn!("Cancelling, press ctrl_c again to exit immediately");
+    cancellation_token.cancel();
+
     tokio::select! {
         biased;
 
-        res = wait_on_cancellation_signal() => {
-            res.map_err(BinstallError::Io)
-                .and(Err(BinstallError::UserAbort))
+        () = signals.recv() => {
+            warn!("Installation cancelled, exiting without cleaning up");
+            std::process::exit(BinstallError::UserAbort.exit_number().into())
         }
-        res = handle => res,
+        _ = handle => Err(BinstallError::UserAbort),
     }
 }
 
@@ -35,43 +55,50 @@ fn ignore_signals() -> io::Result<()> {
     Ok(())
 }
 
-/// If call to it returns `Ok(())`, then all calls to this function after
-/// that also returns `Ok(())`.
-async fn wait_on_cancellation_signal() -> Result<(), io::Error> {
-    #[cfg(unix)]
-    unix::wait_on_cancellation_signal_unix().await?;
-
-    #[cfg(not(unix))]
-    signal::ctrl_c().await?;
-
-    Ok(())
-}
+#[cfg(unix)]
+use unix::Signals as CancellationSignals;
+#[cfg(windows)]
+use windows::Signals as CancellationSignals;
 
 #[cfg(unix)]
 mod unix {
     use super::*;
-    use signal::unix::{signal, SignalKind};
+    use tokio::signal::unix::{signal, Signal, SignalKind};
 
-    /// Same as [`wait_on_cancellation_signal`] but is only available on unix.
-    pub async fn wait_on_cancellation_signal_unix() -> Result<(), io::Error> {
-        tokio::select! {
-            biased;
+    /// Listeners of the signals requesting the cancellation, kept across
+    /// calls to [`Signals::recv`] so that no signal is missed in between.
+    pub struct Signals {
+        interrupt: Signal,
+        hangup: Signal,
+        terminate: Signal,
+        quit: Signal,
+    }
 
-            res = wait_for_signal_unix(SignalKind::interrupt()) => res,
-            res = wait_for_signal_unix(SignalKind::hangup()) => res,
-            res = wait_for_signal_unix(SignalKind::terminate()) => res,
-            res = wait_for_signal_unix(SignalKind::quit()) => res,
+    impl Signals {
+        pub fn new() -> io::Result<Self> {
+            Ok(Self {
+                interrupt: signal(SignalKind::interrupt())?,
+                hangup: signal(SignalKind::hangup())?,
+                terminate: signal(SignalKind::terminate())?,
+                quit: signal(SignalKind::quit())?,
+            })
         }
-    }
 
-    /// Wait for first arrival of signal.
-    pub async fn wait_for_signal_unix(kind: signal::unix::SignalKind) -> Result<(), io::Error> {
-        let mut sig_listener = signal::unix::signal(kind)?;
-        if sig_listener.recv().await.is_some() {
-            Ok(())
-        } else {
-            // Use pending() here for the same reason as above.
-            std::future::pending().await
+        /// Wait for the next signal.
+        pub async fn recv(&mut self) {
+            let res = tokio::select! {
+                biased;
+
+                res = self.interrupt.recv() => res,
+                res = self.hangup.recv() => res,
+                res = self.terminate.recv() => res,
+                res = self.quit.recv() => res,
+            };
+
+            if res.is_none() {
+                // The listener can no longer receive any signal.
+                std::future::pending().await
+            }
         }
     }
 
@@ -82,3 +109,39 @@ mod unix {
         Ok(())
     }
 }
+
+#[cfg(windows)]
+mod windows {
+    use super::*;
+    use tokio::signal::windows::{ctrl_break, ctrl_c, CtrlBreak, CtrlC};
+
+    /// Same as the unix `Signals`, for ctrl_c and ctrl_break.
+    pub struct Signals {
+        ctrl_c: CtrlC,
+        ctrl_break: CtrlBreak,
+    }
+
+    impl Signals {
+        pub fn new() -> io::Result<Self> {
+            Ok(Self {
+                ctrl_c: ctrl_c()?,
+                ctrl_break: ctrl_break()?,
+            })
+        }
+
+        /// Wait for the next signal.
+        pub async fn recv(&mut self) {
+            let res = tokio::select! {
+                biased;
+
+                res = self.ctrl_c.recv() => res,
+                res = self.ctrl_break.recv() => res,
+            };
+
+            if res.is_none() {
+                // The listener can no longer receive any signal.
+                std::future::pending().await
+            }
+        }
+    }
+}
@@ -1,56 +1,376 @@
 use std::{
-    io::{self, BufRead, StdinLock, Write},
-    thread,
+    collections::BTreeSet,
+    fmt::Write as _,
+    io::{self, BufRead, IsTerminal, StdinLock, Write},
+    iter, thread,
 };
 
-use binstalk::errors::BinstallError;
+use binstalk::{
+    errors::BinstallError,
+    helpers::remote::CancellationToken,
+    ops::resolve::{format_size, ResolutionFetch, ResolutionSource},
+};
+use compact_str::{CompactString, ToCompactString};
 use tokio::sync::oneshot;
 
-fn ask_for_confirm(stdin: &mut StdinLock, input: &mut String) -> io::Result<()> {
-    {
-        let mut stdout = io::stdout().lock();
+/// A crate about to be installed, shown in the confirmation prompt.
+#[derive(Clone, Debug)]
+pub(crate) struct PendingCrate {
+    pub(crate) name: CompactString,
+    pub(crate) version: CompactString,
+    /// Where it is downloaded from, `cargo install` if it is built from
+    /// source.
+    pub(crate) source: CompactString,
+    pub(crate) third_party: bool,
+    pub(crate) download_size: Option<u64>,
+}
 
-        write!(&mut stdout, "Do you wish to continue? yes/[no]\n? ")?;
-        stdout.flush()?;
+impl PendingCrate {
+    pub(crate) fn from_fetch(fetch: &ResolutionFetch) -> Self {
+        Self {
+            name: fetch.name.clone(),
+            version: fetch.new_version.to_compact_string(),
+            source: fetch.fetcher.source_name(),
+            third_party: fetch.fetcher.is_third_party(),
+            download_size: fetch.download_size,
+        }
     }
 
-    stdin.read_line(input)?;
+    pub(crate) fn from_source(source: &ResolutionSource) -> Self {
+        Self {
+            name: source.name.clone(),
+            version: source.version.clone(),
+            source: "cargo install".into(),
+            third_party: false,
+            download_size: None,
+        }
+    }
+}
 
-    Ok(())
+/// Where the confirmation is asked, abstracted so that it can be scripted
+/// in tests.
+pub(crate) trait Prompt {
+    /// Show `message` to the user and return their answer, `None` at the end
+    /// of the input.
+    fn ask(&mut self, message: &str) -> io::Result<Option<String>>;
 }
 
-pub async fn confirm() -> Result<(), BinstallError> {
-    let (tx, rx) = oneshot::channel();
+struct TerminalPrompt(StdinLock<'static>);
+
+impl Prompt for TerminalPrompt {
+    fn ask(&mut self, message: &str) -> io::Result<Option<String>> {
+        {
+            let mut stdout = io::stdout().lock();
+
+            write!(&mut stdout, "{message}\n? ")?;
+            stdout.flush()?;
+        }
 
-    thread::spawn(move || {
-        // This task should be the only one able to
-        // access stdin
-        let mut stdin = io::stdin().lock();
         let mut input = String::with_capacity(16);
+        Ok((self.0.read_line(&mut input)? != 0).then_some(input))
+    }
+}
 
-        let res = loop {
-            if ask_for_confirm(&mut stdin, &mut input).is_err() {
-                break false;
-            }
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Answer {
+    Yes,
+    No,
+    /// Install this crate and all the following ones.
+    All,
+    /// Install nothing.
+    Quit,
+}
+
+impl Answer {
+    /// Return `None` if the answer is not understood, `all` and `quit` are
+    /// only accepted if `batch`.
+    fn parse(input: &str, batch: bool) -> Option<Self> {
+        Some(match input.trim().to_lowercase().as_str() {
+            "yes" | "y" => Self::Yes,
+            "no" | "n" | "" => Self::No,
+            "all" | "a" if batch => Self::All,
+            "quit" | "q" if batch => Self::Quit,
+            _ => return None,
+        })
+    }
+}
 
-            match input.as_str().trim() {
-                "yes" | "y" | "YES" | "Y" => break true,
-                "no" | "n" | "NO" | "N" | "" => break false,
-                _ => {
-                    input.clear();
-                    continue;
+/// Ask `prompt` until the answer is understood, the end of the input
+/// counts as [`Answer::Quit`].
+fn ask_until_understood(
+    prompt: &mut dyn Prompt,
+    message: &str,
+    batch: bool,
+) -> Result<Answer, BinstallError> {
+    loop {
+        match prompt.ask(message) {
+            Ok(Some(input)) => {
+                if let Some(answer) = Answer::parse(&input, batch) {
+                    break Ok(answer);
                 }
             }
+            Ok(None) | Err(_) => break Ok(Answer::Quit),
+        }
+    }
+}
+
+/// Return the table of the crates shown before asking for confirmation.
+fn format_table(crates: &[PendingCrate]) -> String {
+    const HEADER: [&str; 5] = ["Crate", "Version", "Source", "Third-party", "Size"];
+
+    let rows: Vec<[CompactString; 5]> = crates
+        .iter()
+        .map(|pending| {
+            [
+                pending.name.clone(),
+                pending.version.clone(),
+                pending.source.clone(),
+                if pending.third_party { "yes" } else { "no" }.into(),
+                pending
+                    .download_size
+                    .map_or_else(|| "unknown".into(), |size| format_size(size).into()),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADER.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    let header = HEADER.map(CompactString::from);
+    for row in iter::once(&header).chain(&rows) {
+        let (last, cells) = row.split_last().unwrap();
+        for (cell, width) in cells.iter().zip(widths) {
+            write!(table, "{cell:width$}  ").unwrap();
+        }
+        writeln!(table, "{last}").unwrap();
+    }
+    table
+}
+
+/// Ask which of `crates` to install with `prompt`, return the names of the
+/// declined ones.
+///
+/// If `third_party_only`, only the crates downloaded from a third-party
+/// source are asked about, the others are installed.
+///
+/// Fails with [`BinstallError::UserAbort`] if the user quits or declines
+/// all of them.
+pub(crate) fn ask_crates(
+    prompt: &mut dyn Prompt,
+    crates: &[PendingCrate],
+    third_party_only: bool,
+) -> Result<BTreeSet<CompactString>, BinstallError> {
+    let asked: Vec<_> = crates
+        .iter()
+        .filter(|pending| !third_party_only || pending.third_party)
+        .collect();
+    let mut declined = BTreeSet::new();
+
+    if asked.is_empty() {
+        return Ok(declined);
+    }
+
+    let mut table = Some(format_table(crates));
+    let mut message = |question: String| match table.take() {
+        Some(table) => format!("{table}{question}"),
+        None => question,
+    };
+
+    if crates.len() == 1 {
+        let question = message("Do you wish to continue? yes/[no]".into());
+        return match ask_until_understood(prompt, &question, false)? {
+            Answer::Yes | Answer::All => Ok(declined),
+            Answer::No | Answer::Quit => Err(BinstallError::UserAbort),
         };
+    }
+
+    for pending in asked {
+        let question = message(format!(
+            "Install {} v{}? [y]es/[n]o/[a]ll/[q]uit, default no",
+            pending.name, pending.version
+        ));
+        match ask_until_understood(prompt, &question, true)? {
+            Answer::Yes => (),
+            Answer::No => {
+                declined.insert(pending.name.clone());
+            }
+            Answer::All => break,
+            Answer::Quit => return Err(BinstallError::UserAbort),
+        }
+    }
+
+    if declined.len() == crates.len() {
+        Err(BinstallError::UserAbort)
+    } else {
+        Ok(declined)
+    }
+}
+
+/// Ask the user which of `crates` to install, see [`ask_crates`].
+///
+/// Fails with [`BinstallError::NotInteractive`] if stdin is not a terminal
+/// and there is something to ask, and with [`BinstallError::UserAbort`] if
+/// `cancellation_token` is cancelled in the meantime.
+pub async fn confirm(
+    cancellation_token: &CancellationToken,
+    crates: Vec<PendingCrate>,
+    third_party_only: bool,
+) -> Result<BTreeSet<CompactString>, BinstallError> {
+    if third_party_only && !crates.iter().any(|pending| pending.third_party) {
+        return Ok(BTreeSet::new());
+    }
+
+    if !io::stdin().is_terminal() {
+        return Err(BinstallError::NotInteractive);
+    }
+
+    let (tx, rx) = oneshot::channel();
+
+    thread::spawn(move || {
+        // This task should be the only one able to
+        // access stdin
+        let mut prompt = TerminalPrompt(io::stdin().lock());
+        let res = ask_crates(&mut prompt, &crates, third_party_only);
 
         // The main thread might be terminated by signal and thus cancelled
         // the confirmation.
         tx.send(res).ok();
     });
 
-    if rx.await.unwrap() {
-        Ok(())
-    } else {
-        Err(BinstallError::UserAbort)
+    tokio::select! {
+        biased;
+
+        () = cancellation_token.cancelled() => Err(BinstallError::UserAbort),
+        res = rx => res.unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Answers the questions with the scripted inputs, and records them.
+    struct ScriptedPrompt {
+        inputs: VecDeque<&'static str>,
+        messages: Vec<String>,
+    }
+
+    impl ScriptedPrompt {
+        fn new(inputs: &[&'static str]) -> Self {
+            Self {
+                inputs: inputs.iter().copied().collect(),
+                messages: Vec::new(),
+            }
+        }
+    }
+
+    impl Prompt for ScriptedPrompt {
+        fn ask(&mut self, message: &str) -> io::Result<Option<String>> {
+            self.messages.push(message.to_owned());
+            Ok(self.inputs.pop_front().map(|input| format!("{input}\n")))
+        }
+    }
+
+    fn pending(name: &str, third_party: bool) -> PendingCrate {
+        PendingCrate {
+            name: name.into(),
+            version: "1.0.0".into(),
+            source: if third_party {
+                "QuickInstall"
+            } else {
+                "github.com"
+            }
+            .into(),
+            third_party,
+            download_size: third_party.then_some(1_500_000),
+        }
+    }
+
+    fn declined(names: &[&str]) -> BTreeSet<CompactString> {
+        names.iter().copied().map(CompactString::from).collect()
+    }
+
+    #[test]
+    fn single_crate() {
+        let crates = [pending("a", false)];
+
+        let mut prompt = ScriptedPrompt::new(&["maybe", "Y"]);
+        assert_eq!(
+            ask_crates(&mut prompt, &crates, false).unwrap(),
+            declined(&[])
+        );
+        assert_eq!(prompt.messages.len(), 2);
+        assert_eq!(
+            prompt.messages[0],
+            "\
+Crate  Version  Source      Third-party  Size
+a      1.0.0    github.com  no           unknown
+Do you wish to continue? yes/[no]"
+        );
+        assert_eq!(prompt.messages[1], prompt.messages[0]);
+
+        for inputs in [&[""][..], &["a"], &[]] {
+            let mut prompt = ScriptedPrompt::new(inputs);
+            let err = ask_crates(&mut prompt, &crates, false).unwrap_err();
+            assert!(matches!(err, BinstallError::UserAbort), "{err}");
+        }
+    }
+
+    #[test]
+    fn per_crate() {
+        let crates = [pending("a", false), pending("b", true), pending("c", false)];
+
+        let mut prompt = ScriptedPrompt::new(&["y", "no", "y"]);
+        assert_eq!(
+            ask_crates(&mut prompt, &crates, false).unwrap(),
+            declined(&["b"])
+        );
+        assert!(
+            prompt.messages[0].ends_with("Install a v1.0.0? [y]es/[n]o/[a]ll/[q]uit, default no")
+        );
+        assert!(prompt.messages[0].contains("b      1.0.0    QuickInstall  yes          1.5 MB\n"));
+
+        // The rest are installed.
+        let mut prompt = ScriptedPrompt::new(&["n", "a"]);
+        assert_eq!(
+            ask_crates(&mut prompt, &crates, false).unwrap(),
+            declined(&["a"])
+        );
+        assert_eq!(prompt.messages.len(), 2);
+
+        for inputs in [&["y", "q"][..], &["n", "n", "n"], &["y"]] {
+            let mut prompt = ScriptedPrompt::new(inputs);
+            let err = ask_crates(&mut prompt, &crates, false).unwrap_err();
+            assert!(matches!(err, BinstallError::UserAbort), "{err}");
+        }
+    }
+
+    #[test]
+    fn third_party_only() {
+        let crates = [pending("a", false), pending("b", true)];
+
+        let mut prompt = ScriptedPrompt::new(&["n"]);
+        assert_eq!(
+            ask_crates(&mut prompt, &crates, true).unwrap(),
+            declined(&["b"])
+        );
+        assert_eq!(prompt.messages.len(), 1);
+        assert!(
+            prompt.messages[0].ends_with("Install b v1.0.0? [y]es/[n]o/[a]ll/[q]uit, default no")
+        );
+
+        let mut prompt = ScriptedPrompt::new(&[]);
+        assert_eq!(
+            ask_crates(&mut prompt, &crates[..1], true).unwrap(),
+            declined(&[])
+        );
+        assert!(prompt.messages.is_empty());
     }
 }
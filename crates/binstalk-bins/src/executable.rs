@@ -0,0 +1,700 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Number of bytes read to sniff a file, enough to find the PE header of
+/// the binaries produced by the usual linkers.
+const HEAD_LEN: usize = 1024;
+
+/// Type of the ELF section listing the symbol versions needed from shared
+/// libraries.
+const SHT_GNU_VERNEED: u32 = 0x6fff_fffe;
+
+/// Maximum size of the ELF sections read, in case the headers are corrupted.
+const MAX_SECTION_LEN: u64 = 16 * 1024 * 1024;
+
+/// The kind of a file, sniffed from its first bytes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FileKind {
+    Elf {
+        machine: u16,
+    },
+    MachO {
+        cpu_type: u32,
+    },
+    /// A Mach-O binary for multiple architectures, `archs` has the bits
+    /// of the [`ARCHS`] it has a slice for.
+    UniversalMachO {
+        archs: u16,
+    },
+    /// `machine` is `None` if the PE header is too far in the file.
+    Pe {
+        machine: Option<u16>,
+    },
+    Wasm,
+    /// A file starting with a shebang.
+    Script,
+    Empty,
+    Other,
+}
+
+/// Architectures binaries are checked against, with their ids in ELF,
+/// Mach-O and PE headers.
+struct Arch {
+    name: &'static str,
+    elf: u16,
+    mach_o: Option<u32>,
+    pe: Option<u16>,
+}
+
+const ARCHS: &[Arch] = &[
+    Arch {
+        name: "x86_64",
+        elf: 62,
+        mach_o: Some(0x0100_0007),
+        pe: Some(0x8664),
+    },
+    Arch {
+        name: "x86",
+        elf: 3,
+        mach_o: Some(7),
+        pe: Some(0x14c),
+    },
+    Arch {
+        name: "aarch64",
+        elf: 183,
+        mach_o: Some(0x0100_000c),
+        pe: Some(0xaa64),
+    },
+    Arch {
+        name: "arm",
+        elf: 40,
+        mach_o: None,
+        pe: Some(0x1c4),
+    },
+    Arch {
+        name: "riscv",
+        elf: 243,
+        mach_o: None,
+        pe: None,
+    },
+    Arch {
+        name: "powerpc",
+        elf: 20,
+        mach_o: None,
+        pe: None,
+    },
+    Arch {
+        name: "powerpc64",
+        elf: 21,
+        mach_o: None,
+        pe: None,
+    },
+    Arch {
+        name: "s390x",
+        elf: 22,
+        mach_o: None,
+        pe: None,
+    },
+    Arch {
+        name: "mips",
+        elf: 8,
+        mach_o: None,
+        pe: None,
+    },
+    Arch {
+        name: "loongarch64",
+        elf: 258,
+        mach_o: None,
+        pe: None,
+    },
+];
+
+/// Return the bit of `arch` in [`FileKind::UniversalMachO::archs`].
+fn arch_bit(arch: &Arch) -> u16 {
+    let index = ARCHS
+        .iter()
+        .position(|known| known.name == arch.name)
+        .unwrap();
+    1 << index
+}
+
+/// Return the bits of the [`ARCHS`] a fat Mach-O binary has a slice for,
+/// from its header in `head`.
+fn fat_archs(head: &[u8], entry_len: usize) -> u16 {
+    let count = read_u32(head, 4, true).unwrap_or(0) as usize;
+    (0..count)
+        .map_while(|i| read_u32(head, 8 + i * entry_len, true))
+        .filter_map(|cpu_type| ARCHS.iter().find(|arch| arch.mach_o == Some(cpu_type)))
+        .fold(0, |archs, arch| archs | arch_bit(arch))
+}
+
+/// Return the architecture of `target`, if it is one of [`ARCHS`].
+fn target_arch(target: &str) -> Option<&'static Arch> {
+    let arch = target.split('-').next()?;
+    let name = match arch {
+        "i386" | "i586" | "i686" => "x86",
+        "powerpc64" | "powerpc64le" => "powerpc64",
+        _ if arch.starts_with("aarch64") || arch == "arm64ec" => "aarch64",
+        _ if arch.starts_with("arm") || arch.starts_with("thumb") => "arm",
+        _ if arch.starts_with("riscv") => "riscv",
+        _ if arch.starts_with("mips") => "mips",
+        _ => arch,
+    };
+    ARCHS.iter().find(|known| known.name == name)
+}
+
+fn read_u16(head: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = head.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn read_u32(head: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = head.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn read_u64(head: &[u8], offset: usize, big_endian: bool) -> Option<u64> {
+    let bytes = head.get(offset..offset + 8)?.try_into().ok()?;
+    Some(if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    })
+}
+
+/// Read `len` bytes of `file` at `offset`, `None` if it is too short or
+/// `len` is above [`MAX_SECTION_LEN`].
+fn read_at(file: &mut File, offset: u64, len: u64) -> io::Result<Option<Vec<u8>>> {
+    if len > MAX_SECTION_LEN {
+        return Ok(None);
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut buf)?;
+
+    Ok((buf.len() as u64 == len).then_some(buf))
+}
+
+/// Header of an ELF section.
+struct Section {
+    kind: u32,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+}
+
+/// Return the newest glibc version the ELF binary at `path` needs, as
+/// `(major, minor)`, from the `GLIBC_x.y` versions of its dynamic symbols.
+///
+/// Return `None` if it is not an ELF binary or does not need glibc, e.g. if
+/// it is static or linked to musl.
+pub fn required_glibc_version(path: &Path) -> io::Result<Option<(u32, u32)>> {
+    let mut file = File::open(path)?;
+
+    let Some(header) = read_at(&mut file, 0, 64)? else {
+        return Ok(None);
+    };
+    if !header.starts_with(b"\x7fELF") {
+        return Ok(None);
+    }
+    let is_64 = header[4] == 2;
+    let big_endian = header[5] == 2;
+
+    let (shoff, shentsize, shnum) = if is_64 {
+        (
+            read_u64(&header, 0x28, big_endian),
+            read_u16(&header, 0x3a, big_endian),
+            read_u16(&header, 0x3c, big_endian),
+        )
+    } else {
+        (
+            read_u32(&header, 0x20, big_endian).map(u64::from),
+            read_u16(&header, 0x2e, big_endian),
+            read_u16(&header, 0x30, big_endian),
+        )
+    };
+    let (Some(shoff), Some(shentsize), Some(shnum)) = (shoff, shentsize, shnum) else {
+        return Ok(None);
+    };
+    let shentsize = usize::from(shentsize);
+
+    let Some(headers) = read_at(&mut file, shoff, (shentsize * usize::from(shnum)) as u64)? else {
+        return Ok(None);
+    };
+    let section = |index: usize| {
+        let header = headers.get(index * shentsize..)?.get(..shentsize)?;
+        Some(if is_64 {
+            Section {
+                kind: read_u32(header, 4, big_endian)?,
+                offset: read_u64(header, 24, big_endian)?,
+                size: read_u64(header, 32, big_endian)?,
+                link: read_u32(header, 40, big_endian)?,
+                info: read_u32(header, 44, big_endian)?,
+            }
+        } else {
+            Section {
+                kind: read_u32(header, 4, big_endian)?,
+                offset: read_u32(header, 16, big_endian)?.into(),
+                size: read_u32(header, 20, big_endian)?.into(),
+                link: read_u32(header, 24, big_endian)?,
+                info: read_u32(header, 28, big_endian)?,
+            }
+        })
+    };
+
+    let Some(verneed) = (0..usize::from(shnum))
+        .filter_map(section)
+        .find(|section| section.kind == SHT_GNU_VERNEED)
+    else {
+        return Ok(None);
+    };
+    // The names of the versions are in the string table it links to.
+    let Some(strtab) = section(verneed.link as usize) else {
+        return Ok(None);
+    };
+    let (Some(entries), Some(strings)) = (
+        read_at(&mut file, verneed.offset, verneed.size)?,
+        read_at(&mut file, strtab.offset, strtab.size)?,
+    ) else {
+        return Ok(None);
+    };
+
+    let version_name = |offset: u32| {
+        let name = strings.get(offset as usize..)?;
+        name.get(..name.iter().position(|b| *b == 0)?)
+    };
+
+    let mut required = None;
+    // Each `Elf_Verneed` entry lists the versions needed from a library
+    // in its `Elf_Vernaux` entries.
+    let mut offset = 0;
+    for _ in 0..verneed.info {
+        let Some(entry) = entries.get(offset..) else {
+            break;
+        };
+        let (Some(count), Some(aux), Some(next)) = (
+            read_u16(entry, 2, big_endian),
+            read_u32(entry, 8, big_endian),
+            read_u32(entry, 12, big_endian),
+        ) else {
+            break;
+        };
+
+        let mut aux_offset = offset + aux as usize;
+        for _ in 0..count {
+            let Some(aux_entry) = entries.get(aux_offset..) else {
+                break;
+            };
+            let (Some(name), Some(aux_next)) = (
+                read_u32(aux_entry, 8, big_endian),
+                read_u32(aux_entry, 12, big_endian),
+            ) else {
+                break;
+            };
+
+            required = required.max(version_name(name).and_then(parse_glibc_version));
+
+            if aux_next == 0 {
+                break;
+            }
+            aux_offset += aux_next as usize;
+        }
+
+        if next == 0 {
+            break;
+        }
+        offset += next as usize;
+    }
+
+    Ok(required)
+}
+
+/// Parse a symbol version such as `GLIBC_2.2.5` into `(2, 2)`.
+fn parse_glibc_version(name: &[u8]) -> Option<(u32, u32)> {
+    let version = std::str::from_utf8(name.strip_prefix(b"GLIBC_")?).ok()?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map_or(Ok(0), str::parse).ok()?;
+    Some((major, minor))
+}
+
+impl FileKind {
+    /// Sniff the kind of a file from `head`, its first bytes.
+    pub fn sniff(head: &[u8]) -> Self {
+        if head.is_empty() {
+            Self::Empty
+        } else if head.starts_with(b"\x7fELF") {
+            // `EI_DATA` is 2 for big endian.
+            let big_endian = head.get(5) == Some(&2);
+            match read_u16(head, 18, big_endian) {
+                Some(machine) => Self::Elf { machine },
+                None => Self::Other,
+            }
+        } else if head.starts_with(&[0xcf, 0xfa, 0xed, 0xfe])
+            || head.starts_with(&[0xce, 0xfa, 0xed, 0xfe])
+        {
+            match read_u32(head, 4, false) {
+                Some(cpu_type) => Self::MachO { cpu_type },
+                None => Self::Other,
+            }
+        } else if head.starts_with(&[0xca, 0xfe, 0xba, 0xbe]) {
+            Self::UniversalMachO {
+                archs: fat_archs(head, 20),
+            }
+        } else if head.starts_with(&[0xca, 0xfe, 0xba, 0xbf]) {
+            // With 64-bit offsets.
+            Self::UniversalMachO {
+                archs: fat_archs(head, 32),
+            }
+        } else if head.starts_with(b"MZ") {
+            let machine = read_u32(head, 0x3c, false).and_then(|offset| {
+                let offset = usize::try_from(offset).ok()?;
+                if head.get(offset..offset + 4)? != b"PE\0\0" {
+                    return None;
+                }
+                read_u16(head, offset + 4, false)
+            });
+            Self::Pe { machine }
+        } else if head.starts_with(b"\0asm") {
+            Self::Wasm
+        } else if head.starts_with(b"#!") {
+            Self::Script
+        } else {
+            Self::Other
+        }
+    }
+
+    /// Sniff the kind of the file at `path`.
+    pub fn sniff_file(path: &Path) -> io::Result<Self> {
+        let mut head = Vec::with_capacity(HEAD_LEN);
+        File::open(path)?
+            .take(HEAD_LEN as u64)
+            .read_to_end(&mut head)?;
+        Ok(Self::sniff(&head))
+    }
+
+    /// Return true if it is an executable, for any target.
+    pub fn is_executable(self) -> bool {
+        !matches!(self, Self::Empty | Self::Other)
+    }
+
+    /// Return true if it can be run on `target`.
+    ///
+    /// Only the format and the architecture are checked, binaries for
+    /// unknown architectures are assumed to be fine.
+    pub fn is_executable_for(self, target: &str) -> bool {
+        let arch = target_arch(target);
+
+        if target.contains("windows") {
+            match self {
+                Self::Pe { machine } => arch
+                    .and_then(|arch| arch.pe)
+                    .zip(machine)
+                    .map_or(true, |(pe, machine)| pe == machine),
+                _ => false,
+            }
+        } else if target.starts_with("wasm") {
+            self == Self::Wasm
+        } else if target.contains("apple") {
+            match self {
+                Self::MachO { cpu_type } => arch
+                    .and_then(|arch| arch.mach_o)
+                    .map_or(true, |mach_o| mach_o == cpu_type),
+                // It needs a slice for the architecture, which is unknown
+                // for the `universal-apple-darwin` target itself.
+                Self::UniversalMachO { archs } => {
+                    arch.map_or(true, |arch| archs & arch_bit(arch) != 0)
+                }
+                Self::Script => true,
+                _ => false,
+            }
+        } else {
+            match self {
+                Self::Elf { machine } => arch.map_or(true, |arch| arch.elf == machine),
+                Self::Script => true,
+                _ => false,
+            }
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arch_name = |id: &dyn Fn(&Arch) -> bool| {
+            ARCHS
+                .iter()
+                .find(|arch| id(arch))
+                .map(|arch| arch.name)
+                .unwrap_or("an unknown architecture")
+        };
+
+        match *self {
+            Self::Elf { machine } => write!(
+                f,
+                "an ELF binary for {}",
+                arch_name(&|arch| arch.elf == machine)
+            ),
+            Self::MachO { cpu_type } => write!(
+                f,
+                "a Mach-O binary for {}",
+                arch_name(&|arch| arch.mach_o == Some(cpu_type))
+            ),
+            Self::UniversalMachO { archs: 0 } => f.write_str("a universal Mach-O binary"),
+            Self::UniversalMachO { archs } => write!(
+                f,
+                "a universal Mach-O binary for {}",
+                ARCHS
+                    .iter()
+                    .filter(|arch| archs & arch_bit(arch) != 0)
+                    .map(|arch| arch.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::Pe { machine: None } => f.write_str("a PE binary"),
+            Self::Pe {
+                machine: Some(machine),
+            } => write!(
+                f,
+                "a PE binary for {}",
+                arch_name(&|arch| arch.pe == Some(machine))
+            ),
+            Self::Wasm => f.write_str("a WebAssembly module"),
+            Self::Script => f.write_str("a script"),
+            Self::Empty => f.write_str("an empty file"),
+            Self::Other => f.write_str("not an executable"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn elf(machine: u16) -> Vec<u8> {
+        let mut head = b"\x7fELF\x02\x01\x01".to_vec();
+        head.resize(18, 0);
+        head.extend(machine.to_le_bytes());
+        head
+    }
+
+    fn mach_o(cpu_type: u32) -> Vec<u8> {
+        let mut head = vec![0xcf, 0xfa, 0xed, 0xfe];
+        head.extend(cpu_type.to_le_bytes());
+        head
+    }
+
+    fn fat(cpu_types: &[u32]) -> Vec<u8> {
+        let mut head = vec![0xca, 0xfe, 0xba, 0xbe];
+        head.extend((cpu_types.len() as u32).to_be_bytes());
+        for cpu_type in cpu_types {
+            head.extend(cpu_type.to_be_bytes());
+            head.extend([0; 16]);
+        }
+        head
+    }
+
+    fn pe(machine: u16) -> Vec<u8> {
+        let mut head = b"MZ".to_vec();
+        head.resize(0x3c, 0);
+        head.extend(0x80_u32.to_le_bytes());
+        head.resize(0x80, 0);
+        head.extend(b"PE\0\0");
+        head.extend(machine.to_le_bytes());
+        head
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(FileKind::sniff(&elf(62)), FileKind::Elf { machine: 62 });
+        assert_eq!(
+            FileKind::sniff(&mach_o(0x0100_000c)),
+            FileKind::MachO {
+                cpu_type: 0x0100_000c
+            }
+        );
+        assert_eq!(
+            FileKind::sniff(&pe(0x8664)),
+            FileKind::Pe {
+                machine: Some(0x8664)
+            }
+        );
+        assert_eq!(FileKind::sniff(b"\0asm\x01\0\0\0"), FileKind::Wasm);
+        assert_eq!(FileKind::sniff(b"#!/bin/sh\n"), FileKind::Script);
+        assert_eq!(FileKind::sniff(b""), FileKind::Empty);
+        assert_eq!(FileKind::sniff(b"version https://git-lfs"), FileKind::Other);
+
+        // Big endian
+        let mut head = b"\x7fELF\x02\x02\x01".to_vec();
+        head.resize(18, 0);
+        head.extend(22_u16.to_be_bytes());
+        assert_eq!(FileKind::sniff(&head), FileKind::Elf { machine: 22 });
+    }
+
+    #[test]
+    fn test_is_executable_for() {
+        let check = |head: &[u8], target| FileKind::sniff(head).is_executable_for(target);
+
+        assert!(check(&elf(62), "x86_64-unknown-linux-gnu"));
+        assert!(check(&elf(62), "x86_64-unknown-linux-musl"));
+        assert!(!check(&elf(183), "x86_64-unknown-linux-gnu"));
+        assert!(check(&elf(183), "aarch64-unknown-linux-gnu"));
+        assert!(check(&elf(40), "armv7-unknown-linux-gnueabihf"));
+        assert!(check(&elf(3), "i686-unknown-linux-gnu"));
+        // Unknown architecture
+        assert!(check(&elf(1234), "sparc64-unknown-linux-gnu"));
+
+        assert!(check(&mach_o(0x0100_000c), "aarch64-apple-darwin"));
+        assert!(!check(&mach_o(0x0100_0007), "aarch64-apple-darwin"));
+        assert!(check(&[0xca, 0xfe, 0xba, 0xbe], "universal-apple-darwin"));
+        let universal = fat(&[0x0100_0007, 0x0100_000c]);
+        assert!(check(&universal, "universal-apple-darwin"));
+        assert!(check(&universal, "aarch64-apple-darwin"));
+        assert!(check(&universal, "x86_64-apple-darwin"));
+        // No slice for Apple silicon.
+        assert!(!check(&fat(&[0x0100_0007]), "aarch64-apple-darwin"));
+        assert!(!check(&elf(62), "x86_64-apple-darwin"));
+
+        assert!(check(&pe(0x8664), "x86_64-pc-windows-msvc"));
+        assert!(!check(&pe(0xaa64), "x86_64-pc-windows-msvc"));
+        assert!(check(b"MZ", "x86_64-pc-windows-gnu"));
+        assert!(!check(b"#!/bin/sh\n", "x86_64-pc-windows-msvc"));
+
+        assert!(check(b"\0asm\x01\0\0\0", "wasm32-wasi"));
+        assert!(!check(&elf(62), "wasm32-wasi"));
+
+        assert!(check(b"#!/bin/sh\n", "x86_64-unknown-linux-gnu"));
+        assert!(!check(b"<!DOCTYPE html>", "x86_64-unknown-linux-gnu"));
+        assert!(!check(b"", "x86_64-unknown-linux-gnu"));
+    }
+
+    /// Build a 64-bit little endian ELF needing the symbol versions `names`
+    /// from libc.
+    fn elf_needing(names: &[&str]) -> Vec<u8> {
+        let mut strings = b"\0libc.so.6\0".to_vec();
+        let name_offsets: Vec<_> = names
+            .iter()
+            .map(|name| {
+                let offset = strings.len() as u32;
+                strings.extend(name.as_bytes());
+                strings.push(0);
+                offset
+            })
+            .collect();
+
+        let mut verneed = Vec::new();
+        verneed.extend(1_u16.to_le_bytes());
+        verneed.extend((names.len() as u16).to_le_bytes());
+        verneed.extend(1_u32.to_le_bytes());
+        verneed.extend(16_u32.to_le_bytes());
+        verneed.extend(0_u32.to_le_bytes());
+        for (i, name) in name_offsets.iter().enumerate() {
+            verneed.extend(0_u32.to_le_bytes());
+            verneed.extend(0_u32.to_le_bytes());
+            verneed.extend(name.to_le_bytes());
+            let next: u32 = if i + 1 == names.len() { 0 } else { 16 };
+            verneed.extend(next.to_le_bytes());
+        }
+
+        let strtab_offset = 64_u64;
+        let verneed_offset = strtab_offset + strings.len() as u64;
+        let shoff = verneed_offset + verneed.len() as u64;
+
+        let section = |kind: u32, offset: u64, size: usize, link: u32, info: u32| {
+            let mut header = Vec::new();
+            header.extend(0_u32.to_le_bytes());
+            header.extend(kind.to_le_bytes());
+            header.resize(24, 0);
+            header.extend(offset.to_le_bytes());
+            header.extend((size as u64).to_le_bytes());
+            header.extend(link.to_le_bytes());
+            header.extend(info.to_le_bytes());
+            header.resize(64, 0);
+            header
+        };
+
+        let mut elf = elf(62);
+        elf.resize(0x28, 0);
+        elf.extend(shoff.to_le_bytes());
+        elf.resize(0x3a, 0);
+        elf.extend(64_u16.to_le_bytes());
+        elf.extend(3_u16.to_le_bytes());
+        elf.resize(64, 0);
+        elf.extend(strings.iter().copied());
+        elf.extend(verneed.iter().copied());
+        elf.extend(section(0, 0, 0, 0, 0));
+        elf.extend(section(3, strtab_offset, strings.len(), 0, 0));
+        elf.extend(section(
+            SHT_GNU_VERNEED,
+            verneed_offset,
+            verneed.len(),
+            1,
+            1,
+        ));
+        elf
+    }
+
+    #[test]
+    fn test_required_glibc_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let required = |content: &[u8]| {
+            let path = dir.path().join("bin");
+            std::fs::write(&path, content).unwrap();
+            required_glibc_version(&path).unwrap()
+        };
+
+        assert_eq!(
+            required(&elf_needing(&[
+                "GLIBC_2.2.5",
+                "GLIBC_2.34",
+                "GLIBC_PRIVATE",
+                "GLIBC_2.17"
+            ])),
+            Some((2, 34))
+        );
+        // Static or musl binaries.
+        assert_eq!(required(&elf_needing(&[])), None);
+        assert_eq!(required(&elf(62)), None);
+        assert_eq!(required(b"#!/bin/sh\n"), None);
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    #[test]
+    fn test_required_glibc_version_of_self() {
+        let exe = std::env::current_exe().unwrap();
+        assert!(matches!(
+            required_glibc_version(&exe).unwrap(),
+            Some((2, _))
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            FileKind::sniff(&elf(183)).to_string(),
+            "an ELF binary for aarch64"
+        );
+        assert_eq!(
+            FileKind::sniff(&pe(0x1234)).to_string(),
+            "a PE binary for an unknown architecture"
+        );
+        assert_eq!(FileKind::sniff(b"text").to_string(), "not an executable");
+        assert_eq!(
+            FileKind::sniff(&fat(&[0x0100_0007, 0x0100_000c])).to_string(),
+            "a universal Mach-O binary for x86_64, aarch64"
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    ffi::OsStr,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use atomic_file_install::atomic_install;
+use leon::Template;
+use normalize_path::NormalizePath;
+use tracing::debug;
+
+use crate::{is_valid_path, Context, Data, Error};
+
+/// Shells whose completions are installed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub const ALL: &'static [Self] = &[Self::Bash, Self::Zsh, Self::Fish];
+
+    /// Return the shell `file_name` is a completion script for, going by
+    /// the names each of them loads completions by.
+    fn of_completion(file_name: &str) -> Option<Self> {
+        if file_name.ends_with(".fish") {
+            Some(Self::Fish)
+        } else if file_name.starts_with('_') && !file_name.contains('.') {
+            Some(Self::Zsh)
+        } else if file_name.ends_with(".bash") || !file_name.contains('.') {
+            Some(Self::Bash)
+        } else {
+            None
+        }
+    }
+
+    /// Directory its completions are installed to, relative to the data dir.
+    fn completions_dir(self) -> &'static str {
+        match self {
+            Self::Bash => "bash-completion/completions",
+            Self::Zsh => "zsh/site-functions",
+            Self::Fish => "fish/vendor_completions.d",
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+        })
+    }
+}
+
+/// Kind of auxiliary files shipped in a package along with the binaries.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ExtraFileKind {
+    /// Shell completions for bash, zsh and fish.
+    Completions,
+    /// Man pages.
+    Man,
+}
+
+impl ExtraFileKind {
+    /// Return destination of `file_name` relative to the data dir,
+    /// or `None` if the file is not recognized or is a completion for
+    /// none of `shells`.
+    fn dest(self, file_name: &str, shells: &[Shell]) -> Option<PathBuf> {
+        match self {
+            Self::Completions => match Shell::of_completion(file_name)? {
+                shell if !shells.contains(&shell) => None,
+                // bash-completion loads completions by the command name
+                Shell::Bash => Some(
+                    Path::new(Shell::Bash.completions_dir())
+                        .join(file_name.strip_suffix(".bash").unwrap_or(file_name)),
+                ),
+                shell => Some(Path::new(shell.completions_dir()).join(file_name)),
+            },
+            Self::Man => {
+                let name = file_name.strip_suffix(".gz").unwrap_or(file_name);
+                let (_, section) = name.rsplit_once('.')?;
+                let section = section.chars().next().filter(char::is_ascii_digit)?;
+
+                Some(
+                    Path::new("man")
+                        .join(format!("man{section}"))
+                        .join(file_name),
+                )
+            }
+        }
+    }
+
+    /// Return the kind of `dest` installed into `data_dir`, along with the
+    /// shell for completions, or `None` if it is not installed there.
+    pub fn of_dest(dest: &Path, data_dir: &Path) -> Option<(Self, Option<Shell>)> {
+        let dest = dest.strip_prefix(data_dir).ok()?;
+
+        if dest.starts_with("man") {
+            Some((Self::Man, None))
+        } else {
+            Shell::ALL
+                .iter()
+                .find(|shell| dest.starts_with(shell.completions_dir()))
+                .map(|shell| (Self::Completions, Some(*shell)))
+        }
+    }
+
+    /// Return true if `file_name` is the conventional name of a file of
+    /// this kind for one of `bins`: `<bin>.bash`, `_<bin>` and `<bin>.fish`
+    /// for completions, `<bin>.<section>` optionally gzipped for man pages.
+    ///
+    /// Used to find them in packages which do not specify where they are,
+    /// so anything else, e.g. a completion named after the crate, is not
+    /// recognized.
+    pub fn is_conventional(self, file_name: &str, bins: &[impl AsRef<str>]) -> bool {
+        bins.iter().map(AsRef::as_ref).any(|bin| match self {
+            Self::Completions => {
+                file_name.strip_prefix('_') == Some(bin)
+                    || file_name.strip_suffix(".bash") == Some(bin)
+                    || file_name.strip_suffix(".fish") == Some(bin)
+            }
+            Self::Man => file_name
+                .strip_suffix(".gz")
+                .unwrap_or(file_name)
+                .strip_prefix(bin)
+                .and_then(|section| section.strip_prefix('.'))
+                .map_or(false, |section| matches!(section.as_bytes(), [b'1'..=b'9'])),
+        })
+    }
+}
+
+impl fmt::Display for ExtraFileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Completions => "completions",
+            Self::Man => "man page",
+        })
+    }
+}
+
+pub struct ExtraFile {
+    pub kind: ExtraFileKind,
+    pub source: PathBuf,
+    pub dest: PathBuf,
+}
+
+impl ExtraFile {
+    /// Collect recognized files of `kind` in the directory rendered
+    /// from `tt`.
+    ///
+    ///  * `shells` - the shells to collect completions for
+    ///  * `data_dir` - usually `$XDG_DATA_HOME` or `$prefix/share`
+    ///  * `list_files` - return names of regular files in the directory,
+    ///    which is relative to the extracted archive, or `None` if it is
+    ///    not a directory.
+    pub fn collect(
+        data: &Data<'_>,
+        kind: ExtraFileKind,
+        tt: &Template<'_>,
+        shells: &[Shell],
+        data_dir: &Path,
+        list_files: &mut dyn FnMut(&Path) -> Option<Vec<String>>,
+    ) -> Result<Vec<Self>, Error> {
+        let dir = Self::source_dir(data, tt)?;
+
+        let Some(mut file_names) = list_files(&dir) else {
+            debug!("{kind} dir {} not found", dir.display());
+            return Ok(Vec::new());
+        };
+        file_names.sort_unstable();
+
+        Ok(file_names
+            .into_iter()
+            .filter_map(|file_name| {
+                let Some(dest) = kind.dest(&file_name, shells) else {
+                    debug!("Ignoring unrecognized {kind} file {file_name}");
+                    return None;
+                };
+
+                Some(Self {
+                    kind,
+                    source: data.bin_path.join(&dir).join(&file_name),
+                    dest: data_dir.join(dest),
+                })
+            })
+            .collect())
+    }
+
+    /// Find the files of `kind` with a [conventional name](ExtraFileKind::is_conventional)
+    /// for `bins` anywhere in the package, for packages which do not specify
+    /// where they are.
+    ///
+    /// If several files would be installed to the same destination, the
+    /// least nested one is.
+    ///
+    ///  * `files` - paths of the regular files extracted to `bin_path`,
+    ///    relative to it
+    pub fn detect<'a>(
+        kind: ExtraFileKind,
+        bins: &[impl AsRef<str>],
+        shells: &[Shell],
+        bin_path: &Path,
+        data_dir: &Path,
+        files: impl IntoIterator<Item = &'a Path>,
+    ) -> Vec<Self> {
+        let mut files: Vec<(&Path, &str)> = files
+            .into_iter()
+            .filter_map(|path| Some((path, path.file_name().and_then(OsStr::to_str)?)))
+            .filter(|(_, file_name)| kind.is_conventional(file_name, bins))
+            .collect();
+        files.sort_unstable_by_key(|(path, _)| (path.components().count(), *path));
+
+        let mut detected: Vec<Self> = Vec::new();
+        for (path, file_name) in files {
+            let Some(dest) = kind.dest(file_name, shells) else {
+                continue;
+            };
+            let dest = data_dir.join(dest);
+
+            if detected.iter().any(|file| file.dest == dest) {
+                debug!(
+                    "Ignoring {kind} '{}', another one is installed to '{}'",
+                    path.display(),
+                    dest.display()
+                );
+                continue;
+            }
+
+            debug!("Found {kind} '{}'", path.display());
+            detected.push(Self {
+                kind,
+                source: bin_path.join(path),
+                dest,
+            });
+        }
+        detected
+    }
+
+    /// Return the directory rendered from `tt`, relative to the extracted
+    /// archive, which is `.` for the top-level.
+    pub fn source_dir(data: &Data<'_>, tt: &Template<'_>) -> Result<PathBuf, Error> {
+        let dir = tt.render(&Context::new(data, data.name))?;
+        let dir = Path::new(&dir).normalize();
+
+        if !is_valid_path(&dir) {
+            return Err(Error::InvalidSourceFilePath(dir.into()));
+        }
+
+        if dir.components().next().is_none() {
+            Ok(PathBuf::from("."))
+        } else {
+            Ok(dir)
+        }
+    }
+
+    pub fn preview(&self) -> impl fmt::Display + '_ {
+        LazyFormat(self)
+    }
+
+    /// Return true if a file already exists at `dest`.
+    pub fn dest_exists(&self) -> bool {
+        fs::symlink_metadata(&self.dest).is_ok()
+    }
+
+    /// Install the file, creating the destination dir if it does not exist.
+    pub fn install(&self) -> Result<(), Error> {
+        if let Some(parent) = self.dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        debug!(
+            "Atomically install {} from '{}' to '{}'",
+            self.kind,
+            self.source.display(),
+            self.dest.display()
+        );
+
+        atomic_install(&self.source, &self.dest)?;
+
+        Ok(())
+    }
+}
+
+struct LazyFormat<'a>(&'a ExtraFile);
+
+impl fmt::Display for LazyFormat<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.0;
+        write!(
+            f,
+            "{} ({} -> {})",
+            file.kind,
+            Path::new(file.source.file_name().unwrap()).display(),
+            file.dest.display()
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extra_file_dest() {
+        use ExtraFileKind::*;
+
+        let dest = |kind: ExtraFileKind, file_name| kind.dest(file_name, Shell::ALL);
+
+        assert_eq!(
+            dest(Completions, "foo.bash").unwrap(),
+            Path::new("bash-completion/completions/foo")
+        );
+        assert_eq!(
+            dest(Completions, "foo").unwrap(),
+            Path::new("bash-completion/completions/foo")
+        );
+        assert_eq!(
+            dest(Completions, "_foo").unwrap(),
+            Path::new("zsh/site-functions/_foo")
+        );
+        assert_eq!(
+            dest(Completions, "foo.fish").unwrap(),
+            Path::new("fish/vendor_completions.d/foo.fish")
+        );
+        assert_eq!(dest(Completions, "_foo.ps1"), None);
+        assert_eq!(
+            Completions.dest("foo.bash", &[Shell::Zsh, Shell::Fish]),
+            None
+        );
+        assert_eq!(Man.dest("foo.1", &[]).unwrap(), Path::new("man/man1/foo.1"));
+
+        assert_eq!(dest(Man, "foo.1").unwrap(), Path::new("man/man1/foo.1"));
+        assert_eq!(
+            dest(Man, "foo-bar.8.gz").unwrap(),
+            Path::new("man/man8/foo-bar.8.gz")
+        );
+        assert_eq!(dest(Man, "README.md"), None);
+        assert_eq!(dest(Man, "foo"), None);
+    }
+
+    #[test]
+    fn test_of_dest() {
+        use ExtraFileKind::*;
+
+        let data_dir = Path::new("/data");
+        let of_dest = |dest: &str| ExtraFileKind::of_dest(Path::new(dest), data_dir);
+
+        assert_eq!(
+            of_dest("/data/bash-completion/completions/foo"),
+            Some((Completions, Some(Shell::Bash)))
+        );
+        assert_eq!(
+            of_dest("/data/zsh/site-functions/_foo"),
+            Some((Completions, Some(Shell::Zsh)))
+        );
+        assert_eq!(
+            of_dest("/data/fish/vendor_completions.d/foo.fish"),
+            Some((Completions, Some(Shell::Fish)))
+        );
+        assert_eq!(of_dest("/data/man/man1/foo.1"), Some((Man, None)));
+        assert_eq!(of_dest("/data/foo"), None);
+        assert_eq!(of_dest("/other/man/man1/foo.1"), None);
+    }
+
+    #[test]
+    fn test_is_conventional() {
+        use ExtraFileKind::*;
+
+        let bins = ["foo", "foo-bar"];
+        for file_name in ["foo.bash", "_foo", "foo.fish", "_foo-bar"] {
+            assert!(Completions.is_conventional(file_name, &bins), "{file_name}");
+        }
+        for file_name in ["foo", "bar.bash", "_foo.ps1", "foo.zsh", "_foo.bash"] {
+            assert!(
+                !Completions.is_conventional(file_name, &bins),
+                "{file_name}"
+            );
+        }
+
+        for file_name in ["foo.1", "foo-bar.8.gz"] {
+            assert!(Man.is_conventional(file_name, &bins), "{file_name}");
+        }
+        for file_name in ["foo", "foo.0", "foo.10", "foo.md", "bar.1", "foo-baz.1"] {
+            assert!(!Man.is_conventional(file_name, &bins), "{file_name}");
+        }
+    }
+
+    #[test]
+    fn test_detect() {
+        use ExtraFileKind::*;
+
+        let bin_path = Path::new("/tmp/bin-foo");
+        let data_dir = Path::new("/data");
+        let files = [
+            "foo-v1/doc/complete/foo.bash",
+            "foo-v1/foo.bash",
+            "foo-v1/_foo",
+            "foo-v1/foo.fish",
+            "foo-v1/foo",
+            "foo-v1/README.md",
+            "foo-v1/doc/foo.1",
+        ]
+        .map(Path::new);
+
+        let detected = ExtraFile::detect(
+            Completions,
+            &["foo"],
+            &[Shell::Bash, Shell::Zsh],
+            bin_path,
+            data_dir,
+            files,
+        );
+        assert_eq!(
+            detected
+                .iter()
+                .map(|file| (file.source.as_path(), file.dest.as_path()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    Path::new("/tmp/bin-foo/foo-v1/_foo"),
+                    Path::new("/data/zsh/site-functions/_foo")
+                ),
+                (
+                    Path::new("/tmp/bin-foo/foo-v1/foo.bash"),
+                    Path::new("/data/bash-completion/completions/foo")
+                ),
+            ]
+        );
+
+        let detected = ExtraFile::detect(Man, &["foo"], &[], bin_path, data_dir, files);
+        assert_eq!(detected.len(), 1);
+        assert_eq!(detected[0].dest, Path::new("/data/man/man1/foo.1"));
+
+        assert!(ExtraFile::detect(Man, &["bar"], &[], bin_path, data_dir, files).is_empty());
+    }
+}
@@ -1,12 +1,14 @@
 use std::{
     borrow::Cow,
-    fmt, io,
+    fmt, fs, io,
     path::{self, Component, Path, PathBuf},
+    process,
 };
 
 use atomic_file_install::{
     atomic_install, atomic_install_noclobber, atomic_symlink_file, atomic_symlink_file_noclobber,
 };
+pub use atomic_file_install::{remove_old_files, take_degradations, Backup, Degradation};
 use binstalk_types::cargo_toml_binstall::{PkgFmt, PkgMeta};
 use compact_str::{format_compact, CompactString};
 use leon::Template;
@@ -15,6 +17,12 @@ use normalize_path::NormalizePath;
 use thiserror::Error as ThisError;
 use tracing::debug;
 
+mod executable;
+pub use executable::{required_glibc_version, FileKind};
+
+mod extra_files;
+pub use extra_files::{ExtraFile, ExtraFileKind, Shell};
+
 #[derive(Debug, ThisError, Diagnostic)]
 pub enum Error {
     /// bin-dir configuration provided generates source path outside
@@ -32,6 +40,25 @@ pub enum Error {
     #[error("bin file {} not found", .0.display())]
     BinFileNotFound(Box<Path>),
 
+    /// Bin file cannot be run on the target.
+    #[error("bin file {} is not an executable for {target}: it is {kind}", .path.display())]
+    NotExecutable {
+        path: Box<Path>,
+        target: CompactString,
+        kind: FileKind,
+    },
+
+    /// Bin file needs a newer glibc than the one of the host.
+    #[error(
+        "bin file {} requires glibc {}.{}, but the host has glibc {}.{}",
+        .path.display(), .required.0, .required.1, .host.0, .host.1
+    )]
+    GlibcTooOld {
+        path: Box<Path>,
+        required: (u32, u32),
+        host: (u32, u32),
+    },
+
     #[error(transparent)]
     Io(#[from] io::Error),
 
@@ -52,12 +79,10 @@ fn is_valid_path(path: &Path) -> bool {
     )
 }
 
-/// Must be called after the archive is downloaded and extracted.
-/// This function might uses blocking I/O.
-pub fn infer_bin_dir_template(
-    data: &Data,
-    has_dir: &mut dyn FnMut(&Path) -> bool,
-) -> Cow<'static, str> {
+const DEFAULT_BIN_DIR_TEMPLATE: &str = "{ bin }{ binary-ext }";
+
+/// Return the dirs the binaries could be in, in order of preference.
+fn possible_dirs<'a>(data: &'a Data) -> impl Iterator<Item = String> + 'a {
     let name = data.name;
     let target = data.target;
     let version = data.version;
@@ -77,20 +102,75 @@ pub fn infer_bin_dir_template(
         |name, _target, _version| name.to_string(),
     ];
 
-    let default_bin_dir_template = Cow::Borrowed("{ bin }{ binary-ext }");
-
     gen_possible_dirs
         .into_iter()
-        .map(|gen_possible_dir| gen_possible_dir(name, target, version))
+        .map(move |gen_possible_dir| gen_possible_dir(name, target, version))
+}
+
+fn bin_dir_template_in(mut dir: String) -> Cow<'static, str> {
+    dir.reserve_exact(1 + DEFAULT_BIN_DIR_TEMPLATE.len());
+    dir += "/";
+    dir += DEFAULT_BIN_DIR_TEMPLATE;
+    Cow::Owned(dir)
+}
+
+/// Escape `text` to be rendered as is in a template.
+fn escape_template(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '{' | '}') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Return the bin-dir template finding the binaries in `dir` of the
+/// archive, or `None` if it is not valid UTF-8.
+pub fn bin_dir_template_for(dir: &Path) -> Option<Cow<'static, str>> {
+    let parts = dir
+        .iter()
+        .map(|part| part.to_str().map(escape_template))
+        .collect::<Option<Vec<_>>>()?;
+    Some(if parts.is_empty() {
+        Cow::Borrowed(DEFAULT_BIN_DIR_TEMPLATE)
+    } else {
+        bin_dir_template_in(parts.join("/"))
+    })
+}
+
+/// Must be called after the archive is downloaded and extracted.
+/// This function might uses blocking I/O.
+///
+///  * `single_top_dir` - the only entry at the root of the archive if it is
+///    a directory, used if none of the usual dirs is found.
+pub fn infer_bin_dir_template(
+    data: &Data,
+    has_dir: &mut dyn FnMut(&Path) -> bool,
+    single_top_dir: Option<&str>,
+) -> Cow<'static, str> {
+    possible_dirs(data)
         .find(|dirname| has_dir(Path::new(&dirname)))
-        .map(|mut dir| {
-            dir.reserve_exact(1 + default_bin_dir_template.len());
-            dir += "/";
-            dir += &default_bin_dir_template;
-            Cow::Owned(dir)
-        })
+        // The archive wraps everything in a dir of another name, e.g.
+        // `tool-v1.2.3-x86_64`.
+        .or_else(|| single_top_dir.map(escape_template))
+        .map(bin_dir_template_in)
         // Fallback to no dir
-        .unwrap_or(default_bin_dir_template)
+        .unwrap_or(Cow::Borrowed(DEFAULT_BIN_DIR_TEMPLATE))
+}
+
+/// Return the bin-dir templates the binaries could be found with before
+/// the archive is extracted: the one in `data.meta` if any, otherwise all
+/// those [`infer_bin_dir_template`] could return.
+pub fn bin_dir_template_candidates<'a>(data: &'a Data) -> Vec<Cow<'a, str>> {
+    match data.meta.bin_dir.as_deref() {
+        Some(bin_dir) => vec![Cow::Borrowed(bin_dir)],
+        None => possible_dirs(data)
+            .map(bin_dir_template_in)
+            .chain([Cow::Borrowed(DEFAULT_BIN_DIR_TEMPLATE)])
+            .collect(),
+    }
 }
 
 pub struct BinFile {
@@ -109,24 +189,10 @@ impl BinFile {
         tt: &Template<'_>,
         no_symlinks: bool,
     ) -> Result<Self, Error> {
-        let binary_ext = if data.target.contains("windows") {
-            ".exe"
-        } else {
-            ""
-        };
+        let ctx = Context::new(data, base_name);
+        let binary_ext = ctx.binary_ext;
 
-        let ctx = Context {
-            name: data.name,
-            repo: data.repo,
-            target: data.target,
-            version: data.version,
-            bin: base_name,
-            binary_ext,
-
-            target_related_info: data.target_related_info,
-        };
-
-        let (source, archive_source_path) = if data.meta.pkg_fmt == Some(PkgFmt::Bin) {
+        let (source, archive_source_path) = if data.meta.pkg_fmt.map_or(false, PkgFmt::is_bin) {
             (
                 data.bin_path.to_path_buf(),
                 data.bin_path.file_name().unwrap().into(),
@@ -152,7 +218,7 @@ impl BinFile {
         // Destination at install dir + base-name{.extension}
         let mut dest = data.install_path.join(ctx.bin);
         if !binary_ext.is_empty() {
-            let binary_ext = binary_ext.strip_prefix('.').unwrap();
+            let binary_ext = binary_ext.strip_prefix('.').unwrap_or(binary_ext);
 
             // PathBuf::set_extension returns false if Path::file_name
             // is None, but we know that the file name must be Some,
@@ -207,44 +273,88 @@ impl BinFile {
         }
     }
 
-    fn pre_install_bin(&self) -> Result<(), Error> {
-        if !self.source.try_exists()? {
-            return Err(Error::BinFileNotFound((&*self.source).into()));
+    /// Return `Ok` if the source can be run on `target`, sniffing its
+    /// format and architecture.
+    ///
+    /// This function uses blocking I/O.
+    pub fn check_executable(&self, target: &str) -> Result<(), Error> {
+        let kind = FileKind::sniff_file(&self.source)?;
+        if kind.is_executable_for(target) {
+            Ok(())
+        } else {
+            Err(Error::NotExecutable {
+                path: (&*self.source).into(),
+                target: target.into(),
+                kind,
+            })
+        }
+    }
+
+    /// Return `Ok` if the source does not need a newer glibc than `host`,
+    /// the `(major, minor)` version of glibc on the host.
+    ///
+    /// This function uses blocking I/O.
+    pub fn check_glibc(&self, host: (u32, u32)) -> Result<(), Error> {
+        match required_glibc_version(&self.source)? {
+            Some(required) if required > host => Err(Error::GlibcTooOld {
+                path: (&*self.source).into(),
+                required,
+                host,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the binary extracted to the staging directory before moving
+    /// it into place, return the file to install.
+    ///
+    /// If the binary is a symlink in the archive, e.g. `tool` to
+    /// `tool-1.2.3`, the file it points to is installed.
+    fn pre_install_bin(&self) -> Result<PathBuf, Error> {
+        let not_found = || Error::BinFileNotFound((&*self.source).into());
+
+        let source = match fs::symlink_metadata(&self.source) {
+            Ok(metadata) if metadata.is_symlink() => {
+                fs::canonicalize(&self.source).map_err(|_| not_found())?
+            }
+            Ok(_) => self.source.clone(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(not_found()),
+            Err(err) => return Err(err.into()),
+        };
+        if !fs::metadata(&source)?.is_file() {
+            return Err(not_found());
         }
 
         #[cfg(unix)]
-        std::fs::set_permissions(
-            &self.source,
-            std::os::unix::fs::PermissionsExt::from_mode(0o755),
-        )?;
+        std::fs::set_permissions(&source, std::os::unix::fs::PermissionsExt::from_mode(0o755))?;
 
-        Ok(())
+        Ok(source)
     }
 
     pub fn install_bin(&self) -> Result<(), Error> {
-        self.pre_install_bin()?;
+        let source = self.pre_install_bin()?;
 
         debug!(
             "Atomically install file from '{}' to '{}'",
-            self.source.display(),
+            source.display(),
             self.dest.display()
         );
 
-        atomic_install(&self.source, &self.dest)?;
+        atomic_install(&source, &self.dest)?;
 
         Ok(())
     }
 
     pub fn install_bin_noclobber(&self) -> Result<(), Error> {
-        self.pre_install_bin()?;
+        let source = self.pre_install_bin()?;
 
         debug!(
             "Installing file from '{}' to '{}' only if dst not exists",
-            self.source.display(),
+            source.display(),
             self.dest.display()
         );
 
-        atomic_install_noclobber(&self.source, &self.dest)?;
+        atomic_install_noclobber(&source, &self.dest)?;
 
         Ok(())
     }
@@ -277,6 +387,54 @@ impl BinFile {
         Ok(())
     }
 
+    /// Link the installed binary into `dir` under its base name, replacing
+    /// the file there if any, and return the link.
+    ///
+    /// The binary is copied instead where symlinks are not supported.
+    pub fn install_link_in(&self, dir: &Path) -> Result<PathBuf, Error> {
+        let link = dir.join(self.base_name.as_str());
+        let dest = if self.dest.is_absolute() {
+            Cow::Borrowed(&self.dest)
+        } else {
+            Cow::Owned(std::env::current_dir()?.join(&self.dest))
+        };
+        debug!(
+            "Create link '{}' pointing to '{}'",
+            link.display(),
+            dest.display()
+        );
+        atomic_symlink_file(&dest, &link)?;
+
+        Ok(link)
+    }
+
+    /// Back up the file at `dest` (if any) so that it can be restored if
+    /// the installation fails.
+    pub fn backup_dest(&self) -> Result<Option<Backup>, Error> {
+        Ok(Backup::create(&self.dest)?)
+    }
+
+    /// Back up the file at `link` (if any) so that it can be restored if
+    /// the installation fails.
+    pub fn backup_link(&self) -> Result<Option<Backup>, Error> {
+        match &self.link {
+            Some(link) => Ok(Backup::create(link)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Return metadata of the file at `dest` if it already exists.
+    pub fn existing_dest(&self) -> Result<Option<ExistingFile<'_>>, Error> {
+        match fs::symlink_metadata(&self.dest) {
+            Ok(metadata) => Ok(Some(ExistingFile {
+                path: &self.dest,
+                metadata,
+            })),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     fn link_dest(&self) -> &Path {
         if cfg!(target_family = "unix") {
             Path::new(self.dest.file_name().unwrap())
@@ -286,6 +444,60 @@ impl BinFile {
     }
 }
 
+/// A file already present at the install destination.
+pub struct ExistingFile<'a> {
+    pub path: &'a Path,
+    pub metadata: fs::Metadata,
+}
+
+impl ExistingFile<'_> {
+    /// Ask the system package manager which package owns this file.
+    ///
+    /// This spawns `dpkg`, `rpm` and `pacman` and returns the first
+    /// successful answer, it always returns `None` on non-linux platform.
+    ///
+    /// This is a blocking function.
+    pub fn query_package_owner(&self) -> Option<String> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        [
+            ("dpkg", &["-S"][..]),
+            ("rpm", &["-qf"][..]),
+            ("pacman", &["-Qoq"][..]),
+        ]
+        .into_iter()
+        .find_map(|(program, args)| {
+            let output = process::Command::new(program)
+                .args(args)
+                .arg(self.path)
+                .stdin(process::Stdio::null())
+                .stderr(process::Stdio::null())
+                .output()
+                .ok()?;
+
+            let stdout = String::from_utf8(output.stdout).ok()?;
+            let owner = stdout.lines().next()?.trim();
+
+            (output.status.success() && !owner.is_empty())
+                .then(|| format!("{owner} (according to {program})"))
+        })
+    }
+}
+
+impl fmt::Display for ExistingFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} bytes", self.path.display(), self.metadata.len())?;
+
+        if let Ok(modified) = self.metadata.modified() {
+            write!(f, ", modified {}", httpdate::fmt_http_date(modified))?;
+        }
+
+        f.write_str(")")
+    }
+}
+
 /// Data required to get bin paths
 pub struct Data<'a> {
     pub name: &'a str,
@@ -299,6 +511,7 @@ pub struct Data<'a> {
     ///  - target_family,
     ///  - target_arch
     ///  - target_libc
+    ///  - target_env
     ///  - target_vendor
     pub target_related_info: &'a dyn leon::Values,
 }
@@ -311,12 +524,30 @@ struct Context<'c> {
     version: &'c str,
     bin: &'c str,
 
-    /// Filename extension on the binary, i.e. .exe on Windows, nothing otherwise
+    /// Filename extension on the binary, e.g. .exe on Windows, see
+    /// [`PkgMeta::binary_ext`]
     binary_ext: &'c str,
 
     target_related_info: &'c dyn leon::Values,
 }
 
+impl<'c> Context<'c> {
+    fn new(data: &'c Data<'_>, bin: &'c str) -> Self {
+        let binary_ext = data.meta.binary_ext(data.target);
+
+        Self {
+            name: data.name,
+            repo: data.repo,
+            target: data.target,
+            version: data.version,
+            bin,
+            binary_ext,
+
+            target_related_info: data.target_related_info,
+        }
+    }
+}
+
 impl leon::Values for Context<'_> {
     fn get_value<'s>(&'s self, key: &str) -> Option<Cow<'s, str>> {
         match key {
@@ -357,3 +588,167 @@ impl fmt::Display for OptionalLazyFormat<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bin_file_binary_ext() {
+        let tt = Template::parse("{ bin }-{ target }{ binary-ext }").unwrap();
+        let bin_file = |target, binary_ext: Option<&str>| {
+            let data = Data {
+                name: "tool",
+                target,
+                version: "1.0.0",
+                repo: None,
+                meta: PkgMeta {
+                    binary_ext: binary_ext.map(ToOwned::to_owned),
+                    ..Default::default()
+                },
+                bin_path: Path::new("pkg"),
+                install_path: Path::new("bin"),
+                target_related_info: &leon::vals(|_| None),
+            };
+            BinFile::new(&data, "tool", &tt, true).unwrap()
+        };
+
+        let file = bin_file("x86_64-pc-windows-msvc", None);
+        assert_eq!(
+            file.source,
+            Path::new("pkg/tool-x86_64-pc-windows-msvc.exe")
+        );
+        assert_eq!(file.dest, Path::new("bin/tool.exe"));
+
+        let file = bin_file("wasm32-wasi", None);
+        assert_eq!(file.source, Path::new("pkg/tool-wasm32-wasi.wasm"));
+        assert_eq!(file.dest, Path::new("bin/tool.wasm"));
+
+        let file = bin_file("x86_64-pc-windows-msvc", Some(""));
+        assert_eq!(file.source, Path::new("pkg/tool-x86_64-pc-windows-msvc"));
+        assert_eq!(file.dest, Path::new("bin/tool"));
+        assert_eq!(file.base_name, "tool");
+    }
+
+    #[test]
+    fn test_install_bin_from_staging() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin_path = dir.path().join("staging");
+        let install_path = dir.path().join("bin");
+        fs::create_dir_all(&bin_path).unwrap();
+        fs::create_dir_all(&install_path).unwrap();
+
+        let tt = Template::parse("{ bin }").unwrap();
+        let data = Data {
+            name: "tool",
+            target: "x86_64-unknown-linux-gnu",
+            version: "1.0.0",
+            repo: None,
+            meta: PkgMeta::default(),
+            bin_path: &bin_path,
+            install_path: &install_path,
+            target_related_info: &leon::vals(|_| None),
+        };
+        let file = BinFile::new(&data, "tool", &tt, true).unwrap();
+
+        // A directory is not a binary.
+        fs::create_dir(&file.source).unwrap();
+        assert!(matches!(
+            file.install_bin().unwrap_err(),
+            Error::BinFileNotFound(_)
+        ));
+        assert!(!file.dest.exists());
+
+        fs::remove_dir(&file.source).unwrap();
+        fs::write(&file.source, "binary").unwrap();
+        file.install_bin().unwrap();
+        assert_eq!(fs::read_to_string(&file.dest).unwrap(), "binary");
+        assert!(!file.source.exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(&file.dest).unwrap().permissions().mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_bin_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin_path = dir.path().join("staging");
+        let install_path = dir.path().join("bin");
+        fs::create_dir_all(&bin_path).unwrap();
+        fs::create_dir_all(&install_path).unwrap();
+
+        let tt = Template::parse("{ bin }").unwrap();
+        let data = Data {
+            name: "tool",
+            target: "x86_64-unknown-linux-gnu",
+            version: "1.0.0",
+            repo: None,
+            meta: PkgMeta::default(),
+            bin_path: &bin_path,
+            install_path: &install_path,
+            target_related_info: &leon::vals(|_| None),
+        };
+        let file = BinFile::new(&data, "tool", &tt, true).unwrap();
+
+        fs::write(bin_path.join("tool-1.0.0"), "binary").unwrap();
+        std::os::unix::fs::symlink("tool-1.0.0", &file.source).unwrap();
+
+        file.install_bin().unwrap();
+        assert!(!fs::symlink_metadata(&file.dest).unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&file.dest).unwrap(), "binary");
+    }
+
+    #[test]
+    fn test_bin_dir_template_for() {
+        assert_eq!(
+            bin_dir_template_for(Path::new("tool-v1/bin")).unwrap(),
+            "tool-v1/bin/{ bin }{ binary-ext }"
+        );
+        assert_eq!(
+            bin_dir_template_for(Path::new("tool-{1}")).unwrap(),
+            "tool-\\{1\\}/{ bin }{ binary-ext }"
+        );
+        assert_eq!(
+            bin_dir_template_for(Path::new("")).unwrap(),
+            DEFAULT_BIN_DIR_TEMPLATE
+        );
+    }
+
+    #[test]
+    fn test_infer_bin_dir_template_single_top_dir() {
+        let data = Data {
+            name: "tool",
+            target: "x86_64-unknown-linux-gnu",
+            version: "1.0.0",
+            repo: None,
+            meta: PkgMeta::default(),
+            bin_path: Path::new("pkg"),
+            install_path: Path::new("bin"),
+            target_related_info: &leon::vals(|_| None),
+        };
+
+        assert_eq!(
+            infer_bin_dir_template(&data, &mut |_| false, Some("tool-{x86_64}")),
+            "tool-\\{x86_64\\}/{ bin }{ binary-ext }"
+        );
+        // The usual dirs are preferred.
+        assert_eq!(
+            infer_bin_dir_template(
+                &data,
+                &mut |dir| dir == Path::new("tool-x86_64-unknown-linux-gnu-v1.0.0"),
+                Some("tool-x86_64-unknown-linux-gnu-v1.0.0"),
+            ),
+            "tool-x86_64-unknown-linux-gnu-v1.0.0/{ bin }{ binary-ext }"
+        );
+        assert_eq!(
+            infer_bin_dir_template(&data, &mut |_| false, None),
+            DEFAULT_BIN_DIR_TEMPLATE
+        );
+    }
+}
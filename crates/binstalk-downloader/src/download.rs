@@ -1,14 +1,31 @@
-use std::{fmt, io, marker::PhantomData, path::Path};
+use std::{
+    fmt::{self, Write as _},
+    future::Future,
+    io,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    pin::Pin,
+};
 
 use binstalk_types::cargo_toml_binstall::PkgFmtDecomposed;
 use bytes::Bytes;
-use futures_util::{stream::FusedStream, Stream, StreamExt};
+use futures_util::{
+    future::Either,
+    stream::{self, FusedStream},
+    Stream, StreamExt, TryStreamExt,
+};
+use sha2::{Digest, Sha256};
 use thiserror::Error as ThisError;
-use tracing::{debug, error, instrument};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, instrument, warn};
 
-pub use binstalk_types::cargo_toml_binstall::{PkgFmt, TarBasedFmt};
+pub use binstalk_types::cargo_toml_binstall::{CompressedBinFmt, PkgFmt, TarBasedFmt};
 
-use crate::remote::{Client, Error as RemoteError, Url};
+use crate::remote::{header, CancellationToken, Client, Error as RemoteError, Url};
 
 mod async_extracter;
 use async_extracter::*;
@@ -22,9 +39,23 @@ mod extracter;
 mod extracted_files;
 pub use extracted_files::{ExtractedFiles, ExtractedFilesEntry};
 
+mod extract_filter;
+pub use extract_filter::ExtractFilter;
+
 mod zip_extraction;
 pub use zip_extraction::ZipError;
 
+mod resume;
+use resume::download_to_file;
+
+mod magic;
+pub use magic::UnexpectedFormat;
+use magic::{has_magic, MAGIC_LEN};
+
+mod disk_space;
+pub use disk_space::DEFAULT_EXPANSION_FACTOR;
+use disk_space::{available_space, ensure_space, extracted_size, is_out_of_space, SpaceCheck};
+
 #[derive(Debug, ThisError)]
 #[non_exhaustive]
 pub enum DownloadError {
@@ -34,6 +65,22 @@ pub enum DownloadError {
     #[error("Failed to download from remote: {0}")]
     Remote(#[from] RemoteError),
 
+    #[error(transparent)]
+    UnexpectedFormat(Box<UnexpectedFormat>),
+
+    /// The package does not fit on the filesystem of `path`, either
+    /// obviously so before it is downloaded or extracted, or because it
+    /// ran out of space while being extracted.
+    #[error(
+        "not enough space on the filesystem of '{}': {needed} bytes needed, {available} available",
+        path.display()
+    )]
+    InsufficientSpace {
+        needed: u64,
+        available: u64,
+        path: PathBuf,
+    },
+
     /// A generic I/O error.
     ///
     /// - Code: `binstall::io`
@@ -90,10 +137,104 @@ impl DataVerifier for () {
     }
 }
 
+/// Receiver of the progress of a [`Download`].
+pub trait DownloadProgress: Send + Sync {
+    /// Called each time data is received, with the number of bytes
+    /// downloaded so far and the `Content-Length` of the response, if any.
+    ///
+    /// `total` is `None` for chunked responses.
+    fn update(&self, downloaded: u64, total: Option<u64>);
+}
+
+impl DownloadProgress for () {
+    fn update(&self, _: u64, _: Option<u64>) {}
+}
+
+/// A [`DataVerifier`] computing the SHA-256 digest of the data before
+/// passing it on to `inner`.
+pub struct Sha256Verifier<'a> {
+    inner: &'a mut dyn DataVerifier,
+    hasher: Sha256,
+    sha256: Option<String>,
+}
+
+impl<'a> Sha256Verifier<'a> {
+    pub fn new(inner: &'a mut dyn DataVerifier) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            sha256: None,
+        }
+    }
+
+    /// Hex-encoded SHA-256 digest of the data, available once
+    /// [`DataVerifier::validate`] is called.
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+}
+
+impl DataVerifier for Sha256Verifier<'_> {
+    fn update(&mut self, data: &Bytes) {
+        self.hasher.update(data);
+        self.inner.update(data);
+    }
+
+    fn validate(&mut self) -> bool {
+        self.sha256 = Some(to_hex(&self.hasher.finalize_reset()));
+        self.inner.validate()
+    }
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .fold(String::with_capacity(digest.len() * 2), |mut hex, byte| {
+            write!(hex, "{byte:02x}").unwrap();
+            hex
+        })
+}
+
+/// An artifact saved by [`Download::and_save`].
+#[derive(Clone, Debug)]
+pub struct SavedArtifact {
+    pub path: PathBuf,
+    /// Hex-encoded SHA-256 digest of the artifact.
+    pub sha256: String,
+    /// Path of the file containing `sha256`, in the format of `sha256sum`.
+    pub digest_path: PathBuf,
+}
+
+type BoxedStream = Pin<Box<dyn Stream<Item = Result<Bytes, RemoteError>> + Send + Sync>>;
+
+/// Where the data is downloaded from.
+enum Source {
+    Url {
+        client: Client,
+        url: Url,
+    },
+    /// The response to a request already sent, e.g. with a token.
+    Stream(BoxedStream),
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // The client is left out, it is the same for every download
+            // and would flood the logs of the spans.
+            Self::Url { url, .. } => f.debug_tuple("Url").field(&url.as_str()).finish(),
+            Self::Stream(_) => f.write_str("Stream"),
+        }
+    }
+}
+
 pub struct Download<'a> {
-    client: Client,
-    url: Url,
+    source: Source,
     data_verifier: Option<&'a mut dyn DataVerifier>,
+    progress: Option<&'a dyn DownloadProgress>,
+    extract_filter: Option<ExtractFilter>,
+    cancellation_token: Option<CancellationToken>,
+    space_expansion_factor: f64,
 }
 
 impl fmt::Debug for Download<'_> {
@@ -101,16 +242,22 @@ impl fmt::Debug for Download<'_> {
         #[allow(dead_code, clippy::type_complexity)]
         #[derive(Debug)]
         struct Download<'a> {
-            client: &'a Client,
-            url: &'a Url,
+            source: &'a Source,
             data_verifier: Option<PhantomData<&'a mut dyn DataVerifier>>,
+            progress: Option<PhantomData<&'a dyn DownloadProgress>>,
+            extract_filter: &'a Option<ExtractFilter>,
+            cancellation_token: &'a Option<CancellationToken>,
+            space_expansion_factor: f64,
         }
 
         fmt::Debug::fmt(
             &Download {
-                client: &self.client,
-                url: &self.url,
+                source: &self.source,
                 data_verifier: self.data_verifier.as_ref().map(|_| PhantomData),
+                progress: self.progress.map(|_| PhantomData),
+                extract_filter: &self.extract_filter,
+                cancellation_token: &self.cancellation_token,
+                space_expansion_factor: self.space_expansion_factor,
             },
             f,
         )
@@ -120,9 +267,27 @@ impl fmt::Debug for Download<'_> {
 impl Download<'static> {
     pub fn new(client: Client, url: Url) -> Self {
         Self {
-            client,
-            url,
+            cancellation_token: Some(client.get_cancellation_token().clone()),
+            space_expansion_factor: client.get_space_expansion_factor(),
+            source: Source::Url { client, url },
             data_verifier: None,
+            progress: None,
+            extract_filter: None,
+        }
+    }
+
+    /// Process the data of `stream` instead of downloading it, e.g. the
+    /// response to a request sent with a token.
+    pub fn from_stream(
+        stream: impl Stream<Item = Result<Bytes, RemoteError>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            source: Source::Stream(Box::pin(stream)),
+            data_verifier: None,
+            progress: None,
+            extract_filter: None,
+            cancellation_token: None,
+            space_expansion_factor: DEFAULT_EXPANSION_FACTOR,
         }
     }
 }
@@ -134,35 +299,269 @@ impl<'a> Download<'a> {
         data_verifier: &'a mut dyn DataVerifier,
     ) -> Self {
         Self {
-            client,
-            url,
+            cancellation_token: Some(client.get_cancellation_token().clone()),
+            space_expansion_factor: client.get_space_expansion_factor(),
+            source: Source::Url { client, url },
             data_verifier: Some(data_verifier),
+            progress: None,
+            extract_filter: None,
+        }
+    }
+
+    /// See [`Download::from_stream`].
+    pub fn from_stream_with_data_verifier(
+        stream: impl Stream<Item = Result<Bytes, RemoteError>> + Send + Sync + 'static,
+        data_verifier: &'a mut dyn DataVerifier,
+    ) -> Self {
+        Self {
+            source: Source::Stream(Box::pin(stream)),
+            data_verifier: Some(data_verifier),
+            progress: None,
+            extract_filter: None,
+            cancellation_token: None,
+            space_expansion_factor: DEFAULT_EXPANSION_FACTOR,
+        }
+    }
+
+    /// Report the progress of the download to `progress`.
+    ///
+    /// The total size is only known when downloading from a url whose
+    /// response has a `Content-Length`.
+    pub fn with_progress<'b>(self, progress: &'b dyn DownloadProgress) -> Download<'b>
+    where
+        'a: 'b,
+    {
+        Download {
+            source: self.source,
+            data_verifier: self
+                .data_verifier
+                .map(|data_verifier| data_verifier as &'b mut dyn DataVerifier),
+            progress: Some(progress),
+            extract_filter: self.extract_filter,
+            cancellation_token: self.cancellation_token,
+            space_expansion_factor: self.space_expansion_factor,
         }
     }
 
+    /// Only extract the entries of the archive matched by `filter` in
+    /// [`Download::and_extract`], skipping the others while streaming.
+    ///
+    /// If it matches none of the expected ones, the whole archive is
+    /// extracted instead.
+    pub fn with_extract_filter(mut self, filter: ExtractFilter) -> Self {
+        self.extract_filter = Some(filter);
+        self
+    }
+
+    /// Stop downloading and extracting, failing with
+    /// [`RemoteError::Cancelled`], once `token` is cancelled.
+    ///
+    /// A download from a url uses the token of its [`Client`] by default.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// * `part_file` - if `Some`, the data is downloaded to it first, so that
+    ///   the download of a url can be resumed if interrupted and the file
+    ///   read again, and the returned stream reads it.
+    /// * `space_check` - checked once the size of the data downloaded to
+    ///   `part_file` is known, before downloading it.
+    ///
+    /// Return the stream along with the `Content-Type` of the response, if
+    /// any.
     async fn get_stream(
         self,
+        part_file: Option<&mut fs::File>,
+        space_check: Option<&SpaceCheck<'_>>,
     ) -> Result<
-        impl FusedStream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin + 'a,
+        (
+            impl FusedStream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin + 'a,
+            Option<String>,
+        ),
         DownloadError,
     > {
         let mut data_verifier = self.data_verifier;
-        Ok(self
-            .client
-            .get_stream(self.url)
-            .await?
+        let mut progress = self.progress;
+        let (stream, total, content_type) = match (self.source, part_file) {
+            (source, Some(part_file)) => {
+                let content_type = match source {
+                    Source::Url { client, url } => {
+                        download_to_file(&client, url, part_file, progress.take(), space_check)
+                            .await?
+                    }
+                    Source::Stream(stream) => {
+                        write_to_file(stream, part_file, progress.take()).await?;
+                        None
+                    }
+                };
+                part_file.rewind().await?;
+
+                let part_file = part_file.try_clone().await?;
+                (
+                    Either::Left(Either::Left(
+                        ReaderStream::new(part_file).map_err(DownloadError::from),
+                    )),
+                    None,
+                    content_type,
+                )
+            }
+            (Source::Url { client, url }, None) => {
+                let response = client.get(url).send(true).await?;
+                let total = response.content_length();
+                let content_type = response
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(ToOwned::to_owned);
+                (
+                    Either::Left(Either::Right(
+                        response.bytes_stream().map_err(DownloadError::from),
+                    )),
+                    total,
+                    content_type,
+                )
+            }
+            (Source::Stream(stream), None) => (
+                Either::Right(stream.map_err(DownloadError::from)),
+                None,
+                None,
+            ),
+        };
+        let mut downloaded = 0;
+
+        let stream = stream
             .map(move |res| {
                 let bytes = res?;
 
                 if let Some(data_verifier) = &mut data_verifier {
                     data_verifier.update(&bytes);
                 }
+                if let Some(progress) = progress {
+                    downloaded += bytes.len() as u64;
+                    progress.update(downloaded, total);
+                }
 
                 Ok(bytes)
             })
             // Call `fuse` at the end to make sure `data_verifier` is only
             // called when the stream still has elements left.
-            .fuse())
+            .fuse();
+
+        Ok((stream, content_type))
+    }
+}
+
+/// Write `stream` to `file`, the counterpart of [`download_to_file`] for
+/// [`Source::Stream`].
+async fn write_to_file(
+    mut stream: BoxedStream,
+    file: &mut fs::File,
+    progress: Option<&dyn DownloadProgress>,
+) -> Result<(), DownloadError> {
+    let mut downloaded = 0;
+
+    while let Some(bytes) = stream.next().await {
+        let bytes = bytes?;
+        file.write_all(&bytes).await?;
+        downloaded += bytes.len() as u64;
+
+        if let Some(progress) = progress {
+            progress.update(downloaded, None);
+        }
+    }
+
+    file.flush().await?;
+
+    Ok(())
+}
+
+/// A temporary file a download is written to, removed on drop.
+struct PartFile {
+    file: fs::File,
+    path: tempfile::TempPath,
+}
+
+impl PartFile {
+    fn new() -> io::Result<Self> {
+        let (file, path) = tempfile::Builder::new()
+            .prefix("binstall-")
+            .suffix(".part")
+            .tempfile()?
+            .into_parts();
+
+        Ok(Self {
+            file: fs::File::from_std(file),
+            path,
+        })
+    }
+}
+
+/// Convert `err` into [`DownloadError::InsufficientSpace`] if the
+/// filesystem of `path` ran out of space while extracting a package needing
+/// `needed` bytes to it, removing what was extracted so far.
+async fn out_of_space_error(err: DownloadError, path: &Path, needed: u64) -> DownloadError {
+    if !is_out_of_space(&err) {
+        return err;
+    }
+
+    let available = available_space(path).unwrap_or(0);
+
+    let res = match fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path).await,
+        Ok(_) => fs::remove_file(path).await,
+        Err(_) => Ok(()),
+    };
+    if let Err(err) = res {
+        warn!("Failed to remove '{}': {err}", path.display());
+    }
+
+    DownloadError::InsufficientSpace {
+        needed,
+        available,
+        path: path.to_owned(),
+    }
+}
+
+/// Check that the data downloaded to `path` starts like `fmt`, so that an
+/// error page served instead of the package is reported as such rather than
+/// as a decoding error.
+async fn check_format(
+    path: &Path,
+    fmt: PkgFmt,
+    content_type: Option<String>,
+) -> Result<(), DownloadError> {
+    let mut head = Vec::with_capacity(MAGIC_LEN);
+    fs::File::open(path)
+        .await?
+        .take(MAGIC_LEN as u64)
+        .read_to_end(&mut head)
+        .await?;
+
+    if has_magic(fmt, &head) {
+        Ok(())
+    } else {
+        Err(DownloadError::UnexpectedFormat(Box::new(
+            UnexpectedFormat::new(fmt, content_type, &head),
+        )))
+    }
+}
+
+/// Run `fut`, unless `cancellation_token` is cancelled first, in which case
+/// it is dropped along with its temporary files.
+async fn cancellable<T>(
+    cancellation_token: Option<CancellationToken>,
+    fut: impl Future<Output = Result<T, DownloadError>>,
+) -> Result<T, DownloadError> {
+    let Some(cancellation_token) = cancellation_token else {
+        return fut.await;
+    };
+
+    tokio::select! {
+        biased;
+
+        () = cancellation_token.cancelled() => Err(RemoteError::Cancelled.into()),
+        res = fut => res,
     }
 }
 
@@ -192,31 +591,39 @@ impl Download<'_> {
     /// [`Download::and_extract`].
     #[instrument(skip(visitor))]
     pub async fn and_visit_tar(
-        self,
+        mut self,
         fmt: TarBasedFmt,
         visitor: &mut dyn TarEntriesVisitor,
     ) -> Result<(), DownloadError> {
-        let has_data_verifier = self.data_verifier.is_some();
-        let mut stream = self.get_stream().await?;
+        let cancellation_token = self.cancellation_token.take();
+        cancellable(cancellation_token, async move {
+            let has_data_verifier = self.data_verifier.is_some();
+            let (mut stream, _) = self.get_stream(None, None).await?;
 
-        debug!("Downloading and extracting then in-memory processing");
+            debug!("Downloading and extracting then in-memory processing");
 
-        let res = extract_tar_based_stream_and_visit(&mut stream, fmt, visitor).await;
+            let res = extract_tar_based_stream_and_visit(&mut stream, fmt, visitor).await;
 
-        if has_data_verifier {
-            consume_stream(&mut stream).await;
-        }
+            if has_data_verifier {
+                consume_stream(&mut stream).await;
+            }
 
-        if res.is_ok() {
-            debug!("Download, extraction and in-memory procession OK");
-        }
+            if res.is_ok() {
+                debug!("Download, extraction and in-memory procession OK");
+            }
 
-        res
+            res
+        })
+        .await
     }
 
     /// Download a file from the provided URL and extract it to the provided path.
     ///
-    /// NOTE that this will only extract directory and regular files.
+    /// The file is downloaded to a temporary file first, so that the download
+    /// can be resumed if interrupted, and extracted once complete.
+    ///
+    /// NOTE that this will only extract directory and regular files, and only
+    /// those matched by the filter set with [`Download::with_extract_filter`].
     #[instrument(skip(path))]
     pub async fn and_extract(
         self,
@@ -224,26 +631,59 @@ impl Download<'_> {
         path: impl AsRef<Path>,
     ) -> Result<ExtractedFiles, DownloadError> {
         async fn inner(
+            mut this: Download<'_>,
+            fmt: PkgFmt,
+            path: &Path,
+        ) -> Result<ExtractedFiles, DownloadError> {
+            let cancellation_token = this.cancellation_token.take();
+            cancellable(cancellation_token, extract(this, fmt, path)).await
+        }
+
+        async fn extract(
             this: Download<'_>,
             fmt: PkgFmt,
             path: &Path,
         ) -> Result<ExtractedFiles, DownloadError> {
             let has_data_verifier = this.data_verifier.is_some();
-            let mut stream = this.get_stream().await?;
+            let filter = this.extract_filter.clone();
+            let expansion_factor = this.space_expansion_factor;
+            let mut part_file = PartFile::new()?;
+            let space_check = SpaceCheck {
+                part_dir: part_file.path.parent().unwrap_or(Path::new(".")),
+                dst: path,
+                fmt: Some(fmt),
+                expansion_factor,
+            };
+            let (mut stream, content_type) = this
+                .get_stream(Some(&mut part_file.file), Some(&space_check))
+                .await?;
+
+            check_format(&part_file.path, fmt, content_type).await?;
+
+            // The size might not have been known before downloading it.
+            let needed = extracted_size(
+                part_file.file.metadata().await?.len(),
+                fmt,
+                expansion_factor,
+            );
+            ensure_space(path, needed)?;
 
             debug!("Downloading and extracting to: '{}'", path.display());
 
-            let res = match fmt.decompose() {
-                PkgFmtDecomposed::Tar(fmt) => {
-                    extract_tar_based_stream(&mut stream, path, fmt).await
-                }
-                PkgFmtDecomposed::Bin => extract_bin(&mut stream, path).await,
-                PkgFmtDecomposed::Zip => extract_zip(&mut stream, path).await,
-            };
+            let res = extract_stream(&mut stream, fmt, path, filter.as_ref()).await;
 
             if has_data_verifier {
                 consume_stream(&mut stream).await;
             }
+            drop(stream);
+
+            let res =
+                extract_all_if_unmatched(res, &mut part_file.file, fmt, path, filter.as_ref())
+                    .await;
+            let res = match res {
+                Err(err) => Err(out_of_space_error(err, path, needed).await),
+                res => res,
+            };
 
             if res.is_ok() {
                 debug!("Download OK, extracted to: '{}'", path.display());
@@ -255,9 +695,54 @@ impl Download<'_> {
         inner(self, fmt, path.as_ref()).await
     }
 
+    /// Download a file from the provided URL and save it to `path` as is,
+    /// along with its SHA-256 digest in `{path}.sha256`.
+    ///
+    /// The saved file can be extracted later using [`extract_file`].
+    #[instrument(skip(path))]
+    pub async fn and_save(self, path: impl AsRef<Path>) -> Result<SavedArtifact, DownloadError> {
+        async fn inner(
+            mut this: Download<'_>,
+            path: &Path,
+        ) -> Result<SavedArtifact, DownloadError> {
+            let cancellation_token = this.cancellation_token.take();
+            cancellable(cancellation_token, save(this, path)).await
+        }
+
+        async fn save(this: Download<'_>, path: &Path) -> Result<SavedArtifact, DownloadError> {
+            let expansion_factor = this.space_expansion_factor;
+            let mut part_file = PartFile::new()?;
+            let space_check = SpaceCheck {
+                part_dir: part_file.path.parent().unwrap_or(Path::new(".")),
+                dst: path,
+                fmt: None,
+                expansion_factor,
+            };
+            let (mut stream, _) = this
+                .get_stream(Some(&mut part_file.file), Some(&space_check))
+                .await?;
+
+            debug!("Downloading to: '{}'", path.display());
+
+            let artifact = save_stream(&mut stream, path).await?;
+
+            debug!("Download OK, saved to: '{}'", path.display());
+
+            Ok(artifact)
+        }
+
+        inner(self, path.as_ref()).await
+    }
+
     #[instrument]
     pub async fn into_bytes(self) -> Result<Bytes, DownloadError> {
-        let bytes = self.client.get(self.url).send(true).await?.bytes().await?;
+        let bytes = cancellable(self.cancellation_token, async move {
+            Ok(match self.source {
+                Source::Url { client, url } => client.get(url).send(true).await?.bytes().await?,
+                Source::Stream(stream) => stream.map_ok(Vec::from).try_concat().await?.into(),
+            })
+        })
+        .await?;
         if let Some(verifier) = self.data_verifier {
             verifier.update(&bytes);
         }
@@ -265,6 +750,184 @@ impl Download<'_> {
     }
 }
 
+/// Extract an artifact previously saved by [`Download::and_save`] to `path`.
+///
+/// * `filter` - if `Some`, only the entries it matches are extracted, see
+///   [`Download::with_extract_filter`].
+///
+/// NOTE that this will only extract directory and regular files.
+#[instrument(skip(archive, path))]
+pub async fn extract_file(
+    archive: impl AsRef<Path>,
+    fmt: PkgFmt,
+    path: impl AsRef<Path>,
+    filter: Option<&ExtractFilter>,
+) -> Result<ExtractedFiles, DownloadError> {
+    async fn inner(
+        archive: &Path,
+        fmt: PkgFmt,
+        path: &Path,
+        filter: Option<&ExtractFilter>,
+    ) -> Result<ExtractedFiles, DownloadError> {
+        debug!(
+            "Extracting '{}' to: '{}'",
+            archive.display(),
+            path.display()
+        );
+
+        let mut file = fs::File::open(archive).await?;
+        let needed = extracted_size(file.metadata().await?.len(), fmt, DEFAULT_EXPANSION_FACTOR);
+        ensure_space(path, needed)?;
+
+        let mut stream = ReaderStream::new(&mut file)
+            .map(|res| res.map_err(DownloadError::from))
+            .fuse();
+
+        let res = extract_stream(&mut stream, fmt, path, filter).await;
+        drop(stream);
+
+        match extract_all_if_unmatched(res, &mut file, fmt, path, filter).await {
+            Err(err) => Err(out_of_space_error(err, path, needed).await),
+            res => res,
+        }
+    }
+
+    inner(archive.as_ref(), fmt, path.as_ref(), filter).await
+}
+
+/// Process the tar-based archive `data`, e.g. previously downloaded with
+/// [`Download::into_bytes`], in memory.
+#[instrument(skip(data, visitor))]
+pub async fn visit_tar(
+    data: Bytes,
+    fmt: TarBasedFmt,
+    visitor: &mut dyn TarEntriesVisitor,
+) -> Result<(), DownloadError> {
+    extract_tar_based_stream_and_visit(stream::iter([Ok(data)]), fmt, visitor).await
+}
+
+/// * `filter` - ignored for binaries, which are always extracted.
+async fn extract_stream<S>(
+    stream: &mut S,
+    fmt: PkgFmt,
+    path: &Path,
+    filter: Option<&ExtractFilter>,
+) -> Result<ExtractedFiles, DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
+{
+    match fmt.decompose() {
+        PkgFmtDecomposed::Tar(fmt) => extract_tar_based_stream(stream, path, fmt, filter).await,
+        PkgFmtDecomposed::Bin => extract_bin(stream, path, None).await,
+        PkgFmtDecomposed::CompressedBin(fmt) => extract_bin(stream, path, Some(fmt)).await,
+        PkgFmtDecomposed::Zip => extract_zip(stream, path, filter).await,
+    }
+}
+
+/// If `filter` matched none of the expected entries of the archive in
+/// `file`, extract all of it instead and log what was found.
+async fn extract_all_if_unmatched(
+    res: Result<ExtractedFiles, DownloadError>,
+    file: &mut fs::File,
+    fmt: PkgFmt,
+    path: &Path,
+    filter: Option<&ExtractFilter>,
+) -> Result<ExtractedFiles, DownloadError> {
+    let extracted_files = res?;
+    let Some(filter) = filter else {
+        return Ok(extracted_files);
+    };
+    let matched = extracted_files.files().any(|path| filter.is_expected(path));
+    if matched && !has_dangling_symlink(path, &extracted_files) {
+        return Ok(extracted_files);
+    }
+
+    file.rewind().await?;
+    let mut stream = ReaderStream::new(file)
+        .map(|res| res.map_err(DownloadError::from))
+        .fuse();
+    let extracted_files = extract_stream(&mut stream, fmt, path, None).await?;
+
+    if matched {
+        debug!("Some files extracted are symlinks to files filtered out, extracted all of the archive instead");
+        return Ok(extracted_files);
+    }
+
+    let mut files: Vec<_> = extracted_files
+        .files()
+        .map(|file| file.display().to_string())
+        .collect();
+    files.sort_unstable();
+    warn!(
+        "None of the expected files are in the archive, extracted all of it instead: {}",
+        files.join(", ")
+    );
+
+    Ok(extracted_files)
+}
+
+/// Return true if one of the `extracted_files` in `path` is a symlink to a
+/// file which is not extracted.
+fn has_dangling_symlink(path: &Path, extracted_files: &ExtractedFiles) -> bool {
+    extracted_files.files().any(|file| {
+        let file = path.join(file);
+        file.symlink_metadata()
+            .is_ok_and(|metadata| metadata.is_symlink())
+            && !file.exists()
+    })
+}
+
+/// Write `stream` to `path` through a temporary file so that a partial
+/// download is never left at `path`.
+///
+/// The temporary file is removed on drop, even if the future is cancelled.
+async fn save_stream<S>(stream: &mut S, path: &Path) -> Result<SavedArtifact, DownloadError>
+where
+    S: Stream<Item = Result<Bytes, DownloadError>> + Unpin,
+{
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_string_lossy();
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let (file, part_path) = tempfile::Builder::new()
+        .prefix(&*file_name)
+        .suffix(".part")
+        .tempfile_in(dir)?
+        .into_parts();
+    let mut file = fs::File::from_std(file);
+
+    let mut hasher = Sha256::new();
+    while let Some(bytes) = stream.next().await {
+        let bytes = bytes?;
+        hasher.update(&bytes);
+        file.write_all(&bytes).await?;
+    }
+
+    file.sync_all().await?;
+    drop(file);
+
+    part_path.persist(path).map_err(|err| err.error)?;
+
+    let sha256 = to_hex(&hasher.finalize());
+
+    let mut digest_path = path.as_os_str().to_owned();
+    digest_path.push(".sha256");
+    let digest_path = PathBuf::from(digest_path);
+
+    fs::write(&digest_path, format!("{sha256}  {file_name}\n")).await?;
+
+    Ok(SavedArtifact {
+        path: path.to_owned(),
+        sha256,
+        digest_path,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -276,6 +939,463 @@ mod test {
     };
     use tempfile::tempdir;
 
+    #[tokio::test]
+    async fn test_save_and_extract_file() {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(6);
+        header.set_mode(0o755);
+        header.set_cksum();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_data(&mut header, "cargo-binstall", &b"binary"[..])
+            .unwrap();
+        let archive = Bytes::from(builder.into_inner().unwrap());
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cargo-binstall.tar");
+
+        let mut stream =
+            futures_util::stream::iter([Ok(archive.slice(..100)), Ok(archive.slice(100..))]);
+        let artifact = save_stream(&mut stream, &path).await.unwrap();
+
+        let sha256: String = Sha256::digest(&archive)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        assert_eq!(artifact.sha256, sha256);
+        assert_eq!(
+            std::fs::read_to_string(&artifact.digest_path).unwrap(),
+            format!("{sha256}  cargo-binstall.tar\n")
+        );
+
+        // Extract from the saved file without touching the network.
+        let dst = dir.path().join("extracted");
+        let extracted_files = extract_file(&artifact.path, PkgFmt::Tar, &dst, None)
+            .await
+            .unwrap();
+
+        assert!(extracted_files.has_file(Path::new("cargo-binstall")));
+        assert_eq!(
+            std::fs::read(dst.join("cargo-binstall")).unwrap(),
+            b"binary"
+        );
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cancel_download() {
+        use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+        use crate::remote::{
+            test_server::spawn_stalling_server, CancellationToken, Certificate, SpkiPins,
+        };
+
+        static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+        let (url, cert) = spawn_stalling_server(|_| {
+            REQUESTED.store(true, Relaxed);
+            "HTTP/1.1 200 OK\r\nContent-Length: 1048576\r\n\r\nthe first bytes"
+        })
+        .await;
+
+        let cancellation_token = CancellationToken::new();
+        let client = Client::new(
+            "binstalk-downloader-test",
+            None,
+            NonZeroU16::new(1).unwrap(),
+            10.try_into().unwrap(),
+            [Certificate::from_der(&cert).unwrap()],
+            SpkiPins::default(),
+            None,
+        )
+        .unwrap()
+        .cancellation_token(cancellation_token.clone());
+
+        let root = tempdir().unwrap();
+        let dst = root.path().join("tool");
+
+        let task = tokio::spawn(Download::new(client, url).and_extract(PkgFmt::Bin, dst.clone()));
+
+        while !REQUESTED.load(Relaxed) {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        cancellation_token.cancel();
+        let res = tokio::time::timeout(std::time::Duration::from_secs(5), task)
+            .await
+            .expect("the download stops promptly once cancelled")
+            .unwrap();
+        assert!(
+            matches!(res, Err(DownloadError::Remote(RemoteError::Cancelled))),
+            "{res:?}"
+        );
+
+        // Nothing is extracted from an incomplete download, the staging
+        // directory of the fetcher is removed by the resolution.
+        assert!(!dst.exists());
+        assert_eq!(std::fs::read_dir(root.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_progress() {
+        #[derive(Default)]
+        struct Updates(std::sync::Mutex<Vec<(u64, Option<u64>)>>);
+
+        impl DownloadProgress for Updates {
+            fn update(&self, downloaded: u64, total: Option<u64>) {
+                self.0.lock().unwrap().push((downloaded, total));
+            }
+        }
+
+        let updates = Updates::default();
+        let stream = futures_util::stream::iter([
+            Ok(Bytes::from_static(b"#!/bin/sh\n")),
+            Ok(Bytes::from_static(b"echo binary\n")),
+        ]);
+
+        let dir = tempdir().unwrap();
+        Download::from_stream(stream)
+            .with_progress(&updates)
+            .and_extract(PkgFmt::Bin, dir.path().join("tool"))
+            .await
+            .unwrap();
+
+        // The size of a stream is unknown.
+        assert_eq!(updates.0.into_inner().unwrap(), [(10, None), (22, None)]);
+    }
+
+    #[tokio::test]
+    async fn test_extract_compressed_bin() {
+        use std::io::Write;
+
+        let binary = b"#!/bin/sh\necho binary\n".repeat(100);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&binary).unwrap();
+
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(&binary).unwrap();
+
+        let dir = tempdir().unwrap();
+
+        for (fmt, compressed) in [
+            (PkgFmt::Bgz, gz.finish().unwrap()),
+            (PkgFmt::Bxz, xz.finish().unwrap()),
+            (PkgFmt::Bzstd, zstd::encode_all(&binary[..], 0).unwrap()),
+        ] {
+            assert!(fmt.is_bin());
+
+            let compressed = Bytes::from(compressed);
+            let half = compressed.len() / 2;
+            let stream = futures_util::stream::iter([
+                Ok(compressed.slice(..half)),
+                Ok(compressed.slice(half..)),
+            ]);
+
+            let path = dir.path().join(fmt.to_string()).join("tool");
+            let extracted_files = Download::from_stream(stream)
+                .and_extract(fmt, &path)
+                .await
+                .unwrap();
+
+            assert!(extracted_files.has_file(Path::new("tool")));
+            assert_eq!(std::fs::read(&path).unwrap(), binary);
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+
+                let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o755);
+            }
+        }
+    }
+
+    /// Entries of the archives built for the filtering tests, with decoys
+    /// next to the binary and its completions.
+    const FILTER_ENTRIES: [&str; 5] = [
+        "tool-v1/tool",
+        "tool-v1/tool.pdb",
+        "tool-v1/completions/tool.bash",
+        "tool-v1/README.md",
+        "tool-v1/docs/index.html",
+    ];
+
+    fn tool_filter() -> ExtractFilter {
+        ExtractFilter::paths([
+            PathBuf::from("tool-v1/tool"),
+            PathBuf::from("tool-v1/completions"),
+        ])
+    }
+
+    fn build_tar(entries: &[&str]) -> Bytes {
+        let mut builder = tar::Builder::new(Vec::new());
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(entry.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, entry, entry.as_bytes())
+                .unwrap();
+        }
+        Bytes::from(builder.into_inner().unwrap())
+    }
+
+    async fn build_zip(entries: &[&str]) -> Bytes {
+        use async_zip::{base::write::ZipFileWriter, Compression, ZipEntryBuilder};
+
+        let mut writer = ZipFileWriter::new(Vec::new());
+        for entry in entries {
+            writer
+                .write_entry_whole(
+                    ZipEntryBuilder::new((*entry).into(), Compression::Stored),
+                    entry.as_bytes(),
+                )
+                .await
+                .unwrap();
+        }
+        Bytes::from(writer.close().await.unwrap())
+    }
+
+    fn assert_extracted_only(dir: &Path, extracted_files: &ExtractedFiles, expected: &[&str]) {
+        for entry in FILTER_ENTRIES {
+            let path = Path::new(entry);
+            if expected.contains(&entry) {
+                assert!(extracted_files.has_file(path), "{entry} is not extracted");
+                assert_eq!(std::fs::read(dir.join(path)).unwrap(), entry.as_bytes());
+            } else {
+                assert!(!extracted_files.has_file(path), "{entry} is extracted");
+                assert!(!dir.join(path).exists(), "{entry} is written");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_filtered() {
+        let expected = ["tool-v1/tool", "tool-v1/completions/tool.bash"];
+
+        for (fmt, archive) in [
+            (PkgFmt::Tar, build_tar(&FILTER_ENTRIES)),
+            (PkgFmt::Zip, build_zip(&FILTER_ENTRIES).await),
+        ] {
+            let dir = tempdir().unwrap();
+            let extracted_files =
+                Download::from_stream(futures_util::stream::iter([Ok(archive.clone())]))
+                    .with_extract_filter(tool_filter())
+                    .and_extract(fmt, dir.path())
+                    .await
+                    .unwrap();
+            assert_extracted_only(dir.path(), &extracted_files, &expected);
+            assert!(!dir.path().join("tool-v1/docs").exists());
+
+            // The same goes for an artifact saved to the cache.
+            let archive_path = dir.path().join("archive");
+            std::fs::write(&archive_path, &archive).unwrap();
+            let dst = dir.path().join("extracted");
+            let extracted_files = extract_file(&archive_path, fmt, &dst, Some(&tool_filter()))
+                .await
+                .unwrap();
+            assert_extracted_only(&dst, &extracted_files, &expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_filtered_without_match() {
+        // Only decoys, so the filter matches no expected entry.
+        let entries = &[
+            "tool-v1/tool.pdb",
+            "tool-v1/README.md",
+            "tool-v1/docs/index.html",
+        ];
+
+        for (fmt, archive) in [
+            (PkgFmt::Tar, build_tar(entries)),
+            (PkgFmt::Zip, build_zip(entries).await),
+        ] {
+            let dir = tempdir().unwrap();
+            let extracted_files = Download::from_stream(futures_util::stream::iter([Ok(archive)]))
+                .with_extract_filter(tool_filter().with_extra(|path| path.ends_with("README.md")))
+                .and_extract(fmt, dir.path())
+                .await
+                .unwrap();
+
+            // Everything is extracted instead.
+            assert_extracted_only(dir.path(), &extracted_files, entries);
+        }
+    }
+
+    fn tar_entry(path: &[u8], entry_type: tar::EntryType, mode: u32) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        // Set the name directly, as `set_path` refuses to add a "..".
+        header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path);
+        header.set_entry_type(entry_type);
+        header.set_mode(mode);
+        header.set_size(0);
+        header
+    }
+
+    fn tar_file(path: &str, mode: u32, data: &str) -> (tar::Header, Vec<u8>) {
+        let mut header = tar_entry(path.as_bytes(), tar::EntryType::Regular, mode);
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        (header, data.as_bytes().to_vec())
+    }
+
+    fn tar_symlink(path: &str, target: &str) -> (tar::Header, Vec<u8>) {
+        let mut header = tar_entry(path.as_bytes(), tar::EntryType::Symlink, 0o777);
+        header.set_link_name(target).unwrap();
+        header.set_cksum();
+        (header, Vec::new())
+    }
+
+    fn build_raw_tar(entries: impl IntoIterator<Item = (tar::Header, Vec<u8>)>) -> Bytes {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (header, data) in entries {
+            builder.append(&header, &data[..]).unwrap();
+        }
+        Bytes::from(builder.into_inner().unwrap())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_extract_tar_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let archive = build_raw_tar([
+            tar_file("tool-v1.2.3-x86_64/tool-1.2.3", 0o755, "binary"),
+            tar_symlink("tool-v1.2.3-x86_64/tool", "tool-1.2.3"),
+            tar_file("tool-v1.2.3-x86_64/README.md", 0o644, "readme"),
+        ]);
+
+        let dir = tempdir().unwrap();
+        let extracted_files = Download::from_stream(futures_util::stream::iter([Ok(archive)]))
+            .with_extract_filter(ExtractFilter::paths([PathBuf::from(
+                "tool-v1.2.3-x86_64/tool",
+            )]))
+            .and_extract(PkgFmt::Tar, dir.path())
+            .await
+            .unwrap();
+
+        let top_dir = dir.path().join("tool-v1.2.3-x86_64");
+        let mode = |path| {
+            std::fs::metadata(top_dir.join(path))
+                .unwrap()
+                .permissions()
+                .mode()
+        };
+        assert_eq!(mode("tool-1.2.3") & 0o777, 0o755);
+        assert_eq!(mode("README.md") & 0o777, 0o644);
+
+        // The target of the symlink is filtered out, so everything is extracted.
+        assert_eq!(
+            std::fs::read_link(top_dir.join("tool")).unwrap(),
+            Path::new("tool-1.2.3")
+        );
+        assert_eq!(
+            std::fs::read_to_string(top_dir.join("tool")).unwrap(),
+            "binary"
+        );
+        assert!(extracted_files.has_file(Path::new("tool-v1.2.3-x86_64/tool")));
+        assert!(extracted_files.has_file(Path::new("tool-v1.2.3-x86_64/README.md")));
+        assert_eq!(
+            extracted_files.single_top_dir(),
+            Some(OsStr::new("tool-v1.2.3-x86_64"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_extract_tar_outside_of_archive() {
+        for entries in [
+            vec![tar_file("../evil", 0o644, "evil")],
+            vec![tar_file("tool/../../evil", 0o644, "evil")],
+            vec![tar_file("/evil", 0o644, "evil")],
+            vec![tar_symlink("evil", "../evil")],
+            vec![tar_symlink("evil", "/etc/passwd")],
+            vec![tar_symlink("tool/evil", "../../evil")],
+            // Each symlink points inside of the archive, but the chain
+            // resolves to outside of it.
+            vec![
+                tar_file("tool/file", 0o644, "file"),
+                tar_symlink("tool/up", ".."),
+                tar_symlink("evil", "tool/up/.."),
+            ],
+        ] {
+            let dir = tempdir().unwrap();
+            let dst = dir.path().join("dst");
+            let archive = build_raw_tar(entries);
+
+            Download::from_stream(futures_util::stream::iter([Ok(archive)]))
+                .and_extract(PkgFmt::Tar, &dst)
+                .await
+                .unwrap_err();
+
+            assert!(!dir.path().join("evil").exists());
+            assert!(std::fs::symlink_metadata(dst.join("evil")).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extract_unexpected_format() {
+        let page = Bytes::from_static(b"<!DOCTYPE html>\n<html><body>Not Found</body></html>\n");
+
+        for fmt in [PkgFmt::Tgz, PkgFmt::Tar, PkgFmt::Zip, PkgFmt::Bzstd] {
+            let dir = tempdir().unwrap();
+            let err = Download::from_stream(futures_util::stream::iter([Ok(page.clone())]))
+                .and_extract(fmt, dir.path().join("tool"))
+                .await
+                .unwrap_err();
+
+            let DownloadError::UnexpectedFormat(err) = err else {
+                panic!("unexpected error for {fmt}: {err}");
+            };
+            assert_eq!(err.fmt, fmt);
+            assert_eq!(err.content_type, None);
+            assert_eq!(err.head.as_bytes(), &page[..]);
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        }
+
+        // Any data is a valid binary.
+        let dir = tempdir().unwrap();
+        Download::from_stream(futures_util::stream::iter([Ok(page)]))
+            .and_extract(PkgFmt::Bin, dir.path().join("tool"))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_sha256_verifier() {
+        let mut inner = ();
+        let mut verifier = Sha256Verifier::new(&mut inner);
+
+        verifier.update(&Bytes::from_static(b"a"));
+        verifier.update(&Bytes::from_static(b"bc"));
+        assert_eq!(verifier.sha256(), None);
+
+        assert!(verifier.validate());
+        assert_eq!(
+            verifier.sha256(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_stream_failure() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("artifact.tgz");
+
+        let mut stream = futures_util::stream::iter([
+            Ok(Bytes::from_static(b"partial")),
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset").into()),
+        ]);
+        save_stream(&mut stream, &path).await.unwrap_err();
+
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
     #[tokio::test]
     async fn test_and_extract() {
         let client = crate::remote::Client::new(
@@ -284,6 +1404,8 @@ mod test {
             NonZeroU16::new(10).unwrap(),
             1.try_into().unwrap(),
             [],
+            Default::default(),
+            None,
         )
         .unwrap();
 
@@ -11,28 +11,46 @@ use bytes::{Bytes, BytesMut};
 use futures_util::Stream;
 use tokio::sync::mpsc;
 use tokio_util::io::StreamReader;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use super::{
-    extracter::*, zip_extraction::extract_zip_entry, DownloadError, ExtractedFiles, TarBasedFmt,
-    ZipError,
+    extracter::*, zip_extraction::extract_zip_entry, CompressedBinFmt, DownloadError,
+    ExtractFilter, ExtractedFiles, TarBasedFmt, ZipError,
 };
 use crate::utils::{extract_with_blocking_task, StreamReadable};
 
-pub async fn extract_bin<S>(stream: S, path: &Path) -> Result<ExtractedFiles, DownloadError>
+/// * `fmt` - the compression of the binary, `None` if it is not compressed.
+pub async fn extract_bin<S>(
+    stream: S,
+    path: &Path,
+    fmt: Option<CompressedBinFmt>,
+) -> Result<ExtractedFiles, DownloadError>
 where
     S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
 {
-    debug!("Writing to `{}`", path.display());
+    match fmt {
+        Some(fmt) => debug!("Decompressing from {fmt} to `{}`", path.display()),
+        None => debug!("Writing to `{}`", path.display()),
+    }
 
-    extract_with_blocking_decoder(stream, path, |mut rx, path| {
+    extract_with_blocking_decoder(stream, path, move |mut rx, path| {
         let mut file = fs::File::create(path)?;
 
-        while let Some(bytes) = rx.blocking_recv() {
-            file.write_all(&bytes)?;
+        if let Some(fmt) = fmt {
+            let mut decoder = create_bin_decoder(StreamReadable::new(rx), fmt)?;
+            io::copy(&mut decoder, &mut file)?;
+        } else {
+            while let Some(bytes) = rx.blocking_recv() {
+                file.write_all(&bytes)?;
+            }
         }
 
-        file.flush()
+        file.flush()?;
+
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o755))?;
+
+        Ok(())
     })
     .await?;
 
@@ -43,7 +61,12 @@ where
     Ok(extracted_files)
 }
 
-pub async fn extract_zip<S>(stream: S, path: &Path) -> Result<ExtractedFiles, DownloadError>
+/// * `filter` - if `Some`, the entries it does not match are skipped.
+pub async fn extract_zip<S>(
+    stream: S,
+    path: &Path,
+    filter: Option<&ExtractFilter>,
+) -> Result<ExtractedFiles, DownloadError>
 where
     S: Stream<Item = Result<Bytes, DownloadError>> + Unpin + Send + Sync,
 {
@@ -55,34 +78,44 @@ where
     let mut extracted_files = ExtractedFiles::new();
 
     while let Some(mut zip_reader) = zip.next_with_entry().await.map_err(ZipError::from_inner)? {
-        extract_zip_entry(
+        let extracted = extract_zip_entry(
             zip_reader.reader_mut(),
             path,
             &mut buf,
             &mut extracted_files,
+            filter,
         )
         .await?;
 
-        // extract_zip_entry would read the zip_reader until read the file until
-        // eof unless extract_zip itself is cancelled or an error is raised.
-        //
-        // So calling done here should not raise any error.
-        zip = zip_reader.done().await.map_err(ZipError::from_inner)?;
+        zip = if extracted {
+            // extract_zip_entry would read the zip_reader until read the file until
+            // eof unless extract_zip itself is cancelled or an error is raised.
+            //
+            // So calling done here should not raise any error.
+            zip_reader.done().await
+        } else {
+            zip_reader.skip().await
+        }
+        .map_err(ZipError::from_inner)?;
     }
 
     Ok(extracted_files)
 }
 
+/// * `filter` - if `Some`, the entries it does not match are skipped.
 pub async fn extract_tar_based_stream<S>(
     stream: S,
     dst: &Path,
     fmt: TarBasedFmt,
+    filter: Option<&ExtractFilter>,
 ) -> Result<ExtractedFiles, DownloadError>
 where
     S: Stream<Item = Result<Bytes, DownloadError>> + Send + Sync + Unpin,
 {
     debug!("Extracting from {fmt} archive to {}", dst.display());
 
+    let filter = filter.cloned();
+
     extract_with_blocking_decoder(stream, dst, move |rx, dst| {
         // Adapted from https://docs.rs/tar/latest/src/tar/archive.rs.html#189-219
 
@@ -109,39 +142,62 @@ where
         // descendants), to ensure that directory permissions do not interfer with descendant
         // extraction.
         let mut directories = Vec::new();
+        // Symlinks created, checked once all their targets are extracted.
+        let mut symlinks = Vec::new();
 
         while let Some(mut entry) = entries.next().transpose()? {
-            match entry.header().entry_type() {
-                tar::EntryType::Regular => {
-                    // unpack_in returns false if the path contains ".."
-                    // and is skipped.
-                    if entry.unpack_in(dst)? {
-                        let path = entry.path()?;
-
-                        // create normalized_path in the same way
-                        // tar::Entry::unpack_in would normalize the path.
-                        let mut normalized_path = PathBuf::new();
-
-                        for part in path.components() {
-                            match part {
-                                Component::Prefix(..) | Component::RootDir | Component::CurDir => {
-                                    continue
-                                }
-
-                                // unpack_in would return false if this happens.
-                                Component::ParentDir => unreachable!(),
-
-                                Component::Normal(part) => normalized_path.push(part),
-                            }
-                        }
-
-                        extracted_files.add_file(&normalized_path);
+            let entry_type = entry.header().entry_type();
+            if !matches!(
+                entry_type,
+                tar::EntryType::Regular | tar::EntryType::Directory | tar::EntryType::Symlink
+            ) {
+                continue;
+            }
+
+            let path = entry.path()?.into_owned();
+            let normalized_path = normalize_tar_path(&path)
+                .ok_or_else(|| invalid_entry(&path, "its path is outside of the archive"))?;
+
+            if entry_type == tar::EntryType::Symlink {
+                let target = entry
+                    .link_name()?
+                    .ok_or_else(|| invalid_entry(&path, "it is a symlink without target"))?;
+                if !is_inside_archive(&normalized_path, &target) {
+                    return Err(invalid_entry(
+                        &path,
+                        &format!(
+                            "it is a symlink to '{}', outside of the archive",
+                            target.display()
+                        ),
+                    ));
+                }
+            }
+
+            if filter
+                .as_ref()
+                .is_some_and(|filter| !filter.matches(&normalized_path))
+            {
+                continue;
+            }
+
+            if entry_type == tar::EntryType::Directory {
+                directories.push(entry);
+                continue;
+            }
+
+            match entry.unpack_in(dst) {
+                Ok(true) => {
+                    extracted_files.add_file(&normalized_path);
+                    if entry_type == tar::EntryType::Symlink {
+                        symlinks.push(dst.join(&normalized_path));
                     }
                 }
-                tar::EntryType::Directory => {
-                    directories.push(entry);
+                Ok(false) => (),
+                // Creating symlinks requires a privilege on Windows.
+                Err(err) if cfg!(windows) && entry_type == tar::EntryType::Symlink => {
+                    warn!("Skipped symlink '{}': {err}", path.display());
                 }
-                _ => (),
+                Err(err) => return Err(err),
             }
         }
 
@@ -151,11 +207,79 @@ where
             }
         }
 
+        // A chain of symlinks can resolve to outside of the archive even if
+        // each of them points inside of it.
+        let canonical_dst = if symlinks.is_empty() {
+            PathBuf::new()
+        } else {
+            fs::canonicalize(dst)?
+        };
+        for symlink in symlinks {
+            if let Ok(resolved) = fs::canonicalize(&symlink) {
+                if !resolved.starts_with(&canonical_dst) {
+                    fs::remove_file(&symlink)?;
+                    return Err(invalid_entry(
+                        &symlink,
+                        &format!(
+                            "it resolves to '{}', outside of the archive",
+                            resolved.display()
+                        ),
+                    ));
+                }
+            }
+        }
+
         Ok(extracted_files)
     })
     .await
 }
 
+/// Normalize `path` in the same way `tar::Entry::unpack_in` would, returning
+/// `None` if it is absolute or contains "..", which it would not extract.
+fn normalize_tar_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized_path = PathBuf::new();
+
+    for part in path.components() {
+        match part {
+            Component::CurDir => continue,
+            Component::Prefix(..) | Component::RootDir | Component::ParentDir => return None,
+            Component::Normal(part) => normalized_path.push(part),
+        }
+    }
+
+    Some(normalized_path)
+}
+
+/// Return true if `target` of the symlink at `path` is relative and does
+/// not point outside of the archive, without following other symlinks.
+///
+///  * `path` - must be normalized by [`normalize_tar_path`]
+fn is_inside_archive(path: &Path, target: &Path) -> bool {
+    let mut resolved = path.parent().unwrap_or(Path::new("")).to_owned();
+
+    for part in target.components() {
+        match part {
+            Component::CurDir => (),
+            Component::Prefix(..) | Component::RootDir => return false,
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return false;
+                }
+            }
+            Component::Normal(part) => resolved.push(part),
+        }
+    }
+
+    true
+}
+
+fn invalid_entry(path: &Path, reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Refusing to extract '{}': {reason}", path.display()),
+    )
+}
+
 fn extract_with_blocking_decoder<S, F, T>(
     stream: S,
     path: &Path,
@@ -0,0 +1,191 @@
+//! Check the disk space before downloading and extracting packages, so that
+//! running out of it is reported as such rather than as a bare I/O error.
+
+use std::{error::Error, io, path::Path};
+
+use binstalk_types::cargo_toml_binstall::{PkgFmt, PkgFmtDecomposed, TarBasedFmt};
+use tracing::debug;
+
+use super::DownloadError;
+
+/// Default factor compressed packages are assumed to expand by once
+/// extracted, see [`Client::space_expansion_factor`](crate::remote::Client::space_expansion_factor).
+pub const DEFAULT_EXPANSION_FACTOR: f64 = 3.0;
+
+/// Return the space needed to extract a package of `size` bytes in `fmt`,
+/// assuming that compressed ones expand by `expansion_factor`.
+pub(super) fn extracted_size(size: u64, fmt: PkgFmt, expansion_factor: f64) -> u64 {
+    match fmt.decompose() {
+        PkgFmtDecomposed::Tar(TarBasedFmt::Tar) | PkgFmtDecomposed::Bin => size,
+        // The cast saturates on overflow.
+        _ => (size as f64 * expansion_factor.max(1.0)).ceil() as u64,
+    }
+}
+
+/// The disk space checked before downloading a package, once its size is
+/// known.
+pub(super) struct SpaceCheck<'a> {
+    /// Directory of the file the package is downloaded to.
+    pub(super) part_dir: &'a Path,
+    /// Where the package is extracted or saved to.
+    pub(super) dst: &'a Path,
+    /// Format of the package, `None` if it is saved as is.
+    pub(super) fmt: Option<PkgFmt>,
+    pub(super) expansion_factor: f64,
+}
+
+impl SpaceCheck<'_> {
+    /// Fail with [`DownloadError::InsufficientSpace`] if a package of `size`
+    /// bytes obviously does not fit.
+    pub(super) fn ensure(&self, size: u64) -> Result<(), DownloadError> {
+        ensure_space(self.part_dir, size)?;
+
+        let needed = match self.fmt {
+            Some(fmt) => extracted_size(size, fmt, self.expansion_factor),
+            None => size,
+        };
+        ensure_space(self.dst, needed)
+    }
+}
+
+/// Fail with [`DownloadError::InsufficientSpace`] if the filesystem of
+/// `path`, which does not need to exist yet, has less than `needed` bytes
+/// available.
+///
+/// Nothing is checked if the available space cannot be queried.
+pub(super) fn ensure_space(path: &Path, needed: u64) -> Result<(), DownloadError> {
+    match available_space(path) {
+        Some(available) => check(path, needed, available),
+        None => Ok(()),
+    }
+}
+
+fn check(path: &Path, needed: u64, available: u64) -> Result<(), DownloadError> {
+    if needed > available {
+        Err(DownloadError::InsufficientSpace {
+            needed,
+            available,
+            path: path.to_owned(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Return the space available on the filesystem of `path`, or of its
+/// closest existing ancestor.
+pub(super) fn available_space(path: &Path) -> Option<u64> {
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    fs4::available_space(existing)
+        .map_err(|err| {
+            debug!(
+                "Failed to get the space available on '{}': {err}",
+                existing.display()
+            )
+        })
+        .ok()
+}
+
+/// Return true if `err`, or one of its sources, is caused by the filesystem
+/// being full, i.e. `ENOSPC` on unix.
+pub(super) fn is_out_of_space(err: &DownloadError) -> bool {
+    match err {
+        DownloadError::Io(err) => caused_by_out_of_space(err),
+        DownloadError::Unzip(err) => caused_by_out_of_space(err),
+        _ => false,
+    }
+}
+
+fn caused_by_out_of_space(err: &(dyn Error + 'static)) -> bool {
+    #[cfg(unix)]
+    // ENOSPC
+    const CODES: &[i32] = &[28];
+    #[cfg(windows)]
+    // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL
+    const CODES: &[i32] = &[39, 112];
+    #[cfg(not(any(unix, windows)))]
+    const CODES: &[i32] = &[];
+
+    let mut source = Some(err);
+    while let Some(err) = source {
+        source = match err.downcast_ref::<io::Error>() {
+            Some(err) if err.raw_os_error().is_some_and(|code| CODES.contains(&code)) => {
+                return true
+            }
+            // Archive readers wrap the io errors in theirs, which are
+            // not returned by `source`.
+            Some(err) if err.get_ref().is_some() => {
+                err.get_ref().map(|err| err as &(dyn Error + 'static))
+            }
+            _ => err.source(),
+        };
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_extracted_size() {
+        assert_eq!(extracted_size(100, PkgFmt::Tar, 3.0), 100);
+        assert_eq!(extracted_size(100, PkgFmt::Bin, 3.0), 100);
+        assert_eq!(extracted_size(100, PkgFmt::Tgz, 3.0), 300);
+        assert_eq!(extracted_size(100, PkgFmt::Zip, 2.5), 250);
+        assert_eq!(extracted_size(3, PkgFmt::Txz, 1.5), 5);
+        // Packages never shrink once extracted.
+        assert_eq!(extracted_size(100, PkgFmt::Tzstd, 0.5), 100);
+        assert_eq!(extracted_size(u64::MAX, PkgFmt::Tgz, 3.0), u64::MAX);
+    }
+
+    #[test]
+    fn test_check() {
+        let path = Path::new("/tmp/staging");
+
+        check(path, 100, 100).unwrap();
+        check(path, 0, 0).unwrap();
+
+        let err = check(path, 300, 299).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                DownloadError::InsufficientSpace { needed: 300, available: 299, path }
+                    if path == &PathBuf::from("/tmp/staging")
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_available_space() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // Not created yet.
+        assert!(available_space(&dir.path().join("a/b")).is_some());
+        assert!(available_space(Path::new("relative")).is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_out_of_space() {
+        let enospc = || io::Error::from_raw_os_error(28);
+
+        assert!(is_out_of_space(&DownloadError::Io(enospc())));
+        // Wrapped like the tar crate does.
+        assert!(is_out_of_space(&DownloadError::Io(io::Error::new(
+            io::ErrorKind::Other,
+            enospc()
+        ))));
+        assert!(!is_out_of_space(&DownloadError::Io(
+            io::ErrorKind::NotFound.into()
+        )));
+    }
+}
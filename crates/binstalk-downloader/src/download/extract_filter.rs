@@ -0,0 +1,88 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Selects the entries of an archive to extract, see
+/// [`super::Download::with_extract_filter`].
+///
+/// Entries are matched by their normalized path in the archive, e.g.
+/// `cargo-binstall-v1.0.0/cargo-binstall` for `./cargo-binstall-v1.0.0/cargo-binstall`.
+#[derive(Clone)]
+pub struct ExtractFilter {
+    expected: Predicate,
+    extra: Option<Predicate>,
+}
+
+type Predicate = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+impl ExtractFilter {
+    /// Only extract the entries for which `filter` returns `true`.
+    pub fn new(filter: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            expected: Arc::new(filter),
+            extra: None,
+        }
+    }
+
+    /// Only extract `paths` and the entries under them, so that a path can
+    /// also be a directory, e.g. of shell completions.
+    pub fn paths(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let paths: Box<[PathBuf]> = paths.into_iter().collect();
+        Self::new(move |path| paths.iter().any(|expected| path.starts_with(expected)))
+    }
+
+    /// Also extract the entries for which `extra` returns `true`, e.g.
+    /// files which are used if they happen to be there.
+    ///
+    /// Unlike the expected entries, they do not prevent the whole archive
+    /// from being extracted if they are the only ones matched.
+    pub fn with_extra(mut self, extra: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        self.extra = Some(Arc::new(extra));
+        self
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.is_expected(path) || self.extra.as_ref().is_some_and(|extra| extra(path))
+    }
+
+    /// Return true if `path` is one of the expected entries, as opposed to
+    /// the extra ones.
+    pub fn is_expected(&self, path: &Path) -> bool {
+        (self.expected)(path)
+    }
+}
+
+impl fmt::Debug for ExtractFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ExtractFilter")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        let filter = ExtractFilter::paths([
+            PathBuf::from("tool-v1/tool"),
+            PathBuf::from("tool-v1/completions"),
+        ]);
+
+        assert!(filter.matches(Path::new("tool-v1/tool")));
+        assert!(filter.matches(Path::new("tool-v1/completions")));
+        assert!(filter.matches(Path::new("tool-v1/completions/tool.bash")));
+
+        assert!(!filter.matches(Path::new("tool-v1")));
+        assert!(!filter.matches(Path::new("tool-v1/tool.pdb")));
+        assert!(!filter.matches(Path::new("tool-v1/README.md")));
+        assert!(!filter.matches(Path::new("tool")));
+
+        let filter = filter.with_extra(|path| path.ends_with("Cargo.toml"));
+        assert!(filter.matches(Path::new("tool-v1/Cargo.toml")));
+        assert!(!filter.is_expected(Path::new("tool-v1/Cargo.toml")));
+        assert!(filter.is_expected(Path::new("tool-v1/tool")));
+    }
+}
@@ -21,19 +21,19 @@ impl ExtractedFilesEntry {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ExtractedFiles(pub(super) HashMap<Box<Path>, ExtractedFilesEntry>);
 
 impl ExtractedFiles {
-    pub(super) fn new() -> Self {
-        Self(Default::default())
+    pub fn new() -> Self {
+        Self::default()
     }
 
     /// * `path` - must be canonical and must not be empty
     ///
     /// NOTE that if the entry for the `path` is previously set to a dir,
     /// it would be replaced with a file.
-    pub(super) fn add_file(&mut self, path: &Path) {
+    pub fn add_file(&mut self, path: &Path) {
         self.0.insert(path.into(), ExtractedFilesEntry::File);
         self.add_dir_if_has_parent(path);
     }
@@ -53,7 +53,7 @@ impl ExtractedFiles {
     ///
     /// NOTE that if the entry for the `path` is previously set to a dir,
     /// it would be replaced with an empty Dir entry.
-    pub(super) fn add_dir(&mut self, path: &Path) {
+    pub fn add_dir(&mut self, path: &Path) {
         self.add_dir_inner(path, None);
         self.add_dir_if_has_parent(path);
     }
@@ -80,17 +80,17 @@ impl ExtractedFiles {
     }
 
     /// * `path` - must be a relative path without `.`, `..`, `/`, `prefix:/`
-    ///            and must not be empty, for these values it is guaranteed to
-    ///            return `None`.
-    ///            But could be set to "." for top-level.
+    ///   and must not be empty, for these values it is guaranteed to
+    ///   return `None`.
+    ///   But could be set to "." for top-level.
     pub fn get_entry(&self, path: &Path) -> Option<&ExtractedFilesEntry> {
         self.0.get(path)
     }
 
     /// * `path` - must be a relative path without `.`, `..`, `/`, `prefix:/`
-    ///            and must not be empty, for these values it is guaranteed to
-    ///            return `None`.
-    ///            But could be set to "." for top-level.
+    ///   and must not be empty, for these values it is guaranteed to
+    ///   return `None`.
+    ///   But could be set to "." for top-level.
     pub fn get_dir(&self, path: &Path) -> Option<&HashSet<Box<OsStr>>> {
         match self.get_entry(path)? {
             ExtractedFilesEntry::Dir(file_names) => Some(file_names),
@@ -98,11 +98,96 @@ impl ExtractedFiles {
         }
     }
 
+    /// Return the name of the only entry at the root of the archive if it
+    /// is a directory, e.g. `tool-v1.2.3-x86_64`.
+    pub fn single_top_dir(&self) -> Option<&OsStr> {
+        let mut entries = self.get_dir(Path::new("."))?.iter();
+        let name = entries.next()?;
+        if entries.next().is_some() {
+            return None;
+        }
+
+        self.get_dir(Path::new(name)).map(|_| &**name)
+    }
+
     /// * `path` - must be a relative path without `.`, `..`, `/`, `prefix:/`
-    ///            and must not be empty, for these values it is guaranteed to
-    ///            return `false`.
-    ///            But could be set to "." for top-level.
+    ///   and must not be empty, for these values it is guaranteed to
+    ///   return `false`.
+    ///   But could be set to "." for top-level.
     pub fn has_file(&self, path: &Path) -> bool {
         matches!(self.get_entry(path), Some(ExtractedFilesEntry::File))
     }
+
+    /// Add the entries of `other`, e.g. of another package extracted to
+    /// the same directory.
+    pub fn merge(&mut self, other: Self) {
+        for (path, entry) in other.0 {
+            match entry {
+                ExtractedFilesEntry::File => {
+                    self.0.insert(path, ExtractedFilesEntry::File);
+                }
+                ExtractedFilesEntry::Dir(file_names) => {
+                    self.add_dir_inner(&path, None);
+                    for file_name in file_names.iter() {
+                        self.add_dir_inner(&path, Some(file_name));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return `true` if nothing was extracted.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the paths of the extracted files, in no particular order.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.0
+            .iter()
+            .filter(|(_, entry)| matches!(entry, ExtractedFilesEntry::File))
+            .map(|(path, _)| &**path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut files = ExtractedFiles::new();
+        files.add_file(Path::new("tool-v1/tool"));
+
+        let mut other = ExtractedFiles::new();
+        other.add_file(Path::new("tool-v1/tool-helper"));
+        other.add_file(Path::new("tool-helper"));
+        files.merge(other);
+
+        assert!(files.has_file(Path::new("tool-v1/tool")));
+        assert!(files.has_file(Path::new("tool-v1/tool-helper")));
+        assert!(files.has_file(Path::new("tool-helper")));
+        assert_eq!(files.get_dir(Path::new("tool-v1")).unwrap().len(), 2);
+        assert_eq!(files.get_dir(Path::new(".")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_single_top_dir() {
+        let mut files = ExtractedFiles::new();
+        assert_eq!(files.single_top_dir(), None);
+
+        files.add_file(Path::new("tool-v1.2.3-x86_64/bin/tool"));
+        files.add_file(Path::new("tool-v1.2.3-x86_64/README.md"));
+        assert_eq!(
+            files.single_top_dir(),
+            Some(OsStr::new("tool-v1.2.3-x86_64"))
+        );
+
+        files.add_file(Path::new("LICENSE"));
+        assert_eq!(files.single_top_dir(), None);
+
+        let mut files = ExtractedFiles::new();
+        files.add_file(Path::new("tool"));
+        assert_eq!(files.single_top_dir(), None);
+    }
 }
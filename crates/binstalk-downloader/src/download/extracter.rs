@@ -6,7 +6,7 @@ use tar::Archive;
 use xz2::bufread::XzDecoder;
 use zstd::stream::Decoder as ZstdDecoder;
 
-use super::TarBasedFmt;
+use super::{CompressedBinFmt, TarBasedFmt};
 
 pub fn create_tar_decoder(
     dat: impl BufRead + 'static,
@@ -29,3 +29,17 @@ pub fn create_tar_decoder(
 
     Ok(Archive::new(r))
 }
+
+pub fn create_bin_decoder(
+    dat: impl BufRead + 'static,
+    fmt: CompressedBinFmt,
+) -> io::Result<Box<dyn Read>> {
+    use CompressedBinFmt::*;
+
+    Ok(match fmt {
+        Gz => Box::new(GzDecoder::new(dat)),
+        Xz => Box::new(XzDecoder::new(dat)),
+        // See `create_tar_decoder` for why this does not return any error.
+        Zstd => Box::new(ZstdDecoder::with_buffer(dat)?),
+    })
+}
@@ -0,0 +1,107 @@
+use thiserror::Error as ThisError;
+
+use super::{CompressedBinFmt, PkgFmt, PkgFmtDecomposed, TarBasedFmt};
+
+/// Number of bytes [`has_magic`] needs, up to the end of the `ustar` magic
+/// of tar at offset 257.
+pub(super) const MAGIC_LEN: usize = 262;
+
+/// Number of bytes of the data kept in [`UnexpectedFormat`].
+const HEAD_LEN: usize = 100;
+
+/// The data downloaded does not start with the magic number of the format
+/// expected, e.g. it is an HTML error page served with `200 OK`.
+#[derive(Debug, ThisError)]
+#[error(
+    "expected a {fmt} package, but got data of Content-Type {} starting with {head:?}",
+    .content_type.as_deref().unwrap_or("unknown")
+)]
+pub struct UnexpectedFormat {
+    pub fmt: PkgFmt,
+    /// The `Content-Type` of the response, if any.
+    pub content_type: Option<String>,
+    /// The first bytes of the data, decoded as lossy UTF-8.
+    pub head: String,
+}
+
+impl UnexpectedFormat {
+    pub(super) fn new(fmt: PkgFmt, content_type: Option<String>, head: &[u8]) -> Self {
+        Self {
+            fmt,
+            content_type,
+            head: String::from_utf8_lossy(&head[..head.len().min(HEAD_LEN)]).into_owned(),
+        }
+    }
+}
+
+/// Return true if `head`, the first [`MAGIC_LEN`] bytes of the data or all
+/// of it if shorter, starts like `fmt`.
+///
+/// Any data is a valid [`PkgFmt::Bin`].
+pub(super) fn has_magic(fmt: PkgFmt, head: &[u8]) -> bool {
+    let magic: &[u8] = match fmt.decompose() {
+        PkgFmtDecomposed::Bin => return true,
+        PkgFmtDecomposed::Tar(TarBasedFmt::Tar) => {
+            return head.get(257..262) == Some(b"ustar");
+        }
+        PkgFmtDecomposed::Tar(TarBasedFmt::Tbz2) => b"BZh",
+        PkgFmtDecomposed::Tar(TarBasedFmt::Tgz)
+        | PkgFmtDecomposed::CompressedBin(CompressedBinFmt::Gz) => &[0x1f, 0x8b],
+        PkgFmtDecomposed::Tar(TarBasedFmt::Txz)
+        | PkgFmtDecomposed::CompressedBin(CompressedBinFmt::Xz) => &[0xfd, 0x37, 0x7a, 0x58, 0x5a],
+        PkgFmtDecomposed::Tar(TarBasedFmt::Tzstd)
+        | PkgFmtDecomposed::CompressedBin(CompressedBinFmt::Zstd) => &[0x28, 0xb5, 0x2f, 0xfd],
+        PkgFmtDecomposed::Zip => b"PK",
+    };
+
+    head.starts_with(magic)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_has_magic() {
+        let html = b"<!DOCTYPE html>\n<html><head><title>Sign in</title></head></html>";
+
+        let mut tar = vec![0; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+
+        for (fmt, head) in [
+            (PkgFmt::Tar, &tar[..]),
+            (PkgFmt::Tbz2, b"BZh91AY&SY"),
+            (PkgFmt::Tgz, &[0x1f, 0x8b, 0x08, 0x00]),
+            (PkgFmt::Txz, &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]),
+            (PkgFmt::Tzstd, &[0x28, 0xb5, 0x2f, 0xfd, 0x04]),
+            (PkgFmt::Zip, b"PK\x03\x04"),
+            (PkgFmt::Bgz, &[0x1f, 0x8b, 0x08, 0x00]),
+        ] {
+            assert!(has_magic(fmt, head), "{fmt}");
+            assert!(!has_magic(fmt, html), "{fmt}");
+            assert!(!has_magic(fmt, b""), "{fmt}");
+        }
+
+        assert!(has_magic(PkgFmt::Bin, html));
+        assert!(has_magic(PkgFmt::Bin, b""));
+    }
+
+    #[test]
+    fn test_unexpected_format() {
+        let html = "<!DOCTYPE html>".repeat(10);
+        let err = UnexpectedFormat::new(
+            PkgFmt::Tgz,
+            Some("text/html; charset=utf-8".to_owned()),
+            html.as_bytes(),
+        );
+
+        assert_eq!(err.head, html[..HEAD_LEN]);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "expected a Tgz package, but got data of Content-Type text/html; charset=utf-8 starting with {:?}",
+                &html[..HEAD_LEN]
+            )
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::SeekFrom;
+
+use futures_util::StreamExt;
+use tokio::{
+    fs,
+    io::{AsyncSeekExt, AsyncWriteExt},
+};
+use tracing::{debug, warn};
+
+use super::{DownloadError, DownloadProgress, SpaceCheck};
+use crate::remote::{header, Client, StatusCode, Url};
+
+/// Maximum number of times an interrupted download is resumed.
+const MAX_RESUME_COUNT: u8 = 3;
+
+/// Download `url` to `file`, resuming it with a ranged request if the
+/// connection is dropped while receiving the body.
+///
+/// A download can only be resumed if the server advertised
+/// `Accept-Ranges: bytes` and a strong `ETag`, which is sent back in
+/// `If-Range` so that the rest of the same object is received. If the
+/// object changed, the server answers with all of it and the download
+/// restarts from the beginning.
+///
+/// Before downloading anything, fail if the `Content-Length` of the response
+/// does not pass `space_check`.
+///
+/// Return the `Content-Type` of the response, if any.
+pub(super) async fn download_to_file(
+    client: &Client,
+    url: Url,
+    file: &mut fs::File,
+    progress: Option<&dyn DownloadProgress>,
+    space_check: Option<&SpaceCheck<'_>>,
+) -> Result<Option<String>, DownloadError> {
+    let mut downloaded = 0;
+    // The `ETag` of the object being downloaded, if it can be resumed.
+    let mut etag: Option<String> = None;
+    let mut resume_count = 0;
+
+    loop {
+        let mut request = client.get(url.clone());
+        if let Some(etag) = &etag {
+            request = request
+                .header(header::RANGE.as_str(), &format!("bytes={downloaded}-"))
+                .header(header::IF_RANGE.as_str(), etag);
+        }
+        let response = request.send(true).await?;
+
+        let total = if downloaded == 0 {
+            response.content_length()
+        } else if response.status() == StatusCode::PARTIAL_CONTENT
+            && parse_content_range(response.headers()).is_some_and(|(start, _)| start == downloaded)
+        {
+            debug!("Resuming download of '{url}' from byte {downloaded}");
+
+            parse_content_range(response.headers())
+                .and_then(|(_, total)| total)
+                .or_else(|| Some(downloaded + response.content_length()?))
+        } else {
+            debug!("'{url}' changed since the download started, restarting it");
+
+            file.set_len(0).await?;
+            file.seek(SeekFrom::Start(0)).await?;
+            downloaded = 0;
+
+            response.content_length()
+        };
+
+        if let (0, Some(space_check), Some(total)) = (downloaded, space_check, total) {
+            space_check.ensure(total)?;
+        }
+
+        etag = resumable_etag(response.headers());
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+
+        let mut stream = response.bytes_stream();
+        let res = loop {
+            match stream.next().await {
+                Some(Ok(bytes)) => {
+                    file.write_all(&bytes).await?;
+                    downloaded += bytes.len() as u64;
+
+                    if let Some(progress) = progress {
+                        progress.update(downloaded, total);
+                    }
+                }
+                Some(Err(err)) => break Err(err),
+                None => break Ok(()),
+            }
+        };
+
+        match res {
+            Ok(()) => {
+                file.flush().await?;
+                break Ok(content_type);
+            }
+            // Errors in the body are caused by the connection, which can
+            // be resumed on a new one.
+            Err(err) if etag.is_some() && resume_count < MAX_RESUME_COUNT => {
+                resume_count += 1;
+                warn!(
+                    "Download of '{url}' interrupted after {downloaded} bytes, resuming it: {err}"
+                );
+            }
+            Err(err) => break Err(err.into()),
+        }
+    }
+}
+
+/// Return the `ETag` to send in `If-Range` if the response can be
+/// resumed.
+fn resumable_etag(headers: &header::HeaderMap) -> Option<String> {
+    let accept_ranges = headers.get(header::ACCEPT_RANGES)?.to_str().ok()?;
+    if !accept_ranges
+        .split(',')
+        .any(|unit| unit.trim().eq_ignore_ascii_case("bytes"))
+    {
+        return None;
+    }
+
+    let etag = headers.get(header::ETAG)?.to_str().ok()?;
+    // Weak validators cannot be used in `If-Range`.
+    etag.starts_with('"').then(|| etag.to_owned())
+}
+
+/// Parse `Content-Range: bytes <start>-<end>/<total>` into the first byte
+/// of the range and the size of the object, if known.
+fn parse_content_range(headers: &header::HeaderMap) -> Option<(u64, Option<u64>)> {
+    let range = headers
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?;
+    let (range, total) = range.split_once('/')?;
+    let (start, _end) = range.split_once('-')?;
+
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod test {
+    use std::num::{NonZeroU16, NonZeroU64};
+
+    use super::*;
+    use crate::remote::{test_server::spawn_server, Certificate, SpkiPins};
+
+    async fn download(respond: fn(&str) -> &'static str) -> Result<Vec<u8>, DownloadError> {
+        download_with_content_type(respond)
+            .await
+            .map(|(data, _)| data)
+    }
+
+    async fn download_with_content_type(
+        respond: fn(&str) -> &'static str,
+    ) -> Result<(Vec<u8>, Option<String>), DownloadError> {
+        let (url, cert) = spawn_server(respond).await;
+        let client = Client::new(
+            "binstalk-downloader-test",
+            None,
+            NonZeroU16::new(1).unwrap(),
+            NonZeroU64::new(10).unwrap(),
+            [Certificate::from_der(&cert).unwrap()],
+            SpkiPins::default(),
+            None,
+        )
+        .unwrap();
+
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let mut file = fs::File::create(&path).await.unwrap();
+        let content_type = download_to_file(&client, url, &mut file, None, None).await?;
+
+        Ok((std::fs::read(&path).unwrap(), content_type))
+    }
+
+    fn range(request: &str) -> Option<&str> {
+        request.lines().find_map(|line| {
+            let (name, value) = line.split_once(": ")?;
+            name.eq_ignore_ascii_case("range").then_some(value)
+        })
+    }
+
+    /// Answer with the first half of the body then close the connection.
+    const INTERRUPTED: &str = "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\nContent-Length: 20\r\nConnection: close\r\n\r\n0123456789";
+
+    #[tokio::test]
+    async fn test_resume() {
+        let data = download(|request| match range(request) {
+            None => INTERRUPTED,
+            Some("bytes=10-") if request.to_ascii_lowercase().contains("if-range: \"v1\"") => {
+                "HTTP/1.1 206 Partial Content\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\nContent-Range: bytes 10-19/20\r\nContent-Length: 10\r\nConnection: close\r\n\r\nabcdefghij"
+            }
+            Some(_) => "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(data, b"0123456789abcdefghij");
+    }
+
+    #[tokio::test]
+    async fn test_restart_on_changed_object() {
+        let data = download(|request| match range(request) {
+            None => INTERRUPTED,
+            // `If-Range` does not match anymore, so the whole object is sent.
+            Some(_) => "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nETag: \"v2\"\r\nContent-Length: 20\r\nConnection: close\r\n\r\nABCDEFGHIJKLMNOPQRST",
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(data, b"ABCDEFGHIJKLMNOPQRST");
+    }
+
+    #[tokio::test]
+    async fn test_content_type() {
+        let (data, content_type) = download_with_content_type(|_| {
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 6\r\nConnection: close\r\n\r\n<html>"
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(data, b"<html>");
+        assert_eq!(content_type.as_deref(), Some("text/html"));
+    }
+
+    #[tokio::test]
+    async fn test_not_resumable() {
+        download(|_| {
+            "HTTP/1.1 200 OK\r\nContent-Length: 20\r\nConnection: close\r\n\r\n0123456789"
+        })
+        .await
+        .unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        let parse = |value| {
+            let mut headers = header::HeaderMap::new();
+            headers.insert(header::CONTENT_RANGE, value);
+            parse_content_range(&headers)
+        };
+
+        assert_eq!(
+            parse(header::HeaderValue::from_static("bytes 10-19/20")),
+            Some((10, Some(20)))
+        );
+        assert_eq!(
+            parse(header::HeaderValue::from_static("bytes 10-19/*")),
+            Some((10, None))
+        );
+        assert_eq!(parse(header::HeaderValue::from_static("bytes */20")), None);
+    }
+}
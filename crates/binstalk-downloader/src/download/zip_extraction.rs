@@ -1,7 +1,8 @@
 use std::{
     borrow::Cow,
-    io::Write,
+    io::{self, Write},
     path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering::Relaxed},
 };
 
 use async_zip::{
@@ -16,8 +17,9 @@ use tokio::{
     sync::mpsc,
 };
 use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tracing::{debug, warn};
 
-use super::{DownloadError, ExtractedFiles};
+use super::{DownloadError, ExtractFilter, ExtractedFiles};
 use crate::utils::asyncify;
 
 #[derive(Debug, ThisError)]
@@ -39,12 +41,15 @@ impl ZipError {
     }
 }
 
+/// Return `false` if the entry is skipped since `filter` does not match it,
+/// in which case it is left unread.
 pub(super) async fn extract_zip_entry<R>(
     zip_reader: &mut ZipEntryReader<'_, R, WithEntry<'_>>,
     path: &Path,
     buf: &mut BytesMut,
     extracted_files: &mut ExtractedFiles,
-) -> Result<(), DownloadError>
+    filter: Option<&ExtractFilter>,
+) -> Result<bool, DownloadError>
 where
     R: futures_io::AsyncBufRead + Unpin + Send + Sync,
 {
@@ -52,6 +57,10 @@ where
     let raw_filename = zip_reader.entry().filename();
     let (filename, is_dir) = check_filename_and_normalize(raw_filename)?;
 
+    if filter.is_some_and(|filter| !filter.matches(&filename)) {
+        return Ok(false);
+    }
+
     // Calculates the outpath
     let outpath = path.join(&filename);
 
@@ -70,7 +79,13 @@ where
             //
             // If it is a file, then it needs to be at least readable for the
             // current user.
-            let mode: u16 = mode | if is_dir { 0o700 } else { 0o400 };
+            //
+            // The file type, setuid, setgid and sticky bits are dropped, as
+            // they are from tar archives.
+            //
+            // Note that the external attributes are only in the central
+            // directory, so they are unknown when the archive is streamed.
+            let mode: u16 = (mode & 0o777) | if is_dir { 0o700 } else { 0o400 };
             perms = Some(Permissions::from_mode(mode as u32));
         }
     }
@@ -82,7 +97,7 @@ where
         asyncify(move || {
             std::fs::create_dir_all(&outpath)?;
             if let Some(perms) = perms {
-                std::fs::set_permissions(&outpath, perms)?;
+                skip_unsupported_permissions(std::fs::set_permissions(&outpath, perms), &outpath)?;
             }
 
             Ok(())
@@ -109,7 +124,7 @@ where
             outfile.flush()?;
 
             if let Some(perms) = perms {
-                outfile.set_permissions(perms)?;
+                skip_unsupported_permissions(outfile.set_permissions(perms), &outpath)?;
             }
 
             Ok(())
@@ -139,7 +154,41 @@ where
         .await?;
     }
 
-    Ok(())
+    Ok(true)
+}
+
+/// Return true if `err` indicates that the filesystem does not support
+/// permissions: `Unsupported`, or `EPERM` returned by e.g. vfat on Linux,
+/// but not `EACCES` returned for files the user may not write.
+fn is_unsupported(err: &io::Error) -> bool {
+    /// `EPERM` on unix.
+    #[cfg(unix)]
+    const EPERM: i32 = 1;
+
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(EPERM) {
+        return true;
+    }
+
+    err.kind() == io::ErrorKind::Unsupported
+}
+
+/// Ignore failure to set permissions of `path` if the filesystem does not
+/// support it, e.g. vfat.
+fn skip_unsupported_permissions(res: io::Result<()>, path: &Path) -> io::Result<()> {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    match res {
+        Err(err) if is_unsupported(&err) => {
+            if WARNED.swap(true, Relaxed) {
+                debug!("Skipped setting permissions of '{}': {err}", path.display());
+            } else {
+                warn!("Skipped setting permissions of '{}': {err}", path.display());
+            }
+            Ok(())
+        }
+        res => res,
+    }
 }
 
 async fn copy_file_to_mpsc<R>(
@@ -1,60 +1,194 @@
 use std::{
-    collections::HashMap,
-    ops::Deref,
+    collections::hash_map::RandomState,
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    io, slice,
+    str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering::Relaxed},
-        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, AtomicU32, Ordering::Relaxed},
+        Arc,
     },
     time::{Duration, Instant},
 };
 
+use bytes::Bytes;
 use compact_str::CompactString;
-use percent_encoding::{
-    percent_decode_str, utf8_percent_encode, AsciiSet, PercentEncode, CONTROLS,
+use futures_util::Stream;
+use thiserror::Error as ThisError;
+use tokio::{
+    sync::{Mutex as AsyncMutex, OnceCell},
+    time::{sleep, sleep_until},
 };
-use tokio::sync::OnceCell;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+use url::Position;
 
-use crate::remote;
+use crate::{
+    remote,
+    utils::{percent_decode_http_url_path, percent_encode_http_url_path, Map},
+};
+
+mod artifact_check;
+pub use artifact_check::ArtifactCheck;
+
+mod auth;
+pub use auth::GhAuthCheck;
+
+mod host;
+pub use host::{GhApiHost, GITHUB_COM};
+
+mod release_cache;
+pub use release_cache::GhReleaseCache;
+
+mod repo_info;
+pub use repo_info::GhRepoInfo;
+
+mod state;
+pub use state::GhApiState;
 
 mod request;
-pub use request::{GhApiContextError, GhApiError, GhGraphQLErrors};
+pub use request::{
+    GhApiContextError, GhApiError, GhGraphQLErrors, GhReleaseArtifactInfo, GhReleaseArtifacts,
+    GhReleaseMeta, RateLimitStatus,
+};
 
 /// default retry duration if x-ratelimit-reset is not found in response header
 const DEFAULT_RETRY_DURATION: Duration = Duration::from_secs(10 * 60);
 
-fn percent_encode_http_url_path(path: &str) -> PercentEncode<'_> {
-    /// https://url.spec.whatwg.org/#fragment-percent-encode-set
-    const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+/// Default time to live of a release cached as not found, it may be
+/// published any time.
+const DEFAULT_NOT_FOUND_TTL: Duration = Duration::from_secs(5 * 60);
 
-    /// https://url.spec.whatwg.org/#path-percent-encode-set
-    const PATH: &AsciiSet = &FRAGMENT.add(b'#').add(b'?').add(b'{').add(b'}');
+/// Default maximum number of releases and of repositories kept in memory,
+/// far more than a run of the CLI looks up.
+const DEFAULT_MAX_CACHED_RELEASES: usize = 1024;
 
-    const PATH_SEGMENT: &AsciiSet = &PATH.add(b'/').add(b'%');
-
-    // The backslash (\) character is treated as a path separator in special URLs
-    // so it needs to be additionally escaped in that case.
-    //
-    // http is considered to have special path.
-    const SPECIAL_PATH_SEGMENT: &AsciiSet = &PATH_SEGMENT.add(b'\\');
-
-    utf8_percent_encode(path, SPECIAL_PATH_SEGMENT)
+/// The keys required to identify a github release.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GhRelease {
+    pub owner: CompactString,
+    pub repo: CompactString,
+    /// Empty for the latest release, see [`GhRelease::is_latest`].
+    pub tag: CompactString,
 }
 
-fn percent_decode_http_url_path(input: &str) -> CompactString {
-    if input.contains('%') {
-        percent_decode_str(input).decode_utf8_lossy().into()
-    } else {
-        // No '%', no need to decode.
-        CompactString::new(input)
+impl GhRelease {
+    /// Return `true` if this is the latest release of the repository instead
+    /// of the release of a tag, e.g. from
+    /// `https://github.com/{owner}/{repo}/releases/latest/download/{file}`.
+    ///
+    /// It is resolved to the release of the latest tag when looking up its
+    /// artifacts with [`GhApiClient::has_release_artifacts`].
+    pub fn is_latest(&self) -> bool {
+        self.tag.is_empty()
+    }
+
+    fn repo(&self) -> GhRepo {
+        GhRepo {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+        }
     }
 }
 
-/// The keys required to identify a github release.
+/// The keys required to identify a github repository.
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
-pub struct GhRelease {
+pub struct GhRepo {
     pub owner: CompactString,
     pub repo: CompactString,
-    pub tag: CompactString,
+}
+
+impl GhRepo {
+    /// Extract the repository from its url on [`GITHUB_COM`] in any of the
+    /// forms found in the `repository` of crate manifests, e.g.
+    ///  - `https://github.com/owner/repo.git`
+    ///  - `https://github.com/owner/repo/tree/main/subcrate`
+    ///  - `git+https://github.com/owner/repo`
+    ///  - `ssh://git@github.com/owner/repo`
+    ///  - `git@github.com:owner/repo.git`
+    pub fn try_extract_from_url(url: &str) -> Option<Self> {
+        let url = url.trim();
+        let url = url.strip_prefix("git+").unwrap_or(url);
+
+        let (authority, path) = match url.split_once("://") {
+            Some((scheme, rest)) => {
+                if !["https", "http", "ssh", "git"]
+                    .iter()
+                    .any(|s| scheme.eq_ignore_ascii_case(s))
+                {
+                    return None;
+                }
+
+                let (authority, path) = rest.split_once('/')?;
+                // Strip the port.
+                let authority = authority
+                    .split_once(':')
+                    .map_or(authority, |(host, _)| host);
+                (authority, path)
+            }
+            // The scp-like syntax of ssh, e.g. `git@github.com:owner/repo`.
+            None => url.split_once(':')?,
+        };
+
+        // Strip the user, e.g. `git@`.
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        if !(host.eq_ignore_ascii_case(GITHUB_COM) || host.eq_ignore_ascii_case("www.github.com")) {
+            return None;
+        }
+
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        // Ignore empty segments from leading, trailing or duplicate slashes,
+        // and everything after the repository, e.g. `/tree/main/subcrate`.
+        let mut path_segments = path.split('/').filter(|s| !s.is_empty());
+
+        let owner = path_segments.next()?;
+        let repo = path_segments.next()?;
+        let repo = repo.strip_suffix(".git").unwrap_or(repo);
+        if repo.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            owner: percent_decode_http_url_path(owner),
+            repo: percent_decode_http_url_path(repo),
+        })
+    }
+
+    /// The canonical url of the repository,
+    /// `https://github.com/{owner}/{repo}`.
+    pub fn url(&self) -> remote::Url {
+        let mut url = remote::Url::parse("https://github.com").expect("valid url");
+        url.path_segments_mut()
+            .expect("https url has a path")
+            .push(&self.owner)
+            .push(&self.repo);
+        url
+    }
+
+    /// The release of this repository tagged `tag`.
+    pub fn release(&self, tag: CompactString) -> GhRelease {
+        GhRelease {
+            owner: self.owner.clone(),
+            repo: self.repo.clone(),
+            tag,
+        }
+    }
+}
+
+#[derive(Debug, ThisError)]
+#[error("'{0}' is not the url of a repository on github.com")]
+pub struct GhRepoParseError(CompactString);
+
+/// Parse the url of the repository, see [`GhRepo::try_extract_from_url`].
+impl FromStr for GhRepo {
+    type Err = GhRepoParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_extract_from_url(s).ok_or_else(|| GhRepoParseError(s.into()))
+    }
 }
 
 /// The Github Release and one of its artifact.
@@ -65,9 +199,18 @@ pub struct GhReleaseArtifact {
 }
 
 impl GhReleaseArtifact {
-    /// Create [`GhReleaseArtifact`] from url.
-    pub fn try_extract_from_url(url: &remote::Url) -> Option<Self> {
-        if url.domain() != Some("github.com") {
+    /// Create [`GhReleaseArtifact`] from the url of a release download on
+    /// one of `hosts`, e.g. [`GITHUB_COM`] or [`GhApiClient::host`].
+    ///
+    /// The port of `url`, if it is not the default one, must match the one
+    /// of the host.
+    pub fn try_extract_from_url(url: &remote::Url, hosts: &[&str]) -> Option<Self> {
+        url.domain()?;
+        let authority = &url[Position::BeforeHost..Position::AfterPort];
+        if !hosts
+            .iter()
+            .any(|host| authority.eq_ignore_ascii_case(host))
+        {
             return None;
         }
 
@@ -76,11 +219,13 @@ impl GhReleaseArtifact {
         let owner = path_segments.next()?;
         let repo = path_segments.next()?;
 
-        if (path_segments.next()?, path_segments.next()?) != ("releases", "download") {
-            return None;
-        }
-
-        let tag = path_segments.next()?;
+        // Git tags cannot be empty, so an empty tag refers to the latest
+        // release.
+        let tag = match (path_segments.next()?, path_segments.next()?) {
+            ("releases", "download") => path_segments.next().filter(|tag| !tag.is_empty())?,
+            ("releases", "latest") if path_segments.next()? == "download" => "",
+            _ => return None,
+        };
         let artifact_name = path_segments.next()?;
 
         (path_segments.next().is_none() && url.fragment().is_none() && url.query().is_none()).then(
@@ -96,72 +241,627 @@ impl GhReleaseArtifact {
     }
 }
 
+/// Return the owner of the repository `url` in the restful API is about, e.g.
+/// `https://api.github.com/repos/{owner}/{repo}/releases/assets/{id}`.
+fn api_url_owner(url: &remote::Url) -> Option<&str> {
+    let mut path_segments = url.path_segments()?;
+    path_segments.find(|segment| *segment == "repos")?;
+    path_segments.next().filter(|owner| !owner.is_empty())
+}
+
+/// Number of API requests sent and the maximum allowed.
 #[derive(Debug)]
-struct Map<K, V>(RwLock<HashMap<K, Arc<V>>>);
+struct RequestBudget {
+    limit: Option<u32>,
+    used: AtomicU32,
+}
+
+impl RequestBudget {
+    /// Return `false` without consuming the budget if it is exhausted.
+    fn try_consume(&self) -> bool {
+        self.used
+            .fetch_update(Relaxed, Relaxed, |used| {
+                if self.limit.map_or(true, |limit| used < limit) {
+                    Some(used + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.limit
+            .is_some_and(|limit| self.used.load(Relaxed) >= limit)
+    }
+}
+
+/// Retry of the requests failing with a transient error, e.g. a 502 or a
+/// connection reset, see [`GhApiError::is_transient`].
+///
+/// The delay before each retry is multiplied by `backoff_factor` and
+/// randomized by up to 25% so that concurrent lookups don't retry at once.
+#[derive(Copy, Clone, Debug)]
+pub struct GhApiRetryPolicy {
+    /// Number of retries after the first attempt.
+    pub max_retries: u8,
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+    pub backoff_factor: u32,
+}
 
-impl<K, V> Default for Map<K, V> {
+/// 3 retries, after 1s, 3s and 9s.
+impl Default for GhApiRetryPolicy {
     fn default() -> Self {
-        Self(Default::default())
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_secs(1),
+            backoff_factor: 3,
+        }
     }
 }
 
-impl<K, V> Map<K, V>
-where
-    K: Eq + std::hash::Hash,
-    V: Default,
-{
-    fn get(&self, k: K) -> Arc<V> {
-        let optional_value = self.0.read().unwrap().deref().get(&k).cloned();
-        optional_value.unwrap_or_else(|| Arc::clone(self.0.write().unwrap().entry(k).or_default()))
+impl GhApiRetryPolicy {
+    /// Return the delay before the `retry`-th retry, starting from 1.
+    fn delay(&self, retry: u8) -> Duration {
+        let delay = self
+            .initial_delay
+            .saturating_mul(self.backoff_factor.saturating_pow(u32::from(retry) - 1));
+
+        // `RandomState` is randomly seeded, avoiding a dependency on `rand`.
+        let random = RandomState::new().build_hasher().finish();
+        delay.mul_f64(0.75 + (random % 1000) as f64 / 2000.0)
     }
 }
 
 #[derive(Debug)]
 struct Inner {
     client: remote::Client,
-    release_artifacts: Map<GhRelease, OnceCell<Option<request::Artifacts>>>,
-    retry_after: Mutex<Option<Instant>>,
+    host: GhApiHost,
+    release_artifacts: Map<GhRelease, AsyncMutex<CachedReleaseArtifacts>>,
+    /// Time to live of the releases in `release_artifacts` not found.
+    not_found_ttl: Duration,
+    /// Artifacts of releases fetched by earlier processes.
+    release_cache: Option<GhReleaseCache>,
+    /// Tag of the latest release of each repository.
+    latest_releases: Map<GhRepo, OnceCell<Option<CompactString>>>,
+    /// Information about each repository.
+    repo_infos: Map<GhRepo, OnceCell<Option<GhRepoInfo>>>,
+    /// Tags of the releases of each repository, newest first.
+    releases: Map<GhRepo, OnceCell<Vec<CompactString>>>,
+    /// Result of [`GhApiClient::check_auth`].
+    auth_check: OnceCell<GhAuthCheck>,
+    state: GhApiState,
+    budget: RequestBudget,
+    retry_policy: GhApiRetryPolicy,
+    /// Number of API requests sent with any of `auth_tokens`.
+    authenticated_requests: AtomicU32,
+    /// Number of lookups answered by a release already fetched.
+    release_cache_hits: AtomicU32,
+    /// Number of malformed responses received, the API is not used again
+    /// once any is received.
+    malformed_responses: AtomicU32,
+    /// Set once the API could not tell whether an artifact exists, see
+    /// [`GhApiClient::is_api_usable`].
+    api_unusable: AtomicBool,
+}
+
+/// Artifacts of a release in [`Inner::release_artifacts`], the lock is held
+/// while fetching them so that concurrent lookups fetch them once.
+#[derive(Debug, Default)]
+enum CachedReleaseArtifacts {
+    #[default]
+    NotFetched,
+    NotFound {
+        fetched_at: Instant,
+    },
+    /// Published releases are immutable, so they never expire.
+    Found(GhReleaseArtifacts),
+}
+
+impl CachedReleaseArtifacts {
+    /// Return `true` if the cached result can be used without fetching the
+    /// release again: it is found, or not found less than `not_found_ttl` ago.
+    fn is_fresh(&self, not_found_ttl: Duration) -> bool {
+        match self {
+            Self::NotFetched => false,
+            Self::NotFound { fetched_at } => fetched_at.elapsed() < not_found_ttl,
+            Self::Found(_) => true,
+        }
+    }
+
+    fn set(&mut self, artifacts: Option<GhReleaseArtifacts>) {
+        *self = match artifacts {
+            Some(artifacts) => Self::Found(artifacts),
+            None => Self::NotFound {
+                fetched_at: Instant::now(),
+            },
+        };
+    }
 
-    auth_token: Option<CompactString>,
-    is_auth_token_valid: AtomicBool,
+    fn artifacts(&self) -> Option<&GhReleaseArtifacts> {
+        match self {
+            Self::Found(artifacts) => Some(artifacts),
+            _ => None,
+        }
+    }
 }
 
 /// Github API client for querying whether a release artifact exitsts.
-/// Can only handle github.com for now.
+///
+/// It handles the releases of one host, github.com by default or a GitHub
+/// Enterprise Server set with [`GhApiClient::with_host`]. The API base url
+/// is derived from the [`GhApiHost`]: `https://api.github.com` for
+/// github.com, `https://<host>/api/v3` and `https://<host>/api/graphql` for
+/// a GitHub Enterprise Server.
 #[derive(Clone, Debug)]
 pub struct GhApiClient(Arc<Inner>);
 
 impl GhApiClient {
-    pub fn new(client: remote::Client, auth_token: Option<CompactString>) -> Self {
+    /// * `auth_tokens` - tokens used in turn, rotating to the next one when
+    ///   the current one is rate limited, until its rate limit resets, or
+    ///   unauthorized. Requests are sent without token once all of them are
+    ///   unauthorized.
+    pub fn new(
+        client: remote::Client,
+        auth_tokens: impl IntoIterator<Item = CompactString>,
+    ) -> Self {
+        Self::with_request_budget(client, auth_tokens, None)
+    }
+
+    /// * `auth_tokens` - see [`GhApiClient::new`].
+    /// * `budget` - maximum number of API requests (GraphQL and restful
+    ///   alike) this client and its clones would send, once it is used up,
+    ///   lookups return [`HasReleaseArtifact::BudgetExhausted`].
+    pub fn with_request_budget(
+        client: remote::Client,
+        auth_tokens: impl IntoIterator<Item = CompactString>,
+        budget: Option<u32>,
+    ) -> Self {
+        Self::with_state(client, GhApiState::new(auth_tokens), budget)
+    }
+
+    /// Create a client sharing `state` with the other clients created with
+    /// it, so that the rate limit reached by any of them is respected by
+    /// all, and so are the tokens found to be unauthorized.
+    ///
+    /// * `budget` - see [`GhApiClient::with_request_budget`], it is not
+    ///   shared.
+    pub fn with_state(client: remote::Client, state: GhApiState, budget: Option<u32>) -> Self {
         Self(Arc::new(Inner {
             client,
-            release_artifacts: Default::default(),
-            retry_after: Default::default(),
-
-            auth_token,
-            is_auth_token_valid: AtomicBool::new(true),
+            host: GhApiHost::default(),
+            release_artifacts: Map::with_max_len(DEFAULT_MAX_CACHED_RELEASES),
+            not_found_ttl: DEFAULT_NOT_FOUND_TTL,
+            release_cache: None,
+            latest_releases: Map::with_max_len(DEFAULT_MAX_CACHED_RELEASES),
+            repo_infos: Map::with_max_len(DEFAULT_MAX_CACHED_RELEASES),
+            releases: Map::with_max_len(DEFAULT_MAX_CACHED_RELEASES),
+            auth_check: OnceCell::new(),
+            state,
+            budget: RequestBudget {
+                limit: budget,
+                used: AtomicU32::new(0),
+            },
+            retry_policy: GhApiRetryPolicy::default(),
+            authenticated_requests: AtomicU32::new(0),
+            release_cache_hits: AtomicU32::new(0),
+            malformed_responses: AtomicU32::new(0),
+            api_unusable: AtomicBool::new(false),
         }))
     }
+
+    /// The state of the rate limit and of the auth tokens of this client,
+    /// to create other clients sharing it with [`GhApiClient::with_state`].
+    pub fn state(&self) -> GhApiState {
+        self.0.state.clone()
+    }
+
+    fn shared(&self) -> &state::State {
+        &self.0.state.0
+    }
+
+    /// Use the API of `host` instead of the one of github.com.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn with_host(mut self, host: GhApiHost) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("GhApiClient::with_host must be called before cloning it")
+            .host = host;
+        self
+    }
+
+    /// Retry the release lookups failing with a transient error according
+    /// to `retry_policy`, instead of [`GhApiRetryPolicy::default`].
+    ///
+    /// Must be called before the client is cloned.
+    pub fn with_retry_policy(mut self, retry_policy: GhApiRetryPolicy) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("GhApiClient::with_retry_policy must be called before cloning it")
+            .retry_policy = retry_policy;
+        self
+    }
+
+    /// Fetch the releases not found again once they were looked up for
+    /// longer than `ttl`, instead of 5 minutes, as they may be published
+    /// any time. The releases found are never fetched again.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn with_not_found_ttl(mut self, ttl: Duration) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("GhApiClient::with_not_found_ttl must be called before cloning it")
+            .not_found_ttl = ttl;
+        self
+    }
+
+    /// Keep at most `max` releases in memory instead of 1024, evicting the
+    /// least recently used ones, and as many repositories for each of the
+    /// latest releases, the releases and the information of repositories.
+    ///
+    /// The releases being fetched are never evicted, so they are still
+    /// fetched only once.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn with_max_cached_releases(mut self, max: usize) -> Self {
+        let inner = Arc::get_mut(&mut self.0)
+            .expect("GhApiClient::with_max_cached_releases must be called before cloning it");
+        inner.release_artifacts.set_max_len(max);
+        inner.latest_releases.set_max_len(max);
+        inner.repo_infos.set_max_len(max);
+        inner.releases.set_max_len(max);
+        self
+    }
+
+    /// Look up the artifacts of releases in `cache` before sending any
+    /// request, and save the ones fetched there.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn with_release_cache(mut self, cache: GhReleaseCache) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("GhApiClient::with_release_cache must be called before cloning it")
+            .release_cache = Some(cache);
+        self
+    }
+
+    /// Write the releases fetched so far to the cache set by
+    /// [`GhApiClient::with_release_cache`], it is otherwise written when
+    /// the last clone of this client is dropped.
+    pub fn flush_release_cache(&self) -> io::Result<()> {
+        self.0
+            .release_cache
+            .as_ref()
+            .map_or(Ok(()), GhReleaseCache::flush)
+    }
+
+    /// Forget the releases, latest releases and repositories fetched so far,
+    /// so that they are fetched again by the later lookups.
+    ///
+    /// The cache set by [`GhApiClient::with_release_cache`] is kept.
+    pub fn clear_cache(&self) {
+        self.0.release_artifacts.clear();
+        self.0.latest_releases.clear();
+        self.0.repo_infos.clear();
+        self.0.releases.clear();
+    }
+
+    /// Number of releases kept in memory, see
+    /// [`GhApiClient::with_max_cached_releases`].
+    pub fn cache_len(&self) -> usize {
+        self.0.release_artifacts.len()
+    }
+
+    /// The GitHub instance whose API is used, only release artifacts on its
+    /// host can be looked up.
+    pub fn host(&self) -> &GhApiHost {
+        &self.0.host
+    }
+
+    /// Rate limit sent by GitHub with the last response, `None` if no
+    /// response was received yet.
+    ///
+    /// It can be used to switch to HEAD/GET before the rate limit is
+    /// reached.
+    pub fn rate_limit_status(&self) -> Option<RateLimitStatus> {
+        *self.shared().rate_limit.lock().unwrap()
+    }
+
+    /// Return `true` if the rate limit was reached and is not reset yet,
+    /// in which case lookups return [`HasReleaseArtifact::RateLimit`]
+    /// without sending any request.
+    pub fn is_rate_limited(&self) -> bool {
+        self.shared()
+            .retry_after
+            .lock()
+            .unwrap()
+            .is_some_and(|retry_after| retry_after.elapsed().is_zero())
+    }
+
+    /// Number of API requests sent so far.
+    pub fn requests_used(&self) -> u32 {
+        self.0.budget.used.load(Relaxed)
+    }
+
+    /// Number of API requests that can still be sent, `None` if unlimited.
+    pub fn requests_remaining(&self) -> Option<u32> {
+        self.0
+            .budget
+            .limit
+            .map(|limit| limit.saturating_sub(self.requests_used()))
+    }
+
+    /// Number of API requests sent with the auth token so far, the others
+    /// are unauthenticated.
+    pub fn authenticated_requests(&self) -> u32 {
+        self.0.authenticated_requests.load(Relaxed)
+    }
+
+    /// Return `true` if the requests are sent with a token, i.e. not all of
+    /// them are known to be unauthorized.
+    pub fn has_auth_token(&self) -> bool {
+        self.shared()
+            .auth_token_states
+            .iter()
+            .any(|state| !state.lock().unwrap().rejected)
+    }
+
+    /// Number of lookups answered from a release fetched earlier, without
+    /// sending any request.
+    pub fn release_cache_hits(&self) -> u32 {
+        self.0.release_cache_hits.load(Relaxed)
+    }
+
+    /// Number of responses that could not be deserialized, e.g. because a
+    /// proxy truncated or rewrote them.
+    pub fn malformed_responses(&self) -> u32 {
+        self.0.malformed_responses.load(Relaxed)
+    }
+
+    /// Format the number of API requests used and remaining.
+    pub fn budget_usage(&self) -> impl fmt::Display + '_ {
+        BudgetUsage(self)
+    }
+}
+
+struct BudgetUsage<'a>(&'a GhApiClient);
+
+impl fmt::Display for BudgetUsage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = self.0.requests_used();
+        let plural = if used == 1 { "" } else { "s" };
+
+        match self.0.requests_remaining() {
+            Some(remaining) => write!(
+                f,
+                "{used} GitHub API request{plural} used, {remaining} remaining in budget"
+            ),
+            None => write!(f, "{used} GitHub API request{plural} used"),
+        }
+    }
 }
 
 enum FetchReleaseArtifactError {
     Error(GhApiError),
     RateLimit { retry_after: Instant },
     Unauthorized,
+    BudgetExhausted,
+    MalformedResponse,
+}
+
+impl From<FetchReleaseArtifactError> for GhApiError {
+    fn from(err: FetchReleaseArtifactError) -> Self {
+        use FetchReleaseArtifactError as Error;
+
+        match err {
+            Error::Error(err) => err,
+            Error::RateLimit { retry_after } => Self::RateLimit { retry_after },
+            Error::Unauthorized => Self::Unauthorized,
+            Error::BudgetExhausted => Self::BudgetExhausted,
+            Error::MalformedResponse => Self::MalformedResponse,
+        }
+    }
+}
+
+/// Hooks of the requests sent with the auth token if `authenticated`.
+struct Hooks<'a> {
+    client: &'a GhApiClient,
+    authenticated: bool,
+}
+
+impl request::RequestHooks for Hooks<'_> {
+    fn consume_budget(&mut self) -> bool {
+        let inner = &self.client.0;
+        let consumed = inner.budget.try_consume();
+        if consumed && self.authenticated {
+            inner.authenticated_requests.fetch_add(1, Relaxed);
+        }
+        consumed
+    }
+
+    fn record_rate_limit(&mut self, status: RateLimitStatus) {
+        *self.client.shared().rate_limit.lock().unwrap() = Some(status);
+    }
 }
 
 impl GhApiClient {
+    fn hooks(&self, auth_token: Option<&str>) -> Hooks<'_> {
+        Hooks {
+            client: self,
+            authenticated: auth_token.is_some(),
+        }
+    }
+
     async fn do_fetch_release_artifacts(
         &self,
         release: &GhRelease,
+        cached: Option<&GhReleaseArtifacts>,
+        auth_token: Option<&str>,
+    ) -> Result<Option<GhReleaseArtifacts>, FetchReleaseArtifactError> {
+        let retry_policy = self.0.retry_policy;
+        let mut retry = 0;
+
+        let res = loop {
+            let res = request::fetch_release_artifacts(
+                &self.0.client,
+                &self.0.host,
+                release,
+                cached,
+                auth_token,
+                &mut self.hooks(auth_token),
+            )
+            .await;
+
+            match res {
+                Err(err) if err.is_transient() && retry < retry_policy.max_retries => {
+                    retry += 1;
+                    let delay = retry_policy.delay(retry);
+                    warn!(
+                        "Failed to fetch GitHub release {}/{}@{}, retrying in {delay:?}: {err}",
+                        release.owner, release.repo, release.tag
+                    );
+                    sleep(delay).await;
+                }
+                Err(err) if retry > 0 => {
+                    break Err(err.context(format_args!("failed after {} attempts", retry + 1)))
+                }
+                res => break res,
+            }
+        };
+
+        self.handle_fetch_release_ret(res)
+    }
+
+    async fn do_fetch_latest_release(
+        &self,
+        repo: &GhRepo,
+        auth_token: Option<&str>,
+    ) -> Result<Option<CompactString>, FetchReleaseArtifactError> {
+        let res = request::fetch_latest_release(
+            &self.0.client,
+            &self.0.host,
+            repo,
+            auth_token,
+            &mut self.hooks(auth_token),
+        )
+        .await;
+
+        self.handle_fetch_release_ret(res)
+    }
+
+    async fn do_fetch_releases(
+        &self,
+        repo: &GhRepo,
+        auth_token: Option<&str>,
+    ) -> Result<Option<Vec<CompactString>>, FetchReleaseArtifactError> {
+        let res = request::fetch_releases(
+            &self.0.client,
+            &self.0.host,
+            repo,
+            auth_token,
+            &mut self.hooks(auth_token),
+        )
+        .await;
+
+        self.handle_fetch_release_ret(res)
+    }
+
+    async fn do_fetch_repo_info(
+        &self,
+        repo: &GhRepo,
+        auth_token: Option<&str>,
+    ) -> Result<Option<GhRepoInfo>, FetchReleaseArtifactError> {
+        let res = repo_info::fetch_repo_info(
+            &self.0.client,
+            &self.0.host,
+            repo,
+            auth_token,
+            &mut self.hooks(auth_token),
+        )
+        .await;
+
+        self.handle_fetch_release_ret(res)
+    }
+
+    async fn do_fetch_auth_check(
+        &self,
+        auth_token: &str,
+    ) -> Result<Option<GhAuthCheck>, FetchReleaseArtifactError> {
+        let res = auth::fetch_auth_check(
+            &self.0.client,
+            &self.0.host,
+            auth_token,
+            &mut self.hooks(Some(auth_token)),
+        )
+        .await;
+
+        self.handle_fetch_release_ret(res)
+    }
+
+    async fn do_fetch_artifact_url(
+        &self,
+        GhReleaseArtifact {
+            release,
+            artifact_name,
+        }: &GhReleaseArtifact,
+        auth_token: Option<&str>,
+    ) -> Result<Option<CompactString>, FetchReleaseArtifactError> {
+        let res = request::fetch_release_artifacts_restful_api(
+            &self.0.client,
+            &self.0.host,
+            release,
+            None,
+            auth_token,
+            &mut self.hooks(auth_token),
+        )
+        .await;
+
+        Ok(self
+            .handle_fetch_release_ret(res)?
+            .and_then(|artifacts| artifacts.get(artifact_name)?.url.clone()))
+    }
+
+    async fn do_download_artifact(
+        &self,
+        artifact_url: &remote::Url,
+        auth_token: Option<&str>,
+    ) -> Result<Option<remote::Response>, FetchReleaseArtifactError> {
+        let res = request::download_release_artifact(
+            &self.0.client,
+            artifact_url.clone(),
+            auth_token,
+            &mut self.hooks(auth_token),
+        )
+        .await;
+
+        self.handle_fetch_release_ret(res)
+    }
+
+    async fn do_fetch_releases_artifacts(
+        &self,
+        releases: &[&GhRelease],
         auth_token: Option<&str>,
-    ) -> Result<Option<request::Artifacts>, FetchReleaseArtifactError> {
+    ) -> Result<Option<Vec<Option<GhReleaseArtifacts>>>, FetchReleaseArtifactError> {
+        let res = request::fetch_releases_artifacts(
+            &self.0.client,
+            &self.0.host,
+            releases,
+            auth_token,
+            &mut self.hooks(auth_token),
+        )
+        .await;
+
+        self.handle_fetch_release_ret(res)
+    }
+
+    fn handle_fetch_release_ret<T>(
+        &self,
+        res: Result<request::FetchReleaseRet<T>, GhApiError>,
+    ) -> Result<Option<T>, FetchReleaseArtifactError> {
         use request::FetchReleaseRet::*;
         use FetchReleaseArtifactError as Error;
 
-        match request::fetch_release_artifacts(&self.0.client, release, auth_token).await {
+        match res {
             Ok(ReleaseNotFound) => Ok(None),
-            Ok(Artifacts(artifacts)) => Ok(Some(artifacts)),
+            Ok(Found(found)) => Ok(Some(found)),
             Ok(ReachedRateLimit { retry_after }) => {
                 let retry_after = retry_after.unwrap_or(DEFAULT_RETRY_DURATION);
 
@@ -173,306 +873,2549 @@ impl GhApiClient {
                 Err(Error::RateLimit { retry_after })
             }
             Ok(Unauthorized) => Err(Error::Unauthorized),
+            Ok(BudgetExhausted) => Err(Error::BudgetExhausted),
+            Ok(MalformedResponse) => {
+                self.0.malformed_responses.fetch_add(1, Relaxed);
+                Err(Error::MalformedResponse)
+            }
             Err(err) => Err(Error::Error(err)),
         }
     }
 
-    /// The returned future is guaranteed to be pointer size.
-    pub async fn has_release_artifact(
-        &self,
-        GhReleaseArtifact {
-            release,
-            artifact_name,
-        }: GhReleaseArtifact,
-    ) -> Result<HasReleaseArtifact, GhApiError> {
-        use FetchReleaseArtifactError as Error;
+    /// Remember until when the API is rate limited, so that the next
+    /// lookups are answered without sending any request.
+    fn record_error(&self, err: FetchReleaseArtifactError) -> FetchReleaseArtifactError {
+        if let FetchReleaseArtifactError::RateLimit { retry_after } = err {
+            *self.shared().retry_after.lock().unwrap() = Some(retry_after);
+        }
+        err
+    }
 
-        let once_cell = self.0.release_artifacts.get(release.clone());
-        let res = once_cell
-            .get_or_try_init(|| {
-                Box::pin(async {
-                    {
-                        let mut guard = self.0.retry_after.lock().unwrap();
+    /// Return an error without sending any request if the API cannot be
+    /// used at the moment.
+    fn check_api_usable(&self) -> Result<(), FetchReleaseArtifactError> {
+        use FetchReleaseArtifactError as Error;
 
-                        if let Some(retry_after) = *guard {
-                            if retry_after.elapsed().is_zero() {
-                                return Err(Error::RateLimit { retry_after });
-                            } else {
-                                // Instant retry_after is already reached.
-                                *guard = None;
-                            }
-                        };
-                    }
+        if self.0.budget.is_exhausted() {
+            return Err(Error::BudgetExhausted);
+        }
 
-                    if self.0.is_auth_token_valid.load(Relaxed) {
-                        match self
-                            .do_fetch_release_artifacts(&release, self.0.auth_token.as_deref())
-                            .await
-                        {
-                            Err(Error::Unauthorized) => {
-                                self.0.is_auth_token_valid.store(false, Relaxed);
-                            }
-                            res => return res,
-                        }
-                    }
+        if self.malformed_responses() > 0 {
+            return Err(Error::MalformedResponse);
+        }
 
-                    self.do_fetch_release_artifacts(&release, None).await
-                })
-            })
-            .await;
+        {
+            let mut guard = self.shared().retry_after.lock().unwrap();
 
-        match res {
-            Ok(Some(artifacts)) => {
-                let has_artifact = artifacts.contains(&artifact_name);
-                Ok(if has_artifact {
-                    HasReleaseArtifact::Yes
+            if let Some(retry_after) = *guard {
+                if retry_after.elapsed().is_zero() {
+                    return Err(Error::RateLimit { retry_after });
                 } else {
-                    HasReleaseArtifact::No
-                })
-            }
-            Ok(None) => Ok(HasReleaseArtifact::NoSuchRelease),
-            Err(Error::Unauthorized) => Ok(HasReleaseArtifact::Unauthorized),
-            Err(Error::RateLimit { retry_after }) => {
-                *self.0.retry_after.lock().unwrap() = Some(retry_after);
+                    // Instant retry_after is already reached.
+                    *guard = None;
+                }
+            };
+        }
+
+        Ok(())
+    }
+
+    async fn fetch_release_artifacts(
+        &self,
+        release: &GhRelease,
+    ) -> Result<Option<GhReleaseArtifacts>, FetchReleaseArtifactError> {
+        let Some(release_cache) = &self.0.release_cache else {
+            return self.fetch_release_artifacts_from_api(release, None).await;
+        };
+
+        let host = self.0.host.host();
+        if let Some(artifacts) = release_cache.get(host, release) {
+            self.0.release_cache_hits.fetch_add(1, Relaxed);
+            return Ok(artifacts);
+        }
+
+        // An expired release is only fetched again if it is modified.
+        let cached = release_cache.get_expired(host, release);
+        let res = self
+            .fetch_release_artifacts_from_api(release, cached.as_ref())
+            .await;
+        if let Ok(artifacts) = &res {
+            release_cache.insert(host, release, artifacts.as_ref());
+        }
+        res
+    }
+
+    async fn fetch_release_artifacts_from_api(
+        &self,
+        release: &GhRelease,
+        cached: Option<&GhReleaseArtifacts>,
+    ) -> Result<Option<GhReleaseArtifacts>, FetchReleaseArtifactError> {
+        self.check_api_usable()?;
+
+        self.fetch_with_auth_tokens(Some(&release.owner), |auth_token| async move {
+            self.do_fetch_release_artifacts(release, cached, auth_token.as_deref())
+                .await
+        })
+        .await
+    }
+
+    /// Call `fetch` with the first usable auth token, rotating to the next
+    /// one while it is rate limited or unauthorized, and then without token
+    /// if all of them are unauthorized.
+    ///
+    /// A rate limited token is skipped until its rate limit resets. If all
+    /// the tokens left are rate limited, the earliest reset is returned as
+    /// the rate limit.
+    ///
+    /// A token unauthorized for the repositories of `owner` is only skipped
+    /// for them, until it is unauthorized for two different owners in a row.
+    /// It is skipped everywhere if `owner` is unknown.
+    async fn fetch_with_auth_tokens<T, F, Fut>(
+        &self,
+        owner: Option<&str>,
+        fetch: F,
+    ) -> Result<Option<T>, FetchReleaseArtifactError>
+    where
+        F: Fn(Option<CompactString>) -> Fut,
+        Fut: Future<Output = Result<Option<T>, FetchReleaseArtifactError>>,
+    {
+        use FetchReleaseArtifactError as Error;
+
+        let shared = self.shared();
+        // Earliest reset of the tokens rate limited.
+        let mut rate_limit_reset: Option<Instant> = None;
+        let mut record_rate_limit = |retry_after: Instant| {
+            rate_limit_reset =
+                Some(rate_limit_reset.map_or(retry_after, |reset| reset.min(retry_after)));
+        };
+
+        for (index, auth_token) in shared.auth_tokens.iter().enumerate() {
+            {
+                let state = shared.auth_token_states[index].lock().unwrap();
+                if state.rejected || owner.is_some_and(|owner| state.owners.contains(owner)) {
+                    continue;
+                }
+                if let Some(retry_after) = state.retry_after.filter(|_| state.is_rate_limited()) {
+                    record_rate_limit(retry_after);
+                    continue;
+                }
+            }
+
+            match fetch(Some(auth_token.clone())).await {
+                Err(Error::RateLimit { retry_after }) => {
+                    self.rate_limit_auth_token(index, retry_after);
+                    record_rate_limit(retry_after);
+                }
+                Err(Error::Unauthorized) => self.reject_auth_token(index, owner),
+                res => {
+                    shared.auth_token_states[index].lock().unwrap().last_owner = None;
+                    return res;
+                }
+            }
+        }
+
+        match rate_limit_reset {
+            // Requests are only sent without token once all of them are
+            // unauthorized.
+            Some(retry_after) => Err(Error::RateLimit { retry_after }),
+            None => fetch(None).await,
+        }
+    }
+
+    /// Record that the token at `index` is rate limited until `retry_after`.
+    fn rate_limit_auth_token(&self, index: usize, retry_after: Instant) {
+        let mut state = self.shared().auth_token_states[index].lock().unwrap();
+        // Concurrent lookups rate limited with the same token log once.
+        if !state.is_rate_limited() {
+            debug!(
+                "GitHub token #{} is rate limited, not using it for {}s",
+                index + 1,
+                retry_after
+                    .saturating_duration_since(Instant::now())
+                    .as_secs()
+            );
+        }
+        state.retry_after = Some(retry_after);
+    }
+
+    /// Record that the token at `index` is unauthorized for `owner`, and
+    /// not to use it anywhere anymore if `owner` is unknown or it is also
+    /// unauthorized for the previous owner.
+    fn reject_auth_token(&self, index: usize, owner: Option<&str>) {
+        let mut state = self.shared().auth_token_states[index].lock().unwrap();
+
+        let widen = match owner {
+            Some(owner) => {
+                let widen = state
+                    .last_owner
+                    .as_deref()
+                    .is_some_and(|last_owner| last_owner != owner);
+                state.owners.insert(owner.into());
+                state.last_owner = Some(owner.into());
+
+                if !widen {
+                    debug!(
+                        "GitHub token #{} is unauthorized for {owner}, not using it for its repositories",
+                        index + 1
+                    );
+                }
+                widen
+            }
+            None => true,
+        };
+
+        if widen && !state.rejected {
+            debug!(
+                "GitHub token #{} is unauthorized, not using it anymore",
+                index + 1
+            );
+            state.rejected = true;
+        }
+    }
+
+    async fn fetch_latest_release(
+        &self,
+        repo: &GhRepo,
+    ) -> Result<Option<CompactString>, FetchReleaseArtifactError> {
+        self.check_api_usable()?;
+
+        self.fetch_with_auth_tokens(Some(&repo.owner), |auth_token| async move {
+            self.do_fetch_latest_release(repo, auth_token.as_deref())
+                .await
+        })
+        .await
+    }
+
+    async fn fetch_releases(
+        &self,
+        repo: &GhRepo,
+    ) -> Result<Vec<CompactString>, FetchReleaseArtifactError> {
+        self.check_api_usable()?;
+
+        self.fetch_with_auth_tokens(Some(&repo.owner), |auth_token| async move {
+            self.do_fetch_releases(repo, auth_token.as_deref()).await
+        })
+        .await
+        .map(Option::unwrap_or_default)
+    }
+
+    async fn fetch_repo_info(
+        &self,
+        repo: &GhRepo,
+    ) -> Result<Option<GhRepoInfo>, FetchReleaseArtifactError> {
+        self.check_api_usable()?;
+
+        self.fetch_with_auth_tokens(Some(&repo.owner), |auth_token| async move {
+            self.do_fetch_repo_info(repo, auth_token.as_deref()).await
+        })
+        .await
+    }
+
+    async fn fetch_releases_artifacts(
+        &self,
+        releases: &[&GhRelease],
+    ) -> Result<Vec<Option<GhReleaseArtifacts>>, FetchReleaseArtifactError> {
+        self.check_api_usable()?;
+
+        // The owner is only known if all the releases have the same.
+        let owner = releases
+            .first()
+            .map(|first| first.owner.as_str())
+            .filter(|owner| releases.iter().all(|release| release.owner == *owner));
+
+        self.fetch_with_auth_tokens(owner, |auth_token| async move {
+            self.do_fetch_releases_artifacts(releases, auth_token.as_deref())
+                .await
+        })
+        .await
+        .map(Option::unwrap_or_default)
+    }
+
+    /// Tokens are not rotated, unlike in [`GhApiClient::fetch_with_auth_tokens`]:
+    /// the lookups keep finding out on their own that a token is rejected.
+    async fn fetch_auth_check(&self) -> Result<GhAuthCheck, FetchReleaseArtifactError> {
+        let shared = self.shared();
+        let Some(auth_token) = shared
+            .auth_tokens
+            .iter()
+            .zip(&*shared.auth_token_states)
+            .find_map(|(auth_token, state)| {
+                (!state.lock().unwrap().rejected).then_some(auth_token)
+            })
+        else {
+            return Ok(GhAuthCheck::NoToken);
+        };
+
+        self.check_api_usable()?;
+
+        match self.do_fetch_auth_check(auth_token).await {
+            Ok(Some(auth_check)) => Ok(auth_check),
+            // The user of a valid token always exists.
+            Ok(None) => Err(FetchReleaseArtifactError::MalformedResponse),
+            Err(FetchReleaseArtifactError::Unauthorized) => Ok(GhAuthCheck::Rejected),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Check whether the token in use is accepted, to tell the user early
+    /// instead of when the lookups are rate limited.
+    ///
+    /// It is purely diagnostic: the check is sent at most once per client,
+    /// and a rejected token is still only skipped once a lookup is
+    /// unauthorized. Errors are returned like in
+    /// [`GhApiClient::get_latest_release`].
+    pub async fn check_auth(&self) -> Result<GhAuthCheck, GhApiError> {
+        match self
+            .0
+            .auth_check
+            .get_or_try_init(|| Box::pin(self.fetch_auth_check()))
+            .await
+        {
+            Ok(auth_check) => Ok(auth_check.clone()),
+            Err(err) => Err(self.record_error(err).into()),
+        }
+    }
+
+    /// List the releases of `repo` newest first, e.g. to find the tag
+    /// containing a version when the `pkg-url` guessed a wrong one.
+    ///
+    /// Drafts are skipped, and so are releases older than the latest 1000
+    /// to not use up the rate limit on a big repository. A repository that
+    /// does not exist has no release.
+    ///
+    /// The releases are fetched at most once per repository, errors are
+    /// returned like in [`GhApiClient::get_latest_release`].
+    pub async fn list_releases(&self, repo: &GhRepo) -> Result<Vec<GhRelease>, GhApiError> {
+        let once_cell = self.0.releases.get(repo.clone());
+        let mut fetched = false;
+        let res = once_cell
+            .get_or_try_init(|| {
+                fetched = true;
+                Box::pin(self.fetch_releases(repo))
+            })
+            .await;
+        if res.is_ok() && !fetched {
+            self.0.release_cache_hits.fetch_add(1, Relaxed);
+        }
+
+        match res {
+            Ok(tags) => Ok(tags.iter().map(|tag| repo.release(tag.clone())).collect()),
+            Err(err) => Err(self.record_error(err).into()),
+        }
+    }
+
+    /// Return the latest release of `repo`, `None` if it has no release.
+    ///
+    /// Drafts and prereleases are skipped like GitHub does, the tag is
+    /// fetched at most once per repository and the returned release can be
+    /// used in [`GhReleaseArtifact`] lookups.
+    ///
+    /// When the API cannot be used, the variants of [`HasReleaseArtifact`]
+    /// are returned as [`GhApiError::RateLimit`], [`GhApiError::Unauthorized`],
+    /// [`GhApiError::BudgetExhausted`] and [`GhApiError::MalformedResponse`].
+    pub async fn get_latest_release(&self, repo: &GhRepo) -> Result<Option<GhRelease>, GhApiError> {
+        Ok(self.latest_release(repo).await?)
+    }
+
+    async fn latest_release(
+        &self,
+        repo: &GhRepo,
+    ) -> Result<Option<GhRelease>, FetchReleaseArtifactError> {
+        let once_cell = self.0.latest_releases.get(repo.clone());
+        let mut fetched = false;
+        let res = once_cell
+            .get_or_try_init(|| {
+                fetched = true;
+                Box::pin(self.fetch_latest_release(repo))
+            })
+            .await;
+        if res.is_ok() && !fetched {
+            self.0.release_cache_hits.fetch_add(1, Relaxed);
+        }
+
+        match res {
+            Ok(tag) => Ok(tag.clone().map(|tag| repo.release(tag))),
+            Err(err) => Err(self.record_error(err)),
+        }
+    }
+
+    /// Return whether `repo` is private, archived, has releases and its
+    /// default branch, `None` if it does not exist or is not visible with
+    /// the current token.
+    ///
+    /// The information is fetched at most once per repository, errors are
+    /// returned like in [`GhApiClient::get_latest_release`].
+    pub async fn get_repo_info(&self, repo: &GhRepo) -> Result<Option<GhRepoInfo>, GhApiError> {
+        let once_cell = self.0.repo_infos.get(repo.clone());
+        let mut fetched = false;
+        let res = once_cell
+            .get_or_try_init(|| {
+                fetched = true;
+                Box::pin(self.fetch_repo_info(repo))
+            })
+            .await;
+        if res.is_ok() && !fetched {
+            self.0.release_cache_hits.fetch_add(1, Relaxed);
+        }
+
+        match res {
+            Ok(repo_info) => Ok(repo_info.clone()),
+            Err(err) => Err(self.record_error(err).into()),
+        }
+    }
+
+    /// The returned future is guaranteed to be pointer size.
+    ///
+    /// The latest release is looked up first if [`GhRelease::is_latest`].
+    pub async fn has_release_artifact(
+        &self,
+        GhReleaseArtifact {
+            release,
+            artifact_name,
+        }: GhReleaseArtifact,
+    ) -> Result<HasReleaseArtifact, GhApiError> {
+        let mut rets =
+            Box::pin(self.has_release_artifacts(&release, slice::from_ref(&artifact_name))).await?;
+
+        Ok(rets.pop().unwrap())
+    }
+
+    /// Like [`GhApiClient::has_release_artifact`], but on
+    /// [`HasReleaseArtifact::RateLimit`] sleep until the rate limit resets
+    /// and look it up again, unless the reset is more than `max_wait` away
+    /// or `cancel` is cancelled first, in which case the rate limit is
+    /// returned.
+    ///
+    /// Only one of the lookups waiting for the same reset sends a request
+    /// once it is reached, the others wait for it and only send theirs if
+    /// the rate limit is indeed reset.
+    pub async fn has_release_artifact_with_retry(
+        &self,
+        artifact: GhReleaseArtifact,
+        max_wait: Duration,
+        cancel: CancellationToken,
+    ) -> Result<HasReleaseArtifact, GhApiError> {
+        let deadline = tokio::time::Instant::now() + max_wait;
+
+        loop {
+            let ret = self.has_release_artifact(artifact.clone()).await?;
+            let HasReleaseArtifact::RateLimit { retry_after } = ret else {
+                return Ok(ret);
+            };
+
+            let wake_up =
+                tokio::time::Instant::now() + retry_after.saturating_duration_since(Instant::now());
+            if wake_up > deadline {
+                return Ok(ret);
+            }
+
+            debug!("Waiting for the rate limit to reset before looking up {artifact:?} again");
+
+            let mut reset = tokio::select! {
+                _ = cancel.cancelled() => return Ok(ret),
+                reset = async {
+                    sleep_until(wake_up).await;
+                    self.shared().rate_limit_reset.lock().await
+                } => reset,
+            };
+
+            if *reset == Some(retry_after) {
+                // Another lookup already succeeded after this reset.
+                continue;
+            }
+
+            // The sleep is over, even if the clock used by `retry_after`
+            // disagrees.
+            {
+                let mut guard = self.shared().retry_after.lock().unwrap();
+                if *guard == Some(retry_after) {
+                    *guard = None;
+                }
+            }
+
+            let ret = self.has_release_artifact(artifact.clone()).await?;
+            if matches!(ret, HasReleaseArtifact::RateLimit { .. }) {
+                continue;
+            }
+
+            *reset = Some(retry_after);
+            return Ok(ret);
+        }
+    }
+
+    /// Check for existence of multiple artifacts of the same release.
+    ///
+    /// The release is fetched at most once and all `names` are evaluated
+    /// against it, the returned `Vec` is in the same order as `names`.
+    ///
+    /// The latest release is looked up first if [`GhRelease::is_latest`].
+    pub async fn has_release_artifacts(
+        &self,
+        release: &GhRelease,
+        names: &[CompactString],
+    ) -> Result<Vec<HasReleaseArtifact>, GhApiError> {
+        let latest;
+        let release = if release.is_latest() {
+            match self.latest_release(&release.repo()).await {
+                Ok(Some(found)) => {
+                    latest = found;
+                    &latest
+                }
+                res => return has_release_artifacts_ret(res.map(|_| None), names.len()),
+            }
+        } else {
+            release
+        };
+
+        self.has_release_artifacts_inner(release, names, || {
+            Box::pin(self.fetch_release_artifacts(release))
+        })
+        .await
+    }
+
+    /// Return the metadata of `artifact_name` in `release`, `None` if the
+    /// artifact or the release does not exist, e.g. to know the size and
+    /// the digest of the artifact before downloading it.
+    ///
+    /// The release is fetched at most once and shared with
+    /// [`GhApiClient::has_release_artifacts`], errors are returned like in
+    /// [`GhApiClient::get_latest_release`].
+    pub async fn get_artifact(
+        &self,
+        GhReleaseArtifact {
+            release,
+            artifact_name,
+        }: GhReleaseArtifact,
+    ) -> Result<Option<GhReleaseArtifactInfo>, GhApiError> {
+        let info = self
+            .with_release_artifacts(
+                &release,
+                || Box::pin(self.fetch_release_artifacts(&release)),
+                |artifacts| artifacts?.get(&artifact_name).cloned(),
+            )
+            .await?;
+
+        Ok(info)
+    }
+
+    /// Like [`GhApiClient::get_artifact`], but if `artifact_name` does not
+    /// exist return the only artifact whose name differs from it in the
+    /// case, in `-` and `_`, in `.tgz` and `.tar.gz` or in the order of its
+    /// parts, `None` if there is none or more than one.
+    pub async fn get_artifact_fuzzy(
+        &self,
+        GhReleaseArtifact {
+            release,
+            artifact_name,
+        }: GhReleaseArtifact,
+    ) -> Result<Option<GhReleaseArtifactInfo>, GhApiError> {
+        let info = self
+            .with_release_artifacts(
+                &release,
+                || Box::pin(self.fetch_release_artifacts(&release)),
+                |artifacts| {
+                    let artifacts = artifacts?;
+                    artifacts
+                        .get(&artifact_name)
+                        .or_else(|| artifacts.get_fuzzy(&artifact_name))
+                        .cloned()
+                },
+            )
+            .await?;
+
+        Ok(info)
+    }
+
+    /// Return the names of the artifacts of `release` sorted, `None` if it
+    /// does not exist, e.g. to tell which ones are available when the one
+    /// looked up is missing.
+    ///
+    /// The release is fetched at most once and shared with
+    /// [`GhApiClient::has_release_artifacts`], errors are returned like in
+    /// [`GhApiClient::get_latest_release`].
+    pub async fn list_release_artifacts(
+        &self,
+        release: &GhRelease,
+    ) -> Result<Option<Vec<CompactString>>, GhApiError> {
+        let names = self
+            .with_release_artifacts(
+                release,
+                || Box::pin(self.fetch_release_artifacts(release)),
+                |artifacts| {
+                    let mut names: Vec<_> =
+                        artifacts?.iter().map(|info| info.name.clone()).collect();
+                    names.sort_unstable();
+                    Some(names)
+                },
+            )
+            .await?;
+
+        Ok(names)
+    }
+
+    /// Return the metadata of `release`, `None` if it does not exist, e.g.
+    /// to know when it was published.
+    ///
+    /// Drafts are only found with a token, since their artifacts cannot be
+    /// downloaded without one. The release is fetched at most once and
+    /// shared with [`GhApiClient::has_release_artifacts`], errors are
+    /// returned like in [`GhApiClient::get_latest_release`].
+    pub async fn get_release_meta(
+        &self,
+        release: &GhRelease,
+    ) -> Result<Option<GhReleaseMeta>, GhApiError> {
+        let meta = self
+            .with_release_artifacts(
+                release,
+                || Box::pin(self.fetch_release_artifacts(release)),
+                |artifacts| Some(artifacts?.meta().clone()),
+            )
+            .await?;
+
+        Ok(meta)
+    }
+
+    /// Return the url of `artifact` in the restful API, to download it with
+    /// [`GhApiClient::download_artifact`], `None` if the artifact or the
+    /// release does not exist.
+    ///
+    /// The GraphQL API does not return it, so the release is fetched again
+    /// with the restful API. Errors are returned like in
+    /// [`GhApiClient::get_latest_release`].
+    pub async fn get_artifact_url(
+        &self,
+        artifact: &GhReleaseArtifact,
+    ) -> Result<Option<remote::Url>, GhApiError> {
+        self.check_api_usable().map_err(GhApiError::from)?;
+
+        let res = self
+            .fetch_with_auth_tokens(Some(&artifact.release.owner), |auth_token| async move {
+                self.do_fetch_artifact_url(artifact, auth_token.as_deref())
+                    .await
+            })
+            .await;
+
+        match res {
+            Ok(url) => Ok(url.as_deref().map(remote::Url::parse).transpose()?),
+            Err(err) => Err(self.record_error(err).into()),
+        }
+    }
+
+    /// Download the artifact at `artifact_url`, returned by
+    /// [`GhApiClient::get_artifact_url`], `None` if it does not exist.
+    ///
+    /// Unlike its download url, it works for the artifacts of private
+    /// repositories as long as a token can access them. The token is not
+    /// forwarded to the storage the API redirects to. Errors are returned
+    /// like in [`GhApiClient::get_latest_release`].
+    pub async fn download_artifact(
+        &self,
+        artifact_url: &remote::Url,
+    ) -> Result<Option<impl Stream<Item = Result<Bytes, remote::Error>>>, GhApiError> {
+        self.check_api_usable().map_err(GhApiError::from)?;
+
+        let res = self
+            .fetch_with_auth_tokens(api_url_owner(artifact_url), |auth_token| async move {
+                self.do_download_artifact(artifact_url, auth_token.as_deref())
+                    .await
+            })
+            .await;
+
+        match res {
+            Ok(response) => Ok(response.map(remote::Response::bytes_stream)),
+            Err(err) => Err(self.record_error(err).into()),
+        }
+    }
+
+    /// Return the artifacts of each of `releases`, `None` for the ones that
+    /// do not exist, e.g. to probe the candidate tags of a version.
+    ///
+    /// The releases not fetched yet are fetched with one GraphQL query if
+    /// a token is available, otherwise with one restful request each. They
+    /// are cached for the later lookups of [`GhApiClient::has_release_artifacts`]
+    /// and [`GhApiClient::get_artifact`], errors are returned like in
+    /// [`GhApiClient::get_latest_release`].
+    pub async fn has_release_artifacts_batch(
+        &self,
+        releases: &[GhRelease],
+    ) -> Result<Vec<Option<GhReleaseArtifacts>>, GhApiError> {
+        let host = self.0.host.host();
+
+        let mut missing: Vec<&GhRelease> = Vec::new();
+        for release in releases {
+            let cell = self.0.release_artifacts.get(release.clone());
+            // The releases being fetched by concurrent lookups are waited
+            // for below.
+            let Ok(mut cached) = cell.try_lock() else {
+                continue;
+            };
+
+            if cached.is_fresh(self.0.not_found_ttl) {
+                self.0.release_cache_hits.fetch_add(1, Relaxed);
+            } else if let Some(artifacts) = self
+                .0
+                .release_cache
+                .as_ref()
+                .and_then(|release_cache| release_cache.get(host, release))
+            {
+                self.0.release_cache_hits.fetch_add(1, Relaxed);
+                cached.set(artifacts);
+            } else if !missing.contains(&release) {
+                missing.push(release);
+            }
+        }
+
+        if !missing.is_empty() {
+            let fetched = self
+                .fetch_releases_artifacts(&missing)
+                .await
+                .map_err(|err| self.record_error(err))?;
+
+            for (release, artifacts) in missing.into_iter().zip(fetched) {
+                if let Some(release_cache) = &self.0.release_cache {
+                    release_cache.insert(host, release, artifacts.as_ref());
+                }
+                self.0
+                    .release_artifacts
+                    .get(release.clone())
+                    .lock()
+                    .await
+                    .set(artifacts);
+            }
+        }
+
+        let mut rets = Vec::with_capacity(releases.len());
+        for release in releases {
+            // Only waits for the lookups that were in progress concurrently.
+            let cell = self.0.release_artifacts.get(release.clone());
+            let mut cached = cell.lock().await;
+            if !matches!(*cached, CachedReleaseArtifacts::NotFetched) {
+                rets.push(cached.artifacts().cloned());
+                continue;
+            }
+
+            let artifacts = self
+                .fetch_release_artifacts(release)
+                .await
+                .map_err(|err| self.record_error(err))?;
+            cached.set(artifacts);
+            rets.push(cached.artifacts().cloned());
+        }
+
+        Ok(rets)
+    }
+
+    /// Return the first release of `repo` whose tag is in `candidates`,
+    /// `None` if none of them exists, e.g. to find the release of a version
+    /// tagged either `v{version}` or `{version}`.
+    ///
+    /// With a token, all the candidates are fetched with one GraphQL query
+    /// like in [`GhApiClient::has_release_artifacts_batch`], otherwise they
+    /// are fetched one at a time until one exists. The releases fetched are
+    /// cached for the later lookups, errors are returned like in
+    /// [`GhApiClient::get_latest_release`].
+    pub async fn find_release(
+        &self,
+        repo: &GhRepo,
+        candidates: &[CompactString],
+    ) -> Result<Option<GhRelease>, GhApiError> {
+        let releases = candidates.iter().map(|tag| GhRelease {
+            owner: repo.owner.clone(),
+            repo: repo.repo.clone(),
+            tag: tag.clone(),
+        });
+
+        if self.has_auth_token() {
+            let releases: Vec<_> = releases.collect();
+            let rets = self.has_release_artifacts_batch(&releases).await?;
+
+            return Ok(releases
+                .into_iter()
+                .zip(rets)
+                .find_map(|(release, artifacts)| artifacts.map(|_| release)));
+        }
+
+        for release in releases {
+            let exists = self
+                .with_release_artifacts(
+                    &release,
+                    || Box::pin(self.fetch_release_artifacts(&release)),
+                    |artifacts| artifacts.is_some(),
+                )
+                .await?;
+            if exists {
+                return Ok(Some(release));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn has_release_artifacts_inner<F, Fut>(
+        &self,
+        release: &GhRelease,
+        names: &[CompactString],
+        fetch: F,
+    ) -> Result<Vec<HasReleaseArtifact>, GhApiError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<GhReleaseArtifacts>, FetchReleaseArtifactError>>,
+    {
+        let res = self
+            .with_release_artifacts(release, fetch, |artifacts| {
+                let artifacts = artifacts?;
+                Some(
+                    names
+                        .iter()
+                        .map(|artifact_name| {
+                            if artifacts.contains(artifact_name) {
+                                HasReleaseArtifact::Yes
+                            } else {
+                                HasReleaseArtifact::No
+                            }
+                        })
+                        .collect(),
+                )
+            })
+            .await;
+
+        has_release_artifacts_ret(res, names.len())
+    }
+
+    /// Call `f` with the artifacts of `release`, `None` if it does not
+    /// exist, calling `fetch` at most once per release unless it is not
+    /// found for longer than the TTL.
+    async fn with_release_artifacts<F, Fut, T>(
+        &self,
+        release: &GhRelease,
+        fetch: F,
+        f: impl FnOnce(Option<&GhReleaseArtifacts>) -> T,
+    ) -> Result<T, FetchReleaseArtifactError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<GhReleaseArtifacts>, FetchReleaseArtifactError>>,
+    {
+        let cell = self.0.release_artifacts.get(release.clone());
+        let mut cached = cell.lock().await;
+
+        if cached.is_fresh(self.0.not_found_ttl) {
+            self.0.release_cache_hits.fetch_add(1, Relaxed);
+        } else {
+            let artifacts = fetch().await.map_err(|err| self.record_error(err))?;
+            cached.set(artifacts);
+        }
+
+        Ok(f(cached.artifacts()))
+    }
+}
+
+/// Return the answers for `len` artifacts of a release, the same one for all
+/// of them unless the release is found.
+fn has_release_artifacts_ret(
+    res: Result<Option<Vec<HasReleaseArtifact>>, FetchReleaseArtifactError>,
+    len: usize,
+) -> Result<Vec<HasReleaseArtifact>, GhApiError> {
+    use FetchReleaseArtifactError as Error;
+
+    let ret_for_all = |ret| vec![ret; len];
+
+    match res {
+        Ok(Some(rets)) => Ok(rets),
+        Ok(None) => Ok(ret_for_all(HasReleaseArtifact::NoSuchRelease)),
+        Err(Error::Unauthorized) => Ok(ret_for_all(HasReleaseArtifact::Unauthorized)),
+        Err(Error::BudgetExhausted) => Ok(ret_for_all(HasReleaseArtifact::BudgetExhausted)),
+        Err(Error::MalformedResponse) => Ok(ret_for_all(HasReleaseArtifact::MalformedResponse)),
+        Err(Error::RateLimit { retry_after }) => {
+            Ok(ret_for_all(HasReleaseArtifact::RateLimit { retry_after }))
+        }
+        Err(Error::Error(err)) => Err(err),
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum HasReleaseArtifact {
+    Yes,
+    No,
+    NoSuchRelease,
+    /// GitHub returns 401 requiring a token.
+    /// In this case, it makes sense to fallback to HEAD/GET.
+    Unauthorized,
+
+    /// GitHub rate limit is applied per hour, so in case of reaching the rate
+    /// limit, [`GhApiClient`] will return this variant and let the user decide
+    /// what to do.
+    ///
+    /// Usually it is more sensible to fallback to directly HEAD/GET the
+    /// artifact url than waiting until `retry_after`.
+    ///
+    /// If you encounter this frequently, then you should consider getting an
+    /// authentication token (can be personal access or oath access token),
+    /// which should give you 5000 requests per hour per user.
+    ///
+    /// Rate limit for unauthorized user is 60 requests per hour per originating
+    /// IP address, so it is very easy to be rate limited.
+    RateLimit {
+        retry_after: Instant,
+    },
+
+    /// The request budget set by [`GhApiClient::with_request_budget`] is
+    /// used up, fallback to HEAD/GET the artifact url to leave the remaining
+    /// rate limit to other tools.
+    BudgetExhausted,
+
+    /// GitHub returned a response that cannot be deserialized, e.g. because
+    /// a TLS-inspecting proxy truncated or rewrote it.
+    ///
+    /// The API is considered unusable for the rest of the lifetime of the
+    /// [`GhApiClient`], so fallback to HEAD/GET the artifact url.
+    MalformedResponse,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::download::PkgFmt;
+    use compact_str::{format_compact, CompactString, ToCompactString};
+    use std::{env, num::NonZeroU16};
+
+    mod cargo_binstall_v0_20_1 {
+        use super::{CompactString, GhRelease};
+
+        pub(super) const RELEASE: GhRelease = GhRelease {
+            owner: CompactString::new_inline("cargo-bins"),
+            repo: CompactString::new_inline("cargo-binstall"),
+            tag: CompactString::new_inline("v0.20.1"),
+        };
+
+        pub(super) const ARTIFACTS: &[&str] = &[
+            "cargo-binstall-aarch64-apple-darwin.full.zip",
+            "cargo-binstall-aarch64-apple-darwin.zip",
+            "cargo-binstall-aarch64-pc-windows-msvc.full.zip",
+            "cargo-binstall-aarch64-pc-windows-msvc.zip",
+            "cargo-binstall-aarch64-unknown-linux-gnu.full.tgz",
+            "cargo-binstall-aarch64-unknown-linux-gnu.tgz",
+            "cargo-binstall-aarch64-unknown-linux-musl.full.tgz",
+            "cargo-binstall-aarch64-unknown-linux-musl.tgz",
+            "cargo-binstall-armv7-unknown-linux-gnueabihf.full.tgz",
+            "cargo-binstall-armv7-unknown-linux-gnueabihf.tgz",
+            "cargo-binstall-armv7-unknown-linux-musleabihf.full.tgz",
+            "cargo-binstall-armv7-unknown-linux-musleabihf.tgz",
+            "cargo-binstall-universal-apple-darwin.full.zip",
+            "cargo-binstall-universal-apple-darwin.zip",
+            "cargo-binstall-x86_64-apple-darwin.full.zip",
+            "cargo-binstall-x86_64-apple-darwin.zip",
+            "cargo-binstall-x86_64-pc-windows-msvc.full.zip",
+            "cargo-binstall-x86_64-pc-windows-msvc.zip",
+            "cargo-binstall-x86_64-unknown-linux-gnu.full.tgz",
+            "cargo-binstall-x86_64-unknown-linux-gnu.tgz",
+            "cargo-binstall-x86_64-unknown-linux-musl.full.tgz",
+            "cargo-binstall-x86_64-unknown-linux-musl.tgz",
+        ];
+    }
+
+    fn try_extract_artifact_from_str(s: &str) -> Option<GhReleaseArtifact> {
+        GhReleaseArtifact::try_extract_from_url(&url::Url::parse(s).unwrap(), &[GITHUB_COM])
+    }
+
+    fn assert_extract_gh_release_artifacts_failures(urls: &[&str]) {
+        for url in urls {
+            assert_eq!(try_extract_artifact_from_str(url), None);
+        }
+    }
+
+    #[test]
+    fn test_gh_repo_try_extract_from_url() {
+        let cargo_binstall = Some(GhRepo {
+            owner: "cargo-bins".into(),
+            repo: "cargo-binstall".into(),
+        });
+
+        let test_cases = [
+            (
+                "https://github.com/cargo-bins/cargo-binstall",
+                &cargo_binstall,
+            ),
+            (
+                "https://github.com/cargo-bins/cargo-binstall/",
+                &cargo_binstall,
+            ),
+            (
+                "https://github.com/cargo-bins/cargo-binstall.git",
+                &cargo_binstall,
+            ),
+            (
+                "http://www.github.com/cargo-bins/cargo-binstall",
+                &cargo_binstall,
+            ),
+            (
+                "https://GitHub.com/cargo-bins/cargo-binstall#readme",
+                &cargo_binstall,
+            ),
+            (
+                "https://github.com/cargo-bins/cargo-binstall/tree/main/crates/bin",
+                &cargo_binstall,
+            ),
+            (
+                "git+https://github.com/cargo-bins/cargo-binstall.git",
+                &cargo_binstall,
+            ),
+            (
+                "ssh://git@github.com/cargo-bins/cargo-binstall",
+                &cargo_binstall,
+            ),
+            (
+                "ssh://git@github.com:22/cargo-bins/cargo-binstall.git",
+                &cargo_binstall,
+            ),
+            (
+                "git+ssh://git@github.com/cargo-bins/cargo-binstall.git",
+                &cargo_binstall,
+            ),
+            (
+                "git@github.com:cargo-bins/cargo-binstall.git",
+                &cargo_binstall,
+            ),
+            ("git@github.com:/cargo-bins/cargo-binstall", &cargo_binstall),
+            (
+                " https://github.com/cargo-bins/cargo-binstall\n",
+                &cargo_binstall,
+            ),
+            ("https://github.com/cargo-bins", &None),
+            ("https://github.com/cargo-bins/.git", &None),
+            ("https://gitlab.com/cargo-bins/cargo-binstall", &None),
+            (
+                "https://github.com.evil.com/cargo-bins/cargo-binstall",
+                &None,
+            ),
+            ("git@gitlab.com:cargo-bins/cargo-binstall.git", &None),
+            ("ftp://github.com/cargo-bins/cargo-binstall", &None),
+            ("cargo-bins/cargo-binstall", &None),
+        ];
+
+        for (url, expected) in test_cases {
+            assert_eq!(&GhRepo::try_extract_from_url(url), expected, "{url}");
+        }
+
+        let repo: GhRepo = "https://github.com/org/my%20repo".parse().unwrap();
+        assert_eq!(repo.repo, "my repo");
+        assert_eq!(repo.url().as_str(), "https://github.com/org/my%20repo");
+        assert!("https://gitlab.com/org/repo".parse::<GhRepo>().is_err());
+    }
+
+    #[test]
+    fn extract_gh_release_artifacts_failure() {
+        use cargo_binstall_v0_20_1::*;
+
+        let GhRelease { owner, repo, tag } = RELEASE;
+
+        assert_extract_gh_release_artifacts_failures(&[
+            "https://examle.com",
+            "https://github.com",
+            &format!("https://github.com/{owner}"),
+            &format!("https://github.com/{owner}/{repo}"),
+            &format!("https://github.com/{owner}/{repo}/123e"),
+            &format!("https://github.com/{owner}/{repo}/releases/21343"),
+            &format!("https://github.com/{owner}/{repo}/releases/download"),
+            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}"),
+            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}/a/23"),
+            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}/a#a=12"),
+            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}/a?page=3"),
+            &format!("https://github.com/{owner}/{repo}/releases/download//a"),
+            &format!("https://github.com/{owner}/{repo}/releases/latest"),
+            &format!("https://github.com/{owner}/{repo}/releases/latest/download"),
+            &format!("https://github.com/{owner}/{repo}/releases/latest/a"),
+            &format!("https://github.com/{owner}/{repo}/releases/latest/download/a/23"),
+            &format!("https://github.com/{owner}/{repo}/releases/latest/download/a?page=3"),
+        ]);
+    }
+
+    #[test]
+    fn extract_gh_release_artifacts_success() {
+        use cargo_binstall_v0_20_1::*;
+
+        let GhRelease { owner, repo, tag } = RELEASE;
+
+        for artifact in ARTIFACTS {
+            let GhReleaseArtifact {
+                release,
+                artifact_name,
+            } = try_extract_artifact_from_str(&format!(
+                "https://github.com/{owner}/{repo}/releases/download/{tag}/{artifact}"
+            ))
+            .unwrap();
+
+            assert_eq!(release, RELEASE);
+            assert_eq!(artifact_name, artifact);
+        }
+    }
+
+    #[test]
+    fn extract_gh_release_artifacts_latest() {
+        use cargo_binstall_v0_20_1::*;
+
+        let GhRelease { owner, repo, .. } = RELEASE;
+        let latest = GhRelease {
+            tag: CompactString::default(),
+            ..RELEASE
+        };
+
+        for artifact in ARTIFACTS {
+            let release_artifact = try_extract_artifact_from_str(&format!(
+                "https://github.com/{owner}/{repo}/releases/latest/download/{artifact}"
+            ))
+            .unwrap();
+
+            assert!(release_artifact.release.is_latest());
+            assert_eq!(release_artifact.release, latest);
+            assert_eq!(release_artifact.artifact_name, *artifact);
+        }
+
+        let release_artifact = try_extract_artifact_from_str(
+            "https://github.com/org/my%20repo/releases/latest/download/tool%20x86_64.zip",
+        )
+        .unwrap();
+        assert_eq!(release_artifact.release.repo, "my repo");
+        assert_eq!(release_artifact.artifact_name, "tool x86_64.zip");
+        assert!(release_artifact.release.is_latest());
+
+        // A tag named `latest` is not the latest release.
+        let release_artifact = try_extract_artifact_from_str(&format!(
+            "https://github.com/{owner}/{repo}/releases/download/latest/{}",
+            ARTIFACTS[0]
+        ))
+        .unwrap();
+        assert_eq!(release_artifact.release.tag, "latest");
+        assert!(!release_artifact.release.is_latest());
+    }
+
+    #[test]
+    fn extract_gh_release_artifacts_enterprise_host() {
+        let url = url::Url::parse(
+            "https://GitHub.Corp.Example.com/tools/widget/releases/download/v1.0.0/widget.tgz",
+        )
+        .unwrap();
+
+        assert_eq!(
+            GhReleaseArtifact::try_extract_from_url(&url, &[GITHUB_COM]),
+            None
+        );
+
+        let artifact =
+            GhReleaseArtifact::try_extract_from_url(&url, &[GITHUB_COM, "github.corp.example.com"])
+                .unwrap();
+        assert_eq!(artifact.release.owner, "tools");
+        assert_eq!(artifact.release.repo, "widget");
+        assert_eq!(artifact.release.tag, "v1.0.0");
+        assert_eq!(artifact.artifact_name, "widget.tgz");
+
+        // The port must match as well.
+        let url = url::Url::parse(
+            "https://ghe.corp:8443/tools/widget/releases/download/v1.0.0/widget.tgz",
+        )
+        .unwrap();
+        assert_eq!(
+            GhReleaseArtifact::try_extract_from_url(&url, &["ghe.corp"]),
+            None
+        );
+        let artifact = GhReleaseArtifact::try_extract_from_url(&url, &["ghe.corp:8443"]).unwrap();
+        assert_eq!(artifact.artifact_name, "widget.tgz");
+        assert_eq!(artifact.download_url(&GhApiHost::new("ghe.corp:8443")), url);
+    }
+
+    /// Mark this as an async fn so that you won't accidentally use it in
+    /// sync context.
+    async fn create_client() -> Vec<GhApiClient> {
+        let client = remote::Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let mut gh_clients = vec![GhApiClient::new(client.clone(), None)];
+
+        if let Ok(token) = env::var("GITHUB_TOKEN") {
+            gh_clients.push(GhApiClient::new(client, Some(token.into())));
+        }
+
+        gh_clients
+    }
+
+    async fn test_specific_release(release: &GhRelease, artifacts: &[&str]) {
+        for client in create_client().await {
+            eprintln!("In client {client:?}");
+
+            for artifact_name in artifacts {
+                let ret = client
+                    .has_release_artifact(GhReleaseArtifact {
+                        release: release.clone(),
+                        artifact_name: artifact_name.to_compact_string(),
+                    })
+                    .await
+                    .unwrap();
+
+                assert!(
+                    matches!(
+                        ret,
+                        HasReleaseArtifact::Yes | HasReleaseArtifact::RateLimit { .. }
+                    ),
+                    "for '{artifact_name}': answer is {:#?}",
+                    ret
+                );
+            }
+
+            let ret = client
+                .has_release_artifact(GhReleaseArtifact {
+                    release: release.clone(),
+                    artifact_name: "123z".to_compact_string(),
+                })
+                .await
+                .unwrap();
+
+            assert!(
+                matches!(
+                    ret,
+                    HasReleaseArtifact::No | HasReleaseArtifact::RateLimit { .. }
+                ),
+                "ret = {:#?}",
+                ret
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_has_release_artifacts_fetch_release_once() {
+        use std::sync::atomic::AtomicUsize;
+        use tokio::task::yield_now;
+
+        use cargo_binstall_v0_20_1::*;
+
+        let client = create_client().await.remove(0);
+        let fetch_count = Arc::new(AtomicUsize::new(0));
+
+        let names: Vec<CompactString> = ARTIFACTS
+            .iter()
+            .copied()
+            .chain(["123z"])
+            .map(CompactString::from)
+            .collect();
+        let names: Arc<[CompactString]> = names.into();
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let client = client.clone();
+                let fetch_count = fetch_count.clone();
+                let names = names.clone();
+
+                tokio::spawn(async move {
+                    client
+                        .has_release_artifacts_inner(&RELEASE, &names, || async {
+                            fetch_count.fetch_add(1, Relaxed);
+                            // Give other tasks a chance to observe the cache
+                            // entry while it is still initializing.
+                            yield_now().await;
+                            Ok(Some(ARTIFACTS.iter().copied().collect()))
+                        })
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            let rets = task.await.unwrap();
+
+            assert_eq!(rets.len(), names.len());
+            let (last, rest) = rets.split_last().unwrap();
+            assert!(rest.iter().all(|ret| *ret == HasReleaseArtifact::Yes));
+            assert_eq!(*last, HasReleaseArtifact::No);
+        }
+
+        assert_eq!(fetch_count.load(Relaxed), 1);
+        assert_eq!(client.release_cache_hits(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_not_found_ttl() {
+        use std::sync::atomic::AtomicUsize;
+
+        use cargo_binstall_v0_20_1::*;
+
+        let client = create_client().await.remove(0);
+        let client =
+            GhApiClient::new(client.0.client.clone(), None).with_not_found_ttl(Duration::ZERO);
+        let fetch_count = AtomicUsize::new(0);
+        let names = [ARTIFACTS[0].to_compact_string()];
+
+        let (release, fetch_count) = (&RELEASE, &fetch_count);
+        let lookup = |published: bool| {
+            client.has_release_artifacts_inner(release, &names, move || async move {
+                fetch_count.fetch_add(1, Relaxed);
+                Ok(published.then(|| ARTIFACTS.iter().copied().collect()))
+            })
+        };
+
+        // The release not found is fetched again once the TTL expires.
+        assert_eq!(
+            lookup(false).await.unwrap(),
+            [HasReleaseArtifact::NoSuchRelease]
+        );
+        assert_eq!(lookup(true).await.unwrap(), [HasReleaseArtifact::Yes]);
+        assert_eq!(fetch_count.load(Relaxed), 2);
+
+        // The release found is not.
+        assert_eq!(lookup(false).await.unwrap(), [HasReleaseArtifact::Yes]);
+        assert_eq!(fetch_count.load(Relaxed), 2);
+        assert_eq!(client.release_cache_hits(), 1);
+
+        client.clear_cache();
+        assert_eq!(
+            lookup(false).await.unwrap(),
+            [HasReleaseArtifact::NoSuchRelease]
+        );
+        assert_eq!(fetch_count.load(Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_max_cached_releases() {
+        use std::sync::atomic::AtomicUsize;
+
+        use cargo_binstall_v0_20_1::*;
+
+        let client = create_client().await.remove(0);
+        let client = GhApiClient::new(client.0.client.clone(), None).with_max_cached_releases(2);
+        let fetch_count = AtomicUsize::new(0);
+        let names = [ARTIFACTS[0].to_compact_string()];
+        let release = |tag: &str| GhRelease {
+            tag: tag.to_compact_string(),
+            ..RELEASE
+        };
+
+        let (client, names, fetch_count) = (&client, &names, &fetch_count);
+        let lookup = |release: GhRelease| async move {
+            client
+                .has_release_artifacts_inner(&release, names, || async {
+                    fetch_count.fetch_add(1, Relaxed);
+                    Ok(Some(ARTIFACTS.iter().copied().collect()))
+                })
+                .await
+                .unwrap()
+        };
+
+        lookup(release("v1")).await;
+        lookup(release("v2")).await;
+        lookup(release("v1")).await;
+        assert_eq!(fetch_count.load(Relaxed), 2);
+
+        // v2 is the least recently used release.
+        lookup(release("v3")).await;
+        assert_eq!(client.cache_len(), 2);
+        lookup(release("v1")).await;
+        assert_eq!(fetch_count.load(Relaxed), 3);
+        lookup(release("v2")).await;
+        assert_eq!(fetch_count.load(Relaxed), 4);
+
+        // The releases in use are not evicted.
+        let in_use = client.0.release_artifacts.get(release("v2"));
+        lookup(release("v4")).await;
+        lookup(release("v5")).await;
+        assert!(Arc::ptr_eq(
+            &in_use,
+            &client.0.release_artifacts.get(release("v2"))
+        ));
+        assert_eq!(client.cache_len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_budget() {
+        use cargo_binstall_v0_20_1::*;
+
+        let client = create_client().await.remove(0);
+        let client = GhApiClient::with_request_budget(client.0.client.clone(), None, Some(2));
+
+        assert!(client.0.budget.try_consume());
+        assert!(client.0.budget.try_consume());
+        assert!(!client.0.budget.try_consume());
+        assert_eq!(client.requests_used(), 2);
+        assert_eq!(client.requests_remaining(), Some(0));
+        assert_eq!(
+            client.budget_usage().to_string(),
+            "2 GitHub API requests used, 0 remaining in budget"
+        );
+
+        // No request is sent once the budget is exhausted.
+        let ret = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: RELEASE,
+                artifact_name: ARTIFACTS[0].to_compact_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::BudgetExhausted);
+        assert_eq!(client.requests_used(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_response() {
+        use cargo_binstall_v0_20_1::*;
+
+        let client = create_client().await.remove(0);
+        let names = [ARTIFACTS[0].to_compact_string()];
+
+        let rets = client
+            .has_release_artifacts_inner(&RELEASE, &names, || async {
+                let truncated = br#"{"assets": [{"name": "cargo-binstall-aarch"#;
+                client
+                    .handle_fetch_release_ret(Ok(request::parse_restful_response(truncated)))
+                    .map(|release| release.map(|release| release.artifacts))
+            })
+            .await
+            .unwrap();
+        assert_eq!(rets, [HasReleaseArtifact::MalformedResponse]);
+        assert_eq!(client.malformed_responses(), 1);
+
+        // The API is not used again, not even for other releases.
+        let ret = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: GhRelease {
+                    tag: "v0.20.0".into(),
+                    ..RELEASE
+                },
+                artifact_name: ARTIFACTS[0].to_compact_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::MalformedResponse);
+        assert_eq!(client.requests_used(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_gh_api_client_cargo_binstall_v0_20_1() {
+        test_specific_release(
+            &cargo_binstall_v0_20_1::RELEASE,
+            cargo_binstall_v0_20_1::ARTIFACTS,
+        )
+        .await
+    }
+
+    /// Create a client replaying the fixture `name` in `tests/fixtures`,
+    /// instead of sending requests to GitHub.
+    #[cfg(feature = "fixture")]
+    fn create_replay_client(name: &str) -> GhApiClient {
+        create_replay_client_with_host(name, GhApiHost::default())
+    }
+
+    #[cfg(feature = "fixture")]
+    fn create_replay_client_with_host(name: &str, host: GhApiHost) -> GhApiClient {
+        GhApiClient::new(create_replay_remote_client(name), None).with_host(host)
+    }
+
+    #[cfg(feature = "fixture")]
+    fn create_replay_remote_client(name: &str) -> remote::Client {
+        let fixture = remote::Fixture::replay(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(name),
+        )
+        .unwrap();
+
+        remote::Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap()
+        .with_fixture(fixture)
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_rotate_auth_tokens() {
+        let client = GhApiClient::new(
+            create_replay_remote_client("gh-api-token-rotation"),
+            ["garbage".into(), "first".into(), "second".into()],
+        );
+        let artifact = |tag: &str| GhReleaseArtifact {
+            release: GhRelease {
+                owner: "tools".to_compact_string(),
+                repo: "widget".to_compact_string(),
+                tag: tag.to_compact_string(),
+            },
+            artifact_name: "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+        };
+
+        // The unauthorized token is skipped, only for the repositories of
+        // the same owner.
+        let ret = client
+            .has_release_artifact(artifact("v1.0.0"))
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::Yes);
+        let state = |index: usize| client.shared().auth_token_states[index].lock().unwrap();
+        assert!(!state(0).rejected);
+        assert!(state(0).owners.contains("tools"));
+        // GraphQL and then restful API with the unauthorized token.
+        assert_eq!(client.authenticated_requests(), 3);
+
+        // So is the rate limited one.
+        let ret = client
+            .has_release_artifact(artifact("v1.1.0"))
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::Yes);
+        assert!(state(1).is_rate_limited());
+        assert_eq!(client.authenticated_requests(), 5);
+        assert!(!client.is_rate_limited());
+
+        // GraphQL does not return the digest of the artifacts.
+        let info = client
+            .get_artifact(artifact("v1.1.0"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.size, Some(1048576));
+        assert_eq!(info.content_type.as_deref(), Some("application/gzip"));
+        assert_eq!(info.digest, None);
+    }
+
+    #[tokio::test]
+    async fn test_gh_api_client_rate_limited_auth_token_is_used_after_reset() {
+        use std::sync::Mutex;
+
+        let remote_client = remote::Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap();
+        let client = GhApiClient::new(remote_client, ["first".into(), "second".into()]);
+
+        let reset = Instant::now() + Duration::from_millis(200);
+        let used = Mutex::new(Vec::new());
+        let fetch = || {
+            client.fetch_with_auth_tokens(Some("tools"), |auth_token| {
+                used.lock().unwrap().push(auth_token.clone());
+                async move {
+                    match auth_token.as_deref() {
+                        Some("first") if Instant::now() < reset => {
+                            Err(FetchReleaseArtifactError::RateLimit { retry_after: reset })
+                        }
+                        _ => Ok(Some(auth_token)),
+                    }
+                }
+            })
+        };
+        let take_used = || std::mem::take(&mut *used.lock().unwrap());
+
+        // The first token is rate limited, the second one is used instead.
+        assert_eq!(fetch().await.ok(), Some(Some(Some("second".into()))));
+        assert_eq!(take_used(), [Some("first".into()), Some("second".into())]);
+
+        // It is skipped without sending any request until its reset.
+        assert_eq!(fetch().await.ok(), Some(Some(Some("second".into()))));
+        assert_eq!(take_used(), [Some("second".into())]);
+
+        // And used again afterwards.
+        tokio::time::sleep_until(reset.into()).await;
+        assert_eq!(fetch().await.ok(), Some(Some(Some("first".into()))));
+        assert_eq!(take_used(), [Some("first".into())]);
+        assert!(client.has_auth_token());
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_enterprise_server() {
+        let host: GhApiHost = "https://github.corp.example.com/api/v3".parse().unwrap();
+        let client = create_replay_client_with_host("gh-api-ghes-release", host);
+        let release = GhRelease {
+            owner: "tools".to_compact_string(),
+            repo: "widget".to_compact_string(),
+            tag: "v1.0.0".to_compact_string(),
+        };
+
+        let rets = client
+            .has_release_artifacts(
+                &release,
+                &[
+                    "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+                    "widget-x86_64-pc-windows-msvc.zip".to_compact_string(),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(rets, [HasReleaseArtifact::Yes, HasReleaseArtifact::No]);
+        assert_eq!(client.requests_used(), 1);
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_get_artifact() {
+        let host = GhApiHost::new("github.corp.example.com");
+        let client = create_replay_client_with_host("gh-api-ghes-release", host);
+        let artifact = |name: &str| GhReleaseArtifact {
+            release: GhRelease {
+                owner: "tools".to_compact_string(),
+                repo: "widget".to_compact_string(),
+                tag: "v1.0.0".to_compact_string(),
+            },
+            artifact_name: name.to_compact_string(),
+        };
+
+        let info = client
+            .get_artifact(artifact("widget-x86_64-unknown-linux-gnu.tgz"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.name, "widget-x86_64-unknown-linux-gnu.tgz");
+        assert_eq!(info.size, Some(2097152));
+        assert_eq!(info.content_type.as_deref(), Some("application/gzip"));
+        assert_eq!(
+            info.digest.as_deref(),
+            Some("sha256:6c1b3b3a5b7c2a4f0e8d9c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f")
+        );
+        assert_eq!(
+            info.sha256(),
+            Some("6c1b3b3a5b7c2a4f0e8d9c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f")
+        );
+
+        let info = client
+            .get_artifact(artifact("widget-aarch64-apple-darwin.tgz"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.digest, None);
+
+        let info = client
+            .get_artifact(artifact("widget-x86_64-pc-windows-msvc.zip"))
+            .await
+            .unwrap();
+        assert_eq!(info, None);
+
+        // The release is fetched once for all the artifacts.
+        assert_eq!(client.requests_used(), 1);
+        assert_eq!(client.release_cache_hits(), 2);
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_many_artifacts() {
+        let artifact = |i: u32| GhReleaseArtifact {
+            release: GhRelease {
+                owner: "cargo-bins".to_compact_string(),
+                repo: "many-targets".to_compact_string(),
+                tag: "v1.0.0".to_compact_string(),
+            },
+            artifact_name: format_compact!("many-targets-{i:03}.tgz"),
+        };
+
+        // The artifacts beyond the first 100 are on the next page, with
+        // both the GraphQL and the restful API.
+        for client in [
+            GhApiClient::new(
+                create_replay_remote_client("gh-api-many-artifacts"),
+                Some("token".into()),
+            ),
+            create_replay_client("gh-api-many-artifacts"),
+        ] {
+            for i in [0, 99, 100, 129] {
+                assert_eq!(
+                    client.has_release_artifact(artifact(i)).await.unwrap(),
+                    HasReleaseArtifact::Yes,
+                    "artifact #{i}"
+                );
+            }
+            assert_eq!(
+                client.has_release_artifact(artifact(130)).await.unwrap(),
+                HasReleaseArtifact::No
+            );
+            assert_eq!(client.requests_used(), 2);
+        }
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_download_artifact() {
+        let client = GhApiClient::new(
+            create_replay_remote_client("gh-api-private-artifact"),
+            Some("token".into()),
+        );
+        let artifact = GhReleaseArtifact {
+            release: GhRelease {
+                owner: "cargo-bins".to_compact_string(),
+                repo: "private-tool".to_compact_string(),
+                tag: "v1.0.0".to_compact_string(),
+            },
+            artifact_name: "private-tool-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+        };
+
+        let artifact_url = client.get_artifact_url(&artifact).await.unwrap().unwrap();
+        assert_eq!(
+            artifact_url.as_str(),
+            "https://api.github.com/repos/cargo-bins/private-tool/releases/assets/140000001"
+        );
+
+        let stream = client
+            .download_artifact(&artifact_url)
+            .await
+            .unwrap()
+            .unwrap();
+        let data = crate::download::Download::from_stream(stream)
+            .into_bytes()
+            .await
+            .unwrap();
+        assert_eq!(data, b"private artifact content"[..]);
+
+        let missing_url = artifact_url.as_str().replace("140000001", "140000002");
+        assert!(client
+            .download_artifact(&missing_url.parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(client.requests_used(), 3);
+        assert_eq!(client.authenticated_requests(), 3);
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_owner_unauthorized() {
+        let client = GhApiClient::new(
+            create_replay_remote_client("gh-api-owner-unauthorized"),
+            Some("token".into()),
+        );
+        let download = |owner: &str, id: u32| {
+            let client = client.clone();
+            let url: remote::Url =
+                format!("https://api.github.com/repos/{owner}/tool/releases/assets/{id}")
+                    .parse()
+                    .unwrap();
+            async move {
+                let stream = client.download_artifact(&url).await.unwrap().unwrap();
+                crate::download::Download::from_stream(stream)
+                    .into_bytes()
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // The token is unauthorized for the repositories of an organization
+        // enforcing SAML SSO, they are downloaded without it.
+        assert_eq!(
+            download("sso-org", 140000001).await,
+            b"sso-org artifact content\n"[..]
+        );
+        assert_eq!(client.authenticated_requests(), 1);
+
+        // It is still used for the other owners.
+        assert_eq!(
+            download("cargo-bins", 140000002).await,
+            b"cargo-bins artifact content\n"[..]
+        );
+        assert_eq!(client.authenticated_requests(), 2);
+
+        download("sso-org", 140000001).await;
+        assert_eq!(client.authenticated_requests(), 2);
+
+        download("other-org", 140000003).await;
+        assert_eq!(client.authenticated_requests(), 3);
+        assert!(client.has_auth_token());
+
+        // Unauthorized for two different owners in a row, so the token is
+        // invalid.
+        download("third-org", 140000004).await;
+        assert_eq!(client.authenticated_requests(), 4);
+        assert!(!client.has_auth_token());
+
+        download("cargo-bins", 140000002).await;
+        assert_eq!(client.authenticated_requests(), 4);
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_has_release_artifacts_batch() {
+        let releases = [
+            GhRelease {
+                owner: "tools".to_compact_string(),
+                repo: "widget".to_compact_string(),
+                tag: "v1.0.0".to_compact_string(),
+            },
+            GhRelease {
+                owner: "tools".to_compact_string(),
+                repo: "widget".to_compact_string(),
+                tag: "widget/v1.0.0".to_compact_string(),
+            },
+            GhRelease {
+                owner: "tools".to_compact_string(),
+                repo: "gone".to_compact_string(),
+                tag: "v1.0.0".to_compact_string(),
+            },
+        ];
+        let name = "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string();
+
+        // With a token they are fetched with one GraphQL query, without
+        // one restful request each.
+        for (auth_token, requests) in [(Some("token".into()), 1), (None, 3)] {
+            let client = GhApiClient::new(
+                create_replay_remote_client("gh-api-batch-releases"),
+                auth_token,
+            );
+
+            let rets = client.has_release_artifacts_batch(&releases).await.unwrap();
+            assert_eq!(rets.len(), 3);
+            assert!(rets[0].is_none());
+            let artifacts = rets[1].as_ref().unwrap();
+            assert!(artifacts.contains(&name));
+            assert_eq!(artifacts.get(&name).unwrap().size, Some(1048576));
+            assert!(rets[2].is_none());
+            assert_eq!(client.requests_used(), requests);
+
+            // Later lookups are answered from the cache.
+            let ret = client
+                .has_release_artifact(GhReleaseArtifact {
+                    release: releases[1].clone(),
+                    artifact_name: name.clone(),
+                })
+                .await
+                .unwrap();
+            assert_eq!(ret, HasReleaseArtifact::Yes);
+            let rets = client
+                .has_release_artifacts(&releases[0], slice::from_ref(&name))
+                .await
+                .unwrap();
+            assert_eq!(rets, [HasReleaseArtifact::NoSuchRelease]);
+            client.has_release_artifacts_batch(&releases).await.unwrap();
+            assert_eq!(client.requests_used(), requests);
+            assert_eq!(client.release_cache_hits(), 5);
+        }
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_find_release() {
+        let repo = GhRepo {
+            owner: "tools".to_compact_string(),
+            repo: "widget".to_compact_string(),
+        };
+        let candidates = ["v1.0.0", "widget/v1.0.0", "1.0.0"].map(CompactString::from);
+        let name = "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string();
+
+        // With a token all the candidates are fetched with one GraphQL
+        // query, without one the ones after the release found are not.
+        for (auth_token, requests) in [(Some("token".into()), 1), (None, 2)] {
+            let client = GhApiClient::new(
+                create_replay_remote_client("gh-api-find-release"),
+                auth_token,
+            );
+
+            let release = client
+                .find_release(&repo, &candidates)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(release.tag, "widget/v1.0.0");
+            assert_eq!(client.requests_used(), requests);
+
+            let meta = client.get_release_meta(&release).await.unwrap().unwrap();
+            assert_eq!(meta.published_at.as_deref(), Some("2024-11-02T10:00:00Z"));
+            assert!(!meta.prerelease && !meta.draft);
+            assert_eq!(
+                client.list_release_artifacts(&release).await.unwrap(),
+                Some(vec![name.clone()])
+            );
+            let not_found = GhRelease {
+                tag: candidates[0].clone(),
+                ..release.clone()
+            };
+            assert_eq!(
+                client.list_release_artifacts(&not_found).await.unwrap(),
+                None
+            );
+
+            let info = client
+                .get_artifact_fuzzy(GhReleaseArtifact {
+                    release: release.clone(),
+                    artifact_name: "Widget-x86_64-unknown-linux-gnu.tar.gz".to_compact_string(),
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(info.name, name);
+
+            // The release found is cached.
+            let ret = client
+                .has_release_artifact(GhReleaseArtifact {
+                    release,
+                    artifact_name: name.clone(),
+                })
+                .await
+                .unwrap();
+            assert_eq!(ret, HasReleaseArtifact::Yes);
+            assert_eq!(client.requests_used(), requests);
+
+            assert_eq!(client.find_release(&repo, &[]).await.unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_guess_pkg_fmt_of_artifacts() {
+        for artifact in cargo_binstall_v0_20_1::ARTIFACTS
+            .iter()
+            .chain(cargo_audit_v_0_17_6::ARTIFACTS)
+        {
+            let expected = if artifact.ends_with(".zip") {
+                PkgFmt::Zip
+            } else {
+                PkgFmt::Tgz
+            };
+
+            assert_eq!(PkgFmt::guess_from_path(artifact), Some(expected));
+
+            let url = format!(
+                "https://github.com/cargo-bins/cargo-binstall/releases/download/v0.20.1/{artifact}"
+            );
+            let url = url::Url::parse(&url).unwrap();
+            assert_eq!(PkgFmt::guess_from_path(url.path()), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_get_artifact_fuzzy() {
+        let cargo_binstall: GhReleaseArtifacts =
+            cargo_binstall_v0_20_1::ARTIFACTS.iter().copied().collect();
+        let cargo_audit: GhReleaseArtifacts =
+            cargo_audit_v_0_17_6::ARTIFACTS.iter().copied().collect();
+        let other: GhReleaseArtifacts = [
+            "ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz",
+            "ripgrep_14.1.0_amd64.deb",
+            "Tool-Linux-x86_64.tar.gz",
+            "tool-v1.0.0-x86_64-unknown-linux-gnu.tgz",
+            "tool-v1.0.0-x86_64-unknown-linux-gnu.zip",
+        ]
+        .into_iter()
+        .collect();
+
+        for (artifacts, artifact_name, expected) in [
+            (
+                &cargo_binstall,
+                "cargo_binstall-x86_64-unknown-linux-musl.tgz",
+                Some("cargo-binstall-x86_64-unknown-linux-musl.tgz"),
+            ),
+            (
+                &cargo_binstall,
+                "Cargo-Binstall-x86_64-apple-darwin.full.zip",
+                Some("cargo-binstall-x86_64-apple-darwin.full.zip"),
+            ),
+            (
+                &cargo_binstall,
+                "cargo-binstall-x86_64-unknown-linux-gnu.tar.gz",
+                Some("cargo-binstall-x86_64-unknown-linux-gnu.tgz"),
+            ),
+            (
+                &cargo_binstall,
+                "cargo-binstall-x86_64-unknown-linux-gnu.tar.xz",
+                None,
+            ),
+            (
+                &cargo_audit,
+                "cargo-audit-v0.17.6-x86_64-unknown-linux-musl.tar.gz",
+                Some("cargo-audit-x86_64-unknown-linux-musl-v0.17.6.tgz"),
+            ),
+            (
+                &cargo_audit,
+                "cargo-audit-x86_64-pc-windows-msvc-v0.17.6.tgz",
+                None,
+            ),
+            (
+                &other,
+                "ripgrep-14.1.0-x86_64-unknown-linux-musl.tgz",
+                Some("ripgrep-14.1.0-x86_64-unknown-linux-musl.tar.gz"),
+            ),
+            (
+                &other,
+                "tool-linux-x86_64.tgz",
+                Some("Tool-Linux-x86_64.tar.gz"),
+            ),
+            (
+                &other,
+                "tool-x86_64-unknown-linux-gnu-v1.0.0.zip",
+                Some("tool-v1.0.0-x86_64-unknown-linux-gnu.zip"),
+            ),
+            // Parts are not dropped nor added.
+            (&other, "ripgrep-x86_64-unknown-linux-musl.tgz", None),
+            (&other, "ripgrep_14.1.0_amd64_static.deb", None),
+        ] {
+            assert_eq!(
+                artifacts.get_fuzzy(artifact_name).map(|info| &*info.name),
+                expected,
+                "{artifact_name}"
+            );
+        }
+
+        // More than one artifact matching is ambiguous.
+        let ambiguous: GhReleaseArtifacts = ["tool-linux.tgz", "Tool_Linux.tar.gz"]
+            .into_iter()
+            .collect();
+        assert!(ambiguous.get_fuzzy("tool-linux.tgz").is_none());
+        assert!(ambiguous.get("tool-linux.tgz").is_some());
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_release_meta() {
+        let client = create_replay_client("gh-api-release-meta");
+        let release = |tag: &str| GhRelease {
+            owner: "tools".to_compact_string(),
+            repo: "widget".to_compact_string(),
+            tag: tag.to_compact_string(),
+        };
+
+        let meta = client
+            .get_release_meta(&release("v1.0.0-rc.1"))
+            .await
+            .unwrap();
+        assert_eq!(
+            meta,
+            Some(GhReleaseMeta {
+                published_at: Some("2024-11-02T10:00:00Z".into()),
+                prerelease: true,
+                draft: false,
+            })
+        );
+
+        // The artifacts of drafts cannot be downloaded without a token.
+        let ret = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: release("v2.0.0"),
+                artifact_name: "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::NoSuchRelease);
+        assert_eq!(
+            client.get_release_meta(&release("v2.0.0")).await.unwrap(),
+            None
+        );
+        assert_eq!(client.requests_used(), 2);
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_cargo_binstall_no_such_release() {
+        let client = create_replay_client("gh-api-no-such-release");
+        let release = GhRelease {
+            owner: "cargo-bins".to_compact_string(),
+            repo: "cargo-binstall".to_compact_string(),
+            // We are currently at v0.20.1 and we would never release
+            // anything older than v0.20.1
+            tag: "v0.18.2".to_compact_string(),
+        };
+
+        let ret = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: release.clone(),
+                artifact_name: "1234".to_compact_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::NoSuchRelease);
+        assert_eq!(client.requests_used(), 1);
+        assert_eq!(client.authenticated_requests(), 0);
+
+        // The rate limit is recorded from error responses too.
+        let status = client.rate_limit_status().unwrap();
+        assert_eq!((status.remaining, status.limit), (59, 60));
+        assert!(!client.is_rate_limited());
+
+        // The release is only fetched once.
+        let ret = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: release.clone(),
+                artifact_name: "5678".to_compact_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::NoSuchRelease);
+        assert_eq!(client.requests_used(), 1);
+        assert_eq!(client.release_cache_hits(), 1);
+
+        // Requests missing from the fixture fail instead of being sent.
+        let err = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: GhRelease {
+                    tag: "v0.18.1".to_compact_string(),
+                    ..release
+                },
+                artifact_name: "1234".to_compact_string(),
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no response to GET"), "{err}");
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_get_latest_release() {
+        let client = create_replay_client("gh-api-latest-release");
+        let repo = GhRepo {
+            owner: "cargo-bins".to_compact_string(),
+            repo: "cargo-binstall".to_compact_string(),
+        };
 
-                Ok(HasReleaseArtifact::RateLimit { retry_after })
-            }
-            Err(Error::Error(err)) => Err(err),
-        }
+        let release = client.get_latest_release(&repo).await.unwrap();
+        assert_eq!(release, Some(repo.release("v1.4.4".to_compact_string())));
+        assert_eq!(client.requests_used(), 1);
+
+        // The latest release is only fetched once.
+        let release = client.get_latest_release(&repo).await.unwrap();
+        assert_eq!(release.unwrap().tag, "v1.4.4");
+        assert_eq!(client.requests_used(), 1);
+        assert_eq!(client.release_cache_hits(), 1);
+
+        // A repository without any release is not an error.
+        let release = client
+            .get_latest_release(&GhRepo {
+                owner: "cargo-bins".to_compact_string(),
+                repo: "no-releases".to_compact_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(release, None);
+        assert_eq!(client.requests_used(), 2);
     }
-}
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
-pub enum HasReleaseArtifact {
-    Yes,
-    No,
-    NoSuchRelease,
-    /// GitHub returns 401 requiring a token.
-    /// In this case, it makes sense to fallback to HEAD/GET.
-    Unauthorized,
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_latest_download() {
+        let client = create_replay_client("gh-api-latest-download");
+        let artifact = |repo: &str, artifact_name: &str| {
+            GhReleaseArtifact::try_extract_from_url(
+                &url::Url::parse(&format!(
+                    "https://github.com/cargo-bins/{repo}/releases/latest/download/{artifact_name}"
+                ))
+                .unwrap(),
+                &[GITHUB_COM],
+            )
+            .unwrap()
+        };
 
-    /// GitHub rate limit is applied per hour, so in case of reaching the rate
-    /// limit, [`GhApiClient`] will return this variant and let the user decide
-    /// what to do.
-    ///
-    /// Usually it is more sensible to fallback to directly HEAD/GET the
-    /// artifact url than waiting until `retry_after`.
-    ///
-    /// If you encounter this frequently, then you should consider getting an
-    /// authentication token (can be personal access or oath access token),
-    /// which should give you 5000 requests per hour per user.
-    ///
-    /// Rate limit for unauthorized user is 60 requests per hour per originating
-    /// IP address, so it is very easy to be rate limited.
-    RateLimit {
-        retry_after: Instant,
-    },
-}
+        // The latest release is looked up, then its artifacts.
+        let ret = client
+            .has_release_artifact(artifact(
+                "cargo-binstall",
+                "cargo-binstall-x86_64-unknown-linux-musl.tgz",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::Yes);
+        assert_eq!(client.requests_used(), 2);
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use compact_str::{CompactString, ToCompactString};
-    use std::{env, num::NonZeroU16};
+        let ret = client
+            .has_release_artifact(artifact("cargo-binstall", "cargo-binstall.zip"))
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::No);
+        assert_eq!(client.requests_used(), 2);
 
-    mod cargo_binstall_v0_20_1 {
-        use super::{CompactString, GhRelease};
+        let ret = client
+            .has_release_artifact(artifact("no-releases", "tool.tgz"))
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::NoSuchRelease);
+        assert_eq!(client.requests_used(), 3);
+    }
 
-        pub(super) const RELEASE: GhRelease = GhRelease {
-            owner: CompactString::new_inline("cargo-bins"),
-            repo: CompactString::new_inline("cargo-binstall"),
-            tag: CompactString::new_inline("v0.20.1"),
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_get_repo_info() {
+        let repo = |repo: &str| GhRepo {
+            owner: "cargo-bins".to_compact_string(),
+            repo: repo.to_compact_string(),
         };
 
-        pub(super) const ARTIFACTS: &[&str] = &[
-            "cargo-binstall-aarch64-apple-darwin.full.zip",
-            "cargo-binstall-aarch64-apple-darwin.zip",
-            "cargo-binstall-aarch64-pc-windows-msvc.full.zip",
-            "cargo-binstall-aarch64-pc-windows-msvc.zip",
-            "cargo-binstall-aarch64-unknown-linux-gnu.full.tgz",
-            "cargo-binstall-aarch64-unknown-linux-gnu.tgz",
-            "cargo-binstall-aarch64-unknown-linux-musl.full.tgz",
-            "cargo-binstall-aarch64-unknown-linux-musl.tgz",
-            "cargo-binstall-armv7-unknown-linux-gnueabihf.full.tgz",
-            "cargo-binstall-armv7-unknown-linux-gnueabihf.tgz",
-            "cargo-binstall-armv7-unknown-linux-musleabihf.full.tgz",
-            "cargo-binstall-armv7-unknown-linux-musleabihf.tgz",
-            "cargo-binstall-universal-apple-darwin.full.zip",
-            "cargo-binstall-universal-apple-darwin.zip",
-            "cargo-binstall-x86_64-apple-darwin.full.zip",
-            "cargo-binstall-x86_64-apple-darwin.zip",
-            "cargo-binstall-x86_64-pc-windows-msvc.full.zip",
-            "cargo-binstall-x86_64-pc-windows-msvc.zip",
-            "cargo-binstall-x86_64-unknown-linux-gnu.full.tgz",
-            "cargo-binstall-x86_64-unknown-linux-gnu.tgz",
-            "cargo-binstall-x86_64-unknown-linux-musl.full.tgz",
-            "cargo-binstall-x86_64-unknown-linux-musl.tgz",
-        ];
+        let client = GhApiClient::new(
+            create_replay_remote_client("gh-api-repo-info"),
+            Some("token".into()),
+        );
+        let repo_info = client.get_repo_info(&repo("cargo-binstall")).await.unwrap();
+        assert_eq!(
+            repo_info,
+            Some(GhRepoInfo {
+                is_private: false,
+                default_branch: Some("main".into()),
+                archived: false,
+                has_releases: true,
+            })
+        );
+        assert_eq!(client.get_repo_info(&repo("gone")).await.unwrap(), None);
+        assert_eq!(client.requests_used(), 2);
+
+        // It is only fetched once.
+        client.get_repo_info(&repo("cargo-binstall")).await.unwrap();
+        assert_eq!(client.requests_used(), 2);
+        assert_eq!(client.release_cache_hits(), 1);
+
+        // The restful API needs another request for the releases.
+        let client = create_replay_client("gh-api-repo-info");
+        let repo_info = client.get_repo_info(&repo("old-tool")).await.unwrap();
+        assert_eq!(
+            repo_info,
+            Some(GhRepoInfo {
+                is_private: false,
+                default_branch: Some("master".into()),
+                archived: true,
+                has_releases: false,
+            })
+        );
+        assert_eq!(client.get_repo_info(&repo("gone")).await.unwrap(), None);
+        assert_eq!(client.requests_used(), 3);
     }
 
-    fn try_extract_artifact_from_str(s: &str) -> Option<GhReleaseArtifact> {
-        GhReleaseArtifact::try_extract_from_url(&url::Url::parse(s).unwrap())
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_check_auth() {
+        let remote_client = create_replay_remote_client("gh-api-auth-check");
+
+        let client = GhApiClient::new(remote_client.clone(), Some("token".into()));
+        let auth_check = GhAuthCheck::Authenticated {
+            login: "octocat".into(),
+            scopes: Some(vec!["repo".into(), "read:org".into()]),
+        };
+        assert_eq!(client.check_auth().await.unwrap(), auth_check);
+
+        // It is only checked once.
+        assert_eq!(client.check_auth().await.unwrap(), auth_check);
+        assert_eq!(client.requests_used(), 1);
+
+        // A rejected token is still used until a lookup is unauthorized.
+        let client = GhApiClient::new(remote_client, Some("expired".into()));
+        assert_eq!(client.check_auth().await.unwrap(), GhAuthCheck::Rejected);
+        assert!(client.has_auth_token());
+
+        let client = GhApiClient::new(create_replay_remote_client("gh-api-auth-check"), None);
+        assert_eq!(client.check_auth().await.unwrap(), GhAuthCheck::NoToken);
+        assert_eq!(client.requests_used(), 0);
     }
 
-    fn assert_extract_gh_release_artifacts_failures(urls: &[&str]) {
-        for url in urls {
-            assert_eq!(try_extract_artifact_from_str(url), None);
-        }
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_list_releases() {
+        let client = create_replay_client("gh-api-list-releases");
+        let repo = GhRepo {
+            owner: "cargo-bins".to_compact_string(),
+            repo: "cargo-binstall".to_compact_string(),
+        };
+
+        let tags = |releases: Vec<GhRelease>| -> Vec<CompactString> {
+            releases.into_iter().map(|release| release.tag).collect()
+        };
+
+        // The second page is fetched through the `Link` header, and the
+        // draft is skipped.
+        let releases = client.list_releases(&repo).await.unwrap();
+        assert_eq!(tags(releases), ["v1.4.4", "v1.4.3", "v1.4.2", "v1.4.1"]);
+        assert_eq!(client.requests_used(), 2);
+
+        let releases = client.list_releases(&repo).await.unwrap();
+        assert_eq!(releases.len(), 4);
+        assert_eq!(client.requests_used(), 2);
+        assert_eq!(client.release_cache_hits(), 1);
     }
 
-    #[test]
-    fn extract_gh_release_artifacts_failure() {
-        use cargo_binstall_v0_20_1::*;
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_release_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gh-releases.json");
+        let release = GhRelease {
+            owner: "tools".to_compact_string(),
+            repo: "widget".to_compact_string(),
+            tag: "v1.0.0".to_compact_string(),
+        };
+        let artifact = GhReleaseArtifact {
+            release,
+            artifact_name: "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+        };
 
-        let GhRelease { owner, repo, tag } = RELEASE;
+        let host = GhApiHost::new("github.corp.example.com");
+        let client = create_replay_client_with_host("gh-api-ghes-release", host.clone())
+            .with_release_cache(GhReleaseCache::load(path.clone()));
+        let ret = client.has_release_artifact(artifact.clone()).await.unwrap();
+        assert_eq!(ret, HasReleaseArtifact::Yes);
+        assert_eq!(client.requests_used(), 1);
+        drop(client);
+
+        // The next process answers from the cache, without any request.
+        let client = create_replay_client_with_host("gh-api-no-such-release", host)
+            .with_release_cache(GhReleaseCache::load(path));
+        let ret = client.has_release_artifact(artifact.clone()).await.unwrap();
+        assert_eq!(ret, HasReleaseArtifact::Yes);
+        assert_eq!(client.requests_used(), 0);
+        assert_eq!(client.release_cache_hits(), 1);
+
+        // Including the metadata of the artifacts.
+        let info = client.get_artifact(artifact).await.unwrap().unwrap();
+        assert_eq!(info.size, Some(2097152));
+        assert!(info.digest.is_some());
+    }
 
-        assert_extract_gh_release_artifacts_failures(&[
-            "https://examle.com",
-            "https://github.com",
-            &format!("https://github.com/{owner}"),
-            &format!("https://github.com/{owner}/{repo}"),
-            &format!("https://github.com/{owner}/{repo}/123e"),
-            &format!("https://github.com/{owner}/{repo}/releases/21343"),
-            &format!("https://github.com/{owner}/{repo}/releases/download"),
-            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}"),
-            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}/a/23"),
-            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}/a#a=12"),
-            &format!("https://github.com/{owner}/{repo}/releases/download/{tag}/a?page=3"),
-        ]);
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gh_api_client_release_etag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gh-releases.json");
+        let artifact = GhReleaseArtifact {
+            release: GhRelease {
+                owner: "tools".to_compact_string(),
+                repo: "widget".to_compact_string(),
+                tag: "v1.0.0".to_compact_string(),
+            },
+            artifact_name: "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+        };
+        let remote_client = create_replay_remote_client("gh-api-release-etag");
+        let load_cache = || GhReleaseCache::load(path.clone()).found_ttl(Duration::ZERO);
+
+        let client = GhApiClient::new(remote_client.clone(), None).with_release_cache(load_cache());
+        let ret = client.has_release_artifact(artifact.clone()).await.unwrap();
+        assert_eq!(ret, HasReleaseArtifact::Yes);
+        assert_eq!(client.requests_used(), 1);
+        drop(client);
+
+        // The expired release is not modified, so the cached artifacts are
+        // used.
+        let client = GhApiClient::new(remote_client, None).with_release_cache(load_cache());
+        let info = client.get_artifact(artifact).await.unwrap().unwrap();
+        assert_eq!(info.size, Some(1048576));
+        assert_eq!(client.requests_used(), 1);
+        assert_eq!(client.release_cache_hits(), 0);
     }
 
-    #[test]
-    fn extract_gh_release_artifacts_success() {
+    #[tokio::test]
+    async fn test_is_rate_limited() {
         use cargo_binstall_v0_20_1::*;
 
-        let GhRelease { owner, repo, tag } = RELEASE;
+        let client = create_client().await.remove(0);
+        assert!(!client.is_rate_limited());
+        assert_eq!(client.rate_limit_status(), None);
 
-        for artifact in ARTIFACTS {
-            let GhReleaseArtifact {
-                release,
-                artifact_name,
-            } = try_extract_artifact_from_str(&format!(
-                "https://github.com/{owner}/{repo}/releases/download/{tag}/{artifact}"
-            ))
+        *client.shared().retry_after.lock().unwrap() =
+            Some(Instant::now() + Duration::from_secs(60));
+        assert!(client.is_rate_limited());
+
+        // No request is sent while rate limited.
+        let ret = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: RELEASE,
+                artifact_name: ARTIFACTS[0].to_compact_string(),
+            })
+            .await
             .unwrap();
+        assert!(matches!(ret, HasReleaseArtifact::RateLimit { .. }));
+        assert_eq!(client.requests_used(), 0);
 
-            assert_eq!(release, RELEASE);
-            assert_eq!(artifact_name, artifact);
-        }
+        *client.shared().retry_after.lock().unwrap() = Some(Instant::now());
+        assert!(!client.is_rate_limited());
     }
 
-    /// Mark this as an async fn so that you won't accidentally use it in
-    /// sync context.
-    async fn create_client() -> Vec<GhApiClient> {
-        let client = remote::Client::new(
-            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_shared_state() {
+        let first = GhApiClient::new(
+            create_replay_remote_client("gh-api-shared-rate-limit"),
             None,
-            NonZeroU16::new(10).unwrap(),
-            1.try_into().unwrap(),
-            [],
-        )
-        .unwrap();
-
-        let mut gh_clients = vec![GhApiClient::new(client.clone(), None)];
+        );
+        let second = GhApiClient::with_state(
+            create_replay_remote_client("gh-api-shared-rate-limit"),
+            first.state(),
+            None,
+        );
+        let artifact = |tag: &str| GhReleaseArtifact {
+            release: GhRelease {
+                owner: "tools".to_compact_string(),
+                repo: "widget".to_compact_string(),
+                tag: tag.to_compact_string(),
+            },
+            artifact_name: "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+        };
 
-        if let Ok(token) = env::var("GITHUB_TOKEN") {
-            gh_clients.push(GhApiClient::new(client, Some(token.into())));
-        }
+        let ret = first
+            .has_release_artifact(artifact("v1.1.0"))
+            .await
+            .unwrap();
+        assert!(matches!(ret, HasReleaseArtifact::RateLimit { .. }));
+        assert_eq!(first.requests_used(), 1);
+        assert!(second.is_rate_limited());
+
+        // The other client respects the rate limit without sending any
+        // request, even for another release.
+        let ret = second
+            .has_release_artifact(artifact("v1.0.0"))
+            .await
+            .unwrap();
+        assert!(matches!(ret, HasReleaseArtifact::RateLimit { .. }));
+        assert_eq!(second.requests_used(), 0);
 
-        gh_clients
+        // A client with its own state is not rate limited.
+        let other = GhApiClient::new(
+            create_replay_remote_client("gh-api-shared-rate-limit"),
+            None,
+        );
+        assert!(!other.is_rate_limited());
     }
 
-    async fn test_specific_release(release: &GhRelease, artifacts: &[&str]) {
-        for client in create_client().await {
-            eprintln!("In client {client:?}");
+    #[cfg(feature = "fixture")]
+    #[tokio::test(start_paused = true)]
+    async fn test_has_release_artifact_with_retry() {
+        use cargo_audit_v_0_17_6::*;
 
-            for artifact_name in artifacts {
-                let ret = client
-                    .has_release_artifact(GhReleaseArtifact {
-                        release: release.clone(),
-                        artifact_name: artifact_name.to_compact_string(),
-                    })
-                    .await
-                    .unwrap();
+        let client = create_replay_client("gh-api-escaped-tag");
+        let artifact = |i: usize| GhReleaseArtifact {
+            release: RELEASE,
+            artifact_name: ARTIFACTS[i].to_compact_string(),
+        };
+        let rate_limit = || {
+            *client.shared().retry_after.lock().unwrap() =
+                Some(Instant::now() + Duration::from_secs(60))
+        };
 
-                assert!(
-                    matches!(
-                        ret,
-                        HasReleaseArtifact::Yes | HasReleaseArtifact::RateLimit { .. }
-                    ),
-                    "for '{artifact_name}': answer is {:#?}",
-                    ret
-                );
-            }
+        // The reset is too far away, or the wait is cancelled.
+        rate_limit();
+        let ret = client
+            .has_release_artifact_with_retry(
+                artifact(0),
+                Duration::from_secs(30),
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+        assert!(matches!(ret, HasReleaseArtifact::RateLimit { .. }));
 
-            let ret = client
-                .has_release_artifact(GhReleaseArtifact {
-                    release: release.clone(),
-                    artifact_name: "123z".to_compact_string(),
-                })
-                .await
-                .unwrap();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let ret = client
+            .has_release_artifact_with_retry(artifact(0), Duration::from_secs(120), cancel)
+            .await
+            .unwrap();
+        assert!(matches!(ret, HasReleaseArtifact::RateLimit { .. }));
+        assert_eq!(client.requests_used(), 0);
+
+        // Concurrent lookups all succeed once the rate limit resets.
+        let start = tokio::time::Instant::now();
+        let rets = futures_util::future::join_all((0..ARTIFACTS.len()).map(|i| {
+            client.has_release_artifact_with_retry(
+                artifact(i),
+                Duration::from_secs(120),
+                CancellationToken::new(),
+            )
+        }))
+        .await;
+        for ret in rets {
+            assert_eq!(ret.unwrap(), HasReleaseArtifact::Yes);
+        }
+        assert!(start.elapsed() >= Duration::from_secs(59));
+        assert_eq!(client.requests_used(), 1);
+        assert!(!client.is_rate_limited());
+    }
 
+    #[test]
+    fn test_retry_policy_delay() {
+        let retry_policy = GhApiRetryPolicy::default();
+
+        for (retry, secs) in [(1, 1.0), (2, 3.0), (3, 9.0)] {
+            let delay = retry_policy.delay(retry).as_secs_f64();
             assert!(
-                matches!(
-                    ret,
-                    HasReleaseArtifact::No | HasReleaseArtifact::RateLimit { .. }
-                ),
-                "ret = {:#?}",
-                ret
+                (secs * 0.75..=secs * 1.25).contains(&delay),
+                "retry {retry}: {delay}s"
             );
         }
+
+        let no_delay = GhApiRetryPolicy {
+            initial_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(no_delay.delay(3), Duration::ZERO);
     }
 
+    #[cfg(feature = "fixture")]
     #[tokio::test]
-    async fn test_gh_api_client_cargo_binstall_v0_20_1() {
-        test_specific_release(
-            &cargo_binstall_v0_20_1::RELEASE,
-            cargo_binstall_v0_20_1::ARTIFACTS,
+    async fn test_gh_api_client_retry_server_error() {
+        let client = create_replay_client_with_host(
+            "gh-api-server-error",
+            GhApiHost::new("github.corp.example.com"),
         )
-        .await
+        .with_retry_policy(GhApiRetryPolicy {
+            initial_delay: Duration::ZERO,
+            ..Default::default()
+        });
+        let release = GhRelease {
+            owner: "tools".to_compact_string(),
+            repo: "widget".to_compact_string(),
+            tag: "v1.0.0".to_compact_string(),
+        };
+
+        // A 502 is retried.
+        let ret = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: release.clone(),
+                artifact_name: "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(ret, HasReleaseArtifact::Yes);
+        assert_eq!(client.requests_used(), 2);
+
+        // Until the retries are exhausted.
+        let err = client
+            .has_release_artifact(GhReleaseArtifact {
+                release: GhRelease {
+                    tag: "v0.9.0".to_compact_string(),
+                    ..release
+                },
+                artifact_name: "widget-x86_64-unknown-linux-gnu.tgz".to_compact_string(),
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("failed after 4 attempts"), "{err}");
+        assert!(err.is_transient());
+        assert_eq!(client.requests_used(), 6);
     }
 
     #[tokio::test]
-    async fn test_gh_api_client_cargo_binstall_no_such_release() {
-        for client in create_client().await {
-            let release = GhRelease {
+    async fn test_get_latest_release_budget_exhausted() {
+        let client = create_client().await.remove(0);
+        let client = GhApiClient::with_request_budget(client.0.client.clone(), None, Some(0));
+
+        let err = client
+            .get_latest_release(&GhRepo {
                 owner: "cargo-bins".to_compact_string(),
                 repo: "cargo-binstall".to_compact_string(),
-                // We are currently at v0.20.1 and we would never release
-                // anything older than v0.20.1
-                tag: "v0.18.2".to_compact_string(),
-            };
-
-            let ret = client
-                .has_release_artifact(GhReleaseArtifact {
-                    release,
-                    artifact_name: "1234".to_compact_string(),
-                })
-                .await
-                .unwrap();
-
-            assert!(
-                matches!(
-                    ret,
-                    HasReleaseArtifact::NoSuchRelease | HasReleaseArtifact::RateLimit { .. }
-                ),
-                "ret = {:#?}",
-                ret
-            );
-        }
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, GhApiError::BudgetExhausted), "{err}");
+        assert_eq!(client.requests_used(), 0);
     }
 
     mod cargo_audit_v_0_17_6 {
         use super::*;
 
-        const RELEASE: GhRelease = GhRelease {
+        pub(super) const RELEASE: GhRelease = GhRelease {
             owner: CompactString::new_inline("rustsec"),
             repo: CompactString::new_inline("rustsec"),
             tag: CompactString::new_inline("cargo-audit/v0.17.6"),
         };
 
-        const ARTIFACTS: &[&str] = &[
+        pub(super) const ARTIFACTS: &[&str] = &[
             "cargo-audit-aarch64-unknown-linux-gnu-v0.17.6.tgz",
             "cargo-audit-armv7-unknown-linux-gnueabihf-v0.17.6.tgz",
             "cargo-audit-x86_64-apple-darwin-v0.17.6.tgz",
@@ -502,5 +3445,35 @@ mod test {
         async fn test_gh_api_client_cargo_audit_v_0_17_6() {
             test_specific_release(&RELEASE, ARTIFACTS).await
         }
+
+        /// The `/` in the tag must be escaped in the path of the restful
+        /// API, which is used without a token.
+        #[cfg(feature = "fixture")]
+        #[tokio::test]
+        async fn test_gh_api_client_escaped_tag() {
+            let client = create_replay_client("gh-api-escaped-tag");
+
+            for artifact_name in ARTIFACTS {
+                let ret = client
+                    .has_release_artifact(GhReleaseArtifact {
+                        release: RELEASE,
+                        artifact_name: artifact_name.to_compact_string(),
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(ret, HasReleaseArtifact::Yes, "{artifact_name}");
+            }
+
+            let ret = client
+                .has_release_artifact(GhReleaseArtifact {
+                    release: RELEASE,
+                    artifact_name: "cargo-audit-x86_64-unknown-linux-gnu-v0.17.6.zip".into(),
+                })
+                .await
+                .unwrap();
+            assert_eq!(ret, HasReleaseArtifact::No);
+            assert_eq!(client.requests_used(), 1);
+            assert_eq!(client.authenticated_requests(), 0);
+        }
     }
 }
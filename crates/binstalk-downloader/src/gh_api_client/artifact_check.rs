@@ -0,0 +1,230 @@
+use std::sync::atomic::Ordering::Relaxed;
+
+use compact_str::CompactString;
+use tracing::{debug, warn};
+
+use super::{remote, GhApiClient, GhApiError, GhApiHost, GhReleaseArtifact, HasReleaseArtifact};
+
+/// Result of [`GhApiClient::check_artifact`].
+#[derive(Debug)]
+pub enum ArtifactCheck {
+    /// The artifact exists and is downloaded from `url`.
+    Exists {
+        url: remote::Url,
+        /// Size of the artifact, if the server told it when the API could
+        /// not be used, see [`GhApiClient::get_artifact`] otherwise.
+        size: Option<u64>,
+        /// Hex-encoded SHA-256 digest of the artifact, if the API returned
+        /// it, see [`GhReleaseArtifactInfo::sha256`](super::GhReleaseArtifactInfo::sha256).
+        digest: Option<CompactString>,
+    },
+    Missing,
+    Error(GhApiError),
+}
+
+impl GhReleaseArtifact {
+    /// The url the artifact is downloaded from on `host`, e.g.
+    /// `https://github.com/{owner}/{repo}/releases/download/{tag}/{name}`.
+    pub fn download_url(&self, host: &GhApiHost) -> remote::Url {
+        let mut url = remote::Url::parse(&format!("https://{host}")).expect("valid url");
+        {
+            let mut path_segments = url.path_segments_mut().expect("https url has a path");
+            path_segments
+                .push(&self.release.owner)
+                .push(&self.release.repo)
+                .push("releases");
+            if self.release.is_latest() {
+                path_segments.push("latest").push("download");
+            } else {
+                path_segments.push("download").push(&self.release.tag);
+            }
+            path_segments.push(&self.artifact_name);
+        }
+        url
+    }
+}
+
+impl GhApiClient {
+    /// Return `false` once the API could not tell whether an artifact
+    /// exists, e.g. because it is rate limited, it is not used by
+    /// [`GhApiClient::check_artifact`] for the rest of the process then.
+    pub fn is_api_usable(&self) -> bool {
+        !self.0.api_unusable.load(Relaxed)
+    }
+
+    /// Return whether the artifact exists according to `ret`, or `None` if
+    /// the API cannot tell, in which case it is no longer used, see
+    /// [`GhApiClient::is_api_usable`].
+    ///
+    /// The reason is logged once, when the API stops being used.
+    pub fn api_answer(&self, ret: HasReleaseArtifact) -> Option<bool> {
+        match ret {
+            HasReleaseArtifact::Yes => return Some(true),
+            HasReleaseArtifact::No | HasReleaseArtifact::NoSuchRelease => return Some(false),
+            _ => (),
+        }
+
+        if self.0.api_unusable.swap(true, Relaxed) {
+            return None;
+        }
+
+        match ret {
+            HasReleaseArtifact::RateLimit { retry_after } => {
+                warn!("Your GitHub API token (if any) has reached its rate limit and cannot be used again until {retry_after:?}, so we will fallback to HEAD/GET on the url ({}).", self.budget_usage());
+                warn!("If you did not supply a github token, consider doing so: GitHub limits unauthorized users to 60 requests per hour per origin IP address.");
+            }
+            HasReleaseArtifact::Unauthorized => {
+                warn!("GitHub API somehow requires a token for the API access, so we will fallback to HEAD/GET on the url.");
+                warn!("Please consider supplying a token to cargo-binstall to speedup resolution.");
+            }
+            HasReleaseArtifact::BudgetExhausted => {
+                warn!("GitHub API request budget is exhausted ({}), so we will fallback to HEAD/GET on the url.", self.budget_usage());
+            }
+            HasReleaseArtifact::MalformedResponse => {
+                warn!("GitHub API returned a malformed response, so we will fallback to HEAD/GET on the url for the rest of this run.");
+                warn!("This is usually caused by a proxy truncating or rewriting the response, run with `--log-level debug` to see it.");
+            }
+            HasReleaseArtifact::Yes
+            | HasReleaseArtifact::No
+            | HasReleaseArtifact::NoSuchRelease => {
+                unreachable!()
+            }
+        }
+
+        None
+    }
+
+    /// Check whether `artifact` exists with the API, which also caches its
+    /// release, or with a HEAD/GET request to its download url once the API
+    /// cannot tell.
+    pub async fn check_artifact(&self, artifact: &GhReleaseArtifact) -> ArtifactCheck {
+        let download_url = artifact.download_url(&self.0.host);
+        self.check_artifact_at(artifact, download_url).await
+    }
+
+    /// Like [`GhApiClient::check_artifact`], but send the HEAD/GET request
+    /// to `download_url` instead, e.g. the url of the artifact on a mirror.
+    pub async fn check_artifact_at(
+        &self,
+        artifact: &GhReleaseArtifact,
+        download_url: remote::Url,
+    ) -> ArtifactCheck {
+        if self.is_api_usable() {
+            debug!("Using GitHub API to check for existence of artifact, which will also cache the API response");
+
+            match self.has_release_artifact(artifact.clone()).await {
+                Ok(ret) => match self.api_answer(ret) {
+                    Some(true) => {
+                        // The release is already fetched.
+                        let digest = match self.get_artifact(artifact.clone()).await {
+                            Ok(info) => info.and_then(|info| info.sha256().map(Into::into)),
+                            Err(err) => {
+                                debug!("Failed to get the digest of {artifact:?}: {err}");
+                                None
+                            }
+                        };
+
+                        return ArtifactCheck::Exists {
+                            url: download_url,
+                            size: None,
+                            digest,
+                        };
+                    }
+                    Some(false) => return ArtifactCheck::Missing,
+                    None => (),
+                },
+                Err(err) => return ArtifactCheck::Error(err),
+            }
+        }
+
+        match Box::pin(self.0.client.remote_gettable_size(download_url.clone())).await {
+            Ok(Some(size)) => ArtifactCheck::Exists {
+                url: download_url,
+                size,
+                digest: None,
+            },
+            Ok(None) => ArtifactCheck::Missing,
+            Err(err) => ArtifactCheck::Error(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU16;
+
+    use super::*;
+    use crate::gh_api_client::{GhRelease, GITHUB_COM};
+
+    fn artifact(tag: &str) -> GhReleaseArtifact {
+        GhReleaseArtifact {
+            release: GhRelease {
+                owner: "cargo-bins".into(),
+                repo: "cargo-binstall".into(),
+                tag: tag.into(),
+            },
+            artifact_name: "cargo-binstall x86_64.tgz".into(),
+        }
+    }
+
+    #[test]
+    fn test_download_url() {
+        for (tag, url) in [
+            (
+                "v1.0.0",
+                "https://github.com/cargo-bins/cargo-binstall/releases/download/v1.0.0/cargo-binstall%20x86_64.tgz",
+            ),
+            (
+                "",
+                "https://github.com/cargo-bins/cargo-binstall/releases/latest/download/cargo-binstall%20x86_64.tgz",
+            ),
+        ] {
+            let artifact = artifact(tag);
+            let download_url = artifact.download_url(&GhApiHost::default());
+            assert_eq!(download_url.as_str(), url);
+            assert_eq!(
+                GhReleaseArtifact::try_extract_from_url(&download_url, &[GITHUB_COM]),
+                Some(artifact)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_artifact_fallback() {
+        let client = remote::Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap()
+        .offline(true);
+        let gh_api_client = GhApiClient::with_request_budget(client, None, Some(0));
+        assert!(gh_api_client.is_api_usable());
+
+        // The API cannot be used, so the download url is checked instead,
+        // which fails since the client is offline.
+        let artifact = artifact("v1.0.0");
+        let ArtifactCheck::Error(GhApiError::Remote(remote::Error::Offline(url))) =
+            gh_api_client.check_artifact(&artifact).await
+        else {
+            panic!("Expected the download url to be checked");
+        };
+        assert_eq!(*url, artifact.download_url(gh_api_client.host()));
+        assert!(!gh_api_client.is_api_usable());
+
+        // It is not tried again.
+        let mirror_url = remote::Url::parse("https://mirror.example.com/a.tgz").unwrap();
+        let ArtifactCheck::Error(GhApiError::Remote(remote::Error::Offline(url))) = gh_api_client
+            .check_artifact_at(&artifact, mirror_url.clone())
+            .await
+        else {
+            panic!("Expected the mirror url to be checked");
+        };
+        assert_eq!(*url, mirror_url);
+        assert_eq!(gh_api_client.requests_used(), 0);
+    }
+}
@@ -0,0 +1,72 @@
+use compact_str::CompactString;
+use serde::Deserialize;
+
+use super::{
+    remote,
+    request::{parse_json, send_restful_request, FetchReleaseRet, GhApiError, Hooks},
+    GhApiHost,
+};
+
+/// Result of [`GhApiClient::check_auth`](super::GhApiClient::check_auth).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GhAuthCheck {
+    /// The client has no token.
+    NoToken,
+
+    /// The token is valid.
+    Authenticated {
+        /// Login of the user or app the token belongs to.
+        login: CompactString,
+        /// Scopes granted to the token, `None` for tokens without scopes
+        /// such as fine-grained personal access tokens and app tokens.
+        scopes: Option<Vec<CompactString>>,
+    },
+
+    /// The token was rejected with 401, e.g. because it is expired or
+    /// revoked.
+    Rejected,
+}
+
+#[derive(Deserialize)]
+struct User {
+    login: CompactString,
+}
+
+/// Parse the comma separated `x-oauth-scopes` header.
+fn parse_scopes(headers: &remote::header::HeaderMap) -> Option<Vec<CompactString>> {
+    let scopes = headers.get("x-oauth-scopes")?.to_str().ok()?;
+
+    Some(
+        scopes
+            .split(',')
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(CompactString::from)
+            .collect(),
+    )
+}
+
+/// Fetch the user `auth_token` belongs to.
+///
+/// [`FetchReleaseRet::Unauthorized`] is returned if it is rejected.
+pub(super) async fn fetch_auth_check(
+    client: &remote::Client,
+    host: &GhApiHost,
+    auth_token: &str,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<GhAuthCheck>, GhApiError> {
+    let url = host.restful_api_url(format_args!("user"))?;
+
+    let response = match send_restful_request(client, url, Some(auth_token), hooks).await? {
+        Ok(response) => response,
+        Err(ret) => return Ok(ret),
+    };
+    let scopes = parse_scopes(response.headers());
+
+    Ok(match parse_json::<User>(&response.bytes().await?) {
+        Some(User { login }) => {
+            FetchReleaseRet::Found(GhAuthCheck::Authenticated { login, scopes })
+        }
+        None => FetchReleaseRet::MalformedResponse,
+    })
+}
@@ -0,0 +1,218 @@
+use std::{env, fmt, str::FromStr};
+
+use compact_str::CompactString;
+use tracing::warn;
+use url::{ParseError, Position, Url};
+
+/// Host of github.com, the default [`GhApiHost`].
+pub const GITHUB_COM: &str = "github.com";
+
+/// A GitHub instance whose API is used: github.com or a GitHub Enterprise
+/// Server.
+///
+/// The API of github.com is at `https://api.github.com`, the one of a
+/// GitHub Enterprise Server is at `https://<host>/api/v3` and its GraphQL
+/// API at `https://<host>/api/graphql`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GhApiHost {
+    /// Host of the web interface and of the release downloads, e.g.
+    /// `github.com`, with its port if it is not the default one.
+    host: CompactString,
+}
+
+impl Default for GhApiHost {
+    fn default() -> Self {
+        Self {
+            host: GITHUB_COM.into(),
+        }
+    }
+}
+
+impl GhApiHost {
+    /// Create from the host of the web interface, e.g.
+    /// `github.corp.example.com` or `github.corp.example.com:8443`.
+    pub fn new(host: &str) -> Self {
+        let host = host.trim().trim_end_matches('/').to_ascii_lowercase();
+
+        if host == "api.github.com" {
+            Self::default()
+        } else {
+            Self { host: host.into() }
+        }
+    }
+
+    /// Create from the url of the API, e.g. `https://api.github.com` or
+    /// `https://github.corp.example.com/api/v3`.
+    ///
+    /// The port is kept unless it is the default one of the scheme.
+    pub fn from_api_url(url: &Url) -> Result<Self, ParseError> {
+        if !url.has_host() {
+            return Err(ParseError::EmptyHost);
+        }
+        Ok(Self::new(&url[Position::BeforeHost..Position::AfterPort]))
+    }
+
+    /// Read the host from `GITHUB_API_URL` (set by GitHub Actions), or
+    /// from `GH_HOST` (used by `gh`), in this order.
+    pub fn from_env() -> Option<Self> {
+        Self::from_env_vars(|name| env::var(name).ok())
+    }
+
+    fn from_env_vars(get: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if let Some(url) = get("GITHUB_API_URL").filter(|url| !url.is_empty()) {
+            match url.parse() {
+                Ok(host) => return Some(host),
+                Err(err) => warn!("Ignoring invalid GITHUB_API_URL `{url}`: {err}"),
+            }
+        }
+
+        get("GH_HOST")
+            .filter(|host| !host.trim().is_empty())
+            .map(|host| Self::new(&host))
+    }
+
+    /// Host of the web interface and of the release downloads, with its
+    /// port if it is not the default one.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn is_github_com(&self) -> bool {
+        self.host == GITHUB_COM
+    }
+
+    /// Return the url of the restful API endpoint at `path`, which must
+    /// not start with `/`.
+    pub(super) fn restful_api_url(&self, path: fmt::Arguments<'_>) -> Result<Url, ParseError> {
+        if self.is_github_com() {
+            Url::parse(&format!("https://api.github.com/{path}"))
+        } else {
+            Url::parse(&format!("https://{}/api/v3/{path}", self.host))
+        }
+    }
+
+    pub(super) fn graphql_api_url(&self) -> Result<Url, ParseError> {
+        if self.is_github_com() {
+            Url::parse("https://api.github.com/graphql")
+        } else {
+            Url::parse(&format!("https://{}/api/graphql", self.host))
+        }
+    }
+}
+
+/// Parse the url of the API, see [`GhApiHost::from_api_url`].
+impl FromStr for GhApiHost {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_api_url(&Url::parse(s)?)
+    }
+}
+
+impl fmt::Display for GhApiHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.host)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_api_url() {
+        let parse = |s: &str| s.parse::<GhApiHost>().unwrap();
+
+        assert_eq!(parse("https://api.github.com"), GhApiHost::default());
+        assert_eq!(parse("https://api.github.com/"), GhApiHost::default());
+        assert!(parse("https://api.github.com").is_github_com());
+
+        let ghes = parse("https://github.corp.example.com/api/v3");
+        assert_eq!(ghes, GhApiHost::new("github.corp.example.com"));
+        assert_eq!(ghes.host(), "github.corp.example.com");
+        assert!(!ghes.is_github_com());
+
+        let ghes = parse("https://ghe.corp:8443/api/v3");
+        assert_eq!(ghes.host(), "ghe.corp:8443");
+        // The default port of the scheme is omitted.
+        assert_eq!(parse("https://ghe.corp:443/api/v3").host(), "ghe.corp");
+
+        assert!("github.corp.example.com".parse::<GhApiHost>().is_err());
+    }
+
+    #[test]
+    fn test_api_urls() {
+        let path = |host: &GhApiHost| {
+            host.restful_api_url(format_args!("repos/o/r/releases/tags/v1"))
+                .unwrap()
+                .to_string()
+        };
+
+        let github = GhApiHost::default();
+        assert_eq!(
+            path(&github),
+            "https://api.github.com/repos/o/r/releases/tags/v1"
+        );
+        assert_eq!(
+            github.graphql_api_url().unwrap().as_str(),
+            "https://api.github.com/graphql"
+        );
+
+        let ghes = GhApiHost::new("github.corp.example.com");
+        assert_eq!(
+            path(&ghes),
+            "https://github.corp.example.com/api/v3/repos/o/r/releases/tags/v1"
+        );
+        assert_eq!(
+            ghes.graphql_api_url().unwrap().as_str(),
+            "https://github.corp.example.com/api/graphql"
+        );
+
+        let ghes = GhApiHost::new("ghe.corp:8443");
+        assert_eq!(
+            path(&ghes),
+            "https://ghe.corp:8443/api/v3/repos/o/r/releases/tags/v1"
+        );
+        assert_eq!(
+            ghes.graphql_api_url().unwrap().as_str(),
+            "https://ghe.corp:8443/api/graphql"
+        );
+    }
+
+    #[test]
+    fn test_from_env() {
+        let from_env = |vars: &[(&str, &str)]| {
+            GhApiHost::from_env_vars(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+
+        assert_eq!(from_env(&[]), None);
+        assert_eq!(
+            from_env(&[("GITHUB_API_URL", "https://api.github.com")]),
+            Some(GhApiHost::default())
+        );
+        assert_eq!(
+            from_env(&[("GH_HOST", "github.corp.example.com")]),
+            Some(GhApiHost::new("github.corp.example.com"))
+        );
+
+        // GITHUB_API_URL takes precedence, unless it is invalid.
+        assert_eq!(
+            from_env(&[
+                ("GITHUB_API_URL", "https://ghes.example.com/api/v3"),
+                ("GH_HOST", "github.corp.example.com")
+            ]),
+            Some(GhApiHost::new("ghes.example.com"))
+        );
+        assert_eq!(
+            from_env(&[
+                ("GITHUB_API_URL", "not a url"),
+                ("GH_HOST", "github.corp.example.com")
+            ]),
+            Some(GhApiHost::new("github.corp.example.com"))
+        );
+    }
+}
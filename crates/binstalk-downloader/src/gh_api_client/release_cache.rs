@@ -0,0 +1,530 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use compact_str::CompactString;
+use fs_lock::FileLock;
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{debug, warn};
+
+use super::{request::GhReleaseArtifacts, GhRelease, GhReleaseArtifactInfo, GhReleaseMeta};
+
+/// Default time to live of the artifacts of a release, published releases
+/// are rarely changed.
+const DEFAULT_FOUND_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Default time to live of the artifacts of a release fetched within
+/// [`RECENT_RELEASE_AGE`] of being published, CI may still be uploading
+/// some of them.
+const DEFAULT_RECENT_FOUND_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Age of a release up to which it is considered recently published.
+const RECENT_RELEASE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Default time to live of a release that does not exist, it may be
+/// published any time.
+const DEFAULT_NOT_FOUND_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Time an expired release with an `ETag` is kept for, to fetch it again
+/// only if it is modified.
+const ETAG_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// On-disk cache of the artifacts of GitHub releases, shared by the
+/// processes using the same file.
+///
+/// The file is loaded by [`GhReleaseCache::load`], and written back with
+/// the releases fetched by [`GhReleaseCache::flush`] or on drop.
+#[derive(Debug)]
+pub struct GhReleaseCache {
+    path: PathBuf,
+    found_ttl: Duration,
+    recent_found_ttl: Duration,
+    not_found_ttl: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: HashMap<Key, Entry>,
+    dirty: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct Key {
+    host: CompactString,
+    release: GhRelease,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    /// `None` if the release does not exist.
+    artifacts: Option<Vec<GhReleaseArtifactInfo>>,
+    #[serde(default)]
+    meta: GhReleaseMeta,
+    /// `ETag` of the release in the restful API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<CompactString>,
+    /// Seconds since the epoch.
+    fetched_at: u64,
+}
+
+impl Entry {
+    /// Return `true` if the release was fetched within
+    /// [`RECENT_RELEASE_AGE`] of being published, `false` if it is unknown
+    /// when it was published.
+    fn is_recent(&self) -> bool {
+        let Some(published_at) = self
+            .meta
+            .published_at
+            .as_deref()
+            .and_then(|published_at| OffsetDateTime::parse(published_at, &Rfc3339).ok())
+        else {
+            return false;
+        };
+
+        u64::try_from(published_at.unix_timestamp())
+            .ok()
+            .and_then(|published_at| self.fetched_at.checked_sub(published_at))
+            .map_or(true, |age| age < RECENT_RELEASE_AGE.as_secs())
+    }
+
+    fn artifacts(&self) -> Option<GhReleaseArtifacts> {
+        let artifacts: GhReleaseArtifacts = self.artifacts.as_ref()?.iter().cloned().collect();
+        Some(
+            artifacts
+                .with_meta(self.meta.clone())
+                .with_etag(self.etag.clone()),
+        )
+    }
+}
+
+/// Layout of the file.
+#[derive(Default, Serialize, Deserialize)]
+struct CacheFile {
+    entries: Vec<FileEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileEntry {
+    host: CompactString,
+    owner: CompactString,
+    repo: CompactString,
+    tag: CompactString,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+impl GhReleaseCache {
+    /// Load the cache from `path`, a missing or unreadable file is treated
+    /// as an empty cache.
+    pub fn load(path: PathBuf) -> Self {
+        let entries = read_entries(&path);
+
+        Self {
+            path,
+            found_ttl: DEFAULT_FOUND_TTL,
+            recent_found_ttl: DEFAULT_RECENT_FOUND_TTL,
+            not_found_ttl: DEFAULT_NOT_FOUND_TTL,
+            state: Mutex::new(State {
+                entries,
+                dirty: false,
+            }),
+        }
+    }
+
+    /// Time to live of the artifacts of existing releases, 7 days by
+    /// default. Once expired, they are only fetched again if modified.
+    pub fn found_ttl(mut self, ttl: Duration) -> Self {
+        self.found_ttl = ttl;
+        self
+    }
+
+    /// Time to live of the artifacts of releases fetched within a day of
+    /// being published, 10 minutes by default, since their artifacts may
+    /// still be being uploaded.
+    pub fn recent_found_ttl(mut self, ttl: Duration) -> Self {
+        self.recent_found_ttl = ttl;
+        self
+    }
+
+    /// Time to live of releases that do not exist, 1 hour by default.
+    pub fn not_found_ttl(mut self, ttl: Duration) -> Self {
+        self.not_found_ttl = ttl;
+        self
+    }
+
+    fn is_fresh(&self, entry: &Entry, now: u64) -> bool {
+        let ttl = match &entry.artifacts {
+            Some(_) if entry.is_recent() => self.recent_found_ttl,
+            Some(_) => self.found_ttl,
+            None => self.not_found_ttl,
+        };
+        // An entry from the future comes from a wrong system clock.
+        now.checked_sub(entry.fetched_at)
+            .is_some_and(|age| age < ttl.as_secs())
+    }
+
+    /// Return `true` if the entry is fresh or can be fetched again with a
+    /// conditional request.
+    fn is_retained(&self, entry: &Entry, now: u64) -> bool {
+        self.is_fresh(entry, now)
+            || (entry.artifacts.is_some()
+                && entry.etag.is_some()
+                && now
+                    .checked_sub(entry.fetched_at)
+                    .is_some_and(|age| age < ETAG_RETENTION.as_secs()))
+    }
+
+    /// Return the artifacts of `release` on `host`, `Some(None)` if it is
+    /// known not to exist, `None` if it is not cached or expired.
+    pub(super) fn get(
+        &self,
+        host: &str,
+        release: &GhRelease,
+    ) -> Option<Option<GhReleaseArtifacts>> {
+        let state = self.state.lock().unwrap();
+        let entry = state.entries.get(&Key {
+            host: host.into(),
+            release: release.clone(),
+        })?;
+
+        self.is_fresh(entry, now()).then(|| entry.artifacts())
+    }
+
+    /// Return the artifacts of `release` on `host` if they are expired but
+    /// have an `ETag`, to fetch them again only if they are modified.
+    pub(super) fn get_expired(
+        &self,
+        host: &str,
+        release: &GhRelease,
+    ) -> Option<GhReleaseArtifacts> {
+        let state = self.state.lock().unwrap();
+        let entry = state.entries.get(&Key {
+            host: host.into(),
+            release: release.clone(),
+        })?;
+
+        if entry.etag.is_some() && self.is_retained(entry, now()) {
+            entry.artifacts()
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn insert(
+        &self,
+        host: &str,
+        release: &GhRelease,
+        artifacts: Option<&GhReleaseArtifacts>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.insert(
+            Key {
+                host: host.into(),
+                release: release.clone(),
+            },
+            Entry {
+                artifacts: artifacts.map(|artifacts| artifacts.iter().cloned().collect()),
+                meta: artifacts.map_or_else(Default::default, |artifacts| artifacts.meta().clone()),
+                etag: artifacts.and_then(|artifacts| artifacts.etag().map(CompactString::from)),
+                fetched_at: now(),
+            },
+        );
+        state.dirty = true;
+    }
+
+    /// Write the releases fetched since the last flush to the file.
+    ///
+    /// The entries written by other processes in the meantime are kept,
+    /// the file is locked while being merged with them, and replaced
+    /// atomically so that it is never seen partially written.
+    pub fn flush(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if !state.dirty {
+            return Ok(());
+        }
+
+        let dir = self
+            .path
+            .parent()
+            .expect("release cache path must have a parent");
+        fs::create_dir_all(dir)?;
+
+        // The file itself is replaced, so a lock file next to it is locked
+        // instead.
+        let mut lock_path = OsString::from(self.path.as_os_str());
+        lock_path.push(".lock");
+        let _lock = FileLock::new_exclusive(
+            fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(lock_path)?,
+        )?;
+
+        let now = now();
+        let mut entries = read_entries(&self.path);
+        for (key, entry) in &state.entries {
+            match entries.get(key) {
+                Some(on_disk) if on_disk.fetched_at >= entry.fetched_at => (),
+                _ => {
+                    entries.insert(key.clone(), entry.clone());
+                }
+            }
+        }
+        entries.retain(|_, entry| self.is_retained(entry, now));
+
+        let file = CacheFile {
+            entries: entries
+                .iter()
+                .map(|(Key { host, release }, entry)| FileEntry {
+                    host: host.clone(),
+                    owner: release.owner.clone(),
+                    repo: release.repo.clone(),
+                    tag: release.tag.clone(),
+                    entry: entry.clone(),
+                })
+                .collect(),
+        };
+
+        let mut temp_file = NamedTempFile::new_in(dir)?;
+        temp_file.write_all(&serde_json::to_vec(&file)?)?;
+        temp_file.persist(&self.path).map_err(io::Error::from)?;
+
+        state.entries = entries;
+        state.dirty = false;
+
+        Ok(())
+    }
+}
+
+impl Drop for GhReleaseCache {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            warn!(
+                "Failed to save the GitHub release cache to '{}': {err}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Return the entries in `path`, none if it cannot be read or parsed, e.g.
+/// because it was written by an incompatible version.
+fn read_entries(path: &Path) -> HashMap<Key, Entry> {
+    let file = match fs::read(path) {
+        Ok(data) => serde_json::from_slice::<CacheFile>(&data).unwrap_or_else(|err| {
+            debug!(
+                "Ignoring invalid GitHub release cache '{}': {err}",
+                path.display()
+            );
+            CacheFile::default()
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => CacheFile::default(),
+        Err(err) => {
+            debug!(
+                "Ignoring unreadable GitHub release cache '{}': {err}",
+                path.display()
+            );
+            CacheFile::default()
+        }
+    };
+
+    file.entries
+        .into_iter()
+        .map(
+            |FileEntry {
+                 host,
+                 owner,
+                 repo,
+                 tag,
+                 entry,
+             }| {
+                (
+                    Key {
+                        host,
+                        release: GhRelease { owner, repo, tag },
+                    },
+                    entry,
+                )
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn release(tag: &str) -> GhRelease {
+        GhRelease {
+            owner: "cargo-bins".into(),
+            repo: "cargo-binstall".into(),
+            tag: tag.into(),
+        }
+    }
+
+    #[test]
+    fn test_release_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("binstall/gh-releases.json");
+
+        let meta = GhReleaseMeta {
+            published_at: Some("2024-11-02T10:00:00Z".into()),
+            prerelease: true,
+            draft: false,
+        };
+        let artifacts = ["a.tgz", "b.zip"]
+            .into_iter()
+            .collect::<GhReleaseArtifacts>()
+            .with_meta(meta.clone());
+
+        let cache = GhReleaseCache::load(path.clone());
+        assert!(cache.get("github.com", &release("v1")).is_none());
+        cache.insert("github.com", &release("v1"), Some(&artifacts));
+        cache.insert("github.com", &release("v0"), None);
+        drop(cache);
+
+        let cache = GhReleaseCache::load(path.clone());
+        let cached = cache.get("github.com", &release("v1")).unwrap().unwrap();
+        assert!(cached.contains("a.tgz") && cached.contains("b.zip"));
+        assert_eq!(cached.meta(), &meta);
+        assert!(cache.get("github.com", &release("v0")).unwrap().is_none());
+        assert!(cache
+            .get("github.corp.example.com", &release("v1"))
+            .is_none());
+
+        // Releases not found expire sooner.
+        let cache = GhReleaseCache::load(path.clone()).not_found_ttl(Duration::ZERO);
+        assert!(cache.get("github.com", &release("v1")).is_some());
+        assert!(cache.get("github.com", &release("v0")).is_none());
+    }
+
+    #[test]
+    fn test_release_cache_etag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gh-releases.json");
+
+        let artifacts: GhReleaseArtifacts = ["a.tgz"].into_iter().collect();
+        let with_etag = artifacts.clone().with_etag(Some("\"abc\"".into()));
+
+        let cache = GhReleaseCache::load(path.clone()).found_ttl(Duration::ZERO);
+        cache.insert("github.com", &release("v1"), Some(&with_etag));
+        cache.insert("github.com", &release("v2"), Some(&artifacts));
+        cache.flush().unwrap();
+
+        // Only the expired releases with an `ETag` are kept.
+        let cache = GhReleaseCache::load(path).found_ttl(Duration::ZERO);
+        assert!(cache.get("github.com", &release("v1")).is_none());
+        let expired = cache.get_expired("github.com", &release("v1")).unwrap();
+        assert!(expired.contains("a.tgz"));
+        assert_eq!(expired.etag(), Some("\"abc\""));
+        assert!(cache.get_expired("github.com", &release("v2")).is_none());
+    }
+
+    #[test]
+    fn test_release_cache_concurrent_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gh-releases.json");
+
+        let first = GhReleaseCache::load(path.clone());
+        let second = GhReleaseCache::load(path.clone());
+        first.insert("github.com", &release("v1"), None);
+        second.insert("github.com", &release("v2"), None);
+        first.flush().unwrap();
+        second.flush().unwrap();
+
+        // The entries of both are kept.
+        let cache = GhReleaseCache::load(path);
+        assert!(cache.get("github.com", &release("v1")).is_some());
+        assert!(cache.get("github.com", &release("v2")).is_some());
+    }
+
+    #[test]
+    fn test_release_cache_recent_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gh-releases.json");
+
+        let published = |published_at: &str| {
+            ["a.tgz"]
+                .into_iter()
+                .collect::<GhReleaseArtifacts>()
+                .with_meta(GhReleaseMeta {
+                    published_at: Some(published_at.into()),
+                    ..Default::default()
+                })
+        };
+        let just_published = OffsetDateTime::now_utc().format(&Rfc3339).unwrap();
+
+        let cache = GhReleaseCache::load(path).recent_found_ttl(Duration::ZERO);
+        cache.insert(
+            "github.com",
+            &release("v1"),
+            Some(&published(&just_published)),
+        );
+        cache.insert(
+            "github.com",
+            &release("v0"),
+            Some(&published("2024-11-02T10:00:00Z")),
+        );
+
+        // Its artifacts may still be being uploaded.
+        assert!(cache.get("github.com", &release("v1")).is_none());
+        assert!(cache.get("github.com", &release("v0")).is_some());
+    }
+
+    #[test]
+    fn test_release_cache_parallel_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gh-releases.json");
+
+        std::thread::scope(|scope| {
+            for i in 0..16 {
+                let path = path.clone();
+                scope.spawn(move || {
+                    let cache = GhReleaseCache::load(path);
+                    cache.insert("github.com", &release(&format!("v{i}")), None);
+                    cache.flush().unwrap();
+                });
+            }
+        });
+
+        // None of the entries is lost by merging them concurrently.
+        let cache = GhReleaseCache::load(path);
+        for i in 0..16 {
+            assert!(cache
+                .get("github.com", &release(&format!("v{i}")))
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_release_cache_invalid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gh-releases.json");
+        fs::write(&path, b"{\"entries\": [{\"host\": ").unwrap();
+
+        let cache = GhReleaseCache::load(path.clone());
+        assert!(cache.get("github.com", &release("v1")).is_none());
+
+        cache.insert("github.com", &release("v1"), None);
+        cache.flush().unwrap();
+        assert!(GhReleaseCache::load(path)
+            .get("github.com", &release("v1"))
+            .is_some());
+    }
+}
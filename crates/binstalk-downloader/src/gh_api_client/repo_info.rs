@@ -0,0 +1,168 @@
+use compact_str::CompactString;
+use serde::Deserialize;
+
+use super::{
+    percent_encode_http_url_path, remote,
+    request::{
+        parse_json, send_graphql_query, send_restful_request, FetchReleaseRet, GhApiError, Hooks,
+    },
+    GhApiHost, GhRepo,
+};
+
+/// Information about a repository, see
+/// [`GhApiClient::get_repo_info`](super::GhApiClient::get_repo_info).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GhRepoInfo {
+    /// Its artifacts can only be downloaded with a token.
+    pub is_private: bool,
+    /// `None` if the repository is empty.
+    pub default_branch: Option<CompactString>,
+    /// It is read-only and no longer maintained.
+    pub archived: bool,
+    /// It has at least one published release.
+    pub has_releases: bool,
+}
+
+#[derive(Deserialize)]
+struct RestfulRepo {
+    private: bool,
+    archived: bool,
+    default_branch: Option<CompactString>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLRepoInfoData {
+    repository: Option<GraphQLRepoInfo>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLRepoInfo {
+    #[serde(rename = "isPrivate")]
+    is_private: bool,
+    #[serde(rename = "isArchived")]
+    is_archived: bool,
+    #[serde(rename = "defaultBranchRef")]
+    default_branch_ref: Option<GraphQLRef>,
+    releases: GraphQLTotalCount,
+}
+
+#[derive(Deserialize)]
+struct GraphQLRef {
+    name: CompactString,
+}
+
+#[derive(Deserialize)]
+struct GraphQLTotalCount {
+    #[serde(rename = "totalCount")]
+    total_count: u64,
+}
+
+impl From<GraphQLRepoInfo> for GhRepoInfo {
+    fn from(repo: GraphQLRepoInfo) -> Self {
+        Self {
+            is_private: repo.is_private,
+            default_branch: repo.default_branch_ref.map(|branch| branch.name),
+            archived: repo.is_archived,
+            has_releases: repo.releases.total_count > 0,
+        }
+    }
+}
+
+/// The restful API does not tell whether the repository has releases, so
+/// the first release is requested too.
+async fn fetch_repo_info_restful_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    GhRepo { owner, repo }: &GhRepo,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<GhRepoInfo>, GhApiError> {
+    let owner = percent_encode_http_url_path(owner);
+    let repo = percent_encode_http_url_path(repo);
+
+    let url = host.restful_api_url(format_args!("repos/{owner}/{repo}"))?;
+    let restful_repo: RestfulRepo =
+        match send_restful_request(client, url, auth_token, hooks).await? {
+            Ok(response) => match parse_json(&response.bytes().await?) {
+                Some(restful_repo) => restful_repo,
+                None => return Ok(FetchReleaseRet::MalformedResponse),
+            },
+            Err(ret) => return Ok(ret),
+        };
+
+    let url = host.restful_api_url(format_args!("repos/{owner}/{repo}/releases?per_page=1"))?;
+    let releases: Vec<serde_json::Value> =
+        match send_restful_request(client, url, auth_token, hooks).await? {
+            Ok(response) => match parse_json(&response.bytes().await?) {
+                Some(releases) => releases,
+                None => return Ok(FetchReleaseRet::MalformedResponse),
+            },
+            Err(ret) => return Ok(ret),
+        };
+
+    Ok(FetchReleaseRet::Found(GhRepoInfo {
+        is_private: restful_repo.private,
+        default_branch: restful_repo.default_branch,
+        archived: restful_repo.archived,
+        has_releases: !releases.is_empty(),
+    }))
+}
+
+async fn fetch_repo_info_graphql_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    GhRepo { owner, repo }: &GhRepo,
+    auth_token: &str,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<GhRepoInfo>, GhApiError> {
+    let query = format!(
+        r#"
+query {{
+  repository(owner:"{owner}",name:"{repo}") {{
+    isPrivate
+    isArchived
+    defaultBranchRef {{ name }}
+    releases {{ totalCount }}
+  }}
+}}"#
+    );
+
+    let data: GraphQLRepoInfoData =
+        match send_graphql_query(client, &host.graphql_api_url()?, query, auth_token, hooks).await?
+        {
+            Ok(data) => data,
+            Err(ret) => return Ok(ret),
+        };
+
+    Ok(data
+        .repository
+        .map_or(FetchReleaseRet::ReleaseNotFound, |repo| {
+            FetchReleaseRet::Found(repo.into())
+        }))
+}
+
+/// Return [`FetchReleaseRet::ReleaseNotFound`] if the repository does not
+/// exist or is not visible with `auth_token`.
+pub(super) async fn fetch_repo_info(
+    client: &remote::Client,
+    host: &GhApiHost,
+    repo: &GhRepo,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<GhRepoInfo>, GhApiError> {
+    if let Some(auth_token) = auth_token {
+        let res = fetch_repo_info_graphql_api(client, host, repo, auth_token, hooks)
+            .await
+            .map_err(|err| err.context("GraphQL API"));
+
+        match res {
+            // Fallback to Restful API
+            Ok(FetchReleaseRet::Unauthorized) => (),
+            res => return res,
+        }
+    }
+
+    fetch_repo_info_restful_api(client, host, repo, auth_token, hooks)
+        .await
+        .map_err(|err| err.context("Restful API"))
+}
@@ -1,22 +1,27 @@
 use std::{
     borrow::Borrow,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     error, fmt,
     hash::{Hash, Hasher},
     io,
-    sync::OnceLock,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
-use compact_str::{CompactString, ToCompactString};
-use reqwest::{header::HeaderMap, StatusCode};
-use serde::{de::Deserializer, Deserialize, Serialize};
+use compact_str::{format_compact, CompactString, ToCompactString};
+use reqwest::{
+    header::{HeaderMap, ETAG, LINK},
+    StatusCode,
+};
+use serde::{
+    de::{DeserializeOwned, Deserializer},
+    Deserialize, Serialize,
+};
 use serde_json::to_string as to_json_string;
 use thiserror::Error as ThisError;
-use tracing::debug;
-use url::Url;
+use tracing::{debug, instrument, warn};
+use url::Origin;
 
-use super::{percent_encode_http_url_path, remote, GhRelease};
+use super::{percent_encode_http_url_path, remote, GhApiHost, GhRelease, GhRepo};
 
 #[derive(ThisError, Debug)]
 #[error("Context: '{context}', err: '{err}'")]
@@ -44,6 +49,22 @@ pub enum GhApiError {
 
     #[error("Remote failed to process GraphQL query: {0}")]
     GraphQLErrors(#[from] GhGraphQLErrors),
+
+    /// See [`HasReleaseArtifact::RateLimit`](super::HasReleaseArtifact::RateLimit).
+    #[error("GitHub API rate limit reached, retry after {retry_after:?}")]
+    RateLimit { retry_after: Instant },
+
+    /// See [`HasReleaseArtifact::Unauthorized`](super::HasReleaseArtifact::Unauthorized).
+    #[error("GitHub API requires a token")]
+    Unauthorized,
+
+    /// See [`HasReleaseArtifact::BudgetExhausted`](super::HasReleaseArtifact::BudgetExhausted).
+    #[error("GitHub API request budget exhausted")]
+    BudgetExhausted,
+
+    /// See [`HasReleaseArtifact::MalformedResponse`](super::HasReleaseArtifact::MalformedResponse).
+    #[error("GitHub API returned a malformed response")]
+    MalformedResponse,
 }
 
 impl GhApiError {
@@ -54,22 +75,105 @@ impl GhApiError {
             err: self,
         }))
     }
+
+    /// Return `true` if the request may succeed when sent again, see
+    /// [`remote::Error::is_transient`].
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Remote(err) => err.is_transient(),
+            Self::Context(context_error) => context_error.err.is_transient(),
+            _ => false,
+        }
+    }
+
+    /// Return `true` if the rate limit or request budget of the GitHub API
+    /// is reached.
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            Self::RateLimit { .. } | Self::BudgetExhausted => true,
+            Self::Remote(err) => err.is_rate_limited(),
+            Self::Context(context_error) => context_error.err.is_rate_limited(),
+            _ => false,
+        }
+    }
+
+    /// Return `true` if the request failed because of the network, see
+    /// [`remote::Error::is_network`].
+    pub fn is_network(&self) -> bool {
+        match self {
+            Self::Remote(err) => err.is_network(),
+            Self::Context(context_error) => context_error.err.is_network(),
+            _ => false,
+        }
+    }
 }
 
-// Only include fields we do care about
+/// Metadata of an artifact of a release, the restful and GraphQL APIs do
+/// not return the same fields so all of them but the name are optional.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct GhReleaseArtifactInfo {
+    pub name: CompactString,
+    /// Size in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(
+        default,
+        alias = "contentType",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content_type: Option<CompactString>,
+    /// Digest of the artifact prefixed with the algorithm, e.g.
+    /// `sha256:<hex>`, only returned by the restful API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<CompactString>,
+    /// Url of the artifact in the restful API, used to download it with
+    /// [`GhApiClient::download_artifact`](super::GhApiClient::download_artifact),
+    /// only returned by the restful API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<CompactString>,
+}
+
+impl GhReleaseArtifactInfo {
+    /// Return the hex-encoded SHA-256 digest of the artifact, if its
+    /// `digest` is one.
+    pub fn sha256(&self) -> Option<&str> {
+        self.digest
+            .as_deref()?
+            .strip_prefix("sha256:")
+            .filter(|hex| hex.len() == 64 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+    }
+}
 
-#[derive(Eq, Deserialize, Debug)]
-struct Artifact {
-    name: CompactString,
+/// Metadata of a release, see
+/// [`GhApiClient::get_release_meta`](super::GhApiClient::get_release_meta).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct GhReleaseMeta {
+    /// When it was published, e.g. `2024-11-02T10:00:00Z`, `None` for
+    /// drafts.
+    #[serde(
+        default,
+        alias = "publishedAt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub published_at: Option<CompactString>,
+    #[serde(default, alias = "isPrerelease")]
+    pub prerelease: bool,
+    /// Only visible with a token that can push to the repository.
+    #[serde(default, alias = "isDraft")]
+    pub draft: bool,
 }
 
+#[derive(Clone, Eq, Deserialize, Debug)]
+#[serde(transparent)]
+struct Artifact(GhReleaseArtifactInfo);
+
 // Manually implement PartialEq and Hash to ensure it will always produce the
 // same hash as a str with the same content, and that the comparison will be
 // the same to coparing a string.
 
 impl PartialEq for Artifact {
     fn eq(&self, other: &Self) -> bool {
-        self.name.eq(&other.name)
+        self.0.name.eq(&other.0.name)
     }
 }
 
@@ -78,7 +182,7 @@ impl Hash for Artifact {
     where
         H: Hasher,
     {
-        let s: &str = self.name.as_str();
+        let s: &str = self.0.name.as_str();
         s.hash(state)
     }
 }
@@ -88,43 +192,293 @@ impl Hash for Artifact {
 
 impl Borrow<str> for Artifact {
     fn borrow(&self) -> &str {
-        &self.name
+        &self.0.name
     }
 }
 
-#[derive(Debug, Default, Deserialize)]
-pub(super) struct Artifacts {
+/// The artifacts of a release, looked up by name.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct GhReleaseArtifacts {
     assets: HashSet<Artifact>,
+    #[serde(flatten)]
+    meta: GhReleaseMeta,
+    /// `ETag` of the release in the restful API, to only fetch it again if
+    /// it is modified.
+    #[serde(skip)]
+    etag: Option<CompactString>,
 }
 
-impl Artifacts {
-    pub(super) fn contains(&self, artifact_name: &str) -> bool {
+impl GhReleaseArtifacts {
+    pub fn contains(&self, artifact_name: &str) -> bool {
         self.assets.contains(artifact_name)
     }
+
+    pub fn get(&self, artifact_name: &str) -> Option<&GhReleaseArtifactInfo> {
+        self.assets.get(artifact_name).map(|artifact| &artifact.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GhReleaseArtifactInfo> {
+        self.assets.iter().map(|artifact| &artifact.0)
+    }
+
+    /// Return the only artifact whose name matches `artifact_name` once both
+    /// are normalized with [`normalize_artifact_name`], `None` if there is
+    /// none or more than one.
+    pub fn get_fuzzy(&self, artifact_name: &str) -> Option<&GhReleaseArtifactInfo> {
+        let normalized = normalize_artifact_name(artifact_name);
+
+        let mut matches = self
+            .iter()
+            .filter(|info| normalize_artifact_name(&info.name) == normalized);
+
+        let info = matches.next()?;
+        matches.next().is_none().then_some(info)
+    }
+
+    pub fn meta(&self) -> &GhReleaseMeta {
+        &self.meta
+    }
+
+    pub(super) fn with_meta(mut self, meta: GhReleaseMeta) -> Self {
+        self.meta = meta;
+        self
+    }
+
+    pub(super) fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    pub(super) fn with_etag(mut self, etag: Option<CompactString>) -> Self {
+        self.etag = etag;
+        self
+    }
+}
+
+/// Normalize `name` so that names only differing in their case, in `-` and
+/// `_`, in `.tgz` and `.tar.gz` or in the order of their `-` separated parts,
+/// e.g. the target and the version, are equal.
+fn normalize_artifact_name(name: &str) -> Vec<String> {
+    let name = name.to_lowercase();
+    let name = match name.strip_suffix(".tar.gz") {
+        Some(stem) => format!("{stem}.tgz"),
+        None => name,
+    };
+
+    // Keep the extension apart so that it is not moved to another part.
+    let (stem, ext) = match name.rfind('.') {
+        Some(index) if index > 0 && !name[index..].contains(['-', '_']) => name.split_at(index),
+        _ => (name.as_str(), ""),
+    };
+
+    let mut parts: Vec<String> = stem
+        .split(['-', '_'])
+        .filter(|part| !part.is_empty())
+        .map(String::from)
+        .collect();
+    parts.sort_unstable();
+    parts.push(ext.to_owned());
+    parts
+}
+
+impl FromIterator<GhReleaseArtifactInfo> for GhReleaseArtifacts {
+    fn from_iter<I: IntoIterator<Item = GhReleaseArtifactInfo>>(iter: I) -> Self {
+        Self {
+            assets: iter.into_iter().map(Artifact).collect(),
+            meta: GhReleaseMeta::default(),
+            etag: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl<'a> FromIterator<&'a str> for GhReleaseArtifacts {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        iter.into_iter()
+            .map(|name| GhReleaseArtifactInfo {
+                name: name.into(),
+                size: None,
+                content_type: None,
+                digest: None,
+                url: None,
+            })
+            .collect()
+    }
+}
+
+/// A release fetched with the restful API, which lists at most
+/// [`ASSETS_PER_PAGE`] of its artifacts.
+#[derive(Debug, Deserialize)]
+pub(super) struct RestfulRelease {
+    /// Used to list the other artifacts.
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(flatten)]
+    pub(super) artifacts: GhReleaseArtifacts,
+}
+
+/// A release of a repository, the restful and GraphQL APIs name the fields
+/// differently.
+#[derive(Debug, Deserialize)]
+struct ReleaseTag {
+    #[serde(alias = "tagName")]
+    tag_name: CompactString,
+    #[serde(default, alias = "isDraft")]
+    draft: bool,
+    #[serde(default, alias = "isPrerelease")]
+    prerelease: bool,
+}
+
+impl ReleaseTag {
+    /// Drafts and prereleases are never the latest release, like in
+    /// `GET /repos/{owner}/{repo}/releases/latest`.
+    fn into_ret(self) -> FetchReleaseRet<CompactString> {
+        if self.draft || self.prerelease {
+            FetchReleaseRet::ReleaseNotFound
+        } else {
+            FetchReleaseRet::Found(self.tag_name)
+        }
+    }
 }
 
-pub(super) enum FetchReleaseRet {
-    ReachedRateLimit { retry_after: Option<Duration> },
+#[derive(Debug)]
+pub(super) enum FetchReleaseRet<T = GhReleaseArtifacts> {
+    ReachedRateLimit {
+        retry_after: Option<Duration>,
+    },
     ReleaseNotFound,
-    Artifacts(Artifacts),
+    Found(T),
     Unauthorized,
+    BudgetExhausted,
+    /// The response cannot be deserialized, e.g. it is truncated or
+    /// rewritten by a proxy.
+    MalformedResponse,
 }
 
-fn check_for_status(status: StatusCode, headers: &HeaderMap) -> Option<FetchReleaseRet> {
-    match status {
-        remote::StatusCode::FORBIDDEN
-            if headers
-                .get("x-ratelimit-remaining")
-                .map(|val| val == "0")
-                .unwrap_or(false) =>
-        {
-            Some(FetchReleaseRet::ReachedRateLimit {
-                retry_after: headers.get("x-ratelimit-reset").and_then(|value| {
-                    let secs = value.to_str().ok()?.parse().ok()?;
-                    Some(Duration::from_secs(secs))
-                }),
-            })
+impl<T> FetchReleaseRet<T> {
+    /// Return the release found, `None` if it does not exist, or the
+    /// variant to return otherwise.
+    fn into_found<U>(self) -> Result<Option<T>, FetchReleaseRet<U>> {
+        match self {
+            Self::Found(found) => Ok(Some(found)),
+            Self::ReleaseNotFound => Ok(None),
+            Self::ReachedRateLimit { retry_after } => {
+                Err(FetchReleaseRet::ReachedRateLimit { retry_after })
+            }
+            Self::Unauthorized => Err(FetchReleaseRet::Unauthorized),
+            Self::BudgetExhausted => Err(FetchReleaseRet::BudgetExhausted),
+            Self::MalformedResponse => Err(FetchReleaseRet::MalformedResponse),
         }
+    }
+}
+
+/// GitHub resets the rate limit every hour, a later reset comes from a
+/// wrong system clock.
+const MAX_RATE_LIMIT_RESET: Duration = Duration::from_secs(60 * 60);
+
+/// Number of releases requested per page, GitHub allows at most 100.
+const RELEASES_PER_PAGE: u32 = 100;
+
+/// Maximum number of pages of releases fetched, so that listing the
+/// releases of a big repository doesn't use up the rate limit.
+const MAX_RELEASE_PAGES: usize = 10;
+
+/// Number of artifacts requested per page, GitHub allows at most 100.
+const ASSETS_PER_PAGE: usize = 100;
+
+/// Maximum number of artifacts of a release fetched, so that a release with
+/// an unreasonable number of them doesn't use up the rate limit. The ones
+/// beyond are treated as missing.
+const MAX_ASSETS: usize = 1000;
+
+/// Called by the requests of the [`GhApiClient`](super::GhApiClient).
+pub(super) trait RequestHooks: Send {
+    /// Called before sending every request, return `false` if the request
+    /// budget is exhausted.
+    fn consume_budget(&mut self) -> bool;
+
+    /// Called with the rate limit sent with every response.
+    fn record_rate_limit(&mut self, status: RateLimitStatus);
+}
+
+pub(super) type Hooks<'a> = &'a mut dyn RequestHooks;
+
+/// Rate limit of the GitHub API, as sent with the last response.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RateLimitStatus {
+    /// Number of requests left until `reset`.
+    pub remaining: u64,
+    /// Number of requests allowed per hour.
+    pub limit: u64,
+    /// When `remaining` is reset to `limit`.
+    pub reset: Instant,
+}
+
+/// Return the duration until the rate limit resets.
+fn rate_limit_reset(headers: &HeaderMap) -> Option<Duration> {
+    let secs = header_value(headers, "x-ratelimit-reset")?;
+    // It is the time the rate limit resets, in seconds since the epoch.
+    let reset = SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))?;
+    Some(remote::delay_until(reset, headers, MAX_RATE_LIMIT_RESET))
+}
+
+/// Return the duration until the rate limit reported by the `RATE_LIMITED`
+/// `errors` resets, falling back to `x-ratelimit-reset`.
+fn graphql_rate_limit_reset(errors: &GhGraphQLErrors, headers: &HeaderMap) -> Option<Duration> {
+    match errors.rate_limit_reset() {
+        Some(reset) => Some(remote::delay_until(reset, headers, MAX_RATE_LIMIT_RESET)),
+        None => rate_limit_reset(headers),
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+fn parse_rate_limit(headers: &HeaderMap) -> Option<RateLimitStatus> {
+    Some(RateLimitStatus {
+        remaining: header_value(headers, "x-ratelimit-remaining")?,
+        limit: header_value(headers, "x-ratelimit-limit")?,
+        reset: Instant::now() + rate_limit_reset(headers)?,
+    })
+}
+
+/// Log the status and the rate limit of `response`, along with the id
+/// GitHub assigns to the request, which its support asks for.
+fn log_response(response: &remote::Response) {
+    let headers = response.headers();
+
+    debug!(
+        status = %response.status(),
+        rate_limit_remaining = header_value(headers, "x-ratelimit-remaining"),
+        rate_limit_limit = header_value(headers, "x-ratelimit-limit"),
+        rate_limit_reset = header_value(headers, "x-ratelimit-reset"),
+        rate_limit_resource = headers
+            .get("x-ratelimit-resource")
+            .and_then(|val| val.to_str().ok()),
+        request_id = headers
+            .get("x-github-request-id")
+            .and_then(|val| val.to_str().ok()),
+        "GitHub API responded to {}",
+        response.url()
+    );
+}
+
+fn check_for_status<T>(status: StatusCode, headers: &HeaderMap) -> Option<FetchReleaseRet<T>> {
+    // Secondary rate limits are reported with `Retry-After` while requests
+    // may be left in the primary one.
+    let retry_after = remote::parse_header_retry_after(headers)
+        .map(|retry_after| retry_after.min(MAX_RATE_LIMIT_RESET));
+    let rate_limited = status == remote::StatusCode::TOO_MANY_REQUESTS
+        || (status == remote::StatusCode::FORBIDDEN
+            && (retry_after.is_some()
+                || headers
+                    .get("x-ratelimit-remaining")
+                    .is_some_and(|val| val == "0")));
+
+    match status {
+        _ if rate_limited => Some(FetchReleaseRet::ReachedRateLimit {
+            retry_after: retry_after.or_else(|| rate_limit_reset(headers)),
+        }),
 
         remote::StatusCode::UNAUTHORIZED => Some(FetchReleaseRet::Unauthorized),
         remote::StatusCode::NOT_FOUND => Some(FetchReleaseRet::ReleaseNotFound),
@@ -133,43 +487,382 @@ fn check_for_status(status: StatusCode, headers: &HeaderMap) -> Option<FetchRele
     }
 }
 
-async fn fetch_release_artifacts_restful_api(
+/// Send a GET request to the restful API at `url`, return the successful
+/// response, or the [`FetchReleaseRet`] its status maps to.
+pub(super) async fn send_restful_request<T>(
     client: &remote::Client,
-    GhRelease { owner, repo, tag }: &GhRelease,
+    url: remote::Url,
     auth_token: Option<&str>,
-) -> Result<FetchReleaseRet, GhApiError> {
+    hooks: Hooks<'_>,
+) -> Result<Result<remote::Response, FetchReleaseRet<T>>, GhApiError> {
+    send_conditional_restful_request(client, url, None, auth_token, hooks).await
+}
+
+/// Send a GET request to the restful API at `url` like
+/// [`send_restful_request`], with `If-None-Match: etag`.
+///
+/// The response is returned as is if it is `304 Not Modified`, which does
+/// not count against the rate limit.
+async fn send_conditional_restful_request<T>(
+    client: &remote::Client,
+    url: remote::Url,
+    etag: Option<&str>,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<Result<remote::Response, FetchReleaseRet<T>>, GhApiError> {
+    send_get_request(
+        client,
+        url,
+        "application/vnd.github+json",
+        etag,
+        auth_token,
+        hooks,
+    )
+    .await
+}
+
+/// Request the content of the artifact at `artifact_url`, the url of the
+/// artifact in the restful API.
+///
+/// The API redirects to the storage of the artifact on another host, to
+/// which the token is not forwarded.
+pub(super) async fn download_release_artifact(
+    client: &remote::Client,
+    artifact_url: remote::Url,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<remote::Response>, GhApiError> {
+    Ok(
+        match send_get_request(
+            client,
+            artifact_url,
+            "application/octet-stream",
+            None,
+            auth_token,
+            hooks,
+        )
+        .await?
+        {
+            Ok(response) => FetchReleaseRet::Found(response),
+            Err(ret) => ret,
+        },
+    )
+}
+
+async fn send_get_request<T>(
+    client: &remote::Client,
+    url: remote::Url,
+    accept: &str,
+    etag: Option<&str>,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<Result<remote::Response, FetchReleaseRet<T>>, GhApiError> {
+    if !hooks.consume_budget() {
+        return Ok(Err(FetchReleaseRet::BudgetExhausted));
+    }
+
+    debug!(
+        authenticated = auth_token.is_some(),
+        conditional = etag.is_some(),
+        "Sending GitHub API request to {url}"
+    );
+
     let mut request_builder = client
-        .get(Url::parse(&format!(
-            "https://api.github.com/repos/{owner}/{repo}/releases/tags/{tag}",
-            owner = percent_encode_http_url_path(owner),
-            repo = percent_encode_http_url_path(repo),
-            tag = percent_encode_http_url_path(tag),
-        ))?)
-        .header("Accept", "application/vnd.github+json")
+        .get(url)
+        .header("Accept", accept)
         .header("X-GitHub-Api-Version", "2022-11-28");
 
+    if let Some(etag) = etag {
+        request_builder = request_builder.header("If-None-Match", etag);
+    }
+
     if let Some(auth_token) = auth_token {
         request_builder = request_builder.bearer_auth(&auth_token);
     }
 
     let response = request_builder.send(false).await?;
+    log_response(&response);
+    if let Some(status) = parse_rate_limit(response.headers()) {
+        hooks.record_rate_limit(status);
+    }
+
+    if etag.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Ok(response));
+    }
 
     if let Some(ret) = check_for_status(response.status(), response.headers()) {
-        Ok(ret)
+        Ok(Err(ret))
     } else {
-        Ok(FetchReleaseRet::Artifacts(response.json().await?))
+        Ok(Ok(response.error_for_status()?))
     }
 }
 
-#[derive(Deserialize)]
-enum GraphQLResponse {
-    #[serde(rename = "data")]
-    Data(GraphQLData),
+/// Fetch the artifacts of `release`, only if it is modified since `cached`
+/// was fetched if it has an `ETag`.
+pub(super) async fn fetch_release_artifacts_restful_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    GhRelease { owner, repo, tag }: &GhRelease,
+    cached: Option<&GhReleaseArtifacts>,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet, GhApiError> {
+    let owner = percent_encode_http_url_path(owner);
+    let repo = percent_encode_http_url_path(repo);
+
+    let url = host.restful_api_url(format_args!(
+        "repos/{owner}/{repo}/releases/tags/{tag}",
+        tag = percent_encode_http_url_path(tag),
+    ))?;
+
+    let etag = cached.and_then(GhReleaseArtifacts::etag);
+    let response =
+        match send_conditional_restful_request(client, url, etag, auth_token, hooks).await? {
+            Ok(response) => response,
+            Err(ret) => return Ok(ret),
+        };
+
+    if let Some(cached) = cached.filter(|_| response.status() == StatusCode::NOT_MODIFIED) {
+        debug!("GitHub release {owner}/{repo}@{tag} is not modified");
+        return Ok(FetchReleaseRet::Found(cached.clone()));
+    }
+
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(CompactString::from);
+    let RestfulRelease { id, artifacts } =
+        match parse_restful_response(&response.bytes().await?).into_found() {
+            Ok(Some(release)) => release,
+            Ok(None) => return Ok(FetchReleaseRet::ReleaseNotFound),
+            Err(ret) => return Ok(ret),
+        };
+    let mut artifacts = artifacts.with_etag(etag);
+
+    // The artifacts of drafts cannot be downloaded without a token.
+    if auth_token.is_none() && artifacts.meta.draft {
+        debug!("GitHub release {owner}/{repo}@{tag} is a draft");
+        return Ok(FetchReleaseRet::ReleaseNotFound);
+    }
+
+    let Some(id) = id.filter(|_| artifacts.assets.len() >= ASSETS_PER_PAGE) else {
+        return Ok(FetchReleaseRet::Found(artifacts));
+    };
+
+    // The first page is the one listed in the release.
+    let mut url = host.restful_api_url(format_args!(
+        "repos/{owner}/{repo}/releases/{id}/assets?per_page={ASSETS_PER_PAGE}&page=2"
+    ))?;
+    let api_origin = url.origin();
+
+    while artifacts.assets.len() < MAX_ASSETS {
+        let response = match send_restful_request(client, url, auth_token, hooks).await? {
+            Ok(response) => response,
+            Err(ret) => return Ok(ret),
+        };
+        let next_page = next_page_url(response.headers(), &api_origin);
+
+        let body = response.bytes().await?;
+        let Some(assets) = parse_json::<Vec<GhReleaseArtifactInfo>>(&body) else {
+            return Ok(FetchReleaseRet::MalformedResponse);
+        };
+        artifacts.assets.extend(assets.into_iter().map(Artifact));
+
+        match next_page {
+            Some(next_page) => url = next_page,
+            None => return Ok(FetchReleaseRet::Found(artifacts)),
+        }
+    }
+
+    debug!(
+        "Only the first {} artifacts of {owner}/{repo}@{tag} are fetched",
+        artifacts.assets.len()
+    );
+    Ok(FetchReleaseRet::Found(artifacts))
+}
+
+pub(super) fn parse_restful_response(body: &[u8]) -> FetchReleaseRet<RestfulRelease> {
+    parse_json(body).map_or(FetchReleaseRet::MalformedResponse, FetchReleaseRet::Found)
+}
+
+async fn fetch_latest_release_restful_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    GhRepo { owner, repo }: &GhRepo,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<CompactString>, GhApiError> {
+    let url = host.restful_api_url(format_args!(
+        "repos/{owner}/{repo}/releases/latest",
+        owner = percent_encode_http_url_path(owner),
+        repo = percent_encode_http_url_path(repo),
+    ))?;
+
+    Ok(
+        match send_restful_request(client, url, auth_token, hooks).await? {
+            Ok(response) => parse_latest_release_response(&response.bytes().await?),
+            Err(ret) => ret,
+        },
+    )
+}
+
+pub(super) fn parse_latest_release_response(body: &[u8]) -> FetchReleaseRet<CompactString> {
+    parse_json(body).map_or(FetchReleaseRet::MalformedResponse, ReleaseTag::into_ret)
+}
+
+async fn fetch_releases_restful_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    GhRepo { owner, repo }: &GhRepo,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<Vec<CompactString>>, GhApiError> {
+    let mut url = host.restful_api_url(format_args!(
+        "repos/{owner}/{repo}/releases?per_page={RELEASES_PER_PAGE}",
+        owner = percent_encode_http_url_path(owner),
+        repo = percent_encode_http_url_path(repo),
+    ))?;
+    let api_origin = url.origin();
+    let mut tags = Vec::new();
+
+    for _ in 0..MAX_RELEASE_PAGES {
+        let response = match send_restful_request(client, url, auth_token, hooks).await? {
+            Ok(response) => response,
+            Err(ret) => return Ok(ret),
+        };
+        let next_page = next_page_url(response.headers(), &api_origin);
+
+        let body = response.bytes().await?;
+        let Some(releases) = parse_json::<Vec<ReleaseTag>>(&body) else {
+            return Ok(FetchReleaseRet::MalformedResponse);
+        };
+        tags.extend(
+            releases
+                .into_iter()
+                .filter(|release| !release.draft)
+                .map(|release| release.tag_name),
+        );
+
+        match next_page {
+            Some(next_page) => url = next_page,
+            None => return Ok(FetchReleaseRet::Found(tags)),
+        }
+    }
+
+    debug!(
+        "Only the latest {} releases of {owner}/{repo} are listed",
+        tags.len()
+    );
+    Ok(FetchReleaseRet::Found(tags))
+}
+
+/// Return the url of the next page in the `Link` header, e.g.
+/// `<https://api.github.com/repositories/1/releases?page=2>; rel="next"`.
+///
+/// It is `None` unless it has the same scheme, host and port as
+/// `api_origin`, since the auth token is sent to it.
+fn next_page_url(headers: &HeaderMap, api_origin: &Origin) -> Option<remote::Url> {
+    let url = headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (url, params) = link.split_once(';')?;
+            params
+                .split(';')
+                .any(|param| param.trim().replace('"', "") == "rel=next")
+                .then_some(())?;
+
+            url.trim()
+                .strip_prefix('<')?
+                .strip_suffix('>')?
+                .parse::<remote::Url>()
+                .ok()
+        })?;
+
+    if url.origin() != *api_origin {
+        warn!("Not following the next page at {url}, it is not on the GitHub API host");
+        return None;
+    }
+    Some(url)
+}
+
+/// Number of bytes of a malformed response logged.
+const MALFORMED_RESPONSE_PREVIEW_LEN: usize = 300;
+
+/// Return `None` if `body` is not valid json of `T`.
+pub(super) fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Option<T> {
+    match serde_json::from_slice(body) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            debug!(
+                "Malformed GitHub API response ({err}), starting with: '{}'",
+                redacted_preview(body)
+            );
+            None
+        }
+    }
+}
+
+/// Return the start of `body` for logging, with anything looking like a
+/// GitHub token redacted and control characters escaped.
+fn redacted_preview(body: &[u8]) -> String {
+    const TOKEN_PREFIXES: &[&str] = &["ghp_", "gho_", "ghu_", "ghs_", "ghr_", "github_pat_"];
+
+    let end = body.len().min(MALFORMED_RESPONSE_PREVIEW_LEN);
+    let mut preview = String::from_utf8_lossy(&body[..end]).into_owned();
+
+    for prefix in TOKEN_PREFIXES {
+        let mut start = 0;
+        while let Some(pos) = preview[start..].find(prefix) {
+            let token_start = start + pos + prefix.len();
+            let token_len = preview[token_start..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(preview.len() - token_start);
+
+            preview.replace_range(token_start..token_start + token_len, "[REDACTED]");
+            start = token_start;
+        }
+    }
+
+    preview.escape_debug().to_string()
+}
 
-    #[serde(rename = "errors")]
+/// `data` is returned along with `errors` when only some of the fields can
+/// be resolved, then the partial data is only used if all the errors are
+/// `NOT_FOUND`, e.g. a repository that does not exist. Other errors, e.g.
+/// `FORBIDDEN` by an organization enforcing SAML SSO, also leave the
+/// fields `null`, which must not be taken for a release not found.
+#[derive(Deserialize)]
+#[serde(try_from = "RawGraphQLResponse<T>")]
+enum GraphQLResponse<T = GraphQLData> {
+    Data(T),
     Errors(GhGraphQLErrors),
 }
 
+#[derive(Deserialize)]
+struct RawGraphQLResponse<T> {
+    data: Option<T>,
+    errors: Option<GhGraphQLErrors>,
+}
+
+impl<T> TryFrom<RawGraphQLResponse<T>> for GraphQLResponse<T> {
+    type Error = &'static str;
+
+    fn try_from(
+        RawGraphQLResponse { data, errors }: RawGraphQLResponse<T>,
+    ) -> Result<Self, Self::Error> {
+        match (data, errors) {
+            (Some(data), None) => Ok(Self::Data(data)),
+            (Some(data), Some(errors)) if errors.are_not_found() => Ok(Self::Data(data)),
+            (_, Some(errors)) => Ok(Self::Errors(errors)),
+            (None, None) => Err("missing both `data` and `errors`"),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GhGraphQLErrors(Box<[GraphQLError]>);
 
@@ -179,6 +872,82 @@ impl GhGraphQLErrors {
             .iter()
             .any(|error| matches!(error.error_type, GraphQLErrorType::RateLimited))
     }
+
+    fn are_not_found(&self) -> bool {
+        self.0.iter().all(|error| {
+            matches!(&error.error_type, GraphQLErrorType::Other(error_type) if *error_type == "NOT_FOUND")
+        })
+    }
+
+    /// Return when the rate limit resets, from the `resetAt` of the
+    /// `RATE_LIMITED` errors, either in their `extensions` or next to their
+    /// message.
+    fn rate_limit_reset(&self) -> Option<SystemTime> {
+        self.0
+            .iter()
+            .filter(|error| matches!(error.error_type, GraphQLErrorType::RateLimited))
+            .flat_map(|error| &error.others)
+            .find_map(|(k, v)| match &**k {
+                "resetAt" => parse_reset_at(v),
+                "extensions" => parse_reset_at(v.get("resetAt")?),
+                _ => None,
+            })
+    }
+}
+
+/// Parse `resetAt`, an ISO 8601 UTC timestamp such as
+/// `2023-11-14T22:13:20Z` like `rateLimit.resetAt`, or seconds since the
+/// epoch like `x-ratelimit-reset`.
+fn parse_reset_at(value: &serde_json::Value) -> Option<SystemTime> {
+    let secs = match value {
+        serde_json::Value::Number(secs) => secs.as_u64()?,
+        serde_json::Value::String(s) => match s.parse() {
+            Ok(secs) => secs,
+            Err(_) => parse_utc_timestamp(s)?,
+        },
+        _ => return None,
+    };
+
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Parse `YYYY-MM-DDTHH:MM:SS[.fraction](Z|+00:00)` into seconds since the
+/// epoch, the fraction is ignored.
+fn parse_utc_timestamp(s: &str) -> Option<u64> {
+    let s = s.strip_suffix('Z').or_else(|| s.strip_suffix("+00:00"))?;
+    let (date, time) = s.split_once('T')?;
+    let time = time.split_once('.').map_or(time, |(time, _)| time);
+
+    let mut date = date.splitn(3, '-').map(str::parse::<u64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+
+    let mut time = time.splitn(3, ':').map(str::parse::<u64>);
+    let (hour, min, sec) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+
+    if !(1970..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || min > 59
+        || sec > 60
+    {
+        return None;
+    }
+
+    // Days since the epoch of the proleptic Gregorian date, see
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + hour * 3600 + min * 60 + sec)
 }
 
 impl error::Error for GhGraphQLErrors {}
@@ -273,8 +1042,38 @@ struct GraphQLRepo {
     release: Option<GraphQLRelease>,
 }
 
+#[derive(Deserialize)]
+struct GraphQLLatestReleaseData {
+    repository: Option<GraphQLLatestReleaseRepo>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLLatestReleaseRepo {
+    #[serde(rename = "latestRelease")]
+    latest_release: Option<ReleaseTag>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLReleasesData {
+    repository: Option<GraphQLReleasesRepo>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLReleasesRepo {
+    releases: GraphQLReleases,
+}
+
+#[derive(Deserialize)]
+struct GraphQLReleases {
+    nodes: Vec<ReleaseTag>,
+    #[serde(rename = "pageInfo")]
+    page_info: GraphQLPageInfo,
+}
+
 #[derive(Deserialize)]
 struct GraphQLRelease {
+    #[serde(flatten)]
+    meta: GhReleaseMeta,
     #[serde(rename = "releaseAssets")]
     assets: GraphQLReleaseAssets,
 }
@@ -314,28 +1113,96 @@ struct GraphQLQuery {
     query: String,
 }
 
-async fn fetch_release_artifacts_graphql_api(
+/// Send `query` to the GraphQL API at `graphql_endpoint`, return the data
+/// of the response, or the [`FetchReleaseRet`] it maps to.
+pub(super) async fn send_graphql_query<T: DeserializeOwned, U>(
     client: &remote::Client,
-    GhRelease { owner, repo, tag }: &GhRelease,
+    graphql_endpoint: &remote::Url,
+    query: String,
     auth_token: &str,
-) -> Result<FetchReleaseRet, GhApiError> {
-    static GRAPHQL_ENDPOINT: OnceLock<Url> = OnceLock::new();
+    hooks: Hooks<'_>,
+) -> Result<Result<T, FetchReleaseRet<U>>, GhApiError> {
+    let graphql_query = to_json_string(&GraphQLQuery { query }).map_err(remote::Error::from)?;
 
-    let graphql_endpoint = GRAPHQL_ENDPOINT.get_or_init(|| {
-        Url::parse("https://api.github.com/graphql").expect("Literal provided must be a valid url")
-    });
+    if !hooks.consume_budget() {
+        return Ok(Err(FetchReleaseRet::BudgetExhausted));
+    }
 
-    let mut artifacts = Artifacts::default();
-    let mut cond = FilterCondition::Init;
+    debug!("Sending graphql query to {graphql_endpoint}: '{graphql_query}'");
+
+    let request_builder = client
+        .post(graphql_endpoint.clone(), graphql_query)
+        .header("Accept", "application/vnd.github+json")
+        .bearer_auth(&auth_token);
+
+    let response = request_builder.send(false).await?;
+    log_response(&response);
+    if let Some(status) = parse_rate_limit(response.headers()) {
+        hooks.record_rate_limit(status);
+    }
+
+    if let Some(ret) = check_for_status(response.status(), response.headers()) {
+        return Ok(Err(ret));
+    }
+
+    // The rate limit headers are sent along with the `RATE_LIMITED` errors
+    // in a successful response.
+    let headers = response.headers().clone();
+    let body = response.error_for_status()?.bytes().await?;
+    let Some(response) = parse_json::<GraphQLResponse<T>>(&body) else {
+        return Ok(Err(FetchReleaseRet::MalformedResponse));
+    };
+
+    match response {
+        GraphQLResponse::Data(data) => Ok(Ok(data)),
+        GraphQLResponse::Errors(errors) if errors.is_rate_limited() => {
+            Ok(Err(FetchReleaseRet::ReachedRateLimit {
+                retry_after: graphql_rate_limit_reset(&errors, &headers),
+            }))
+        }
+        GraphQLResponse::Errors(errors) => Err(errors.into()),
+    }
+}
 
+async fn fetch_release_artifacts_graphql_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    release: &GhRelease,
+    auth_token: &str,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet, GhApiError> {
+    fetch_release_assets_pages(
+        client,
+        &host.graphql_api_url()?,
+        release,
+        auth_token,
+        hooks,
+        GhReleaseArtifacts::default(),
+        FilterCondition::Init,
+    )
+    .await
+}
+
+/// Fetch the pages of the artifacts of `release` starting at `cond`, and
+/// add them to `artifacts`.
+async fn fetch_release_assets_pages(
+    client: &remote::Client,
+    graphql_endpoint: &remote::Url,
+    GhRelease { owner, repo, tag }: &GhRelease,
+    auth_token: &str,
+    hooks: Hooks<'_>,
+    mut artifacts: GhReleaseArtifacts,
+    mut cond: FilterCondition,
+) -> Result<FetchReleaseRet, GhApiError> {
     loop {
         let query = format!(
             r#"
 query {{
   repository(owner:"{owner}",name:"{repo}") {{
     release(tagName:"{tag}") {{
+      publishedAt isPrerelease isDraft
       releaseAssets({cond}) {{
-        nodes {{ name }}
+        nodes {{ name size contentType }}
         pageInfo {{ endCursor hasNextPage }}
       }}
     }}
@@ -343,47 +1210,36 @@ query {{
 }}"#
         );
 
-        let graphql_query = to_json_string(&GraphQLQuery { query }).map_err(remote::Error::from)?;
-
-        debug!("Sending graphql query to https://api.github.com/graphql: '{graphql_query}'");
-
-        let request_builder = client
-            .post(graphql_endpoint.clone(), graphql_query)
-            .header("Accept", "application/vnd.github+json")
-            .bearer_auth(&auth_token);
-
-        let response = request_builder.send(false).await?;
-
-        if let Some(ret) = check_for_status(response.status(), response.headers()) {
-            return Ok(ret);
-        }
-
-        let response: GraphQLResponse = response.json().await?;
-
-        let data = match response {
-            GraphQLResponse::Data(data) => data,
-            GraphQLResponse::Errors(errors) if errors.is_rate_limited() => {
-                return Ok(FetchReleaseRet::ReachedRateLimit { retry_after: None })
-            }
-            GraphQLResponse::Errors(errors) => return Err(errors.into()),
-        };
+        let data: GraphQLData =
+            match send_graphql_query(client, graphql_endpoint, query, auth_token, hooks).await? {
+                Ok(data) => data,
+                Err(ret) => return Ok(ret),
+            };
 
-        let assets = data
-            .repository
-            .and_then(|repository| repository.release)
-            .map(|release| release.assets);
-
-        if let Some(assets) = assets {
+        if let Some(GraphQLRelease { meta, assets }) =
+            data.repository.and_then(|repository| repository.release)
+        {
+            artifacts.meta = meta;
             artifacts.assets.extend(assets.nodes);
 
             match assets.page_info {
                 GraphQLPageInfo {
                     end_cursor: Some(end_cursor),
                     has_next_page: true,
-                } => {
+                } if artifacts.assets.len() < MAX_ASSETS => {
                     cond = FilterCondition::After(end_cursor);
                 }
-                _ => break Ok(FetchReleaseRet::Artifacts(artifacts)),
+                GraphQLPageInfo {
+                    has_next_page: true,
+                    ..
+                } => {
+                    debug!(
+                        "Only the first {} artifacts of {owner}/{repo}@{tag} are fetched",
+                        artifacts.assets.len()
+                    );
+                    break Ok(FetchReleaseRet::Found(artifacts));
+                }
+                _ => break Ok(FetchReleaseRet::Found(artifacts)),
             }
         } else {
             break Ok(FetchReleaseRet::ReleaseNotFound);
@@ -391,13 +1247,149 @@ query {{
     }
 }
 
+/// Fetch the artifacts of all `releases` with one query, using one aliased
+/// `repository` field per release.
+///
+/// The aliases are named after the index of the releases since tags may
+/// contain characters not allowed in GraphQL names, e.g. `/` and `.`. The
+/// releases with more than 100 artifacts are completed with one query
+/// each.
+async fn fetch_releases_artifacts_graphql_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    releases: &[&GhRelease],
+    auth_token: &str,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<Vec<Option<GhReleaseArtifacts>>>, GhApiError> {
+    let graphql_endpoint = host.graphql_api_url()?;
+
+    let mut query = String::from("\nquery {");
+    for (i, GhRelease { owner, repo, tag }) in releases.iter().enumerate() {
+        query.push_str(&format!(
+            r#"
+  r{i}: repository(owner:"{owner}",name:"{repo}") {{
+    release(tagName:"{tag}") {{
+      publishedAt isPrerelease isDraft
+      releaseAssets({cond}) {{
+        nodes {{ name size contentType }}
+        pageInfo {{ endCursor hasNextPage }}
+      }}
+    }}
+  }}"#,
+            cond = FilterCondition::Init,
+        ));
+    }
+    query.push_str("\n}");
+
+    // Aliases of repositories that do not exist are null.
+    let mut data: HashMap<CompactString, Option<GraphQLRepo>> =
+        match send_graphql_query(client, &graphql_endpoint, query, auth_token, hooks).await? {
+            Ok(data) => data,
+            Err(ret) => return Ok(ret),
+        };
+
+    let mut rets = Vec::with_capacity(releases.len());
+    for (i, release) in releases.iter().enumerate() {
+        let graphql_release = data
+            .remove(&*format_compact!("r{i}"))
+            .flatten()
+            .and_then(|repository| repository.release);
+
+        let Some(GraphQLRelease {
+            meta,
+            assets: GraphQLReleaseAssets { nodes, page_info },
+        }) = graphql_release
+        else {
+            rets.push(None);
+            continue;
+        };
+
+        let artifacts = GhReleaseArtifacts {
+            assets: nodes.into_iter().collect(),
+            meta,
+            etag: None,
+        };
+
+        let artifacts = match page_info {
+            GraphQLPageInfo {
+                end_cursor: Some(end_cursor),
+                has_next_page: true,
+            } => {
+                let ret = fetch_release_assets_pages(
+                    client,
+                    &graphql_endpoint,
+                    release,
+                    auth_token,
+                    hooks,
+                    artifacts,
+                    FilterCondition::After(end_cursor),
+                )
+                .await?;
+
+                match ret.into_found() {
+                    Ok(artifacts) => artifacts,
+                    Err(ret) => return Ok(ret),
+                }
+            }
+            _ => Some(artifacts),
+        };
+
+        rets.push(artifacts);
+    }
+
+    Ok(FetchReleaseRet::Found(rets))
+}
+
+/// Fetch the artifacts of `release`, with the GraphQL API if a token is
+/// available unless `cached` has an `ETag`: only the restful API supports
+/// conditional requests, which are free if the release is not modified.
+#[instrument(
+    level = "debug",
+    skip_all,
+    fields(owner = %release.owner, repo = %release.repo, tag = %release.tag)
+)]
 pub(super) async fn fetch_release_artifacts(
     client: &remote::Client,
+    host: &GhApiHost,
     release: &GhRelease,
+    cached: Option<&GhReleaseArtifacts>,
     auth_token: Option<&str>,
+    hooks: Hooks<'_>,
 ) -> Result<FetchReleaseRet, GhApiError> {
+    let cached = cached.filter(|cached| cached.etag().is_some());
+
+    if let Some(auth_token) = auth_token.filter(|_| cached.is_none()) {
+        let res = fetch_release_artifacts_graphql_api(client, host, release, auth_token, hooks)
+            .await
+            .map_err(|err| err.context("GraphQL API"));
+
+        match res {
+            // Fallback to Restful API
+            Ok(FetchReleaseRet::Unauthorized) => (),
+            res => return res,
+        }
+    }
+
+    fetch_release_artifacts_restful_api(client, host, release, cached, auth_token, hooks)
+        .await
+        .map_err(|err| err.context("Restful API"))
+}
+
+/// Fetch the artifacts of all `releases`, `None` for the ones that do not
+/// exist.
+///
+/// With a token they are fetched with one GraphQL query, otherwise with
+/// one restful request each.
+#[instrument(level = "debug", skip_all, fields(releases = releases.len()))]
+pub(super) async fn fetch_releases_artifacts(
+    client: &remote::Client,
+    host: &GhApiHost,
+    releases: &[&GhRelease],
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<Vec<Option<GhReleaseArtifacts>>>, GhApiError> {
     if let Some(auth_token) = auth_token {
-        let res = fetch_release_artifacts_graphql_api(client, release, auth_token)
+        let res = fetch_releases_artifacts_graphql_api(client, host, releases, auth_token, hooks)
             .await
             .map_err(|err| err.context("GraphQL API"));
 
@@ -408,7 +1400,159 @@ pub(super) async fn fetch_release_artifacts(
         }
     }
 
-    fetch_release_artifacts_restful_api(client, release, auth_token)
+    let mut rets = Vec::with_capacity(releases.len());
+    for release in releases {
+        let ret =
+            fetch_release_artifacts_restful_api(client, host, release, None, auth_token, hooks)
+                .await
+                .map_err(|err| err.context("Restful API"))?;
+
+        match ret.into_found() {
+            Ok(artifacts) => rets.push(artifacts),
+            Err(ret) => return Ok(ret),
+        }
+    }
+
+    Ok(FetchReleaseRet::Found(rets))
+}
+
+async fn fetch_latest_release_graphql_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    GhRepo { owner, repo }: &GhRepo,
+    auth_token: &str,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<CompactString>, GhApiError> {
+    let query = format!(
+        r#"
+query {{
+  repository(owner:"{owner}",name:"{repo}") {{
+    latestRelease {{ tagName isDraft isPrerelease }}
+  }}
+}}"#
+    );
+
+    let data: GraphQLLatestReleaseData =
+        match send_graphql_query(client, &host.graphql_api_url()?, query, auth_token, hooks).await?
+        {
+            Ok(data) => data,
+            Err(ret) => return Ok(ret),
+        };
+
+    Ok(data
+        .repository
+        .and_then(|repository| repository.latest_release)
+        .map_or(FetchReleaseRet::ReleaseNotFound, ReleaseTag::into_ret))
+}
+
+async fn fetch_releases_graphql_api(
+    client: &remote::Client,
+    host: &GhApiHost,
+    GhRepo { owner, repo }: &GhRepo,
+    auth_token: &str,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<Vec<CompactString>>, GhApiError> {
+    let graphql_endpoint = host.graphql_api_url()?;
+
+    let mut tags = Vec::new();
+    let mut cond = FilterCondition::Init;
+
+    for _ in 0..MAX_RELEASE_PAGES {
+        let query = format!(
+            r#"
+query {{
+  repository(owner:"{owner}",name:"{repo}") {{
+    releases({cond},orderBy:{{field:CREATED_AT,direction:DESC}}) {{
+      nodes {{ tagName isDraft }}
+      pageInfo {{ endCursor hasNextPage }}
+    }}
+  }}
+}}"#
+        );
+
+        let data: GraphQLReleasesData =
+            match send_graphql_query(client, &graphql_endpoint, query, auth_token, hooks).await? {
+                Ok(data) => data,
+                Err(ret) => return Ok(ret),
+            };
+
+        let Some(repository) = data.repository else {
+            return Ok(FetchReleaseRet::ReleaseNotFound);
+        };
+        let releases = repository.releases;
+
+        tags.extend(
+            releases
+                .nodes
+                .into_iter()
+                .filter(|release| !release.draft)
+                .map(|release| release.tag_name),
+        );
+
+        match releases.page_info {
+            GraphQLPageInfo {
+                end_cursor: Some(end_cursor),
+                has_next_page: true,
+            } => {
+                cond = FilterCondition::After(end_cursor);
+            }
+            _ => return Ok(FetchReleaseRet::Found(tags)),
+        }
+    }
+
+    debug!(
+        "Only the latest {} releases of {owner}/{repo} are listed",
+        tags.len()
+    );
+    Ok(FetchReleaseRet::Found(tags))
+}
+
+#[instrument(level = "debug", skip_all, fields(owner = %repo.owner, repo = %repo.repo))]
+pub(super) async fn fetch_releases(
+    client: &remote::Client,
+    host: &GhApiHost,
+    repo: &GhRepo,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<Vec<CompactString>>, GhApiError> {
+    if let Some(auth_token) = auth_token {
+        let res = fetch_releases_graphql_api(client, host, repo, auth_token, hooks)
+            .await
+            .map_err(|err| err.context("GraphQL API"));
+
+        match res {
+            // Fallback to Restful API
+            Ok(FetchReleaseRet::Unauthorized) => (),
+            res => return res,
+        }
+    }
+
+    fetch_releases_restful_api(client, host, repo, auth_token, hooks)
+        .await
+        .map_err(|err| err.context("Restful API"))
+}
+
+#[instrument(level = "debug", skip_all, fields(owner = %repo.owner, repo = %repo.repo))]
+pub(super) async fn fetch_latest_release(
+    client: &remote::Client,
+    host: &GhApiHost,
+    repo: &GhRepo,
+    auth_token: Option<&str>,
+    hooks: Hooks<'_>,
+) -> Result<FetchReleaseRet<CompactString>, GhApiError> {
+    if let Some(auth_token) = auth_token {
+        let res = fetch_latest_release_graphql_api(client, host, repo, auth_token, hooks)
+            .await
+            .map_err(|err| err.context("GraphQL API"));
+
+        match res {
+            // Fallback to Restful API
+            Ok(FetchReleaseRet::Unauthorized) => (),
+            res => return res,
+        }
+    }
+
+    fetch_latest_release_restful_api(client, host, repo, auth_token, hooks)
         .await
         .map_err(|err| err.context("Restful API"))
 }
@@ -432,6 +1576,335 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_malformed_response() {
+        assert_matches!(
+            parse_restful_response(br#"{"assets": [{"name": "a"}, {"name": "b"}]}"#),
+            FetchReleaseRet::Found(release) if release.artifacts.contains("b")
+        );
+
+        for body in [
+            &br#"{"assets": [{"name": "a"}, {"na"#[..],
+            b"<html>Blocked by proxy</html>",
+            b"",
+        ] {
+            assert_matches!(
+                parse_restful_response(body),
+                FetchReleaseRet::MalformedResponse
+            );
+        }
+
+        assert!(parse_json::<GraphQLResponse>(br#"{"data": {"repository": nul"#).is_none());
+    }
+
+    #[test]
+    fn test_parse_latest_release() {
+        assert_matches!(
+            parse_latest_release_response(br#"{"tag_name": "v1.2.3", "draft": false, "prerelease": false}"#),
+            FetchReleaseRet::Found(tag) if tag == "v1.2.3"
+        );
+        assert_matches!(
+            parse_latest_release_response(br#"{"tag_name": "v2.0.0-rc.1", "prerelease": true}"#),
+            FetchReleaseRet::ReleaseNotFound
+        );
+        assert_matches!(
+            parse_latest_release_response(br#"{"name": "v1.2.3"}"#),
+            FetchReleaseRet::MalformedResponse
+        );
+
+        let response = parse_json::<GraphQLResponse<GraphQLLatestReleaseData>>(
+            br#"{"data": {"repository": {"latestRelease": {"tagName": "v1.2.3", "isDraft": false, "isPrerelease": false}}}}"#,
+        )
+        .unwrap();
+        let GraphQLResponse::Data(data) = response else {
+            panic!("Expected data");
+        };
+        assert_eq!(
+            data.repository.unwrap().latest_release.unwrap().tag_name,
+            "v1.2.3"
+        );
+    }
+
+    #[test]
+    fn test_graphql_partial_response() {
+        // The repository does not exist.
+        let response = parse_json::<GraphQLResponse>(
+            br#"{"data": {"repository": null}, "errors": [{"type": "NOT_FOUND", "message": "Could not resolve to a Repository"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            response,
+            GraphQLResponse::Data(GraphQLData { repository: None })
+        ));
+
+        let response = parse_json::<GraphQLResponse>(
+            br#"{"data": null, "errors": [{"type": "RATE_LIMITED", "message": "API rate limit exceeded"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(response, GraphQLResponse::Errors(errors) if errors.is_rate_limited()));
+
+        // The repository is not accessible with the token, it may exist.
+        let response = parse_json::<GraphQLResponse>(
+            br#"{"data": {"repository": null}, "errors": [{"type": "FORBIDDEN", "message": "Resource protected by organization SAML enforcement"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(response, GraphQLResponse::Errors(_)));
+
+        let response = parse_json::<GraphQLResponse>(
+            br#"{"data": {"repository": null}, "errors": [{"type": "NOT_FOUND", "message": "Could not resolve to a Repository"}, {"type": "FORBIDDEN", "message": "Resource protected by organization SAML enforcement"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(response, GraphQLResponse::Errors(_)));
+
+        assert!(parse_json::<GraphQLResponse>(b"{}").is_none());
+    }
+
+    #[test]
+    fn test_next_page_url() {
+        let api_origin = remote::Url::parse("https://api.github.com/")
+            .unwrap()
+            .origin();
+        let next_page = |link: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(LINK, link.parse().unwrap());
+            next_page_url(&headers, &api_origin).map(|url| url.to_string())
+        };
+
+        assert_eq!(
+            next_page(
+                r#"<https://api.github.com/repositories/1/releases?per_page=100&page=2>; rel="next", <https://api.github.com/repositories/1/releases?per_page=100&page=5>; rel="last""#
+            )
+            .as_deref(),
+            Some("https://api.github.com/repositories/1/releases?per_page=100&page=2")
+        );
+        assert_eq!(
+            next_page(
+                r#"<https://api.github.com/repositories/1/releases?per_page=100&page=4>; rel="prev", <https://api.github.com/repositories/1/releases?per_page=100&page=1>; rel="first""#
+            ),
+            None
+        );
+        assert_eq!(next_page(r#"not a link; rel="next""#), None);
+        assert_eq!(next_page_url(&HeaderMap::new(), &api_origin), None);
+
+        // The auth token is not sent to another host, scheme or port.
+        for url in [
+            "https://evil.example.com/repositories/1/releases?page=2",
+            "http://api.github.com/repositories/1/releases?page=2",
+            "https://api.github.com:8443/repositories/1/releases?page=2",
+        ] {
+            assert_eq!(next_page(&format!(r#"<{url}>; rel="next""#)), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_redacted_preview() {
+        assert_eq!(
+            redacted_preview(b"token=ghp_abcDEF123 and github_pat_11AB_x9, done\n"),
+            "token=ghp_[REDACTED] and github_pat_[REDACTED], done\\n"
+        );
+
+        let preview = redacted_preview(&[b'a'; 1000]);
+        assert_eq!(preview.len(), MALFORMED_RESPONSE_PREVIEW_LEN);
+    }
+
+    #[test]
+    fn test_rate_limit_reset() {
+        let retry_after = |reset: &str, date: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+            headers.insert("x-ratelimit-reset", reset.parse().unwrap());
+            headers.insert(remote::header::DATE, date.parse().unwrap());
+
+            match check_for_status::<GhReleaseArtifacts>(StatusCode::FORBIDDEN, &headers) {
+                Some(FetchReleaseRet::ReachedRateLimit { retry_after }) => retry_after,
+                ret => panic!("Expected ReachedRateLimit, found {ret:?}"),
+            }
+        };
+
+        // The reset is a timestamp, not a duration.
+        assert_eq!(
+            retry_after("1700000100", "Tue, 14 Nov 2023 22:13:20 GMT"),
+            Some(Duration::from_secs(100))
+        );
+        assert_eq!(
+            retry_after("1700000000", "Tue, 14 Nov 2023 22:15:00 GMT"),
+            Some(Duration::ZERO)
+        );
+
+        // A reset days after the server time is clamped.
+        assert_eq!(
+            retry_after("1700999999", "Tue, 14 Nov 2023 22:13:20 GMT"),
+            Some(MAX_RATE_LIMIT_RESET)
+        );
+
+        assert_eq!(retry_after("soon", "Tue, 14 Nov 2023 22:13:20 GMT"), None);
+    }
+
+    #[test]
+    fn test_secondary_rate_limit() {
+        let check = |status: StatusCode, headers: &[(&'static str, &str)]| {
+            let mut header_map = HeaderMap::new();
+            header_map.insert(
+                remote::header::DATE,
+                "Tue, 14 Nov 2023 22:13:20 GMT".parse().unwrap(),
+            );
+            for (name, value) in headers {
+                header_map.insert(*name, value.parse().unwrap());
+            }
+
+            match check_for_status::<GhReleaseArtifacts>(status, &header_map) {
+                Some(FetchReleaseRet::ReachedRateLimit { retry_after }) => Some(retry_after),
+                _ => None,
+            }
+        };
+
+        assert_eq!(
+            check(StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "60")]),
+            Some(Some(Duration::from_secs(60)))
+        );
+        assert_eq!(
+            check(
+                StatusCode::TOO_MANY_REQUESTS,
+                &[("retry-after", "Tue, 14 Nov 2023 22:15:00 GMT")]
+            ),
+            Some(Some(Duration::from_secs(100)))
+        );
+        assert_eq!(check(StatusCode::TOO_MANY_REQUESTS, &[]), Some(None));
+
+        // The primary rate limit is not used up.
+        assert_eq!(
+            check(
+                StatusCode::FORBIDDEN,
+                &[("x-ratelimit-remaining", "4000"), ("retry-after", "30")]
+            ),
+            Some(Some(Duration::from_secs(30)))
+        );
+        assert_eq!(
+            check(StatusCode::FORBIDDEN, &[("retry-after", "86400")]),
+            Some(Some(MAX_RATE_LIMIT_RESET))
+        );
+
+        // Primary rate limit.
+        assert_eq!(
+            check(
+                StatusCode::FORBIDDEN,
+                &[
+                    ("x-ratelimit-remaining", "0"),
+                    ("x-ratelimit-reset", "1700000100")
+                ]
+            ),
+            Some(Some(Duration::from_secs(100)))
+        );
+
+        // Any other forbidden request, e.g. to a blocked repository.
+        assert_eq!(
+            check(StatusCode::FORBIDDEN, &[("x-ratelimit-remaining", "4000")]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_rate_limit() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-limit", "60".parse().unwrap());
+        headers.insert("x-ratelimit-remaining", "59".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1700003600".parse().unwrap());
+        headers.insert(
+            remote::header::DATE,
+            "Tue, 14 Nov 2023 22:13:20 GMT".parse().unwrap(),
+        );
+
+        let before = Instant::now();
+        let status = parse_rate_limit(&headers).unwrap();
+        assert_eq!(status.remaining, 59);
+        assert_eq!(status.limit, 60);
+        assert!(status.reset >= before + Duration::from_secs(3600));
+        assert!(status.reset <= Instant::now() + Duration::from_secs(3600));
+
+        headers.remove("x-ratelimit-limit");
+        assert_eq!(parse_rate_limit(&headers), None);
+    }
+
+    #[test]
+    fn test_parse_utc_timestamp() {
+        assert_eq!(parse_utc_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_utc_timestamp("2023-11-14T22:13:20Z"),
+            Some(1_700_000_000)
+        );
+        assert_eq!(
+            parse_utc_timestamp("2024-02-29T12:00:00.123+00:00"),
+            Some(1_709_208_000)
+        );
+
+        for s in [
+            "2023-11-14T22:13:20",
+            "2023-11-14 22:13:20Z",
+            "2023-13-14T22:13:20Z",
+            "2023-11-14T22:13Z",
+            "2023-11-14T22:13:20+02:00",
+            "soon",
+        ] {
+            assert_eq!(parse_utc_timestamp(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_graphql_rate_limit_reset() {
+        let rate_limit_reset = |body: &[u8]| match parse_json::<GraphQLResponse>(body).unwrap() {
+            GraphQLResponse::Errors(errors) if errors.is_rate_limited() => {
+                errors.rate_limit_reset()
+            }
+            _ => panic!("Expected rate limited errors"),
+        };
+        let at = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+        // Captured from api.github.com, which sends no reset time.
+        assert_eq!(
+            rate_limit_reset(
+                br#"{"errors":[{"type":"RATE_LIMITED","code":"graphql_rate_limit","message":"API rate limit already exceeded for user ID 1."}]}"#
+            ),
+            None
+        );
+        assert_eq!(
+            rate_limit_reset(
+                br#"{"data":null,"errors":[{"type":"RATE_LIMITED","message":"API rate limit exceeded for user ID 1.","extensions":{"resetAt":"2023-11-14T22:13:20Z"}}]}"#
+            ),
+            at(1_700_000_000)
+        );
+        assert_eq!(
+            rate_limit_reset(
+                br#"{"errors":[{"type":"NOT_FOUND","message":"Could not resolve to a Repository","resetAt":"2023-11-14T20:00:00Z"},{"type":"RATE_LIMITED","message":"API rate limit exceeded","resetAt":1700000100}]}"#
+            ),
+            at(1_700_000_100)
+        );
+        assert_eq!(
+            rate_limit_reset(
+                br#"{"errors":[{"type":"RATE_LIMITED","message":"API rate limit exceeded","extensions":{"resetAt":"tomorrow"}}]}"#
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_graphql_rate_limit_reset_header_fallback() {
+        let Some(GraphQLResponse::Errors(errors)) = parse_json::<GraphQLResponse>(
+            br#"{"errors":[{"type":"RATE_LIMITED","message":"API rate limit exceeded"}]}"#,
+        ) else {
+            panic!("Expected errors");
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("date", "Tue, 14 Nov 2023 22:13:20 GMT".parse().unwrap());
+        assert_eq!(graphql_rate_limit_reset(&errors, &headers), None);
+
+        headers.insert("x-ratelimit-reset", "1700000100".parse().unwrap());
+        assert_eq!(
+            graphql_rate_limit_reset(&errors, &headers),
+            Some(Duration::from_secs(100))
+        );
+    }
+
     #[test]
     fn test_graph_ql_error_type() {
         let deserialize = |input: &str| {
@@ -0,0 +1,77 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use compact_str::CompactString;
+use tokio::sync::Mutex as AsyncMutex;
+
+use super::RateLimitStatus;
+
+/// Rate limit and auth tokens of [`super::GhApiClient`], shared by all the
+/// clients created with the same state (or a clone of it), see
+/// [`super::GhApiClient::with_state`].
+///
+/// Once any of them reaches the rate limit, the others answer with
+/// [`super::HasReleaseArtifact::RateLimit`] too, without sending any
+/// request, and a token rejected by one is skipped by all of them.
+#[derive(Clone, Debug)]
+pub struct GhApiState(pub(super) Arc<State>);
+
+#[derive(Debug)]
+pub(super) struct State {
+    pub(super) retry_after: Mutex<Option<Instant>>,
+    /// The `retry_after` a lookup waited for and then succeeded after, so
+    /// that the other lookups waiting for it retry without waiting for each
+    /// other, see [`super::GhApiClient::has_release_artifact_with_retry`].
+    pub(super) rate_limit_reset: AsyncMutex<Option<Instant>>,
+    /// Rate limit sent with the last response.
+    pub(super) rate_limit: Mutex<Option<RateLimitStatus>>,
+
+    pub(super) auth_tokens: Box<[CompactString]>,
+    /// State of each of `auth_tokens`.
+    pub(super) auth_token_states: Box<[Mutex<AuthTokenState>]>,
+}
+
+/// Whether an auth token can be used.
+#[derive(Debug, Default)]
+pub(super) struct AuthTokenState {
+    /// Set once the token is unauthorized everywhere, it is never used
+    /// again.
+    pub(super) rejected: bool,
+    /// Until when the token is rate limited, it is used again afterwards.
+    pub(super) retry_after: Option<Instant>,
+    /// Owners whose repositories the token is unauthorized for, e.g.
+    /// organizations enforcing SAML SSO that the token is not authorized
+    /// for.
+    pub(super) owners: HashSet<CompactString>,
+    /// Owner of the last unauthorized request, reset by any request that is
+    /// not unauthorized.
+    pub(super) last_owner: Option<CompactString>,
+}
+
+impl AuthTokenState {
+    /// Return `true` if the token is rate limited at the moment.
+    pub(super) fn is_rate_limited(&self) -> bool {
+        self.retry_after
+            .is_some_and(|retry_after| retry_after.elapsed().is_zero())
+    }
+}
+
+impl GhApiState {
+    /// * `auth_tokens` - see [`super::GhApiClient::new`].
+    pub fn new(auth_tokens: impl IntoIterator<Item = CompactString>) -> Self {
+        let auth_tokens: Box<[CompactString]> = auth_tokens.into_iter().collect();
+        let auth_token_states = auth_tokens.iter().map(|_| Default::default()).collect();
+
+        Self(Arc::new(State {
+            retry_after: Default::default(),
+            rate_limit_reset: Default::default(),
+            rate_limit: Default::default(),
+
+            auth_tokens,
+            auth_token_states,
+        }))
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use compact_str::CompactString;
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+use crate::{
+    remote,
+    utils::{percent_decode_http_url_path, percent_encode_http_url_path, Map},
+};
+
+mod request;
+pub use request::GiteaApiError;
+use request::{FetchReleaseRet, GiteaReleaseArtifacts};
+
+/// Host of codeberg.org, the default host of [`GiteaApiClient`].
+pub const CODEBERG_ORG: &str = "codeberg.org";
+
+/// Retry duration if `Retry-After` is not sent.
+const DEFAULT_RETRY_DURATION: Duration = Duration::from_secs(60);
+
+/// The release of a repository on a Gitea instance.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GiteaRelease {
+    /// Host of the Gitea instance, e.g. [`CODEBERG_ORG`].
+    pub host: CompactString,
+    pub owner: CompactString,
+    pub repo: CompactString,
+    pub tag: CompactString,
+}
+
+/// The Gitea Release and one of its artifact.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GiteaReleaseArtifact {
+    pub release: GiteaRelease,
+    pub artifact_name: CompactString,
+}
+
+impl GiteaReleaseArtifact {
+    /// Create [`GiteaReleaseArtifact`] from the url of a release download on
+    /// one of `hosts`, e.g. [`GiteaApiClient::hosts`]:
+    /// `https://{host}/{owner}/{repo}/releases/download/{tag}/{name}`.
+    pub fn try_extract_from_url(url: &remote::Url, hosts: &[&str]) -> Option<Self> {
+        let domain = url.domain()?;
+        let host = hosts
+            .iter()
+            .find(|host| domain.eq_ignore_ascii_case(host))?;
+
+        let mut path_segments = url.path_segments()?;
+
+        let owner = path_segments.next()?;
+        let repo = path_segments.next()?;
+
+        if (path_segments.next()?, path_segments.next()?) != ("releases", "download") {
+            return None;
+        }
+
+        let tag = path_segments.next()?;
+        let artifact_name = path_segments.next().filter(|name| !name.is_empty())?;
+
+        (path_segments.next().is_none() && url.fragment().is_none() && url.query().is_none()).then(
+            || Self {
+                release: GiteaRelease {
+                    host: host.to_ascii_lowercase().into(),
+                    owner: percent_decode_http_url_path(owner),
+                    repo: percent_decode_http_url_path(repo),
+                    tag: percent_decode_http_url_path(tag),
+                },
+                artifact_name: percent_decode_http_url_path(artifact_name),
+            },
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    client: remote::Client,
+    hosts: Vec<CompactString>,
+    release_artifacts: Map<GiteaRelease, OnceCell<Option<GiteaReleaseArtifacts>>>,
+    /// Until when each host is rate limited.
+    retry_after: Map<CompactString, Mutex<Option<Instant>>>,
+
+    auth_token: Option<CompactString>,
+    /// Set once `auth_token` is unauthorized, requests are then sent
+    /// without it.
+    auth_token_rejected: AtomicBool,
+}
+
+/// Gitea API client for querying whether a release artifact exists, on
+/// codeberg.org or other Gitea and Forgejo instances.
+#[derive(Clone, Debug)]
+pub struct GiteaApiClient(Arc<Inner>);
+
+impl GiteaApiClient {
+    /// * `auth_token` - sent to all the hosts, see [`GiteaApiClient::with_hosts`].
+    pub fn new(client: remote::Client, auth_token: Option<CompactString>) -> Self {
+        Self(Arc::new(Inner {
+            client,
+            hosts: vec![CODEBERG_ORG.into()],
+            release_artifacts: Default::default(),
+            retry_after: Default::default(),
+
+            auth_token,
+            auth_token_rejected: AtomicBool::new(false),
+        }))
+    }
+
+    /// Read the token from `GITEA_TOKEN`.
+    pub fn auth_token_from_env() -> Option<CompactString> {
+        env::var("GITEA_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(CompactString::from)
+    }
+
+    /// Check the release artifacts on `hosts`, e.g. `gitea.example.com`,
+    /// instead of codeberg.org only.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn with_hosts<I>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        Arc::get_mut(&mut self.0)
+            .expect("GiteaApiClient::with_hosts must be called before cloning it")
+            .hosts = hosts
+            .into_iter()
+            .map(|host| {
+                host.as_ref()
+                    .trim()
+                    .trim_end_matches('/')
+                    .to_ascii_lowercase()
+                    .into()
+            })
+            .collect();
+        self
+    }
+
+    /// Hosts of the web interface, of the API and of the release downloads.
+    pub fn hosts(&self) -> Vec<&str> {
+        self.0.hosts.iter().map(CompactString::as_str).collect()
+    }
+
+    pub fn has_auth_token(&self) -> bool {
+        self.0.auth_token.is_some() && !self.0.auth_token_rejected.load(Relaxed)
+    }
+}
+
+enum FetchReleaseArtifactError {
+    Error(GiteaApiError),
+    RateLimit { retry_after: Instant },
+    Unauthorized,
+}
+
+impl GiteaApiClient {
+    async fn do_fetch_release_artifacts(
+        &self,
+        release: &GiteaRelease,
+        auth_token: Option<&str>,
+    ) -> Result<Option<GiteaReleaseArtifacts>, FetchReleaseArtifactError> {
+        use FetchReleaseArtifactError as Error;
+
+        match request::fetch_release_artifacts(&self.0.client, release, auth_token).await {
+            Ok(FetchReleaseRet::ReleaseNotFound) => Ok(None),
+            Ok(FetchReleaseRet::Found(artifacts)) => Ok(Some(artifacts)),
+            Ok(FetchReleaseRet::ReachedRateLimit { retry_after }) => {
+                let retry_after = retry_after.unwrap_or(DEFAULT_RETRY_DURATION);
+
+                let now = Instant::now();
+                let retry_after = now
+                    .checked_add(retry_after)
+                    .unwrap_or_else(|| now + DEFAULT_RETRY_DURATION);
+
+                Err(Error::RateLimit { retry_after })
+            }
+            Ok(FetchReleaseRet::Unauthorized) => Err(Error::Unauthorized),
+            Err(err) => Err(Error::Error(err)),
+        }
+    }
+
+    /// Fetch with the token, and without it once it is unauthorized.
+    async fn fetch_release_artifacts(
+        &self,
+        release: &GiteaRelease,
+    ) -> Result<Option<GiteaReleaseArtifacts>, FetchReleaseArtifactError> {
+        use FetchReleaseArtifactError as Error;
+
+        {
+            let retry_after = self.0.retry_after.get(release.host.clone());
+            let mut guard = retry_after.lock().unwrap();
+
+            if let Some(retry_after) = *guard {
+                if retry_after.elapsed().is_zero() {
+                    return Err(Error::RateLimit { retry_after });
+                } else {
+                    // Instant retry_after is already reached.
+                    *guard = None;
+                }
+            };
+        }
+
+        if let Some(auth_token) = self
+            .0
+            .auth_token
+            .as_deref()
+            .filter(|_| self.has_auth_token())
+        {
+            match self
+                .do_fetch_release_artifacts(release, Some(auth_token))
+                .await
+            {
+                Err(Error::Unauthorized) => {
+                    if !self.0.auth_token_rejected.swap(true, Relaxed) {
+                        debug!("Gitea token is unauthorized, not using it");
+                    }
+                }
+                res => return res,
+            }
+        }
+
+        self.do_fetch_release_artifacts(release, None).await
+    }
+
+    /// The returned future is guaranteed to be pointer size.
+    pub async fn has_release_artifact(
+        &self,
+        GiteaReleaseArtifact {
+            release,
+            artifact_name,
+        }: GiteaReleaseArtifact,
+    ) -> Result<HasReleaseArtifact, GiteaApiError> {
+        use FetchReleaseArtifactError as Error;
+
+        let once_cell = self.0.release_artifacts.get(release.clone());
+        let res = once_cell
+            .get_or_try_init(|| Box::pin(self.fetch_release_artifacts(&release)))
+            .await;
+
+        match res {
+            Ok(Some(artifacts)) => Ok(if artifacts.contains(&artifact_name) {
+                HasReleaseArtifact::Yes
+            } else {
+                HasReleaseArtifact::No
+            }),
+            Ok(None) => Ok(HasReleaseArtifact::NoSuchRelease),
+            Err(Error::Unauthorized) => Ok(HasReleaseArtifact::Unauthorized),
+            Err(Error::RateLimit { retry_after }) => {
+                *self.0.retry_after.get(release.host).lock().unwrap() = Some(retry_after);
+                Ok(HasReleaseArtifact::RateLimit { retry_after })
+            }
+            Err(Error::Error(err)) => Err(err),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum HasReleaseArtifact {
+    Yes,
+    No,
+    /// The release or the repository does not exist, or the repository is
+    /// private and the token cannot access it.
+    NoSuchRelease,
+    /// Gitea returns 401 requiring a token.
+    /// In this case, it makes sense to fallback to HEAD/GET.
+    Unauthorized,
+
+    /// It is usually more sensible to fallback to directly HEAD/GET the
+    /// artifact url than waiting until `retry_after`.
+    RateLimit {
+        retry_after: Instant,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn try_extract_artifact_from_str(s: &str) -> Option<GiteaReleaseArtifact> {
+        GiteaReleaseArtifact::try_extract_from_url(
+            &url::Url::parse(s).unwrap(),
+            &[CODEBERG_ORG, "gitea.example.com"],
+        )
+    }
+
+    #[test]
+    fn extract_gitea_release_artifacts() {
+        let artifact = |host: &str, tag: &str, artifact_name: &str| GiteaReleaseArtifact {
+            release: GiteaRelease {
+                host: host.into(),
+                owner: "owner".into(),
+                repo: "repo".into(),
+                tag: tag.into(),
+            },
+            artifact_name: artifact_name.into(),
+        };
+
+        assert_eq!(
+            try_extract_artifact_from_str(
+                "https://codeberg.org/owner/repo/releases/download/v1.0.0/tool.tgz"
+            ),
+            Some(artifact(CODEBERG_ORG, "v1.0.0", "tool.tgz"))
+        );
+        assert_eq!(
+            try_extract_artifact_from_str(
+                "https://Gitea.Example.com/owner/repo/releases/download/release%2F1/tool%20x.zip"
+            ),
+            Some(artifact("gitea.example.com", "release/1", "tool x.zip"))
+        );
+
+        for url in [
+            "https://codeberg.org/owner/repo/releases/download/v1.0.0/",
+            "https://codeberg.org/owner/repo/releases/download/v1.0.0",
+            "https://codeberg.org/owner/repo/releases/tag/v1.0.0",
+            "https://codeberg.org/owner/repo/releases/download/v1.0.0/tool.tgz/a",
+            "https://codeberg.org/owner/repo/releases/download/v1.0.0/tool.tgz?a",
+            "https://codeberg.org/owner/repo/releases/download/v1.0.0/tool.tgz#a",
+            "https://github.com/owner/repo/releases/download/v1.0.0/tool.tgz",
+        ] {
+            assert_eq!(try_extract_artifact_from_str(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_with_hosts() {
+        let client = remote::Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            std::num::NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let gitea_api_client = GiteaApiClient::new(client.clone(), None);
+        assert_eq!(gitea_api_client.hosts(), [CODEBERG_ORG]);
+
+        let gitea_api_client =
+            GiteaApiClient::new(client, None).with_hosts(["codeberg.org", " Gitea.Example.com/"]);
+        assert_eq!(
+            gitea_api_client.hosts(),
+            [CODEBERG_ORG, "gitea.example.com"]
+        );
+    }
+
+    #[cfg(feature = "fixture")]
+    fn create_replay_remote_client(name: &str) -> remote::Client {
+        let fixture = remote::Fixture::replay(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(name),
+        )
+        .unwrap();
+
+        remote::Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            std::num::NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap()
+        .with_fixture(fixture)
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gitea_api_client_release() {
+        let client = GiteaApiClient::new(
+            create_replay_remote_client("gitea-api-release"),
+            Some("token".into()),
+        );
+        let has_release_artifact = |tag: &str, artifact_name: &str| {
+            client.has_release_artifact(GiteaReleaseArtifact {
+                release: GiteaRelease {
+                    host: CODEBERG_ORG.into(),
+                    owner: "cargo-bins".into(),
+                    repo: "tool".into(),
+                    tag: tag.into(),
+                },
+                artifact_name: artifact_name.into(),
+            })
+        };
+
+        assert_eq!(
+            has_release_artifact("v1.0.0", "tool-x86_64-unknown-linux-gnu.tgz")
+                .await
+                .unwrap(),
+            HasReleaseArtifact::Yes
+        );
+        assert_eq!(
+            has_release_artifact("v1.0.0", "tool-aarch64-apple-darwin.tgz")
+                .await
+                .unwrap(),
+            HasReleaseArtifact::No
+        );
+        assert_eq!(
+            has_release_artifact("v0.0.0", "tool-x86_64-unknown-linux-gnu.tgz")
+                .await
+                .unwrap(),
+            HasReleaseArtifact::NoSuchRelease
+        );
+        assert!(client.has_auth_token());
+
+        // Rate limited lookups are answered without sending any request.
+        let rate_limited = has_release_artifact("v2.0.0", "tool.tgz").await.unwrap();
+        assert!(
+            matches!(rate_limited, HasReleaseArtifact::RateLimit { retry_after }
+                if retry_after > Instant::now() + Duration::from_secs(30)),
+            "{rate_limited:?}"
+        );
+        assert!(matches!(
+            has_release_artifact("v3.0.0", "tool.tgz").await.unwrap(),
+            HasReleaseArtifact::RateLimit { .. }
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+use std::{collections::HashSet, time::Duration};
+
+use compact_str::CompactString;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use thiserror::Error as ThisError;
+use tracing::debug;
+
+use super::{percent_encode_http_url_path, remote, GiteaRelease};
+
+#[derive(ThisError, Debug)]
+#[non_exhaustive]
+pub enum GiteaApiError {
+    #[error("Remote Error: {0}")]
+    Remote(#[from] remote::Error),
+
+    #[error("Failed to parse url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    #[error("Gitea API returned a malformed response: {0}")]
+    MalformedResponse(#[from] serde_json::Error),
+}
+
+impl GiteaApiError {
+    /// Return `true` if the request may succeed when sent again, see
+    /// [`remote::Error::is_transient`].
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Remote(err) => err.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+/// Names of the artifacts of a release.
+#[derive(Clone, Debug)]
+pub(super) struct GiteaReleaseArtifacts(HashSet<CompactString>);
+
+impl GiteaReleaseArtifacts {
+    pub(super) fn contains(&self, artifact_name: &str) -> bool {
+        self.0.contains(artifact_name)
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum FetchReleaseRet<T = GiteaReleaseArtifacts> {
+    ReachedRateLimit { retry_after: Option<Duration> },
+    ReleaseNotFound,
+    Found(T),
+    Unauthorized,
+}
+
+/// A later `Retry-After` comes from a misconfigured server.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Return the duration from `Retry-After` in seconds, which is what Gitea
+/// and the proxies in front of Codeberg send.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let secs: u64 = headers.get("retry-after")?.to_str().ok()?.parse().ok()?;
+    Some(Duration::from_secs(secs).min(MAX_RETRY_AFTER))
+}
+
+fn check_for_status<T>(
+    status: remote::StatusCode,
+    headers: &HeaderMap,
+) -> Option<FetchReleaseRet<T>> {
+    match status {
+        remote::StatusCode::TOO_MANY_REQUESTS => Some(FetchReleaseRet::ReachedRateLimit {
+            retry_after: retry_after(headers),
+        }),
+
+        remote::StatusCode::UNAUTHORIZED => Some(FetchReleaseRet::Unauthorized),
+        // Private repositories are reported as not found as well.
+        remote::StatusCode::NOT_FOUND => Some(FetchReleaseRet::ReleaseNotFound),
+
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct Release {
+    #[serde(default)]
+    assets: Option<Vec<Asset>>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: CompactString,
+}
+
+fn parse_release(body: &[u8]) -> Result<GiteaReleaseArtifacts, GiteaApiError> {
+    let release: Release = serde_json::from_slice(body)?;
+
+    Ok(GiteaReleaseArtifacts(
+        release
+            .assets
+            .into_iter()
+            .flatten()
+            .map(|asset| asset.name)
+            .collect(),
+    ))
+}
+
+pub(super) async fn fetch_release_artifacts(
+    client: &remote::Client,
+    GiteaRelease {
+        host,
+        owner,
+        repo,
+        tag,
+    }: &GiteaRelease,
+    auth_token: Option<&str>,
+) -> Result<FetchReleaseRet, GiteaApiError> {
+    let url = remote::Url::parse(&format!(
+        "https://{host}/api/v1/repos/{owner}/{repo}/releases/tags/{tag}",
+        owner = percent_encode_http_url_path(owner),
+        repo = percent_encode_http_url_path(repo),
+        tag = percent_encode_http_url_path(tag),
+    ))?;
+
+    let mut request_builder = client.get(url).header("Accept", "application/json");
+
+    if let Some(auth_token) = auth_token {
+        request_builder = request_builder.header("Authorization", &format!("token {auth_token}"));
+    }
+
+    let response = request_builder.send(false).await?;
+
+    if let Some(ret) = check_for_status(response.status(), response.headers()) {
+        return Ok(ret);
+    }
+
+    let body = response.error_for_status()?.bytes().await?;
+    let artifacts = parse_release(&body)?;
+    debug!(
+        "Found {} artifacts in Gitea release {owner}/{repo}@{tag} on {host}",
+        artifacts.0.len(),
+    );
+
+    Ok(FetchReleaseRet::Found(artifacts))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_release() {
+        let body = br#"{
+            "id": 1,
+            "tag_name": "v1.0.0",
+            "draft": false,
+            "prerelease": false,
+            "assets": [
+                {
+                    "id": 10,
+                    "name": "tool-x86_64-unknown-linux-gnu.tgz",
+                    "size": 1024,
+                    "browser_download_url": "https://codeberg.org/o/r/releases/download/v1.0.0/tool-x86_64-unknown-linux-gnu.tgz"
+                },
+                {
+                    "id": 11,
+                    "name": "tool x86_64.zip",
+                    "size": 2048,
+                    "browser_download_url": "https://codeberg.org/o/r/releases/download/v1.0.0/tool%20x86_64.zip"
+                }
+            ]
+        }"#;
+
+        let artifacts = parse_release(body).unwrap();
+        assert!(artifacts.contains("tool-x86_64-unknown-linux-gnu.tgz"));
+        assert!(artifacts.contains("tool x86_64.zip"));
+        assert_eq!(artifacts.0.len(), 2);
+
+        // Older versions of Gitea return `null` for releases without assets.
+        assert_eq!(
+            parse_release(br#"{"tag_name": "v1", "assets": null}"#)
+                .unwrap()
+                .0
+                .len(),
+            0
+        );
+        assert!(parse_release(b"{\"assets\": ").is_err());
+    }
+
+    #[test]
+    fn test_check_for_status() {
+        let retry_after = |status: u16, headers: &[(&'static str, &str)]| {
+            let mut header_map = HeaderMap::new();
+            for (name, value) in headers {
+                header_map.insert(*name, value.parse().unwrap());
+            }
+
+            match check_for_status::<()>(status.try_into().unwrap(), &header_map) {
+                Some(FetchReleaseRet::ReachedRateLimit { retry_after }) => Some(retry_after),
+                _ => None,
+            }
+        };
+
+        assert_eq!(
+            retry_after(429, &[("retry-after", "30")]),
+            Some(Some(Duration::from_secs(30)))
+        );
+        assert_eq!(retry_after(429, &[("retry-after", "soon")]), Some(None));
+        assert_eq!(retry_after(403, &[("retry-after", "30")]), None);
+    }
+}
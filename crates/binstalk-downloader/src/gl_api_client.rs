@@ -0,0 +1,469 @@
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use compact_str::{CompactString, ToCompactString};
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+use crate::{
+    remote,
+    utils::{percent_decode_http_url_path, percent_encode_http_url_path, Map},
+};
+
+mod request;
+pub use request::GlApiError;
+use request::{FetchReleaseRet, GlReleaseArtifacts};
+
+/// Host of gitlab.com, the default host of [`GlApiClient`].
+pub const GITLAB_COM: &str = "gitlab.com";
+
+/// Retry duration if neither `Retry-After` nor `RateLimit-Reset` is sent.
+const DEFAULT_RETRY_DURATION: Duration = Duration::from_secs(60);
+
+/// The release of a GitLab project.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GlRelease {
+    /// Full path of the project including its groups, e.g.
+    /// `group/subgroup/project`.
+    pub project: CompactString,
+    pub tag: CompactString,
+}
+
+/// The GitLab Release and one of its artifact.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GlReleaseArtifact {
+    pub release: GlRelease,
+    /// Filepath of the release link, which may contain `/`.
+    pub artifact_name: CompactString,
+}
+
+/// Parse `{project}/-/releases/{tag}/downloads/{filepath}`.
+fn parse_artifact_path<'a>(
+    path_segments: impl Iterator<Item = &'a str>,
+) -> Option<(GlRelease, CompactString)> {
+    let path_segments: Vec<&str> = path_segments.collect();
+
+    let index = path_segments
+        .windows(2)
+        .position(|segments| segments == ["-", "releases"])?;
+    let (project, rest) = path_segments.split_at(index);
+
+    // Projects are always in a namespace.
+    if project.len() < 2 || project.iter().any(|segment| segment.is_empty()) {
+        return None;
+    }
+
+    let (tag, filepath) = match rest {
+        [_, _, tag, "downloads", filepath @ ..] if !filepath.is_empty() => (tag, filepath),
+        _ => return None,
+    };
+    if tag.is_empty() || filepath.iter().any(|segment| segment.is_empty()) {
+        return None;
+    }
+
+    let decode_path = |segments: &[&str]| {
+        segments
+            .iter()
+            .map(|segment| percent_decode_http_url_path(segment))
+            .collect::<Vec<_>>()
+            .join("/")
+            .to_compact_string()
+    };
+
+    Some((
+        GlRelease {
+            project: decode_path(project),
+            tag: percent_decode_http_url_path(tag),
+        },
+        decode_path(filepath),
+    ))
+}
+
+impl GlReleaseArtifact {
+    /// Create [`GlReleaseArtifact`] from the url of a release link on one of
+    /// `hosts`, e.g. [`GITLAB_COM`] or [`GlApiClient::host`]:
+    /// `https://{host}/{project}/-/releases/{tag}/downloads/{filepath}`.
+    pub fn try_extract_from_url(url: &remote::Url, hosts: &[&str]) -> Option<Self> {
+        let domain = url.domain()?;
+        if !hosts.iter().any(|host| domain.eq_ignore_ascii_case(host)) {
+            return None;
+        }
+
+        if url.fragment().is_some() || url.query().is_some() {
+            return None;
+        }
+
+        let (release, artifact_name) = parse_artifact_path(url.path_segments()?)?;
+        Some(Self {
+            release,
+            artifact_name,
+        })
+    }
+}
+
+/// Token sent to the GitLab API.
+#[derive(Clone, Eq, PartialEq)]
+pub enum GlAuthToken {
+    /// Personal, project or group access token, sent as `PRIVATE-TOKEN`.
+    Private(CompactString),
+    /// Token of a GitLab CI job, sent as `JOB-TOKEN`.
+    Job(CompactString),
+}
+
+impl GlAuthToken {
+    /// Read the token from `GITLAB_TOKEN`, or from `CI_JOB_TOKEN` (set in
+    /// GitLab CI jobs), in this order.
+    pub fn from_env() -> Option<Self> {
+        Self::from_env_vars(|name| env::var(name).ok())
+    }
+
+    fn from_env_vars(get: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        let get = |name| get(name).filter(|token| !token.is_empty());
+
+        get("GITLAB_TOKEN")
+            .map(|token| Self::Private(token.into()))
+            .or_else(|| get("CI_JOB_TOKEN").map(|token| Self::Job(token.into())))
+    }
+}
+
+/// Do not leak the token in logs.
+impl std::fmt::Debug for GlAuthToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Private(_) => f.write_str("Private(..)"),
+            Self::Job(_) => f.write_str("Job(..)"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    client: remote::Client,
+    host: CompactString,
+    release_artifacts: Map<GlRelease, OnceCell<Option<GlReleaseArtifacts>>>,
+    retry_after: Mutex<Option<Instant>>,
+
+    auth_token: Option<GlAuthToken>,
+    /// Set once `auth_token` is unauthorized, requests are then sent
+    /// without it.
+    auth_token_rejected: AtomicBool,
+}
+
+/// GitLab API client for querying whether a release artifact exists, on
+/// gitlab.com or a self-managed instance.
+#[derive(Clone, Debug)]
+pub struct GlApiClient(Arc<Inner>);
+
+impl GlApiClient {
+    pub fn new(client: remote::Client, auth_token: Option<GlAuthToken>) -> Self {
+        Self(Arc::new(Inner {
+            client,
+            host: GITLAB_COM.into(),
+            release_artifacts: Default::default(),
+            retry_after: Default::default(),
+
+            auth_token,
+            auth_token_rejected: AtomicBool::new(false),
+        }))
+    }
+
+    /// Use the API of `host`, e.g. `gitlab.example.com`, instead of the one
+    /// of gitlab.com.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn with_host(mut self, host: &str) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("GlApiClient::with_host must be called before cloning it")
+            .host = host
+            .trim()
+            .trim_end_matches('/')
+            .to_ascii_lowercase()
+            .into();
+        self
+    }
+
+    /// Host of the web interface, of the API and of the release downloads.
+    pub fn host(&self) -> &str {
+        &self.0.host
+    }
+
+    pub fn has_auth_token(&self) -> bool {
+        self.0.auth_token.is_some() && !self.0.auth_token_rejected.load(Relaxed)
+    }
+}
+
+enum FetchReleaseArtifactError {
+    Error(GlApiError),
+    RateLimit { retry_after: Instant },
+    Unauthorized,
+}
+
+impl GlApiClient {
+    async fn do_fetch_release_artifacts(
+        &self,
+        release: &GlRelease,
+        auth_token: Option<&GlAuthToken>,
+    ) -> Result<Option<GlReleaseArtifacts>, FetchReleaseArtifactError> {
+        use FetchReleaseArtifactError as Error;
+
+        match request::fetch_release_artifacts(&self.0.client, &self.0.host, release, auth_token)
+            .await
+        {
+            Ok(FetchReleaseRet::ReleaseNotFound) => Ok(None),
+            Ok(FetchReleaseRet::Found(artifacts)) => Ok(Some(artifacts)),
+            Ok(FetchReleaseRet::ReachedRateLimit { retry_after }) => {
+                let retry_after = retry_after.unwrap_or(DEFAULT_RETRY_DURATION);
+
+                let now = Instant::now();
+                let retry_after = now
+                    .checked_add(retry_after)
+                    .unwrap_or_else(|| now + DEFAULT_RETRY_DURATION);
+
+                Err(Error::RateLimit { retry_after })
+            }
+            Ok(FetchReleaseRet::Unauthorized) => Err(Error::Unauthorized),
+            Err(err) => Err(Error::Error(err)),
+        }
+    }
+
+    /// Fetch with the token, and without it once it is unauthorized.
+    async fn fetch_release_artifacts(
+        &self,
+        release: &GlRelease,
+    ) -> Result<Option<GlReleaseArtifacts>, FetchReleaseArtifactError> {
+        use FetchReleaseArtifactError as Error;
+
+        {
+            let mut guard = self.0.retry_after.lock().unwrap();
+
+            if let Some(retry_after) = *guard {
+                if retry_after.elapsed().is_zero() {
+                    return Err(Error::RateLimit { retry_after });
+                } else {
+                    // Instant retry_after is already reached.
+                    *guard = None;
+                }
+            };
+        }
+
+        if let Some(auth_token) = self.0.auth_token.as_ref().filter(|_| self.has_auth_token()) {
+            match self
+                .do_fetch_release_artifacts(release, Some(auth_token))
+                .await
+            {
+                Err(Error::Unauthorized) => {
+                    if !self.0.auth_token_rejected.swap(true, Relaxed) {
+                        debug!("GitLab token is unauthorized, not using it");
+                    }
+                }
+                res => return res,
+            }
+        }
+
+        self.do_fetch_release_artifacts(release, None).await
+    }
+
+    /// The returned future is guaranteed to be pointer size.
+    pub async fn has_release_artifact(
+        &self,
+        GlReleaseArtifact {
+            release,
+            artifact_name,
+        }: GlReleaseArtifact,
+    ) -> Result<HasReleaseArtifact, GlApiError> {
+        use FetchReleaseArtifactError as Error;
+
+        let once_cell = self.0.release_artifacts.get(release.clone());
+        let res = once_cell
+            .get_or_try_init(|| Box::pin(self.fetch_release_artifacts(&release)))
+            .await;
+
+        match res {
+            Ok(Some(artifacts)) => Ok(if artifacts.contains(&artifact_name) {
+                HasReleaseArtifact::Yes
+            } else {
+                HasReleaseArtifact::No
+            }),
+            Ok(None) => Ok(HasReleaseArtifact::NoSuchRelease),
+            Err(Error::Unauthorized) => Ok(HasReleaseArtifact::Unauthorized),
+            Err(Error::RateLimit { retry_after }) => {
+                *self.0.retry_after.lock().unwrap() = Some(retry_after);
+                Ok(HasReleaseArtifact::RateLimit { retry_after })
+            }
+            Err(Error::Error(err)) => Err(err),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum HasReleaseArtifact {
+    Yes,
+    No,
+    /// The release or the project does not exist, or the project is
+    /// private and the token cannot access it.
+    NoSuchRelease,
+    /// GitLab returns 401 requiring a token.
+    /// In this case, it makes sense to fallback to HEAD/GET.
+    Unauthorized,
+
+    /// GitLab rate limits per minute, it is usually more sensible to
+    /// fallback to directly HEAD/GET the artifact url than waiting until
+    /// `retry_after`.
+    RateLimit {
+        retry_after: Instant,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn try_extract_artifact_from_str(s: &str) -> Option<GlReleaseArtifact> {
+        GlReleaseArtifact::try_extract_from_url(&url::Url::parse(s).unwrap(), &[GITLAB_COM])
+    }
+
+    #[test]
+    fn extract_gl_release_artifacts() {
+        let artifact = |project: &str, tag: &str, artifact_name: &str| GlReleaseArtifact {
+            release: GlRelease {
+                project: project.into(),
+                tag: tag.into(),
+            },
+            artifact_name: artifact_name.into(),
+        };
+
+        assert_eq!(
+            try_extract_artifact_from_str(
+                "https://gitlab.com/group/tool/-/releases/v1.0.0/downloads/tool.tgz"
+            ),
+            Some(artifact("group/tool", "v1.0.0", "tool.tgz"))
+        );
+        assert_eq!(
+            try_extract_artifact_from_str(
+                "https://GitLab.com/group/sub%20group/tool/-/releases/release%2F1.0/downloads/bin/tool%20x.zip"
+            ),
+            Some(artifact("group/sub group/tool", "release/1.0", "bin/tool x.zip"))
+        );
+
+        for url in [
+            "https://gitlab.com/group/tool/-/releases/v1.0.0/downloads/",
+            "https://gitlab.com/group/tool/-/releases/v1.0.0/downloads",
+            "https://gitlab.com/group/tool/-/releases/v1.0.0/evidences/1",
+            "https://gitlab.com/group/tool/-/releases/v1.0.0",
+            "https://gitlab.com/tool/-/releases/v1.0.0/downloads/tool.tgz",
+            "https://gitlab.com/group/tool/-/releases/v1.0.0/downloads/tool.tgz?a",
+            "https://gitlab.com/group/tool/-/releases/v1.0.0/downloads/tool.tgz#a",
+            "https://gitlab.com/group/tool/releases/download/v1.0.0/tool.tgz",
+            "https://gitlab.example.com/group/tool/-/releases/v1.0.0/downloads/tool.tgz",
+        ] {
+            assert_eq!(try_extract_artifact_from_str(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_auth_token_from_env() {
+        let from_env = |vars: &[(&str, &str)]| {
+            GlAuthToken::from_env_vars(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+
+        assert_eq!(from_env(&[]), None);
+        assert_eq!(from_env(&[("GITLAB_TOKEN", "")]), None);
+        assert_eq!(
+            from_env(&[("CI_JOB_TOKEN", "job"), ("GITLAB_TOKEN", "glpat")]),
+            Some(GlAuthToken::Private("glpat".into()))
+        );
+        assert_eq!(
+            from_env(&[("CI_JOB_TOKEN", "job")]),
+            Some(GlAuthToken::Job("job".into()))
+        );
+    }
+
+    #[cfg(feature = "fixture")]
+    fn create_replay_remote_client(name: &str) -> remote::Client {
+        let fixture = remote::Fixture::replay(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(name),
+        )
+        .unwrap();
+
+        remote::Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            std::num::NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap()
+        .with_fixture(fixture)
+    }
+
+    #[cfg(feature = "fixture")]
+    #[tokio::test]
+    async fn test_gl_api_client_release() {
+        let client = GlApiClient::new(
+            create_replay_remote_client("gl-api-release"),
+            Some(GlAuthToken::Private("glpat".into())),
+        );
+        let has_release_artifact = |tag: &str, artifact_name: &str| {
+            client.has_release_artifact(GlReleaseArtifact {
+                release: GlRelease {
+                    project: "cargo-bins/sub/tool".into(),
+                    tag: tag.into(),
+                },
+                artifact_name: artifact_name.into(),
+            })
+        };
+
+        // The token is rejected, so the release is fetched without it.
+        assert_eq!(
+            has_release_artifact("v1.0.0", "tool-x86_64-unknown-linux-gnu.tgz")
+                .await
+                .unwrap(),
+            HasReleaseArtifact::Yes
+        );
+        assert!(!client.has_auth_token());
+        assert_eq!(
+            has_release_artifact("v1.0.0", "bin/tool-x86_64-pc-windows-msvc.zip")
+                .await
+                .unwrap(),
+            HasReleaseArtifact::Yes
+        );
+        assert_eq!(
+            has_release_artifact("v1.0.0", "tool-aarch64-apple-darwin.tgz")
+                .await
+                .unwrap(),
+            HasReleaseArtifact::No
+        );
+        assert_eq!(
+            has_release_artifact("v0.0.0", "tool-x86_64-unknown-linux-gnu.tgz")
+                .await
+                .unwrap(),
+            HasReleaseArtifact::NoSuchRelease
+        );
+
+        // Rate limited lookups are answered without sending any request.
+        let rate_limited = has_release_artifact("v2.0.0", "tool.tgz").await.unwrap();
+        assert!(
+            matches!(rate_limited, HasReleaseArtifact::RateLimit { retry_after }
+                if retry_after > Instant::now() + Duration::from_secs(30)),
+            "{rate_limited:?}"
+        );
+        assert!(matches!(
+            has_release_artifact("v3.0.0", "tool.tgz").await.unwrap(),
+            HasReleaseArtifact::RateLimit { .. }
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime},
+};
+
+use compact_str::CompactString;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use thiserror::Error as ThisError;
+use tracing::debug;
+
+use super::{parse_artifact_path, percent_encode_http_url_path, remote, GlAuthToken, GlRelease};
+
+#[derive(ThisError, Debug)]
+#[non_exhaustive]
+pub enum GlApiError {
+    #[error("Remote Error: {0}")]
+    Remote(#[from] remote::Error),
+
+    #[error("Failed to parse url: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+
+    #[error("GitLab API returned a malformed response: {0}")]
+    MalformedResponse(#[from] serde_json::Error),
+}
+
+impl GlApiError {
+    /// Return `true` if the request may succeed when sent again, see
+    /// [`remote::Error::is_transient`].
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Remote(err) => err.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+/// Paths of the artifacts of a release, relative to its downloads, e.g.
+/// `bin/tool-x86_64-unknown-linux-gnu.tgz`.
+#[derive(Clone, Debug)]
+pub(super) struct GlReleaseArtifacts(HashSet<CompactString>);
+
+impl GlReleaseArtifacts {
+    pub(super) fn contains(&self, artifact_name: &str) -> bool {
+        self.0.contains(artifact_name)
+    }
+}
+
+#[derive(Debug)]
+pub(super) enum FetchReleaseRet<T = GlReleaseArtifacts> {
+    ReachedRateLimit { retry_after: Option<Duration> },
+    ReleaseNotFound,
+    Found(T),
+    Unauthorized,
+}
+
+/// GitLab.com resets the rate limits every minute, a much later reset
+/// comes from a wrong system clock.
+const MAX_RATE_LIMIT_RESET: Duration = Duration::from_secs(60 * 60);
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+/// Return the duration until the rate limit resets, from `Retry-After` in
+/// seconds or from `RateLimit-Reset` in seconds since the epoch.
+fn rate_limit_reset(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(secs) = header_value(headers, "retry-after") {
+        return Some(Duration::from_secs(secs).min(MAX_RATE_LIMIT_RESET));
+    }
+
+    let secs = header_value(headers, "ratelimit-reset")?;
+    let reset = SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))?;
+    Some(remote::delay_until(reset, headers, MAX_RATE_LIMIT_RESET))
+}
+
+fn check_for_status<T>(
+    status: remote::StatusCode,
+    headers: &HeaderMap,
+) -> Option<FetchReleaseRet<T>> {
+    let rate_limited = status == remote::StatusCode::TOO_MANY_REQUESTS
+        || (!status.is_success()
+            && headers
+                .get("ratelimit-remaining")
+                .is_some_and(|val| val == "0"));
+
+    match status {
+        _ if rate_limited => Some(FetchReleaseRet::ReachedRateLimit {
+            retry_after: rate_limit_reset(headers),
+        }),
+
+        remote::StatusCode::UNAUTHORIZED => Some(FetchReleaseRet::Unauthorized),
+        // Private projects are reported as not found as well.
+        remote::StatusCode::NOT_FOUND => Some(FetchReleaseRet::ReleaseNotFound),
+
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct Release {
+    assets: Assets,
+}
+
+#[derive(Deserialize)]
+struct Assets {
+    #[serde(default)]
+    links: Vec<Link>,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    /// Url of the link under the downloads of the release, the same as the
+    /// url of the link unless it has a filepath.
+    direct_asset_url: Option<remote::Url>,
+}
+
+/// Return the filepaths of the links of `release` in the json `body`, the
+/// links without a filepath cannot be downloaded from the release so they
+/// are skipped.
+fn parse_release(body: &[u8]) -> Result<GlReleaseArtifacts, GlApiError> {
+    let release: Release = serde_json::from_slice(body)?;
+
+    Ok(GlReleaseArtifacts(
+        release
+            .assets
+            .links
+            .into_iter()
+            .filter_map(|link| {
+                let url = link.direct_asset_url?;
+                let (_, artifact_name) = parse_artifact_path(url.path_segments()?)?;
+                Some(artifact_name)
+            })
+            .collect(),
+    ))
+}
+
+pub(super) async fn fetch_release_artifacts(
+    client: &remote::Client,
+    host: &str,
+    GlRelease { project, tag }: &GlRelease,
+    auth_token: Option<&GlAuthToken>,
+) -> Result<FetchReleaseRet, GlApiError> {
+    // The project can be referred to by its url-encoded path instead of
+    // its numeric id.
+    let url = remote::Url::parse(&format!(
+        "https://{host}/api/v4/projects/{project}/releases/{tag}",
+        project = percent_encode_http_url_path(project),
+        tag = percent_encode_http_url_path(tag),
+    ))?;
+
+    let mut request_builder = client.get(url).header("Accept", "application/json");
+
+    match auth_token {
+        Some(GlAuthToken::Private(token)) => {
+            request_builder = request_builder.header("PRIVATE-TOKEN", token.as_str());
+        }
+        Some(GlAuthToken::Job(token)) => {
+            request_builder = request_builder.header("JOB-TOKEN", token.as_str());
+        }
+        None => (),
+    }
+
+    let response = request_builder.send(false).await?;
+
+    if let Some(ret) = check_for_status(response.status(), response.headers()) {
+        return Ok(ret);
+    }
+
+    let body = response.error_for_status()?.bytes().await?;
+    let artifacts = parse_release(&body)?;
+    debug!(
+        "Found {} artifacts in GitLab release {}@{}",
+        artifacts.0.len(),
+        project,
+        tag
+    );
+
+    Ok(FetchReleaseRet::Found(artifacts))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_release() {
+        let body = br#"{
+            "tag_name": "v1.0.0",
+            "assets": {
+                "count": 4,
+                "sources": [],
+                "links": [
+                    {
+                        "name": "linux",
+                        "url": "https://gitlab.com/group/tool/-/package_files/1/download",
+                        "direct_asset_url": "https://gitlab.com/group/tool/-/releases/v1.0.0/downloads/tool-x86_64-unknown-linux-gnu.tgz"
+                    },
+                    {
+                        "name": "nested",
+                        "url": "https://example.com/tool.zip",
+                        "direct_asset_url": "https://gitlab.com/group/tool/-/releases/v1.0.0/downloads/bin/tool%20x86_64.zip"
+                    },
+                    {
+                        "name": "external",
+                        "url": "https://example.com/tool.tgz",
+                        "direct_asset_url": "https://example.com/tool.tgz"
+                    }
+                ]
+            }
+        }"#;
+
+        let artifacts = parse_release(body).unwrap();
+        assert!(artifacts.contains("tool-x86_64-unknown-linux-gnu.tgz"));
+        assert!(artifacts.contains("bin/tool x86_64.zip"));
+        assert!(!artifacts.contains("tool.tgz"));
+        assert_eq!(artifacts.0.len(), 2);
+
+        assert!(parse_release(b"{\"assets\": ").is_err());
+    }
+
+    #[test]
+    fn test_check_for_status() {
+        let retry_after = |status: u16, headers: &[(&'static str, &str)]| {
+            let mut header_map = HeaderMap::new();
+            for (name, value) in headers {
+                header_map.insert(*name, value.parse().unwrap());
+            }
+
+            match check_for_status::<()>(status.try_into().unwrap(), &header_map) {
+                Some(FetchReleaseRet::ReachedRateLimit { retry_after }) => Some(retry_after),
+                _ => None,
+            }
+        };
+
+        assert_eq!(
+            retry_after(429, &[("retry-after", "60")]),
+            Some(Some(Duration::from_secs(60)))
+        );
+        assert_eq!(
+            retry_after(403, &[("ratelimit-remaining", "0"), ("retry-after", "5")]),
+            Some(Some(Duration::from_secs(5)))
+        );
+        assert_eq!(retry_after(429, &[]), Some(None));
+        assert_eq!(retry_after(403, &[("ratelimit-remaining", "1")]), None);
+        assert_eq!(retry_after(200, &[("ratelimit-remaining", "0")]), None);
+    }
+}
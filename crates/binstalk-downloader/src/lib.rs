@@ -10,6 +10,14 @@ pub mod download;
 #[cfg(feature = "gh-api-client")]
 pub mod gh_api_client;
 
+/// GitLab API client, for gitlab.com and self-managed GitLab instances.
+#[cfg(feature = "gl-api-client")]
+pub mod gl_api_client;
+
+/// Gitea API client, for codeberg.org and other Gitea and Forgejo instances.
+#[cfg(feature = "gitea-api-client")]
+pub mod gitea_api_client;
+
 pub mod remote;
 
 mod utils;
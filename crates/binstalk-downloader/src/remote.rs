@@ -1,7 +1,13 @@
 use std::{
-    num::{NonZeroU16, NonZeroU64, NonZeroU8},
+    error::Error as StdError,
+    future::Future,
+    io,
+    num::{NonZeroU16, NonZeroU64},
     ops::ControlFlow,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
     time::{Duration, SystemTime},
 };
 
@@ -15,6 +21,10 @@ use reqwest::{
 use thiserror::Error as ThisError;
 use tracing::{debug, info, instrument};
 
+use crate::download::DEFAULT_EXPANSION_FACTOR;
+
+pub use tokio_util::sync::CancellationToken;
+
 pub use reqwest::{header, Error as ReqwestError, Method, StatusCode};
 pub use url::Url;
 
@@ -30,6 +40,31 @@ pub use request_builder::{Body, RequestBuilder, Response};
 mod tls_version;
 pub use tls_version::TLSVersion;
 
+mod host_limit;
+pub use host_limit::{HostLimit, HostLimits};
+
+mod geo_block;
+pub use geo_block::GeoBlocked;
+use geo_block::{is_geo_blocked, might_be_geo_blocked, MAX_BODY_LEN};
+
+mod clock_skew;
+pub(crate) use clock_skew::delay_until;
+pub use clock_skew::CertificateNotYetValid;
+use clock_skew::{is_cert_not_yet_valid, warn_clock_skew};
+
+#[cfg(feature = "fixture")]
+mod fixture;
+#[cfg(feature = "fixture")]
+pub use fixture::{Fixture, FixtureError};
+
+mod spki_pins;
+use spki_pins::find_spki_pin_mismatch;
+pub use spki_pins::{InvalidSpkiPin, SpkiPinMismatch, SpkiPins};
+
+#[cfg(any(all(test, feature = "rustls"), feature = "test-server"))]
+#[doc(hidden)]
+pub mod test_server;
+
 #[cfg(feature = "hickory-dns")]
 mod resolver;
 #[cfg(feature = "hickory-dns")]
@@ -38,10 +73,40 @@ use resolver::TrustDnsResolver;
 #[cfg(feature = "json")]
 pub use request_builder::JsonError;
 
+tokio::task_local! {
+    static MAX_REDIRECTS: usize;
+}
+
+/// Run `fut`, following at most `max` redirections for each of the requests
+/// it sends, instead of the default of the [`Client`].
+pub async fn with_max_redirects<F: Future>(max: usize, fut: F) -> F::Output {
+    MAX_REDIRECTS.scope(max, fut).await
+}
+
+#[derive(Debug, ThisError)]
+#[error("Too many redirections, at most {0} are followed")]
+struct TooManyRedirects(usize);
+
+fn redirect_policy() -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(|attempt| {
+        let max = MAX_REDIRECTS
+            .try_with(|max| *max)
+            .unwrap_or(DEFAULT_MAX_REDIRECTS);
+
+        if attempt.previous().len() > max {
+            attempt.error(TooManyRedirects(max))
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
 const MAX_RETRY_DURATION: Duration = Duration::from_secs(120);
-const MAX_RETRY_COUNT: u8 = 3;
+const DEFAULT_RETRIES: u8 = 2;
 const DEFAULT_RETRY_DURATION_FOR_RATE_LIMIT: Duration = Duration::from_millis(200);
 const RETRY_DURATION_FOR_TIMEOUT: Duration = Duration::from_millis(200);
+/// Same as the default policy of reqwest.
+const DEFAULT_MAX_REDIRECTS: usize = 10;
 #[allow(dead_code)]
 const DEFAULT_MIN_TLS: TLSVersion = TLSVersion::TLS_1_2;
 
@@ -54,11 +119,101 @@ pub enum Error {
     #[error(transparent)]
     Http(Box<HttpError>),
 
+    #[error(transparent)]
+    SpkiPinMismatch(Box<SpkiPinMismatch>),
+
+    #[error(transparent)]
+    GeoBlocked(Box<GeoBlocked>),
+
+    #[error(transparent)]
+    CertificateNotYetValid(Box<CertificateNotYetValid>),
+
+    #[error("Failed to parse PEM encoded certificates: {0}")]
+    InvalidPem(io::Error),
+
+    #[error("Refusing to send a request to {0} in offline mode")]
+    Offline(Box<Url>),
+
+    /// The [`CancellationToken`] of the client is cancelled.
+    #[error("The request is cancelled")]
+    Cancelled,
+
+    #[cfg(feature = "fixture")]
+    #[error(transparent)]
+    Fixture(Box<FixtureError>),
+
     #[cfg(feature = "json")]
     #[error("Failed to parse http response body as Json: {0}")]
     Json(#[from] JsonError),
 }
 
+impl Error {
+    /// Return `true` if the request may succeed when sent again, i.e. on a
+    /// 5xx status code, a timeout or a connection reset.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Reqwest(err) => is_transient(err),
+            Error::Http(http_error) => is_transient(&http_error.err),
+            _ => false,
+        }
+    }
+
+    /// Return the status of the response if the request failed because of
+    /// it.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Reqwest(err) => err.status(),
+            Error::Http(http_error) => http_error.err.status(),
+            _ => None,
+        }
+    }
+
+    /// Return `true` if the server refused the request because too many
+    /// are sent, i.e. on a 429 status code.
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+    }
+
+    /// Return `true` if the request failed because of the network rather
+    /// than the server rejecting it: a [transient](Self::is_transient)
+    /// failure, a geo-block or a certificate which cannot be trusted.
+    pub fn is_network(&self) -> bool {
+        match self {
+            Error::SpkiPinMismatch(_) | Error::GeoBlocked(_) | Error::CertificateNotYetValid(_) => {
+                true
+            }
+            err => err.is_transient(),
+        }
+    }
+}
+
+fn is_transient(err: &reqwest::Error) -> bool {
+    if let Some(status) = err.status() {
+        return status.is_server_error();
+    }
+
+    if err.is_timeout() || err.is_connect() {
+        return true;
+    }
+
+    let mut source = err.source();
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<io::Error>() {
+            return matches!(
+                io_err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::TimedOut
+            );
+        }
+        source = err.source();
+    }
+
+    false
+}
+
 #[derive(Debug, ThisError)]
 #[error("could not {method} {url}: {err}")]
 pub struct HttpError {
@@ -79,6 +234,15 @@ impl HttpError {
 struct Inner {
     client: reqwest::Client,
     service: DelayRequest,
+    #[cfg(feature = "fixture")]
+    fixture: Option<Fixture>,
+    /// Number of bytes of response bodies received.
+    bytes_downloaded: AtomicU64,
+    offline: bool,
+    timeout: Option<Duration>,
+    retries: u8,
+    cancellation_token: CancellationToken,
+    space_expansion_factor: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -92,6 +256,11 @@ impl Client {
     /// * `num_request` - maximum number of requests to be processed for
     ///   each `per` duration.
     ///
+    /// * `spki_pins` - only enforced with feature `rustls`.
+    ///
+    /// * `proxy` - proxy for all requests, instead of the ones set in the
+    ///   environment, e.g. with `HTTPS_PROXY`.
+    ///
     /// The Client created would use at least tls 1.2
     pub fn new(
         user_agent: impl AsRef<str>,
@@ -99,6 +268,8 @@ impl Client {
         per_millis: NonZeroU16,
         num_request: NonZeroU64,
         certificates: impl IntoIterator<Item = Certificate>,
+        spki_pins: SpkiPins,
+        proxy: Option<Url>,
     ) -> Result<Self, Error> {
         fn inner(
             user_agent: &str,
@@ -106,12 +277,19 @@ impl Client {
             per_millis: NonZeroU16,
             num_request: NonZeroU64,
             certificates: &mut dyn Iterator<Item = Certificate>,
+            spki_pins: SpkiPins,
+            proxy: Option<Url>,
         ) -> Result<Client, Error> {
             let mut builder = reqwest::ClientBuilder::new()
                 .user_agent(user_agent)
                 .https_only(true)
+                .redirect(redirect_policy())
                 .tcp_nodelay(false);
 
+            if let Some(proxy) = proxy {
+                builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+            }
+
             #[cfg(feature = "hickory-dns")]
             {
                 builder = builder.dns_resolver(Arc::new(TrustDnsResolver::default()));
@@ -125,9 +303,29 @@ impl Client {
 
                 builder = builder.min_tls_version(tls_ver.into());
 
+                #[cfg(feature = "rustls")]
+                let mut certificates_der = Vec::new();
+
                 for certificate in certificates {
                     builder = builder.add_root_certificate(certificate.0);
+
+                    #[cfg(feature = "rustls")]
+                    certificates_der.extend(certificate.1);
                 }
+
+                #[cfg(feature = "rustls")]
+                if !spki_pins.is_empty() {
+                    builder = builder.use_preconfigured_tls(spki_pins::tls_config(
+                        tls_ver,
+                        &certificates_der,
+                        spki_pins,
+                    ));
+                }
+            }
+
+            #[cfg(not(feature = "rustls"))]
+            if !spki_pins.is_empty() {
+                tracing::warn!("SPKI pins are only supported with rustls and are ignored");
             }
 
             let client = builder.build()?;
@@ -139,6 +337,14 @@ impl Client {
                     Duration::from_millis(per_millis.get() as u64),
                     client,
                 ),
+                #[cfg(feature = "fixture")]
+                fixture: None,
+                bytes_downloaded: AtomicU64::new(0),
+                offline: false,
+                timeout: None,
+                retries: DEFAULT_RETRIES,
+                cancellation_token: CancellationToken::new(),
+                space_expansion_factor: DEFAULT_EXPANSION_FACTOR,
             })))
         }
 
@@ -148,14 +354,119 @@ impl Client {
             per_millis,
             num_request,
             &mut certificates.into_iter(),
+            spki_pins,
+            proxy,
         )
     }
 
+    /// Record the responses to `fixture`, or replay them from it.
+    ///
+    /// Must be called before the client is cloned.
+    #[cfg(feature = "fixture")]
+    pub fn with_fixture(mut self, fixture: Fixture) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("Client::with_fixture must be called before cloning it")
+            .fixture = Some(fixture);
+        self
+    }
+
+    /// Refuse to send any request, failing with [`Error::Offline`].
+    ///
+    /// Must be called before the client is cloned.
+    pub fn offline(mut self, offline: bool) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("Client::offline must be called before cloning it")
+            .offline = offline;
+        self
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.0.offline
+    }
+
+    /// Limit the requests sent to each host, [`HostLimits::default`] by
+    /// default. The limits are shared by all the clones of the client.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn host_limits(mut self, host_limits: HostLimits) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("Client::host_limits must be called before cloning it")
+            .service
+            .set_host_limits(host_limits);
+        self
+    }
+
+    /// Fail the requests which take longer than `timeout`, from connecting
+    /// to receiving the whole body, with a [transient](Error::is_transient)
+    /// error.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("Client::timeout must be called before cloning it")
+            .timeout = timeout;
+        self
+    }
+
+    /// Send the requests failing with a timeout, a connection error or a
+    /// 408, 429, 503 or 504 status code again up to `retries` times, 2 by
+    /// default.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn retries(mut self, retries: u8) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("Client::retries must be called before cloning it")
+            .retries = retries;
+        self
+    }
+
+    /// Fail the requests in flight and the ones sent afterwards with
+    /// [`Error::Cancelled`] once `token` is cancelled, along with the
+    /// [`Download`](crate::download::Download)s from this client.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("Client::cancellation_token must be called before cloning it")
+            .cancellation_token = token;
+        self
+    }
+
+    pub fn get_cancellation_token(&self) -> &CancellationToken {
+        &self.0.cancellation_token
+    }
+
+    /// Before downloading a compressed package from this client, check
+    /// that it fits once extracted assuming that it expands by `factor`,
+    /// [`DEFAULT_EXPANSION_FACTOR`] by default.
+    ///
+    /// Must be called before the client is cloned.
+    pub fn space_expansion_factor(mut self, factor: f64) -> Self {
+        Arc::get_mut(&mut self.0)
+            .expect("Client::space_expansion_factor must be called before cloning it")
+            .space_expansion_factor = factor;
+        self
+    }
+
+    pub fn get_space_expansion_factor(&self) -> f64 {
+        self.0.space_expansion_factor
+    }
+
     /// Return inner reqwest client.
     pub fn get_inner(&self) -> &reqwest::Client {
         &self.0.client
     }
 
+    /// Number of bytes of response bodies received by this client and its
+    /// clones so far.
+    pub fn bytes_downloaded(&self) -> u64 {
+        self.0.bytes_downloaded.load(Relaxed)
+    }
+
+    fn add_bytes_downloaded(&self, len: usize) {
+        self.0.bytes_downloaded.fetch_add(len as u64, Relaxed);
+    }
+
     /// Return `Err(_)` for fatal error tht cannot be retried.
     ///
     /// Return `Ok(ControlFlow::Continue(res))` for retryable error, `res`
@@ -165,7 +476,7 @@ impl Client {
     ///
     /// Return `Ok(ControlFlow::Break(response))` when succeeds and no need
     /// to retry.
-    #[instrument]
+    #[instrument(skip_all, fields(method = %request.method(), url = %url))]
     async fn do_send_request(
         &self,
         request: Request,
@@ -173,7 +484,11 @@ impl Client {
     ) -> Result<ControlFlow<reqwest::Response, Result<reqwest::Response, ReqwestError>>, ReqwestError>
     {
         let response = match self.0.service.call(request).await {
-            Err(err) if err.is_timeout() || err.is_connect() => {
+            Err(err)
+                if (err.is_timeout() || err.is_connect())
+                    && find_spki_pin_mismatch(&err).is_none()
+                    && !is_cert_not_yet_valid(&err) =>
+            {
                 let duration = RETRY_DURATION_FOR_TIMEOUT;
 
                 info!("Received timeout error from reqwest. Delay future request by {duration:#?}");
@@ -221,23 +536,18 @@ impl Client {
         &self,
         request: &Request,
     ) -> Result<reqwest::Response, ReqwestError> {
-        let mut count = 0;
-        let max_retry_count = NonZeroU8::new(MAX_RETRY_COUNT).unwrap();
+        let mut retries = 0;
 
-        // Since max_retry_count is non-zero, there is at least one iteration.
         loop {
-            // Increment the counter before checking for terminal condition.
-            count += 1;
-
             match self
                 .do_send_request(request.try_clone().unwrap(), request.url())
                 .await?
             {
                 ControlFlow::Break(response) => break Ok(response),
-                ControlFlow::Continue(res) if count >= max_retry_count.get() => {
+                ControlFlow::Continue(res) if retries >= self.0.retries => {
                     break res;
                 }
-                _ => (),
+                ControlFlow::Continue(_) => retries += 1,
             }
         }
     }
@@ -248,24 +558,79 @@ impl Client {
         request: Request,
         error_for_status: bool,
     ) -> Result<reqwest::Response, Error> {
+        tokio::select! {
+            biased;
+
+            () = self.0.cancellation_token.cancelled() => Err(Error::Cancelled),
+            res = self.send_request_uncancelled(request, error_for_status) => res,
+        }
+    }
+
+    async fn send_request_uncancelled(
+        &self,
+        mut request: Request,
+        error_for_status: bool,
+    ) -> Result<reqwest::Response, Error> {
+        if self.0.offline {
+            return Err(Error::Offline(Box::new(request.url().clone())));
+        }
+
+        if let (None, Some(timeout)) = (request.timeout(), self.0.timeout) {
+            *request.timeout_mut() = Some(timeout);
+        }
+
         debug!("Downloading from: '{}'", request.url());
 
-        self.send_request_inner(&request)
-            .await
-            .and_then(|response| {
-                if error_for_status {
-                    response.error_for_status()
-                } else {
-                    Ok(response)
-                }
-            })
-            .map_err(|err| {
-                Error::Http(Box::new(HttpError {
-                    method: request.method().clone(),
+        let to_error = |err: ReqwestError| {
+            if let Some(mismatch) = find_spki_pin_mismatch(&err) {
+                return Error::SpkiPinMismatch(Box::new(mismatch.clone()));
+            }
+
+            if is_cert_not_yet_valid(&err) {
+                let local_time = SystemTime::now();
+                warn_clock_skew(local_time, None);
+
+                return Error::CertificateNotYetValid(Box::new(CertificateNotYetValid {
                     url: request.url().clone(),
-                    err,
-                }))
-            })
+                    local_time,
+                }));
+            }
+
+            Error::Http(Box::new(HttpError {
+                method: request.method().clone(),
+                url: request.url().clone(),
+                err,
+            }))
+        };
+
+        #[cfg(feature = "fixture")]
+        let response = match &self.0.fixture {
+            Some(fixture) => match fixture.replay_response(&request) {
+                Some(res) => res.map_err(|err| Error::Fixture(Box::new(err)))?,
+                None => {
+                    let response = self.send_request_inner(&request).await.map_err(to_error)?;
+                    fixture.record_response(&request, response).await?
+                }
+            },
+            None => self.send_request_inner(&request).await.map_err(to_error)?,
+        };
+        #[cfg(not(feature = "fixture"))]
+        let response = self.send_request_inner(&request).await.map_err(to_error)?;
+
+        if !error_for_status {
+            return Ok(response);
+        }
+
+        match response.error_for_status_ref() {
+            Ok(_) => Ok(response),
+            Err(err) if might_be_geo_blocked(response.status()) => {
+                match check_geo_blocked(request.method(), response).await {
+                    Some(geo_blocked) => Err(Error::GeoBlocked(Box::new(geo_blocked))),
+                    None => Err(to_error(err)),
+                }
+            }
+            Err(err) => Err(to_error(err)),
+        }
     }
 
     async fn head_or_fallback_to_get(
@@ -279,9 +644,8 @@ impl Client {
 
         let retry_with_get = move || async move {
             // Retry using GET
-            info!("HEAD on {url} is not allowed, fallback to GET");
-            self.send_request(Request::new(Method::GET, url), error_for_status)
-                .await
+            debug!("HEAD on {url} is not allowed, fallback to a ranged GET");
+            self.send_request(ranged_get(url), error_for_status).await
         };
 
         let is_retryable = |status| {
@@ -292,7 +656,8 @@ impl Client {
                     | StatusCode::FORBIDDEN          // 403
                     | StatusCode::NOT_FOUND          // 404
                     | StatusCode::METHOD_NOT_ALLOWED // 405
-                    | StatusCode::GONE // 410
+                    | StatusCode::GONE               // 410
+                    | StatusCode::NOT_IMPLEMENTED // 501
             )
         };
 
@@ -307,13 +672,53 @@ impl Client {
         }
     }
 
-    /// Check if remote exists using `Method::GET`.
+    /// Check if remote exists using a `Method::GET` for its first byte,
+    /// since some hosts reject `Method::HEAD` for files that exist.
+    ///
+    /// Return [`Error::GeoBlocked`] if the host refuses the request because
+    /// of the region or network it is sent from.
     pub async fn remote_gettable(&self, url: Url) -> Result<bool, Error> {
-        Ok(self.get(url).send(false).await?.status().is_success())
+        Ok(self.remote_gettable_size(url).await?.is_some())
+    }
+
+    /// Like [`Client::remote_gettable`], but return `None` if the remote
+    /// does not exist, and its size otherwise, if the server tells it.
+    pub async fn remote_gettable_size(&self, url: Url) -> Result<Option<Option<u64>>, Error> {
+        let response = self
+            .get(url)
+            .header(header::RANGE.as_str(), FIRST_BYTE)
+            .send(false)
+            .await?;
+        let status = response.status();
+
+        if might_be_geo_blocked(status) {
+            return match check_geo_blocked(&Method::GET, response.inner).await {
+                Some(geo_blocked) => Err(Error::GeoBlocked(Box::new(geo_blocked))),
+                None => Ok(None),
+            };
+        }
+
+        if !status.is_success() {
+            return Ok(None);
+        }
+
+        // The size is only in `Content-Range` if the range is honoured.
+        let size = if status == StatusCode::PARTIAL_CONTENT {
+            response
+                .headers()
+                .get(header::CONTENT_RANGE)
+                .and_then(|range| range.to_str().ok())
+                .and_then(|range| range.rsplit_once('/'))
+                .and_then(|(_, size)| size.trim().parse().ok())
+        } else {
+            response.content_length()
+        };
+
+        Ok(Some(size))
     }
 
     /// Attempt to get final redirected url using `Method::HEAD` or fallback
-    /// to `Method::GET`.
+    /// to a `Method::GET` for the first byte.
     pub async fn get_redirected_final_url(&self, url: Url) -> Result<Url, Error> {
         self.head_or_fallback_to_get(url, true)
             .await
@@ -348,29 +753,268 @@ impl Client {
     }
 }
 
-fn parse_header_retry_after(headers: &HeaderMap) -> Option<Duration> {
+/// Value of the `Range` header requesting only the first byte, which is
+/// answered with `206 Partial Content` or with `200 OK` and the whole body
+/// by servers not supporting ranges.
+///
+/// The body is never read by the existence checks, so it is not downloaded
+/// past what is already buffered by the connection either way.
+const FIRST_BYTE: &str = "bytes=0-0";
+
+/// Create a `Method::GET` request for the first byte of `url`.
+fn ranged_get(url: Url) -> Request {
+    let mut request = Request::new(Method::GET, url);
+    request
+        .headers_mut()
+        .insert(header::RANGE, header::HeaderValue::from_static(FIRST_BYTE));
+    request
+}
+
+/// Return `Some` if `response` is a geo-block, looking at the start of its
+/// body.
+async fn check_geo_blocked(method: &Method, mut response: reqwest::Response) -> Option<GeoBlocked> {
+    let status = response.status();
+    let url = response.url().clone();
+
+    let mut body = Vec::new();
+    while body.len() < MAX_BODY_LEN {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+
+    is_geo_blocked(status, &body).then(|| GeoBlocked {
+        method: method.clone(),
+        url,
+        status,
+    })
+}
+
+/// Return the duration to wait from `Retry-After`, either in seconds or an
+/// HTTP date.
+pub(crate) fn parse_header_retry_after(headers: &HeaderMap) -> Option<Duration> {
     let header = headers
         .get_all(RETRY_AFTER)
         .into_iter()
-        .last()?
+        .next_back()?
         .to_str()
         .ok()?;
 
     match header.parse::<u64>() {
         Ok(dur) => Some(Duration::from_secs(dur)),
         Err(_) => {
-            let system_time = parse_http_date(header).ok()?;
+            let retry_after = parse_http_date(header).ok()?;
 
-            let retry_after_unix_timestamp =
-                system_time.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+            Some(delay_until(retry_after, headers, MAX_RETRY_DURATION))
+        }
+    }
+}
 
-            let curr_time_unix_timestamp = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .expect("SystemTime before UNIX EPOCH!");
+#[cfg(all(test, feature = "rustls"))]
+mod test {
+    use std::sync::atomic::AtomicBool;
+
+    use rustls::pki_types::CertificateDer;
+
+    use super::*;
+    use test_server::spawn_server;
+
+    /// Reject `HEAD` and answer the ranged `GET` sent on fallback with a
+    /// partial response, anything else is a bad request.
+    fn respond(request: &str, head_status: &'static str) -> &'static str {
+        if request.starts_with("HEAD ") {
+            head_status
+        } else if request
+            .to_ascii_lowercase()
+            .contains("\r\nrange: bytes=0-0\r\n")
+        {
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-0/1024\r\nContent-Length: 1\r\nConnection: close\r\n\r\nx"
+        } else {
+            "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        }
+    }
 
-            // retry_after_unix_timestamp - curr_time_unix_timestamp
-            // If underflows, returns Duration::ZERO.
-            Some(retry_after_unix_timestamp.saturating_sub(curr_time_unix_timestamp))
+    #[tokio::test]
+    async fn test_head_rejected() {
+        let servers = [
+            spawn_server(|request| {
+                respond(
+                    request,
+                    "HTTP/1.1 405 Method Not Allowed\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+            })
+            .await,
+            spawn_server(|request| {
+                respond(
+                    request,
+                    "HTTP/1.1 501 Not Implemented\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+            })
+            .await,
+        ];
+
+        for (url, cert) in servers {
+            let client = Client::new(
+                "binstalk-downloader-test",
+                None,
+                NonZeroU16::new(1).unwrap(),
+                NonZeroU64::new(10).unwrap(),
+                [Certificate::from_der(&cert).unwrap()],
+                SpkiPins::default(),
+                None,
+            )
+            .unwrap();
+
+            assert!(client.remote_gettable(url.clone()).await.unwrap());
+            assert_eq!(
+                client.remote_gettable_size(url.clone()).await.unwrap(),
+                Some(Some(1024))
+            );
+            assert_eq!(
+                client.get_redirected_final_url(url.clone()).await.unwrap(),
+                url
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline() {
+        static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+        let (url, cert) = spawn_server(|_| {
+            REQUESTED.store(true, Relaxed);
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        })
+        .await;
+        let client = Client::new(
+            "binstalk-downloader-test",
+            None,
+            NonZeroU16::new(1).unwrap(),
+            NonZeroU64::new(10).unwrap(),
+            [Certificate::from_der(&cert).unwrap()],
+            SpkiPins::default(),
+            None,
+        )
+        .unwrap()
+        .offline(true);
+
+        let err = client.get(url.clone()).send(true).await.unwrap_err();
+        assert!(
+            matches!(&err, Error::Offline(offline_url) if **offline_url == url),
+            "{err:?}"
+        );
+        client.remote_gettable(url.clone()).await.unwrap_err();
+        client.get_redirected_final_url(url).await.unwrap_err();
+
+        assert!(!REQUESTED.load(Relaxed));
+    }
+
+    fn test_client(cert: &CertificateDer<'static>) -> Client {
+        Client::new(
+            "binstalk-downloader-test",
+            None,
+            NonZeroU16::new(1).unwrap(),
+            NonZeroU64::new(10).unwrap(),
+            [Certificate::from_der(cert).unwrap()],
+            SpkiPins::default(),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        static NO_RETRY: AtomicU64 = AtomicU64::new(0);
+        static RETRIES: AtomicU64 = AtomicU64::new(0);
+
+        let (url, cert) = spawn_server(|_| {
+            NO_RETRY.fetch_add(1, Relaxed);
+            UNAVAILABLE
+        })
+        .await;
+        let err = test_client(&cert)
+            .retries(0)
+            .get(url)
+            .send(true)
+            .await
+            .unwrap_err();
+        assert!(err.is_transient(), "{err:?}");
+        assert_eq!(NO_RETRY.load(Relaxed), 1);
+
+        let (url, cert) = spawn_server(|_| {
+            RETRIES.fetch_add(1, Relaxed);
+            UNAVAILABLE
+        })
+        .await;
+        test_client(&cert)
+            .retries(4)
+            .get(url)
+            .send(true)
+            .await
+            .unwrap_err();
+        assert_eq!(RETRIES.load(Relaxed), 5);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 16)]
+    async fn test_host_limit() {
+        static IN_FLIGHT: AtomicU64 = AtomicU64::new(0);
+        static MAX_IN_FLIGHT: AtomicU64 = AtomicU64::new(0);
+
+        let (url, cert) = spawn_server(|_| {
+            let in_flight = IN_FLIGHT.fetch_add(1, Relaxed) + 1;
+            MAX_IN_FLIGHT.fetch_max(in_flight, Relaxed);
+            std::thread::sleep(Duration::from_millis(200));
+            IN_FLIGHT.fetch_sub(1, Relaxed);
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        })
+        .await;
+
+        let mut host_limits = HostLimits::empty();
+        host_limits.insert(
+            "localhost",
+            HostLimit {
+                max_concurrent: 3.try_into().unwrap(),
+                min_interval: Duration::from_millis(1),
+            },
+        );
+        let client = test_client(&cert).host_limits(host_limits);
+
+        let tasks: Vec<_> = (0..12)
+            .map(|_| {
+                let client = client.clone();
+                let url = url.clone();
+                tokio::spawn(async move { client.get(url).send(true).await.map(drop) })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
         }
+
+        let max_in_flight = MAX_IN_FLIGHT.load(Relaxed);
+        assert!((2..=3).contains(&max_in_flight), "{max_in_flight}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_timeout() {
+        let (url, cert) = spawn_server(|_| {
+            std::thread::sleep(Duration::from_secs(2));
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        })
+        .await;
+
+        let err = test_client(&cert)
+            .timeout(Some(Duration::from_millis(100)))
+            .retries(0)
+            .get(url)
+            .send(true)
+            .await
+            .unwrap_err();
+        assert!(err.is_transient(), "{err:?}");
+        assert!(
+            matches!(&err, Error::Http(http_error) if http_error.err.is_timeout()),
+            "{err:?}"
+        );
     }
 }
@@ -4,7 +4,13 @@ use reqwest::tls;
 use super::Error;
 
 #[derive(Clone, Debug)]
-pub struct Certificate(#[cfg(feature = "__tls")] pub(super) tls::Certificate);
+pub struct Certificate(
+    #[cfg(feature = "__tls")] pub(super) tls::Certificate,
+    /// DER encoded certificates, used to create rustls config with
+    /// SPKI pins.
+    #[cfg(feature = "rustls")]
+    pub(super) Vec<rustls_pki_types::CertificateDer<'static>>,
+);
 
 #[cfg_attr(not(feature = "__tls"), allow(unused_variables))]
 impl Certificate {
@@ -15,7 +21,13 @@ impl Certificate {
 
         #[cfg(feature = "__tls")]
         tls::Certificate::from_der(der.as_ref())
-            .map(Self)
+            .map(|cert| {
+                Self(
+                    cert,
+                    #[cfg(feature = "rustls")]
+                    vec![der.as_ref().to_vec().into()],
+                )
+            })
             .map_err(Error::from)
     }
 
@@ -25,8 +37,80 @@ impl Certificate {
         return Ok(Self());
 
         #[cfg(feature = "__tls")]
-        tls::Certificate::from_pem(pem.as_ref())
-            .map(Self)
-            .map_err(Error::from)
+        Ok(Self(
+            tls::Certificate::from_pem(pem.as_ref())?,
+            #[cfg(feature = "rustls")]
+            rustls_pemfile::certs(&mut pem.as_ref())
+                .collect::<Result<_, _>>()
+                .map_err(Error::InvalidPem)?,
+        ))
+    }
+
+    /// Create Certificates from a PEM encoded bundle of certificates, e.g.
+    /// a CA certificate along with intermediate ones.
+    pub fn from_pem_bundle(pem: impl AsRef<[u8]>) -> Result<Vec<Self>, Error> {
+        #[cfg(not(feature = "__tls"))]
+        return Ok(vec![Self()]);
+
+        #[cfg(feature = "__tls")]
+        {
+            let certs = tls::Certificate::from_pem_bundle(pem.as_ref())?;
+
+            #[cfg(feature = "rustls")]
+            let mut pem = pem.as_ref();
+            #[cfg(feature = "rustls")]
+            let mut certificates_der = rustls_pemfile::certs(&mut pem);
+
+            certs
+                .into_iter()
+                .map(|cert| {
+                    Ok(Self(
+                        cert,
+                        #[cfg(feature = "rustls")]
+                        certificates_der
+                            .next()
+                            .transpose()
+                            .map_err(Error::InvalidPem)?
+                            .into_iter()
+                            .collect(),
+                    ))
+                })
+                .collect()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_pem_bundle() {
+        let pem: String = ["ca.example.com", "intermediate.example.com"]
+            .into_iter()
+            .map(|name| {
+                rcgen::generate_simple_self_signed(vec![name.to_owned()])
+                    .unwrap()
+                    .cert
+                    .pem()
+            })
+            .collect();
+
+        let certs = Certificate::from_pem_bundle(&pem).unwrap();
+        assert_eq!(certs.len(), 2);
+        for cert in &certs {
+            assert_eq!(cert.1.len(), 1);
+        }
+        assert_ne!(certs[0].1, certs[1].1);
+
+        assert!(Certificate::from_pem_bundle("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_pem() {
+        let pem = "-----BEGIN CERTIFICATE-----\nnot base64!\n-----END CERTIFICATE-----\n";
+
+        Certificate::from_pem(pem).unwrap_err();
+        Certificate::from_pem_bundle(pem).unwrap_err();
     }
 }
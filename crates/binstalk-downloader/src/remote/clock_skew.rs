@@ -0,0 +1,267 @@
+//! Detect a system clock far off the actual time, which breaks validation
+//! of certificates and the delays computed from server timestamps.
+
+use std::{
+    error::Error as StdError,
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use httpdate::{fmt_http_date, parse_http_date};
+use reqwest::header::{HeaderMap, DATE};
+use thiserror::Error as ThisError;
+use tracing::warn;
+use url::Url;
+
+/// A delay until a server timestamp longer than this, or a server time
+/// this far off the local one, means the system clock is wrong.
+const MAX_PLAUSIBLE_SKEW: Duration = Duration::from_secs(2 * 60 * 60);
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// The certificate presented by the server is not valid yet, which almost
+/// always means the system clock is behind.
+#[derive(Debug, ThisError)]
+#[error(
+    "certificate of {host} is not yet valid, the system clock appears to be wrong \
+    (local time is {local_time}): fix the system clock, e.g. by enabling NTP \
+    synchronization, then retry",
+    host = self.url.host_str().unwrap_or_default(),
+    local_time = fmt_http_date(self.local_time)
+)]
+pub struct CertificateNotYetValid {
+    pub(super) url: Url,
+    pub(super) local_time: SystemTime,
+}
+
+impl CertificateNotYetValid {
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn local_time(&self) -> SystemTime {
+        self.local_time
+    }
+}
+
+/// Return the time until `deadline`, a timestamp sent by a server, clamped
+/// to `max`.
+///
+/// Warns if the system clock appears to be wrong.
+pub(crate) fn delay_until(deadline: SystemTime, headers: &HeaderMap, max: Duration) -> Duration {
+    let now = SystemTime::now();
+    let server_date = server_date(headers);
+
+    let (delay, skewed) = clamp_delay(deadline, now, server_date, max);
+    if skewed {
+        warn_clock_skew(now, server_date);
+    }
+
+    delay
+}
+
+/// Return the delay until `deadline` clamped to `max`, and whether the
+/// clock appears to be skewed.
+///
+/// The delay is computed from `server_date` if there is one, since it is
+/// right even when the local clock is not.
+fn clamp_delay(
+    deadline: SystemTime,
+    now: SystemTime,
+    server_date: Option<SystemTime>,
+    max: Duration,
+) -> (Duration, bool) {
+    let local_delay = deadline.duration_since(now).unwrap_or_default();
+
+    let skewed = local_delay > MAX_PLAUSIBLE_SKEW
+        || server_date.is_some_and(|date| abs_diff(now, date) > MAX_PLAUSIBLE_SKEW);
+
+    let delay = match server_date {
+        Some(date) => deadline.duration_since(date).unwrap_or_default(),
+        None => local_delay,
+    };
+
+    (delay.min(max), skewed)
+}
+
+fn abs_diff(x: SystemTime, y: SystemTime) -> Duration {
+    x.duration_since(y).unwrap_or_else(|err| err.duration())
+}
+
+fn server_date(headers: &HeaderMap) -> Option<SystemTime> {
+    parse_http_date(headers.get(DATE)?.to_str().ok()?).ok()
+}
+
+/// Warn that the system clock appears to be wrong, only once since every
+/// request would hit it.
+pub(super) fn warn_clock_skew(now: SystemTime, server_date: Option<SystemTime>) {
+    if WARNED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    let server_date = server_date
+        .map(|date| format!(", server time is {}", fmt_http_date(date)))
+        .unwrap_or_default();
+
+    warn!(
+        "The system clock appears to be wrong (local time is {}{server_date}), \
+        fix it, e.g. by enabling NTP synchronization",
+        fmt_http_date(now),
+    );
+}
+
+/// Return `true` if there is a certificate "not yet valid" error in the
+/// chain of `err`.
+pub(super) fn is_cert_not_yet_valid(mut err: &(dyn StdError + 'static)) -> bool {
+    loop {
+        #[cfg(feature = "rustls")]
+        if let Some(rustls::Error::InvalidCertificate(rustls::CertificateError::NotValidYet)) =
+            err.downcast_ref::<rustls::Error>()
+        {
+            return true;
+        }
+
+        // native-tls only reports it in the message, e.g. openssl's
+        // "certificate is not yet valid".
+        if err.to_string().to_lowercase().contains("not yet valid") {
+            return true;
+        }
+
+        err = match err.downcast_ref::<io::Error>() {
+            // io::Error::source skips the custom error it contains.
+            Some(io_err) => match io_err.get_ref() {
+                Some(inner) => inner,
+                None => return false,
+            },
+            None => match err.source() {
+                Some(source) => source,
+                None => return false,
+            },
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+    const MAX: Duration = Duration::from_secs(120);
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+    }
+
+    #[test]
+    fn test_clamp_delay() {
+        // Plausible delays are kept as is.
+        assert_eq!(
+            clamp_delay(at(60), at(0), None, MAX),
+            (Duration::from_secs(60), false)
+        );
+        assert_eq!(clamp_delay(at(600), at(0), None, MAX), (MAX, false));
+
+        // Deadlines in the past mean no delay.
+        assert_eq!(
+            clamp_delay(at(0), at(60), None, MAX),
+            (Duration::ZERO, false)
+        );
+
+        // A clock hours behind gives implausible delays.
+        assert_eq!(
+            clamp_delay(at(3 * HOUR.as_secs()), at(0), None, MAX),
+            (MAX, true)
+        );
+        assert_eq!(
+            clamp_delay(at(2 * HOUR.as_secs() + 1), at(0), None, HOUR),
+            (HOUR, true)
+        );
+
+        // The server time is used when available.
+        let server_date = Some(at(3 * HOUR.as_secs()));
+        assert_eq!(
+            clamp_delay(at(3 * HOUR.as_secs() + 30), at(0), server_date, MAX),
+            (Duration::from_secs(30), true)
+        );
+
+        // A clock ahead is detected from the server time only.
+        assert_eq!(
+            clamp_delay(at(30), at(3 * HOUR.as_secs()), None, MAX),
+            (Duration::ZERO, false)
+        );
+        assert_eq!(
+            clamp_delay(at(30), at(3 * HOUR.as_secs()), Some(at(0)), MAX),
+            (Duration::from_secs(30), true)
+        );
+
+        // Small differences between the clocks are fine.
+        assert_eq!(
+            clamp_delay(at(90), at(0), Some(at(5)), MAX),
+            (Duration::from_secs(85), false)
+        );
+    }
+
+    #[test]
+    fn test_server_date() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(server_date(&headers), None);
+
+        headers.insert(DATE, "Tue, 14 Nov 2023 22:13:20 GMT".parse().unwrap());
+        assert_eq!(server_date(&headers), Some(at(0)));
+
+        headers.insert(DATE, "yesterday".parse().unwrap());
+        assert_eq!(server_date(&headers), None);
+    }
+
+    #[derive(Debug, ThisError)]
+    #[error("error trying to connect")]
+    struct Connect(#[source] io::Error);
+
+    #[test]
+    fn test_is_cert_not_yet_valid() {
+        let openssl = io::Error::new(
+            io::ErrorKind::Other,
+            "error:0A000086:SSL routines:tls_post_process_server_certificate:\
+            certificate verify failed:../ssl/statem/statem_clnt.c:1889: \
+            (certificate is not yet valid)",
+        );
+        assert!(is_cert_not_yet_valid(&Connect(openssl)));
+
+        let expired = io::Error::new(io::ErrorKind::Other, "certificate has expired");
+        assert!(!is_cert_not_yet_valid(&Connect(expired)));
+
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert!(!is_cert_not_yet_valid(&Connect(refused)));
+    }
+
+    #[cfg(feature = "rustls")]
+    #[test]
+    fn test_is_cert_not_yet_valid_rustls() {
+        let not_yet_valid = io::Error::new(
+            io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(rustls::CertificateError::NotValidYet),
+        );
+        assert!(is_cert_not_yet_valid(&Connect(not_yet_valid)));
+
+        let expired = io::Error::new(
+            io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(rustls::CertificateError::Expired),
+        );
+        assert!(!is_cert_not_yet_valid(&Connect(expired)));
+    }
+
+    #[test]
+    fn test_certificate_not_yet_valid_display() {
+        let err = CertificateNotYetValid {
+            url: Url::parse("https://github.com/cargo-bins/cargo-binstall").unwrap(),
+            local_time: at(0),
+        };
+        assert_eq!(
+            err.to_string(),
+            "certificate of github.com is not yet valid, the system clock appears to be wrong \
+            (local time is Tue, 14 Nov 2023 22:13:20 GMT): fix the system clock, e.g. by \
+            enabling NTP synchronization, then retry"
+        );
+    }
+}
@@ -8,6 +8,8 @@ use reqwest::{Request, Url};
 use tokio::time::{sleep_until, Duration, Instant};
 use tracing::debug;
 
+use super::host_limit::{HostLimits, HostThrottles};
+
 pub(super) type RequestResult = Result<reqwest::Response, reqwest::Error>;
 
 trait IterExt: Iterator {
@@ -137,6 +139,7 @@ enum Readiness {
 pub(super) struct DelayRequest {
     inner: Mutex<Inner>,
     hosts_to_delay: Mutex<HashMap<CompactString, Instant>>,
+    host_throttles: HostThrottles,
 }
 
 impl DelayRequest {
@@ -144,9 +147,14 @@ impl DelayRequest {
         Self {
             inner: Mutex::new(Inner::new(num_request, per, client)),
             hosts_to_delay: Default::default(),
+            host_throttles: HostThrottles::new(HostLimits::default()),
         }
     }
 
+    pub(super) fn set_host_limits(&mut self, host_limits: HostLimits) {
+        self.host_throttles = HostThrottles::new(host_limits);
+    }
+
     pub(super) fn add_urls_to_delay(&self, urls: &[&Url], delay_duration: Duration) {
         let deadline = Instant::now() + delay_duration;
 
@@ -220,6 +228,12 @@ impl DelayRequest {
     }
 
     pub(super) async fn call(&self, req: Request) -> RequestResult {
+        // Kept until the response is received (per-host throttler).
+        let _permit = match req.url().host_str() {
+            Some(host) => self.host_throttles.acquire(host).await,
+            None => None,
+        };
+
         // Put all variables in a block so that will be dropped before polling
         // the future returned by reqwest.
         {
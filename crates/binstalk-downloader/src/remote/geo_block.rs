@@ -0,0 +1,124 @@
+use reqwest::{Method, StatusCode};
+use thiserror::Error as ThisError;
+use url::Url;
+
+/// Lowercase snippets of the bodies of common geo-block responses.
+const GEO_BLOCK_BODIES: &[&str] = &[
+    // CloudFront geographic restrictions.
+    "configured to block access from your country",
+    // Cloudflare "Access denied" for banned countries.
+    "error code: 1009",
+    "banned the country or region",
+    "not available in your country",
+    "not available in your region",
+];
+
+/// Only this many bytes of the body are looked at.
+pub(super) const MAX_BODY_LEN: usize = 4096;
+
+/// The host refused the request because of the region or network it was
+/// sent from, e.g. with `451 Unavailable For Legal Reasons`.
+#[derive(Debug, ThisError)]
+#[error("{host} is unavailable from your region/network: {method} {url} returned {status}", host = self.host())]
+pub struct GeoBlocked {
+    pub(super) method: Method,
+    pub(super) url: Url,
+    pub(super) status: StatusCode,
+}
+
+impl GeoBlocked {
+    /// Return the host which refused the request.
+    pub fn host(&self) -> &str {
+        self.url.host_str().unwrap_or_default()
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+}
+
+/// Return `true` if a response with `status` might be a geo-block, in
+/// which case its body has to be checked with [`is_geo_blocked`].
+pub(super) fn might_be_geo_blocked(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::FORBIDDEN | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS
+    )
+}
+
+/// Return `true` if the response is a geo-block.
+///
+///  * `body` - start of the body of the response.
+pub(super) fn is_geo_blocked(status: StatusCode, body: &[u8]) -> bool {
+    match status {
+        StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => true,
+        StatusCode::FORBIDDEN => {
+            let body = &body[..body.len().min(MAX_BODY_LEN)];
+            let body = String::from_utf8_lossy(body).to_lowercase();
+
+            GEO_BLOCK_BODIES
+                .iter()
+                .any(|snippet| body.contains(snippet))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_geo_blocked() {
+        assert!(is_geo_blocked(
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            b""
+        ));
+
+        assert!(is_geo_blocked(
+            StatusCode::FORBIDDEN,
+            b"<H1>403 ERROR</H1><H2>The request could not be satisfied.</H2>\n\
+            The Amazon CloudFront distribution is configured to block access from your country."
+        ));
+        assert!(is_geo_blocked(
+            StatusCode::FORBIDDEN,
+            b"<title>Access denied | example.com used Cloudflare to restrict access</title>\
+            <span>Error code: 1009</span>"
+        ));
+        assert!(is_geo_blocked(
+            StatusCode::FORBIDDEN,
+            b"This content is NOT available in your region."
+        ));
+
+        // A genuine 403, e.g. a private repository.
+        assert!(!is_geo_blocked(StatusCode::FORBIDDEN, b"Access Denied"));
+        assert!(!is_geo_blocked(StatusCode::FORBIDDEN, b""));
+
+        // Only these statuses are geo-blocks.
+        assert!(!is_geo_blocked(
+            StatusCode::NOT_FOUND,
+            b"not available in your country"
+        ));
+        assert!(!might_be_geo_blocked(StatusCode::NOT_FOUND));
+        assert!(might_be_geo_blocked(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn test_geo_blocked_display() {
+        let err = GeoBlocked {
+            method: Method::GET,
+            url: Url::parse("https://objects.example.com/a.tgz").unwrap(),
+            status: StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+        };
+        assert_eq!(err.host(), "objects.example.com");
+        assert_eq!(
+            err.to_string(),
+            "objects.example.com is unavailable from your region/network: \
+            GET https://objects.example.com/a.tgz returned 451 Unavailable For Legal Reasons"
+        );
+    }
+}
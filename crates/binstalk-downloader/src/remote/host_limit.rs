@@ -0,0 +1,189 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use compact_str::CompactString;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{sleep_until, Duration, Instant},
+};
+
+/// Hosts of GitHub which trip its secondary rate limit when flooded with
+/// requests, e.g. when resolving a large batch of crates.
+const GITHUB_HOSTS: &[&str] = &[
+    "github.com",
+    "api.github.com",
+    "objects.githubusercontent.com",
+    "release-assets.githubusercontent.com",
+];
+
+/// Limits of the requests sent to a host.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HostLimit {
+    /// Maximum number of requests waiting for a response from the host.
+    pub max_concurrent: NonZeroUsize,
+    /// Minimum interval between two requests to the host, a random delay
+    /// of up to half of it is added.
+    pub min_interval: Duration,
+}
+
+impl HostLimit {
+    /// Limit of the hosts of GitHub by default.
+    pub const GITHUB: HostLimit = HostLimit {
+        max_concurrent: match NonZeroUsize::new(4) {
+            Some(max_concurrent) => max_concurrent,
+            None => unreachable!(),
+        },
+        min_interval: Duration::from_millis(50),
+    };
+}
+
+/// [`HostLimit`] of each host, the requests to the others are not limited
+/// beyond the rate limit of the client.
+///
+/// By default, the hosts of GitHub are limited with [`HostLimit::GITHUB`].
+#[derive(Clone, Debug)]
+pub struct HostLimits(HashMap<CompactString, HostLimit>);
+
+impl Default for HostLimits {
+    fn default() -> Self {
+        Self(
+            GITHUB_HOSTS
+                .iter()
+                .map(|host| (CompactString::from(*host), HostLimit::GITHUB))
+                .collect(),
+        )
+    }
+}
+
+impl HostLimits {
+    /// No host is limited.
+    pub fn empty() -> Self {
+        Self(HashMap::new())
+    }
+
+    /// Limit the requests to `host`, replacing its previous limit.
+    pub fn insert(&mut self, host: impl Into<CompactString>, limit: HostLimit) {
+        self.0.insert(host.into(), limit);
+    }
+
+    /// Stop limiting the requests to `host`.
+    pub fn remove(&mut self, host: &str) {
+        self.0.remove(host);
+    }
+
+    pub fn get(&self, host: &str) -> Option<HostLimit> {
+        self.0.get(host).copied()
+    }
+}
+
+#[derive(Debug)]
+struct HostState {
+    limit: HostLimit,
+    semaphore: Arc<Semaphore>,
+    /// When the next request can be sent.
+    next: Mutex<Instant>,
+}
+
+/// Enforce the [`HostLimits`], shared by all the clones of the client.
+#[derive(Debug)]
+pub(super) struct HostThrottles {
+    limits: HostLimits,
+    states: Mutex<HashMap<CompactString, Arc<HostState>>>,
+}
+
+impl HostThrottles {
+    pub(super) fn new(limits: HostLimits) -> Self {
+        Self {
+            limits,
+            states: Default::default(),
+        }
+    }
+
+    fn state(&self, host: &str) -> Option<Arc<HostState>> {
+        let limit = self.limits.get(host)?;
+
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(host.into()).or_insert_with(|| {
+            Arc::new(HostState {
+                limit,
+                semaphore: Arc::new(Semaphore::new(limit.max_concurrent.get())),
+                next: Mutex::new(Instant::now()),
+            })
+        });
+
+        Some(state.clone())
+    }
+
+    /// Wait until a request can be sent to `host`, the permit returned must
+    /// be kept until its response is received.
+    pub(super) async fn acquire(&self, host: &str) -> Option<OwnedSemaphorePermit> {
+        let state = self.state(host)?;
+
+        let permit = state
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+
+        let until = {
+            let mut next = state.next.lock().unwrap();
+            let until = Instant::now().max(*next);
+            *next = until + state.limit.min_interval + jitter(state.limit.min_interval / 2);
+            until
+        };
+        sleep_until(until).await;
+
+        Some(permit)
+    }
+}
+
+/// A random duration of up to `max`.
+fn jitter(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64((random % 1024) as f64 / 1024.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_limits() {
+        let limits = HostLimits::default();
+        assert_eq!(limits.get("github.com"), Some(HostLimit::GITHUB));
+        assert_eq!(
+            limits.get("objects.githubusercontent.com"),
+            Some(HostLimit::GITHUB)
+        );
+        assert_eq!(limits.get("crates.io"), None);
+
+        assert_eq!(HostLimits::empty().get("github.com"), None);
+    }
+
+    #[tokio::test]
+    async fn test_min_interval() {
+        let mut limits = HostLimits::empty();
+        limits.insert(
+            "example.com",
+            HostLimit {
+                max_concurrent: NonZeroUsize::new(8).unwrap(),
+                min_interval: Duration::from_millis(20),
+            },
+        );
+        let throttles = HostThrottles::new(limits);
+
+        let start = Instant::now();
+        for _ in 0..4 {
+            drop(throttles.acquire("example.com").await.unwrap());
+        }
+        // The first request is sent right away.
+        assert!(start.elapsed() >= Duration::from_millis(60));
+
+        assert!(throttles.acquire("crates.io").await.is_none());
+    }
+}
@@ -45,27 +45,36 @@ impl RequestBuilder {
         Ok(Response {
             inner: self.client.send_request(request, error_for_status).await?,
             method,
+            client: self.client,
         })
     }
 }
 
 #[derive(Debug)]
 pub struct Response {
-    inner: reqwest::Response,
+    pub(super) inner: reqwest::Response,
     method: Method,
+    client: Client,
 }
 
 impl Response {
     pub async fn bytes(self) -> Result<Bytes, Error> {
-        self.inner.bytes().await.map_err(Error::from)
+        let bytes = self.inner.bytes().await.map_err(Error::from)?;
+        self.client.add_bytes_downloaded(bytes.len());
+        Ok(bytes)
     }
 
     pub fn bytes_stream(self) -> impl Stream<Item = Result<Bytes, Error>> {
         let url = Box::new(self.inner.url().clone());
         let method = self.method;
+        let client = self.client;
 
         self.inner.bytes_stream().map(move |res| {
-            res.map_err(|err| {
+            res.map(|bytes| {
+                client.add_bytes_downloaded(bytes.len());
+                bytes
+            })
+            .map_err(|err| {
                 Error::Http(Box::new(HttpError {
                     method: method.clone(),
                     url: Url::clone(&*url),
@@ -75,6 +84,11 @@ impl Response {
         })
     }
 
+    /// `Content-Length` of the response, if known.
+    pub fn content_length(&self) -> Option<u64> {
+        self.inner.content_length()
+    }
+
     pub fn status(&self) -> StatusCode {
         self.inner.status()
     }
@@ -0,0 +1,323 @@
+use std::{collections::BTreeMap, error::Error as StdError, io};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use compact_str::CompactString;
+use thiserror::Error as ThisError;
+
+type Pin = [u8; 32];
+
+/// Base64 encoded sha256 hashes of the SubjectPublicKeyInfo of the
+/// certificates accepted for specific hosts.
+///
+/// Connections to a host with pins fail unless the end-entity certificate
+/// presented matches one of them, in addition to the usual verification.
+/// Hosts without pins are verified as usual.
+///
+/// Pins are only enforced with the `rustls` feature.
+#[derive(Clone, Debug, Default)]
+pub struct SpkiPins(BTreeMap<CompactString, Vec<Pin>>);
+
+#[derive(Debug, ThisError)]
+#[error("invalid SPKI pin {pin:?} for {host}: expected base64 encoded sha256 hash")]
+pub struct InvalidSpkiPin {
+    host: CompactString,
+    pin: CompactString,
+}
+
+impl SpkiPins {
+    /// Add a base64 encoded sha256 `pin` for `host`.
+    pub fn add(&mut self, host: &str, pin: &str) -> Result<(), InvalidSpkiPin> {
+        let decoded = STANDARD
+            .decode(pin)
+            .ok()
+            .and_then(|decoded| Pin::try_from(decoded).ok())
+            .ok_or_else(|| InvalidSpkiPin {
+                host: host.into(),
+                pin: pin.into(),
+            })?;
+
+        self.0
+            .entry(host.to_ascii_lowercase().into())
+            .or_default()
+            .push(decoded);
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[cfg(feature = "rustls")]
+    fn get(&self, host: &str) -> Option<&[Pin]> {
+        self.0
+            .get(host.to_ascii_lowercase().as_str())
+            .map(Vec::as_slice)
+    }
+}
+
+#[derive(Clone, Debug, ThisError)]
+#[error("SPKI pin mismatch for {host}: presented certificate has SPKI sha256 {presented}")]
+pub struct SpkiPinMismatch {
+    host: CompactString,
+    /// Base64 encoded sha256 of the SPKI presented.
+    presented: CompactString,
+}
+
+impl SpkiPinMismatch {
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Base64 encoded sha256 of the SubjectPublicKeyInfo presented.
+    pub fn presented(&self) -> &str {
+        &self.presented
+    }
+}
+
+/// Look for [`SpkiPinMismatch`] in the chain of `err`.
+pub(super) fn find_spki_pin_mismatch<'a>(
+    mut err: &'a (dyn StdError + 'static),
+) -> Option<&'a SpkiPinMismatch> {
+    loop {
+        if let Some(mismatch) = err.downcast_ref::<SpkiPinMismatch>() {
+            return Some(mismatch);
+        }
+
+        #[cfg(feature = "rustls")]
+        if let Some(rustls::Error::InvalidCertificate(rustls::CertificateError::Other(other))) =
+            err.downcast_ref::<rustls::Error>()
+        {
+            // rustls::Error does not return its inner error as source.
+            err = other.0.as_ref();
+            continue;
+        }
+
+        err = match err.downcast_ref::<io::Error>() {
+            // io::Error::source skips the custom error it contains.
+            Some(io_err) => io_err.get_ref()?,
+            None => err.source()?,
+        };
+    }
+}
+
+/// Return sha256 of the SubjectPublicKeyInfo of a DER encoded X.509
+/// certificate.
+#[cfg(feature = "rustls")]
+fn spki_sha256(cert: &rustls_pki_types::CertificateDer<'_>) -> Option<Pin> {
+    let cert = webpki::EndEntityCert::try_from(cert).ok()?;
+    let digest = ring::digest::digest(
+        &ring::digest::SHA256,
+        cert.subject_public_key_info().as_ref(),
+    );
+    digest.as_ref().try_into().ok()
+}
+
+#[cfg(feature = "rustls")]
+pub(super) use verifier::tls_config;
+
+#[cfg(feature = "rustls")]
+mod verifier {
+    use std::sync::Arc;
+
+    use compact_str::ToCompactString;
+    use rustls::{
+        client::{
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+            WebPkiServerVerifier,
+        },
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        CertificateError, ClientConfig, DigitallySignedStruct, OtherError, RootCertStore,
+        SignatureScheme,
+    };
+    use tracing::warn;
+
+    use super::*;
+    use crate::remote::TLSVersion;
+
+    #[derive(Debug)]
+    struct PinningVerifier {
+        inner: Arc<WebPkiServerVerifier>,
+        spki_pins: SpkiPins,
+    }
+
+    impl ServerCertVerifier for PinningVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &CertificateDer<'_>,
+            intermediates: &[CertificateDer<'_>],
+            server_name: &ServerName<'_>,
+            ocsp_response: &[u8],
+            now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            let verified = self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+
+            let ServerName::DnsName(host) = server_name else {
+                return Ok(verified);
+            };
+            let Some(pins) = self.spki_pins.get(host.as_ref()) else {
+                return Ok(verified);
+            };
+
+            let presented = spki_sha256(end_entity).ok_or(rustls::Error::InvalidCertificate(
+                CertificateError::BadEncoding,
+            ))?;
+
+            if pins.contains(&presented) {
+                Ok(verified)
+            } else {
+                let mismatch = SpkiPinMismatch {
+                    host: host.as_ref().to_compact_string(),
+                    presented: STANDARD.encode(presented).into(),
+                };
+                Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                    OtherError(Arc::new(mismatch)),
+                )))
+            }
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.inner.verify_tls12_signature(message, cert, dss)
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            self.inner.verify_tls13_signature(message, cert, dss)
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.inner.supported_verify_schemes()
+        }
+    }
+
+    /// Create a rustls config equivalent to the one created by reqwest,
+    /// except that `spki_pins` are enforced.
+    pub(in crate::remote) fn tls_config(
+        min_tls: TLSVersion,
+        certificates: &[CertificateDer<'static>],
+        spki_pins: SpkiPins,
+    ) -> ClientConfig {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+        match rustls_native_certs::load_native_certs() {
+            Ok(native_certs) => {
+                roots.add_parsable_certificates(native_certs);
+            }
+            Err(err) => warn!("Failed to load native root certificates: {err}"),
+        }
+
+        for certificate in certificates {
+            if let Err(err) = roots.add(certificate.clone()) {
+                warn!("Failed to add root certificate: {err}");
+            }
+        }
+
+        let inner = WebPkiServerVerifier::builder(Arc::new(roots))
+            .build()
+            .expect("root store is never empty since it contains webpki roots");
+
+        let versions: &[_] = if min_tls >= TLSVersion::TLS_1_3 {
+            &[&rustls::version::TLS13]
+        } else {
+            rustls::ALL_VERSIONS
+        };
+
+        let mut config = ClientConfig::builder_with_protocol_versions(versions)
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(PinningVerifier { inner, spki_pins }))
+            .with_no_client_auth();
+
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        config
+    }
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod test {
+    use std::num::{NonZeroU16, NonZeroU64};
+
+    use rustls::pki_types::CertificateDer;
+
+    use super::*;
+    use crate::remote::{test_server::spawn_server, Certificate, Client, Error};
+
+    /// An empty 200 response.
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    fn create_client(cert: &CertificateDer<'_>, pin: &str) -> Client {
+        let mut spki_pins = SpkiPins::default();
+        spki_pins.add("localhost", pin).unwrap();
+
+        Client::new(
+            "binstalk-downloader-test",
+            None,
+            NonZeroU16::new(1).unwrap(),
+            NonZeroU64::new(10).unwrap(),
+            [Certificate::from_der(cert).unwrap()],
+            spki_pins,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_spki_pins() {
+        let (url, cert) = spawn_server(|_| OK).await;
+        let pin = STANDARD.encode(spki_sha256(&cert).unwrap());
+
+        let client = create_client(&cert, &pin);
+        assert!(client.remote_gettable(url.clone()).await.unwrap());
+
+        let client = create_client(&cert, &STANDARD.encode([0; 32]));
+        match client.remote_gettable(url).await {
+            Err(Error::SpkiPinMismatch(mismatch)) => {
+                assert_eq!(mismatch.host(), "localhost");
+                assert_eq!(mismatch.presented(), pin);
+            }
+            res => panic!("Expected SpkiPinMismatch, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn test_spki_sha256() {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let expected = ring::digest::digest(&ring::digest::SHA256, &key_pair.public_key_der());
+
+        assert_eq!(spki_sha256(cert.der()).unwrap(), expected.as_ref());
+        assert_eq!(spki_sha256(&CertificateDer::from(&b"not der"[..])), None);
+    }
+
+    #[test]
+    fn test_invalid_spki_pin() {
+        let mut spki_pins = SpkiPins::default();
+
+        spki_pins.add("localhost", "not base64").unwrap_err();
+        spki_pins
+            .add("localhost", &STANDARD.encode([0; 16]))
+            .unwrap_err();
+        assert!(spki_pins.is_empty());
+
+        spki_pins
+            .add("LocalHost", &STANDARD.encode([0; 32]))
+            .unwrap();
+        assert_eq!(spki_pins.get("localhost").unwrap(), [[0; 32]]);
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use rcgen::{generate_simple_self_signed, CertifiedKey};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    ServerConfig,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use tokio_rustls::TlsAcceptor;
+
+use super::Url;
+
+/// Spawn a https server for localhost which answers every request with the
+/// raw http/1.1 response returned by `respond` for the request, including
+/// its body if it has a `Content-Length`, return its url and certificate.
+pub async fn spawn_server<R>(respond: fn(&str) -> R) -> (Url, CertificateDer<'static>)
+where
+    R: AsRef<[u8]> + Send + 'static,
+{
+    spawn(respond, false).await
+}
+
+/// Like [`spawn_server`], but keep the connections open after sending the
+/// response, so that the rest of a body longer than its `Content-Length`
+/// never arrives, like from a very slow server.
+pub async fn spawn_stalling_server(
+    respond: fn(&str) -> &'static str,
+) -> (Url, CertificateDer<'static>) {
+    spawn(respond, true).await
+}
+
+async fn spawn<R>(respond: fn(&str) -> R, stall: bool) -> (Url, CertificateDer<'static>)
+where
+    R: AsRef<[u8]> + Send + 'static,
+{
+    let CertifiedKey { cert, key_pair } =
+        generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert = cert.der().clone();
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![cert.clone()],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der())),
+        )
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+
+            tokio::spawn(async move {
+                let Ok(mut stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+
+                let mut buffer = [0; 4096];
+                let mut request = Vec::new();
+                while request.len() < request_len(&request).unwrap_or(usize::MAX) {
+                    match stream.read(&mut buffer).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buffer[..n]),
+                    }
+                }
+
+                let response = respond(&String::from_utf8_lossy(&request));
+                stream.write_all(response.as_ref()).await.ok();
+                if stall {
+                    stream.flush().await.ok();
+                    std::future::pending::<()>().await;
+                }
+                stream.shutdown().await.ok();
+            });
+        }
+    });
+
+    let url = Url::parse(&format!("https://localhost:{port}/")).unwrap();
+
+    (url, cert)
+}
+
+/// Return the length of the head and body of `request`, once its head is
+/// complete.
+fn request_len(request: &[u8]) -> Option<usize> {
+    let head_len = request
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")?
+        + 4;
+    let head = String::from_utf8_lossy(&request[..head_len]);
+
+    let body_len = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    Some(head_len + body_len)
+}
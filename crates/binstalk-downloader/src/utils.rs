@@ -7,6 +7,13 @@ use bytes::{Buf, Bytes};
 use futures_util::{FutureExt, Stream, StreamExt};
 use tokio::{sync::mpsc, task};
 
+#[cfg(any(
+    feature = "gh-api-client",
+    feature = "gl-api-client",
+    feature = "gitea-api-client"
+))]
+pub(crate) use api_client::*;
+
 pub(super) fn extract_with_blocking_task<E, StreamError, S, F, T>(
     stream: S,
     f: F,
@@ -170,3 +177,148 @@ impl BufRead for StreamReadable {
         self.bytes.advance(amt);
     }
 }
+
+/// Helpers shared by the API clients.
+#[cfg(any(
+    feature = "gh-api-client",
+    feature = "gl-api-client",
+    feature = "gitea-api-client"
+))]
+mod api_client {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering::Relaxed},
+            Arc, RwLock,
+        },
+    };
+
+    use compact_str::CompactString;
+    use percent_encoding::{
+        percent_decode_str, utf8_percent_encode, AsciiSet, PercentEncode, CONTROLS,
+    };
+
+    pub(crate) fn percent_encode_http_url_path(path: &str) -> PercentEncode<'_> {
+        /// https://url.spec.whatwg.org/#fragment-percent-encode-set
+        const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+
+        /// https://url.spec.whatwg.org/#path-percent-encode-set
+        const PATH: &AsciiSet = &FRAGMENT.add(b'#').add(b'?').add(b'{').add(b'}');
+
+        const PATH_SEGMENT: &AsciiSet = &PATH.add(b'/').add(b'%');
+
+        // The backslash (\) character is treated as a path separator in special URLs
+        // so it needs to be additionally escaped in that case.
+        //
+        // http is considered to have special path.
+        const SPECIAL_PATH_SEGMENT: &AsciiSet = &PATH_SEGMENT.add(b'\\');
+
+        utf8_percent_encode(path, SPECIAL_PATH_SEGMENT)
+    }
+
+    pub(crate) fn percent_decode_http_url_path(input: &str) -> CompactString {
+        if input.contains('%') {
+            percent_decode_str(input).decode_utf8_lossy().into()
+        } else {
+            // No '%', no need to decode.
+            CompactString::new(input)
+        }
+    }
+
+    /// Map whose values are created on first access and shared, e.g. the
+    /// `OnceCell` of each release.
+    ///
+    /// Once it holds `max_len` values, the least recently used value that is
+    /// not in use is evicted to make room for a new one, so that a value in
+    /// use, e.g. the `OnceCell` of a release being fetched, stays shared.
+    #[derive(Debug)]
+    pub(crate) struct Map<K, V> {
+        entries: RwLock<HashMap<K, Entry<V>>>,
+        max_len: usize,
+        /// Incremented on every access, to find the least recently used
+        /// value.
+        clock: AtomicU64,
+    }
+
+    #[derive(Debug)]
+    struct Entry<V> {
+        value: Arc<V>,
+        last_used: AtomicU64,
+    }
+
+    impl<K, V> Default for Map<K, V> {
+        fn default() -> Self {
+            Self {
+                entries: Default::default(),
+                max_len: usize::MAX,
+                clock: AtomicU64::new(0),
+            }
+        }
+    }
+
+    impl<K, V> Map<K, V>
+    where
+        K: Clone + Eq + std::hash::Hash,
+        V: Default,
+    {
+        #[cfg(feature = "gh-api-client")]
+        pub(crate) fn with_max_len(max_len: usize) -> Self {
+            Self {
+                max_len,
+                ..Default::default()
+            }
+        }
+
+        #[cfg(feature = "gh-api-client")]
+        pub(crate) fn set_max_len(&mut self, max_len: usize) {
+            self.max_len = max_len;
+        }
+
+        #[cfg(feature = "gh-api-client")]
+        pub(crate) fn len(&self) -> usize {
+            self.entries.read().unwrap().len()
+        }
+
+        pub(crate) fn get(&self, k: K) -> Arc<V> {
+            let now = self.clock.fetch_add(1, Relaxed);
+
+            if let Some(entry) = self.entries.read().unwrap().get(&k) {
+                entry.last_used.store(now, Relaxed);
+                return Arc::clone(&entry.value);
+            }
+
+            let mut entries = self.entries.write().unwrap();
+            if entries.len() >= self.max_len && !entries.contains_key(&k) {
+                Self::evict_least_recently_used(&mut entries);
+            }
+
+            let entry = entries.entry(k).or_insert_with(|| Entry {
+                value: Arc::default(),
+                last_used: AtomicU64::new(now),
+            });
+            entry.last_used.store(now, Relaxed);
+            Arc::clone(&entry.value)
+        }
+
+        /// Remove the least recently used value not in use, if any, the map
+        /// exceeds `max_len` until one is no longer used otherwise.
+        fn evict_least_recently_used(entries: &mut HashMap<K, Entry<V>>) {
+            let evicted = entries
+                .iter()
+                .filter(|(_, entry)| Arc::strong_count(&entry.value) == 1)
+                .min_by_key(|(_, entry)| entry.last_used.load(Relaxed))
+                .map(|(k, _)| k.clone());
+
+            if let Some(evicted) = evicted {
+                entries.remove(&evicted);
+            }
+        }
+
+        /// Remove all the values, the ones still in use are not shared with
+        /// the later accesses.
+        #[cfg(feature = "gh-api-client")]
+        pub(crate) fn clear(&self) {
+            self.entries.write().unwrap().clear();
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use filetime::FileTime;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use tracing::debug;
+use url::Url;
+
+use crate::common::artifact_file_name;
+
+/// Cache of the packages downloaded by [`crate::Fetcher::fetch_and_extract`],
+/// so that installing the same version again skips the download.
+///
+/// Packages are saved to `<dir>/<key>/<file name>`, where `<key>` is the
+/// sha256 of their url and the version of the crate. Once the cache grows
+/// past its maximum size, the least recently used ones are removed.
+///
+/// Cached packages are still verified against their signature and
+/// checksum, if any, before being used. These are saved alongside the
+/// package, so that it can be verified in offline mode.
+#[derive(Clone, Debug)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl ArtifactCache {
+    /// Default maximum size of the cache, 1 GiB.
+    pub const DEFAULT_MAX_SIZE: u64 = 1 << 30;
+
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            max_size: Self::DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Maximum size of the cache in bytes, checked when a package is added.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Return the entry of the package at `url` for `version` of the crate.
+    pub(crate) fn entry(&self, url: &Url, version: &str) -> CacheEntry<'_> {
+        let digest = Sha256::new()
+            .chain_update(url.as_str())
+            .chain_update([0])
+            .chain_update(version)
+            .finalize();
+        let key = digest.iter().fold(String::new(), |mut key, byte| {
+            write!(key, "{byte:02x}").unwrap();
+            key
+        });
+
+        let dir = self.dir.join(key);
+        let path = dir.join(artifact_file_name(url));
+
+        CacheEntry {
+            cache: self,
+            dir,
+            path,
+        }
+    }
+
+    /// Remove the least recently used packages until the cache fits in its
+    /// maximum size.
+    pub fn prune(&self) -> io::Result<()> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            // Skip the packages being downloaded.
+            if entry.file_name().to_string_lossy().starts_with('.') || !entry.file_type()?.is_dir()
+            {
+                continue;
+            }
+
+            let path = entry.path();
+            let size = dir_size(&path)?;
+            let last_used = FileTime::from_last_modification_time(&entry.metadata()?);
+
+            total += size;
+            entries.push((last_used, size, path));
+        }
+
+        entries.sort_unstable_by_key(|(last_used, ..)| *last_used);
+
+        for (_, size, path) in entries {
+            if total <= self.max_size {
+                break;
+            }
+
+            debug!("Removing '{}' from the cache", path.display());
+            fs::remove_dir_all(&path)?;
+            total -= size;
+        }
+
+        Ok(())
+    }
+}
+
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    fs::read_dir(dir)?.try_fold(0, |size, entry| Ok(size + entry?.metadata()?.len()))
+}
+
+/// A package in the [`ArtifactCache`], which may not be cached yet.
+pub(crate) struct CacheEntry<'a> {
+    cache: &'a ArtifactCache,
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl CacheEntry<'_> {
+    /// Return the path of the package, if it is cached.
+    pub(crate) fn cached(&self) -> Option<&Path> {
+        self.path.is_file().then_some(&*self.path)
+    }
+
+    /// Mark the package as used, for [`ArtifactCache::prune`].
+    pub(crate) fn touch(&self) {
+        filetime::set_file_mtime(&self.dir, FileTime::now()).ok();
+    }
+
+    pub(crate) fn remove(&self) {
+        fs::remove_dir_all(&self.dir).ok();
+    }
+
+    /// Return the file downloaded from `url` alongside the package, e.g.
+    /// its signature, if it is cached.
+    pub(crate) fn load_file(&self, url: &Url) -> Option<Vec<u8>> {
+        fs::read(self.dir.join(artifact_file_name(url))).ok()
+    }
+
+    /// Save `data` downloaded from `url` alongside the package.
+    pub(crate) fn save_file(&self, url: &Url, data: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.dir.join(artifact_file_name(url));
+        let mut part_path = path.clone().into_os_string();
+        part_path.push(".part");
+        fs::write(&part_path, data)?;
+        fs::rename(&part_path, path)
+    }
+
+    /// Create a temporary directory to download the package to, which is
+    /// then moved into the cache by [`CacheEntry::store`].
+    pub(crate) fn staging_dir(&self) -> io::Result<TempDir> {
+        fs::create_dir_all(&self.cache.dir)?;
+        tempfile::Builder::new()
+            .prefix(".download-")
+            .tempdir_in(&self.cache.dir)
+    }
+
+    /// Move the files of `staging_dir` into the cache, the package last so
+    /// that it is never partially cached, then prune the cache.
+    pub(crate) fn store(&self, staging_dir: TempDir) -> io::Result<()> {
+        // Another process may have cached it in the meantime.
+        if self.cached().is_none() {
+            let file_name = self
+                .path
+                .file_name()
+                .expect("package must have a file name");
+            fs::create_dir_all(&self.dir)?;
+
+            for entry in fs::read_dir(staging_dir.path())? {
+                let entry = entry?;
+                if entry.file_name() != file_name {
+                    fs::rename(entry.path(), self.dir.join(entry.file_name()))?;
+                }
+            }
+            fs::rename(staging_dir.path().join(file_name), &self.path)?;
+        }
+
+        self.cache.prune()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entry_is_keyed_by_url_and_version() {
+        let cache = ArtifactCache::new(PathBuf::from("cache"));
+        let url = Url::parse("https://example.com/v1/tool-x86_64.tgz").unwrap();
+
+        let entry = cache.entry(&url, "1.0.0");
+        assert_eq!(entry.path.file_name().unwrap(), "tool-x86_64.tgz");
+        assert_eq!(entry.dir.parent().unwrap(), Path::new("cache"));
+
+        assert_eq!(cache.entry(&url, "1.0.0").dir, entry.dir);
+        assert_ne!(cache.entry(&url, "1.0.1").dir, entry.dir);
+
+        let other = Url::parse("https://example.com/v2/tool-x86_64.tgz").unwrap();
+        assert_ne!(cache.entry(&other, "1.0.0").dir, entry.dir);
+    }
+
+    #[test]
+    fn prune_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ArtifactCache::new(dir.path().to_owned()).max_size(250);
+
+        let entries = ["a", "b", "c"].map(|name| {
+            let url = Url::parse(&format!("https://example.com/{name}.tgz")).unwrap();
+            let entry = cache.entry(&url, "1.0.0");
+            fs::create_dir(&entry.dir).unwrap();
+            fs::write(&entry.path, [0; 100]).unwrap();
+            entry
+        });
+        // `b` is the least recently used, then `a`.
+        for (entry, secs) in entries.iter().zip([200, 100, 300]) {
+            filetime::set_file_mtime(&entry.dir, FileTime::from_unix_time(secs, 0)).unwrap();
+        }
+        // Downloads in progress are left alone.
+        let staging_dir = entries[0].staging_dir().unwrap();
+
+        cache.prune().unwrap();
+
+        assert!(entries[0].cached().is_some());
+        assert!(entries[1].cached().is_none());
+        assert!(entries[2].cached().is_some());
+        assert!(staging_dir.path().is_dir());
+    }
+}
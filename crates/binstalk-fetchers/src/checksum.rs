@@ -0,0 +1,94 @@
+use binstalk_types::cargo_toml_binstall::ChecksumAlgorithm;
+use compact_str::CompactString;
+
+use crate::FetchError;
+
+/// Return the length of the hex-encoded digests of `algorithm`.
+pub(super) fn digest_len(algorithm: ChecksumAlgorithm) -> Result<usize, FetchError> {
+    match algorithm {
+        ChecksumAlgorithm::Sha256 => Ok(64),
+        algorithm => Err(FetchError::UnsupportedChecksumAlgorithm(algorithm)),
+    }
+}
+
+/// Find the hex-encoded digest of `file_name` in the `contents` of a
+/// checksum file, which is either:
+///
+///  - only the digest, possibly followed by a newline
+///  - a `sha256sum` style manifest, with one `{digest}  {file}` line per
+///    file, or `{digest} *{file}` for files checked in binary mode
+///
+/// The files of a manifest are matched by their last path component, since
+/// some manifests are generated from another directory, e.g. `dist/{file}`.
+pub(super) fn find_checksum(
+    contents: &str,
+    file_name: &str,
+    digest_len: usize,
+) -> Option<CompactString> {
+    let is_digest =
+        |s: &str| s.len() == digest_len && s.bytes().all(|byte| byte.is_ascii_hexdigit());
+
+    let contents = contents.trim();
+    if is_digest(contents) {
+        return Some(contents.to_ascii_lowercase().into());
+    }
+
+    contents.lines().find_map(|line| {
+        let (digest, name) = line.trim_end().split_once(' ')?;
+        let name = name.trim_start();
+        let name = name.strip_prefix('*').unwrap_or(name);
+
+        (is_digest(digest) && name.rsplit('/').next() == Some(file_name))
+            .then(|| digest.to_ascii_lowercase().into())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const OTHER_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn test_find_checksum() {
+        let file_name = "tool-x86_64-unknown-linux-gnu.tgz";
+        let find = |contents: &str| find_checksum(contents, file_name, 64);
+
+        // Only the digest.
+        assert_eq!(find(SHA256).as_deref(), Some(SHA256));
+        assert_eq!(find(&format!("{SHA256}\n")).as_deref(), Some(SHA256));
+        assert_eq!(find(&SHA256.to_ascii_uppercase()).as_deref(), Some(SHA256));
+        assert_eq!(find(&SHA256[1..]), None);
+
+        // `sha256sum` manifests.
+        let manifest = format!(
+            "{OTHER_SHA256}  tool-x86_64-pc-windows-msvc.zip\n\
+             {SHA256}  {file_name}\n\
+             {OTHER_SHA256}  {file_name}.sig\n"
+        );
+        assert_eq!(find(&manifest).as_deref(), Some(SHA256));
+        assert_eq!(
+            find(&format!("{SHA256} *{file_name}\r\n")).as_deref(),
+            Some(SHA256)
+        );
+        assert_eq!(
+            find(&format!("{SHA256}  ./dist/{file_name}")).as_deref(),
+            Some(SHA256)
+        );
+        // The digest of a single file, named after it.
+        assert_eq!(
+            find(&format!("{SHA256}  {file_name}")).as_deref(),
+            Some(SHA256)
+        );
+
+        assert_eq!(
+            find(&format!(
+                "{OTHER_SHA256}  tool-x86_64-pc-windows-msvc.zip\n"
+            )),
+            None
+        );
+        assert_eq!(find(&format!("not-a-digest  {file_name}\n")), None);
+        assert_eq!(find(""), None);
+    }
+}
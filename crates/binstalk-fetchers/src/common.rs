@@ -1,60 +1,820 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering::Relaxed},
-    Once,
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Once,
+    },
 };
 
-use binstalk_downloader::gh_api_client::{GhReleaseArtifact, HasReleaseArtifact};
+use binstalk_downloader::{
+    download::DownloadError,
+    gh_api_client::{ArtifactCheck, GhApiError, GhRelease, GhReleaseArtifact},
+    gitea_api_client::{self, GiteaReleaseArtifact},
+    gl_api_client::{self, GlReleaseArtifact},
+    remote::StatusCode,
+};
 pub(super) use binstalk_downloader::{
-    download::{Download, ExtractedFiles},
+    download::{
+        extract_file, DataVerifier, Download, DownloadProgress, ExtractFilter, ExtractedFiles,
+        SavedArtifact, Sha256Verifier,
+    },
     gh_api_client::GhApiClient,
+    gitea_api_client::GiteaApiClient,
+    gl_api_client::GlApiClient,
     remote::{Client, Url},
 };
-pub(super) use binstalk_types::cargo_toml_binstall::{PkgFmt, PkgMeta};
+pub(super) use binstalk_types::{
+    cargo_toml_binstall::{PkgFmt, PkgMeta, Strategy},
+    crate_info::FetchSource,
+};
 pub(super) use compact_str::CompactString;
 pub(super) use tokio::task::JoinHandle;
 pub(super) use tracing::{debug, instrument, warn};
 
-use crate::FetchError;
+use bytes::Bytes;
+use tempfile::TempDir;
+use tracing::{info, trace};
+
+use crate::{futures_resolver::resolve_in_order, CacheEntry, FetchError, SignatureVerifier};
+
+pub(super) use binstalk_types::crate_info::{SignatureCheck, Verification};
+
+static GL_API_CLIENT_FAILED: AtomicBool = AtomicBool::new(false);
+static GITEA_API_CLIENT_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Return `None` if the GitLab API cannot answer this and we should fallback
+/// to HEAD/GET on the url.
+fn gl_api_answer(ret: gl_api_client::HasReleaseArtifact) -> Option<bool> {
+    use gl_api_client::HasReleaseArtifact;
+
+    static WARN_RATE_LIMIT_ONCE: Once = Once::new();
+    static WARN_UNAUTHORIZED_ONCE: Once = Once::new();
+
+    match ret {
+        HasReleaseArtifact::Yes => return Some(true),
+        HasReleaseArtifact::No | HasReleaseArtifact::NoSuchRelease => return Some(false),
+
+        HasReleaseArtifact::RateLimit { retry_after } => {
+            WARN_RATE_LIMIT_ONCE.call_once(|| {
+                warn!("Your GitLab API token (if any) has reached its rate limit and cannot be used again until {retry_after:?}, so we will fallback to HEAD/GET on the url.");
+            });
+        }
+        HasReleaseArtifact::Unauthorized => {
+            WARN_UNAUTHORIZED_ONCE.call_once(|| {
+                warn!("GitLab API somehow requires a token for the API access, so we will fallback to HEAD/GET on the url.");
+                warn!("Please consider supplying a token to cargo-binstall with `GITLAB_TOKEN` to speedup resolution.");
+            });
+        }
+    }
+
+    GL_API_CLIENT_FAILED.store(true, Relaxed);
+
+    None
+}
+
+/// Return `None` if the Gitea API cannot answer this and we should fallback
+/// to HEAD/GET on the url.
+fn gitea_api_answer(ret: gitea_api_client::HasReleaseArtifact) -> Option<bool> {
+    use gitea_api_client::HasReleaseArtifact;
+
+    static WARN_RATE_LIMIT_ONCE: Once = Once::new();
+    static WARN_UNAUTHORIZED_ONCE: Once = Once::new();
+
+    match ret {
+        HasReleaseArtifact::Yes => return Some(true),
+        HasReleaseArtifact::No | HasReleaseArtifact::NoSuchRelease => return Some(false),
+
+        HasReleaseArtifact::RateLimit { retry_after } => {
+            WARN_RATE_LIMIT_ONCE.call_once(|| {
+                warn!("Your Gitea API token (if any) has reached its rate limit and cannot be used again until {retry_after:?}, so we will fallback to HEAD/GET on the url.");
+            });
+        }
+        HasReleaseArtifact::Unauthorized => {
+            WARN_UNAUTHORIZED_ONCE.call_once(|| {
+                warn!("Gitea API somehow requires a token for the API access, so we will fallback to HEAD/GET on the url.");
+                warn!("Please consider supplying a token to cargo-binstall with `GITEA_TOKEN` to speedup resolution.");
+            });
+        }
+    }
+
+    GITEA_API_CLIENT_FAILED.store(true, Relaxed);
+
+    None
+}
 
 /// This function returns a future where its size should be at most size of
 /// 2-4 pointers.
+///
+/// The API used to check for the artifact is picked by the host of `url`,
+/// and `download_url` is checked directly if none can be used, see
+/// [`UrlRewrites`](crate::UrlRewrites).
+///
+/// Return `None` if the artifact does not exist, and its size otherwise if
+/// it is known without asking an API again, see [`release_artifact_size`].
 pub(super) async fn does_url_exist(
     client: Client,
     gh_api_client: GhApiClient,
+    gl_api_client: GlApiClient,
+    gitea_api_client: GiteaApiClient,
     url: &Url,
-) -> Result<bool, FetchError> {
-    static GH_API_CLIENT_FAILED: AtomicBool = AtomicBool::new(false);
-    static WARN_RATE_LIMIT_ONCE: Once = Once::new();
-    static WARN_UNAUTHORIZED_ONCE: Once = Once::new();
-
+    download_url: &Url,
+) -> Result<Option<Option<u64>>, FetchError> {
     debug!("Checking for package at: '{url}'");
 
-    if !GH_API_CLIENT_FAILED.load(Relaxed) {
-        if let Some(artifact) = GhReleaseArtifact::try_extract_from_url(url) {
-            debug!("Using GitHub API to check for existence of artifact, which will also cache the API response");
+    if let Some(artifact) = release_artifact(&gh_api_client, url) {
+        return match Box::pin(gh_api_client.check_artifact_at(&artifact, download_url.clone()))
+            .await
+        {
+            ArtifactCheck::Exists { size, .. } => Ok(Some(size)),
+            ArtifactCheck::Missing => Ok(None),
+            ArtifactCheck::Error(GhApiError::Remote(err)) => Err(err.into()),
+            ArtifactCheck::Error(err) => Err(err.into()),
+        };
+    }
+
+    if !GL_API_CLIENT_FAILED.load(Relaxed) {
+        if let Some(artifact) =
+            GlReleaseArtifact::try_extract_from_url(url, &[gl_api_client.host()])
+        {
+            debug!("Using GitLab API to check for existence of artifact, which will also cache the API response");
 
             // The future returned has the same size as a pointer
-            match gh_api_client.has_release_artifact(artifact).await? {
-                HasReleaseArtifact::Yes => return Ok(true),
-                HasReleaseArtifact::No | HasReleaseArtifact::NoSuchRelease => return Ok(false),
-
-                HasReleaseArtifact::RateLimit { retry_after } => {
-                    WARN_RATE_LIMIT_ONCE.call_once(|| {
-                        warn!("Your GitHub API token (if any) has reached its rate limit and cannot be used again until {retry_after:?}, so we will fallback to HEAD/GET on the url.");
-                        warn!("If you did not supply a github token, consider doing so: GitHub limits unauthorized users to 60 requests per hour per origin IP address.");
-                    });
+            let ret = gl_api_client.has_release_artifact(artifact).await?;
+            if let Some(exists) = gl_api_answer(ret) {
+                return Ok(exists.then_some(None));
+            }
+        }
+    }
+
+    if !GITEA_API_CLIENT_FAILED.load(Relaxed) {
+        if let Some(artifact) =
+            GiteaReleaseArtifact::try_extract_from_url(url, &gitea_api_client.hosts())
+        {
+            debug!("Using Gitea API to check for existence of artifact, which will also cache the API response");
+
+            // The future returned has the same size as a pointer
+            let ret = gitea_api_client.has_release_artifact(artifact).await?;
+            if let Some(exists) = gitea_api_answer(ret) {
+                return Ok(exists.then_some(None));
+            }
+        }
+    }
+
+    Ok(Box::pin(client.remote_gettable_size(download_url.clone())).await?)
+}
+
+/// Return the release artifact at `url` if it is on the host whose API is
+/// used by `gh_api_client`, e.g. a GitHub Enterprise Server.
+fn release_artifact(gh_api_client: &GhApiClient, url: &Url) -> Option<GhReleaseArtifact> {
+    GhReleaseArtifact::try_extract_from_url(url, &[gh_api_client.host().host()])
+}
+
+/// Return the size of the artifact of the GitHub release at `url`, from the
+/// release fetched when checking for its existence.
+pub(super) async fn release_artifact_size(gh_api_client: &GhApiClient, url: &Url) -> Option<u64> {
+    if !gh_api_client.is_api_usable() {
+        return None;
+    }
+
+    let artifact = release_artifact(gh_api_client, url)?;
+
+    match gh_api_client.get_artifact(artifact).await {
+        Ok(info) => info?.size,
+        Err(err) => {
+            debug!("Failed to get the size of '{url}': {err}");
+            None
+        }
+    }
+}
+
+/// Return the hex-encoded SHA-256 digest GitHub returns for the artifact of
+/// the release at `url`, from the release fetched when checking for its
+/// existence.
+///
+/// Only the restful API returns it.
+pub(super) async fn release_artifact_sha256(
+    gh_api_client: &GhApiClient,
+    url: &Url,
+) -> Option<CompactString> {
+    if !gh_api_client.is_api_usable() {
+        return None;
+    }
+
+    let artifact = release_artifact(gh_api_client, url)?;
+
+    match gh_api_client.get_artifact(artifact).await {
+        Ok(info) => info?.sha256().map(CompactString::from),
+        Err(err) => {
+            debug!("Failed to get the digest of '{url}': {err}");
+            None
+        }
+    }
+}
+
+/// Return the url of the only artifact of the GitHub release of `url` whose
+/// name is close to the one in `url`, see
+/// [`GhApiClient::get_artifact_fuzzy`].
+///
+/// It is only meant to be used once the exact names are not found, since the
+/// release is already cached by then.
+pub(super) async fn find_fuzzy_release_artifact(
+    gh_api_client: &GhApiClient,
+    url: &Url,
+) -> Option<Url> {
+    if !gh_api_client.is_api_usable() {
+        return None;
+    }
+
+    let artifact = release_artifact(gh_api_client, url)?;
+
+    let info = match gh_api_client.get_artifact_fuzzy(artifact).await {
+        Ok(info) => info?,
+        Err(err) => {
+            debug!("Failed to look for artifacts close to '{url}': {err}");
+            return None;
+        }
+    };
+
+    let mut fuzzy_url = url.clone();
+    fuzzy_url.path_segments_mut().ok()?.pop().push(&info.name);
+
+    Some(fuzzy_url)
+}
+
+/// Return the release and artifact names if all `urls` are artifacts of the
+/// same GitHub release and the GitHub API is still usable.
+pub(super) fn batchable_gh_release_artifacts(
+    gh_api_client: &GhApiClient,
+    urls: &[Url],
+) -> Option<(GhRelease, Vec<CompactString>)> {
+    if !gh_api_client.is_api_usable() {
+        return None;
+    }
+
+    let mut release = None;
+    let names = urls
+        .iter()
+        .map(|url| {
+            let artifact = release_artifact(gh_api_client, url)?;
+
+            match &release {
+                Some(release) if *release != artifact.release => None,
+                Some(_) => Some(artifact.artifact_name),
+                None => {
+                    release = Some(artifact.release);
+                    Some(artifact.artifact_name)
                 }
-                HasReleaseArtifact::Unauthorized => {
-                    WARN_UNAUTHORIZED_ONCE.call_once(|| {
-                        warn!("GitHub API somehow requires a token for the API access, so we will fallback to HEAD/GET on the url.");
-                        warn!("Please consider supplying a token to cargo-binstall to speedup resolution.");
-                    });
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((release?, names))
+}
+
+/// Return index of the first url in `urls` that exists, along with its size
+/// if it is known without asking the API again.
+///
+/// `urls` must be artifacts of `release` with `names`, which is checked using
+/// one fetch of the release instead of one per url. They are downloaded from
+/// `download_urls` if the API cannot be used.
+pub(super) async fn find_first_existing_release_artifact(
+    client: Client,
+    gh_api_client: GhApiClient,
+    (release, names): (GhRelease, Vec<CompactString>),
+    download_urls: &[Url],
+) -> Result<Option<(usize, Option<u64>)>, FetchError> {
+    debug!(
+        "Checking for {} candidate artifacts of release {release:?}",
+        names.len()
+    );
+
+    let answers = gh_api_client
+        .has_release_artifacts(&release, &names)
+        .await?
+        .into_iter()
+        .map(|ret| gh_api_client.api_answer(ret))
+        .collect::<Option<Vec<_>>>();
+
+    if let Some(answers) = answers {
+        return Ok(answers
+            .into_iter()
+            .position(|exists| exists)
+            .map(|i| (i, None)));
+    }
+
+    // Check them all at once, so that the ones timing out do not delay
+    // the others, but keep their order.
+    resolve_in_order(download_urls.iter().cloned().enumerate().map(|(i, url)| {
+        let client = client.clone();
+
+        async move {
+            debug!("Checking for package at: '{url}'");
+
+            Ok(client
+                .remote_gettable_size(url)
+                .await?
+                .map(|size| (i, size)))
+        }
+    }))
+    .await
+}
+
+/// Download the artifact at `url` through the GitHub API, if it is the
+/// artifact of a release that could not be downloaded directly because it
+/// does not exist for `err`, e.g. because its repository is private.
+///
+/// `err` is returned if it cannot be downloaded through the API either.
+async fn download_through_gh_api<'a>(
+    client: &Client,
+    gh_api_client: &GhApiClient,
+    url: &Url,
+    err: DownloadError,
+    data_verifier: &'a mut dyn DataVerifier,
+    progress: &'a dyn DownloadProgress,
+) -> Result<Download<'a>, FetchError> {
+    let artifact = match &err {
+        DownloadError::Remote(remote_err)
+            if remote_err.status() == Some(StatusCode::NOT_FOUND)
+                && gh_api_client.has_auth_token() =>
+        {
+            release_artifact(gh_api_client, url)
+        }
+        _ => None,
+    };
+    let Some(artifact) = artifact else {
+        return Err(err.into());
+    };
+
+    debug!(url=%url, "Not found, downloading it through the GitHub API");
+
+    let Some(artifact_url) = gh_api_client.get_artifact_url(&artifact).await? else {
+        return Err(err.into());
+    };
+    match gh_api_client.download_artifact(&artifact_url).await? {
+        Some(stream) => Ok(
+            Download::from_stream_with_data_verifier(stream, data_verifier)
+                .with_progress(progress)
+                .with_cancellation_token(client.get_cancellation_token().clone()),
+        ),
+        None => Err(err.into()),
+    }
+}
+
+/// Download the package at `url` and extract it to `dst`, checking its
+/// signature using `verifier` and its sha256 against `checksum`, if any.
+///
+/// The artifacts of GitHub releases that cannot be downloaded directly are
+/// downloaded through `gh_api_client`.
+///
+///  * `download_url` - where `url` is downloaded from, see
+///    [`UrlRewrites`](crate::UrlRewrites)
+///  * `filter` - if `Some`, only the entries it matches are extracted
+///  * `name` - name of the package, used for logging
+///  * `progress` - receiver of the progress of the download
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn download_and_extract(
+    client: Client,
+    gh_api_client: &GhApiClient,
+    url: &Url,
+    download_url: &Url,
+    fmt: PkgFmt,
+    dst: &Path,
+    filter: Option<&ExtractFilter>,
+    name: &str,
+    verifier: &SignatureVerifier,
+    checksum: Option<&str>,
+    progress: &dyn DownloadProgress,
+) -> Result<(ExtractedFiles, Verification), FetchError> {
+    debug!(url=%download_url, dst=%dst.display(), fmt=?fmt, "Downloading package");
+
+    let mut data_verifier = verifier.data_verifier()?;
+    let mut data_verifier = Sha256Verifier::new(data_verifier.as_mut());
+    let download =
+        Download::new_with_data_verifier(client.clone(), download_url.clone(), &mut data_verifier)
+            .with_progress(progress);
+    let res = with_extract_filter(download, filter)
+        .and_extract(fmt, dst)
+        .await;
+    let res = match res {
+        Ok(files) => Ok(files),
+        Err(err) => {
+            match download_through_gh_api(
+                &client,
+                gh_api_client,
+                url,
+                err,
+                &mut data_verifier,
+                progress,
+            )
+            .await
+            {
+                Ok(download) => with_extract_filter(download, filter)
+                    .and_extract(fmt, dst)
+                    .await
+                    .map_err(FetchError::from),
+                Err(err) => Err(err),
+            }
+        }
+    };
+    let files = res.map_err(|err| match err {
+        FetchError::Download(DownloadError::UnexpectedFormat(err)) => {
+            FetchError::UnexpectedFormat {
+                url: download_url.as_str().into(),
+                err,
+            }
+        }
+        err => err,
+    })?;
+
+    let verification = finish_verification(verifier, &mut data_verifier, name, checksum)?;
+
+    Ok((files, verification))
+}
+
+fn with_extract_filter<'a>(download: Download<'a>, filter: Option<&ExtractFilter>) -> Download<'a> {
+    match filter {
+        Some(filter) => download.with_extract_filter(filter.clone()),
+        None => download,
+    }
+}
+
+/// Like [`download_and_extract`], but load the package from `cache` if it
+/// is cached, and save it to `cache` otherwise.
+///
+/// A cached package is verified like a downloaded one, and downloaded again
+/// if it fails verification.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn download_and_extract_cached(
+    cache: Option<CacheEntry<'_>>,
+    client: Client,
+    gh_api_client: &GhApiClient,
+    url: &Url,
+    download_url: &Url,
+    fmt: PkgFmt,
+    dst: &Path,
+    filter: Option<&ExtractFilter>,
+    name: &str,
+    verifier: &SignatureVerifier,
+    checksum: Option<&str>,
+    progress: &dyn DownloadProgress,
+) -> Result<(ExtractedFiles, Verification), FetchError> {
+    let package = match &cache {
+        Some(cache) => {
+            CachedPackage::fetch(
+                cache,
+                client.clone(),
+                gh_api_client,
+                url,
+                download_url,
+                name,
+                verifier,
+                checksum,
+                progress,
+            )
+            .await?
+        }
+        None => None,
+    };
+    let (Some(cache), Some(package)) = (cache, package) else {
+        return download_and_extract(
+            client,
+            gh_api_client,
+            url,
+            download_url,
+            fmt,
+            dst,
+            filter,
+            name,
+            verifier,
+            checksum,
+            progress,
+        )
+        .await;
+    };
+
+    let files = extract_file(&package.path, fmt, dst, filter).await?;
+
+    Ok((files, package.store(&cache, url)))
+}
+
+/// Like [`download_and_save`], but copy the package from `cache` if it is
+/// cached, and save it to `cache` otherwise, so that it can be installed
+/// in offline mode afterwards.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn download_and_save_cached(
+    cache: Option<CacheEntry<'_>>,
+    client: Client,
+    gh_api_client: &GhApiClient,
+    url: &Url,
+    download_url: &Url,
+    dir: &Path,
+    name: &str,
+    verifier: &SignatureVerifier,
+    checksum: Option<&str>,
+    progress: &dyn DownloadProgress,
+) -> Result<(SavedArtifact, Verification), FetchError> {
+    let package = match &cache {
+        Some(cache) => {
+            CachedPackage::fetch(
+                cache,
+                client.clone(),
+                gh_api_client,
+                url,
+                download_url,
+                name,
+                verifier,
+                checksum,
+                progress,
+            )
+            .await?
+        }
+        None => None,
+    };
+    let (Some(cache), Some(package)) = (cache, package) else {
+        return download_and_save(
+            client,
+            gh_api_client,
+            url,
+            download_url,
+            dir,
+            name,
+            verifier,
+            checksum,
+            progress,
+        )
+        .await;
+    };
+
+    let artifact = copy_artifact(&package.path, dir, &package.verification.sha256)
+        .map_err(DownloadError::from)?;
+
+    Ok((artifact, package.store(&cache, url)))
+}
+
+/// A verified package of the [`ArtifactCache`](crate::ArtifactCache),
+/// either cached or downloaded to a staging directory.
+struct CachedPackage {
+    path: PathBuf,
+    verification: Verification,
+    /// The directory the package was downloaded to, if it is not cached.
+    staging_dir: Option<TempDir>,
+}
+
+impl CachedPackage {
+    /// Return the package at `url` in `cache` if it passes verification,
+    /// or download it otherwise.
+    ///
+    /// Return `None` if it cannot be downloaded to the cache.
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch(
+        cache: &CacheEntry<'_>,
+        client: Client,
+        gh_api_client: &GhApiClient,
+        url: &Url,
+        download_url: &Url,
+        name: &str,
+        verifier: &SignatureVerifier,
+        checksum: Option<&str>,
+        progress: &dyn DownloadProgress,
+    ) -> Result<Option<Self>, FetchError> {
+        if let Some(path) = cache.cached() {
+            debug!(url=%url, path=%path.display(), "Using cached package");
+
+            match verify_file(path, name, verifier, checksum) {
+                Ok(verification) => {
+                    cache.touch();
+                    return Ok(Some(Self {
+                        path: path.to_owned(),
+                        verification,
+                        staging_dir: None,
+                    }));
                 }
+                Err(err) => {
+                    warn!("Downloading package '{name}' again, since the cached one at '{}' failed verification: {err}", path.display());
+                    cache.remove();
+                }
+            }
+        }
+
+        let staging_dir = match cache.staging_dir() {
+            Ok(staging_dir) => staging_dir,
+            Err(err) => {
+                warn!("Failed to create a directory in the cache, not caching '{url}': {err}");
+                return Ok(None);
+            }
+        };
+
+        let (artifact, verification) = download_and_save(
+            client,
+            gh_api_client,
+            url,
+            download_url,
+            staging_dir.path(),
+            name,
+            verifier,
+            checksum,
+            progress,
+        )
+        .await?;
+
+        Ok(Some(Self {
+            path: artifact.path,
+            verification,
+            staging_dir: Some(staging_dir),
+        }))
+    }
+
+    /// Move the downloaded package into `cache`, once it is used.
+    fn store(self, cache: &CacheEntry<'_>, url: &Url) -> Verification {
+        if let Some(staging_dir) = self.staging_dir {
+            if let Err(err) = cache.store(staging_dir) {
+                warn!("Failed to cache '{url}': {err}");
             }
+        }
+
+        self.verification
+    }
+}
+
+/// Copy the package at `path` to `dir`, along with a file containing its
+/// `sha256` like the one written by [`Download::and_save`].
+fn copy_artifact(path: &Path, dir: &Path, sha256: &str) -> io::Result<SavedArtifact> {
+    let file_name = path.file_name().expect("package must have a file name");
+    let dst = dir.join(file_name);
+    fs::create_dir_all(dir)?;
+    fs::copy(path, &dst)?;
+
+    let mut digest_path = dst.clone().into_os_string();
+    digest_path.push(".sha256");
+    let digest_path = PathBuf::from(digest_path);
+    fs::write(
+        &digest_path,
+        format!("{sha256}  {}\n", file_name.to_string_lossy()),
+    )?;
+
+    Ok(SavedArtifact {
+        path: dst,
+        sha256: sha256.into(),
+        digest_path,
+    })
+}
+
+/// Download `url` from `download_url`, e.g. the signature of a package, and
+/// save it to `cache` alongside the package.
+///
+/// In offline mode, it is loaded from `cache` instead.
+pub(super) async fn download_bytes_cached(
+    client: Client,
+    cache: Option<&CacheEntry<'_>>,
+    url: &Url,
+    download_url: Url,
+) -> Result<Bytes, FetchError> {
+    if client.is_offline() {
+        if let Some(data) = cache.and_then(|cache| cache.load_file(url)) {
+            debug!(url=%url, "Using cached file");
+            return Ok(data.into());
+        }
+    }
+
+    let data = Download::new(client, download_url).into_bytes().await?;
+
+    if let Some(cache) = cache {
+        if let Err(err) = cache.save_file(url, &data) {
+            warn!("Failed to cache '{url}': {err}");
+        }
+    }
+
+    Ok(data)
+}
+
+/// Verify the package at `path` like [`download_and_extract`] does.
+fn verify_file(
+    path: &Path,
+    name: &str,
+    verifier: &SignatureVerifier,
+    checksum: Option<&str>,
+) -> Result<Verification, FetchError> {
+    let mut data_verifier = verifier.data_verifier()?;
+    let mut data_verifier = Sha256Verifier::new(data_verifier.as_mut());
+
+    let mut file = fs::File::open(path).map_err(DownloadError::from)?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let n = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(DownloadError::from(err).into()),
+        };
+        data_verifier.update(&Bytes::copy_from_slice(&buffer[..n]));
+    }
+
+    finish_verification(verifier, &mut data_verifier, name, checksum)
+}
+
+/// Download the package at `url` to `dir` without extracting it, checking
+/// its signature using `verifier` and its sha256 against `checksum`, if any.
+///
+/// The file is named after the last segment of `url` and removed if the
+/// signature is invalid or the checksum does not match. Like in [`download_and_extract`], `gh_api_client`
+/// is used if it cannot be downloaded directly.
+///
+///  * `download_url` - where `url` is downloaded from, see
+///    [`UrlRewrites`](crate::UrlRewrites)
+///  * `name` - name of the package, used for logging
+///  * `progress` - receiver of the progress of the download
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn download_and_save(
+    client: Client,
+    gh_api_client: &GhApiClient,
+    url: &Url,
+    download_url: &Url,
+    dir: &Path,
+    name: &str,
+    verifier: &SignatureVerifier,
+    checksum: Option<&str>,
+    progress: &dyn DownloadProgress,
+) -> Result<(SavedArtifact, Verification), FetchError> {
+    let path = dir.join(artifact_file_name(url));
+    debug!(url=%download_url, path=%path.display(), "Downloading package without extracting");
+
+    let mut data_verifier = verifier.data_verifier()?;
+    let mut data_verifier = Sha256Verifier::new(data_verifier.as_mut());
+    let res =
+        Download::new_with_data_verifier(client.clone(), download_url.clone(), &mut data_verifier)
+            .with_progress(progress)
+            .and_save(&path)
+            .await;
+    let artifact = match res {
+        Ok(artifact) => artifact,
+        Err(err) => {
+            download_through_gh_api(
+                &client,
+                gh_api_client,
+                url,
+                err,
+                &mut data_verifier,
+                progress,
+            )
+            .await?
+            .and_save(&path)
+            .await?
+        }
+    };
 
-            GH_API_CLIENT_FAILED.store(true, Relaxed);
+    match finish_verification(verifier, &mut data_verifier, name, checksum) {
+        Ok(verification) => Ok((artifact, verification)),
+        Err(err) => {
+            std::fs::remove_file(&artifact.path).ok();
+            std::fs::remove_file(&artifact.digest_path).ok();
+            Err(err)
         }
     }
+}
+
+/// Validate the downloaded data and return how it is verified.
+fn finish_verification(
+    verifier: &SignatureVerifier,
+    data_verifier: &mut Sha256Verifier<'_>,
+    name: &str,
+    checksum: Option<&str>,
+) -> Result<Verification, FetchError> {
+    trace!("validating signature (if any)");
+    if !data_verifier.validate() {
+        return Err(verifier.invalid_error());
+    }
+
+    let signature = verifier.check();
+    if let SignatureCheck::Verified { .. } = &signature {
+        info!("Signature of package '{name}' {signature}");
+    }
+
+    let sha256 = data_verifier
+        .sha256()
+        .expect("sha256 is available after validate() is called");
+
+    if let Some(checksum) = checksum {
+        if !checksum.eq_ignore_ascii_case(sha256) {
+            return Err(FetchError::ChecksumMismatch {
+                expected: checksum.into(),
+                actual: sha256.into(),
+            });
+        }
+        info!("Verified checksum for package '{name}'");
+    }
+
+    Ok(Verification {
+        sha256: sha256.into(),
+        signature,
+    })
+}
 
-    Ok(Box::pin(client.remote_gettable(url.clone())).await?)
+/// Return the last segment of `url` if it is a valid file name.
+pub(super) fn artifact_file_name(url: &Url) -> &str {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !matches!(*name, "" | "." | "..") && !name.contains('\\'))
+        .unwrap_or("artifact")
 }
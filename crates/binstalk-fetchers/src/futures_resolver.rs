@@ -1,5 +1,5 @@
-use std::{future::Future, pin::Pin};
-use tokio::sync::mpsc;
+use std::{future::Future, panic, pin::Pin};
+use tokio::{sync::mpsc, task::JoinSet};
 
 /// Given multiple futures with output = `Result<Option<T>, E>`,
 /// returns the the first one that returns either `Err(_)` or
@@ -74,3 +74,110 @@ impl<T: Send + 'static, E: Send + 'static> FuturesResolver<T, E> {
         async move { rx.recv().await.transpose() }
     }
 }
+
+/// Given multiple futures with output = `Result<Option<T>, E>` in order of
+/// priority, returns the first one in that order that returns either
+/// `Err(_)` or `Ok(Some(_))`.
+///
+/// Unlike [`FuturesResolver`], which returns whichever completes first,
+/// the output of a future is only used once all the ones before it returned
+/// `Ok(None)`, but without waiting for the ones after it: they all run at
+/// once, and the ones still running are aborted once the result is known.
+pub(crate) async fn resolve_in_order<T, E, Fut, Iter>(iter: Iter) -> Result<Option<T>, E>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    Fut: Future<Output = Result<Option<T>, E>> + Send + 'static,
+    Iter: IntoIterator<Item = Fut>,
+{
+    let mut set = JoinSet::new();
+    for (i, fut) in iter.into_iter().enumerate() {
+        set.spawn(async move { (i, fut.await) });
+    }
+
+    let mut results: Vec<_> = (0..set.len()).map(|_| None).collect();
+    // Index of the first future whose output is not known yet.
+    let mut next = 0;
+
+    while let Some(res) = set.join_next().await {
+        let (i, res) = res.unwrap_or_else(|err| panic::resume_unwind(err.into_panic()));
+        results[i] = Some(res);
+
+        while let Some(res) = results.get_mut(next).and_then(Option::take) {
+            match res {
+                Ok(None) => next += 1,
+                // Dropping `set` aborts the futures still running.
+                res => return res,
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{future::pending, time::Duration};
+
+    use tokio::time::{sleep, timeout};
+
+    use super::*;
+
+    type Res = Result<Option<usize>, ()>;
+
+    async fn after(millis: u64, res: Res) -> Res {
+        sleep(Duration::from_millis(millis)).await;
+        res
+    }
+
+    #[tokio::test]
+    async fn test_resolve_in_order() {
+        // A lower priority success is only used once the ones before it
+        // failed.
+        let res = resolve_in_order([
+            Box::pin(after(100, Ok(None))) as Pin<Box<dyn Future<Output = Res> + Send>>,
+            Box::pin(after(200, Ok(Some(1)))),
+            Box::pin(after(0, Ok(Some(2)))),
+        ])
+        .await;
+        assert_eq!(res, Ok(Some(1)));
+
+        let res = resolve_in_order([after(100, Err(())), after(0, Ok(Some(1)))]).await;
+        assert_eq!(res, Err(()));
+
+        let res = resolve_in_order([after(100, Ok(None)), after(0, Ok(None))]).await;
+        assert_eq!(res, Ok(None));
+
+        let res =
+            resolve_in_order(std::iter::empty::<Pin<Box<dyn Future<Output = Res> + Send>>>()).await;
+        assert_eq!(res, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_in_order_does_not_wait_for_slow_ones() {
+        // The slow endpoint never answers, e.g. until it times out.
+        let slow = || Box::pin(pending()) as Pin<Box<dyn Future<Output = Res> + Send>>;
+
+        // Checked one after the other, this would take 2s even if the slow
+        // endpoint was never checked.
+        let res = timeout(
+            Duration::from_millis(1900),
+            resolve_in_order([
+                Box::pin(after(1000, Ok(None))),
+                Box::pin(after(1000, Ok(Some(1)))),
+                slow(),
+            ]),
+        )
+        .await
+        .expect("the futures were not resolved at once");
+        assert_eq!(res, Ok(Some(1)));
+
+        let res = timeout(
+            Duration::from_secs(10),
+            resolve_in_order([Box::pin(after(100, Ok(Some(0)))), slow(), slow()]),
+        )
+        .await
+        .expect("waited for the slow endpoint");
+        assert_eq!(res, Ok(Some(0)));
+    }
+}
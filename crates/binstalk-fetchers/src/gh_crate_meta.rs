@@ -1,27 +1,48 @@
-use std::{borrow::Cow, fmt, iter, path::Path, sync::Arc};
+use std::{
+    borrow::Cow,
+    fmt, fs, iter,
+    path::{Path, PathBuf},
+    ptr,
+    sync::Arc,
+};
 
+use binstalk_downloader::download::DownloadError;
+use binstalk_types::cargo_toml_binstall::{default_binary_ext, PkgMeta, SigningAlgorithm};
 use compact_str::{CompactString, ToCompactString};
 use either::Either;
 use leon::Template;
 use once_cell::sync::OnceCell;
 use strum::IntoEnumIterator;
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, error, info, trace, warn, Instrument};
 use url::Url;
 
 use crate::{
-    common::*, futures_resolver::FuturesResolver, Data, FetchError, InvalidPkgFmtError, RepoInfo,
-    SignaturePolicy, SignatureVerifier, TargetDataErased,
+    checksum, common::*, futures_resolver::FuturesResolver, ArtifactCache, CacheEntry,
+    ChecksumPolicy, Data, FetchError, InvalidPkgFmtError, RemoteError, RepoInfo, SignaturePolicy,
+    SignatureVerifier, TargetDataErased,
 };
 
 pub(crate) mod hosting;
 
+mod url_template;
+pub use url_template::UrlTemplateError;
+
+mod url_rewrite;
+pub use url_rewrite::{UrlRewrite, UrlRewriteError, UrlRewrites};
+
 pub struct GhCrateMeta {
     client: Client,
     gh_api_client: GhApiClient,
+    gl_api_client: GlApiClient,
+    gitea_api_client: GiteaApiClient,
     data: Arc<Data>,
     target_data: Arc<TargetDataErased>,
     signature_policy: SignaturePolicy,
-    resolution: OnceCell<Resolved>,
+    checksum_policy: ChecksumPolicy,
+    artifact_cache: Option<ArtifactCache>,
+    /// The artifact of the crate, or of each of its binaries if pkg-url
+    /// contains `{ bin }`, never empty.
+    resolution: OnceCell<Vec<Resolved>>,
 }
 
 #[derive(Debug)]
@@ -31,70 +52,572 @@ struct Resolved {
     archive_suffix: Option<String>,
     repo: Option<String>,
     subcrate: Option<String>,
+    repo_subpath: Option<String>,
+    /// The binary this artifact is for, if the crate publishes one
+    /// artifact per binary.
+    bin: Option<CompactString>,
+    /// Size of the artifact, if it is known from checking its existence.
+    size: Option<u64>,
+    /// Source of the template `url` is rendered from, `None` if it was
+    /// found by its name similar to the one rendered.
+    template: Option<CompactString>,
 }
 
 impl GhCrateMeta {
-    fn launch_baseline_find_tasks(
+    fn resolution(&self) -> &[Resolved] {
+        self.resolution.get().unwrap() // find() is called first
+    }
+
+    /// Return where the artifact of `resolved` is extracted to, which is
+    /// `dst` except for the raw binaries of a crate publishing one artifact
+    /// per binary: they are saved in `dst` under the name of the binary.
+    fn extract_dst(dst: &Path, resolved: &Resolved) -> Result<PathBuf, FetchError> {
+        match &resolved.bin {
+            Some(bin) if resolved.pkg_fmt.is_bin() => {
+                fs::create_dir_all(dst).map_err(DownloadError::from)?;
+                Ok(dst.join(bin.as_str()))
+            }
+            _ => Ok(dst.to_owned()),
+        }
+    }
+
+    /// Return where `url` is downloaded from, see [`UrlRewrites`].
+    fn download_url(&self, url: &Url) -> Url {
+        match self.data.url_rewrites.rewrite(url) {
+            Some(download_url) => {
+                debug!("Rewrote '{url}' to '{download_url}'");
+                download_url
+            }
+            None => url.clone(),
+        }
+    }
+
+    /// Like [`GhCrateMeta::download_url`], but log the rewrite of the url of
+    /// the package since it is the one installed.
+    fn package_download_url(&self, resolved: &Resolved) -> Url {
+        let download_url = self.download_url(&resolved.url);
+        if download_url != resolved.url {
+            info!("Downloading '{}' from '{download_url}'", resolved.url);
+        }
+        download_url
+    }
+
+    fn cache_entry(&self, resolved: &Resolved) -> Option<CacheEntry<'_>> {
+        self.artifact_cache
+            .as_ref()
+            .map(|cache| cache.entry(&resolved.url, &self.data.version))
+    }
+
+    async fn signature_verifier(
         &self,
-        futures_resolver: &FuturesResolver<Resolved, FetchError>,
-        pkg_fmt: PkgFmt,
-        pkg_url: &Template<'_>,
+        resolved: &Resolved,
+    ) -> Result<SignatureVerifier, FetchError> {
+        Ok(
+            match (self.signature_policy, &self.target_data.meta.signing) {
+                (SignaturePolicy::Ignore, _) => SignatureVerifier::Skipped,
+                (SignaturePolicy::IfPresent, None) => SignatureVerifier::NotPublished,
+                (SignaturePolicy::Require, None) => {
+                    return Err(FetchError::MissingSignature);
+                }
+                (_, Some(config)) => {
+                    let template = match (config.file.as_deref(), config.algorithm) {
+                        (Some(file), _) => url_template::parse(file)?,
+                        (None, SigningAlgorithm::Sigstore) => {
+                            leon_macros::template!("{ url }.sigstore.json")
+                        }
+                        (None, _) => leon_macros::template!("{ url }.sig"),
+                    };
+                    trace!(?template, "parsed signature file template");
+
+                    let sign_url = self.render_with_resolution(resolved, &template)?;
+
+                    debug!(?sign_url, "Downloading signature");
+                    let signature = download_bytes_cached(
+                        self.client.clone(),
+                        self.cache_entry(resolved).as_ref(),
+                        &sign_url,
+                        self.download_url(&sign_url),
+                    )
+                    .await?;
+                    trace!(?signature, "got signature contents");
+
+                    SignatureVerifier::new(config, &signature, resolved.repo.as_deref())?
+                }
+            },
+        )
+    }
+
+    /// Download the checksum of the package published by the crate, falling
+    /// back to the digest GitHub returns for the artifacts of releases.
+    async fn checksum(&self, resolved: &Resolved) -> Result<Option<CompactString>, FetchError> {
+        let meta = &self.target_data.meta;
+        let name = &self.data.name;
+
+        let Some(checksum_url) = meta.pkg_checksum_url.as_deref() else {
+            if let Some(digest) = release_artifact_sha256(&self.gh_api_client, &resolved.url).await
+            {
+                debug!("Using the digest of the GitHub release artifact as checksum");
+                return Ok(Some(digest));
+            }
+            return match self.checksum_policy {
+                ChecksumPolicy::IfPresent => Ok(None),
+                ChecksumPolicy::Require => Err(FetchError::MissingChecksum),
+            };
+        };
+
+        let digest_len = checksum::digest_len(meta.pkg_checksum_algo.unwrap_or_default())?;
+        let template = url_template::parse(checksum_url)?;
+        let checksum_url = self.render_with_resolution(resolved, &template)?;
+        let file_name = artifact_file_name(&resolved.url);
+
+        debug!(%checksum_url, "Downloading checksum");
+        let reason = match download_bytes_cached(
+            self.client.clone(),
+            self.cache_entry(resolved).as_ref(),
+            &checksum_url,
+            self.download_url(&checksum_url),
+        )
+        .await
+        {
+            Ok(contents) => {
+                let contents = String::from_utf8_lossy(&contents);
+                match checksum::find_checksum(&contents, file_name, digest_len) {
+                    Some(checksum) => return Ok(Some(checksum)),
+                    None => format!("{checksum_url} has no checksum for {file_name}"),
+                }
+            }
+            Err(err) => format!("failed to download {checksum_url}: {err}"),
+        };
+
+        if let Some(digest) = release_artifact_sha256(&self.gh_api_client, &resolved.url).await {
+            debug!("Using the digest of the GitHub release artifact as checksum, {reason}");
+            return Ok(Some(digest));
+        }
+
+        if self.checksum_policy == ChecksumPolicy::Require {
+            error!("Checksum of package '{name}' is missing, {reason}");
+            return Err(FetchError::MissingChecksum);
+        }
+
+        warn!("Not verifying the checksum of package '{name}', {reason}");
+        Ok(None)
+    }
+
+    /// Render `template` with the context of the package at `resolved`,
+    /// including its `url`.
+    fn render_with_resolution(
+        &self,
+        resolved: &Resolved,
+        template: &Template<'_>,
+    ) -> Result<Url, FetchError> {
+        let pkg_tag = self.pkg_tag()?;
+
+        Context::from_data_with_repo(
+            &self.data,
+            &self.target_data.target,
+            &self.target_data.target_related_info,
+            resolved.archive_suffix.as_deref(),
+            resolved.repo.as_deref(),
+            resolved.subcrate.as_deref(),
+            resolved.repo_subpath.as_deref(),
+        )
+        .with_bin(resolved.bin.as_deref())
+        .with_tag(pkg_tag.as_ref())?
+        .with_binary_ext(self.target_data.meta.binary_ext.as_deref())
+        .with_url(&resolved.url)
+        .render_url_with(template)
+    }
+
+    /// The `pkg-tag` template of the crate, if any.
+    fn pkg_tag(&self) -> Result<Option<Template<'_>>, FetchError> {
+        self.target_data
+            .meta
+            .pkg_tag
+            .as_deref()
+            .map(url_template::parse)
+            .transpose()
+    }
+
+    /// Render the urls of `candidate`, one per file extension of its format
+    /// if the template depends on the format.
+    fn render_candidate(
+        &self,
+        candidate: &Candidate<'_>,
         repo: Option<&str>,
         subcrate: Option<&str>,
-    ) {
-        let render_url = |ext| {
-            let ctx = Context::from_data_with_repo(
+        repo_subpath: Option<&str>,
+        bin: Option<&str>,
+    ) -> Result<Vec<Resolved>, FetchError> {
+        let Candidate { template, pkg_fmt } = *candidate;
+
+        let is_windows = self.target_data.target.contains("windows");
+
+        let exts = if url_template::has_any_of_keys(template, FORMAT_KEYS) {
+            Either::Left(pkg_fmt.extensions(is_windows).iter().copied().map(Some))
+        } else {
+            Either::Right(iter::once(None))
+        };
+
+        let pkg_tag = self.pkg_tag()?;
+
+        let mut resolutions = Vec::new();
+        for ext in exts {
+            let mut ctx = Context::from_data_with_repo(
                 &self.data,
                 &self.target_data.target,
                 &self.target_data.target_related_info,
                 ext,
                 repo,
                 subcrate,
+                repo_subpath,
             );
-            match ctx.render_url_with(pkg_url) {
-                Ok(url) => Some(url),
-                Err(err) => {
-                    warn!("Failed to render url for {ctx:#?}: {err}");
-                    None
-                }
+            ctx.with_bin(bin)
+                .with_tag(pkg_tag.as_ref())?
+                .with_binary_ext(self.target_data.meta.binary_ext.as_deref());
+
+            match ctx.render_url_with(template) {
+                Ok(url) => resolutions.push(Resolved {
+                    url,
+                    pkg_fmt,
+                    archive_suffix: ext.map(ToString::to_string),
+                    repo: repo.map(ToString::to_string),
+                    subcrate: subcrate.map(ToString::to_string),
+                    repo_subpath: repo_subpath.map(ToString::to_string),
+                    bin: bin.map(CompactString::from),
+                    size: None,
+                    template: Some(url_template::source(template)),
+                }),
+                // Do not request anything on behalf of a template rendering
+                // to an unsafe url.
+                Err(err @ FetchError::UrlTemplate(_)) => return Err(err),
+                Err(err) => warn!("Failed to render url for {ctx:#?}: {err}"),
             }
-        };
+        }
 
-        let is_windows = self.target_data.target.contains("windows");
+        Ok(resolutions)
+    }
 
-        let urls = if pkg_url.has_any_of_keys(&["format", "archive-format", "archive-suffix"]) {
-            // build up list of potential URLs
-            Either::Left(
-                pkg_fmt
-                    .extensions(is_windows)
-                    .iter()
-                    .filter_map(|ext| render_url(Some(ext)).map(|url| (url, Some(ext)))),
-            )
-        } else {
-            Either::Right(render_url(None).map(|url| (url, None)).into_iter())
-        };
+    fn launch_baseline_find_tasks(
+        &self,
+        futures_resolver: &FuturesResolver<Resolved, FetchError>,
+        candidates: &[Candidate<'_>],
+        repo: Option<&str>,
+        subcrate: Option<&str>,
+        repo_subpath: Option<&str>,
+        bin: Option<&str>,
+    ) -> Result<(), FetchError> {
+        let mut resolutions = Vec::new();
+        for candidate in candidates {
+            resolutions.extend(self.render_candidate(
+                candidate,
+                repo,
+                subcrate,
+                repo_subpath,
+                bin,
+            )?);
+        }
+
+        if self.client.is_offline() {
+            // Only the cached packages can be installed offline.
+            let resolved = resolutions.into_iter().find(|resolved| {
+                self.cache_entry(resolved)
+                    .map_or(false, |cache| cache.cached().is_some())
+            });
+            futures_resolver.push(async move { Ok(resolved) });
+
+            return Ok(());
+        }
+
+        let urls: Vec<_> = resolutions
+            .iter()
+            .map(|resolved| resolved.url.clone())
+            .collect();
+
+        if let Some(batch) = batchable_gh_release_artifacts(&self.gh_api_client, &urls) {
+            // All potential URLs are artifacts of the same GitHub release,
+            // check them all against one fetch of the release.
+            let client = self.client.clone();
+            let gh_api_client = self.gh_api_client.clone();
+            let download_urls: Vec<_> = urls.iter().map(|url| self.download_url(url)).collect();
+
+            futures_resolver.push(async move {
+                let found = find_first_existing_release_artifact(
+                    client,
+                    gh_api_client,
+                    batch,
+                    &download_urls,
+                )
+                .await?;
+
+                Ok(found.map(|(i, size)| {
+                    let mut resolved = resolutions.swap_remove(i);
+                    resolved.size = size;
+                    resolved
+                }))
+            });
+
+            return Ok(());
+        }
 
         // go check all potential URLs at once
-        futures_resolver.extend(urls.map(move |(url, ext)| {
+        futures_resolver.extend(resolutions.into_iter().map(move |resolved| {
             let client = self.client.clone();
             let gh_api_client = self.gh_api_client.clone();
+            let gl_api_client = self.gl_api_client.clone();
+            let gitea_api_client = self.gitea_api_client.clone();
+            let download_url = self.download_url(&resolved.url);
 
-            let repo = repo.map(ToString::to_string);
-            let subcrate = subcrate.map(ToString::to_string);
-            let archive_suffix = ext.map(ToString::to_string);
             async move {
-                Ok(does_url_exist(client, gh_api_client, &url)
-                    .await?
-                    .then_some(Resolved {
-                        url,
-                        pkg_fmt,
-                        repo,
-                        subcrate,
-                        archive_suffix,
-                    }))
+                let size = does_url_exist(
+                    client,
+                    gh_api_client,
+                    gl_api_client,
+                    gitea_api_client,
+                    &resolved.url,
+                    &download_url,
+                )
+                .await?;
+
+                Ok(size.map(|size| Resolved { size, ..resolved }))
             }
         }));
+
+        Ok(())
+    }
+
+    /// Return one of `candidates` that exists, probing them all at once.
+    ///
+    /// If `in_order`, the candidates of a template are only probed once none
+    /// of the ones of the previous templates exists, so that the first
+    /// template with an existing candidate is used.
+    #[allow(clippy::too_many_arguments)]
+    async fn resolve_candidates(
+        &self,
+        candidates: &[Candidate<'_>],
+        in_order: bool,
+        repo: Option<&str>,
+        subcrate: Option<&str>,
+        repo_subpath: Option<&str>,
+        bin: Option<&str>,
+    ) -> Result<Option<Resolved>, FetchError> {
+        let groups = if in_order {
+            split_by_template(candidates)
+        } else {
+            vec![candidates]
+        };
+
+        for candidates in groups {
+            let resolver = FuturesResolver::default();
+            self.launch_baseline_find_tasks(
+                &resolver,
+                candidates,
+                repo,
+                subcrate,
+                repo_subpath,
+                bin,
+            )?;
+
+            if let Some(resolved) = resolver.resolve().await? {
+                return Ok(Some(resolved));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Return the first of `candidates` with an artifact of the same GitHub
+    /// release whose name only differs in the case, in `-` and `_`, in
+    /// `.tgz` and `.tar.gz` or in the order of its parts, with its url.
+    async fn find_fuzzy(
+        &self,
+        candidates: &[Candidate<'_>],
+        repo: Option<&str>,
+        subcrate: Option<&str>,
+        repo_subpath: Option<&str>,
+        bin: Option<&str>,
+    ) -> Result<Option<Resolved>, FetchError> {
+        for candidate in candidates {
+            for mut resolved in
+                self.render_candidate(candidate, repo, subcrate, repo_subpath, bin)?
+            {
+                if let Some(url) =
+                    find_fuzzy_release_artifact(&self.gh_api_client, &resolved.url).await
+                {
+                    info!(
+                        "Artifact '{}' not found, using '{url}' which has a similar name",
+                        resolved.url
+                    );
+                    resolved.url = url;
+                    resolved.template = None;
+                    return Ok(Some(resolved));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Log the metadata the crate could specify to be found at `resolved`
+    /// without guessing its pkg-url.
+    fn log_guessed_pkg_url(&self, resolved: &Resolved) {
+        let Some(template) = &resolved.template else {
+            return;
+        };
+
+        info!(
+            "Found '{}' by guessing the pkg-url of {}, its authors can specify it in \
+            Cargo.toml to skip the guessing:\n\
+            [package.metadata.binstall]\n\
+            pkg-url = \"{template}\"\n\
+            pkg-fmt = \"{}\"",
+            resolved.url,
+            self.data.name,
+            resolved.pkg_fmt.to_string().to_lowercase(),
+        );
+    }
+
+    /// Find the artifact of the crate, or of its binary `bin` if `Some`,
+    /// among the urls rendered from `templates`.
+    ///
+    /// If `other_fmts`, templates depending on the format are also tried
+    /// with the formats other than `pkg_fmt`.
+    #[allow(clippy::too_many_arguments)]
+    async fn find_artifact<'t>(
+        &self,
+        templates: &'t [Template<'t>],
+        pkg_fmt: Option<PkgFmt>,
+        other_fmts: bool,
+        in_order: bool,
+        repo: Option<&str>,
+        subcrate: Option<&str>,
+        repo_subpath: Option<&str>,
+        bin: Option<&str>,
+    ) -> Result<Option<Resolved>, FetchError> {
+        let (candidates, mut fallback_candidates) = candidates(templates, pkg_fmt);
+        if !other_fmts {
+            fallback_candidates.clear();
+        }
+
+        let mut resolved = self
+            .resolve_candidates(&candidates, in_order, repo, subcrate, repo_subpath, bin)
+            .await?;
+
+        if resolved.is_none() && !fallback_candidates.is_empty() {
+            debug!(
+                ?pkg_fmt,
+                "No artifact found in pkg-fmt, trying other formats"
+            );
+
+            resolved = self
+                .resolve_candidates(
+                    &fallback_candidates,
+                    in_order,
+                    repo,
+                    subcrate,
+                    repo_subpath,
+                    bin,
+                )
+                .await?;
+        }
+
+        if resolved.is_none() && self.client.is_offline() {
+            // Report the package that would have been downloaded.
+            let missing = match candidates.first() {
+                Some(candidate) => self
+                    .render_candidate(candidate, repo, subcrate, repo_subpath, bin)?
+                    .into_iter()
+                    .next(),
+                None => None,
+            };
+            if let Some(missing) = missing {
+                return Err(RemoteError::Offline(Box::new(missing.url)).into());
+            }
+        } else if resolved.is_none() {
+            debug!("No artifact found, trying artifacts with similar names");
+
+            for candidates in [&candidates, &fallback_candidates] {
+                resolved = self
+                    .find_fuzzy(candidates, repo, subcrate, repo_subpath, bin)
+                    .await?;
+                if resolved.is_some() {
+                    break;
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+}
+
+/// Keys of pkg-url that depend on the format of the artifact.
+const FORMAT_KEYS: &[&str] = &["format", "archive-format", "archive-suffix"];
+
+/// A pkg-url template together with the format of the artifact it is
+/// rendered for.
+#[derive(Clone, Copy, Debug)]
+struct Candidate<'t> {
+    template: &'t Template<'t>,
+    pkg_fmt: PkgFmt,
+}
+
+/// Return the candidates to probe first, and the ones to probe only if none
+/// of the former exists.
+///
+/// If `pkg_fmt` is specified, templates depending on the format are retried
+/// with every other format, e.g. to find the zip of a crate which publishes
+/// tarballs for all the other targets.
+fn candidates<'t>(
+    templates: &'t [Template<'t>],
+    pkg_fmt: Option<PkgFmt>,
+) -> (Vec<Candidate<'t>>, Vec<Candidate<'t>>) {
+    let pkg_fmts = if let Some(pkg_fmt) = pkg_fmt {
+        Either::Left(iter::once(pkg_fmt))
+    } else {
+        Either::Right(PkgFmt::iter())
+    };
+
+    // Iterate over templates first, to probe all formats of the first
+    // template before the next one.
+    let primary = templates
+        .iter()
+        .flat_map(|template| {
+            pkg_fmts
+                .clone()
+                .map(move |pkg_fmt| Candidate { template, pkg_fmt })
+        })
+        .collect();
+
+    let fallback = match pkg_fmt {
+        Some(pkg_fmt) => templates
+            .iter()
+            .filter(|template| url_template::has_any_of_keys(template, FORMAT_KEYS))
+            .flat_map(|template| {
+                PkgFmt::iter()
+                    .filter(move |fmt| *fmt != pkg_fmt)
+                    .map(move |pkg_fmt| Candidate { template, pkg_fmt })
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    (primary, fallback)
+}
+
+/// Split `candidates` into the consecutive ones of the same template.
+fn split_by_template<'c, 't>(mut candidates: &'c [Candidate<'t>]) -> Vec<&'c [Candidate<'t>]> {
+    let mut groups = Vec::new();
+
+    while let Some(first) = candidates.first() {
+        let len = candidates
+            .iter()
+            .take_while(|candidate| ptr::eq(candidate.template, first.template))
+            .count();
+        let (group, rest) = candidates.split_at(len);
+        groups.push(group);
+        candidates = rest;
     }
+
+    groups
 }
 
 #[async_trait::async_trait]
@@ -102,16 +625,24 @@ impl super::Fetcher for GhCrateMeta {
     fn new(
         client: Client,
         gh_api_client: GhApiClient,
+        gl_api_client: GlApiClient,
+        gitea_api_client: GiteaApiClient,
         data: Arc<Data>,
         target_data: Arc<TargetDataErased>,
         signature_policy: SignaturePolicy,
+        checksum_policy: ChecksumPolicy,
+        artifact_cache: Option<ArtifactCache>,
     ) -> Arc<dyn super::Fetcher> {
         Arc::new(Self {
             client,
             gh_api_client,
+            gl_api_client,
+            gitea_api_client,
             data,
             target_data,
             signature_policy,
+            checksum_policy,
+            artifact_cache,
             resolution: OnceCell::new(),
         })
     }
@@ -122,15 +653,42 @@ impl super::Fetcher for GhCrateMeta {
 
             let repo = info.map(|info| &info.repo);
             let subcrate = info.and_then(|info| info.subcrate.as_deref());
+            let repo_subpath = info.and_then(|info| info.subpath.as_deref());
 
             let mut pkg_fmt = self.target_data.meta.pkg_fmt;
 
-            let pkg_urls = if let Some(pkg_url) = self.target_data.meta.pkg_url.as_deref() {
-                let template = Template::parse(pkg_url)?;
+            let pkg_url = &self.target_data.meta.pkg_url;
+            // The templates given by the crate are tried in order.
+            let in_order = pkg_url.len() > 1;
 
-                if pkg_fmt.is_none()
-                    && !template.has_any_of_keys(&["format", "archive-format", "archive-suffix"])
-                {
+            let pkg_urls = if !pkg_url.is_empty() {
+                let templates = url_template::parse_all(pkg_url)?;
+
+                let mut templates_without_format = pkg_url
+                    .iter()
+                    .zip(&templates)
+                    .filter(|(_, template)| !url_template::has_any_of_keys(template, FORMAT_KEYS))
+                    .map(|(pkg_url, _)| pkg_url);
+
+                if let Some(pkg_fmt) = pkg_fmt {
+                    // The pkg-fmt specified is used even if it does not match
+                    // the extension of the pkg-url, which will likely fail to
+                    // be extracted.
+                    for pkg_url in templates_without_format {
+                        if let Some(guess) =
+                            PkgFmt::guess_from_path(pkg_url).filter(|guess| *guess != pkg_fmt)
+                        {
+                            warn!(
+                                "Crate {crate_name}@{version} on target {target} specifies \
+                                pkg-fmt=\"{pkg_fmt}\" but its pkg-url looks like a {guess}: \
+                                pkg-url=\"{pkg_url}\"",
+                                crate_name = self.data.name,
+                                version = self.data.version,
+                                target = self.target_data.target,
+                            );
+                        }
+                    }
+                } else if let Some(pkg_url) = templates_without_format.next() {
                     // The crate does not specify the pkg-fmt, yet its pkg-url
                     // template doesn't contains format, archive-format or
                     // archive-suffix which is required for automatically
@@ -138,7 +696,7 @@ impl super::Fetcher for GhCrateMeta {
                     //
                     // We will attempt to guess the pkg-fmt there, but this is
                     // just a best-effort
-                    pkg_fmt = PkgFmt::guess_pkg_format(pkg_url);
+                    pkg_fmt = PkgFmt::guess_from_path(pkg_url);
 
                     let crate_name = &self.data.name;
                     let version = &self.data.version;
@@ -149,7 +707,7 @@ impl super::Fetcher for GhCrateMeta {
                             crate_name: crate_name.clone(),
                             version: version.clone(),
                             target: target.into(),
-                            pkg_url: pkg_url.into(),
+                            pkg_url: pkg_url.as_str().into(),
                             reason:
                                 &"pkg-fmt is not specified, yet pkg-url does not contain format, \
                                 archive-format or archive-suffix which is required for automatically \
@@ -167,7 +725,7 @@ impl super::Fetcher for GhCrateMeta {
                     );
                 }
 
-                Either::Left(iter::once(template))
+                Either::Left(templates.into_iter())
             } else if let Some(RepoInfo {
                 repo,
                 repository_host,
@@ -176,6 +734,8 @@ impl super::Fetcher for GhCrateMeta {
             {
                 if let Some(pkg_urls) = repository_host.get_default_pkg_url_template() {
                     let has_subcrate = subcrate.is_some();
+                    // A subpath of one directory is the same as the subcrate.
+                    let has_nested_subpath = repo_subpath.map_or(false, |p| p.contains('/'));
 
                     Either::Right(
                         pkg_urls
@@ -183,7 +743,10 @@ impl super::Fetcher for GhCrateMeta {
                             // If subcrate is Some, then all templates will be included.
                             // Otherwise, only templates without key "subcrate" will be
                             // included.
-                            .filter(move |template| has_subcrate || !template.has_key("subcrate")),
+                            .filter(move |template| has_subcrate || !template.has_key("subcrate"))
+                            .filter(move |template| {
+                                has_nested_subpath || !template.has_key("repo-subpath")
+                            }),
                     )
                 } else {
                     warn!(
@@ -211,108 +774,160 @@ impl super::Fetcher for GhCrateMeta {
             // Convert Option<Url> to Option<String> to reduce size of future.
             let repo = repo.map(|u| u.as_str().trim_end_matches('/'));
 
-            // Use reference to self to fix error of closure
-            // launch_baseline_find_tasks which moves `this`
-            let this = &self;
-
-            let pkg_fmts = if let Some(pkg_fmt) = pkg_fmt {
-                Either::Left(iter::once(pkg_fmt))
+            let templates: Vec<_> = pkg_urls.collect();
+            // Each binary has its own artifact if the pkg-url depends on it.
+            let bins = if !self.data.bins.is_empty()
+                && templates
+                    .iter()
+                    .any(|template| url_template::has_any_of_keys(template, &["bin"]))
+            {
+                Either::Left(self.data.bins.iter().map(|bin| Some(bin.as_str())))
             } else {
-                Either::Right(PkgFmt::iter())
+                Either::Right(iter::once(None))
             };
 
-            let resolver = FuturesResolver::default();
+            let mut resolution: Vec<Resolved> = Vec::new();
+            for bin in bins {
+                // The artifacts of the other binaries are looked for in the
+                // format of the first one.
+                let (pkg_fmt, other_fmts) = match resolution.first() {
+                    Some(first) => (Some(first.pkg_fmt), false),
+                    None => (pkg_fmt, true),
+                };
 
-            // Iterate over pkg_urls first to avoid String::clone.
-            for pkg_url in pkg_urls {
-                //             Clone iter pkg_fmts to ensure all pkg_fmts is
-                //             iterated over for each pkg_url, which is
-                //             basically cartesian product.
-                //             |
-                for pkg_fmt in pkg_fmts.clone() {
-                    this.launch_baseline_find_tasks(&resolver, pkg_fmt, &pkg_url, repo, subcrate);
+                match self
+                    .find_artifact(
+                        &templates,
+                        pkg_fmt,
+                        other_fmts,
+                        in_order,
+                        repo,
+                        subcrate,
+                        repo_subpath,
+                        bin,
+                    )
+                    .await?
+                {
+                    Some(resolved) => {
+                        debug!(?resolved, "Winning URL found!");
+                        if pkg_url.is_empty() && resolution.is_empty() {
+                            self.log_guessed_pkg_url(&resolved);
+                        }
+                        resolution.push(resolved);
+                    }
+                    None => {
+                        return match bin {
+                            Some(bin) => Err(FetchError::MissingBinArtifact(bin.into())),
+                            None => Ok(false),
+                        };
+                    }
                 }
             }
 
-            if let Some(resolved) = resolver.resolve().await? {
-                debug!(?resolved, "Winning URL found!");
-                self.resolution.set(resolved).unwrap(); // find() is called first
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        })
+            self.resolution.set(resolution).unwrap(); // find() is called first
+            Ok(true)
+        }.in_current_span())
     }
 
-    async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
-        let resolved = self.resolution.get().unwrap(); // find() is called first
-        trace!(?resolved, "preparing to fetch");
+    async fn fetch_and_extract(
+        &self,
+        dst: &Path,
+        filter: Option<&ExtractFilter>,
+        progress: &dyn DownloadProgress,
+    ) -> Result<(ExtractedFiles, Verification), FetchError> {
+        let mut extracted_files = ExtractedFiles::new();
+        let mut verification = None;
+
+        // The artifacts of all the binaries are extracted to `dst`, and
+        // the verification of the first one is reported.
+        for resolved in self.resolution() {
+            trace!(?resolved, "preparing to fetch");
+
+            let verifier = self.signature_verifier(resolved).await?;
+            let checksum = self.checksum(resolved).await?;
+            let download_url = self.package_download_url(resolved);
+
+            let (files, artifact_verification) = download_and_extract_cached(
+                self.cache_entry(resolved),
+                self.client.clone(),
+                &self.gh_api_client,
+                &resolved.url,
+                &download_url,
+                resolved.pkg_fmt,
+                &Self::extract_dst(dst, resolved)?,
+                filter,
+                &self.data.name,
+                &verifier,
+                checksum.as_deref(),
+                progress,
+            )
+            .await?;
 
-        let verifier = match (self.signature_policy, &self.target_data.meta.signing) {
-            (SignaturePolicy::Ignore, _) | (SignaturePolicy::IfPresent, None) => {
-                SignatureVerifier::Noop
-            }
-            (SignaturePolicy::Require, None) => {
-                return Err(FetchError::MissingSignature);
-            }
-            (_, Some(config)) => {
-                let template = match config.file.as_deref() {
-                    Some(file) => Template::parse(file)?,
-                    None => leon_macros::template!("{ url }.sig"),
-                };
-                trace!(?template, "parsed signature file template");
-
-                let sign_url = Context::from_data_with_repo(
-                    &self.data,
-                    &self.target_data.target,
-                    &self.target_data.target_related_info,
-                    resolved.archive_suffix.as_deref(),
-                    resolved.repo.as_deref(),
-                    resolved.subcrate.as_deref(),
-                )
-                .with_url(&resolved.url)
-                .render_url_with(&template)?;
+            extracted_files.merge(files);
+            verification.get_or_insert(artifact_verification);
+        }
 
-                debug!(?sign_url, "Downloading signature");
-                let signature = Download::new(self.client.clone(), sign_url)
-                    .into_bytes()
-                    .await?;
-                trace!(?signature, "got signature contents");
+        Ok((extracted_files, verification.unwrap()))
+    }
 
-                SignatureVerifier::new(config, &signature)?
-            }
-        };
+    async fn fetch_and_save(
+        &self,
+        dir: &Path,
+        progress: &dyn DownloadProgress,
+    ) -> Result<(SavedArtifact, Verification), FetchError> {
+        let mut saved = None;
+
+        // The artifacts of all the binaries are saved to `dir`, and the
+        // first one is reported.
+        for resolved in self.resolution() {
+            trace!(?resolved, "preparing to fetch");
+
+            let verifier = self.signature_verifier(resolved).await?;
+            let checksum = self.checksum(resolved).await?;
+            let download_url = self.package_download_url(resolved);
+
+            let artifact = download_and_save_cached(
+                self.cache_entry(resolved),
+                self.client.clone(),
+                &self.gh_api_client,
+                &resolved.url,
+                &download_url,
+                dir,
+                &self.data.name,
+                &verifier,
+                checksum.as_deref(),
+                progress,
+            )
+            .await?;
 
-        debug!(
-            url=%resolved.url,
-            dst=%dst.display(),
-            fmt=?resolved.pkg_fmt,
-            "Downloading package",
-        );
-        let mut data_verifier = verifier.data_verifier()?;
-        let files = Download::new_with_data_verifier(
-            self.client.clone(),
-            resolved.url.clone(),
-            data_verifier.as_mut(),
-        )
-        .and_extract(resolved.pkg_fmt, dst)
-        .await?;
-        trace!("validating signature (if any)");
-        if data_verifier.validate() {
-            if let Some(info) = verifier.info() {
-                info!(
-                    "Verified signature for package '{}': {info}",
-                    self.data.name
-                );
-            }
-            Ok(files)
-        } else {
-            Err(FetchError::InvalidSignature)
+            saved.get_or_insert(artifact);
         }
+
+        Ok(saved.unwrap())
     }
 
     fn pkg_fmt(&self) -> PkgFmt {
-        self.resolution.get().unwrap().pkg_fmt
+        self.resolution()[0].pkg_fmt
+    }
+
+    fn has_artifact_per_bin(&self) -> bool {
+        self.resolution()[0].bin.is_some()
+    }
+
+    async fn report_size(&self) -> Result<Option<u64>, FetchError> {
+        let mut total = 0;
+        for resolved in self.resolution() {
+            let size = match resolved.size {
+                Some(size) => Some(size),
+                None => release_artifact_size(&self.gh_api_client, &resolved.url).await,
+            };
+            let Some(size) = size else {
+                return Ok(None);
+            };
+            total += size;
+        }
+
+        Ok(Some(total))
     }
 
     fn target_meta(&self) -> PkgMeta {
@@ -324,6 +939,7 @@ impl super::Fetcher for GhCrateMeta {
     fn source_name(&self) -> CompactString {
         self.resolution
             .get()
+            .and_then(|resolution| resolution.first())
             .map(|resolved| {
                 if let Some(domain) = resolved.url.domain() {
                     domain.to_compact_string()
@@ -336,6 +952,15 @@ impl super::Fetcher for GhCrateMeta {
             .unwrap_or_else(|| "invalid url".into())
     }
 
+    fn fetch_source(&self) -> FetchSource {
+        let resolved = &self.resolution()[0];
+
+        FetchSource::GhCrateMeta {
+            url: resolved.url.clone(),
+            repo: resolved.repo.as_deref().map(CompactString::from),
+        }
+    }
+
     fn fetcher_name(&self) -> &'static str {
         "GhCrateMeta"
     }
@@ -344,6 +969,10 @@ impl super::Fetcher for GhCrateMeta {
         false
     }
 
+    fn strategy(&self) -> Strategy {
+        Strategy::CrateMetaData
+    }
+
     fn target(&self) -> &str {
         &self.target_data.target
     }
@@ -353,28 +982,101 @@ impl super::Fetcher for GhCrateMeta {
     }
 }
 
+/// Check the url templates of `meta` and of its overrides, so that a
+/// template which is malformed or uses an unknown key is reported before
+/// anything is requested.
+pub fn check_url_templates(meta: &PkgMeta) -> Result<(), FetchError> {
+    let overrides = meta.overrides.values().map(|o| {
+        (
+            &o.pkg_url,
+            o.pkg_tag.as_deref(),
+            o.pkg_checksum_url.as_deref(),
+            &o.signing,
+        )
+    });
+
+    iter::once((
+        &meta.pkg_url,
+        meta.pkg_tag.as_deref(),
+        meta.pkg_checksum_url.as_deref(),
+        &meta.signing,
+    ))
+    .chain(overrides)
+    .try_for_each(|(pkg_url, pkg_tag, pkg_checksum_url, signing)| {
+        url_template::parse_all(pkg_url)?;
+
+        let signing_file = signing.as_ref().and_then(|signing| signing.file.as_deref());
+        pkg_tag
+            .into_iter()
+            .chain(pkg_checksum_url)
+            .chain(signing_file)
+            .try_for_each(|template| url_template::parse(template).map(drop))
+    })
+}
+
+/// Split the url of a repository into its owner and its name, e.g.
+/// `cargo-bins` and `cargo-binstall` for
+/// `https://github.com/cargo-bins/cargo-binstall.git`.
+///
+/// The owner is everything before the name, e.g. a group and its subgroup
+/// on GitLab.
+fn split_repo(repo: &str) -> Option<(&str, &str)> {
+    let (_scheme, rest) = repo.split_once("://")?;
+    let (_host, path) = rest.split_once('/')?;
+    let path = path.split(['?', '#']).next()?.trim_matches('/');
+
+    let (owner, name) = path.rsplit_once('/')?;
+    let name = name.strip_suffix(".git").unwrap_or(name);
+
+    (!owner.is_empty() && !name.is_empty()).then_some((owner, name))
+}
+
 /// Template for constructing download paths
 #[derive(Clone)]
 struct Context<'c> {
     name: &'c str,
     repo: Option<&'c str>,
+    /// Owner of `repo`, empty if there is none or it cannot be parsed.
+    repo_owner: &'c str,
+    /// Name of `repo`, empty if there is none or it cannot be parsed.
+    repo_name: &'c str,
     target: &'c str,
+    /// Version without its build metadata, e.g. `1.2.3` for `1.2.3+build.5`,
+    /// since release tags and artifact names almost never include it.
     version: &'c str,
+    /// Version with its build metadata.
+    version_full: &'c str,
+
+    /// `version` parsed, `None` if it is not a valid semver version.
+    semver: Option<semver::Version>,
 
     /// Archive format e.g. tar.gz, zip
     archive_format: Option<&'c str>,
 
     archive_suffix: Option<&'c str>,
 
-    /// Filename extension on the binary, i.e. .exe on Windows, nothing otherwise
+    /// Filename extension on the binary, e.g. .exe on Windows, see
+    /// [`default_binary_ext`]
     binary_ext: &'c str,
 
     /// Workspace of the crate inside the repository.
     subcrate: Option<&'c str>,
 
+    /// Path of the crate inside the repository, e.g. `tools/mycrate`.
+    repo_subpath: Option<&'c str>,
+
+    /// Name of the binary, if the crate publishes one artifact per binary.
+    bin: Option<&'c str>,
+
+    /// Git tag of the release rendered from `pkg-tag`, `v{ version }` if
+    /// `None`.
+    tag: Option<String>,
+
     /// Url of the file being downloaded (only for signing.file)
     url: Option<&'c Url>,
 
+    allow_insecure_http: bool,
+
     target_related_info: &'c dyn leon::Values,
 }
 
@@ -383,11 +1085,17 @@ impl fmt::Debug for Context<'_> {
         f.debug_struct("Context")
             .field("name", &self.name)
             .field("repo", &self.repo)
+            .field("repo_owner", &self.repo_owner)
+            .field("repo_name", &self.repo_name)
             .field("target", &self.target)
             .field("version", &self.version)
+            .field("version_full", &self.version_full)
             .field("archive_format", &self.archive_format)
             .field("binary_ext", &self.binary_ext)
             .field("subcrate", &self.subcrate)
+            .field("repo_subpath", &self.repo_subpath)
+            .field("bin", &self.bin)
+            .field("tag", &self.tag)
             .field("url", &self.url)
             .finish_non_exhaustive()
     }
@@ -395,11 +1103,34 @@ impl fmt::Debug for Context<'_> {
 
 impl leon::Values for Context<'_> {
     fn get_value<'s>(&'s self, key: &str) -> Option<Cow<'s, str>> {
+        let (key, formatters) = url_template::split_key(key);
+
+        formatters.fold(self.value(key), |value, formatter| {
+            url_template::format(value?, formatter)
+        })
+    }
+}
+
+impl<'c> Context<'c> {
+    /// Value of `key`, before any formatter is applied.
+    fn value(&self, key: &str) -> Option<Cow<'_, str>> {
         match key {
             "name" => Some(Cow::Borrowed(self.name)),
             "repo" => self.repo.map(Cow::Borrowed),
+            "repo-owner" => Some(Cow::Borrowed(self.repo_owner)),
+            "repo-name" => Some(Cow::Borrowed(self.repo_name)),
             "target" => Some(Cow::Borrowed(self.target)),
             "version" => Some(Cow::Borrowed(self.version)),
+            // `+` is not escaped in the path of urls but it may be decoded as
+            // a space by the server.
+            "version-full" => Some(Cow::Owned(self.version_full.replace('+', "%2B"))),
+
+            // Empty if the version is not semver, like the missing parts of
+            // a version.
+            "version-major" => Some(self.version_component(|v| v.major.to_string().into())),
+            "version-minor" => Some(self.version_component(|v| v.minor.to_string().into())),
+            "version-patch" => Some(self.version_component(|v| v.patch.to_string().into())),
+            "version-prerelease" => Some(self.version_component(|v| v.pre.as_str().into())),
 
             "archive-format" => self.archive_format.map(Cow::Borrowed),
 
@@ -412,14 +1143,26 @@ impl leon::Values for Context<'_> {
 
             "subcrate" => self.subcrate.map(Cow::Borrowed),
 
+            "bin" => self.bin.map(Cow::Borrowed),
+
+            // Escaped so that it can be used in a release tag.
+            "repo-subpath" => self
+                .repo_subpath
+                .map(|subpath| Cow::Owned(subpath.replace('/', "%2F"))),
+
+            // Escaped like repo-subpath, so that it is a single segment of
+            // the url and decoded back when extracting the release.
+            "tag" => Some(Cow::Owned(match &self.tag {
+                Some(tag) => tag.replace('/', "%2F"),
+                None => format!("v{}", self.version),
+            })),
+
             "url" => self.url.map(|url| Cow::Borrowed(url.as_str())),
 
             key => self.target_related_info.get_value(key),
         }
     }
-}
 
-impl<'c> Context<'c> {
     fn from_data_with_repo(
         data: &'c Data,
         target: &'c str,
@@ -427,6 +1170,7 @@ impl<'c> Context<'c> {
         archive_suffix: Option<&'c str>,
         repo: Option<&'c str>,
         subcrate: Option<&'c str>,
+        repo_subpath: Option<&'c str>,
     ) -> Self {
         let archive_format = archive_suffix.map(|archive_suffix| {
             if archive_suffix.is_empty() {
@@ -439,37 +1183,99 @@ impl<'c> Context<'c> {
             }
         });
 
+        let (repo_owner, repo_name) = repo.and_then(split_repo).unwrap_or_default();
+
         Self {
             name: &data.name,
             repo,
+            repo_owner,
+            repo_name,
             target,
 
-            version: &data.version,
+            version: data
+                .version
+                .split_once('+')
+                .map_or(&*data.version, |(version, _build)| version),
+            version_full: &data.version,
+            semver: semver::Version::parse(&data.version).ok(),
             archive_format,
             archive_suffix,
-            binary_ext: if target.contains("windows") {
-                ".exe"
-            } else {
-                ""
-            },
+            binary_ext: default_binary_ext(target),
             subcrate,
+            repo_subpath,
+            bin: None,
+            tag: None,
             url: None,
+            allow_insecure_http: data.allow_insecure_http,
 
             target_related_info,
         }
     }
 
-    fn with_url(&mut self, url: &'c Url) -> &mut Self {
-        self.url = Some(url);
+    fn version_component<'s>(
+        &'s self,
+        f: impl FnOnce(&'s semver::Version) -> Cow<'s, str>,
+    ) -> Cow<'s, str> {
+        self.semver.as_ref().map_or(Cow::Borrowed(""), f)
+    }
+
+    fn with_bin(&mut self, bin: Option<&'c str>) -> &mut Self {
+        self.bin = bin;
         self
     }
 
-    fn render_url_with(&self, template: &Template<'_>) -> Result<Url, FetchError> {
-        debug!(?template, context=?self, "render url template");
-        Ok(Url::parse(&template.render(self)?)?)
+    /// Render `pkg_tag` as the `tag` of the release.
+    fn with_tag(&mut self, pkg_tag: Option<&Template<'_>>) -> Result<&mut Self, FetchError> {
+        if let Some(pkg_tag) = pkg_tag {
+            let tag = pkg_tag
+                .render(&*self)
+                .map_err(|err| self.render_error(pkg_tag, err.into()))?;
+            self.tag = Some(tag);
+        }
+        Ok(self)
     }
 
-    #[cfg(test)]
+    /// Override the extension of the binaries, e.g. with `binary-ext` of
+    /// the crate metadata.
+    fn with_binary_ext(&mut self, binary_ext: Option<&'c str>) -> &mut Self {
+        if let Some(binary_ext) = binary_ext {
+            self.binary_ext = binary_ext;
+        }
+        self
+    }
+
+    fn with_url(&mut self, url: &'c Url) -> &mut Self {
+        self.url = Some(url);
+        self
+    }
+
+    fn render_url_with(&self, template: &Template<'_>) -> Result<Url, FetchError> {
+        debug!(?template, context=?self, "render url template");
+        url_template::render_url(template, self, self.allow_insecure_http)
+            .map_err(|err| self.render_error(template, err))
+    }
+
+    /// Keys with a value in this context, sorted.
+    fn available_keys(&self) -> Vec<&'static str> {
+        url_template::KEYS
+            .iter()
+            .copied()
+            .filter(|key| leon::Values::get_value(self, key).is_some())
+            .collect()
+    }
+
+    /// Report the keys available if `template` uses one missing from this
+    /// context.
+    fn render_error(&self, template: &Template<'_>, err: FetchError) -> FetchError {
+        match err {
+            FetchError::TemplateRender(leon::RenderError::MissingKey(key)) => {
+                url_template::missing_key(template, &key, self.available_keys())
+            }
+            err => err,
+        }
+    }
+
+    #[cfg(test)]
     fn render_url(&self, template: &str) -> Result<Url, FetchError> {
         self.render_url_with(&Template::parse(template)?)
     }
@@ -477,9 +1283,13 @@ impl<'c> Context<'c> {
 
 #[cfg(test)]
 mod test {
-    use super::{super::Data, Context};
-    use compact_str::ToCompactString;
-    use url::Url;
+    use super::{super::Data, *};
+    use crate::{Fetcher, TargetData};
+    use binstalk_downloader::{download::DownloadError, remote::Fixture};
+    use binstalk_types::cargo_toml_binstall::PkgOverride;
+    use hosting::RepositoryHost;
+
+    use std::{num::NonZeroU16, slice};
 
     const DEFAULT_PKG_URL: &str = "{ repo }/releases/download/v{ version }/{ name }-{ target }-v{ version }.{ archive-format }";
 
@@ -501,6 +1311,7 @@ mod test {
             Some(archive_format),
             data.repo.as_deref(),
             None,
+            None,
         );
 
         let expected_url = Url::parse(expected_url).unwrap();
@@ -537,6 +1348,198 @@ mod test {
         );
     }
 
+    #[test]
+    fn missing_key() {
+        let data = Data::new(
+            "cargo-binstall".to_compact_string(),
+            "1.2.3".to_compact_string(),
+            None,
+        );
+        let target_info = leon::vals(|_| None);
+        let ctx = Context::from_data_with_repo(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            &target_info,
+            Some(".tgz"),
+            None,
+            None,
+            None,
+        );
+
+        // The crate has no repository.
+        let err = ctx.render_url(DEFAULT_PKG_URL).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Refusing url template: template `{ repo }/releases/download/v{ version }/{ name }-{ target }-v{ version }.{ archive-format }` \
+            uses unknown variable `repo`, the variables available are: archive-format, \
+            archive-suffix, binary-ext, format, name, repo-name, repo-owner, tag, target, version, \
+            version-full, version-major, version-minor, version-patch, version-prerelease"
+        );
+    }
+
+    #[test]
+    fn formatters() {
+        use leon::Values;
+
+        let data = Data::new(
+            "Cargo-Binstall".to_compact_string(),
+            "1.2.3-rc.1+build.5".to_compact_string(),
+            Some("https://github.com/cargo-bins/cargo-binstall".to_string()),
+        );
+        let target_info = leon::vals(|key| match key {
+            "target-family" | "target-os" => Some("linux".into()),
+            "target-arch" => Some("aarch64".into()),
+            "target-libc" | "target-env" => Some("gnu".into()),
+            "target-vendor" => Some("unknown".into()),
+            _ => None,
+        });
+        let url = Url::parse("https://example.com/pkg.tgz").unwrap();
+        let mut ctx = Context::from_data_with_repo(
+            &data,
+            "aarch64-unknown-linux-gnu",
+            &target_info,
+            Some(".tgz"),
+            data.repo.as_deref(),
+            Some("sub"),
+            Some("tools/sub"),
+        );
+        ctx.with_bin(Some("bin")).with_url(&url);
+
+        // Every key has a value here, so that each formatter is applied to
+        // all of them.
+        assert_eq!(ctx.available_keys(), url_template::KEYS);
+        for key in url_template::KEYS {
+            let value = ctx.get_value(key).unwrap();
+            for formatter in url_template::FORMATTERS {
+                assert_eq!(
+                    ctx.get_value(&format!("{key} | {formatter}")),
+                    url_template::format(value.clone(), formatter),
+                    "{key} | {formatter}"
+                );
+            }
+        }
+
+        assert_eq!(
+            ctx.render_url(
+                "{ repo }/releases/download/v{ version }/{ name | lowercase }-{ target-os | capitalize }-\
+                { target-arch | arch-alias }-{ version | underscore-dots }.{ archive-format | uppercase }"
+            )
+            .unwrap()
+            .as_str(),
+            "https://github.com/cargo-bins/cargo-binstall/releases/download/v1.2.3-rc.1/\
+            cargo-binstall-Linux-arm64-1_2_3-rc_1.TGZ"
+        );
+
+        // Formatters are applied in order.
+        assert_eq!(
+            ctx.get_value("name | uppercase | capitalize").as_deref(),
+            Some("CARGO-BINSTALL")
+        );
+        assert_eq!(
+            ctx.get_value("name | lowercase | capitalize").as_deref(),
+            Some("Cargo-binstall")
+        );
+
+        let err = ctx
+            .render_url("{ repo }/{ name | titlecase }.tgz")
+            .unwrap_err();
+        let FetchError::UrlTemplate(UrlTemplateError::UnknownFormatter { formatter, .. }) = &err
+        else {
+            panic!("Expected UnknownFormatter, found {err:?}");
+        };
+        assert_eq!(formatter.as_str(), "titlecase");
+    }
+
+    #[test]
+    fn url_templates_are_checked() {
+        let mut meta = PkgMeta {
+            pkg_url: vec![DEFAULT_PKG_URL.to_owned()],
+            ..Default::default()
+        };
+        check_url_templates(&meta).unwrap();
+
+        meta.overrides.insert(
+            "x86_64-pc-windows-msvc".to_owned(),
+            PkgOverride {
+                pkg_checksum_url: Some(
+                    "{ repo }/releases/download/{ tag }/{ nmae }.sha256".to_owned(),
+                ),
+                ..Default::default()
+            },
+        );
+        let err = check_url_templates(&meta).unwrap_err();
+        let FetchError::UrlTemplate(UrlTemplateError::UnknownKey { key, .. }) = &err else {
+            panic!("Expected UnknownKey, found {err:?}");
+        };
+        assert_eq!(key.as_str(), "nmae");
+    }
+
+    #[test]
+    fn repo_owner_and_name() {
+        let template =
+            "https://example.com/{ repo-owner }/{ repo-name }-v{ version }-{ target }.tgz";
+
+        for (repo, expected_url) in [
+            (
+                "https://github.com/cargo-bins/cargo-binstall",
+                "https://example.com/cargo-bins/cargo-binstall-v1.2.3-x86_64-unknown-linux-gnu.tgz",
+            ),
+            (
+                "https://gitlab.com/group/subgroup/binstall.git/",
+                "https://example.com/group/subgroup/binstall-v1.2.3-x86_64-unknown-linux-gnu.tgz",
+            ),
+            (
+                "https://git.example.org/owner/repo.git",
+                "https://example.com/owner/repo-v1.2.3-x86_64-unknown-linux-gnu.tgz",
+            ),
+            // Not a repository, so empty.
+            (
+                "https://example.org/",
+                "https://example.com//-v1.2.3-x86_64-unknown-linux-gnu.tgz",
+            ),
+        ] {
+            assert_context_rendering(
+                &Data::new(
+                    "cargo-binstall".to_compact_string(),
+                    "1.2.3".to_compact_string(),
+                    Some(repo.to_string()),
+                ),
+                "x86_64-unknown-linux-gnu",
+                ".tgz",
+                template,
+                expected_url,
+            );
+        }
+
+        let no_repo = Data::new(
+            "cargo-binstall".to_compact_string(),
+            "1.2.3".to_compact_string(),
+            None,
+        );
+        assert_context_rendering(
+            &no_repo,
+            "x86_64-unknown-linux-gnu",
+            ".tgz",
+            template,
+            "https://example.com//-v1.2.3-x86_64-unknown-linux-gnu.tgz",
+        );
+        // Templates not using them are unaffected.
+        assert_context_rendering(
+            &no_repo,
+            "x86_64-unknown-linux-gnu",
+            ".tgz",
+            "https://example.com/{ name }-v{ version }-{ target }.tgz",
+            "https://example.com/cargo-binstall-v1.2.3-x86_64-unknown-linux-gnu.tgz",
+        );
+
+        assert_eq!(split_repo("not a url"), None);
+        assert_eq!(split_repo("https://github.com/cargo-bins"), None);
+        assert_eq!(
+            split_repo("https://github.com/cargo-bins/cargo-binstall?tab=readme#install"),
+            Some(("cargo-bins", "cargo-binstall"))
+        );
+    }
+
     #[test]
     fn different_url() {
         assert_context_rendering(
@@ -552,6 +1555,128 @@ mod test {
         );
     }
 
+    #[test]
+    fn version_components() {
+        let data = Data::new(
+            "radio-sx128x".to_compact_string(),
+            "0.14.1-alpha.5".to_compact_string(),
+            Some("https://github.com/rust-iot/rust-radio-sx128x".to_string()),
+        );
+
+        assert_context_rendering(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            ".tgz",
+            "{ repo }/releases/download/{ version-major }.{ version-minor }/sx128x-util-{ version }.{ archive-format }",
+            "https://github.com/rust-iot/rust-radio-sx128x/releases/download/0.14/sx128x-util-0.14.1-alpha.5.tgz"
+        );
+        assert_context_rendering(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            ".tgz",
+            "https://example.com/{ version-major }.{ version-minor }.{ version-patch }/{ version-prerelease }/{ name }.{ archive-format }",
+            "https://example.com/0.14.1/alpha.5/radio-sx128x.tgz"
+        );
+
+        // No prerelease, and empty components for versions not semver.
+        for (version, expected) in [("9.0.0", "9-0-0-"), ("9.0", "---"), ("v1.2.3", "---")] {
+            assert_context_rendering(
+                &Data::new("tool".to_compact_string(), version.to_compact_string(), None),
+                "x86_64-unknown-linux-gnu",
+                ".tgz",
+                "https://example.com/{ version-major }-{ version-minor }-{ version-patch }-{ version-prerelease }",
+                &format!("https://example.com/{expected}"),
+            );
+        }
+    }
+
+    #[test]
+    fn pkg_tag() {
+        use binstalk_downloader::gh_api_client::{GhReleaseArtifact, GITHUB_COM};
+
+        let data = Data::new(
+            "cargo-audit".to_compact_string(),
+            "0.17.6".to_compact_string(),
+            Some("https://github.com/rustsec/rustsec".to_string()),
+        );
+        let target_info = leon::vals(|_| None);
+        let render = |pkg_tag: Option<&str>, template: &str| {
+            let pkg_tag = pkg_tag.map(|pkg_tag| Template::parse(pkg_tag).unwrap());
+            Context::from_data_with_repo(
+                &data,
+                "x86_64-unknown-linux-gnu",
+                &target_info,
+                Some(".tgz"),
+                data.repo.as_deref(),
+                None,
+                None,
+            )
+            .with_tag(pkg_tag.as_ref())
+            .unwrap()
+            .render_url(template)
+            .unwrap()
+        };
+
+        // The default tag renders like the existing templates.
+        assert_eq!(
+            render(
+                None,
+                "{ repo }/releases/download/{ tag }/{ name }-{ target }.tgz"
+            ),
+            render(
+                None,
+                "{ repo }/releases/download/v{ version }/{ name }-{ target }.tgz"
+            )
+        );
+
+        let url = render(
+            Some("{ name }/v{ version }"),
+            "{ repo }/releases/download/{ tag }/{ name }-{ target }-v{ version }.{ archive-format }",
+        );
+        assert_eq!(
+            url.as_str(),
+            "https://github.com/rustsec/rustsec/releases/download/cargo-audit%2Fv0.17.6/cargo-audit-x86_64-unknown-linux-gnu-v0.17.6.tgz"
+        );
+
+        let artifact = GhReleaseArtifact::try_extract_from_url(&url, &[GITHUB_COM]).unwrap();
+        assert_eq!(artifact.release.tag, "cargo-audit/v0.17.6");
+        assert_eq!(
+            artifact.artifact_name,
+            "cargo-audit-x86_64-unknown-linux-gnu-v0.17.6.tgz"
+        );
+    }
+
+    #[test]
+    fn build_metadata() {
+        let data = Data::new(
+            "cargo-binstall".to_compact_string(),
+            "1.2.3+abc".to_compact_string(),
+            Some("https://github.com/ryankurte/cargo-binstall".to_string()),
+        );
+
+        assert_context_rendering(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            ".tgz",
+            DEFAULT_PKG_URL,
+            "https://github.com/ryankurte/cargo-binstall/releases/download/v1.2.3/cargo-binstall-x86_64-unknown-linux-gnu-v1.2.3.tgz"
+        );
+        assert_context_rendering(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            ".tgz",
+            "{ repo }/releases/download/v{ version-full }/{ name }-{ target }.{ archive-format }",
+            "https://github.com/ryankurte/cargo-binstall/releases/download/v1.2.3%2Babc/cargo-binstall-x86_64-unknown-linux-gnu.tgz"
+        );
+        assert_context_rendering(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            ".tgz",
+            "https://example.com/{ version-major }.{ version-minor }.{ version-patch }",
+            "https://example.com/1.2.3",
+        );
+    }
+
     #[test]
     fn deprecated_format() {
         assert_context_rendering(
@@ -596,4 +1721,745 @@ mod test {
             "https://github.com/watchexec/cargo-watch/releases/download/v9.0.0/cargo-watch-v9.0.0-aarch64-pc-windows-msvc.exe"
         );
     }
+
+    #[test]
+    fn insecure_http() {
+        let data = Data::new(
+            "cargo-binstall".to_compact_string(),
+            "1.2.3".to_compact_string(),
+            None,
+        );
+        let target_info = leon::vals(|_| None);
+        let render = |data: &Data| {
+            Context::from_data_with_repo(
+                data,
+                "x86_64-unknown-linux-gnu",
+                &target_info,
+                Some(".tgz"),
+                None,
+                None,
+                None,
+            )
+            .render_url("http://mirror.internal/{ name }-{ target }.tgz")
+        };
+
+        let err = render(&data).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FetchError::UrlTemplate(UrlTemplateError::Insecure { .. })
+            ),
+            "{err:?}"
+        );
+
+        let data = data.with_allow_insecure_http(true);
+        assert_eq!(
+            render(&data).unwrap().as_str(),
+            "http://mirror.internal/cargo-binstall-x86_64-unknown-linux-gnu.tgz"
+        );
+    }
+
+    #[test]
+    fn binary_ext() {
+        let data = Data::new(
+            "cargo-watch".to_compact_string(),
+            "9.0.0".to_compact_string(),
+            None,
+        );
+        let template = "https://example.com/{ name }-{ target }{ binary-ext }";
+
+        assert_context_rendering(
+            &data,
+            "wasm32-wasi",
+            ".bin",
+            template,
+            "https://example.com/cargo-watch-wasm32-wasi.wasm",
+        );
+        assert_context_rendering(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            ".bin",
+            template,
+            "https://example.com/cargo-watch-x86_64-unknown-linux-gnu",
+        );
+    }
+
+    #[test]
+    fn binary_ext_override() {
+        let mut fetcher = gh_crate_meta("x86_64-pc-windows-msvc", Some(PkgFmt::Bin));
+        Arc::get_mut(&mut Arc::get_mut(&mut fetcher).unwrap().target_data)
+            .unwrap()
+            .meta
+            .binary_ext = Some(String::new());
+
+        let templates =
+            [Template::parse("https://example.com/{ name }-{ target }{ binary-ext }").unwrap()];
+        let (primary, _) = candidates(&templates, Some(PkgFmt::Bin));
+
+        assert_eq!(
+            rendered_urls(&fetcher, &primary)[0],
+            (
+                "https://example.com/cargo-binstall-x86_64-pc-windows-msvc".to_string(),
+                PkgFmt::Bin
+            )
+        );
+    }
+
+    #[test]
+    fn repo_subpath() {
+        let data = Data::new(
+            "mycrate".to_compact_string(),
+            "1.2.3".to_compact_string(),
+            None,
+        );
+        let target_info = leon::vals(|_| None);
+        let ctx = Context::from_data_with_repo(
+            &data,
+            "x86_64-unknown-linux-gnu",
+            &target_info,
+            Some(".tgz"),
+            Some("https://github.com/org/monorepo"),
+            Some("mycrate"),
+            Some("tools/mycrate"),
+        );
+
+        let urls: Vec<_> = RepositoryHost::GitHub
+            .get_default_pkg_url_template()
+            .unwrap()
+            .filter(|template| template.has_key("repo-subpath"))
+            .map(|template| ctx.render_url_with(&template).unwrap().to_string())
+            .collect();
+
+        for url in [
+            "https://github.com/org/monorepo/releases/download/tools%2Fmycrate-v1.2.3/mycrate-x86_64-unknown-linux-gnu-v1.2.3.tgz",
+            "https://github.com/org/monorepo/releases/download/tools%2Fmycrate%2Fv1.2.3/mycrate-x86_64-unknown-linux-gnu.tgz",
+        ] {
+            assert!(urls.iter().any(|u| u == url), "{url} not in {urls:#?}");
+        }
+    }
+
+    fn gh_crate_meta(target: &str, pkg_fmt: Option<PkgFmt>) -> Arc<GhCrateMeta> {
+        gh_crate_meta_with(target, pkg_fmt, Vec::new(), None)
+    }
+
+    fn gh_crate_meta_with(
+        target: &str,
+        pkg_fmt: Option<PkgFmt>,
+        pkg_url: Vec<String>,
+        fixture: Option<&str>,
+    ) -> Arc<GhCrateMeta> {
+        let mut client = Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        if let Some(fixture) = fixture {
+            let fixture = Fixture::replay(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("tests/fixtures")
+                    .join(fixture),
+            )
+            .unwrap();
+            client = client.with_fixture(fixture);
+        }
+
+        let data = Data::new(
+            "cargo-binstall".to_compact_string(),
+            "1.2.3".to_compact_string(),
+            None,
+        );
+        let target_data = TargetData {
+            target: target.to_string(),
+            meta: PkgMeta {
+                pkg_url,
+                pkg_fmt,
+                ..Default::default()
+            },
+            target_related_info: leon::vals(|_| None),
+        };
+
+        Arc::new(GhCrateMeta {
+            gh_api_client: GhApiClient::new(client.clone(), None),
+            gl_api_client: GlApiClient::new(client.clone(), None),
+            gitea_api_client: GiteaApiClient::new(client.clone(), None),
+            client,
+            data: Arc::new(data),
+            target_data: Arc::new(target_data),
+            signature_policy: SignaturePolicy::IfPresent,
+            checksum_policy: ChecksumPolicy::IfPresent,
+            artifact_cache: None,
+            resolution: OnceCell::new(),
+        })
+    }
+
+    fn rendered_urls(fetcher: &GhCrateMeta, candidates: &[Candidate<'_>]) -> Vec<(String, PkgFmt)> {
+        candidates
+            .iter()
+            .flat_map(|candidate| {
+                fetcher
+                    .render_candidate(candidate, None, None, None, None)
+                    .unwrap()
+            })
+            .map(|resolved| (resolved.url.to_string(), resolved.pkg_fmt))
+            .collect()
+    }
+
+    #[test]
+    fn candidates_fallback_to_other_formats() {
+        let templates = [
+            Template::parse("https://example.com/{ name }-{ target }{ archive-suffix }").unwrap(),
+            Template::parse("https://example.com/{ name }-{ target }.tgz").unwrap(),
+        ];
+
+        let (primary, fallback) = candidates(&templates, Some(PkgFmt::Tgz));
+        assert_eq!(primary.len(), 2);
+        assert!(primary
+            .iter()
+            .all(|candidate| candidate.pkg_fmt == PkgFmt::Tgz));
+
+        // Only the template depending on the format is retried.
+        assert_eq!(fallback.len(), PkgFmt::iter().count() - 1);
+        assert!(fallback
+            .iter()
+            .all(|candidate| candidate.pkg_fmt != PkgFmt::Tgz
+                && ptr::eq(candidate.template, &templates[0])));
+
+        let (primary, fallback) = candidates(&templates[..1], None);
+        assert_eq!(primary.len(), PkgFmt::iter().count());
+        assert!(fallback.is_empty());
+    }
+
+    #[test]
+    fn candidates_are_rendered_with_their_format() {
+        let fetcher = gh_crate_meta("x86_64-pc-windows-msvc", Some(PkgFmt::Tgz));
+        let templates =
+            [
+                Template::parse("https://example.com/{ name }-{ target }.{ archive-format }")
+                    .unwrap(),
+            ];
+
+        let (primary, fallback) = candidates(&templates, Some(PkgFmt::Tgz));
+
+        assert_eq!(
+            rendered_urls(&fetcher, &primary),
+            [
+                (
+                    "https://example.com/cargo-binstall-x86_64-pc-windows-msvc.tgz".to_string(),
+                    PkgFmt::Tgz
+                ),
+                (
+                    "https://example.com/cargo-binstall-x86_64-pc-windows-msvc.tar.gz".to_string(),
+                    PkgFmt::Tgz
+                ),
+            ]
+        );
+
+        let fallback = rendered_urls(&fetcher, &fallback);
+        assert!(fallback.contains(&(
+            "https://example.com/cargo-binstall-x86_64-pc-windows-msvc.zip".to_string(),
+            PkgFmt::Zip
+        )));
+        assert!(fallback.contains(&(
+            "https://example.com/cargo-binstall-x86_64-pc-windows-msvc.exe".to_string(),
+            PkgFmt::Bin
+        )));
+    }
+
+    #[test]
+    fn candidates_split_by_template() {
+        let templates = [
+            Template::parse("https://example.com/{ name }-{ target }{ archive-suffix }").unwrap(),
+            Template::parse("https://example.com/{ name }{ archive-suffix }").unwrap(),
+        ];
+
+        let (primary, _) = candidates(&templates, None);
+        let groups = split_by_template(&primary);
+        assert_eq!(groups.len(), 2);
+        for (group, template) in groups.iter().zip(&templates) {
+            assert_eq!(group.len(), PkgFmt::iter().count());
+            assert!(group
+                .iter()
+                .all(|candidate| ptr::eq(candidate.template, template)));
+        }
+
+        assert!(split_by_template(&[]).is_empty());
+    }
+
+    #[test]
+    fn pkg_fmt_override() {
+        let meta = PkgMeta {
+            pkg_url: vec!["https://example.com/{ name }-{ target }.{ archive-format }".to_string()],
+            pkg_fmt: Some(PkgFmt::Tgz),
+            overrides: [(
+                "x86_64-pc-windows-msvc".to_string(),
+                PkgOverride {
+                    pkg_fmt: Some(PkgFmt::Zip),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+
+        for (target, expected_url) in [
+            (
+                "x86_64-unknown-linux-gnu",
+                "https://example.com/cargo-binstall-x86_64-unknown-linux-gnu.tgz",
+            ),
+            (
+                "x86_64-pc-windows-msvc",
+                "https://example.com/cargo-binstall-x86_64-pc-windows-msvc.zip",
+            ),
+        ] {
+            let meta = meta.merge_overrides(meta.overrides.get(target));
+            let template = Template::parse(&meta.pkg_url[0]).unwrap();
+            let (candidates, _) = candidates(slice::from_ref(&template), meta.pkg_fmt);
+
+            let fetcher = gh_crate_meta_with(target, meta.pkg_fmt, meta.pkg_url.clone(), None);
+            let resolved = fetcher
+                .render_candidate(&candidates[0], None, None, None, None)
+                .unwrap();
+            assert_eq!(resolved[0].url.as_str(), expected_url);
+            assert_eq!(resolved[0].pkg_fmt, meta.pkg_fmt.unwrap());
+        }
+    }
+
+    #[test]
+    fn compressed_bin_archive_format() {
+        let pkg_url = "https://example.com/{ name }-{ target }.{ archive-format }";
+        let template = Template::parse(pkg_url).unwrap();
+
+        for (pkg_fmt, archive_format) in [
+            (PkgFmt::Bgz, "gz"),
+            (PkgFmt::Bxz, "xz"),
+            (PkgFmt::Bzstd, "zst"),
+        ] {
+            let fetcher = gh_crate_meta_with(
+                "x86_64-unknown-linux-musl",
+                Some(pkg_fmt),
+                vec![pkg_url.to_string()],
+                None,
+            );
+            let (candidates, _) = candidates(slice::from_ref(&template), Some(pkg_fmt));
+
+            let resolved = fetcher
+                .render_candidate(&candidates[0], None, None, None, None)
+                .unwrap();
+            assert_eq!(resolved.len(), 1);
+            assert_eq!(
+                resolved[0].url.as_str(),
+                format!(
+                    "https://example.com/cargo-binstall-x86_64-unknown-linux-musl.{archive_format}"
+                )
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn pkg_urls_are_tried_in_order() {
+        let pkg_url = [
+            "{ name }-v{ version }-{ target }.tgz",
+            "{ name }-{ target }.tgz",
+            "{ name }.tgz",
+        ]
+        .map(|file| {
+            format!("https://github.com/tools/widget/releases/download/v{{ version }}/{file}")
+        })
+        .to_vec();
+
+        let fetcher = gh_crate_meta_with(
+            "x86_64-unknown-linux-gnu",
+            Some(PkgFmt::Tgz),
+            pkg_url,
+            Some("gh-pkg-urls"),
+        );
+
+        // The first template has no artifact, both of the others do.
+        assert!(fetcher.clone().find().await.unwrap().unwrap());
+        assert_eq!(
+            fetcher.resolution()[0].url.as_str(),
+            "https://github.com/tools/widget/releases/download/v1.2.3/cargo-binstall-x86_64-unknown-linux-gnu.tgz"
+        );
+        assert_eq!(fetcher.gh_api_client.requests_used(), 1);
+    }
+
+    fn gh_checksum_fetcher(
+        checksum_url: &str,
+        checksum_policy: ChecksumPolicy,
+    ) -> Arc<GhCrateMeta> {
+        let mut fetcher = gh_crate_meta_with(
+            "x86_64-unknown-linux-gnu",
+            Some(PkgFmt::Bin),
+            vec!["https://example.com/releases/v{ version }/{ name }-{ target }".to_string()],
+            Some("gh-checksum"),
+        );
+
+        let inner = Arc::get_mut(&mut fetcher).unwrap();
+        Arc::get_mut(&mut inner.target_data)
+            .unwrap()
+            .meta
+            .pkg_checksum_url = Some(checksum_url.to_string());
+        inner.checksum_policy = checksum_policy;
+
+        fetcher
+    }
+
+    async fn fetch_with_checksum(
+        checksum_url: &str,
+        checksum_policy: ChecksumPolicy,
+    ) -> Result<(), FetchError> {
+        let fetcher = gh_checksum_fetcher(checksum_url, checksum_policy);
+        assert!(fetcher.clone().find().await.unwrap()?);
+
+        let dir = tempfile::tempdir().unwrap();
+        fetcher
+            .fetch_and_extract(&dir.path().join("cargo-binstall"), None, &())
+            .await
+            .map(drop)
+    }
+
+    #[tokio::test]
+    async fn checksum_from_manifest() {
+        fetch_with_checksum(
+            "https://example.com/releases/v{ version }/SHA256SUMS",
+            ChecksumPolicy::Require,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn checksum_from_raw_digest() {
+        fetch_with_checksum("{ url }.sha256", ChecksumPolicy::Require)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch() {
+        let err = fetch_with_checksum(
+            "https://example.com/releases/v{ version }/OTHER.sha256",
+            ChecksumPolicy::IfPresent,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(
+            matches!(&err, FetchError::ChecksumMismatch { expected, actual }
+                if expected.starts_with("d9298a10") && actual.starts_with("1537e87d")),
+            "{err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn checksum_missing() {
+        let checksum_url = "https://example.com/releases/v{ version }/MISSING.sha256";
+
+        fetch_with_checksum(checksum_url, ChecksumPolicy::IfPresent)
+            .await
+            .unwrap();
+
+        let err = fetch_with_checksum(checksum_url, ChecksumPolicy::Require)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FetchError::MissingChecksum), "{err:?}");
+    }
+
+    async fn fetch_with_release_digest(
+        target: &str,
+        checksum_policy: ChecksumPolicy,
+    ) -> Result<(), FetchError> {
+        let mut fetcher = gh_crate_meta_with(
+            target,
+            Some(PkgFmt::Bin),
+            vec![
+                "https://github.com/tools/widget/releases/download/v{ version }/{ name }-{ target }"
+                    .to_string(),
+            ],
+            Some("gh-release-digest"),
+        );
+        Arc::get_mut(&mut fetcher).unwrap().checksum_policy = checksum_policy;
+        assert!(fetcher.clone().find().await.unwrap()?);
+
+        let dir = tempfile::tempdir().unwrap();
+        fetcher
+            .fetch_and_extract(&dir.path().join("cargo-binstall"), None, &())
+            .await
+            .map(drop)
+    }
+
+    #[tokio::test]
+    async fn checksum_from_release_digest() {
+        fetch_with_release_digest("x86_64-unknown-linux-gnu", ChecksumPolicy::Require)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn release_digest_mismatch() {
+        let err = fetch_with_release_digest("aarch64-unknown-linux-gnu", ChecksumPolicy::IfPresent)
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(&err, FetchError::ChecksumMismatch { expected, actual }
+                if expected.starts_with("d9298a10") && actual.starts_with("1537e87d")),
+            "{err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn release_without_digest() {
+        fetch_with_release_digest("x86_64-unknown-linux-musl", ChecksumPolicy::IfPresent)
+            .await
+            .unwrap();
+
+        let err = fetch_with_release_digest("x86_64-unknown-linux-musl", ChecksumPolicy::Require)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FetchError::MissingChecksum), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn html_page_instead_of_package() {
+        let fetcher = gh_crate_meta_with(
+            "x86_64-unknown-linux-gnu",
+            Some(PkgFmt::Tgz),
+            vec!["https://example.com/releases/v{ version }/{ name }-{ target }.tgz".to_string()],
+            Some("gh-html-page"),
+        );
+        assert!(fetcher.clone().find().await.unwrap().unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let err = fetcher
+            .fetch_and_extract(&dir.path().join("cargo-binstall"), None, &())
+            .await
+            .unwrap_err();
+
+        assert!(
+            matches!(&err, FetchError::UnexpectedFormat { url, err }
+                if *url == "https://example.com/releases/v1.2.3/cargo-binstall-x86_64-unknown-linux-gnu.tgz"
+                    && err.content_type.as_deref() == Some("text/html; charset=utf-8")
+                    && err.head.starts_with("<!DOCTYPE html>")),
+            "{err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn url_rewrite() {
+        let mut fetcher = gh_crate_meta_with(
+            "x86_64-unknown-linux-gnu",
+            Some(PkgFmt::Bin),
+            vec![
+                "https://upstream.example.org/releases/v{ version }/{ name }-{ target }"
+                    .to_string(),
+            ],
+            Some("gh-checksum"),
+        );
+
+        let inner = Arc::get_mut(&mut fetcher).unwrap();
+        Arc::get_mut(&mut inner.data).unwrap().url_rewrites =
+            UrlRewrites::new(["https://upstream.example.org/=>https://example.com/"
+                .parse()
+                .unwrap()]);
+        Arc::get_mut(&mut inner.target_data)
+            .unwrap()
+            .meta
+            .pkg_checksum_url = Some("{ url }.sha256".to_string());
+        inner.checksum_policy = ChecksumPolicy::Require;
+
+        // Only the mirror is in the fixture.
+        assert!(fetcher.clone().find().await.unwrap().unwrap());
+        assert_eq!(
+            fetcher.resolution()[0].url.as_str(),
+            "https://upstream.example.org/releases/v1.2.3/cargo-binstall-x86_64-unknown-linux-gnu"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        fetcher
+            .fetch_and_extract(&dir.path().join("cargo-binstall"), None, &())
+            .await
+            .unwrap();
+    }
+
+    fn bin_artifacts_fetcher(bins: &[&str]) -> Arc<GhCrateMeta> {
+        let mut fetcher = gh_crate_meta_with(
+            "x86_64-unknown-linux-gnu",
+            Some(PkgFmt::Bin),
+            vec!["https://example.com/releases/v{ version }/{ bin }-{ target }".to_string()],
+            Some("gh-bin-artifacts"),
+        );
+
+        let inner = Arc::get_mut(&mut fetcher).unwrap();
+        Arc::get_mut(&mut inner.data).unwrap().bins =
+            bins.iter().copied().map(CompactString::from).collect();
+
+        fetcher
+    }
+
+    #[tokio::test]
+    async fn artifact_per_bin() {
+        let fetcher = bin_artifacts_fetcher(&["cargo-binstall", "helper"]);
+        assert!(fetcher.clone().find().await.unwrap().unwrap());
+        assert!(fetcher.has_artifact_per_bin());
+        // The sizes of both artifacts, from checking their existence.
+        assert_eq!(fetcher.report_size().await.unwrap(), Some(34));
+
+        let dir = tempfile::tempdir().unwrap();
+        let dst = dir.path().join("cargo-binstall");
+        let (files, _) = fetcher.fetch_and_extract(&dst, None, &()).await.unwrap();
+
+        for bin in ["cargo-binstall", "helper"] {
+            assert!(files.has_file(Path::new(bin)), "{files:?}");
+            assert_eq!(
+                std::fs::read_to_string(dst.join(bin)).unwrap(),
+                format!("{bin} binary")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn artifact_per_bin_missing() {
+        let fetcher = bin_artifacts_fetcher(&["cargo-binstall", "missing"]);
+        let err = fetcher.clone().find().await.unwrap().unwrap_err();
+
+        assert!(
+            matches!(&err, FetchError::MissingBinArtifact(bin) if *bin == "missing"),
+            "{err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_package() {
+        /// Record whether anything is downloaded.
+        #[derive(Default)]
+        struct Downloaded(std::sync::atomic::AtomicBool);
+
+        impl DownloadProgress for Downloaded {
+            fn update(&self, _: u64, _: Option<u64>) {
+                self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = ArtifactCache::new(cache_dir.path().to_owned());
+
+        let fetch = || async {
+            let mut fetcher = gh_checksum_fetcher("{ url }.sha256", ChecksumPolicy::Require);
+            Arc::get_mut(&mut fetcher).unwrap().artifact_cache = Some(cache.clone());
+            assert!(fetcher.clone().find().await.unwrap().unwrap());
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("cargo-binstall");
+            let downloaded = Downloaded::default();
+            fetcher
+                .fetch_and_extract(&path, None, &downloaded)
+                .await
+                .unwrap();
+
+            let cached = cache
+                .entry(&fetcher.resolution()[0].url, "1.2.3")
+                .cached()
+                .map(Path::to_owned)
+                .unwrap();
+            assert_eq!(
+                std::fs::read(path).unwrap(),
+                std::fs::read(&cached).unwrap()
+            );
+
+            (downloaded.0.into_inner(), cached)
+        };
+
+        let (downloaded, cached) = fetch().await;
+        assert!(downloaded);
+
+        // Loaded from the cache.
+        assert!(!fetch().await.0);
+
+        // A cached package failing verification is downloaded again.
+        std::fs::write(&cached, b"corrupted").unwrap();
+        assert!(fetch().await.0);
+        assert!(!fetch().await.0);
+    }
+
+    #[tokio::test]
+    async fn offline_from_cache() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = ArtifactCache::new(cache_dir.path().to_owned());
+
+        let fetcher = |offline| {
+            let mut fetcher = gh_checksum_fetcher("{ url }.sha256", ChecksumPolicy::Require);
+            let inner = Arc::get_mut(&mut fetcher).unwrap();
+            inner.artifact_cache = Some(cache.clone());
+            if offline {
+                inner.client = Client::new(
+                    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+                    None,
+                    NonZeroU16::new(10).unwrap(),
+                    1.try_into().unwrap(),
+                    [],
+                    Default::default(),
+                    None,
+                )
+                .unwrap()
+                .offline(true);
+            }
+            fetcher
+        };
+
+        let err = fetcher(true).find().await.unwrap().unwrap_err();
+        assert!(
+            matches!(&err, FetchError::Download(DownloadError::Remote(RemoteError::Offline(url)))
+                if url.as_str() == "https://example.com/releases/v1.2.3/cargo-binstall-x86_64-unknown-linux-gnu"),
+            "{err:?}"
+        );
+
+        // Seed the cache, like `--prefetch` does.
+        let online = fetcher(false);
+        assert!(online.clone().find().await.unwrap().unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let (artifact, _) = online.fetch_and_save(dir.path(), &()).await.unwrap();
+
+        // The package and its checksum are loaded from the cache.
+        let offline = fetcher(true);
+        assert!(offline.clone().find().await.unwrap().unwrap());
+        let path = dir.path().join("cargo-binstall");
+        offline.fetch_and_extract(&path, None, &()).await.unwrap();
+        assert_eq!(
+            std::fs::read(path).unwrap(),
+            std::fs::read(artifact.path).unwrap()
+        );
+    }
+
+    #[test]
+    fn extraction_format_follows_matched_candidate() {
+        let fetcher = gh_crate_meta("x86_64-pc-windows-msvc", Some(PkgFmt::Tgz));
+        let templates =
+            [
+                Template::parse("https://example.com/{ name }-{ target }{ archive-suffix }")
+                    .unwrap(),
+            ];
+
+        // Pretend only the zip exists.
+        let (_, fallback) = candidates(&templates, Some(PkgFmt::Tgz));
+        let resolved = fallback
+            .iter()
+            .flat_map(|candidate| {
+                fetcher
+                    .render_candidate(candidate, None, None, None, None)
+                    .unwrap()
+            })
+            .find(|resolved| resolved.url.path().ends_with(".zip"))
+            .unwrap();
+        fetcher.resolution.set(vec![resolved]).unwrap();
+
+        assert_eq!(fetcher.pkg_fmt(), PkgFmt::Zip);
+        assert_eq!(fetcher.target_meta().pkg_fmt, Some(PkgFmt::Zip));
+    }
 }
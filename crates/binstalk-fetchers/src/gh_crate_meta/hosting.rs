@@ -36,6 +36,8 @@ const GITHUB_RELEASE_PATHS: &[Template<'_>] = &[
     // %2F is escaped form of '/'
     template!("{ repo }/releases/download/{ subcrate }%2F{ version }"),
     template!("{ repo }/releases/download/{ subcrate }%2Fv{ version }"),
+    template!("{ repo }/releases/download/{ repo-subpath }%2Fv{ version }"),
+    template!("{ repo }/releases/download/{ repo-subpath }-v{ version }"),
 ];
 
 const GITLAB_RELEASE_PATHS: &[Template<'_>] = &[
@@ -44,6 +46,8 @@ const GITLAB_RELEASE_PATHS: &[Template<'_>] = &[
     // %2F is escaped form of '/'
     template!("{ repo }/-/releases/{ subcrate }%2F{ version }/downloads/binaries"),
     template!("{ repo }/-/releases/{ subcrate }%2Fv{ version }/downloads/binaries"),
+    template!("{ repo }/-/releases/{ repo-subpath }%2Fv{ version }/downloads/binaries"),
+    template!("{ repo }/-/releases/{ repo-subpath }-v{ version }/downloads/binaries"),
 ];
 
 const BITBUCKET_RELEASE_PATHS: &[Template<'_>] = &[template!("{ repo }/downloads")];
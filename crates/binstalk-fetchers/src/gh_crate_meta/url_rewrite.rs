@@ -0,0 +1,160 @@
+use std::{str::FromStr, sync::Arc};
+
+use thiserror::Error as ThisError;
+use url::Url;
+
+/// A rule of [`UrlRewrites`], parsed from `<from-prefix>=><to-prefix>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UrlRewrite {
+    from: String,
+    to: String,
+}
+
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum UrlRewriteError {
+    #[error("expected `<from-prefix>=><to-prefix>`, got {0:?}")]
+    MissingSeparator(String),
+
+    #[error("prefix {prefix:?} is not a valid url: {err}")]
+    InvalidPrefix {
+        prefix: String,
+        #[source]
+        err: url::ParseError,
+    },
+}
+
+impl UrlRewrite {
+    /// Rewrite the urls starting with `from` to start with `to` instead.
+    ///
+    /// Both prefixes must be urls, which are normalized like the urls they
+    /// are matched against, e.g. `https://github.com` matches the urls
+    /// starting with `https://github.com/`.
+    pub fn new(from: &str, to: &str) -> Result<Self, UrlRewriteError> {
+        let parse = |prefix: &str| {
+            Url::parse(prefix.trim()).map(String::from).map_err(|err| {
+                UrlRewriteError::InvalidPrefix {
+                    prefix: prefix.to_owned(),
+                    err,
+                }
+            })
+        };
+
+        Ok(Self {
+            from: parse(from)?,
+            to: parse(to)?,
+        })
+    }
+}
+
+impl FromStr for UrlRewrite {
+    type Err = UrlRewriteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once("=>")
+            .ok_or_else(|| UrlRewriteError::MissingSeparator(s.to_owned()))?;
+
+        Self::new(from, to)
+    }
+}
+
+/// Rules rewriting the urls of packages, e.g. to download the artifacts of
+/// GitHub releases from a mirror.
+///
+/// Only where the artifacts are downloaded from is rewritten: the original
+/// urls are still the ones checked with the APIs of GitHub, GitLab and
+/// Gitea, and the ones the packages are cached under.
+#[derive(Clone, Debug, Default)]
+pub struct UrlRewrites(Arc<[UrlRewrite]>);
+
+impl UrlRewrites {
+    pub fn new(rules: impl IntoIterator<Item = UrlRewrite>) -> Self {
+        let mut rules: Vec<_> = rules.into_iter().collect();
+        // The longest prefix matching a url is used, whatever the order of
+        // the rules, and the first rule if several have the same prefix.
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.from.len()));
+
+        Self(rules.into())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Return `url` rewritten by the rule with the longest prefix matching
+    /// it, if any.
+    pub fn rewrite(&self, url: &Url) -> Option<Url> {
+        self.0.iter().find_map(|rule| {
+            let rest = url.as_str().strip_prefix(&rule.from)?;
+            Url::parse(&format!("{}{rest}", rule.to)).ok()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rewrites(rules: &[&str]) -> UrlRewrites {
+        UrlRewrites::new(rules.iter().map(|rule| rule.parse().unwrap()))
+    }
+
+    fn rewrite(rewrites: &UrlRewrites, url: &str) -> Option<String> {
+        rewrites
+            .rewrite(&Url::parse(url).unwrap())
+            .map(String::from)
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let rewrites = rewrites(&[
+            "https://github.com => https://mirror.example.com/github/",
+            "https://github.com/cargo-bins/=>https://bins.example.com/",
+        ]);
+
+        assert_eq!(
+            rewrite(
+                &rewrites,
+                "https://github.com/owner/repo/releases/download/v1/a.tgz"
+            )
+            .as_deref(),
+            Some("https://mirror.example.com/github/owner/repo/releases/download/v1/a.tgz")
+        );
+        // The longest prefix wins.
+        assert_eq!(
+            rewrite(
+                &rewrites,
+                "https://github.com/cargo-bins/cargo-binstall/a.tgz"
+            )
+            .as_deref(),
+            Some("https://bins.example.com/cargo-binstall/a.tgz")
+        );
+        assert_eq!(
+            rewrite(&rewrites, "https://github.com.example.org/a.tgz"),
+            None
+        );
+        assert_eq!(
+            rewrite(&rewrites, "https://gitlab.com/owner/repo/a.tgz"),
+            None
+        );
+
+        assert!(UrlRewrites::default().is_empty());
+        assert_eq!(
+            rewrite(&UrlRewrites::default(), "https://github.com/a.tgz"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_malformed_rule() {
+        for rule in [
+            "https://github.com/",
+            "https://github.com/ -> https://mirror.example.com/",
+            "github.com=>mirror.example.com",
+            "https://github.com/=>",
+        ] {
+            rule.parse::<UrlRewrite>().unwrap_err();
+        }
+    }
+}
@@ -3,9 +3,16 @@
 use std::{path::Path, sync::Arc};
 
 use binstalk_downloader::{
-    download::DownloadError, gh_api_client::GhApiError, remote::Error as RemoteError,
+    download::{DownloadError, DownloadProgress, SavedArtifact, UnexpectedFormat},
+    gh_api_client::{GhApiError, GhRepo},
+    gitea_api_client::GiteaApiError,
+    gl_api_client::GlApiError,
+    remote::{Error as RemoteError, GeoBlocked},
+};
+use binstalk_types::{
+    cargo_toml_binstall::{ChecksumAlgorithm, SigningAlgorithm},
+    crate_info::Verification,
 };
-use binstalk_types::cargo_toml_binstall::SigningAlgorithm;
 use thiserror::Error as ThisError;
 use tokio::sync::OnceCell;
 pub use url::ParseError as UrlParseError;
@@ -21,9 +28,18 @@ pub use quickinstall::*;
 mod common;
 use common::*;
 
+mod artifact_cache;
+pub use artifact_cache::ArtifactCache;
+use artifact_cache::CacheEntry;
+
 mod signing;
 use signing::*;
 
+#[cfg(feature = "sigstore")]
+mod sigstore;
+
+mod checksum;
+
 mod futures_resolver;
 
 use gh_crate_meta::hosting::RepositoryHost;
@@ -40,7 +56,6 @@ pub struct InvalidPkgFmtError {
 
 #[derive(Debug, ThisError, miette::Diagnostic)]
 #[non_exhaustive]
-#[cfg_attr(feature = "miette", derive(miette::Diagnostic))]
 pub enum FetchError {
     #[error(transparent)]
     Download(#[from] DownloadError),
@@ -56,12 +71,21 @@ pub enum FetchError {
     #[error("Failed to render template: {0}")]
     GhApi(#[from] GhApiError),
 
+    #[error("Failed to check the GitLab release: {0}")]
+    GlApi(#[from] GlApiError),
+
+    #[error("Failed to check the Gitea release: {0}")]
+    GiteaApi(#[from] GiteaApiError),
+
     #[error(transparent)]
     InvalidPkgFmt(Box<InvalidPkgFmtError>),
 
     #[error("Failed to parse url: {0}")]
     UrlParse(#[from] UrlParseError),
 
+    #[error("Refusing url template: {0}")]
+    UrlTemplate(#[from] UrlTemplateError),
+
     #[error("Signing algorithm not supported: {0:?}")]
     UnsupportedSigningAlgorithm(SigningAlgorithm),
 
@@ -70,6 +94,81 @@ pub enum FetchError {
 
     #[error("Failed to verify signature")]
     InvalidSignature,
+
+    #[error("Failed to verify sigstore bundle: {0}")]
+    InvalidAttestation(CompactString),
+
+    #[error("Checksum algorithm not supported: {0:?}")]
+    UnsupportedChecksumAlgorithm(ChecksumAlgorithm),
+
+    #[error("No checksum present")]
+    MissingChecksum,
+
+    #[error("Checksum mismatch, expected sha256 {expected} but the package has sha256 {actual}")]
+    ChecksumMismatch {
+        expected: CompactString,
+        actual: CompactString,
+    },
+
+    #[error("No artifact found for binary `{0}`")]
+    MissingBinArtifact(CompactString),
+
+    #[error("{url} is not a package: {err}")]
+    UnexpectedFormat {
+        url: CompactString,
+        #[source]
+        err: Box<UnexpectedFormat>,
+    },
+}
+
+impl FetchError {
+    /// Return the geo-block which caused this error, if any.
+    pub fn geo_blocked(&self) -> Option<&GeoBlocked> {
+        match self {
+            Self::Download(DownloadError::Remote(RemoteError::GeoBlocked(geo_blocked))) => {
+                Some(geo_blocked)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the url which could not be downloaded in offline mode, if
+    /// that caused this error.
+    pub fn offline_missing(&self) -> Option<&str> {
+        match self {
+            Self::Download(DownloadError::Remote(RemoteError::Offline(url))) => Some(url.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Return `true` if a server refused the request because of rate
+    /// limiting, see [`GhApiError::is_rate_limited`].
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            Self::GhApi(err) => err.is_rate_limited(),
+            _ => self
+                .remote_error()
+                .map_or(false, RemoteError::is_rate_limited),
+        }
+    }
+
+    /// Return `true` if the request failed because of the network, see
+    /// [`RemoteError::is_network`].
+    pub fn is_network(&self) -> bool {
+        match self {
+            Self::GhApi(err) => err.is_network(),
+            _ => self.remote_error().map_or(false, RemoteError::is_network),
+        }
+    }
+
+    fn remote_error(&self) -> Option<&RemoteError> {
+        match self {
+            Self::Download(DownloadError::Remote(err))
+            | Self::GlApi(GlApiError::Remote(err))
+            | Self::GiteaApi(GiteaApiError::Remote(err)) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl From<RemoteError> for FetchError {
@@ -87,19 +186,47 @@ impl From<InvalidPkgFmtError> for FetchError {
 #[async_trait::async_trait]
 pub trait Fetcher: Send + Sync {
     /// Create a new fetcher from some data
-    #[allow(clippy::new_ret_no_self)]
+    #[allow(clippy::new_ret_no_self, clippy::too_many_arguments)]
     fn new(
         client: Client,
         gh_api_client: GhApiClient,
+        gl_api_client: GlApiClient,
+        gitea_api_client: GiteaApiClient,
         data: Arc<Data>,
         target_data: Arc<TargetDataErased>,
         signature_policy: SignaturePolicy,
+        checksum_policy: ChecksumPolicy,
+        artifact_cache: Option<ArtifactCache>,
     ) -> Arc<dyn Fetcher>
     where
         Self: Sized;
 
-    /// Fetch a package and extract
-    async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError>;
+    /// Fetch a package and extract, return the extracted files and how
+    /// the package was verified.
+    ///
+    /// The package is loaded from the [`ArtifactCache`] passed to
+    /// [`Fetcher::new`] if it is there, and saved to it otherwise.
+    ///
+    /// If `filter` is `Some`, only the entries of the package it matches
+    /// are extracted, see [`ExtractFilter`].
+    ///
+    /// The progress of the download is reported to `progress`.
+    async fn fetch_and_extract(
+        &self,
+        dst: &Path,
+        filter: Option<&ExtractFilter>,
+        progress: &dyn DownloadProgress,
+    ) -> Result<(ExtractedFiles, Verification), FetchError>;
+
+    /// Fetch a package and save it to `dir` as is, along with its digest,
+    /// without extracting it.
+    ///
+    /// The progress of the download is reported to `progress`.
+    async fn fetch_and_save(
+        &self,
+        dir: &Path,
+        progress: &dyn DownloadProgress,
+    ) -> Result<(SavedArtifact, Verification), FetchError>;
 
     /// Find the package, if it is available for download
     ///
@@ -109,21 +236,51 @@ pub trait Fetcher: Send + Sync {
     ///
     /// Must return `true` if a package is available, `false` if none is, and reserve errors to
     /// fatal conditions only.
+    ///
+    /// The task spawned should run in the current span, the one of the
+    /// fetcher.
     fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>>;
 
     /// Report to upstream that cargo-binstall tries to use this fetcher.
     /// Currently it is only overriden by [`quickinstall::QuickInstall`].
+    ///
+    /// It must send the report in the background, and is not called if
+    /// telemetry is disabled.
     fn report_to_upstream(self: Arc<Self>) {}
 
     /// Return the package format
     fn pkg_fmt(&self) -> PkgFmt;
 
+    /// Return true if each binary is downloaded from its own artifact, i.e.
+    /// the pkg-url contains `{ bin }`.
+    ///
+    /// Raw binaries are then extracted to the directory passed to
+    /// [`Fetcher::fetch_and_extract`] under the name of the binary, instead
+    /// of being extracted to that path.
+    fn has_artifact_per_bin(&self) -> bool {
+        false
+    }
+
+    /// Return the number of bytes [`Fetcher::fetch_and_extract`] will
+    /// download, if it is known, e.g. to show it before installing.
+    ///
+    /// Must be called after [`Fetcher::find`] returns `true`.
+    async fn report_size(&self) -> Result<Option<u64>, FetchError> {
+        Ok(None)
+    }
+
     /// Return finalized target meta.
     fn target_meta(&self) -> PkgMeta;
 
     /// A short human-readable name or descriptor for the package source
     fn source_name(&self) -> CompactString;
 
+    /// Return the strategy and url of the package, recorded so that the
+    /// same strategy is tried first when the crate is upgraded.
+    ///
+    /// Must be called after [`Fetcher::find`] returns `true`.
+    fn fetch_source(&self) -> FetchSource;
+
     /// A short human-readable name, must contains only characters
     /// and numbers and it also must be unique.
     ///
@@ -134,6 +291,10 @@ pub trait Fetcher: Send + Sync {
     /// Should return true if the remote is from a third-party source
     fn is_third_party(&self) -> bool;
 
+    /// Return the strategy this fetcher implements, for the crates which
+    /// disable it.
+    fn strategy(&self) -> Strategy;
+
     /// Return the target for this fetcher
     fn target(&self) -> &str;
 
@@ -145,6 +306,50 @@ struct RepoInfo {
     repo: Url,
     repository_host: RepositoryHost,
     subcrate: Option<CompactString>,
+    /// Path of the crate inside the repository, e.g. `tools/mycrate`.
+    subpath: Option<CompactString>,
+}
+
+/// Prefixes of branch names containing slashes, e.g. `release/1.x`.
+///
+/// Urls of the form `tree/{branch}/{subpath}` do not tell where the branch
+/// name ends, so it is assumed to be one path segment unless it starts
+/// with one of these, in which case it is checked against the branches of
+/// the repository, see [`DetectedSubpath`].
+const SLASHED_BRANCH_PREFIXES: &[&str] = &[
+    "bugfix", "dev", "feature", "feat", "fix", "hotfix", "release", "releases",
+];
+
+/// Subpath detected by [`RepoInfo::detect_subpath`].
+#[derive(Debug, Default)]
+struct DetectedSubpath {
+    /// Subpath assuming that the branch is the longest candidate, e.g.
+    /// `release/1.x` in `tree/release/1.x/{subpath}`.
+    subpath: Option<CompactString>,
+    /// If the branch may also be just a prefix in
+    /// [`SLASHED_BRANCH_PREFIXES`], e.g. `dev` in `tree/dev/cli`, the url of
+    /// that branch and the subpath if it exists.
+    short_branch: Option<(Url, Option<CompactString>)>,
+}
+
+impl DetectedSubpath {
+    /// Resolve the branch by checking if the shorter one exists, or assume
+    /// the longer one if `client` is offline or the check fails.
+    async fn resolve(self, client: &Client) -> Option<CompactString> {
+        match self.short_branch {
+            Some((url, subpath)) if !client.is_offline() => {
+                match client.remote_gettable(url.clone()).await {
+                    Ok(true) => subpath,
+                    Ok(false) => self.subpath,
+                    Err(err) => {
+                        debug!("Failed to check if branch {url} exists: {err}");
+                        self.subpath
+                    }
+                }
+            }
+            _ => self.subpath,
+        }
+    }
 }
 
 /// What to do about package signatures
@@ -160,12 +365,28 @@ pub enum SignaturePolicy {
     Require,
 }
 
+/// What to do about package checksums
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChecksumPolicy {
+    /// Verify the checksum if the crate publishes one, warn if it cannot be
+    /// found
+    IfPresent,
+
+    /// Require checksums to be present (and matching)
+    Require,
+}
+
 /// Data required to fetch a package
 #[derive(Clone, Debug)]
 pub struct Data {
     name: CompactString,
     version: CompactString,
     repo: Option<String>,
+    /// Names of the binaries of the crate, for the `{ bin }` of pkg-url.
+    bins: Vec<CompactString>,
+    /// Whether plain http urls are allowed, see [`Data::with_allow_insecure_http`].
+    allow_insecure_http: bool,
+    url_rewrites: UrlRewrites,
     repo_info: OnceCell<Option<RepoInfo>>,
 }
 
@@ -175,21 +396,70 @@ impl Data {
             name,
             version,
             repo,
+            bins: Vec::new(),
+            allow_insecure_http: false,
+            url_rewrites: UrlRewrites::default(),
             repo_info: OnceCell::new(),
         }
     }
 
-    #[instrument(level = "debug")]
+    /// Set the names of the binaries of the crate, so that one artifact is
+    /// downloaded per binary if its pkg-url contains `{ bin }`.
+    pub fn with_bins(mut self, bins: impl IntoIterator<Item = CompactString>) -> Self {
+        self.bins = bins.into_iter().collect();
+        self
+    }
+
+    /// Allow pkg-url and signing.file to render to plain http urls, e.g.
+    /// of internal mirrors, which are rejected by default.
+    pub fn with_allow_insecure_http(mut self, allow_insecure_http: bool) -> Self {
+        self.allow_insecure_http = allow_insecure_http;
+        self
+    }
+
+    /// Download the packages, their signatures and checksums from the urls
+    /// rewritten by `url_rewrites`, e.g. to use a mirror.
+    pub fn with_url_rewrites(mut self, url_rewrites: UrlRewrites) -> Self {
+        self.url_rewrites = url_rewrites;
+        self
+    }
+
+    #[instrument(level = "debug", skip_all, fields(repo = ?self.repo))]
     async fn get_repo_info(&self, client: &Client) -> Result<&Option<RepoInfo>, FetchError> {
         self.repo_info
             .get_or_try_init(move || {
                 Box::pin(async move {
                     if let Some(repo) = self.repo.as_deref() {
-                        let mut repo = client.get_redirected_final_url(Url::parse(repo)?).await?;
+                        let (repo, subpath) = match GhRepo::try_extract_from_url(repo) {
+                            // Use the canonical url of GitHub repositories, so
+                            // that `{ repo }` is the same whatever form the
+                            // manifest uses, e.g. `git@github.com:owner/repo.git`.
+                            Some(gh_repo) => {
+                                let subpath = Url::parse(repo)
+                                    .map(|mut repo| {
+                                        RepoInfo::detect_subpath(&mut repo, RepositoryHost::GitHub)
+                                    })
+                                    .unwrap_or_default()
+                                    .resolve(client)
+                                    .await;
+                                let repo = final_url(client, gh_repo.url()).await?;
+                                (repo, subpath)
+                            }
+                            None => {
+                                let mut repo = final_url(client, Url::parse(repo)?).await?;
+                                let repository_host =
+                                    RepositoryHost::guess_git_hosting_services(&repo);
+                                let subpath = RepoInfo::detect_subpath(&mut repo, repository_host)
+                                    .resolve(client)
+                                    .await;
+                                (repo, subpath)
+                            }
+                        };
                         let repository_host = RepositoryHost::guess_git_hosting_services(&repo);
 
                         let repo_info = RepoInfo {
-                            subcrate: RepoInfo::detect_subcrate(&mut repo, repository_host),
+                            subcrate: subpath.as_deref().map(RepoInfo::subcrate_of),
+                            subpath,
                             repo,
                             repository_host,
                         };
@@ -206,66 +476,102 @@ impl Data {
     }
 }
 
+/// Follow the redirections of `url`, e.g. of a renamed repository, unless
+/// `client` is offline.
+async fn final_url(client: &Client, url: Url) -> Result<Url, FetchError> {
+    if client.is_offline() {
+        Ok(url)
+    } else {
+        Ok(client.get_redirected_final_url(url).await?)
+    }
+}
+
 impl RepoInfo {
-    /// If `repo` contains a subcrate, then extracts and returns it.
-    /// It will also remove that subcrate path from `repo` to match
-    /// `scheme:/{repo_owner}/{repo_name}`
-    fn detect_subcrate(repo: &mut Url, repository_host: RepositoryHost) -> Option<CompactString> {
+    /// If `repo` points inside of the repository, e.g. to a subdirectory
+    /// with `/tree/{branch}/{subpath}` or to a file with
+    /// `/blob/{branch}/{subpath}/{file}`, then extracts and returns the
+    /// subpath.
+    ///
+    /// It will also remove everything after the repository from `repo` to
+    /// match `scheme:/{repo_owner}/{repo_name}`
+    fn detect_subpath(repo: &mut Url, repository_host: RepositoryHost) -> DetectedSubpath {
         match repository_host {
-            RepositoryHost::GitHub => Self::detect_subcrate_common(repo, &["tree"]),
-            RepositoryHost::GitLab => Self::detect_subcrate_common(repo, &["-", "blob"]),
+            RepositoryHost::GitHub => Self::detect_subpath_common(repo, &[]),
+            RepositoryHost::GitLab => Self::detect_subpath_common(repo, &["-"]),
             _ => None,
         }
+        .unwrap_or_default()
     }
 
-    fn detect_subcrate_common(repo: &mut Url, seps: &[&str]) -> Option<CompactString> {
-        let mut path_segments = repo.path_segments()?;
+    fn detect_subpath_common(repo: &mut Url, seps: &[&str]) -> Option<DetectedSubpath> {
+        // Ignore empty segments from trailing or duplicate slashes.
+        let path_segments: Vec<&str> = repo.path_segments()?.filter(|s| !s.is_empty()).collect();
 
-        let _repo_owner = path_segments.next()?;
-        let _repo_name = path_segments.next()?;
+        let [repo_owner, repo_name, rest @ ..] = &path_segments[..] else {
+            return None;
+        };
 
         // Skip separators
-        for sep in seps.iter().copied() {
-            if path_segments.next()? != sep {
-                return None;
-            }
-        }
+        let rest = rest.strip_prefix(seps)?;
+
+        let (is_blob, rest) = match rest.split_first()? {
+            (&"tree", rest) => (false, rest),
+            (&"blob", rest) => (true, rest),
+            _ => return None,
+        };
+
+        let subpath_of = |subpath: &[&str]| -> Option<CompactString> {
+            let mut subpath = subpath;
 
-        // Skip branch name
-        let _branch_name = path_segments.next()?;
+            // A blob url points to a file, e.g. `{subpath}/Cargo.toml`.
+            if is_blob {
+                if let [dir @ .., file] = subpath {
+                    if file.contains('.') {
+                        subpath = dir;
+                    }
+                }
+            }
 
-        let (subcrate, is_crate_present) = match path_segments.next()? {
-            // subcrate url is of path /crates/$subcrate_name, e.g. wasm-bindgen-cli
-            "crates" => (path_segments.next()?, true),
-            // subcrate url is of path $subcrate_name, e.g. cargo-audit
-            subcrate => (subcrate, false),
+            (!subpath.is_empty()).then(|| subpath.join("/").into())
         };
 
-        if path_segments.next().is_some() {
-            // A subcrate url should not contain anything more.
-            None
-        } else {
-            let subcrate = subcrate.into();
-
-            // Pop subcrate path to match regular repo style:
-            //
-            // scheme:/{addr}/{repo_owner}/{repo_name}
-            //
-            // path_segments() succeeds, so path_segments_mut()
-            // must also succeeds.
-            let mut paths = repo.path_segments_mut().unwrap();
-
-            paths.pop(); // pop subcrate
-            if is_crate_present {
-                paths.pop(); // pop crate
+        // Skip branch name, trying the longer one first.
+        let detected = match rest {
+            [prefix, _branch, subpath @ ..] if SLASHED_BRANCH_PREFIXES.contains(prefix) => {
+                let mut url = repo.clone();
+                url.path_segments_mut()
+                    .ok()?
+                    .clear()
+                    .extend([repo_owner, repo_name])
+                    .extend(seps)
+                    .extend(["tree", prefix]);
+
+                DetectedSubpath {
+                    subpath: subpath_of(subpath),
+                    short_branch: Some((url, subpath_of(&rest[1..]))),
+                }
             }
-            paths.pop(); // pop branch name
-            seps.iter().for_each(|_| {
-                paths.pop();
-            }); // pop separators
+            [_branch, subpath @ ..] => DetectedSubpath {
+                subpath: subpath_of(subpath),
+                short_branch: None,
+            },
+            [] => return None,
+        };
 
-            Some(subcrate)
-        }
+        // Pop everything after the repository to match regular repo style:
+        //
+        // scheme:/{addr}/{repo_owner}/{repo_name}
+        let path = format!("/{repo_owner}/{repo_name}");
+        repo.set_path(&path);
+
+        Some(detected)
+    }
+
+    /// Return the subcrate in `subpath`, which is its last directory.
+    ///
+    /// e.g. `cargo-audit` for `cargo-audit` or `cli` for `crates/cli`.
+    fn subcrate_of(subpath: &str) -> CompactString {
+        subpath.rsplit('/').next().unwrap_or(subpath).into()
     }
 }
 
@@ -278,6 +584,7 @@ pub struct TargetData<T: leon::Values + ?Sized> {
     ///  - target_family,
     ///  - target_arch
     ///  - target_libc
+    ///  - target_env
     ///  - target_vendor
     pub target_related_info: T,
 }
@@ -288,21 +595,26 @@ pub type TargetDataErased = TargetData<dyn leon::Values + Send + Sync + 'static>
 mod test {
     use super::*;
 
+    fn detect(url: &str) -> (Option<CompactString>, Url) {
+        let mut repo = Url::parse(url).unwrap();
+        let repository_host = RepositoryHost::guess_git_hosting_services(&repo);
+        let subpath = RepoInfo::detect_subpath(&mut repo, repository_host).subpath;
+        (subpath, repo)
+    }
+
     #[test]
     fn test_detect_subcrate_github() {
         // cargo-audit
         let urls = [
             "https://github.com/RustSec/rustsec/tree/main/cargo-audit",
             "https://github.com/RustSec/rustsec/tree/master/cargo-audit",
+            "https://github.com/RustSec/rustsec/tree/main/cargo-audit/",
+            "https://github.com/RustSec/rustsec/blob/main/cargo-audit/Cargo.toml",
         ];
         for url in urls {
-            let mut repo = Url::parse(url).unwrap();
-
-            let repository_host = RepositoryHost::guess_git_hosting_services(&repo);
-            assert_eq!(repository_host, RepositoryHost::GitHub);
-
-            let subcrate_prefix = RepoInfo::detect_subcrate(&mut repo, repository_host).unwrap();
-            assert_eq!(subcrate_prefix, "cargo-audit");
+            let (subpath, repo) = detect(url);
+            assert_eq!(subpath.as_deref(), Some("cargo-audit"), "{url}");
+            assert_eq!(RepoInfo::subcrate_of(&subpath.unwrap()), "cargo-audit");
 
             assert_eq!(
                 repo,
@@ -316,13 +628,9 @@ mod test {
             "https://github.com/rustwasm/wasm-bindgen/tree/master/crates/cli",
         ];
         for url in urls {
-            let mut repo = Url::parse(url).unwrap();
-
-            let repository_host = RepositoryHost::guess_git_hosting_services(&repo);
-            assert_eq!(repository_host, RepositoryHost::GitHub);
-
-            let subcrate_prefix = RepoInfo::detect_subcrate(&mut repo, repository_host).unwrap();
-            assert_eq!(subcrate_prefix, "cli");
+            let (subpath, repo) = detect(url);
+            assert_eq!(subpath.as_deref(), Some("crates/cli"), "{url}");
+            assert_eq!(RepoInfo::subcrate_of(&subpath.unwrap()), "cli");
 
             assert_eq!(
                 repo,
@@ -332,24 +640,144 @@ mod test {
     }
 
     #[test]
-    fn test_detect_subcrate_gitlab() {
+    fn test_detect_subpath_github_monorepo() {
         let urls = [
-            "https://gitlab.kitware.com/NobodyXu/hello/-/blob/main/cargo-binstall",
-            "https://gitlab.kitware.com/NobodyXu/hello/-/blob/master/cargo-binstall",
+            "https://github.com/org/monorepo/tree/main/tools/mycrate",
+            "https://github.com/org/monorepo/tree/main/tools/mycrate/",
+            "https://github.com/org/monorepo/blob/main/tools/mycrate/Cargo.toml",
+            "https://github.com/org/monorepo/blob/main/tools/mycrate/",
+            // Branch names containing slashes
+            "https://github.com/org/monorepo/tree/release/1.x/tools/mycrate",
+            "https://github.com/org/monorepo/tree/feature/new-cli/tools/mycrate/",
+            "https://github.com/org/monorepo/blob/release/1.x/tools/mycrate/README.md",
         ];
         for url in urls {
-            let mut repo = Url::parse(url).unwrap();
+            let (subpath, repo) = detect(url);
+            assert_eq!(subpath.as_deref(), Some("tools/mycrate"), "{url}");
+            assert_eq!(RepoInfo::subcrate_of(&subpath.unwrap()), "mycrate");
+
+            assert_eq!(repo, Url::parse("https://github.com/org/monorepo").unwrap());
+        }
 
+        // Points to the root of the repository.
+        let urls = [
+            "https://github.com/org/monorepo/tree/main",
+            "https://github.com/org/monorepo/tree/main/",
+            "https://github.com/org/monorepo/blob/main/Cargo.toml",
+        ];
+        for url in urls {
+            let (subpath, repo) = detect(url);
+            assert_eq!(subpath, None, "{url}");
+            assert_eq!(repo, Url::parse("https://github.com/org/monorepo").unwrap());
+        }
+
+        // Not pointing inside of the repository.
+        let (subpath, repo) = detect("https://github.com/org/monorepo/");
+        assert_eq!(subpath, None);
+        assert_eq!(
+            repo,
+            Url::parse("https://github.com/org/monorepo/").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_detect_subpath_multi_segment_branch() {
+        let detect_all = |url| {
+            let mut repo = Url::parse(url).unwrap();
             let repository_host = RepositoryHost::guess_git_hosting_services(&repo);
-            assert_eq!(repository_host, RepositoryHost::GitLab);
+            RepoInfo::detect_subpath(&mut repo, repository_host)
+        };
 
-            let subcrate_prefix = RepoInfo::detect_subcrate(&mut repo, repository_host).unwrap();
-            assert_eq!(subcrate_prefix, "cargo-binstall");
+        let detected = detect_all("https://github.com/org/monorepo/tree/release/1.x/crates/cli");
+        assert_eq!(detected.subpath.as_deref(), Some("crates/cli"));
+        let (url, subpath) = detected.short_branch.unwrap();
+        assert_eq!(url.as_str(), "https://github.com/org/monorepo/tree/release");
+        assert_eq!(subpath.as_deref(), Some("1.x/crates/cli"));
+
+        // Either branch `dev/cli` at the root, or branch `dev` with `cli`.
+        let detected = detect_all("https://github.com/org/monorepo/tree/dev/cli");
+        assert_eq!(detected.subpath, None);
+        let (url, subpath) = detected.short_branch.unwrap();
+        assert_eq!(url.as_str(), "https://github.com/org/monorepo/tree/dev");
+        assert_eq!(subpath.as_deref(), Some("cli"));
+
+        let detected = detect_all("https://gitlab.com/org/monorepo/-/blob/dev/cli/Cargo.toml");
+        assert_eq!(detected.subpath, None);
+        let (url, subpath) = detected.short_branch.unwrap();
+        assert_eq!(url.as_str(), "https://gitlab.com/org/monorepo/-/tree/dev");
+        assert_eq!(subpath.as_deref(), Some("cli"));
+
+        let detected = detect_all("https://github.com/org/monorepo/tree/main/crates/cli");
+        assert!(detected.short_branch.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_multi_segment_branch() {
+        use binstalk_downloader::remote::{test_server::spawn_server, Certificate};
+        use std::num::NonZeroU16;
+
+        // Only branch `dev` exists.
+        let (url, cert) = spawn_server(|request| {
+            if request.starts_with("GET /org/monorepo/tree/dev ") {
+                "HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n."
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+            }
+        })
+        .await;
+        let new_client = || {
+            Client::new(
+                "binstall-test",
+                None,
+                NonZeroU16::new(10).unwrap(),
+                1.try_into().unwrap(),
+                [Certificate::from_der(&cert).unwrap()],
+                Default::default(),
+                None,
+            )
+            .unwrap()
+        };
+        let client = new_client();
+
+        let detected = |branch: &str| DetectedSubpath {
+            subpath: None,
+            short_branch: Some((
+                url.join(&format!("org/monorepo/tree/{branch}")).unwrap(),
+                Some("cli".into()),
+            )),
+        };
+
+        assert_eq!(
+            detected("dev").resolve(&client).await.as_deref(),
+            Some("cli")
+        );
+        assert_eq!(detected("release").resolve(&client).await, None);
+        assert_eq!(
+            detected("dev").resolve(&new_client().offline(true)).await,
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_subcrate_gitlab() {
+        let urls = [
+            "https://gitlab.kitware.com/NobodyXu/hello/-/blob/main/cargo-binstall",
+            "https://gitlab.kitware.com/NobodyXu/hello/-/blob/master/cargo-binstall",
+            "https://gitlab.kitware.com/NobodyXu/hello/-/tree/main/cargo-binstall/",
+        ];
+        for url in urls {
+            let (subpath, repo) = detect(url);
+            assert_eq!(subpath.as_deref(), Some("cargo-binstall"), "{url}");
 
             assert_eq!(
                 repo,
                 Url::parse("https://gitlab.kitware.com/NobodyXu/hello").unwrap()
             );
         }
+
+        let (subpath, repo) =
+            detect("https://gitlab.com/org/monorepo/-/tree/release/1.x/tools/mycrate");
+        assert_eq!(subpath.as_deref(), Some("tools/mycrate"));
+        assert_eq!(repo, Url::parse("https://gitlab.com/org/monorepo").unwrap());
     }
 }
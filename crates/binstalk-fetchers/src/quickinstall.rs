@@ -1,18 +1,21 @@
-use std::{borrow::Cow, path::Path, sync::Arc};
+use std::{borrow::Cow, path::Path, sync::Arc, time::Duration};
 
 use binstalk_downloader::remote::Method;
 use binstalk_types::cargo_toml_binstall::{PkgFmt, PkgMeta, PkgSigning};
-use tokio::sync::OnceCell;
-use tracing::{error, info, trace};
+use tokio::{sync::OnceCell, time::timeout};
+use tracing::{error, trace, Instrument};
 use url::Url;
 
 use crate::{
-    common::*, Data, FetchError, SignaturePolicy, SignatureVerifier, SigningAlgorithm,
-    TargetDataErased,
+    common::*, ArtifactCache, CacheEntry, ChecksumPolicy, Data, FetchError, RemoteError,
+    SignaturePolicy, SignatureVerifier, SigningAlgorithm, TargetDataErased,
 };
 
 const BASE_URL: &str = "https://github.com/cargo-bins/cargo-quickinstall/releases/download";
 const STATS_URL: &str = "https://warehouse-clerk-tmp.vercel.app/api/crate";
+/// The report is sent in the background and never retried, so that it
+/// cannot delay the installation.
+const STATS_TIMEOUT: Duration = Duration::from_secs(5);
 
 const QUICKINSTALL_SIGN_KEY: Cow<'static, str> =
     Cow::Borrowed("RWTdnnab2pAka9OdwgCMYyOE66M/BlQoFWaJ/JjwcPV+f3n24IRTj97t");
@@ -52,18 +55,29 @@ async fn get_quickinstall_supported_targets(
 pub struct QuickInstall {
     client: Client,
     gh_api_client: GhApiClient,
+    gl_api_client: GlApiClient,
+    gitea_api_client: GiteaApiClient,
     is_supported_v: OnceCell<bool>,
 
     package: String,
+    version: CompactString,
     package_url: Url,
     signature_url: Url,
     stats_url: Url,
     signature_policy: SignaturePolicy,
+    checksum_policy: ChecksumPolicy,
+    artifact_cache: Option<ArtifactCache>,
 
     target_data: Arc<TargetDataErased>,
 }
 
 impl QuickInstall {
+    fn cache_entry(&self) -> Option<CacheEntry<'_>> {
+        self.artifact_cache
+            .as_ref()
+            .map(|cache| cache.entry(&self.package_url, &self.version))
+    }
+
     async fn is_supported(&self) -> Result<bool, FetchError> {
         self.is_supported_v
             .get_or_try_init(|| async {
@@ -75,6 +89,41 @@ impl QuickInstall {
             .await
             .copied()
     }
+
+    async fn signature_verifier(&self) -> Result<SignatureVerifier, FetchError> {
+        Ok(if self.signature_policy == SignaturePolicy::Ignore {
+            SignatureVerifier::Skipped
+        } else {
+            debug!(url=%self.signature_url, "Downloading signature");
+            match download_bytes_cached(
+                self.client.clone(),
+                self.cache_entry().as_ref(),
+                &self.signature_url,
+                self.signature_url.clone(),
+            )
+            .await
+            {
+                Ok(signature) => {
+                    trace!(?signature, "got signature contents");
+                    let config = PkgSigning {
+                        algorithm: SigningAlgorithm::Minisign,
+                        pubkey: QUICKINSTALL_SIGN_KEY,
+                        file: None,
+                    };
+                    SignatureVerifier::new(&config, &signature, None)?
+                }
+                Err(err) => {
+                    if self.signature_policy == SignaturePolicy::Require {
+                        error!("Failed to download signature: {err}");
+                        return Err(FetchError::MissingSignature);
+                    }
+
+                    debug!("Failed to download signature, skipping verification: {err}");
+                    SignatureVerifier::NotPublished
+                }
+            }
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -82,9 +131,13 @@ impl super::Fetcher for QuickInstall {
     fn new(
         client: Client,
         gh_api_client: GhApiClient,
+        gl_api_client: GlApiClient,
+        gitea_api_client: GiteaApiClient,
         data: Arc<Data>,
         target_data: Arc<TargetDataErased>,
         signature_policy: SignaturePolicy,
+        checksum_policy: ChecksumPolicy,
+        artifact_cache: Option<ArtifactCache>,
     ) -> Arc<dyn super::Fetcher> {
         let crate_name = &data.name;
         let version = &data.version;
@@ -97,6 +150,8 @@ impl super::Fetcher for QuickInstall {
         Arc::new(Self {
             client,
             gh_api_client,
+            gl_api_client,
+            gitea_api_client,
             is_supported_v: OnceCell::new(),
 
             package_url: Url::parse(&url)
@@ -106,35 +161,66 @@ impl super::Fetcher for QuickInstall {
             stats_url: Url::parse(&format!("{STATS_URL}/{package}.tar.gz",))
                 .expect("stats_url is pre-generated and should never be invalid url"),
             package,
+            version: version.clone(),
             signature_policy,
+            checksum_policy,
+            artifact_cache,
 
             target_data,
         })
     }
 
     fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>> {
-        tokio::spawn(async move {
-            if !self.is_supported().await? {
-                return Ok(false);
-            }
+        tokio::spawn(
+            async move {
+                let offline = self.client.is_offline();
+                if !offline && !self.is_supported().await? {
+                    return Ok(false);
+                }
+
+                // Quickinstall does not publish checksums, only signatures.
+                if self.checksum_policy == ChecksumPolicy::Require {
+                    return Err(FetchError::MissingChecksum);
+                }
+
+                if offline {
+                    // Only a cached package can be installed offline.
+                    return if self
+                        .cache_entry()
+                        .map_or(false, |cache| cache.cached().is_some())
+                    {
+                        Ok(true)
+                    } else {
+                        Err(RemoteError::Offline(Box::new(self.package_url.clone())).into())
+                    };
+                }
+
+                if self.signature_policy == SignaturePolicy::Require {
+                    does_url_exist(
+                        self.client.clone(),
+                        self.gh_api_client.clone(),
+                        self.gl_api_client.clone(),
+                        self.gitea_api_client.clone(),
+                        &self.signature_url,
+                        &self.signature_url,
+                    )
+                    .await
+                    .map_err(|_| FetchError::MissingSignature)?;
+                }
 
-            if self.signature_policy == SignaturePolicy::Require {
                 does_url_exist(
                     self.client.clone(),
                     self.gh_api_client.clone(),
-                    &self.signature_url,
+                    self.gl_api_client.clone(),
+                    self.gitea_api_client.clone(),
+                    &self.package_url,
+                    &self.package_url,
                 )
                 .await
-                .map_err(|_| FetchError::MissingSignature)?;
+                .map(|size| size.is_some())
             }
-
-            does_url_exist(
-                self.client.clone(),
-                self.gh_api_client.clone(),
-                &self.package_url,
-            )
-            .await
-        })
+            .in_current_span(),
+        )
     }
 
     fn report_to_upstream(self: Arc<Self>) {
@@ -147,66 +233,62 @@ and universal2-apple-darwin.
 Quickinstall does not support these targets, it only supports targets supported
 by rust officially."#,
             );
+        } else if self.client.is_offline() {
+            debug!("Not sending quickinstall report in offline mode");
         } else if self.is_supported_v.get().copied() != Some(false) {
             tokio::spawn(async move {
-                if let Err(err) = self.report().await {
-                    warn!(
-                        "Failed to send quickinstall report for package {}: {err}",
-                        self.package
-                    )
-                }
+                self.report_within(STATS_TIMEOUT).await;
             });
         }
     }
 
-    async fn fetch_and_extract(&self, dst: &Path) -> Result<ExtractedFiles, FetchError> {
-        let verifier = if self.signature_policy == SignaturePolicy::Ignore {
-            SignatureVerifier::Noop
-        } else {
-            debug!(url=%self.signature_url, "Downloading signature");
-            match Download::new(self.client.clone(), self.signature_url.clone())
-                .into_bytes()
-                .await
-            {
-                Ok(signature) => {
-                    trace!(?signature, "got signature contents");
-                    let config = PkgSigning {
-                        algorithm: SigningAlgorithm::Minisign,
-                        pubkey: QUICKINSTALL_SIGN_KEY,
-                        file: None,
-                    };
-                    SignatureVerifier::new(&config, &signature)?
-                }
-                Err(err) => {
-                    if self.signature_policy == SignaturePolicy::Require {
-                        error!("Failed to download signature: {err}");
-                        return Err(FetchError::MissingSignature);
-                    }
+    async fn fetch_and_extract(
+        &self,
+        dst: &Path,
+        filter: Option<&ExtractFilter>,
+        progress: &dyn DownloadProgress,
+    ) -> Result<(ExtractedFiles, Verification), FetchError> {
+        let verifier = self.signature_verifier().await?;
+        let checksum = release_artifact_sha256(&self.gh_api_client, &self.package_url).await;
+
+        download_and_extract_cached(
+            self.cache_entry(),
+            self.client.clone(),
+            &self.gh_api_client,
+            &self.package_url,
+            &self.package_url,
+            self.pkg_fmt(),
+            dst,
+            filter,
+            &self.package,
+            &verifier,
+            checksum.as_deref(),
+            progress,
+        )
+        .await
+    }
 
-                    debug!("Failed to download signature, skipping verification: {err}");
-                    SignatureVerifier::Noop
-                }
-            }
-        };
+    async fn fetch_and_save(
+        &self,
+        dir: &Path,
+        progress: &dyn DownloadProgress,
+    ) -> Result<(SavedArtifact, Verification), FetchError> {
+        let verifier = self.signature_verifier().await?;
+        let checksum = release_artifact_sha256(&self.gh_api_client, &self.package_url).await;
 
-        debug!(url=%self.package_url, "Downloading package");
-        let mut data_verifier = verifier.data_verifier()?;
-        let files = Download::new_with_data_verifier(
+        download_and_save_cached(
+            self.cache_entry(),
             self.client.clone(),
-            self.package_url.clone(),
-            data_verifier.as_mut(),
+            &self.gh_api_client,
+            &self.package_url,
+            &self.package_url,
+            dir,
+            &self.package,
+            &verifier,
+            checksum.as_deref(),
+            progress,
         )
-        .and_extract(self.pkg_fmt(), dst)
-        .await?;
-        trace!("validating signature (if any)");
-        if data_verifier.validate() {
-            if let Some(info) = verifier.info() {
-                info!("Verified signature for package '{}': {info}", self.package);
-            }
-            Ok(files)
-        } else {
-            Err(FetchError::InvalidSignature)
-        }
+        .await
     }
 
     fn pkg_fmt(&self) -> PkgFmt {
@@ -217,6 +299,9 @@ by rust officially."#,
         let mut meta = self.target_data.meta.clone();
         meta.pkg_fmt = Some(self.pkg_fmt());
         meta.bin_dir = Some("{ bin }{ binary-ext }".to_string());
+        // QuickInstall packages only contain the binaries.
+        meta.completions_dir = None;
+        meta.man_dir = None;
         meta
     }
 
@@ -224,6 +309,12 @@ by rust officially."#,
         CompactString::from("QuickInstall")
     }
 
+    fn fetch_source(&self) -> FetchSource {
+        FetchSource::QuickInstall {
+            url: self.package_url.clone(),
+        }
+    }
+
     fn fetcher_name(&self) -> &'static str {
         "QuickInstall"
     }
@@ -232,6 +323,10 @@ by rust officially."#,
         true
     }
 
+    fn strategy(&self) -> Strategy {
+        Strategy::QuickInstall
+    }
+
     fn target(&self) -> &str {
         &self.target_data.target
     }
@@ -259,12 +354,42 @@ impl QuickInstall {
 
         Ok(())
     }
+
+    /// Send the report, giving up after `duration`, return `false` if it
+    /// timed out.
+    async fn report_within(&self, duration: Duration) -> bool {
+        match timeout(duration, self.report()).await {
+            Ok(Ok(())) => true,
+            Ok(Err(err)) => {
+                warn!(
+                    "Failed to send quickinstall report for package {}: {err}",
+                    self.package
+                );
+                true
+            }
+            Err(_) => {
+                debug!(
+                    "Timed out sending quickinstall report for package {}",
+                    self.package
+                );
+                false
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{get_quickinstall_supported_targets, Client, CompactString};
-    use std::num::NonZeroU16;
+    use super::*;
+    use std::{
+        num::NonZeroU16,
+        sync::atomic::{AtomicBool, Ordering::Relaxed},
+        time::Instant,
+    };
+
+    use binstalk_downloader::remote::{test_server::spawn_stalling_server, Certificate};
+
+    use crate::TargetData;
 
     /// Mark this as an async fn so that you won't accidentally use it in
     /// sync context.
@@ -275,6 +400,8 @@ mod test {
             NonZeroU16::new(10).unwrap(),
             1.try_into().unwrap(),
             [],
+            Default::default(),
+            None,
         )
         .unwrap()
     }
@@ -304,4 +431,54 @@ mod test {
                 .unwrap();
         });
     }
+
+    #[tokio::test]
+    async fn test_report_timeout() {
+        static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+        // The stats endpoint never answers.
+        let (url, cert) = spawn_stalling_server(|_| {
+            REQUESTED.store(true, Relaxed);
+            ""
+        })
+        .await;
+        let client = Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [Certificate::from_der(&cert).unwrap()],
+            Default::default(),
+            None,
+        )
+        .unwrap();
+        let target_data = TargetData {
+            target: "x86_64-unknown-linux-gnu".to_string(),
+            meta: PkgMeta::default(),
+            target_related_info: leon::vals(|_| None),
+        };
+        let quickinstall = QuickInstall {
+            gh_api_client: GhApiClient::new(client.clone(), None),
+            gl_api_client: GlApiClient::new(client.clone(), None),
+            gitea_api_client: GiteaApiClient::new(client.clone(), None),
+            client,
+            is_supported_v: OnceCell::new_with(Some(true)),
+
+            package: "foo-1.0.0-x86_64-unknown-linux-gnu".to_string(),
+            version: "1.0.0".into(),
+            package_url: url.join("foo.tar.gz").unwrap(),
+            signature_url: url.join("foo.tar.gz.sig").unwrap(),
+            stats_url: url.join("api/crate/foo.tar.gz").unwrap(),
+            signature_policy: SignaturePolicy::IfPresent,
+            checksum_policy: ChecksumPolicy::IfPresent,
+            artifact_cache: None,
+
+            target_data: Arc::new(target_data),
+        };
+
+        let start = Instant::now();
+        assert!(!quickinstall.report_within(Duration::from_millis(200)).await);
+        assert!(start.elapsed() < STATS_TIMEOUT);
+        assert!(REQUESTED.load(Relaxed));
+    }
 }
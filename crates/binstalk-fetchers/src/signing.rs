@@ -1,43 +1,96 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
 use binstalk_downloader::download::DataVerifier;
-use binstalk_types::cargo_toml_binstall::{PkgSigning, SigningAlgorithm};
+use binstalk_types::{
+    cargo_toml_binstall::{PkgSigning, SigningAlgorithm},
+    crate_info::SignatureCheck,
+};
 use bytes::Bytes;
+use compact_str::{format_compact, CompactString};
 use minisign_verify::{PublicKey, Signature, StreamVerifier};
 use tracing::{error, trace};
 
 use crate::FetchError;
 
+#[cfg(feature = "sigstore")]
+use crate::sigstore::SigstoreVerifier;
+
 pub enum SignatureVerifier {
-    Noop,
+    /// Signature verification is disabled.
+    Skipped,
+    /// The package does not publish a signature.
+    NotPublished,
     Minisign(Box<MinisignVerifier>),
+    #[cfg(feature = "sigstore")]
+    Sigstore(Box<SigstoreVerifier>),
 }
 
 impl SignatureVerifier {
-    pub fn new(config: &PkgSigning, signature: &[u8]) -> Result<Self, FetchError> {
+    /// Create a verifier of `signature`, `repo` being the repository of
+    /// the crate which sigstore bundles must be signed from.
+    pub fn new(
+        config: &PkgSigning,
+        signature: &[u8],
+        repo: Option<&str>,
+    ) -> Result<Self, FetchError> {
+        #[cfg(not(feature = "sigstore"))]
+        let _ = repo;
+
         match config.algorithm {
             SigningAlgorithm::Minisign => MinisignVerifier::new(config, signature)
                 .map(Box::new)
                 .map(Self::Minisign),
+            #[cfg(feature = "sigstore")]
+            SigningAlgorithm::Sigstore => SigstoreVerifier::new(signature, repo)
+                .map(Box::new)
+                .map(Self::Sigstore),
             algorithm => Err(FetchError::UnsupportedSigningAlgorithm(algorithm)),
         }
     }
 
     pub fn data_verifier(&self) -> Result<Box<dyn DataVerifier + '_>, FetchError> {
         match self {
-            Self::Noop => Ok(Box::new(())),
+            Self::Skipped | Self::NotPublished => Ok(Box::new(())),
             Self::Minisign(v) => v.data_verifier(),
+            #[cfg(feature = "sigstore")]
+            Self::Sigstore(v) => Ok(v.data_verifier()),
+        }
+    }
+
+    /// Error to return when the data verifier rejects the data.
+    pub fn invalid_error(&self) -> FetchError {
+        match self {
+            #[cfg(feature = "sigstore")]
+            Self::Sigstore(_) => {
+                FetchError::InvalidAttestation("the bundle does not cover the package".into())
+            }
+            _ => FetchError::InvalidSignature,
         }
     }
 
-    pub fn info(&self) -> Option<String> {
+    /// Result of the check, only meaningful once the data verifier
+    /// returned by [`SignatureVerifier::data_verifier`] validated the data.
+    pub fn check(&self) -> SignatureCheck {
         match self {
-            Self::Noop => None,
-            Self::Minisign(v) => Some(v.signature.trusted_comment().into()),
+            Self::Skipped => SignatureCheck::Skipped,
+            Self::NotPublished => SignatureCheck::NotPublished,
+            Self::Minisign(v) => SignatureCheck::Verified {
+                algorithm: SigningAlgorithm::Minisign,
+                key_id: v.key_id.clone(),
+                trusted_comment: v.signature.trusted_comment().into(),
+            },
+            #[cfg(feature = "sigstore")]
+            Self::Sigstore(v) => SignatureCheck::Verified {
+                algorithm: SigningAlgorithm::Sigstore,
+                key_id: v.identity().clone(),
+                trusted_comment: v.issuer().clone(),
+            },
         }
     }
 }
 
 pub struct MinisignVerifier {
     pubkey: PublicKey,
+    key_id: CompactString,
     signature: Signature,
 }
 
@@ -59,7 +112,11 @@ impl MinisignVerifier {
             FetchError::InvalidSignature
         })?;
 
-        Ok(Self { pubkey, signature })
+        Ok(Self {
+            pubkey,
+            key_id: minisign_key_id(&config.pubkey),
+            signature,
+        })
     }
 
     pub fn data_verifier(&self) -> Result<Box<dyn DataVerifier + '_>, FetchError> {
@@ -73,6 +130,22 @@ impl MinisignVerifier {
     }
 }
 
+/// Return the key id of a minisign public key the way minisign prints it,
+/// i.e. as upper case hex of the little-endian u64.
+///
+/// `pubkey` must have been successfully parsed by [`PublicKey::from_base64`].
+fn minisign_key_id(pubkey: &str) -> CompactString {
+    let decoded = STANDARD.decode(pubkey).unwrap_or_default();
+
+    match decoded.get(2..10) {
+        Some(key_id) => format_compact!(
+            "{:016X}",
+            u64::from_le_bytes(key_id.try_into().expect("slice has 8 bytes"))
+        ),
+        None => CompactString::default(),
+    }
+}
+
 pub struct MinisignDataVerifier<'a>(StreamVerifier<'a>);
 
 impl<'a> DataVerifier for MinisignDataVerifier<'a> {
@@ -89,3 +162,17 @@ impl<'a> DataVerifier for MinisignDataVerifier<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_minisign_key_id() {
+        // Public key of quickinstall.
+        assert_eq!(
+            minisign_key_id("RWTdnnab2pAka9OdwgCMYyOE66M/BlQoFWaJ/JjwcPV+f3n24IRTj97t"),
+            "6B2490DA9B769EDD"
+        );
+    }
+}
@@ -0,0 +1,893 @@
+//! Verification of [sigstore bundles](https://docs.sigstore.dev/about/bundle/),
+//! signed with a short-lived certificate issued by Fulcio and logged in Rekor.
+//!
+//! A bundle is accepted if:
+//!  - its certificate is issued by a certificate authority of the trusted root,
+//!    for code signing, to an identity under the repository of the crate,
+//!    authenticated by GitHub Actions if the repository is on github.com,
+//!  - its transparency log entry is promised by a log of the trusted root, at a
+//!    time the certificate was valid, and covers the signature of the bundle,
+//!  - its signature is made by the certificate, either over the package itself
+//!    or over an in-toto statement listing the sha256 digest of the package.
+
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use binstalk_downloader::{download::DataVerifier, gh_api_client::GhRepo};
+use bytes::Bytes;
+use compact_str::{format_compact, CompactString};
+use once_cell::sync::Lazy;
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use sha2::{Digest, Sha256, Sha384};
+use tracing::{error, trace};
+use x509_cert::{
+    der::{
+        asn1::{ObjectIdentifier, Utf8StringRef},
+        Decode, Encode,
+    },
+    ext::pkix::{name::GeneralName, ExtendedKeyUsage, SubjectAltName},
+    spki::SubjectPublicKeyInfoOwned,
+    Certificate,
+};
+
+use crate::FetchError;
+
+const ECDSA_WITH_SHA_256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_WITH_SHA_384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const SECP256R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const SECP384R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+const CODE_SIGNING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.3");
+/// Fulcio extension of the OIDC issuer, as a raw string.
+const FULCIO_ISSUER_V1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+/// Fulcio extension of the OIDC issuer, as a DER encoded UTF8String.
+const FULCIO_ISSUER_V2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.8");
+
+/// OIDC issuer of the identities of the GitHub Actions workflows.
+const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+/// Trusted root of the public good instance of sigstore.
+static PUBLIC_GOOD: Lazy<TrustedRoot> = Lazy::new(|| {
+    TrustedRoot::from_json(include_bytes!("sigstore/trusted_root.json"))
+        .expect("trusted root of the public good instance of sigstore is valid")
+});
+
+fn invalid(reason: impl Into<CompactString>) -> FetchError {
+    FetchError::InvalidAttestation(reason.into())
+}
+
+fn decode_base64(encoded: &str, what: &str) -> Result<Vec<u8>, FetchError> {
+    STANDARD
+        .decode(encoded)
+        .map_err(|err| invalid(format_compact!("{what} is not valid base64: {err}")))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Certificate authorities and transparency logs trusted to vouch for
+/// the identity of signers.
+pub struct TrustedRoot {
+    /// Public keys of the transparency logs, by log id.
+    tlogs: BTreeMap<Vec<u8>, PublicKey>,
+    /// Certificates issuing the certificates of signers.
+    issuers: Vec<Certificate>,
+}
+
+impl TrustedRoot {
+    /// Parse a trusted root in the JSON format distributed by sigstore.
+    pub fn from_json(json: &[u8]) -> Result<Self, FetchError> {
+        let root: json::TrustedRoot = serde_json::from_slice(json)
+            .map_err(|err| invalid(format_compact!("trusted root is invalid: {err}")))?;
+
+        let tlogs = root
+            .tlogs
+            .iter()
+            .map(|tlog| {
+                let key_id = decode_base64(&tlog.log_id.key_id, "log id")?;
+                let key = decode_base64(&tlog.public_key.raw_bytes, "log public key")?;
+                let key = SubjectPublicKeyInfoOwned::from_der(&key)
+                    .map_err(|err| invalid(format_compact!("log public key is invalid: {err}")))?;
+
+                Ok((key_id, PublicKey::new(&key)?))
+            })
+            .collect::<Result<_, FetchError>>()?;
+
+        let issuers = root
+            .certificate_authorities
+            .iter()
+            .filter_map(|ca| ca.cert_chain.certificates.first())
+            .map(|cert| parse_certificate(&cert.raw_bytes))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { tlogs, issuers })
+    }
+}
+
+fn parse_certificate(encoded: &str) -> Result<Certificate, FetchError> {
+    Certificate::from_der(&decode_base64(encoded, "certificate")?)
+        .map_err(|err| invalid(format_compact!("certificate is invalid: {err}")))
+}
+
+enum PublicKey {
+    P256(p256::ecdsa::VerifyingKey),
+    P384(p384::ecdsa::VerifyingKey),
+}
+
+impl PublicKey {
+    fn new(spki: &SubjectPublicKeyInfoOwned) -> Result<Self, FetchError> {
+        let curve = spki
+            .algorithm
+            .parameters
+            .as_ref()
+            .and_then(|parameters| parameters.decode_as::<ObjectIdentifier>().ok());
+        let key = spki.subject_public_key.raw_bytes();
+
+        match curve {
+            Some(SECP256R1) => p256::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                .map(Self::P256)
+                .map_err(|_| invalid("P-256 public key is invalid")),
+            Some(SECP384R1) => p384::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                .map(Self::P384)
+                .map_err(|_| invalid("P-384 public key is invalid")),
+            _ => Err(invalid(format_compact!(
+                "public key algorithm {} is not supported",
+                spki.algorithm.oid
+            ))),
+        }
+    }
+
+    /// Verify the DER encoded ECDSA `signature` of the message hashed to `prehash`.
+    fn verify_prehash(&self, prehash: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Self::P256(key) => p256::ecdsa::Signature::from_der(signature)
+                .map_or(false, |signature| {
+                    key.verify_prehash(prehash, &signature).is_ok()
+                }),
+            Self::P384(key) => p384::ecdsa::Signature::from_der(signature)
+                .map_or(false, |signature| {
+                    key.verify_prehash(prehash, &signature).is_ok()
+                }),
+        }
+    }
+
+    /// Verify the DER encoded ECDSA `signature` of `message`, hashed with
+    /// the digest matching the curve of the key.
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Self::P256(_) => self.verify_prehash(&Sha256::digest(message), signature),
+            Self::P384(_) => self.verify_prehash(&Sha384::digest(message), signature),
+        }
+    }
+}
+
+/// What the signature of a bundle covers, checked once the package is
+/// downloaded.
+enum Signed {
+    /// Signature of the package itself.
+    Package {
+        key: PublicKey,
+        signature: Vec<u8>,
+        /// The sha256 digest the bundle claims for the package.
+        sha256: Option<Vec<u8>>,
+    },
+    /// Verified in-toto statement, listing the hex sha256 digests of its subjects.
+    Statement { sha256: Vec<String> },
+}
+
+pub struct SigstoreVerifier {
+    identity: CompactString,
+    issuer: CompactString,
+    signed: Signed,
+}
+
+impl SigstoreVerifier {
+    /// Verify `bundle` against the trusted root of the public good instance
+    /// of sigstore, requiring it to be signed by an identity under `repo`.
+    pub fn new(bundle: &[u8], repo: Option<&str>) -> Result<Self, FetchError> {
+        Self::with_trusted_root(bundle, repo, &PUBLIC_GOOD)
+    }
+
+    pub fn with_trusted_root(
+        bundle: &[u8],
+        repo: Option<&str>,
+        trusted_root: &TrustedRoot,
+    ) -> Result<Self, FetchError> {
+        let repo = repo.ok_or_else(|| {
+            invalid("the crate has no repository to check the identity of the signer against")
+        })?;
+
+        trace!(?bundle, "parsing sigstore bundle");
+        let bundle: json::Bundle = serde_json::from_slice(bundle)
+            .map_err(|err| invalid(format_compact!("bundle is invalid: {err}")))?;
+        let material = &bundle.verification_material;
+
+        let cert = material
+            .certificate
+            .as_ref()
+            .or_else(|| {
+                material
+                    .x509_certificate_chain
+                    .as_ref()
+                    .and_then(|chain| chain.certificates.first())
+            })
+            .ok_or_else(|| invalid("bundle has no certificate"))?;
+        let cert = parse_certificate(&cert.raw_bytes)?;
+        let tbs = &cert.tbs_certificate;
+
+        verify_issued_by_trusted_root(&cert, trusted_root)?;
+
+        let code_signing = tbs
+            .get::<ExtendedKeyUsage>()
+            .ok()
+            .flatten()
+            .map_or(false, |(_, usage)| usage.0.contains(&CODE_SIGNING));
+        if !code_signing {
+            return Err(invalid("certificate is not issued for code signing"));
+        }
+
+        let identity = certificate_identity(&cert)?;
+        let issuer = certificate_issuer(&cert);
+        check_identity(&identity, &issuer, repo)?;
+
+        let key = PublicKey::new(&tbs.subject_public_key_info)?;
+
+        let (signed, signature) = match (&bundle.message_signature, &bundle.dsse_envelope) {
+            (Some(message), None) => {
+                if !matches!(key, PublicKey::P256(_)) {
+                    return Err(invalid(
+                        "only P-256 keys are supported for signing packages",
+                    ));
+                }
+
+                let signature = decode_base64(&message.signature, "signature")?;
+                let sha256 = message
+                    .message_digest
+                    .as_ref()
+                    .map(|digest| match digest.algorithm.as_str() {
+                        "SHA2_256" => decode_base64(&digest.digest, "message digest"),
+                        algorithm => Err(invalid(format_compact!(
+                            "message digest algorithm {algorithm} is not supported"
+                        ))),
+                    })
+                    .transpose()?;
+
+                (
+                    Signed::Package {
+                        key,
+                        signature: signature.clone(),
+                        sha256,
+                    },
+                    signature,
+                )
+            }
+            (None, Some(envelope)) => {
+                let (sha256, signature) = verify_envelope(envelope, &key)?;
+                (Signed::Statement { sha256 }, signature)
+            }
+            _ => {
+                return Err(invalid(
+                    "bundle must have either a message signature or a DSSE envelope",
+                ))
+            }
+        };
+
+        verify_tlog_entry(&material.tlog_entries, &cert, &signature, trusted_root)?;
+
+        Ok(Self {
+            identity,
+            issuer,
+            signed,
+        })
+    }
+
+    /// Identity of the signer, e.g. the workflow which built the package.
+    pub fn identity(&self) -> &CompactString {
+        &self.identity
+    }
+
+    /// OIDC issuer of the identity of the signer.
+    pub fn issuer(&self) -> &CompactString {
+        &self.issuer
+    }
+
+    pub fn data_verifier(&self) -> Box<dyn DataVerifier + '_> {
+        Box::new(SigstoreDataVerifier {
+            verifier: self,
+            hasher: Sha256::new(),
+        })
+    }
+
+    /// Check the bundle covers the package of the given sha256 digest.
+    fn verify_sha256(&self, sha256: &[u8]) -> Result<(), FetchError> {
+        match &self.signed {
+            Signed::Package {
+                key,
+                signature,
+                sha256: claimed,
+            } => {
+                if claimed
+                    .as_deref()
+                    .map_or(false, |claimed| claimed != sha256)
+                {
+                    return Err(invalid("message digest does not match the package"));
+                }
+                if !key.verify_prehash(sha256, signature) {
+                    return Err(invalid("signature does not match the package"));
+                }
+            }
+            Signed::Statement { sha256: subjects } => {
+                let sha256 = hex(sha256);
+                if !subjects
+                    .iter()
+                    .any(|subject| subject.eq_ignore_ascii_case(&sha256))
+                {
+                    return Err(invalid("statement has no subject matching the package"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn verify_issued_by_trusted_root(
+    cert: &Certificate,
+    trusted_root: &TrustedRoot,
+) -> Result<(), FetchError> {
+    let tbs = cert
+        .tbs_certificate
+        .to_der()
+        .map_err(|err| invalid(format_compact!("certificate is invalid: {err}")))?;
+    let prehash = match cert.signature_algorithm.oid {
+        ECDSA_WITH_SHA_256 => Sha256::digest(&tbs).to_vec(),
+        ECDSA_WITH_SHA_384 => Sha384::digest(&tbs).to_vec(),
+        oid => {
+            return Err(invalid(format_compact!(
+                "certificate signature algorithm {oid} is not supported"
+            )))
+        }
+    };
+
+    let issued = trusted_root
+        .issuers
+        .iter()
+        .filter(|issuer| issuer.tbs_certificate.subject == cert.tbs_certificate.issuer)
+        .filter_map(|issuer| PublicKey::new(&issuer.tbs_certificate.subject_public_key_info).ok())
+        .any(|key| key.verify_prehash(&prehash, cert.signature.raw_bytes()));
+
+    if issued {
+        Ok(())
+    } else {
+        Err(invalid(
+            "certificate is not issued by a trusted certificate authority",
+        ))
+    }
+}
+
+/// Return the URI or email of the subject alternative name of `cert`.
+fn certificate_identity(cert: &Certificate) -> Result<CompactString, FetchError> {
+    let (_, san) = cert
+        .tbs_certificate
+        .get::<SubjectAltName>()
+        .ok()
+        .flatten()
+        .ok_or_else(|| invalid("certificate has no subject alternative name"))?;
+
+    san.0
+        .iter()
+        .find_map(|name| match name {
+            GeneralName::UniformResourceIdentifier(uri) => Some(uri.as_str().into()),
+            GeneralName::Rfc822Name(email) => Some(email.as_str().into()),
+            _ => None,
+        })
+        .ok_or_else(|| invalid("certificate has no identity"))
+}
+
+/// Return the OIDC issuer recorded by Fulcio in `cert`, empty if there is none.
+fn certificate_issuer(cert: &Certificate) -> CompactString {
+    let extensions = cert.tbs_certificate.extensions.as_deref().unwrap_or(&[]);
+    let find = |oid| {
+        extensions
+            .iter()
+            .find(|extension| extension.extn_id == oid)
+            .map(|extension| extension.extn_value.as_bytes())
+    };
+
+    find(FULCIO_ISSUER_V2)
+        .and_then(|value| Utf8StringRef::from_der(value).ok())
+        .map(|issuer| issuer.as_str().into())
+        .or_else(|| {
+            find(FULCIO_ISSUER_V1).and_then(|value| std::str::from_utf8(value).ok().map(Into::into))
+        })
+        .unwrap_or_default()
+}
+
+/// Check `identity` is under `repo`, e.g. that it is a workflow of it, and
+/// that it is authenticated by GitHub Actions if `repo` is on github.com, as
+/// cosign does.
+fn check_identity(identity: &str, issuer: &str, repo: &str) -> Result<(), FetchError> {
+    let repo = repo.trim_end_matches('/');
+    let repo = repo.strip_suffix(".git").unwrap_or(repo);
+
+    if GhRepo::try_extract_from_url(repo).is_some() && issuer != GITHUB_ACTIONS_ISSUER {
+        return Err(invalid(format_compact!(
+            "certificate identity {identity} is issued by {issuer:?}, not by GitHub Actions"
+        )));
+    }
+
+    // The identity must continue with a path segment of the repository,
+    // so that `github.com/o/r` does not accept `github.com/o/r-evil`.
+    let is_under_repo = identity
+        .get(..repo.len())
+        .map_or(false, |prefix| prefix.eq_ignore_ascii_case(repo))
+        && identity[repo.len()..].starts_with('/');
+
+    if is_under_repo {
+        Ok(())
+    } else {
+        Err(invalid(format_compact!(
+            "certificate identity {identity} is not under the repository {repo}"
+        )))
+    }
+}
+
+/// Verify the signature of `envelope` and return the sha256 digests of the
+/// subjects of the in-toto statement it holds, along with the signature.
+fn verify_envelope(
+    envelope: &json::DsseEnvelope,
+    key: &PublicKey,
+) -> Result<(Vec<String>, Vec<u8>), FetchError> {
+    if envelope.payload_type != IN_TOTO_PAYLOAD_TYPE {
+        return Err(invalid(format_compact!(
+            "DSSE payload type {} is not supported",
+            envelope.payload_type
+        )));
+    }
+
+    let payload = decode_base64(&envelope.payload, "DSSE payload")?;
+
+    let mut pae = format!(
+        "DSSEv1 {} {} {} ",
+        envelope.payload_type.len(),
+        envelope.payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    pae.extend_from_slice(&payload);
+
+    let [signature] = envelope.signatures.as_slice() else {
+        return Err(invalid("DSSE envelope must have exactly one signature"));
+    };
+    let signature = decode_base64(&signature.sig, "DSSE signature")?;
+    if !key.verify(&pae, &signature) {
+        return Err(invalid("DSSE signature is invalid"));
+    }
+
+    let statement: json::Statement = serde_json::from_slice(&payload)
+        .map_err(|err| invalid(format_compact!("in-toto statement is invalid: {err}")))?;
+    let sha256 = statement
+        .subject
+        .into_iter()
+        .filter_map(|subject| subject.digest.get("sha256").cloned())
+        .collect();
+
+    Ok((sha256, signature))
+}
+
+/// Verify one of `entries` is promised by a transparency log of the trusted
+/// root while `cert` was valid, and covers `signature`.
+fn verify_tlog_entry(
+    entries: &[json::TlogEntry],
+    cert: &Certificate,
+    signature: &[u8],
+    trusted_root: &TrustedRoot,
+) -> Result<(), FetchError> {
+    let validity = &cert.tbs_certificate.validity;
+    let not_before = validity.not_before.to_unix_duration().as_secs();
+    let not_after = validity.not_after.to_unix_duration().as_secs();
+
+    let mut reason = CompactString::new("bundle has no transparency log entry");
+
+    for entry in entries {
+        match verify_tlog_entry_inner(entry, signature, trusted_root) {
+            Ok(integrated_time) if (not_before..=not_after).contains(&integrated_time) => {
+                return Ok(())
+            }
+            Ok(_) => {
+                reason =
+                    "transparency log entry is not integrated while the certificate is valid".into()
+            }
+            Err(FetchError::InvalidAttestation(err)) => reason = err,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(invalid(reason))
+}
+
+/// Verify the signed entry timestamp of `entry` and return its integrated time.
+fn verify_tlog_entry_inner(
+    entry: &json::TlogEntry,
+    signature: &[u8],
+    trusted_root: &TrustedRoot,
+) -> Result<u64, FetchError> {
+    let log_id = decode_base64(&entry.log_id.key_id, "log id")?;
+    let key = trusted_root
+        .tlogs
+        .get(&log_id)
+        .ok_or_else(|| invalid("transparency log entry is not from a trusted log"))?;
+
+    let promise = entry
+        .inclusion_promise
+        .as_ref()
+        .ok_or_else(|| invalid("transparency log entry has no inclusion promise"))?;
+    let integrated_time: u64 = entry
+        .integrated_time
+        .parse()
+        .map_err(|_| invalid("integrated time of transparency log entry is invalid"))?;
+    let log_index: u64 = entry
+        .log_index
+        .parse()
+        .map_err(|_| invalid("log index of transparency log entry is invalid"))?;
+
+    // Canonical JSON of the entry, as signed by the log.
+    let signed_entry = format!(
+        r#"{{"body":"{}","integratedTime":{integrated_time},"logID":"{}","logIndex":{log_index}}}"#,
+        entry.canonicalized_body,
+        hex(&log_id),
+    );
+    let timestamp = decode_base64(&promise.signed_entry_timestamp, "signed entry timestamp")?;
+    if !key.verify(signed_entry.as_bytes(), &timestamp) {
+        return Err(invalid("signed entry timestamp is invalid"));
+    }
+
+    let body = decode_base64(&entry.canonicalized_body, "transparency log entry")?;
+    let body: json::RekorEntry = serde_json::from_slice(&body)
+        .map_err(|err| invalid(format_compact!("transparency log entry is invalid: {err}")))?;
+    let logged_signatures: Vec<&serde_json::Value> = match body.kind.as_str() {
+        "hashedrekord" => body
+            .spec
+            .pointer("/signature/content")
+            .into_iter()
+            .collect(),
+        "dsse" => body
+            .spec
+            .pointer("/signatures")
+            .and_then(|signatures| signatures.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|signature| signature.get("signature"))
+            .collect(),
+        kind => {
+            return Err(invalid(format_compact!(
+                "transparency log entry kind {kind} is not supported"
+            )))
+        }
+    };
+
+    let covers_signature = logged_signatures
+        .into_iter()
+        .filter_map(|logged| logged.as_str())
+        .any(|logged| {
+            STANDARD
+                .decode(logged)
+                .map_or(false, |logged| logged == signature)
+        });
+    if !covers_signature {
+        return Err(invalid(
+            "transparency log entry does not cover the signature",
+        ));
+    }
+
+    Ok(integrated_time)
+}
+
+pub struct SigstoreDataVerifier<'a> {
+    verifier: &'a SigstoreVerifier,
+    hasher: Sha256,
+}
+
+impl<'a> DataVerifier for SigstoreDataVerifier<'a> {
+    fn update(&mut self, data: &Bytes) {
+        self.hasher.update(data);
+    }
+
+    fn validate(&mut self) -> bool {
+        let sha256 = self.hasher.finalize_reset();
+
+        if let Err(err) = self.verifier.verify_sha256(&sha256) {
+            error!("Failed to verify sigstore bundle: {err}");
+            false
+        } else {
+            true
+        }
+    }
+}
+
+/// The subset of the JSON formats of sigstore used for verification.
+mod json {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct TrustedRoot {
+        #[serde(default)]
+        pub(super) tlogs: Vec<TransparencyLog>,
+        #[serde(default)]
+        pub(super) certificate_authorities: Vec<CertificateAuthority>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct TransparencyLog {
+        pub(super) public_key: RawBytes,
+        pub(super) log_id: LogId,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct CertificateAuthority {
+        pub(super) cert_chain: CertChain,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct CertChain {
+        pub(super) certificates: Vec<RawBytes>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct RawBytes {
+        pub(super) raw_bytes: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct LogId {
+        pub(super) key_id: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct Bundle {
+        pub(super) verification_material: VerificationMaterial,
+        pub(super) message_signature: Option<MessageSignature>,
+        pub(super) dsse_envelope: Option<DsseEnvelope>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct VerificationMaterial {
+        /// Certificate of the signer, since bundle v0.3.
+        pub(super) certificate: Option<RawBytes>,
+        /// Certificate chain starting with the certificate of the signer,
+        /// before bundle v0.3.
+        pub(super) x509_certificate_chain: Option<CertChain>,
+        #[serde(default)]
+        pub(super) tlog_entries: Vec<TlogEntry>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct TlogEntry {
+        pub(super) log_index: String,
+        pub(super) log_id: LogId,
+        pub(super) integrated_time: String,
+        pub(super) inclusion_promise: Option<InclusionPromise>,
+        pub(super) canonicalized_body: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct InclusionPromise {
+        pub(super) signed_entry_timestamp: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct MessageSignature {
+        pub(super) message_digest: Option<MessageDigest>,
+        pub(super) signature: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct MessageDigest {
+        pub(super) algorithm: String,
+        pub(super) digest: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct DsseEnvelope {
+        pub(super) payload: String,
+        pub(super) payload_type: String,
+        pub(super) signatures: Vec<DsseSignature>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct DsseSignature {
+        pub(super) sig: String,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Statement {
+        pub(super) subject: Vec<Subject>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Subject {
+        #[serde(default)]
+        pub(super) digest: BTreeMap<String, String>,
+    }
+
+    /// Entry of the Rekor transparency log.
+    #[derive(Deserialize)]
+    pub(super) struct RekorEntry {
+        pub(super) kind: String,
+        pub(super) spec: serde_json::Value,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    const REPO: &str = "https://github.com/cargo-bins/cargo-binstall";
+
+    fn fixture(name: &str) -> Vec<u8> {
+        std::fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures/sigstore")
+                .join(name),
+        )
+        .unwrap()
+    }
+
+    fn verify(bundle: &str, repo: &str, package: &[u8]) -> Result<SigstoreVerifier, FetchError> {
+        let trusted_root = TrustedRoot::from_json(&fixture("trusted_root.json")).unwrap();
+        let verifier =
+            SigstoreVerifier::with_trusted_root(&fixture(bundle), Some(repo), &trusted_root)?;
+        verifier.verify_sha256(&Sha256::digest(package))?;
+        Ok(verifier)
+    }
+
+    fn assert_rejected(res: Result<SigstoreVerifier, FetchError>, expected: &str) {
+        match res {
+            Err(FetchError::InvalidAttestation(reason)) => {
+                assert!(reason.contains(expected), "{reason}")
+            }
+            Err(err) => panic!("unexpected error: {err}"),
+            Ok(_) => panic!("bundle is accepted"),
+        }
+    }
+
+    #[test]
+    fn test_public_good_trusted_root() {
+        assert!(!PUBLIC_GOOD.tlogs.is_empty());
+        assert!(!PUBLIC_GOOD.issuers.is_empty());
+    }
+
+    #[test]
+    fn test_accept_message_signature() {
+        let package = fixture("cargo-binstall-x86_64-unknown-linux-gnu");
+        let verifier = verify("message.sigstore.json", REPO, &package).unwrap();
+
+        assert_eq!(
+            verifier.identity().as_str(),
+            "https://github.com/cargo-bins/cargo-binstall/.github/workflows/release-packages.yml@refs/tags/v1.2.3"
+        );
+        assert_eq!(
+            verifier.issuer().as_str(),
+            "https://token.actions.githubusercontent.com"
+        );
+    }
+
+    #[test]
+    fn test_accept_dsse_envelope() {
+        let package = fixture("cargo-binstall-x86_64-unknown-linux-gnu");
+        verify("dsse.sigstore.json", REPO, &package).unwrap();
+
+        // Repository urls are compared case insensitively and may end with `.git`.
+        verify(
+            "dsse.sigstore.json",
+            "https://github.com/Cargo-Bins/cargo-binstall.git",
+            &package,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reject_other_repo() {
+        let package = fixture("cargo-binstall-x86_64-unknown-linux-gnu");
+
+        for repo in [
+            "https://github.com/cargo-bins/cargo",
+            "https://github.com/evil/cargo-binstall",
+        ] {
+            assert_rejected(
+                verify("message.sigstore.json", repo, &package),
+                "is not under the repository",
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_identity() {
+        let identity = "https://github.com/o/r/.github/workflows/release.yml@refs/tags/v1";
+
+        check_identity(identity, GITHUB_ACTIONS_ISSUER, "https://github.com/o/r").unwrap();
+        check_identity(
+            identity,
+            GITHUB_ACTIONS_ISSUER,
+            "https://github.com/O/R.git/",
+        )
+        .unwrap();
+
+        // Only the workflows of GitHub Actions can sign for a GitHub repository.
+        for issuer in ["https://accounts.google.com", ""] {
+            assert!(matches!(
+                check_identity(identity, issuer, "https://github.com/o/r"),
+                Err(FetchError::InvalidAttestation(reason)) if reason.contains("not by GitHub Actions")
+            ));
+        }
+
+        // The repository must end on a path boundary of the identity.
+        for identity in [
+            "https://github.com/o/r-evil/.github/workflows/release.yml@refs/tags/v1",
+            "https://github.com/o/r.evil/.github/workflows/release.yml@refs/tags/v1",
+            "https://github.com/o/r",
+        ] {
+            assert!(matches!(
+                check_identity(identity, GITHUB_ACTIONS_ISSUER, "https://github.com/o/r"),
+                Err(FetchError::InvalidAttestation(reason)) if reason.contains("is not under the repository")
+            ));
+        }
+
+        // Other forges may use other identity providers.
+        check_identity(
+            "https://gitlab.com/o/r//.gitlab-ci.yml@refs/tags/v1",
+            "https://gitlab.com",
+            "https://gitlab.com/o/r",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reject_other_package() {
+        assert_rejected(
+            verify("message.sigstore.json", REPO, b"tampered"),
+            "does not match the package",
+        );
+        assert_rejected(
+            verify("dsse.sigstore.json", REPO, b"tampered"),
+            "no subject matching the package",
+        );
+    }
+
+    #[test]
+    fn test_reject_untrusted_ca() {
+        let package = fixture("cargo-binstall-x86_64-unknown-linux-gnu");
+        assert_rejected(
+            verify("untrusted-ca.sigstore.json", REPO, &package),
+            "not issued by a trusted certificate authority",
+        );
+    }
+
+    #[test]
+    fn test_reject_expired_certificate() {
+        let package = fixture("cargo-binstall-x86_64-unknown-linux-gnu");
+        assert_rejected(
+            verify("expired.sigstore.json", REPO, &package),
+            "not integrated while the certificate is valid",
+        );
+    }
+}
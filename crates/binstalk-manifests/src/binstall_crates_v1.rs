@@ -179,8 +179,23 @@ impl Records {
 
     /// **Warning: This will overwrite all existing records!**
     pub fn overwrite(mut self) -> Result<(), Error> {
+        self.flush()
+    }
+
+    /// Write the records to the file, while keeping it locked.
+    ///
+    /// **Warning: This will overwrite all existing records!**
+    pub fn flush(&mut self) -> Result<(), Error> {
         self.file.rewind()?;
-        write_to(&mut self.file, &mut self.data.into_iter())?;
+
+        let writer = io::BufWriter::with_capacity(BUFFER_SIZE, &mut self.file);
+        let mut ser = serde_json::Serializer::new(writer);
+
+        for item in &self.data {
+            item.serialize(&mut ser)?;
+        }
+
+        ser.into_inner().flush()?;
 
         let len = self.file.stream_position()?;
         self.file.set_len(len)?;
@@ -239,7 +254,7 @@ impl<'a> IntoIterator for &'a Records {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::crate_info::CrateSource;
+    use crate::crate_info::{CrateSource, FetchSource};
 
     use compact_str::CompactString;
     use detect_targets::TARGET;
@@ -270,6 +285,14 @@ mod test {
                 source: CrateSource::cratesio_registry(),
                 target: target.clone(),
                 bins: vec!["1".into(), "2".into()],
+                selected_bins: Vec::new(),
+                bin_digests: Default::default(),
+                extra_files: Vec::new(),
+                links: Vec::new(),
+                degraded: false,
+                artifact_version: None,
+                verification: None,
+                fetch_source: None,
             },
             CrateInfo {
                 name: "b".into(),
@@ -278,6 +301,14 @@ mod test {
                 source: CrateSource::cratesio_registry(),
                 target: target.clone(),
                 bins: vec!["1".into(), "2".into()],
+                selected_bins: Vec::new(),
+                bin_digests: Default::default(),
+                extra_files: Vec::new(),
+                links: Vec::new(),
+                degraded: false,
+                artifact_version: None,
+                verification: None,
+                fetch_source: None,
             },
             CrateInfo {
                 name: "a".into(),
@@ -286,6 +317,14 @@ mod test {
                 source: CrateSource::cratesio_registry(),
                 target: target.clone(),
                 bins: vec!["1".into()],
+                selected_bins: Vec::new(),
+                bin_digests: Default::default(),
+                extra_files: Vec::new(),
+                links: Vec::new(),
+                degraded: false,
+                artifact_version: None,
+                verification: None,
+                fetch_source: None,
             },
         ];
 
@@ -316,6 +355,14 @@ mod test {
             source: CrateSource::cratesio_registry(),
             target,
             bins: vec!["1".into(), "2".into()],
+            selected_bins: Vec::new(),
+            bin_digests: Default::default(),
+            extra_files: Vec::new(),
+            links: Vec::new(),
+            degraded: false,
+            artifact_version: None,
+            verification: None,
+            fetch_source: None,
         };
         append_to_path(path, [new_metadata.clone()]).unwrap();
         metadata_set.insert(new_metadata);
@@ -323,4 +370,75 @@ mod test {
         let records = Records::load_from_path(path).unwrap();
         assert_records_eq!(&records, &metadata_set);
     }
+
+    #[test]
+    fn fetch_source_rw_test() {
+        let named_tempfile = NamedTempFile::new().unwrap();
+        let path = named_tempfile.path();
+
+        let fetch_sources = [
+            Some(FetchSource::GhCrateMeta {
+                url: "https://github.com/cargo-bins/cargo-binstall/releases/download/v0.1.0/a-x86_64-unknown-linux-gnu.tgz".parse().unwrap(),
+                repo: Some("https://github.com/cargo-bins/cargo-binstall".into()),
+            }),
+            Some(FetchSource::GhCrateMeta {
+                url: "https://example.com/b.tgz".parse().unwrap(),
+                repo: None,
+            }),
+            Some(FetchSource::QuickInstall {
+                url: "https://github.com/cargo-bins/cargo-quickinstall/releases/download/c-0.1.0/c-0.1.0-x86_64-unknown-linux-gnu.tar.gz".parse().unwrap(),
+            }),
+            Some(FetchSource::SourceBuild),
+            None,
+        ];
+
+        append_to_path(
+            path,
+            fetch_sources
+                .iter()
+                .zip(["a", "b", "c", "d", "e"])
+                .map(|(fetch_source, name)| CrateInfo {
+                    name: name.into(),
+                    version_req: "*".into(),
+                    current_version: Version::new(0, 1, 0),
+                    source: CrateSource::cratesio_registry(),
+                    target: TARGET.into(),
+                    bins: vec![name.into()],
+                    selected_bins: Vec::new(),
+                    bin_digests: Default::default(),
+                    extra_files: Vec::new(),
+                    links: Vec::new(),
+                    degraded: false,
+                    artifact_version: None,
+                    verification: None,
+                    fetch_source: fetch_source.clone(),
+                }),
+        )
+        .unwrap();
+
+        let records = Records::load_from_path(path).unwrap();
+        let loaded: Vec<_> = records
+            .into_iter()
+            .map(|data| data.crate_info.fetch_source.clone())
+            .collect();
+        assert_eq!(loaded, fetch_sources);
+    }
+
+    #[test]
+    fn without_fetch_source_test() {
+        let named_tempfile = NamedTempFile::new().unwrap();
+        let path = named_tempfile.path();
+
+        // Record written before the fetch source was recorded.
+        fs::write(
+            path,
+            r#"{"name":"a","version_req":"*","current_version":"0.1.0","source":{"source_type":"Registry","url":"https://github.com/rust-lang/crates.io-index"},"target":"x86_64-unknown-linux-gnu","bins":["a"]}"#,
+        )
+        .unwrap();
+
+        let records = Records::load_from_path(path).unwrap();
+        let crate_info = records.get("a").unwrap();
+        assert_eq!(crate_info.fetch_source, None);
+        assert_eq!(crate_info.bins, ["a"]);
+    }
 }
@@ -19,13 +19,13 @@ use miette::Diagnostic;
 use serde::Deserialize;
 use thiserror::Error;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct Install {
     /// `cargo install` destination directory
     pub root: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct Http {
     /// HTTP proxy in libcurl format: "host:port"
     ///
@@ -36,9 +36,19 @@ pub struct Http {
     /// env: CARGO_HTTP_TIMEOUT or HTTP_TIMEOUT
     pub timeout: Option<u64>,
     /// path to Certificate Authority (CA) bundle
+    ///
+    /// env: CARGO_HTTP_CAINFO
     pub cainfo: Option<PathBuf>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct Net {
+    /// Do not access the network
+    ///
+    /// env: CARGO_NET_OFFLINE
+    pub offline: Option<bool>,
+}
+
 #[derive(Eq, PartialEq, Debug, Deserialize)]
 #[serde(untagged)]
 pub enum Env {
@@ -50,23 +60,74 @@ pub enum Env {
     },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct Registry {
     pub index: Option<CompactString>,
+    /// Token of a private registry, usually set in `credentials.toml`.
+    ///
+    /// env: CARGO_REGISTRIES_<name>_TOKEN
+    pub token: Option<CompactString>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct DefaultRegistry {
     pub default: Option<CompactString>,
 }
 
+/// Configurations specific to binstall, under the `binstall` table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Binstall {
+    /// Base64 encoded sha256 hashes of the SubjectPublicKeyInfo of the
+    /// certificates accepted for specific hosts.
+    pub spki_pins: Option<BTreeMap<CompactString, Vec<CompactString>>>,
+    /// Versions of crates known to be broken, which are skipped when
+    /// resolving.
+    pub denylist: Option<BTreeMap<CompactString, Vec<DenylistEntry>>>,
+    /// Appended to the User-Agent of the requests.
+    pub user_agent_suffix: Option<CompactString>,
+    /// Url to report the installed crates to.
+    pub usage_report_endpoint: Option<CompactString>,
+    /// Do not report anything, even if `usage_report_endpoint` is set.
+    pub disable_telemetry: Option<bool>,
+    /// Prefixes of the urls of packages mapped to the prefixes of the urls
+    /// they are downloaded from instead, e.g. of a mirror.
+    pub url_rewrite: Option<BTreeMap<CompactString, CompactString>>,
+}
+
+/// A version in the `binstall.denylist` table.
+#[derive(Eq, PartialEq, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum DenylistEntry {
+    Version(CompactString),
+    WithOptions {
+        version: CompactString,
+        /// Only deny the artifacts of this target.
+        target: Option<CompactString>,
+        /// Why the version is denied.
+        reason: Option<CompactString>,
+    },
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub struct Config {
     pub install: Option<Install>,
     pub http: Option<Http>,
+    pub net: Option<Net>,
     pub env: Option<BTreeMap<CompactString, Env>>,
     pub registries: Option<BTreeMap<CompactString, Registry>>,
     pub registry: Option<DefaultRegistry>,
+    pub binstall: Option<Binstall>,
+}
+
+/// Merge `lower` into `higher`, keeping the values of `higher`.
+fn merge_map<K: Ord, V>(higher: &mut Option<BTreeMap<K, V>>, lower: Option<BTreeMap<K, V>>) {
+    if let Some(lower) = lower {
+        let higher = higher.get_or_insert_with(Default::default);
+        for (key, value) in lower {
+            higher.entry(key).or_insert(value);
+        }
+    }
 }
 
 fn join_if_relative(path: Option<&mut PathBuf>, dir: &Path) {
@@ -85,10 +146,144 @@ impl Config {
         Self::load_from_path(Self::default_path()?)
     }
 
+    /// Load the configs the way cargo discovers them: `.cargo/config.toml`
+    /// (or `.cargo/config`) in `cwd` and every parent of it, then
+    /// `config.toml` and `credentials.toml` in `cargo_home`.
+    ///
+    /// A config closer to `cwd` takes precedence over the ones further
+    /// away, and `cargo_home` has the lowest precedence.
+    ///
+    /// The security-relevant configs, see [`Config::remove_untrusted`], are
+    /// only read from `cargo_home`, so that a cloned repository cannot
+    /// change them for the runs inside it.
+    pub fn load_hierarchy(cwd: &Path, cargo_home: &Path) -> Result<Self, ConfigLoadError> {
+        let mut config = Config::default();
+        let mut loaded_cargo_home = false;
+
+        for dir in cwd.ancestors() {
+            let dot_cargo = dir.join(".cargo");
+            let path = ["config.toml", "config"]
+                .into_iter()
+                .map(|file_name| dot_cargo.join(file_name))
+                .find(|path| path.is_file());
+
+            if let Some(path) = path {
+                let mut loaded = Self::load_from_path(path)?;
+                if dot_cargo == cargo_home {
+                    loaded_cargo_home = true;
+                } else {
+                    loaded.remove_untrusted();
+                }
+                config.merge(loaded);
+            }
+        }
+
+        if !loaded_cargo_home {
+            config.merge(Self::load_from_path(cargo_home.join("config.toml"))?);
+        }
+        config.merge(Self::load_from_path(cargo_home.join("credentials.toml"))?);
+
+        Ok(config)
+    }
+
+    /// Remove the configs which are only trusted in `$CARGO_HOME` and the
+    /// environment: `http.cainfo`, `binstall.spki-pins`, `binstall.url-rewrite`,
+    /// `binstall.usage-report-endpoint` and `binstall.disable-telemetry`.
+    fn remove_untrusted(&mut self) {
+        if let Some(http) = &mut self.http {
+            http.cainfo = None;
+        }
+        if let Some(binstall) = &mut self.binstall {
+            binstall.spki_pins = None;
+            binstall.url_rewrite = None;
+            binstall.usage_report_endpoint = None;
+            binstall.disable_telemetry = None;
+        }
+    }
+
+    /// Merge `lower`, which has a lower precedence, into `self`.
+    fn merge(&mut self, lower: Config) {
+        if let Some(lower) = lower.install {
+            let install = self.install.get_or_insert_with(Default::default);
+            install.root = install.root.take().or(lower.root);
+        }
+        if let Some(lower) = lower.http {
+            let http = self.http.get_or_insert_with(Default::default);
+            http.proxy = http.proxy.take().or(lower.proxy);
+            http.timeout = http.timeout.or(lower.timeout);
+            http.cainfo = http.cainfo.take().or(lower.cainfo);
+        }
+        if let Some(lower) = lower.net {
+            let net = self.net.get_or_insert_with(Default::default);
+            net.offline = net.offline.or(lower.offline);
+        }
+        merge_map(&mut self.env, lower.env);
+        if let Some(lower) = lower.registries {
+            let registries = self.registries.get_or_insert_with(Default::default);
+            for (name, lower) in lower {
+                let registry = registries.entry(name).or_default();
+                registry.index = registry.index.take().or(lower.index);
+                registry.token = registry.token.take().or(lower.token);
+            }
+        }
+        if let Some(lower) = lower.registry {
+            let registry = self.registry.get_or_insert_with(Default::default);
+            registry.default = registry.default.take().or(lower.default);
+        }
+        if let Some(lower) = lower.binstall {
+            let binstall = self.binstall.get_or_insert_with(Default::default);
+            merge_map(&mut binstall.spki_pins, lower.spki_pins);
+            merge_map(&mut binstall.denylist, lower.denylist);
+            merge_map(&mut binstall.url_rewrite, lower.url_rewrite);
+            binstall.user_agent_suffix = binstall
+                .user_agent_suffix
+                .take()
+                .or(lower.user_agent_suffix);
+            binstall.usage_report_endpoint = binstall
+                .usage_report_endpoint
+                .take()
+                .or(lower.usage_report_endpoint);
+            binstall.disable_telemetry = binstall.disable_telemetry.or(lower.disable_telemetry);
+        }
+    }
+
+    /// Override the configs with their environment variables, e.g.
+    /// `CARGO_HTTP_PROXY` for `http.proxy`, as cargo does.
+    ///
+    ///  * `get_env` - return the value of an environment variable.
+    pub fn apply_env(
+        &mut self,
+        get_env: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigLoadError> {
+        let invalid =
+            |name: &'static str, value: String| ConfigLoadError::InvalidEnv { name, value };
+
+        if let Some(proxy) = get_env("CARGO_HTTP_PROXY") {
+            self.http.get_or_insert_with(Default::default).proxy = Some(proxy.into());
+        }
+        if let Some(timeout) = get_env("CARGO_HTTP_TIMEOUT") {
+            let timeout = timeout
+                .parse()
+                .map_err(|_| invalid("CARGO_HTTP_TIMEOUT", timeout))?;
+            self.http.get_or_insert_with(Default::default).timeout = Some(timeout);
+        }
+        if let Some(cainfo) = get_env("CARGO_HTTP_CAINFO") {
+            self.http.get_or_insert_with(Default::default).cainfo = Some(cainfo.into());
+        }
+        if let Some(offline) = get_env("CARGO_NET_OFFLINE") {
+            let offline = offline
+                .parse()
+                .map_err(|_| invalid("CARGO_NET_OFFLINE", offline))?;
+            self.net.get_or_insert_with(Default::default).offline = Some(offline);
+        }
+
+        Ok(())
+    }
+
     /// * `dir` - path to the dir where the config.toml is located.
-    ///           For relative path in the config, `Config::load_from_reader`
-    ///           will join the `dir` and the relative path to form the final
-    ///           path.
+    ///   For relative path in the config, `Config::load_from_reader`
+    ///   will join the `dir` and the relative path to form the final
+    ///   path.
     pub fn load_from_reader<R: io::Read>(
         mut reader: R,
         dir: &Path,
@@ -159,6 +354,9 @@ pub enum ConfigLoadError {
 
     #[error("Failed to deserialize toml: {0}")]
     TomlParse(Box<toml_edit::de::Error>),
+
+    #[error("Invalid value of environment variable {name}: {value:?}")]
+    InvalidEnv { name: &'static str, value: String },
 }
 
 impl From<toml_edit::de::Error> for ConfigLoadError {
@@ -177,7 +375,7 @@ impl From<toml_edit::TomlError> for ConfigLoadError {
 mod tests {
     use super::*;
 
-    use std::{io::Cursor, path::MAIN_SEPARATOR};
+    use std::{fs, io::Cursor, path::MAIN_SEPARATOR};
 
     use compact_str::format_compact;
 
@@ -198,6 +396,30 @@ cainfo = "cert.pem"         # path to Certificate Authority (CA) bundle
 
 [install]
 root = "/some/path"         # `cargo install` destination directory
+
+[net]
+offline = true              # do not access the network
+retry = 3                   # unrelated keys are ignored
+
+[unrelated]
+key = { nested = [1, 2] }
+
+[binstall]
+user-agent-suffix = "corp-ci/7"
+usage-report-endpoint = "https://usage.example.com/binstall"
+disable-telemetry = true
+
+[binstall.spki-pins]
+"mirror.example.com" = ["47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="]
+
+[binstall.url-rewrite]
+"https://github.com/" = "https://mirror.example.com/github/"
+
+[binstall.denylist]
+"cargo-binstall" = [
+    "1.4.0",
+    { version = "1.4.1", target = "x86_64-unknown-linux-musl", reason = "truncated upload" },
+]
     "#;
 
     #[test]
@@ -214,6 +436,8 @@ root = "/some/path"         # `cargo install` destination directory
         assert_eq!(http.timeout.unwrap(), 30);
         assert_eq!(http.cainfo.unwrap(), Path::new("root").join("cert.pem"));
 
+        assert_eq!(config.net.unwrap().offline, Some(true));
+
         let env = config.env.unwrap();
         assert_eq!(env.len(), 3);
         assert_eq!(
@@ -236,5 +460,189 @@ root = "/some/path"         # `cargo install` destination directory
                 relative: Some(true),
             }
         );
+
+        let binstall = config.binstall.unwrap();
+        assert_eq!(binstall.user_agent_suffix.as_deref(), Some("corp-ci/7"));
+        assert_eq!(
+            binstall.usage_report_endpoint.as_deref(),
+            Some("https://usage.example.com/binstall")
+        );
+        assert_eq!(binstall.disable_telemetry, Some(true));
+        let spki_pins = binstall.spki_pins.unwrap();
+        assert_eq!(
+            spki_pins.get("mirror.example.com").unwrap(),
+            &["47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="]
+        );
+
+        let url_rewrite = binstall.url_rewrite.unwrap();
+        assert_eq!(
+            url_rewrite
+                .get("https://github.com/")
+                .map(CompactString::as_str),
+            Some("https://mirror.example.com/github/")
+        );
+
+        let denylist = binstall.denylist.unwrap();
+        assert_eq!(
+            denylist.get("cargo-binstall").unwrap(),
+            &[
+                DenylistEntry::Version(CompactString::new("1.4.0")),
+                DenylistEntry::WithOptions {
+                    version: CompactString::new("1.4.1"),
+                    target: Some(CompactString::new("x86_64-unknown-linux-musl")),
+                    reason: Some(CompactString::new("truncated upload")),
+                },
+            ]
+        );
+    }
+
+    fn write_config(dir: &Path, file_name: &str, content: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(file_name), content).unwrap();
+    }
+
+    #[test]
+    fn test_load_hierarchy() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+
+        let cargo_home = root.join("cargo-home");
+        let project = root.join("workspace").join("project");
+        let cwd = project.join("src");
+        fs::create_dir_all(&cwd).unwrap();
+
+        write_config(
+            &cargo_home,
+            "config.toml",
+            r#"
+[http]
+proxy = "home-proxy:3128"
+timeout = 10
+cainfo = "home.pem"
+
+[net]
+offline = false
+
+[binstall]
+user-agent-suffix = "home"
+disable-telemetry = true
+
+[binstall.spki-pins]
+"github.com" = ["home-pin"]
+
+[binstall.url-rewrite]
+"https://github.com/" = "https://home.example.com/github/"
+
+[registries.private]
+index = "sparse+https://home.example.com/index/"
+"#,
+        );
+        write_config(
+            &cargo_home,
+            "credentials.toml",
+            r#"
+[registries.private]
+token = "secret"
+"#,
+        );
+        write_config(
+            &root.join("workspace").join(".cargo"),
+            "config.toml",
+            r#"
+[http]
+cainfo = "workspace.pem"
+
+[net]
+offline = true
+
+[binstall]
+disable-telemetry = false
+usage-report-endpoint = "https://workspace.example.com/report"
+
+[binstall.spki-pins]
+"github.com" = ["workspace-pin"]
+
+[binstall.url-rewrite]
+"https://github.com/" = "https://workspace.example.com/github/"
+"https://static.crates.io/" = "https://workspace.example.com/crates/"
+"#,
+        );
+        // `config` without extension is still supported.
+        write_config(
+            &project.join(".cargo"),
+            "config",
+            r#"
+[http]
+proxy = "project-proxy:3128"
+"#,
+        );
+
+        let config = Config::load_hierarchy(&cwd, &cargo_home).unwrap();
+
+        let http = config.http.unwrap();
+        // Closest config wins.
+        assert_eq!(http.proxy.as_deref(), Some("project-proxy:3128"));
+        // Cargo home has the lowest precedence.
+        assert_eq!(http.timeout, Some(10));
+        assert_eq!(config.net.unwrap().offline, Some(true));
+        let binstall = config.binstall.unwrap();
+        assert_eq!(binstall.user_agent_suffix.as_deref(), Some("home"));
+
+        // Except for the security-relevant configs, which are ignored in
+        // the project.
+        assert_eq!(http.cainfo.unwrap(), cargo_home.join("home.pem"));
+        assert_eq!(binstall.disable_telemetry, Some(true));
+        assert_eq!(binstall.usage_report_endpoint, None);
+        assert_eq!(binstall.spki_pins.unwrap()["github.com"], ["home-pin"]);
+        let url_rewrite = binstall.url_rewrite.unwrap();
+        assert_eq!(url_rewrite.len(), 1);
+        assert_eq!(
+            url_rewrite["https://github.com/"],
+            "https://home.example.com/github/"
+        );
+        // The token in credentials.toml is merged into the registry.
+        let registry = &config.registries.unwrap()["private"];
+        assert_eq!(
+            registry.index.as_deref(),
+            Some("sparse+https://home.example.com/index/")
+        );
+        assert_eq!(registry.token.as_deref(), Some("secret"));
+
+        // Without any config in the hierarchy.
+        let config = Config::load_hierarchy(root, &root.join("no-cargo-home")).unwrap();
+        assert!(config.http.is_none());
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let mut config = Config::load_from_reader(Cursor::new(&CONFIG), Path::new("root")).unwrap();
+
+        let env = |name: &str| match name {
+            "CARGO_HTTP_PROXY" => Some("env-proxy:8080".to_string()),
+            "CARGO_HTTP_CAINFO" => Some("/etc/ssl/env.pem".to_string()),
+            "CARGO_HTTP_TIMEOUT" => Some("5".to_string()),
+            "CARGO_NET_OFFLINE" => Some("false".to_string()),
+            _ => None,
+        };
+        config.apply_env(env).unwrap();
+
+        let http = config.http.unwrap();
+        assert_eq!(http.proxy.as_deref(), Some("env-proxy:8080"));
+        assert_eq!(http.cainfo.unwrap(), Path::new("/etc/ssl/env.pem"));
+        assert_eq!(http.timeout, Some(5));
+        assert_eq!(config.net.unwrap().offline, Some(false));
+
+        // Nothing set, nothing changed.
+        let mut config = Config::default();
+        config.apply_env(|_| None).unwrap();
+        assert!(config.http.is_none() && config.net.is_none());
+
+        assert!(matches!(
+            Config::default().apply_env(|name| (name == "CARGO_NET_OFFLINE").then(|| "yes".into())),
+            Err(ConfigLoadError::InvalidEnv {
+                name: "CARGO_NET_OFFLINE",
+                ..
+            })
+        ));
     }
 }
@@ -29,7 +29,7 @@ use crate::helpers::create_if_not_exist;
 use super::crate_info::CrateInfo;
 
 mod crate_version_source;
-use crate_version_source::*;
+pub(crate) use crate_version_source::*;
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct CratesToml<'a> {
@@ -159,6 +159,11 @@ impl CratesToml<'_> {
         Self::append_to_path(Self::default_path()?, iter)
     }
 
+    /// Return an iterator over all binaries recorded.
+    pub fn bins(&self) -> impl Iterator<Item = &CompactString> {
+        self.v1.iter().flat_map(|(_cvs, bins)| bins.iter())
+    }
+
     /// Return BTreeMap with crate name as key and its corresponding version
     /// as value.
     pub fn collect_into_crates_versions(
@@ -235,6 +240,14 @@ mod tests {
                 source: CrateSource::cratesio_registry(),
                 target: TARGET.into(),
                 bins: vec!["cargo-binstall".into()],
+                selected_bins: Vec::new(),
+                bin_digests: Default::default(),
+                extra_files: Vec::new(),
+                links: Vec::new(),
+                degraded: false,
+                artifact_version: None,
+                verification: None,
+                fetch_source: None,
             }],
         )
         .unwrap();
@@ -261,6 +274,14 @@ mod tests {
                 source: CrateSource::cratesio_registry(),
                 target: TARGET.into(),
                 bins: vec!["cargo-binstall".into()],
+                selected_bins: Vec::new(),
+                bin_digests: Default::default(),
+                extra_files: Vec::new(),
+                links: Vec::new(),
+                degraded: false,
+                artifact_version: None,
+                verification: None,
+                fetch_source: None,
             }],
         )
         .unwrap();
@@ -0,0 +1,291 @@
+//! Cargo's `.crates2.json` manifest.
+//!
+//! This manifest is the successor of [`.crates.toml`](crate::cargo_crates_v1), it records how
+//! each crate is installed on top of its binaries.
+//!
+//! Cargo keeps both manifests in sync and drops entries of `.crates2.json` missing from
+//! `.crates.toml`, so Binstall writes to both for `cargo install --list` and `cargo uninstall`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{self, Seek},
+    iter::IntoIterator,
+    path::{Path, PathBuf},
+};
+
+use compact_str::CompactString;
+use fs_lock::FileLock;
+use home::cargo_home;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    cargo_crates_v1::CrateVersionSource, crate_info::CrateInfo, helpers::create_if_not_exist,
+};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Crates2Json {
+    /// Keyed by the package id, e.g.
+    /// `ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)`.
+    pub installs: BTreeMap<String, InstallInfo>,
+    /// Fields added by newer versions of cargo, kept as is.
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_json::Value>,
+}
+
+/// Same fields as cargo's own `InstallInfo`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct InstallInfo {
+    /// The `--version` requested, if any.
+    pub version_req: Option<String>,
+    pub bins: BTreeSet<CompactString>,
+    pub features: BTreeSet<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    pub profile: String,
+    pub target: Option<String>,
+    /// Output of `rustc -vV`, `None` for prebuilt binaries.
+    pub rustc: Option<String>,
+    /// Fields added by newer versions of cargo, kept as is.
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_json::Value>,
+}
+
+impl From<&CrateInfo> for InstallInfo {
+    fn from(metadata: &CrateInfo) -> Self {
+        Self {
+            version_req: Some(&metadata.version_req)
+                .filter(|version_req| version_req.as_str() != "*")
+                .map(ToString::to_string),
+            bins: metadata.bins.iter().cloned().collect(),
+            features: BTreeSet::new(),
+            all_features: false,
+            no_default_features: false,
+            profile: "release".to_string(),
+            target: Some(metadata.target.to_string()),
+            rustc: None,
+            other: BTreeMap::new(),
+        }
+    }
+}
+
+impl Crates2Json {
+    pub fn default_path() -> Result<PathBuf, Crates2JsonParseError> {
+        Ok(cargo_home()?.join(".crates2.json"))
+    }
+
+    pub fn load() -> Result<Self, Crates2JsonParseError> {
+        Self::load_from_path(Self::default_path()?)
+    }
+
+    pub fn load_from_reader<R: io::Read>(mut reader: R) -> Result<Self, Crates2JsonParseError> {
+        fn inner(reader: &mut dyn io::Read) -> Result<Crates2Json, Crates2JsonParseError> {
+            let mut vec = Vec::new();
+            reader.read_to_end(&mut vec)?;
+
+            if vec.is_empty() {
+                Ok(Crates2Json::default())
+            } else {
+                serde_json::from_slice(&vec).map_err(Crates2JsonParseError::from)
+            }
+        }
+
+        inner(&mut reader)
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, Crates2JsonParseError> {
+        let file = FileLock::new_shared(File::open(path)?)?;
+        Self::load_from_reader(file)
+    }
+
+    /// Remove all versions of crate `name`.
+    pub fn remove(&mut self, name: &str) {
+        self.installs.retain(|pkg_id, _info| {
+            pkg_id
+                .split_once(' ')
+                .map(|(crate_name, _rest)| crate_name != name)
+                .unwrap_or_default()
+        });
+    }
+
+    /// Write it the same way as cargo, without pretty printing.
+    pub fn write_to_writer<W: io::Write>(
+        &self,
+        mut writer: W,
+    ) -> Result<(), Crates2JsonParseError> {
+        serde_json::to_writer(&mut writer, self)?;
+        Ok(())
+    }
+
+    pub fn write_to_file(&self, file: &mut File) -> Result<(), Crates2JsonParseError> {
+        self.write_to_writer(&mut *file)?;
+        let pos = file.stream_position()?;
+        file.set_len(pos)?;
+
+        Ok(())
+    }
+
+    pub fn write_to_path(&self, path: impl AsRef<Path>) -> Result<(), Crates2JsonParseError> {
+        let mut file = FileLock::new_exclusive(File::create(path)?)?;
+        self.write_to_file(&mut file)
+    }
+
+    /// Record the crates, replacing any other version of them.
+    pub fn append_to_file<'a, Iter>(
+        file: &mut File,
+        iter: Iter,
+    ) -> Result<(), Crates2JsonParseError>
+    where
+        Iter: IntoIterator<Item = &'a CrateInfo>,
+    {
+        fn inner(
+            file: &mut File,
+            iter: &mut dyn Iterator<Item = &CrateInfo>,
+        ) -> Result<(), Crates2JsonParseError> {
+            let mut c2 = Crates2Json::load_from_reader(&mut *file)?;
+
+            for metadata in iter {
+                c2.remove(&metadata.name);
+                c2.installs.insert(
+                    CrateVersionSource::from(metadata).to_string(),
+                    InstallInfo::from(metadata),
+                );
+            }
+
+            file.rewind()?;
+            c2.write_to_file(file)?;
+
+            Ok(())
+        }
+
+        inner(file, &mut iter.into_iter())
+    }
+
+    pub fn append_to_path<'a, Iter>(
+        path: impl AsRef<Path>,
+        iter: Iter,
+    ) -> Result<(), Crates2JsonParseError>
+    where
+        Iter: IntoIterator<Item = &'a CrateInfo>,
+    {
+        let mut file = FileLock::new_exclusive(create_if_not_exist(path.as_ref())?)?;
+        Self::append_to_file(&mut file, iter)
+    }
+
+    pub fn append<'a, Iter>(iter: Iter) -> Result<(), Crates2JsonParseError>
+    where
+        Iter: IntoIterator<Item = &'a CrateInfo>,
+    {
+        Self::append_to_path(Self::default_path()?, iter)
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum Crates2JsonParseError {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to (de)serialize json: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cargo_crates_v1::CratesToml;
+
+    use std::fs;
+
+    use semver::Version;
+    use tempfile::TempDir;
+
+    /// Written by `cargo install --path . --root ...` of cargo 1.95.0.
+    const CARGO_CRATES_V2: &str = r#"{"installs":{"realcargo 0.1.0 (path+file:///tmp/realcargo)":{"version_req":null,"bins":["realcargo"],"features":[],"all_features":false,"no_default_features":false,"profile":"release","target":"x86_64-unknown-linux-gnu","rustc":"rustc 1.95.0 (59807616e 2026-04-14)\nbinary: rustc\ncommit-hash: 59807616e1fa2540724bfbac14d7976d7e4a3860\ncommit-date: 2026-04-14\nhost: x86_64-unknown-linux-gnu\nrelease: 1.95.0\nLLVM version: 22.1.2\n"}}}"#;
+
+    fn crate_info(version: Version, version_req: &str) -> CrateInfo {
+        CrateInfo {
+            version_req: version_req.into(),
+            ..CrateInfo::for_test("cargo-binstall", version)
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let c2 = Crates2Json::load_from_reader(CARGO_CRATES_V2.as_bytes()).unwrap();
+        assert_eq!(c2.installs.len(), 1);
+
+        let mut written = Vec::new();
+        c2.write_to_writer(&mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), CARGO_CRATES_V2);
+    }
+
+    #[test]
+    fn test_unknown_fields_kept() {
+        let raw = r#"{"installs":{"a 0.1.0 (registry+https://github.com/rust-lang/crates.io-index)":{"version_req":null,"bins":["a"],"features":[],"all_features":false,"no_default_features":false,"profile":"release","target":null,"rustc":null,"new_field":1}},"new_top":true}"#;
+
+        let mut written = Vec::new();
+        Crates2Json::load_from_reader(raw.as_bytes())
+            .unwrap()
+            .write_to_writer(&mut written)
+            .unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_append() {
+        let tempdir = TempDir::new().unwrap();
+        let path = tempdir.path().join(".crates2.json");
+        fs::write(&path, CARGO_CRATES_V2).unwrap();
+
+        Crates2Json::append_to_path(&path, &[crate_info(Version::new(0, 11, 1), "*")]).unwrap();
+        Crates2Json::append_to_path(&path, &[crate_info(Version::new(0, 12, 0), "0.12")]).unwrap();
+
+        let c2 = Crates2Json::load_from_path(&path).unwrap();
+        assert_eq!(
+            c2.installs.keys().collect::<Vec<_>>(),
+            [
+                "cargo-binstall 0.12.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                "realcargo 0.1.0 (path+file:///tmp/realcargo)",
+            ]
+        );
+
+        let info = &c2.installs
+            ["cargo-binstall 0.12.0 (registry+https://github.com/rust-lang/crates.io-index)"];
+        assert_eq!(info.version_req.as_deref(), Some("0.12"));
+        assert_eq!(
+            info.bins
+                .iter()
+                .map(CompactString::as_str)
+                .collect::<Vec<_>>(),
+            ["cargo-binstall"]
+        );
+        assert_eq!(info.profile, "release");
+        assert_eq!(info.target.as_deref(), Some("x86_64-unknown-linux-gnu"));
+
+        // Cargo drops entries of `.crates2.json` whose package id is not in
+        // `.crates.toml`, so both must agree.
+        let v1_path = tempdir.path().join(".crates.toml");
+        CratesToml::append_to_path(&v1_path, &[crate_info(Version::new(0, 12, 0), "0.12")])
+            .unwrap();
+        let v1 = fs::read_to_string(&v1_path).unwrap();
+        assert!(v1.contains(
+            "\"cargo-binstall 0.12.0 (registry+https://github.com/rust-lang/crates.io-index)\""
+        ));
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let tempdir = TempDir::new().unwrap();
+        let path = tempdir.path().join(".crates2.json");
+
+        File::create(&path).unwrap();
+
+        assert!(Crates2Json::load_from_path(&path)
+            .unwrap()
+            .installs
+            .is_empty());
+    }
+}
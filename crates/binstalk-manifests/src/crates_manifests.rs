@@ -1,8 +1,8 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fs,
     io::{self, Seek},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use fs_lock::FileLock;
@@ -12,6 +12,7 @@ use thiserror::Error as ThisError;
 use crate::{
     binstall_crates_v1::{Error as BinstallCratesV1Error, Records as BinstallCratesV1Records},
     cargo_crates_v1::{CratesToml, CratesTomlParseError},
+    cargo_crates_v2::{Crates2Json, Crates2JsonParseError},
     crate_info::CrateInfo,
     CompactString, Version,
 };
@@ -27,6 +28,10 @@ pub enum ManifestsError {
     #[diagnostic(transparent)]
     CargoManifestV1(#[from] CratesTomlParseError),
 
+    #[error("failed to parse cargo v2 manifest: {0}")]
+    #[diagnostic(transparent)]
+    CargoManifestV2(#[from] Crates2JsonParseError),
+
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
 }
@@ -34,6 +39,7 @@ pub enum ManifestsError {
 pub struct Manifests {
     binstall: BinstallCratesV1Records,
     cargo_crates_v1: FileLock,
+    cargo_crates_v2: FileLock,
 }
 
 impl Manifests {
@@ -44,20 +50,23 @@ impl Manifests {
 
         let binstall = BinstallCratesV1Records::load_from_path(&metadata_path)?;
 
-        // Read cargo_install_v1_metadata
-        let manifest_path = cargo_roots.join(".crates.toml");
-
-        let cargo_crates_v1 = fs::File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(false)
-            .open(manifest_path)
-            .and_then(FileLock::new_exclusive)?;
+        // Lock them in the same order as cargo-install to avoid deadlocks.
+        let open_exclusive = |file_name| {
+            fs::File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(cargo_roots.join(file_name))
+                .and_then(FileLock::new_exclusive)
+        };
+        let cargo_crates_v1 = open_exclusive(".crates.toml")?;
+        let cargo_crates_v2 = open_exclusive(".crates2.json")?;
 
         Ok(Self {
             binstall,
             cargo_crates_v1,
+            cargo_crates_v2,
         })
     }
 
@@ -79,16 +88,180 @@ impl Manifests {
             .map_err(ManifestsError::from)
     }
 
-    pub fn update(mut self, metadata_vec: Vec<CrateInfo>) -> Result<(), ManifestsError> {
+    /// Return names of all binaries recorded in either `.crates.toml` or
+    /// binstall's `crates-v1.json`.
+    ///
+    /// Used to tell apart files in the install path that are not installed
+    /// by cargo-install nor binstall.
+    pub fn load_tracked_bins(&mut self) -> Result<BTreeSet<CompactString>, ManifestsError> {
         self.rewind_cargo_crates_v1()?;
 
-        CratesToml::append_to_file(&mut self.cargo_crates_v1, &metadata_vec)?;
+        let mut bins: BTreeSet<CompactString> =
+            CratesToml::load_from_reader(&mut self.cargo_crates_v1)?
+                .bins()
+                .cloned()
+                .collect();
+
+        bins.extend(
+            (&self.binstall)
+                .into_iter()
+                .flat_map(|data| data.crate_info.bins.iter().cloned()),
+        );
 
-        for metadata in metadata_vec {
-            self.binstall.replace(metadata);
-        }
-        self.binstall.overwrite()?;
+        Ok(bins)
+    }
+
+    /// Return the crates recorded in binstall's `crates-v1.json`, with the
+    /// digests of their binaries.
+    pub fn load_crate_infos(&self) -> BTreeMap<CompactString, CrateInfo> {
+        (&self.binstall)
+            .into_iter()
+            .map(|data| (data.crate_info.name.clone(), data.crate_info.clone()))
+            .collect()
+    }
+
+    /// Return all extra files (shell completions, man pages) recorded in
+    /// binstall's `crates-v1.json`, with the name of the crate they are
+    /// recorded for.
+    pub fn load_tracked_extra_files(&self) -> BTreeMap<PathBuf, CompactString> {
+        (&self.binstall)
+            .into_iter()
+            .flat_map(|data| {
+                data.crate_info
+                    .extra_files
+                    .iter()
+                    .map(|file| (file.clone(), data.crate_info.name.clone()))
+            })
+            .collect()
+    }
+
+    /// Record `metadata` in `.crates.toml`, `.crates2.json` and binstall's
+    /// `crates-v1.json` right away, so that it is kept even if the process
+    /// dies before the other crates are installed.
+    ///
+    /// The files recorded for the previous version of the crate are left
+    /// to the caller to remove.
+    pub fn record(&mut self, metadata: CrateInfo) -> Result<(), ManifestsError> {
+        self.rewind_cargo_crates_v1()?;
+
+        CratesToml::append_to_file(&mut self.cargo_crates_v1, [&metadata])?;
+
+        self.cargo_crates_v2.rewind()?;
+        Crates2Json::append_to_file(&mut self.cargo_crates_v2, [&metadata])?;
+
+        self.binstall.replace(metadata);
+        self.binstall.flush()?;
 
         Ok(())
     }
+
+    /// Remove the crate `name` from `.crates.toml`, `.crates2.json` and
+    /// binstall's `crates-v1.json`, and return its record in the latter if
+    /// any.
+    ///
+    /// The files installed for it are left to the caller to remove.
+    pub fn remove(&mut self, name: &str) -> Result<Option<CrateInfo>, ManifestsError> {
+        self.rewind_cargo_crates_v1()?;
+        let mut crates_toml = CratesToml::load_from_reader(&mut self.cargo_crates_v1)?;
+        crates_toml.remove(name);
+        self.rewind_cargo_crates_v1()?;
+        crates_toml.write_to_file(&mut self.cargo_crates_v1)?;
+
+        self.cargo_crates_v2.rewind()?;
+        let mut crates2_json = Crates2Json::load_from_reader(&mut self.cargo_crates_v2)?;
+        crates2_json.remove(name);
+        self.cargo_crates_v2.rewind()?;
+        crates2_json.write_to_file(&mut self.cargo_crates_v2)?;
+
+        let crate_info = self.binstall.take(name);
+        self.binstall.flush()?;
+
+        Ok(crate_info)
+    }
+
+    /// [`Manifests::record`] all of `metadata_vec`.
+    pub fn update(mut self, metadata_vec: Vec<CrateInfo>) -> Result<(), ManifestsError> {
+        metadata_vec
+            .into_iter()
+            .try_for_each(|metadata| self.record(metadata))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use semver::Version;
+
+    fn crate_info(name: &str) -> CrateInfo {
+        CrateInfo::for_test(name, Version::new(0, 1, 0))
+    }
+
+    #[test]
+    fn test_record_survives_abort() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut manifests = Manifests::open_exclusive(dir.path()).unwrap();
+        manifests.record(crate_info("a")).unwrap();
+        manifests.record(crate_info("b")).unwrap();
+        // Abort before the rest of the batch is installed, without any
+        // further bookkeeping.
+        drop(manifests);
+
+        let mut manifests = Manifests::open_exclusive(dir.path()).unwrap();
+
+        let installed = manifests.load_installed_crates().unwrap();
+        assert_eq!(
+            installed
+                .keys()
+                .map(CompactString::as_str)
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+
+        let bins = manifests.load_tracked_bins().unwrap();
+        assert_eq!(
+            bins.iter().map(CompactString::as_str).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+
+        manifests.cargo_crates_v2.rewind().unwrap();
+        let c2 = Crates2Json::load_from_reader(&mut manifests.cargo_crates_v2).unwrap();
+        assert_eq!(c2.installs.len(), 2);
+
+        assert!(manifests.binstall.contains("a"));
+        assert!(manifests.binstall.contains("b"));
+        assert_eq!(manifests.binstall.len(), 2);
+    }
+
+    #[test]
+    fn test_remove() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut manifests = Manifests::open_exclusive(dir.path()).unwrap();
+        manifests.record(crate_info("a")).unwrap();
+        manifests.record(crate_info("b")).unwrap();
+
+        assert_eq!(manifests.remove("a").unwrap().unwrap().name, "a");
+        assert!(manifests.remove("c").unwrap().is_none());
+        drop(manifests);
+
+        let mut manifests = Manifests::open_exclusive(dir.path()).unwrap();
+
+        let installed = manifests.load_installed_crates().unwrap();
+        assert_eq!(
+            installed
+                .keys()
+                .map(CompactString::as_str)
+                .collect::<Vec<_>>(),
+            ["b"]
+        );
+
+        manifests.cargo_crates_v2.rewind().unwrap();
+        let c2 = Crates2Json::load_from_reader(&mut manifests.cargo_crates_v2).unwrap();
+        assert_eq!(c2.installs.len(), 1);
+
+        assert!(!manifests.binstall.contains("a"));
+        assert_eq!(manifests.binstall.len(), 1);
+    }
 }
@@ -5,16 +5,20 @@
 //!   ([Cargo.toml's `[metadata.binstall]`][cargo_toml_binstall]);
 //! - manifests that record which packages _are_ installed
 //!   ([Cargo's `.crates.toml`][cargo_crates_v1] and
+//!   [`.crates2.json`][cargo_crates_v2], and
 //!   [Binstall's `.crates-v1.json`][binstall_crates_v1]);
-//! - manifests that specify which packages _to_ install (currently none).
+//! - manifests that specify which packages _to_ install
+//!   ([Binstall's lockfile][lockfile]).
 
 mod helpers;
 
 pub mod binstall_crates_v1;
 pub mod cargo_config;
 pub mod cargo_crates_v1;
-/// Contains both [`binstall_crates_v1`] and [`cargo_crates_v1`].
+pub mod cargo_crates_v2;
+/// Contains [`binstall_crates_v1`], [`cargo_crates_v1`] and [`cargo_crates_v2`].
 pub mod crates_manifests;
+pub mod lockfile;
 
 pub use binstalk_types::{cargo_toml_binstall, crate_info};
 pub use compact_str::CompactString;
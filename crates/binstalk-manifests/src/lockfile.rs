@@ -0,0 +1,229 @@
+//! Binstall's lockfile, written by `--write-lockfile` and installed from by
+//! `--from-lockfile`.
+//!
+//! It records the exact artifact installed for each crate, along with its
+//! digest, so that the same artifacts can be installed again elsewhere
+//! without resolving the crates on the registry.
+//!
+//! The format is TOML, with one `[[crate]]` table per crate and target
+//! sorted by name and target, so that lockfiles written on different targets
+//! can be merged and diffed.
+
+use std::{
+    fs::File,
+    io::{self, Seek},
+    path::Path,
+};
+
+use fs_lock::FileLock;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::crate_info::LockedCrate;
+
+/// The version of the format written, lockfiles of other versions are
+/// rejected.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Lockfile {
+    version: u32,
+    #[serde(default, rename = "crate")]
+    crates: Vec<LockedCrate>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self {
+            version: SCHEMA_VERSION,
+            crates: Vec::new(),
+        }
+    }
+}
+
+impl Lockfile {
+    pub fn load_from_reader<R: io::Read>(mut reader: R) -> Result<Self, LockfileError> {
+        fn inner(reader: &mut dyn io::Read) -> Result<Lockfile, LockfileError> {
+            let mut vec = Vec::new();
+            reader.read_to_end(&mut vec)?;
+
+            let mut lockfile: Lockfile = toml_edit::de::from_slice(&vec)?;
+            if lockfile.version != SCHEMA_VERSION {
+                return Err(LockfileError::UnsupportedVersion(lockfile.version));
+            }
+            lockfile.crates.sort_by(|a, b| key(a).cmp(&key(b)));
+
+            Ok(lockfile)
+        }
+
+        inner(&mut reader)
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, LockfileError> {
+        let file = FileLock::new_shared(File::open(path)?)?;
+        Self::load_from_reader(file)
+    }
+
+    /// All the entries, sorted by name and target.
+    pub fn crates(&self) -> &[LockedCrate] {
+        &self.crates
+    }
+
+    /// Add `locked`, replacing the entry of the same crate and target if
+    /// there is one.
+    pub fn insert(&mut self, locked: LockedCrate) {
+        match self
+            .crates
+            .binary_search_by(|entry| key(entry).cmp(&key(&locked)))
+        {
+            Ok(pos) => self.crates[pos] = locked,
+            Err(pos) => self.crates.insert(pos, locked),
+        }
+    }
+
+    pub fn into_crates(self) -> Vec<LockedCrate> {
+        self.crates
+    }
+
+    pub fn write_to_writer<W: io::Write>(&self, mut writer: W) -> Result<(), LockfileError> {
+        fn inner(this: &Lockfile, writer: &mut dyn io::Write) -> Result<(), LockfileError> {
+            let data = toml_edit::ser::to_string_pretty(this)?;
+            writer.write_all(data.as_bytes())?;
+            Ok(())
+        }
+
+        inner(self, &mut writer)
+    }
+
+    pub fn write_to_path(&self, path: impl AsRef<Path>) -> Result<(), LockfileError> {
+        let mut file = FileLock::new_exclusive(File::create(path)?)?;
+        self.write_to_writer(&mut *file)?;
+        let pos = file.stream_position()?;
+        file.set_len(pos)?;
+
+        Ok(())
+    }
+}
+
+fn key(locked: &LockedCrate) -> (&str, &str) {
+    (&locked.name, &locked.target)
+}
+
+#[derive(Debug, Diagnostic, Error)]
+#[non_exhaustive]
+pub enum LockfileError {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Failed to deserialize toml: {0}")]
+    TomlParse(Box<toml_edit::de::Error>),
+
+    #[error("Failed to serialie toml: {0}")]
+    TomlWrite(Box<toml_edit::ser::Error>),
+
+    #[error("lockfile version {0} is not supported, only version {SCHEMA_VERSION} is")]
+    UnsupportedVersion(u32),
+}
+
+impl From<toml_edit::ser::Error> for LockfileError {
+    fn from(e: toml_edit::ser::Error) -> Self {
+        LockfileError::TomlWrite(Box::new(e))
+    }
+}
+
+impl From<toml_edit::de::Error> for LockfileError {
+    fn from(e: toml_edit::de::Error) -> Self {
+        LockfileError::TomlParse(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cargo_toml_binstall::PkgFmt, Url, Version};
+
+    use tempfile::TempDir;
+
+    fn locked(name: &str, target: &str) -> LockedCrate {
+        LockedCrate {
+            name: name.into(),
+            version: Version::new(1, 2, 3),
+            target: target.into(),
+            url: Url::parse(&format!(
+                "https://github.com/o/{name}/releases/download/v1.2.3/{name}-{target}.tgz"
+            ))
+            .unwrap(),
+            sha256: "0123456789abcdef".into(),
+            pkg_fmt: PkgFmt::Tgz,
+            bin_dir: Some(format!("{name}-{target}/{{ bin }}{{ binary-ext }}").into()),
+            bins: vec![name.into()],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tempdir = TempDir::new().unwrap();
+        let path = tempdir.path().join("binstall.lock");
+
+        let mut lockfile = Lockfile::default();
+        lockfile.insert(locked("b", "x86_64-unknown-linux-gnu"));
+        lockfile.insert(locked("a", "x86_64-unknown-linux-gnu"));
+        let raw = LockedCrate {
+            pkg_fmt: PkgFmt::Bin,
+            bin_dir: None,
+            ..locked("a", "aarch64-apple-darwin")
+        };
+        lockfile.insert(raw.clone());
+        lockfile.write_to_path(&path).unwrap();
+
+        let loaded = Lockfile::load_from_path(&path).unwrap();
+        assert_eq!(loaded, lockfile);
+        assert_eq!(
+            loaded
+                .crates()
+                .iter()
+                .map(|entry| (entry.name.as_str(), entry.target.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("a", "aarch64-apple-darwin"),
+                ("a", "x86_64-unknown-linux-gnu"),
+                ("b", "x86_64-unknown-linux-gnu"),
+            ]
+        );
+        assert_eq!(loaded.crates()[0], raw);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("version = 1\n"), "{contents}");
+        assert_eq!(contents.matches("[[crate]]").count(), 3, "{contents}");
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut lockfile = Lockfile::default();
+        lockfile.insert(locked("a", "x86_64-unknown-linux-gnu"));
+
+        let mut other = Lockfile::default();
+        other.insert(locked("a", "aarch64-apple-darwin"));
+        let upgraded = LockedCrate {
+            version: Version::new(1, 3, 0),
+            ..locked("a", "x86_64-unknown-linux-gnu")
+        };
+        other.insert(upgraded.clone());
+
+        for entry in other.crates().iter().cloned() {
+            lockfile.insert(entry);
+        }
+        assert_eq!(lockfile.crates().len(), 2);
+        assert_eq!(lockfile.crates()[1], upgraded);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let err = Lockfile::load_from_reader("version = 2\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, LockfileError::UnsupportedVersion(2)), "{err}");
+
+        let lockfile = Lockfile::load_from_reader("version = 1\n".as_bytes()).unwrap();
+        assert!(lockfile.crates().is_empty());
+    }
+}
@@ -1,9 +1,8 @@
 use std::borrow::Cow;
 
-use base16::{decode as decode_base16, encode_lower as encode_base16};
 use binstalk_downloader::{
     bytes::Bytes,
-    download::{DataVerifier, Download},
+    download::visit_tar,
     remote::{Client, Url},
 };
 use binstalk_types::cargo_toml_binstall::{Meta, TarBasedFmt};
@@ -13,74 +12,75 @@ use leon::{Template, Values};
 use semver::{Version, VersionReq};
 use serde::Deserialize;
 use serde_json::Error as JsonError;
-use sha2::{Digest, Sha256};
 use tracing::{debug, instrument};
 
-use crate::{visitor::ManifestVisitor, RegistryError};
+use crate::{
+    crate_download::{fetch_crate_file, CrateCache},
+    visitor::ManifestVisitor,
+    RegistryError,
+};
 
 #[derive(Deserialize)]
 pub(super) struct RegistryConfig {
     pub(super) dl: CompactString,
+    /// All requests to the registry need a token.
+    #[serde(default, rename = "auth-required")]
+    pub(super) auth_required: bool,
+    /// Url of the web API of the registry, used to search it.
+    #[serde(default)]
+    pub(super) api: Option<CompactString>,
 }
 
-struct Sha256Digest {
-    expected: Vec<u8>,
-    actual: Option<Vec<u8>>,
-    state: Option<Sha256>,
-}
-
-impl Sha256Digest {
-    fn new(checksum: Vec<u8>) -> Self {
-        Self {
-            expected: checksum,
-            actual: None,
-            state: Some(Sha256::new()),
-        }
+/// Return the manifest of the crate from `cache`, or download its `.crate`
+/// file and extract the manifest from it.
+///
+///  * `token` - sent in the `Authorization` header to download the crate
+///    from a private registry.
+#[instrument(skip(client, token))]
+pub(super) async fn parse_manifest(
+    client: Client,
+    crate_name: &str,
+    crate_url: Url,
+    matched_version: MatchedVersion,
+    cache: Option<&CrateCache>,
+    token: Option<&str>,
+) -> Result<Manifest<Meta>, RegistryError> {
+    if let Some(manifest) =
+        cache.and_then(|cache| cache.load_manifest(crate_name, &matched_version))
+    {
+        return Ok(manifest);
     }
-}
 
-impl DataVerifier for Sha256Digest {
-    fn update(&mut self, data: &Bytes) {
-        if let Some(ref mut state) = &mut self.state {
-            state.update(data);
-        }
-    }
+    debug!("Fetching crate from: {crate_url} and extracting Cargo.toml from it");
 
-    fn validate(&mut self) -> bool {
-        if let Some(state) = self.state.take() {
-            self.actual = Some(state.finalize().to_vec());
-        }
+    let data = fetch_crate_file(
+        &client,
+        crate_name,
+        crate_url,
+        &matched_version,
+        cache,
+        token,
+    )
+    .await?;
 
-        self.actual.as_ref().unwrap() == &self.expected
+    let manifest = extract_manifest(crate_name, &matched_version.version, data).await?;
+    if let Some(cache) = cache {
+        cache.save_manifest(crate_name, &matched_version, &manifest);
     }
+
+    Ok(manifest)
 }
 
-#[instrument]
-pub(super) async fn parse_manifest(
-    client: Client,
+/// Extract `Cargo.toml` from the `.crate` file `data`.
+pub(super) async fn extract_manifest(
     crate_name: &str,
-    crate_url: Url,
-    MatchedVersion { version, cksum }: MatchedVersion,
+    version: &str,
+    data: Bytes,
 ) -> Result<Manifest<Meta>, RegistryError> {
-    debug!("Fetching crate from: {crate_url} and extracting Cargo.toml from it");
-
     let mut manifest_visitor = ManifestVisitor::new(format!("{crate_name}-{version}").into());
+    visit_tar(data, TarBasedFmt::Tgz, &mut manifest_visitor).await?;
 
-    let checksum = decode_base16(cksum.as_bytes()).map_err(RegistryError::from)?;
-    let mut digest = Sha256Digest::new(checksum);
-
-    Download::new_with_data_verifier(client, crate_url, &mut digest)
-        .and_visit_tar(TarBasedFmt::Tgz, &mut manifest_visitor)
-        .await?;
-
-    if !digest.validate() {
-        Err(RegistryError::UnmatchedChecksum {
-            expected: encode_base16(digest.expected.as_slice()).into(),
-            actual: encode_base16(digest.actual.unwrap().as_slice()).into(),
-        })
-    } else {
-        manifest_visitor.load_manifest()
-    }
+    manifest_visitor.load_manifest(version)
 }
 
 /// Return components of crate prefix
@@ -150,13 +150,14 @@ pub(super) fn render_dl_template(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub(super) struct RegistryIndexEntry {
-    vers: CompactString,
-    yanked: bool,
-    cksum: String,
+    pub(super) vers: CompactString,
+    pub(super) yanked: bool,
+    pub(super) cksum: String,
 }
 
+#[derive(Debug)]
 pub(super) struct MatchedVersion {
     pub(super) version: CompactString,
     /// sha256 checksum encoded in base16
@@ -164,9 +165,12 @@ pub(super) struct MatchedVersion {
 }
 
 impl MatchedVersion {
+    /// Find the latest version matching `version_req`, skipping yanked
+    /// versions and the `excluded` ones.
     pub(super) fn find(
         it: &mut dyn Iterator<Item = Result<RegistryIndexEntry, JsonError>>,
         version_req: &VersionReq,
+        excluded: &[Version],
     ) -> Result<Self, RegistryError> {
         let mut ret = Option::<(Self, Version)>::None;
 
@@ -189,6 +193,13 @@ impl MatchedVersion {
                 continue;
             }
 
+            if excluded
+                .iter()
+                .any(|excluded| excluded.cmp_precedence(&ver).is_eq())
+            {
+                continue;
+            }
+
             let matched = Self {
                 version: num,
                 cksum: entry.cksum,
@@ -209,3 +220,52 @@ impl MatchedVersion {
             })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::Deserializer as JsonDeserializer;
+
+    const INDEX: &str = r#"
+{"vers":"1.3.0","yanked":false,"cksum":"a"}
+{"vers":"1.3.1","yanked":false,"cksum":"b"}
+{"vers":"1.4.0","yanked":true,"cksum":"c"}
+{"vers":"1.4.1","yanked":false,"cksum":"d"}
+{"vers":"2.0.0","yanked":false,"cksum":"e"}
+"#;
+
+    fn find(version_req: &str, excluded: &[&str]) -> Option<CompactString> {
+        let excluded: Vec<_> = excluded
+            .iter()
+            .map(|version| Version::parse(version).unwrap())
+            .collect();
+
+        MatchedVersion::find(
+            &mut JsonDeserializer::from_str(INDEX).into_iter(),
+            &VersionReq::parse(version_req).unwrap(),
+            &excluded,
+        )
+        .ok()
+        .map(|matched| matched.version)
+    }
+
+    #[test]
+    fn test_find_matched_version() {
+        assert_eq!(find("^1", &[]).as_deref(), Some("1.4.1"));
+        assert_eq!(find("~1.4", &[]).as_deref(), Some("1.4.1"));
+        assert_eq!(find("=1.4.0", &[]), None);
+    }
+
+    #[test]
+    fn test_find_matched_version_excluded() {
+        // Excluded versions are skipped just like yanked ones.
+        assert_eq!(find("^1", &["1.4.1"]).as_deref(), Some("1.3.1"));
+        assert_eq!(find("^1", &["1.4.1", "1.3.1"]).as_deref(), Some("1.3.0"));
+        assert_eq!(find("*", &["2.0.0"]).as_deref(), Some("1.4.1"));
+        assert_eq!(find("~1.4", &["1.4.1"]), None);
+
+        // Build metadata is ignored.
+        assert_eq!(find("^1", &["1.4.1+build.1"]).as_deref(), Some("1.3.1"));
+    }
+}
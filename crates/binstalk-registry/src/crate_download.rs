@@ -0,0 +1,687 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering::Relaxed},
+        Arc,
+    },
+};
+
+use base16::{decode as decode_base16, encode_lower as encode_base16};
+use binstalk_downloader::{
+    bytes::Bytes,
+    download::Download,
+    remote::{with_max_redirects, Client},
+};
+use binstalk_types::cargo_toml_binstall::Meta;
+use cargo_toml_workspace::cargo_toml::Manifest;
+use compact_str::CompactString;
+use semver::{Version, VersionReq};
+use serde_json::Deserializer as JsonDeserializer;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{MatchedVersion, RegistryError, RegistryIndexEntry};
+
+/// Maximum number of redirections followed when fetching a `.crate` file,
+/// which is enough for crates.io downloads, that are redirected once.
+const MAX_REDIRECTS: usize = 5;
+
+/// Content-addressed cache of `.crate` files.
+///
+/// The files are saved to `<dir>/sha256/<checksum>/<crate>-<version>.crate`,
+/// alongside the artifacts saved by `--prefetch`.
+///
+/// The manifests extracted from them are saved next to them as
+/// `<crate>-<version>.json`, so that a crate version, with or without
+/// binstall metadata, is never extracted twice.
+///
+/// The files fetched from sparse indexes are saved to
+/// `<dir>/index/<hash of the index url>/<crate>`, to resolve the crates
+/// with their yanked versions in offline mode.
+#[derive(Clone, Debug)]
+pub struct CrateCache {
+    dir: PathBuf,
+    offline: bool,
+    /// Shared by the clones.
+    hits: Arc<AtomicU32>,
+}
+
+impl CrateCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            offline: false,
+            hits: Default::default(),
+        }
+    }
+
+    /// Never download `.crate` files, fail if they are not cached.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Number of `.crate` files and manifests loaded from the cache so far.
+    pub fn hits(&self) -> u32 {
+        self.hits.load(Relaxed)
+    }
+
+    fn path(
+        &self,
+        crate_name: &str,
+        MatchedVersion { version, cksum }: &MatchedVersion,
+    ) -> PathBuf {
+        self.dir
+            .join("sha256")
+            .join(cksum.to_lowercase())
+            .join(format!("{crate_name}-{version}.crate"))
+    }
+
+    fn manifest_path(&self, crate_name: &str, matched_version: &MatchedVersion) -> PathBuf {
+        self.path(crate_name, matched_version)
+            .with_extension("json")
+    }
+
+    /// Return the cached manifest of the crate, if it is readable.
+    pub(crate) fn load_manifest(
+        &self,
+        crate_name: &str,
+        matched_version: &MatchedVersion,
+    ) -> Option<Manifest<Meta>> {
+        let path = self.manifest_path(crate_name, matched_version);
+        let data = fs::read(&path).ok()?;
+        match serde_json::from_slice(&data) {
+            Ok(manifest) => {
+                debug!("Using cached '{}'", path.display());
+                self.hits.fetch_add(1, Relaxed);
+                Some(manifest)
+            }
+            Err(err) => {
+                warn!("Ignoring corrupt cached '{}': {err}", path.display());
+                None
+            }
+        }
+    }
+
+    pub(crate) fn save_manifest(
+        &self,
+        crate_name: &str,
+        matched_version: &MatchedVersion,
+        manifest: &Manifest<Meta>,
+    ) {
+        let path = self.manifest_path(crate_name, matched_version);
+        let res = serde_json::to_vec(manifest)
+            .map_err(io::Error::from)
+            .and_then(|data| Self::save(&path, &data));
+        if let Err(err) = res {
+            warn!("Failed to cache '{}': {err}", path.display());
+        }
+    }
+
+    fn index_path(&self, index_url: &Url, crate_name: &str) -> PathBuf {
+        let url_hash = encode_base16(&Sha256::digest(index_url.as_str())[..8]);
+        self.dir
+            .join("index")
+            .join(url_hash)
+            .join(crate_name.to_lowercase())
+    }
+
+    /// Return the cached file of `crate_name` in the sparse index at
+    /// `index_url`, if any.
+    pub(crate) fn load_index(&self, index_url: &Url, crate_name: &str) -> Option<Vec<u8>> {
+        let path = self.index_path(index_url, crate_name);
+        let data = fs::read(&path).ok()?;
+        debug!("Using cached '{}'", path.display());
+        Some(data)
+    }
+
+    pub(crate) fn save_index(&self, index_url: &Url, crate_name: &str, data: &[u8]) {
+        let path = self.index_path(index_url, crate_name);
+        if let Err(err) = Self::save(&path, data) {
+            warn!("Failed to cache '{}': {err}", path.display());
+        }
+    }
+
+    /// Find the latest cached `.crate` file of `crate_name` with version
+    /// matching `version_req`, to resolve the crate without fetching the
+    /// index.
+    ///
+    /// The versions are taken from `index`, the cached file of the crate in
+    /// the index, skipping the yanked ones. Without it, they are taken from
+    /// the cached `.crate` files.
+    ///
+    /// Versions in `excluded` and corrupt files are skipped.
+    pub(crate) fn find_matched(
+        &self,
+        crate_name: &str,
+        index: Option<&[u8]>,
+        version_req: &VersionReq,
+        excluded: &[Version],
+    ) -> Option<(MatchedVersion, Bytes)> {
+        let entries: Vec<_> = match index {
+            Some(index) => JsonDeserializer::from_slice(index)
+                .into_iter::<RegistryIndexEntry>()
+                .filter_map(Result::ok)
+                .collect(),
+            None => self.cached_entries(crate_name)?,
+        };
+
+        let mut excluded = excluded.to_vec();
+        loop {
+            let matched_version = MatchedVersion::find(
+                &mut entries.iter().map(|entry| Ok(entry.clone())),
+                version_req,
+                &excluded,
+            )
+            .ok()?;
+
+            let data = decode_base16(matched_version.cksum.as_bytes())
+                .ok()
+                .and_then(|expected| {
+                    self.load(&self.path(crate_name, &matched_version), &expected)
+                });
+            match data {
+                Some(data) => break Some((matched_version, data)),
+                None => excluded.push(Version::parse(&matched_version.version).ok()?),
+            }
+        }
+    }
+
+    /// Return the entries of the cached `.crate` files of `crate_name`, as
+    /// if none of them were yanked.
+    fn cached_entries(&self, crate_name: &str) -> Option<Vec<RegistryIndexEntry>> {
+        let prefix = format!("{crate_name}-");
+
+        let entries = fs::read_dir(self.dir.join("sha256"))
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let cksum = entry.file_name().into_string().ok()?;
+
+                let vers = fs::read_dir(entry.path()).ok()?.find_map(|file| {
+                    let file_name = file.ok()?.file_name().into_string().ok()?;
+                    let version = file_name.strip_prefix(&prefix)?.strip_suffix(".crate")?;
+                    Some(CompactString::from(version))
+                })?;
+
+                Some(RegistryIndexEntry {
+                    vers,
+                    yanked: false,
+                    cksum,
+                })
+            })
+            .collect();
+
+        Some(entries)
+    }
+
+    /// Return the cached `.crate` file, if it is intact.
+    fn load(&self, path: &Path, expected: &[u8]) -> Option<Bytes> {
+        let data = fs::read(path).ok()?;
+        if Sha256::digest(&data).as_slice() == expected {
+            debug!("Using cached '{}'", path.display());
+            self.hits.fetch_add(1, Relaxed);
+            Some(data.into())
+        } else {
+            warn!("Ignoring corrupt cached '{}'", path.display());
+            None
+        }
+    }
+
+    /// Save `data` to `path` through a temporary file so that a partial
+    /// `.crate` file is never left in the cache.
+    fn save(path: &Path, data: &[u8]) -> io::Result<()> {
+        let dir = path.parent().expect("cached crate path must have a parent");
+        fs::create_dir_all(dir)?;
+
+        let mut part_path = path.as_os_str().to_owned();
+        part_path.push(".part");
+        fs::write(&part_path, data)?;
+        fs::rename(&part_path, path)
+    }
+}
+
+/// Fetch the `.crate` file of `crate_name` from `crate_url`, and verify it
+/// against the checksum of the index.
+///
+/// If the crates.io download endpoint fails, e.g. with a transient 403
+/// from its CDN, the file is fetched from static.crates.io directly.
+///
+///  * `token` - sent in the `Authorization` header, for private registries.
+pub(super) async fn fetch_crate_file(
+    client: &Client,
+    crate_name: &str,
+    crate_url: Url,
+    matched_version: &MatchedVersion,
+    cache: Option<&CrateCache>,
+    token: Option<&str>,
+) -> Result<Bytes, RegistryError> {
+    let expected = decode_base16(matched_version.cksum.as_bytes()).map_err(RegistryError::from)?;
+
+    let cached_path = cache.map(|cache| cache.path(crate_name, matched_version));
+    if let (Some(cache), Some(path)) = (cache, &cached_path) {
+        if let Some(data) = cache.load(path, &expected) {
+            return Ok(data);
+        }
+        if cache.offline {
+            return Err(RegistryError::NotCached {
+                crate_name: crate_name.into(),
+                version: matched_version.version.clone(),
+            });
+        }
+    }
+
+    let fallback_url = static_crates_io_url(&crate_url, crate_name, &matched_version.version);
+
+    let data = with_max_redirects(
+        MAX_REDIRECTS,
+        download_crate_file(client, crate_url, fallback_url, token),
+    )
+    .await?;
+
+    let actual = Sha256::digest(&data);
+    if actual.as_slice() != expected {
+        return Err(RegistryError::UnmatchedChecksum {
+            expected: encode_base16(&expected).into(),
+            actual: encode_base16(actual.as_slice()).into(),
+        });
+    }
+
+    if let Some(path) = cached_path {
+        if let Err(err) = CrateCache::save(&path, &data) {
+            warn!("Failed to cache '{}': {err}", path.display());
+        }
+    }
+
+    Ok(data)
+}
+
+async fn download_crate_file(
+    client: &Client,
+    crate_url: Url,
+    fallback_url: Option<Url>,
+    token: Option<&str>,
+) -> Result<Bytes, RegistryError> {
+    debug!("Fetching crate from: {crate_url}");
+
+    if let Some(token) = token {
+        return Ok(client
+            .get(crate_url)
+            .header("Authorization", token)
+            .send(true)
+            .await?
+            .bytes()
+            .await?);
+    }
+
+    match Download::new(client.clone(), crate_url.clone())
+        .into_bytes()
+        .await
+    {
+        Ok(data) => Ok(data),
+        Err(err) => match fallback_url {
+            Some(fallback_url) => {
+                warn!(
+                    "Failed to fetch crate from {crate_url}: {err}, \
+                    retrying from {fallback_url}"
+                );
+                Ok(Download::new(client.clone(), fallback_url)
+                    .into_bytes()
+                    .await?)
+            }
+            None => Err(err.into()),
+        },
+    }
+}
+
+/// Return the url of the `.crate` file on static.crates.io, if `crate_url`
+/// is a crates.io download url other than this one.
+fn static_crates_io_url(crate_url: &Url, crate_name: &str, version: &str) -> Option<Url> {
+    if !matches!(crate_url.host_str(), Some("crates.io" | "static.crates.io")) {
+        return None;
+    }
+
+    let url = Url::parse(&format!(
+        "https://static.crates.io/crates/{crate_name}/{crate_name}-{version}.crate"
+    ))
+    .ok()?;
+
+    (url != *crate_url).then_some(url)
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU16;
+
+    use binstalk_downloader::remote::{test_server::spawn_server, Certificate, Fixture};
+
+    use super::*;
+
+    const CRATE_URL: &str = "https://crates.io/api/v1/crates/foo/1.0.0/download";
+    const CRATE: &[u8] = include_bytes!("../tests/fixtures/crate-download-foo-1.0.0.crate");
+    const CKSUM: &str = "444f243074fc65fb8a99784687bf4d0e580879529e9228b7ce15c6c41620c7a4";
+
+    /// Create a client replaying the fixture `name` in `tests/fixtures`,
+    /// or at `name` if it is absolute.
+    fn replay_client(name: &str) -> Client {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join(name);
+
+        Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [],
+            Default::default(),
+            None,
+        )
+        .unwrap()
+        .with_fixture(Fixture::replay(dir).unwrap())
+    }
+
+    fn matched_version(cksum: &str) -> MatchedVersion {
+        MatchedVersion {
+            version: "1.0.0".into(),
+            cksum: cksum.into(),
+        }
+    }
+
+    async fn fetch(
+        client: &Client,
+        cksum: &str,
+        cache: Option<&CrateCache>,
+    ) -> Result<Bytes, RegistryError> {
+        fetch_crate_file(
+            client,
+            "foo",
+            Url::parse(CRATE_URL).unwrap(),
+            &matched_version(cksum),
+            cache,
+            None,
+        )
+        .await
+    }
+
+    fn server_client(cert: &[u8]) -> Client {
+        Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [Certificate::from_der(cert).unwrap()],
+            Default::default(),
+            None,
+        )
+        .unwrap()
+    }
+
+    /// sha256 of `crate`
+    const SERVED_CKSUM: &str = "f5fe331d2367a7a67ee20bd579c77b929ae49439d8b0d8e9c3b98609797b6b69";
+
+    #[tokio::test]
+    async fn test_fetch_crate_file_redirected() {
+        let (url, cert) = spawn_server(|request| {
+            if request.starts_with("GET /foo-1.0.0.crate ") {
+                "HTTP/1.1 302 Found\r\nLocation: /static/foo-1.0.0.crate\r\n\
+                Content-Length: 0\r\nConnection: close\r\n\r\n"
+            } else if request.starts_with("GET /static/foo-1.0.0.crate ") {
+                "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\ncrate"
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            }
+        })
+        .await;
+        let client = server_client(&cert);
+
+        let data = fetch_crate_file(
+            &client,
+            "foo",
+            url.join("foo-1.0.0.crate").unwrap(),
+            &matched_version(SERVED_CKSUM),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(data, "crate".as_bytes());
+        assert_eq!(client.bytes_downloaded(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_crate_file_too_many_redirects() {
+        let (url, cert) = spawn_server(|request| {
+            if request.starts_with("GET /a ") {
+                "HTTP/1.1 302 Found\r\nLocation: /b\r\n\
+                Content-Length: 0\r\nConnection: close\r\n\r\n"
+            } else {
+                "HTTP/1.1 302 Found\r\nLocation: /a\r\n\
+                Content-Length: 0\r\nConnection: close\r\n\r\n"
+            }
+        })
+        .await;
+        let client = server_client(&cert);
+
+        let err = fetch_crate_file(
+            &client,
+            "foo",
+            url.join("a").unwrap(),
+            &matched_version(SERVED_CKSUM),
+            None,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            format!("{err:?}").contains(&format!("TooManyRedirects({MAX_REDIRECTS})")),
+            "{err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_crate_file_fallback() {
+        let client = replay_client("crate-download-fallback");
+        assert_eq!(fetch(&client, CKSUM, None).await.unwrap(), CRATE);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_crate_file_with_token() {
+        let (url, cert) = spawn_server(|request| {
+            if request
+                .to_ascii_lowercase()
+                .contains("\r\nauthorization: secret\r\n")
+            {
+                "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\ncrate"
+            } else {
+                "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            }
+        })
+        .await;
+        let client = server_client(&cert);
+        let matched_version = matched_version(SERVED_CKSUM);
+        let fetch = |token| {
+            fetch_crate_file(
+                &client,
+                "foo",
+                url.join("foo-1.0.0.crate").unwrap(),
+                &matched_version,
+                None,
+                token,
+            )
+        };
+
+        assert_eq!(fetch(Some("secret")).await.unwrap(), "crate".as_bytes());
+        assert!(fetch(None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_crate_file_unmatched_checksum() {
+        let client = replay_client("crate-download-redirect");
+        let cksum = "0".repeat(64);
+
+        let err = fetch(&client, &cksum, None).await.unwrap_err();
+        assert!(
+            matches!(err, RegistryError::UnmatchedChecksum { .. }),
+            "{err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_crate_file_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CrateCache::new(dir.path().to_owned());
+
+        let client = replay_client("crate-download-redirect");
+        fetch(&client, CKSUM, Some(&cache)).await.unwrap();
+        assert!(cache.path("foo", &matched_version(CKSUM)).is_file());
+
+        // Nothing is downloaded in offline mode: no response is recorded
+        // for this client.
+        let cache = cache.offline(true);
+        let empty_dir = tempfile::tempdir().unwrap();
+        let client = replay_client(empty_dir.path().to_str().unwrap());
+
+        assert_eq!(fetch(&client, CKSUM, Some(&cache)).await.unwrap(), CRATE);
+        assert_eq!(cache.hits(), 1);
+
+        let cksum = "0".repeat(64);
+        let err = fetch(&client, &cksum, Some(&cache)).await.unwrap_err();
+        assert!(matches!(err, RegistryError::NotCached { .. }), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn test_manifest_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CrateCache::new(dir.path().to_owned());
+
+        let parse = |client: Client| {
+            crate::parse_manifest(
+                client,
+                "foo",
+                Url::parse(CRATE_URL).unwrap(),
+                matched_version(CKSUM),
+                Some(&cache),
+                None,
+            )
+        };
+
+        let manifest = parse(replay_client("crate-download-redirect"))
+            .await
+            .unwrap();
+        assert_eq!(cache.hits(), 0);
+
+        // The manifest is loaded from the cache even without the `.crate`
+        // file, and nothing is downloaded.
+        fs::remove_file(cache.path("foo", &matched_version(CKSUM))).unwrap();
+        let empty_dir = tempfile::tempdir().unwrap();
+        let client = replay_client(empty_dir.path().to_str().unwrap());
+
+        let cached = parse(client.clone()).await.unwrap();
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(client.bytes_downloaded(), 0);
+        assert_eq!(
+            serde_json::to_value(&cached).unwrap(),
+            serde_json::to_value(&manifest).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_matched() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CrateCache::new(dir.path().to_owned());
+
+        let client = replay_client("crate-download-redirect");
+        fetch(&client, CKSUM, Some(&cache)).await.unwrap();
+
+        // Corrupted files are ignored.
+        let corrupted = cache.path(
+            "foo",
+            &MatchedVersion {
+                version: "1.1.0".into(),
+                cksum: "0".repeat(64),
+            },
+        );
+        CrateCache::save(&corrupted, b"corrupted").unwrap();
+
+        let find = |version_req: &str, excluded: &[Version]| {
+            cache
+                .find_matched(
+                    "foo",
+                    None,
+                    &VersionReq::parse(version_req).unwrap(),
+                    excluded,
+                )
+                .map(|(matched_version, data)| (matched_version.version, data))
+        };
+
+        assert_eq!(find("^1", &[]), Some(("1.0.0".into(), CRATE.into())));
+        assert_eq!(find("=1.0.0", &[]), Some(("1.0.0".into(), CRATE.into())));
+        assert_eq!(find("=1.0.0", &[Version::new(1, 0, 0)]), None);
+        assert_eq!(find("^2", &[]), None);
+        assert_eq!(
+            cache
+                .find_matched("fo", None, &VersionReq::STAR, &[])
+                .map(|_| ()),
+            None
+        );
+
+        // The versions yanked in the cached index are skipped, and the ones
+        // not cached too.
+        let index_url = Url::parse("https://index.crates.io/").unwrap();
+        let find_indexed = |index: String| {
+            cache.save_index(&index_url, "foo", index.as_bytes());
+            let index = cache.load_index(&index_url, "foo").unwrap();
+            cache
+                .find_matched("foo", Some(&index), &VersionReq::STAR, &[])
+                .map(|(matched_version, _data)| matched_version.version)
+        };
+        let entry = |vers: &str, cksum: &str, yanked: bool| {
+            format!(
+                r#"{{"name": "foo", "vers": "{vers}", "cksum": "{cksum}", "yanked": {yanked}}}"#
+            )
+        };
+
+        assert_eq!(
+            find_indexed(
+                entry("1.0.0", CKSUM, false) + "\n" + &entry("1.2.0", &"1".repeat(64), false)
+            ),
+            Some("1.0.0".into())
+        );
+        assert_eq!(find_indexed(entry("1.0.0", CKSUM, true)), None);
+        assert_eq!(
+            cache.load_index(&Url::parse("https://example.com/").unwrap(), "foo"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_static_crates_io_url() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        let fallback = |crate_url: &str| {
+            static_crates_io_url(&url(crate_url), "foo", "1.0.0").map(String::from)
+        };
+
+        let expected = Some("https://static.crates.io/crates/foo/foo-1.0.0.crate".to_string());
+        assert_eq!(
+            fallback("https://crates.io/api/v1/crates/foo/1.0.0/download"),
+            expected
+        );
+        assert_eq!(
+            fallback("https://static.crates.io/crates/foo/1.0.0/download"),
+            expected
+        );
+
+        assert_eq!(
+            fallback("https://static.crates.io/crates/foo/foo-1.0.0.crate"),
+            None
+        );
+        assert_eq!(
+            fallback("https://example.com/api/v1/crates/foo/1.0.0/download"),
+            None
+        );
+    }
+}
@@ -105,7 +105,7 @@ async fn fetch_crate_cratesio_version_matched(
 
 /// Find the crate by name, get its latest stable version matches `version_req`,
 /// retrieve its Cargo.toml and infer all its bins.
-#[instrument]
+#[instrument(skip(client))]
 pub async fn fetch_crate_cratesio_api(
     client: Client,
     name: &str,
@@ -157,5 +157,13 @@ pub async fn fetch_crate_cratesio_api(
         .push(&version)
         .push("download");
 
-    parse_manifest(client, name, crate_url, MatchedVersion { version, cksum }).await
+    parse_manifest(
+        client,
+        name,
+        crate_url,
+        MatchedVersion { version, cksum },
+        None,
+        None,
+    )
+    .await
 }
@@ -5,7 +5,7 @@ use binstalk_types::cargo_toml_binstall::Meta;
 use cargo_toml_workspace::cargo_toml::Manifest;
 use compact_str::{CompactString, ToCompactString};
 use once_cell::sync::OnceCell;
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use serde_json::{from_slice as json_from_slice, Deserializer as JsonDeserializer};
 use simple_git::{GitCancellationToken, GitUrl, Repository};
 use tempfile::TempDir;
@@ -14,8 +14,8 @@ use tracing::instrument;
 use url::Url;
 
 use crate::{
-    crate_prefix_components, parse_manifest, render_dl_template, MatchedVersion, RegistryConfig,
-    RegistryError,
+    crate_prefix_components, parse_manifest, render_dl_template, search::WebApi, CrateCache,
+    MatchedVersion, RegistryConfig, RegistryError,
 };
 
 #[derive(Debug)]
@@ -23,6 +23,7 @@ struct GitIndex {
     _tempdir: TempDir,
     repo: Repository,
     dl_template: CompactString,
+    api: Option<CompactString>,
 }
 
 impl GitIndex {
@@ -52,6 +53,7 @@ impl GitIndex {
             _tempdir: tempdir,
             repo,
             dl_template: config.dl,
+            api: config.api,
         })
     }
 }
@@ -60,6 +62,7 @@ impl GitIndex {
 struct GitRegistryInner {
     url: GitUrl,
     git_index: OnceCell<GitIndex>,
+    crate_cache: Option<CrateCache>,
 }
 
 #[derive(Clone, Debug)]
@@ -70,15 +73,42 @@ impl GitRegistry {
         Self(Arc::new(GitRegistryInner {
             url,
             git_index: Default::default(),
+            crate_cache: None,
         }))
     }
 
+    /// Must be called before the registry is cloned.
+    pub(crate) fn set_crate_cache(&mut self, cache: CrateCache) {
+        Arc::get_mut(&mut self.0)
+            .expect("Registry::with_crate_cache must be called before cloning it")
+            .crate_cache = Some(cache);
+    }
+
+    pub(crate) fn crate_cache(&self) -> Option<&CrateCache> {
+        self.0.crate_cache.as_ref()
+    }
+
+    pub(crate) fn url(&self) -> &GitUrl {
+        &self.0.url
+    }
+
+    /// WARNING: This is a blocking operation.
+    fn git_index(
+        &self,
+        cancellation_token: GitCancellationToken,
+    ) -> Result<&GitIndex, RegistryError> {
+        self.0
+            .git_index
+            .get_or_try_init(|| GitIndex::new(self.0.url.clone(), cancellation_token))
+    }
+
     /// WARNING: This is a blocking operation.
     fn find_crate_matched_ver(
         repo: &Repository,
         crate_name: &str,
         (c1, c2): &(CompactString, Option<CompactString>),
         version_req: &VersionReq,
+        excluded: &[Version],
     ) -> Result<MatchedVersion, RegistryError> {
         let mut path = PathBuf::with_capacity(128);
         path.push(&**c1);
@@ -94,19 +124,22 @@ impl GitRegistry {
         MatchedVersion::find(
             &mut JsonDeserializer::from_slice(&crate_versions).into_iter(),
             version_req,
+            excluded,
         )
     }
 
-    #[instrument]
+    #[instrument(skip(client))]
     pub async fn fetch_crate_matched(
         &self,
         client: Client,
         name: &str,
         version_req: &VersionReq,
+        excluded: &[Version],
     ) -> Result<Manifest<Meta>, RegistryError> {
         let crate_prefix = crate_prefix_components(name)?;
         let crate_name = name.to_compact_string();
         let version_req = version_req.clone();
+        let excluded = excluded.to_vec();
         let this = self.clone();
 
         let cancellation_token = GitCancellationToken::default();
@@ -118,13 +151,16 @@ impl GitRegistry {
                 _tempdir: _,
                 repo,
                 dl_template,
-            } = this
-                .0
-                .git_index
-                .get_or_try_init(|| GitIndex::new(this.0.url.clone(), cancellation_token))?;
+                ..
+            } = this.git_index(cancellation_token)?;
 
-            let matched_version =
-                Self::find_crate_matched_ver(repo, &crate_name, &crate_prefix, &version_req)?;
+            let matched_version = Self::find_crate_matched_ver(
+                repo,
+                &crate_name,
+                &crate_prefix,
+                &version_req,
+                &excluded,
+            )?;
 
             let url = Url::parse(&render_dl_template(
                 dl_template,
@@ -140,6 +176,64 @@ impl GitRegistry {
         // Git operation done, disarm it
         cancel_on_drop.disarm();
 
-        parse_manifest(client, name, dl_url, matched_version).await
+        parse_manifest(
+            client,
+            name,
+            dl_url,
+            matched_version,
+            self.0.crate_cache.as_ref(),
+            None,
+        )
+        .await
+    }
+
+    /// Find the latest version of `crate_name` matching `version_req` in
+    /// the index, without downloading the crate.
+    pub(crate) async fn find_latest_version(
+        &self,
+        crate_name: &str,
+        version_req: &VersionReq,
+    ) -> Result<CompactString, RegistryError> {
+        let crate_prefix = crate_prefix_components(crate_name)?;
+        let crate_name = crate_name.to_compact_string();
+        let version_req = version_req.clone();
+        let this = self.clone();
+
+        let cancellation_token = GitCancellationToken::default();
+        // Cancel git operation if the future is cancelled (dropped).
+        let cancel_on_drop = cancellation_token.clone().cancel_on_drop();
+
+        let version = spawn_blocking(move || {
+            let GitIndex { repo, .. } = this.git_index(cancellation_token)?;
+
+            Self::find_crate_matched_ver(repo, &crate_name, &crate_prefix, &version_req, &[])
+                .map(|matched_version| matched_version.version)
+        })
+        .await??;
+
+        // Git operation done, disarm it
+        cancel_on_drop.disarm();
+
+        Ok(version)
+    }
+
+    /// Return the web API of the registry, if it has one.
+    pub(crate) async fn web_api(&self) -> Result<Option<WebApi>, RegistryError> {
+        let this = self.clone();
+
+        let cancellation_token = GitCancellationToken::default();
+        // Cancel git operation if the future is cancelled (dropped).
+        let cancel_on_drop = cancellation_token.clone().cancel_on_drop();
+
+        let api = spawn_blocking(move || {
+            this.git_index(cancellation_token)
+                .map(|git_index| git_index.api.clone())
+        })
+        .await??;
+
+        // Git operation done, disarm it
+        cancel_on_drop.disarm();
+
+        Ok(api.map(|url| WebApi { url, token: None }))
     }
 }
@@ -4,6 +4,7 @@ use std::{io, str::FromStr, sync::Arc};
 
 use base16::DecodeError as Base16DecodeError;
 use binstalk_downloader::{
+    bytes::Bytes,
     download::DownloadError,
     remote::{Client, Error as RemoteError},
 };
@@ -12,10 +13,11 @@ use cargo_toml_workspace::cargo_toml::{Error as CargoTomlError, Manifest};
 use compact_str::CompactString;
 use leon::{ParseError, RenderError};
 use miette::Diagnostic;
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use serde_json::Error as JsonError;
 use thiserror::Error as ThisError;
 use tokio::task;
+use tracing::{debug, instrument};
 use url::{ParseError as UrlParseError, Url};
 
 #[cfg(feature = "git")]
@@ -28,6 +30,9 @@ mod visitor;
 mod common;
 use common::*;
 
+mod crate_download;
+pub use crate_download::CrateCache;
+
 #[cfg(feature = "git")]
 mod git_registry;
 #[cfg(feature = "git")]
@@ -38,6 +43,9 @@ mod crates_io_registry;
 #[cfg(any(feature = "crates_io_api", test))]
 pub use crates_io_registry::fetch_crate_cratesio_api;
 
+mod search;
+pub use search::{CrateProvidingBin, SimilarCrate};
+
 mod sparse_registry;
 pub use sparse_registry::SparseRegistry;
 
@@ -72,6 +80,26 @@ pub enum RegistryError {
         actual: Box<str>,
     },
 
+    #[error("{crate_name} v{version} is not in the cache, and downloading it is disabled in offline mode")]
+    #[diagnostic(help("Disable `net.offline` once to download it, it is cached afterwards."))]
+    NotCached {
+        crate_name: CompactString,
+        version: CompactString,
+    },
+
+    #[error("{crate_name} is not in the cache, and the index at {url} cannot be fetched in offline mode")]
+    #[diagnostic(help("Run `cargo binstall --prefetch` without `--offline` to cache it."))]
+    OfflineMissing {
+        crate_name: CompactString,
+        url: CompactString,
+    },
+
+    #[error("The registry at {url} requires authentication, but no token is configured")]
+    #[diagnostic(help(
+        "Set `CARGO_REGISTRIES_<name>_TOKEN` or `registries.<name>.token` in credentials.toml."
+    ))]
+    AuthRequired { url: CompactString },
+
     #[error("no version matching requirement '{req}'")]
     VersionMismatch { req: semver::VersionReq },
 
@@ -173,30 +201,238 @@ impl Registry {
         }
     }
 
+    /// Load the `.crate` files from `cache`, and save the ones downloaded
+    /// there.
+    ///
+    /// Must be called before the registry is cloned.
+    pub fn with_crate_cache(mut self, cache: CrateCache) -> Self {
+        match &mut self {
+            Self::Sparse(sparse_registry) => {
+                Arc::get_mut(sparse_registry)
+                    .expect("Registry::with_crate_cache must be called before cloning it")
+                    .set_crate_cache(cache);
+            }
+            #[cfg(feature = "git")]
+            Self::Git(git_registry) => git_registry.set_crate_cache(cache),
+        }
+        self
+    }
+
+    /// Send `token` with the requests to the registry, to use a private
+    /// sparse registry.
+    ///
+    /// Git registries fetch the index with git, the token is ignored.
+    ///
+    /// Must be called before the registry is cloned.
+    pub fn with_token(mut self, token: CompactString) -> Self {
+        match &mut self {
+            Self::Sparse(sparse_registry) => {
+                Arc::get_mut(sparse_registry)
+                    .expect("Registry::with_token must be called before cloning it")
+                    .set_token(token);
+            }
+            #[cfg(feature = "git")]
+            Self::Git(_) => (),
+        }
+        self
+    }
+
+    /// Number of `.crate` files and manifests loaded from the cache set by
+    /// [`Registry::with_crate_cache`] so far.
+    pub fn crate_cache_hits(&self) -> u32 {
+        self.crate_cache().map_or(0, CrateCache::hits)
+    }
+
     /// Fetch the latest crate with `crate_name` and with version matching
     /// `version_req`.
+    ///
+    /// Versions in `excluded` are skipped as if they were yanked.
+    ///
+    /// If `client` is offline, the crate is resolved from the `.crate` files
+    /// of the cache set by [`Registry::with_crate_cache`] instead.
     pub async fn fetch_crate_matched(
         &self,
         client: Client,
         crate_name: &str,
         version_req: &VersionReq,
+        excluded: &[Version],
     ) -> Result<Manifest<Meta>, RegistryError> {
+        if client.is_offline() {
+            return self
+                .fetch_cached_crate_matched(crate_name, version_req, excluded)
+                .await;
+        }
+
         match self {
             Self::Sparse(sparse_registry) => {
                 sparse_registry
-                    .fetch_crate_matched(client, crate_name, version_req)
+                    .fetch_crate_matched(client, crate_name, version_req, excluded)
                     .await
             }
             #[cfg(feature = "git")]
             Self::Git(git_registry) => {
                 git_registry
-                    .fetch_crate_matched(client, crate_name, version_req)
+                    .fetch_crate_matched(client, crate_name, version_req, excluded)
                     .await
             }
         }
     }
 }
 
+impl Registry {
+    /// Find the latest version of `crate_name` matching `version_req` in the
+    /// index, without downloading the crate.
+    ///
+    /// If `client` is offline, it is found in the `.crate` files of the
+    /// cache set by [`Registry::with_crate_cache`] instead.
+    pub async fn find_latest_version(
+        &self,
+        client: Client,
+        crate_name: &str,
+        version_req: &VersionReq,
+    ) -> Result<Version, RegistryError> {
+        let version = if client.is_offline() {
+            self.find_cached_matched(crate_name, version_req, &[])
+                .map(|(matched_version, _data)| matched_version.version)
+                .ok_or_else(|| RegistryError::OfflineMissing {
+                    crate_name: crate_name.into(),
+                    url: self.url(),
+                })?
+        } else {
+            match self {
+                Self::Sparse(sparse_registry) => {
+                    sparse_registry
+                        .find_latest_version(&client, crate_name, version_req)
+                        .await?
+                }
+                #[cfg(feature = "git")]
+                Self::Git(git_registry) => {
+                    git_registry
+                        .find_latest_version(crate_name, version_req)
+                        .await?
+                }
+            }
+        };
+
+        Ok(Version::parse(&version).expect("the versions matched are valid"))
+    }
+
+    /// Search the registry for binary `bin` with its web API, returning the
+    /// crates among the first `limit` found whose latest version provides
+    /// it, the most relevant first.
+    ///
+    /// The crates are verified with the binaries of their latest version,
+    /// crates.io reports them but other registries may not, in which case
+    /// the crates found are kept unverified.
+    ///
+    /// Registries without a web API, i.e. without `api` in their
+    /// `config.json`, find no crates.
+    #[instrument(skip(self, client))]
+    pub async fn search_crates_providing_bin(
+        &self,
+        client: &Client,
+        bin: &str,
+        limit: usize,
+    ) -> Result<Vec<CrateProvidingBin>, RegistryError> {
+        match self.web_api(client).await? {
+            Some(web_api) => web_api.search_providing_bin(client, bin, limit).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Find the crates in the registry whose name is close to `name`, e.g.
+    /// with a typo fixed, the closest and most downloaded first.
+    #[instrument(skip(self, client))]
+    pub async fn search_similar_crates(
+        &self,
+        client: &Client,
+        name: &str,
+    ) -> Result<Vec<SimilarCrate>, RegistryError> {
+        let found = self
+            .search(client, name, search::SIMILAR_SEARCH_LIMIT)
+            .await?;
+        Ok(search::rank_similar_crates(name, found))
+    }
+
+    async fn search(
+        &self,
+        client: &Client,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<search::SearchCrate>, RegistryError> {
+        match self.web_api(client).await? {
+            Some(web_api) => web_api.search(client, query, limit).await,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Return the web API of the registry, if it has one.
+    async fn web_api(&self, client: &Client) -> Result<Option<search::WebApi>, RegistryError> {
+        let web_api = match self {
+            Self::Sparse(sparse_registry) => sparse_registry.web_api(client).await?,
+            #[cfg(feature = "git")]
+            Self::Git(git_registry) => git_registry.web_api().await?,
+        };
+        if web_api.is_none() {
+            debug!("Registry {} has no web API to search", self.url());
+        }
+        Ok(web_api)
+    }
+
+    fn crate_cache(&self) -> Option<&CrateCache> {
+        match self {
+            Self::Sparse(sparse_registry) => sparse_registry.crate_cache(),
+            #[cfg(feature = "git")]
+            Self::Git(git_registry) => git_registry.crate_cache(),
+        }
+    }
+
+    fn url(&self) -> CompactString {
+        match self {
+            Self::Sparse(sparse_registry) => sparse_registry.url().as_str().into(),
+            #[cfg(feature = "git")]
+            Self::Git(git_registry) => git_registry.url().to_string().into(),
+        }
+    }
+
+    /// Find the latest cached `.crate` file of `crate_name` matching
+    /// `version_req`, skipping the versions yanked in the cached index.
+    fn find_cached_matched(
+        &self,
+        crate_name: &str,
+        version_req: &VersionReq,
+        excluded: &[Version],
+    ) -> Option<(MatchedVersion, Bytes)> {
+        let crate_cache = self.crate_cache()?;
+        let index = match self {
+            Self::Sparse(sparse_registry) => {
+                crate_cache.load_index(sparse_registry.url(), crate_name)
+            }
+            #[cfg(feature = "git")]
+            Self::Git(_) => None,
+        };
+
+        crate_cache.find_matched(crate_name, index.as_deref(), version_req, excluded)
+    }
+
+    async fn fetch_cached_crate_matched(
+        &self,
+        crate_name: &str,
+        version_req: &VersionReq,
+        excluded: &[Version],
+    ) -> Result<Manifest<Meta>, RegistryError> {
+        match self.find_cached_matched(crate_name, version_req, excluded) {
+            Some((matched_version, data)) => {
+                extract_manifest(crate_name, &matched_version.version, data).await
+            }
+            None => Err(RegistryError::OfflineMissing {
+                crate_name: crate_name.into(),
+                url: self.url(),
+            }),
+        }
+    }
+}
+
 impl FromStr for Registry {
     type Err = InvalidRegistryError;
 
@@ -225,6 +461,8 @@ mod test {
             NonZeroU16::new(10).unwrap(),
             1.try_into().unwrap(),
             [],
+            Default::default(),
+            None,
         )
         .unwrap()
     }
@@ -243,7 +481,7 @@ mod test {
         let crate_name = "cargo-binstall";
         let version_req = &VersionReq::parse("=1.0.0").unwrap();
         let manifest_from_sparse = sparse_registry
-            .fetch_crate_matched(client.clone(), crate_name, version_req)
+            .fetch_crate_matched(client.clone(), crate_name, version_req, &[])
             .await
             .unwrap();
 
@@ -277,12 +515,12 @@ mod test {
         let crate_name = "cargo-binstall";
         let version_req = &VersionReq::parse("=1.0.0").unwrap();
         let manifest_from_git = git_registry
-            .fetch_crate_matched(client.clone(), crate_name, version_req)
+            .fetch_crate_matched(client.clone(), crate_name, version_req, &[])
             .await
             .unwrap();
 
         let manifest_from_cratesio_api = Registry::default()
-            .fetch_crate_matched(client, crate_name, version_req)
+            .fetch_crate_matched(client, crate_name, version_req, &[])
             .await
             .unwrap();
 
@@ -294,4 +532,45 @@ mod test {
             serialized_manifest_from_cratesio_api
         );
     }
+
+    #[tokio::test]
+    async fn test_offline_registry() {
+        let client = create_client().await.offline(true);
+
+        let dir = tempfile::tempdir().unwrap();
+        let crate_dir = dir
+            .path()
+            .join("sha256")
+            .join("444f243074fc65fb8a99784687bf4d0e580879529e9228b7ce15c6c41620c7a4");
+        std::fs::create_dir_all(&crate_dir).unwrap();
+        std::fs::write(
+            crate_dir.join("foo-1.0.0.crate"),
+            include_bytes!("../tests/fixtures/crate-download-foo-1.0.0.crate"),
+        )
+        .unwrap();
+
+        let registry = Registry::default().with_crate_cache(CrateCache::new(dir.path().to_owned()));
+
+        let manifest = registry
+            .fetch_crate_matched(client.clone(), "foo", &VersionReq::STAR, &[])
+            .await
+            .unwrap();
+        assert_eq!(manifest.package().version(), "1.0.0");
+
+        let version = registry
+            .find_latest_version(client.clone(), "foo", &VersionReq::STAR)
+            .await
+            .unwrap();
+        assert_eq!(version, Version::new(1, 0, 0));
+
+        let err = registry
+            .fetch_crate_matched(client, "bar", &VersionReq::STAR, &[])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, RegistryError::OfflineMissing { crate_name, url }
+                if *crate_name == "bar" && *url == "https://index.crates.io/"),
+            "{err:?}"
+        );
+    }
 }
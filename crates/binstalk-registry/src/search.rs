@@ -0,0 +1,309 @@
+use std::cmp;
+
+use binstalk_downloader::remote::{Client, RequestBuilder, Url};
+use compact_str::CompactString;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::RegistryError;
+
+/// The web API of a registry, see
+/// <https://doc.rust-lang.org/cargo/reference/registry-web-api.html>.
+#[derive(Clone)]
+pub(crate) struct WebApi {
+    pub(crate) url: CompactString,
+    /// Sent in the `Authorization` header, for private registries.
+    pub(crate) token: Option<CompactString>,
+}
+
+/// Results of the search API of a registry, see
+/// <https://doc.rust-lang.org/cargo/reference/registry-web-api.html#search>.
+#[derive(Deserialize)]
+struct Search {
+    crates: Vec<SearchCrate>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SearchCrate {
+    pub(crate) name: CompactString,
+    /// Latest version, which the search API does not require.
+    #[serde(default)]
+    max_version: Option<CompactString>,
+    /// Number of downloads of all its versions, which crates.io reports
+    /// but the search API does not require.
+    #[serde(default)]
+    downloads: u64,
+}
+
+/// A crate found by searching a registry for a binary.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrateProvidingBin {
+    pub name: CompactString,
+    /// Whether its latest version provides the binary, `false` if the
+    /// registry does not tell, unlike crates.io.
+    pub verified: bool,
+}
+
+impl WebApi {
+    fn get(&self, client: &Client, url: Url) -> RequestBuilder {
+        let request = client.get(url);
+        match &self.token {
+            Some(token) => request.header("Authorization", token.as_str()),
+            None => request,
+        }
+    }
+
+    fn crates_url(&self) -> Result<Url, RegistryError> {
+        Ok(Url::parse(&format!(
+            "{}/api/v1/crates",
+            self.url.trim_end_matches('/')
+        ))?)
+    }
+
+    /// Search the registry for `query`, returning at most `limit` crates,
+    /// the most relevant first.
+    pub(crate) async fn search(
+        &self,
+        client: &Client,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchCrate>, RegistryError> {
+        let mut url = self.crates_url()?;
+        url.query_pairs_mut()
+            .append_pair("q", query)
+            .append_pair("per_page", &limit.to_string());
+
+        let search: Search = self.get(client, url).send(true).await?.json().await?;
+
+        Ok(search.crates)
+    }
+
+    /// Return whether version `version` of crate `name` provides binary
+    /// `bin`, or `None` if the registry does not report the binaries of
+    /// the crates.
+    async fn provides_bin(
+        &self,
+        client: &Client,
+        name: &str,
+        version: &str,
+        bin: &str,
+    ) -> Result<Option<bool>, RegistryError> {
+        #[derive(Deserialize)]
+        struct VersionInfo {
+            version: VersionBins,
+        }
+
+        #[derive(Deserialize)]
+        struct VersionBins {
+            #[serde(default)]
+            bin_names: Option<Vec<CompactString>>,
+        }
+
+        let mut url = self.crates_url()?;
+        url.path_segments_mut().unwrap().push(name).push(version);
+
+        let info: VersionInfo = self.get(client, url).send(true).await?.json().await?;
+
+        Ok(info
+            .version
+            .bin_names
+            .map(|bin_names| bin_names.iter().any(|bin_name| bin_name.as_str() == bin)))
+    }
+
+    /// Search the registry for `bin`, and keep the crates among the first
+    /// `limit` found which may provide it, checking the binaries of their
+    /// latest version concurrently.
+    pub(crate) async fn search_providing_bin(
+        &self,
+        client: &Client,
+        bin: &str,
+        limit: usize,
+    ) -> Result<Vec<CrateProvidingBin>, RegistryError> {
+        let found = self.search(client, bin, limit).await?;
+
+        let checks: Vec<_> = found
+            .into_iter()
+            .map(|found| {
+                let (this, client, bin) = (self.clone(), client.clone(), CompactString::from(bin));
+                tokio::spawn(async move {
+                    let provides_bin = match &found.max_version {
+                        Some(version) => this
+                            .provides_bin(&client, &found.name, version, &bin)
+                            .await
+                            .unwrap_or_else(|err| {
+                                debug!("Failed to find the binaries of {}: {err}", found.name);
+                                None
+                            }),
+                        None => None,
+                    };
+                    (found.name, provides_bin)
+                })
+            })
+            .collect();
+
+        let mut providing = Vec::new();
+        for check in checks {
+            match check.await? {
+                (name, Some(true)) => providing.push(CrateProvidingBin {
+                    name,
+                    verified: true,
+                }),
+                (name, None) => providing.push(CrateProvidingBin {
+                    name,
+                    verified: false,
+                }),
+                (name, Some(false)) => debug!("{name} does not provide binary `{bin}`"),
+            }
+        }
+
+        Ok(providing)
+    }
+}
+
+/// Number of search results ranked by [`rank_similar_crates`].
+pub(crate) const SIMILAR_SEARCH_LIMIT: usize = 20;
+
+/// Maximum number of crates returned by [`rank_similar_crates`].
+const MAX_SIMILAR_CRATES: usize = 3;
+
+/// A crate with a name similar to one which is not found.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SimilarCrate {
+    pub name: CompactString,
+    /// Number of downloads of all its versions.
+    pub downloads: u64,
+}
+
+/// Keep the crates found whose name is within a few edits of `name`, or
+/// contains it, and sort them by distance, then by downloads.
+pub(crate) fn rank_similar_crates(name: &str, found: Vec<SearchCrate>) -> Vec<SimilarCrate> {
+    let name = normalize(name);
+    let max_distance = (name.chars().count() / 3).clamp(1, 3);
+
+    let mut ranked: Vec<_> = found
+        .into_iter()
+        .filter_map(|found| {
+            let candidate = normalize(&found.name);
+            let distance = edit_distance(&name, &candidate);
+
+            // Crates containing the name come after the close ones.
+            let far = if distance <= max_distance {
+                false
+            } else if name.chars().count() >= 3 && candidate.contains(&*name) {
+                true
+            } else {
+                return None;
+            };
+
+            Some((
+                (far, distance, cmp::Reverse(found.downloads)),
+                SimilarCrate {
+                    name: found.name,
+                    downloads: found.downloads,
+                },
+            ))
+        })
+        .collect();
+
+    ranked.sort_by_key(|(key, _)| *key);
+    ranked
+        .into_iter()
+        .take(MAX_SIMILAR_CRATES)
+        .map(|(_, similar)| similar)
+        .collect()
+}
+
+/// Crate names are compared case-insensitively, with `-` and `_` being the
+/// same, like crates.io does.
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace('_', "-")
+}
+
+/// Return the Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rank(name: &str, response: &str) -> Vec<(String, u64)> {
+        let search: Search = serde_json::from_str(response).unwrap();
+        rank_similar_crates(name, search.crates)
+            .into_iter()
+            .map(|similar| (similar.name.into(), similar.downloads))
+            .collect()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("ripgrep", "ripgrep"), 0);
+        assert_eq!(edit_distance("ripgrap", "ripgrep"), 1);
+        assert_eq!(edit_distance("rigprep", "ripgrep"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("cargo-nextest", "nextest"), 6);
+    }
+
+    #[test]
+    fn test_rank_similar_crates() {
+        let response = r#"{
+            "crates": [
+                {"name": "ripgrep_all", "max_version": "0.10.6", "downloads": 90000},
+                {"name": "grep", "max_version": "0.3.1", "downloads": 4000000},
+                {"name": "ripgrep", "max_version": "14.1.0", "downloads": 1500000},
+                {"name": "ripgrip", "max_version": "0.1.0", "downloads": 120},
+                {"name": "rip", "max_version": "0.1.0", "downloads": 5000}
+            ],
+            "meta": {"total": 5}
+        }"#;
+
+        assert_eq!(
+            rank("ripgrap", response),
+            [("ripgrep".into(), 1500000), ("ripgrip".into(), 120)]
+        );
+        assert_eq!(
+            rank("ripgrep-al", response),
+            [("ripgrep_all".into(), 90000), ("ripgrep".into(), 1500000)]
+        );
+    }
+
+    #[test]
+    fn test_rank_similar_crates_containing_name() {
+        let response = r#"{
+            "crates": [
+                {"name": "nextest-runner", "max_version": "0.1.0", "downloads": 300},
+                {"name": "cargo-nextest", "max_version": "0.9.0", "downloads": 900000},
+                {"name": "nextes", "max_version": "0.1.0", "downloads": 10},
+                {"name": "cargo-next", "max_version": "0.2.0", "downloads": 5000},
+                {"name": "Next-Test", "max_version": "0.1.0", "downloads": 50}
+            ]
+        }"#;
+
+        assert_eq!(
+            rank("nextest", response),
+            [
+                ("nextes".into(), 10),
+                ("Next-Test".into(), 50),
+                ("cargo-nextest".into(), 900000),
+            ]
+        );
+
+        // Too short to look for crates containing it.
+        let response = r#"{"crates": [{"name": "ripgrep", "max_version": "14.1.0"}]}"#;
+        assert!(rank("rg", response).is_empty());
+    }
+}
@@ -1,22 +1,35 @@
-use binstalk_downloader::remote::{Client, Error as RemoteError};
+use std::fmt;
+
+use binstalk_downloader::remote::{Client, Error as RemoteError, RequestBuilder};
 use binstalk_types::cargo_toml_binstall::Meta;
 use cargo_toml_workspace::cargo_toml::Manifest;
 use compact_str::CompactString;
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use serde_json::Deserializer as JsonDeserializer;
 use tokio::sync::OnceCell;
 use tracing::instrument;
 use url::Url;
 
 use crate::{
-    crate_prefix_components, parse_manifest, render_dl_template, MatchedVersion, RegistryConfig,
-    RegistryError,
+    crate_prefix_components, parse_manifest, render_dl_template, search::WebApi, CrateCache,
+    MatchedVersion, RegistryConfig, RegistryError,
 };
 
-#[derive(Debug)]
 pub struct SparseRegistry {
     url: Url,
-    dl_template: OnceCell<CompactString>,
+    config: OnceCell<RegistryConfig>,
+    crate_cache: Option<CrateCache>,
+    token: Option<CompactString>,
+}
+
+impl fmt::Debug for SparseRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SparseRegistry")
+            .field("url", &self.url)
+            .field("crate_cache", &self.crate_cache)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish_non_exhaustive()
+    }
 }
 
 impl SparseRegistry {
@@ -24,31 +37,84 @@ impl SparseRegistry {
     pub fn new(url: Url) -> Self {
         Self {
             url,
-            dl_template: Default::default(),
+            config: Default::default(),
+            crate_cache: None,
+            token: None,
+        }
+    }
+
+    pub(crate) fn set_token(&mut self, token: CompactString) {
+        self.token = Some(token);
+    }
+
+    pub(crate) fn set_crate_cache(&mut self, cache: CrateCache) {
+        self.crate_cache = Some(cache);
+    }
+
+    pub(crate) fn crate_cache(&self) -> Option<&CrateCache> {
+        self.crate_cache.as_ref()
+    }
+
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Send the token, if any, with every request since a private
+    /// registry requires it for the `config.json` too.
+    fn get(&self, client: &Client, url: Url) -> RequestBuilder {
+        let request = client.get(url);
+        match &self.token {
+            Some(token) => request.header("Authorization", token),
+            None => request,
         }
     }
 
-    async fn get_dl_template(&self, client: &Client) -> Result<&str, RegistryError> {
-        self.dl_template
+    async fn get_config(&self, client: &Client) -> Result<&RegistryConfig, RegistryError> {
+        self.config
             .get_or_try_init(|| {
                 Box::pin(async {
                     let mut url = self.url.clone();
                     url.path_segments_mut().unwrap().push("config.json");
-                    let config: RegistryConfig = client.get(url).send(true).await?.json().await?;
-                    Ok(config.dl)
+                    Ok(self.get(client, url).send(true).await?.json().await?)
                 })
             })
             .await
-            .map(AsRef::as_ref)
+    }
+
+    /// Return the token to send to download the crates, only if the registry
+    /// requires it: `config.dl` may be on another host, e.g. a CDN, as
+    /// cargo does.
+    fn dl_token<'a>(&'a self, config: &RegistryConfig) -> Option<&'a str> {
+        self.token.as_deref().filter(|_| config.auth_required)
+    }
+
+    /// Same as [`SparseRegistry::get_config`], but fail if the registry
+    /// requires a token and none is set.
+    async fn get_authorized_config(
+        &self,
+        client: &Client,
+    ) -> Result<&RegistryConfig, RegistryError> {
+        let config = self.get_config(client).await?;
+        if config.auth_required && self.token.is_none() {
+            return Err(RegistryError::AuthRequired {
+                url: self.url.as_str().into(),
+            });
+        }
+        Ok(config)
     }
 
     /// `url` must be a valid http(s) url.
+    ///
+    /// The file of the crate in the index is saved to the cache set by
+    /// [`SparseRegistry::set_crate_cache`], for offline mode.
     async fn find_crate_matched_ver(
+        &self,
         client: &Client,
         mut url: Url,
         crate_name: &str,
         (c1, c2): &(CompactString, Option<CompactString>),
         version_req: &VersionReq,
+        excluded: &[Version],
     ) -> Result<MatchedVersion, RegistryError> {
         {
             let mut path = url.path_segments_mut().unwrap();
@@ -61,8 +127,8 @@ impl SparseRegistry {
             path.push(&crate_name.to_lowercase());
         }
 
-        let body = client
-            .get(url)
+        let body = self
+            .get(client, url)
             .send(true)
             .await
             .map_err(|e| match e {
@@ -72,36 +138,261 @@ impl SparseRegistry {
             .bytes()
             .await
             .map_err(RegistryError::from)?;
+        if let Some(cache) = &self.crate_cache {
+            cache.save_index(&self.url, crate_name, &body);
+        }
         MatchedVersion::find(
             &mut JsonDeserializer::from_slice(&body).into_iter(),
             version_req,
+            excluded,
         )
     }
 
-    #[instrument]
+    #[instrument(skip(client))]
     pub async fn fetch_crate_matched(
         &self,
         client: Client,
         crate_name: &str,
         version_req: &VersionReq,
+        excluded: &[Version],
     ) -> Result<Manifest<Meta>, RegistryError> {
         let crate_prefix = crate_prefix_components(crate_name)?;
-        let dl_template = self.get_dl_template(&client).await?;
-        let matched_version = Self::find_crate_matched_ver(
-            &client,
-            self.url.clone(),
-            crate_name,
-            &crate_prefix,
-            version_req,
-        )
-        .await?;
+        let config = self.get_authorized_config(&client).await?;
+
+        let matched_version = self
+            .find_crate_matched_ver(
+                &client,
+                self.url.clone(),
+                crate_name,
+                &crate_prefix,
+                version_req,
+                excluded,
+            )
+            .await?;
         let dl_url = Url::parse(&render_dl_template(
-            dl_template,
+            &config.dl,
             crate_name,
             &crate_prefix,
             &matched_version,
         )?)?;
 
-        parse_manifest(client, crate_name, dl_url, matched_version).await
+        parse_manifest(
+            client,
+            crate_name,
+            dl_url,
+            matched_version,
+            self.crate_cache.as_ref(),
+            self.dl_token(config),
+        )
+        .await
+    }
+
+    /// Find the latest version of `crate_name` matching `version_req` in
+    /// the index, without downloading the crate.
+    pub(crate) async fn find_latest_version(
+        &self,
+        client: &Client,
+        crate_name: &str,
+        version_req: &VersionReq,
+    ) -> Result<CompactString, RegistryError> {
+        let crate_prefix = crate_prefix_components(crate_name)?;
+        self.get_authorized_config(client).await?;
+
+        self.find_crate_matched_ver(
+            client,
+            self.url.clone(),
+            crate_name,
+            &crate_prefix,
+            version_req,
+            &[],
+        )
+        .await
+        .map(|matched_version| matched_version.version)
+    }
+
+    /// Return the web API of the registry, if it has one.
+    pub(crate) async fn web_api(&self, client: &Client) -> Result<Option<WebApi>, RegistryError> {
+        let config = self.get_authorized_config(client).await?;
+        Ok(config.api.clone().map(|url| WebApi {
+            url,
+            token: self.dl_token(config).map(Into::into),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU16;
+
+    use binstalk_downloader::remote::{test_server::spawn_server, Certificate};
+
+    use super::*;
+    use crate::CrateProvidingBin;
+
+    /// A private registry, whose index is only served with the token
+    /// `secret`.
+    fn respond(request: &str) -> &'static str {
+        let authorized = request
+            .to_ascii_lowercase()
+            .contains("\r\nauthorization: secret\r\n");
+
+        if request.contains("/config.json ") {
+            "HTTP/1.1 200 OK\r\nContent-Length: 65\r\nConnection: close\r\n\r\n\
+            {\"dl\": \"https://cdn.example.com/{crate}\", \"auth-required\": true}\n"
+        } else if !authorized {
+            "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        } else {
+            "HTTP/1.1 200 OK\r\nContent-Length: 65\r\nConnection: close\r\n\r\n\
+            {\"name\": \"foo\", \"vers\": \"1.0.0\", \"cksum\": \"00\", \"yanked\": false}\n"
+        }
+    }
+
+    async fn find_latest_version(token: Option<&str>) -> Result<CompactString, RegistryError> {
+        let (url, cert) = spawn_server(respond).await;
+        let client = Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [Certificate::from_der(&cert).unwrap()],
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        let mut registry = SparseRegistry::new(url);
+        if let Some(token) = token {
+            registry.set_token(token.into());
+        }
+        registry
+            .find_latest_version(&client, "foo", &VersionReq::STAR)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_token_sent() {
+        assert_eq!(find_latest_version(Some("secret")).await.unwrap(), "1.0.0");
+    }
+
+    #[tokio::test]
+    async fn test_auth_required() {
+        assert!(matches!(
+            find_latest_version(None).await,
+            Err(RegistryError::AuthRequired { .. })
+        ));
+    }
+
+    /// A registry whose web API, on the same host, finds `foo` and `foo-cli`
+    /// for any query.
+    fn respond_search(request: &str) -> &'static str {
+        if request.contains("/config.json ") {
+            let host = request
+                .lines()
+                .find_map(|line| line.strip_prefix("host: "))
+                .unwrap();
+            let body = format!(
+                "{{\"dl\": \"https://cdn.example.com/{{crate}}\", \"api\": \"https://{host}\"}}"
+            );
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            Box::leak(response.into_boxed_str())
+        } else if request.starts_with("GET /api/v1/crates?q=foo&per_page=2 ") {
+            "HTTP/1.1 200 OK\r\nContent-Length: 50\r\nConnection: close\r\n\r\n\
+            {\"crates\": [{\"name\": \"foo\"}, {\"name\": \"foo-cli\"}]}"
+        } else if request.starts_with("GET /api/v1/crates?q=foo&per_page=3 ") {
+            "HTTP/1.1 200 OK\r\nContent-Length: 123\r\nConnection: close\r\n\r\n\
+            {\"crates\": [{\"name\": \"foo\", \"max_version\": \"1.0.0\"}, \
+            {\"name\": \"foo-cli\", \"max_version\": \"0.1.0\"}, {\"name\": \"foo-unknown\"}]}"
+        } else if request.starts_with("GET /api/v1/crates/foo/1.0.0 ") {
+            "HTTP/1.1 200 OK\r\nContent-Length: 30\r\nConnection: close\r\n\r\n\
+            {\"version\": {\"bin_names\": []}}"
+        } else if request.starts_with("GET /api/v1/crates/foo-cli/0.1.0 ") {
+            "HTTP/1.1 200 OK\r\nContent-Length: 35\r\nConnection: close\r\n\r\n\
+            {\"version\": {\"bin_names\": [\"foo\"]}}"
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        }
+    }
+
+    async fn search_client() -> (SparseRegistry, Client) {
+        let (url, cert) = spawn_server(respond_search).await;
+        let client = Client::new(
+            concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [Certificate::from_der(&cert).unwrap()],
+            Default::default(),
+            None,
+        )
+        .unwrap();
+
+        (SparseRegistry::new(url), client)
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let (registry, client) = search_client().await;
+
+        let found: Vec<_> = registry
+            .web_api(&client)
+            .await
+            .unwrap()
+            .unwrap()
+            .search(&client, "foo", 2)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|found| found.name)
+            .collect();
+        assert_eq!(found, ["foo", "foo-cli"]);
+    }
+
+    #[tokio::test]
+    async fn test_search_providing_bin() {
+        let (registry, client) = search_client().await;
+
+        let found = registry
+            .web_api(&client)
+            .await
+            .unwrap()
+            .unwrap()
+            .search_providing_bin(&client, "foo", 3)
+            .await
+            .unwrap();
+        // `foo` does not provide binary `foo`, and the binaries of
+        // `foo-unknown` are not known without its version.
+        assert_eq!(
+            found,
+            [
+                CrateProvidingBin {
+                    name: "foo-cli".into(),
+                    verified: true,
+                },
+                CrateProvidingBin {
+                    name: "foo-unknown".into(),
+                    verified: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dl_token() {
+        let config = |auth_required| RegistryConfig {
+            dl: "https://cdn.example.com/{crate}".into(),
+            auth_required,
+            api: None,
+        };
+
+        let mut registry = SparseRegistry::new(Url::parse("https://example.com/index/").unwrap());
+        assert_eq!(registry.dl_token(&config(true)), None);
+
+        registry.set_token("secret".into());
+        assert_eq!(registry.dl_token(&config(true)), Some("secret"));
+        // The downloads may be on another host.
+        assert_eq!(registry.dl_token(&config(false)), None);
     }
 }
@@ -2,10 +2,10 @@ use std::path::{Path, PathBuf};
 
 use binstalk_downloader::download::{DownloadError, TarEntriesVisitor, TarEntry};
 use binstalk_types::cargo_toml_binstall::Meta;
-use cargo_toml_workspace::cargo_toml::{Manifest, Value};
+use cargo_toml_workspace::cargo_toml::{Dependency, Inheritable, Manifest, Value};
 use normalize_path::NormalizePath;
 use tokio::io::AsyncReadExt;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::{vfs::Vfs, RegistryError};
 
@@ -66,12 +66,23 @@ impl TarEntriesVisitor for ManifestVisitor {
 
 impl ManifestVisitor {
     /// Load binstall metadata using the extracted information stored in memory.
-    pub(super) fn load_manifest(self) -> Result<Manifest<Meta>, RegistryError> {
+    ///
+    ///  * `version` - version of the crate in the index.
+    pub(super) fn load_manifest(self, version: &str) -> Result<Manifest<Meta>, RegistryError> {
         debug!("Loading manifest directly from extracted file");
 
         // Load and parse manifest
         let mut manifest = Manifest::from_slice_with_metadata(&self.cargo_toml_content)?;
 
+        if manifest.workspace.is_none() && manifest.needs_workspace_inheritance() {
+            warn!(
+                "Cargo.toml of {} still inherits from its workspace, which is not packaged \
+                with it, ignoring the inherited fields",
+                self.manifest_dir_path.display()
+            );
+            drop_workspace_inheritance(&mut manifest, version);
+        }
+
         // Checks vfs for binary output names
         manifest.complete_from_abstract_filesystem::<Value, _>(&self.vfs, None)?;
 
@@ -79,3 +90,98 @@ impl ManifestVisitor {
         Ok(manifest)
     }
 }
+
+/// `cargo package` resolves the workspace inheritance of the published
+/// `Cargo.toml`, but one which is not, e.g. from a git registry, cannot be
+/// resolved without the root of its workspace.
+///
+/// Take the version from the index, and unset the other inherited fields,
+/// which leaves `{ repo }` empty if the repository is inherited.
+fn drop_workspace_inheritance(manifest: &mut Manifest<Meta>, version: &str) {
+    fn unset<T>(field: &mut Option<Inheritable<T>>) {
+        if field.as_ref().map_or(false, |field| !field.is_set()) {
+            *field = None;
+        }
+    }
+
+    fn set_default<T: Default>(field: &mut Inheritable<T>) {
+        if !field.is_set() {
+            *field = Inheritable::Set(T::default());
+        }
+    }
+
+    if let Some(package) = &mut manifest.package {
+        if !package.version.is_set() {
+            package.version = Inheritable::Set(version.into());
+        }
+
+        set_default(&mut package.edition);
+        set_default(&mut package.authors);
+        set_default(&mut package.readme);
+        set_default(&mut package.keywords);
+        set_default(&mut package.categories);
+        set_default(&mut package.exclude);
+        set_default(&mut package.include);
+        set_default(&mut package.publish);
+
+        unset(&mut package.rust_version);
+        unset(&mut package.description);
+        unset(&mut package.homepage);
+        unset(&mut package.documentation);
+        unset(&mut package.license);
+        unset(&mut package.license_file);
+        unset(&mut package.repository);
+    }
+
+    // Dependencies are not needed to install the binaries.
+    for dependencies in [
+        &mut manifest.dependencies,
+        &mut manifest.build_dependencies,
+        &mut manifest.dev_dependencies,
+    ] {
+        dependencies.retain(|_, dep| !matches!(dep, Dependency::Inherited(_)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drop_workspace_inheritance() {
+        let cargo_toml = br#"
+[package]
+name = "tool"
+version.workspace = true
+repository.workspace = true
+edition.workspace = true
+description = "A tool"
+
+[package.metadata.binstall]
+pkg-fmt = "zip"
+
+[dependencies]
+serde.workspace = true
+"#;
+
+        let mut visitor = ManifestVisitor::new("tool-1.2.3".into());
+        visitor.cargo_toml_content = cargo_toml.to_vec();
+        visitor.vfs.add_path(Path::new("Cargo.toml"));
+        visitor.vfs.add_path(Path::new("src/main.rs"));
+
+        let manifest = visitor.load_manifest("1.2.3").unwrap();
+        let package = manifest.package.as_ref().unwrap();
+        assert_eq!(package.version(), "1.2.3");
+        assert_eq!(package.repository(), None);
+        assert_eq!(package.description(), Some("A tool"));
+        assert_eq!(
+            package
+                .metadata
+                .as_ref()
+                .and_then(|meta| meta.binstall.as_ref())
+                .and_then(|meta| meta.pkg_fmt),
+            Some(binstalk_types::cargo_toml_binstall::PkgFmt::Zip)
+        );
+        assert_eq!(manifest.bin[0].name.as_deref(), Some("tool"));
+    }
+}
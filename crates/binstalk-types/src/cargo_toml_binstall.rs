@@ -2,9 +2,13 @@
 //!
 //! This manifest defines how a particular binary crate may be installed by Binstall.
 
-use std::{borrow::Cow, collections::BTreeMap};
+use std::{
+    borrow::Cow,
+    collections::{btree_map::Entry, BTreeMap},
+};
 
 use serde::{Deserialize, Serialize};
+use strum_macros::Display;
 
 mod package_formats;
 #[doc(inline)]
@@ -19,14 +23,37 @@ pub struct Meta {
     pub binstall: Option<PkgMeta>,
 }
 
+/// Strategy for installing the package
+#[derive(
+    Clone, Copy, Debug, Display, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Strategy {
+    /// Attempt to download official pre-built artifacts using
+    /// information provided in `Cargo.toml`.
+    CrateMetaData,
+    /// Query third-party QuickInstall for the crates.
+    QuickInstall,
+    /// Build the crates from source using `cargo-build`.
+    Compile,
+}
+
 /// Metadata for binary installation use.
 ///
 /// Exposed via `[package.metadata]` in `Cargo.toml`
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct PkgMeta {
-    /// URL template for package downloads
-    pub pkg_url: Option<String>,
+    /// URL templates for package downloads, tried in order
+    ///
+    /// Either a string or an array of strings in the manifest.
+    #[serde(with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub pkg_url: Vec<String>,
+
+    /// Template of the git tag of the release, available to `pkg_url` as
+    /// `{ tag }`, `v{ version }` by default
+    pub pkg_tag: Option<String>,
 
     /// Format for package downloads
     pub pkg_fmt: Option<PkgFmt>,
@@ -34,9 +61,33 @@ pub struct PkgMeta {
     /// Path template for binary files in packages
     pub bin_dir: Option<String>,
 
+    /// Extension of the binaries including the leading `.`, available to
+    /// the templates as `{ binary-ext }`, the default of the target if
+    /// `None`, see [`default_binary_ext`]
+    pub binary_ext: Option<String>,
+
+    /// Path template for the directory of shell completions in packages
+    pub completions_dir: Option<String>,
+
+    /// Path template for the directory of man pages in packages
+    pub man_dir: Option<String>,
+
     /// Package signing configuration
     pub signing: Option<PkgSigning>,
 
+    /// URL template of the checksum of the package, either a file
+    /// containing only the digest or a `sha256sum` style manifest listing
+    /// the digests of the release artifacts
+    pub pkg_checksum_url: Option<String>,
+
+    /// Algorithm of the checksum at `pkg_checksum_url`, sha256 by default
+    pub pkg_checksum_algo: Option<ChecksumAlgorithm>,
+
+    /// Strategies never used to install the crate, e.g. `quick-install` for
+    /// crates which only want their own artifacts to be installed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disabled_strategies: Vec<Strategy>,
+
     /// Target specific overrides
     pub overrides: BTreeMap<String, PkgOverride>,
 }
@@ -44,8 +95,11 @@ pub struct PkgMeta {
 impl PkgMeta {
     /// Merge configuration overrides into object
     pub fn merge(&mut self, pkg_override: &PkgOverride) {
-        if let Some(o) = &pkg_override.pkg_url {
-            self.pkg_url = Some(o.clone());
+        if !pkg_override.pkg_url.is_empty() {
+            self.pkg_url = pkg_override.pkg_url.clone();
+        }
+        if let Some(o) = &pkg_override.pkg_tag {
+            self.pkg_tag = Some(o.clone());
         }
         if let Some(o) = &pkg_override.pkg_fmt {
             self.pkg_fmt = Some(*o);
@@ -53,6 +107,53 @@ impl PkgMeta {
         if let Some(o) = &pkg_override.bin_dir {
             self.bin_dir = Some(o.clone());
         }
+        if let Some(o) = &pkg_override.binary_ext {
+            self.binary_ext = Some(o.clone());
+        }
+        if let Some(o) = &pkg_override.completions_dir {
+            self.completions_dir = Some(o.clone());
+        }
+        if let Some(o) = &pkg_override.man_dir {
+            self.man_dir = Some(o.clone());
+        }
+        if let Some(o) = &pkg_override.pkg_checksum_url {
+            self.pkg_checksum_url = Some(o.clone());
+        }
+        if let Some(o) = &pkg_override.pkg_checksum_algo {
+            self.pkg_checksum_algo = Some(*o);
+        }
+    }
+
+    /// Fill the settings missing from `[package.metadata.binstall]` with the
+    /// ones of `[workspace.metadata.binstall]`, the package taking
+    /// precedence, also within the overrides of a target.
+    pub fn inherit_workspace(&mut self, workspace: &PkgMeta) {
+        if self.pkg_url.is_empty() {
+            self.pkg_url = workspace.pkg_url.clone();
+        }
+        inherit(&mut self.pkg_tag, &workspace.pkg_tag);
+        inherit(&mut self.pkg_fmt, &workspace.pkg_fmt);
+        inherit(&mut self.bin_dir, &workspace.bin_dir);
+        inherit(&mut self.binary_ext, &workspace.binary_ext);
+        inherit(&mut self.completions_dir, &workspace.completions_dir);
+        inherit(&mut self.man_dir, &workspace.man_dir);
+        inherit(&mut self.signing, &workspace.signing);
+        inherit(&mut self.pkg_checksum_url, &workspace.pkg_checksum_url);
+        inherit(&mut self.pkg_checksum_algo, &workspace.pkg_checksum_algo);
+        if self.disabled_strategies.is_empty() {
+            self.disabled_strategies = workspace.disabled_strategies.clone();
+        }
+
+        for (target, workspace_override) in &workspace.overrides {
+            match self.overrides.entry(target.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(workspace_override.clone());
+                }
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().inherit_workspace(workspace_override);
+                }
+            }
+        }
     }
 
     /// Merge configuration overrides into object
@@ -66,8 +167,16 @@ impl PkgMeta {
             pkg_url: pkg_overrides
                 .clone()
                 .into_iter()
-                .find_map(|pkg_override| pkg_override.pkg_url.clone())
-                .or_else(|| self.pkg_url.clone()),
+                .map(|pkg_override| &pkg_override.pkg_url)
+                .find(|pkg_url| !pkg_url.is_empty())
+                .unwrap_or(&self.pkg_url)
+                .clone(),
+
+            pkg_tag: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.pkg_tag.clone())
+                .or_else(|| self.pkg_tag.clone()),
 
             pkg_fmt: pkg_overrides
                 .clone()
@@ -81,14 +190,77 @@ impl PkgMeta {
                 .find_map(|pkg_override| pkg_override.bin_dir.clone())
                 .or_else(|| self.bin_dir.clone()),
 
+            binary_ext: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.binary_ext.clone())
+                .or_else(|| self.binary_ext.clone()),
+
+            completions_dir: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.completions_dir.clone())
+                .or_else(|| self.completions_dir.clone()),
+
+            man_dir: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.man_dir.clone())
+                .or_else(|| self.man_dir.clone()),
+
             signing: pkg_overrides
+                .clone()
                 .into_iter()
                 .find_map(|pkg_override| pkg_override.signing.clone())
                 .or_else(|| self.signing.clone()),
 
+            pkg_checksum_url: pkg_overrides
+                .clone()
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.pkg_checksum_url.clone())
+                .or_else(|| self.pkg_checksum_url.clone()),
+
+            pkg_checksum_algo: pkg_overrides
+                .into_iter()
+                .find_map(|pkg_override| pkg_override.pkg_checksum_algo)
+                .or(self.pkg_checksum_algo),
+
+            disabled_strategies: self.disabled_strategies.clone(),
+
             overrides: Default::default(),
         }
     }
+
+    /// Return the extension of the binaries for `target`, `binary_ext` if
+    /// specified, otherwise [`default_binary_ext`].
+    pub fn binary_ext(&self, target: &str) -> &str {
+        self.binary_ext
+            .as_deref()
+            .unwrap_or_else(|| default_binary_ext(target))
+    }
+}
+
+/// Set `field` to the value of the workspace if it is not set.
+fn inherit<T: Clone>(field: &mut Option<T>, workspace: &Option<T>) {
+    if field.is_none() {
+        *field = workspace.clone();
+    }
+}
+
+/// Return the extension of the binaries built for `target` including the
+/// leading `.`, based on its architecture and operating system: `.wasm` for
+/// wasm, `.exe` for windows and none otherwise.
+pub fn default_binary_ext(target: &str) -> &'static str {
+    let mut components = target.split('-');
+    let arch = components.next().unwrap_or_default();
+
+    if arch.starts_with("wasm") {
+        ".wasm"
+    } else if components.any(|component| component == "windows") {
+        ".exe"
+    } else {
+        ""
+    }
 }
 
 /// Target specific overrides for binary installation
@@ -97,8 +269,12 @@ impl PkgMeta {
 #[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", default)]
 pub struct PkgOverride {
-    /// URL template override for package downloads
-    pub pkg_url: Option<String>,
+    /// URL templates override for package downloads, tried in order
+    #[serde(with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub pkg_url: Vec<String>,
+
+    /// Template override of the git tag of the release
+    pub pkg_tag: Option<String>,
 
     /// Format override for package downloads
     pub pkg_fmt: Option<PkgFmt>,
@@ -106,8 +282,74 @@ pub struct PkgOverride {
     /// Path template override for binary files in packages
     pub bin_dir: Option<String>,
 
+    /// Extension override of the binaries
+    pub binary_ext: Option<String>,
+
+    /// Path template override for the directory of shell completions in packages
+    pub completions_dir: Option<String>,
+
+    /// Path template override for the directory of man pages in packages
+    pub man_dir: Option<String>,
+
     /// Package signing configuration
     pub signing: Option<PkgSigning>,
+
+    /// URL template override of the checksum of the package
+    pub pkg_checksum_url: Option<String>,
+
+    /// Algorithm override of the checksum of the package
+    pub pkg_checksum_algo: Option<ChecksumAlgorithm>,
+}
+
+impl PkgOverride {
+    fn inherit_workspace(&mut self, workspace: &PkgOverride) {
+        if self.pkg_url.is_empty() {
+            self.pkg_url = workspace.pkg_url.clone();
+        }
+        inherit(&mut self.pkg_tag, &workspace.pkg_tag);
+        inherit(&mut self.pkg_fmt, &workspace.pkg_fmt);
+        inherit(&mut self.bin_dir, &workspace.bin_dir);
+        inherit(&mut self.binary_ext, &workspace.binary_ext);
+        inherit(&mut self.completions_dir, &workspace.completions_dir);
+        inherit(&mut self.man_dir, &workspace.man_dir);
+        inherit(&mut self.signing, &workspace.signing);
+        inherit(&mut self.pkg_checksum_url, &workspace.pkg_checksum_url);
+        inherit(&mut self.pkg_checksum_algo, &workspace.pkg_checksum_algo);
+    }
+}
+
+/// (De)serialize a `Vec` from either a single value or an array of them.
+mod one_or_many {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    pub(super) fn serialize<S, T>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize,
+    {
+        match values {
+            [value] => value.serialize(serializer),
+            values => values.serialize(serializer),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de>,
+    {
+        Ok(match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -126,7 +368,9 @@ pub struct PkgSigning {
     /// Signing algorithm supported by Binstall.
     pub algorithm: SigningAlgorithm,
 
-    /// Signing public key
+    /// Signing public key, unused by sigstore which checks the identity
+    /// of the signer against the repository of the crate instead
+    #[serde(default)]
     pub pubkey: Cow<'static, str>,
 
     /// Signature file override template (url to download)
@@ -140,4 +384,184 @@ pub struct PkgSigning {
 pub enum SigningAlgorithm {
     /// [minisign](https://jedisct1.github.io/minisign/)
     Minisign,
+
+    /// [sigstore](https://www.sigstore.dev/) bundle, e.g. as produced by
+    /// `cosign sign-blob --bundle` or GitHub artifact attestations
+    Sigstore,
+}
+
+/// Algorithm of the checksum of a package.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ChecksumAlgorithm {
+    /// SHA-256, e.g. as output by `sha256sum`
+    Sha256,
+}
+
+impl Default for ChecksumAlgorithm {
+    fn default() -> Self {
+        Self::Sha256
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn meta() -> PkgMeta {
+        PkgMeta {
+            pkg_url: vec!["{ repo }/{ name }-{ target }.{ archive-format }".to_string()],
+            pkg_fmt: Some(PkgFmt::Tgz),
+            bin_dir: Some("{ bin }{ binary-ext }".to_string()),
+            overrides: BTreeMap::from([
+                (
+                    "x86_64-pc-windows-msvc".to_string(),
+                    PkgOverride {
+                        pkg_fmt: Some(PkgFmt::Zip),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "aarch64-apple-darwin".to_string(),
+                    PkgOverride {
+                        pkg_url: vec!["{ repo }/{ name }-macos.{ archive-format }".to_string()],
+                        bin_dir: Some("{ name }.app/{ bin }".to_string()),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_overrides() {
+        let meta = meta();
+        let merged = |target: &str| meta.merge_overrides(meta.overrides.get(target));
+
+        // Only the format changes, everything else comes from the base meta.
+        let windows = merged("x86_64-pc-windows-msvc");
+        assert_eq!(windows.pkg_fmt, Some(PkgFmt::Zip));
+        assert_eq!(windows.pkg_url, meta.pkg_url);
+        assert_eq!(windows.bin_dir, meta.bin_dir);
+        assert!(windows.overrides.is_empty());
+
+        let macos = merged("aarch64-apple-darwin");
+        assert_eq!(macos.pkg_fmt, Some(PkgFmt::Tgz));
+        assert_eq!(
+            macos.pkg_url,
+            ["{ repo }/{ name }-macos.{ archive-format }"]
+        );
+        assert_eq!(macos.bin_dir.as_deref(), Some("{ name }.app/{ bin }"));
+
+        // Targets are matched exactly.
+        let linux = merged("x86_64-unknown-linux-gnu");
+        assert_eq!(linux.pkg_fmt, meta.pkg_fmt);
+        assert_eq!(linux.pkg_url, meta.pkg_url);
+        assert_eq!(linux.bin_dir, meta.bin_dir);
+        assert_eq!(merged("x86_64-pc-windows-gnu").pkg_fmt, Some(PkgFmt::Tgz));
+
+        // Earlier overrides take precedence over later ones.
+        let cli = PkgOverride {
+            pkg_fmt: Some(PkgFmt::Tzstd),
+            ..Default::default()
+        };
+        let merged = meta.merge_overrides([&cli, &meta.overrides["x86_64-pc-windows-msvc"]]);
+        assert_eq!(merged.pkg_fmt, Some(PkgFmt::Tzstd));
+    }
+
+    #[test]
+    fn test_binary_ext() {
+        for (target, ext) in [
+            ("x86_64-pc-windows-msvc", ".exe"),
+            ("aarch64-pc-windows-gnullvm", ".exe"),
+            ("wasm32-wasip1", ".wasm"),
+            ("wasm32-unknown-unknown", ".wasm"),
+            ("x86_64-unknown-linux-gnu", ""),
+            ("aarch64-apple-darwin", ""),
+        ] {
+            assert_eq!(default_binary_ext(target), ext, "{target}");
+            assert_eq!(PkgMeta::default().binary_ext(target), ext, "{target}");
+        }
+
+        // Windows binaries shipped without an extension.
+        let meta = PkgMeta {
+            binary_ext: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(meta.binary_ext("x86_64-pc-windows-msvc"), "");
+
+        // Overrides win over the base meta.
+        let pkg_override = PkgOverride {
+            binary_ext: Some(".exe".to_string()),
+            ..Default::default()
+        };
+        let merged = meta.merge_overrides([&pkg_override]);
+        assert_eq!(merged.binary_ext("x86_64-pc-windows-msvc"), ".exe");
+    }
+
+    #[test]
+    fn test_inherit_workspace() {
+        let workspace = meta();
+        let mut package = PkgMeta {
+            pkg_url: vec!["{ repo }/releases/{ name }.tgz".to_string()],
+            overrides: BTreeMap::from([(
+                "x86_64-pc-windows-msvc".to_string(),
+                PkgOverride {
+                    bin_dir: Some("{ bin }.exe".to_string()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        package.inherit_workspace(&workspace);
+
+        // The package takes precedence.
+        assert_eq!(package.pkg_url, ["{ repo }/releases/{ name }.tgz"]);
+        assert_eq!(package.pkg_fmt, workspace.pkg_fmt);
+        assert_eq!(package.bin_dir, workspace.bin_dir);
+
+        // Overrides are merged per target and per setting.
+        let windows = &package.overrides["x86_64-pc-windows-msvc"];
+        assert_eq!(windows.bin_dir.as_deref(), Some("{ bin }.exe"));
+        assert_eq!(windows.pkg_fmt, Some(PkgFmt::Zip));
+        assert_eq!(
+            package.overrides["aarch64-apple-darwin"],
+            workspace.overrides["aarch64-apple-darwin"]
+        );
+    }
+
+    #[test]
+    fn test_disabled_strategies() {
+        let meta = PkgMeta {
+            disabled_strategies: vec![Strategy::QuickInstall, Strategy::Compile],
+            ..meta()
+        };
+        assert_eq!(Strategy::CrateMetaData.to_string(), "crate-meta-data");
+
+        // They apply to every target, and are inherited from the workspace.
+        let pkg_override = PkgOverride::default();
+        assert_eq!(
+            meta.merge_overrides([&pkg_override]).disabled_strategies,
+            meta.disabled_strategies
+        );
+
+        let mut package = PkgMeta::default();
+        package.inherit_workspace(&meta);
+        assert_eq!(package.disabled_strategies, meta.disabled_strategies);
+    }
+
+    #[test]
+    fn test_merge() {
+        let meta = meta();
+
+        for pkg_override in meta.overrides.values() {
+            let mut merged = meta.clone();
+            merged.merge(pkg_override);
+            merged.overrides.clear();
+
+            assert_eq!(merged, meta.merge_overrides([pkg_override]));
+        }
+    }
 }
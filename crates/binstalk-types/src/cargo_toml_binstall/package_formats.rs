@@ -22,6 +22,12 @@ pub enum PkgFmt {
     Zip,
     /// Download format is raw / binary
     Bin,
+    /// Download format is raw / binary compressed with GZip
+    Bgz,
+    /// Download format is raw / binary compressed with XZ
+    Bxz,
+    /// Download format is raw / binary compressed with Zstd
+    Bzstd,
 }
 
 impl Default for PkgFmt {
@@ -40,10 +46,21 @@ impl PkgFmt {
             PkgFmt::Txz => PkgFmtDecomposed::Tar(TarBasedFmt::Txz),
             PkgFmt::Tzstd => PkgFmtDecomposed::Tar(TarBasedFmt::Tzstd),
             PkgFmt::Bin => PkgFmtDecomposed::Bin,
+            PkgFmt::Bgz => PkgFmtDecomposed::CompressedBin(CompressedBinFmt::Gz),
+            PkgFmt::Bxz => PkgFmtDecomposed::CompressedBin(CompressedBinFmt::Xz),
+            PkgFmt::Bzstd => PkgFmtDecomposed::CompressedBin(CompressedBinFmt::Zstd),
             PkgFmt::Zip => PkgFmtDecomposed::Zip,
         }
     }
 
+    /// Return true if the package is the binary itself, compressed or not.
+    pub fn is_bin(self) -> bool {
+        matches!(
+            self.decompose(),
+            PkgFmtDecomposed::Bin | PkgFmtDecomposed::CompressedBin(_)
+        )
+    }
+
     /// List of possible file extensions for the format
     /// (with prefix `.`).
     ///
@@ -63,6 +80,9 @@ impl PkgFmt {
                     &[".bin", ""]
                 }
             }
+            PkgFmt::Bgz => &[".gz"],
+            PkgFmt::Bxz => &[".xz"],
+            PkgFmt::Bzstd => &[".zst"],
             PkgFmt::Zip => &[".zip"],
         }
     }
@@ -71,31 +91,53 @@ impl PkgFmt {
     pub fn guess_pkg_format(pkg_url: &str) -> Option<Self> {
         let mut it = pkg_url.rsplitn(3, '.');
 
-        let guess = match it.next()? {
+        let ext = it.next()?;
+        // The extension must not be the whole file name, neither can `.tar`.
+        let is_tar = match (it.next()?, it.next()) {
+            ("tar", Some(_)) => true,
+            ("tar", None) => return None,
+            _ => false,
+        };
+
+        match ext {
             "tar" => Some(PkgFmt::Tar),
 
             "tbz2" => Some(PkgFmt::Tbz2),
-            "bz2" if it.next() == Some("tar") => Some(PkgFmt::Tbz2),
+            "bz2" if is_tar => Some(PkgFmt::Tbz2),
 
             "tgz" => Some(PkgFmt::Tgz),
-            "gz" if it.next() == Some("tar") => Some(PkgFmt::Tgz),
+            "gz" if is_tar => Some(PkgFmt::Tgz),
+            "gz" => Some(PkgFmt::Bgz),
 
             "txz" => Some(PkgFmt::Txz),
-            "xz" if it.next() == Some("tar") => Some(PkgFmt::Txz),
+            "xz" if is_tar => Some(PkgFmt::Txz),
+            "xz" => Some(PkgFmt::Bxz),
 
             "tzstd" | "tzst" => Some(PkgFmt::Tzstd),
-            "zst" if it.next() == Some("tar") => Some(PkgFmt::Tzstd),
+            "zst" if is_tar => Some(PkgFmt::Tzstd),
+            "zst" => Some(PkgFmt::Bzstd),
 
             "exe" | "bin" => Some(PkgFmt::Bin),
             "zip" => Some(PkgFmt::Zip),
 
             _ => None,
-        };
+        }
+    }
 
-        if it.next().is_some() {
-            guess
-        } else {
+    /// Given the path of a package, e.g. of a rendered pkg-url or of a
+    /// release artifact, guess its pkg-fmt from the extension of its file
+    /// name.
+    ///
+    /// A file name without any extension is guessed to be a binary.
+    pub fn guess_from_path(path: &str) -> Option<Self> {
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+
+        if file_name.is_empty() {
             None
+        } else if !file_name.contains('.') {
+            Some(PkgFmt::Bin)
+        } else {
+            Self::guess_pkg_format(file_name)
         }
     }
 }
@@ -104,9 +146,21 @@ impl PkgFmt {
 pub enum PkgFmtDecomposed {
     Tar(TarBasedFmt),
     Bin,
+    CompressedBin(CompressedBinFmt),
     Zip,
 }
 
+/// Compression of a binary downloaded as is.
+#[derive(Debug, Display, Copy, Clone, Eq, PartialEq)]
+pub enum CompressedBinFmt {
+    /// Binary compressed with GZip
+    Gz,
+    /// Binary compressed with XZ
+    Xz,
+    /// Binary compressed with Zstd
+    Zstd,
+}
+
 #[derive(Debug, Display, Copy, Clone, Eq, PartialEq)]
 pub enum TarBasedFmt {
     /// Download format is TAR (uncompressed)
@@ -132,3 +186,43 @@ impl From<TarBasedFmt> for PkgFmt {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_guess_from_path() {
+        for (path, expected) in [
+            ("tool.tar.gz", Some(PkgFmt::Tgz)),
+            ("tool.tgz", Some(PkgFmt::Tgz)),
+            ("tool.tar.xz", Some(PkgFmt::Txz)),
+            ("tool.txz", Some(PkgFmt::Txz)),
+            ("tool.tar.zst", Some(PkgFmt::Tzstd)),
+            ("tool.tar.bz2", Some(PkgFmt::Tbz2)),
+            ("tool.tar", Some(PkgFmt::Tar)),
+            ("tool.zip", Some(PkgFmt::Zip)),
+            ("tool.exe", Some(PkgFmt::Bin)),
+            (
+                "/releases/download/v1.0.0/tool-x86_64-linux",
+                Some(PkgFmt::Bin),
+            ),
+            (
+                "{ repo }/releases/download/v{ version }/{ name }-{ target }",
+                Some(PkgFmt::Bin),
+            ),
+            ("/releases/download/v1.0.0/tool.zip", Some(PkgFmt::Zip)),
+            // A version in the file name is not an extension.
+            ("tool-1.0.0-x86_64-linux", None),
+            ("tool.gz", Some(PkgFmt::Bgz)),
+            ("tool-x86_64-unknown-linux-musl.xz", Some(PkgFmt::Bxz)),
+            ("tool-1.0.0-x86_64.zst", Some(PkgFmt::Bzstd)),
+            ("tool.bz2", None),
+            ("tar.gz", None),
+            ("tool.deb", None),
+            ("/releases/download/v1.0.0/", None),
+        ] {
+            assert_eq!(PkgFmt::guess_from_path(path), expected, "{path}");
+        }
+    }
+}
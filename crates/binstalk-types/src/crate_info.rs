@@ -1,6 +1,6 @@
 //! Common structure for crate information for post-install manifests.
 
-use std::{borrow, cmp, hash};
+use std::{borrow, cmp, collections::BTreeMap, fmt, hash, path::PathBuf};
 
 use compact_str::CompactString;
 use maybe_owned::MaybeOwned;
@@ -9,6 +9,8 @@ use semver::Version;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::cargo_toml_binstall::{PkgFmt, SigningAlgorithm};
+
 pub fn cratesio_url() -> &'static Url {
     static CRATESIO: Lazy<Url, fn() -> Url> =
         Lazy::new(|| Url::parse("https://github.com/rust-lang/crates.io-index").unwrap());
@@ -24,6 +26,111 @@ pub struct CrateInfo {
     pub source: CrateSource,
     pub target: CompactString,
     pub bins: Vec<CompactString>,
+    /// Binaries selected with `--bin` or `crate:bin`, installed again when
+    /// the crate is upgraded, empty if all of them are installed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub selected_bins: Vec<CompactString>,
+    /// SHA-256 digests of the installed binaries, by their names in `bins`,
+    /// to detect missing or corrupt binaries.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bin_digests: BTreeMap<CompactString, CompactString>,
+    /// Shell completions and man pages installed along with the binaries.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_files: Vec<PathBuf>,
+    /// Links to the binaries created in `--link-dir`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<PathBuf>,
+    /// The filesystem did not support symlinks, renames or permissions,
+    /// so the installation fell back to copying or skipped them.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub degraded: bool,
+    /// Result of comparing `current_version` with the version found in the
+    /// artifact, `None` if it is not checked or no version is found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_version: Option<ArtifactVersion>,
+    /// How the artifact was verified, `None` if installed from source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+    /// Where the crate was fetched from, tried first when it is upgraded.
+    ///
+    /// `None` for records written before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_source: Option<FetchSource>,
+}
+
+/// The strategy and url the crate was fetched with.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "kebab-case")]
+pub enum FetchSource {
+    /// Artifact found with the pkg-url of the crate, or the default ones.
+    GhCrateMeta {
+        /// Url of the artifact, or of the first one if each binary has its
+        /// own artifact.
+        url: Url,
+        /// Repository of the crate, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        repo: Option<CompactString>,
+    },
+    /// Artifact built by cargo-quickinstall.
+    QuickInstall { url: Url },
+    /// Built from source with `cargo install`.
+    SourceBuild,
+}
+
+/// The artifact installed for a crate on a target, recorded in a lockfile
+/// to install it again without resolving the crate.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedCrate {
+    pub name: CompactString,
+    pub version: Version,
+    pub target: CompactString,
+    pub url: Url,
+    /// Hex-encoded SHA-256 digest of the artifact.
+    pub sha256: CompactString,
+    pub pkg_fmt: PkgFmt,
+    /// Where the binaries are in the artifact, `None` for raw binaries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bin_dir: Option<CompactString>,
+    /// The binaries installed, without their extension.
+    pub bins: Vec<CompactString>,
+}
+
+/// Result of comparing the resolved version with the one found in the
+/// artifact.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArtifactVersion {
+    Matched,
+    Mismatched { found: CompactString },
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+#[cfg(feature = "test-utils")]
+impl CrateInfo {
+    /// `name@version` installed from crates.io with a binary of the same
+    /// name, for the tests of the dependent crates.
+    pub fn for_test(name: &str, current_version: Version) -> Self {
+        Self {
+            name: name.into(),
+            version_req: "*".into(),
+            current_version,
+            source: CrateSource::cratesio_registry(),
+            target: "x86_64-unknown-linux-gnu".into(),
+            bins: vec![name.into()],
+            selected_bins: Vec::new(),
+            bin_digests: Default::default(),
+            extra_files: Vec::new(),
+            links: Vec::new(),
+            degraded: false,
+            artifact_version: None,
+            verification: None,
+            fetch_source: None,
+        }
+    }
 }
 
 impl borrow::Borrow<str> for CrateInfo {
@@ -60,7 +167,68 @@ impl hash::Hash for CrateInfo {
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+/// How the artifact was verified before installing it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Verification {
+    /// Hex-encoded SHA-256 digest of the artifact, computed while downloading it.
+    pub sha256: CompactString,
+    pub signature: SignatureCheck,
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256 {}, signature {}", self.sha256, self.signature)
+    }
+}
+
+/// Result of checking the signature of the artifact.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum SignatureCheck {
+    /// The signature is valid.
+    Verified {
+        algorithm: SigningAlgorithm,
+        /// Identifier of the public key, as printed by the signing tool,
+        /// or the identity in the certificate of a sigstore bundle.
+        key_id: CompactString,
+        /// Comment covered by the signature, or the OIDC issuer of the
+        /// identity of a sigstore bundle.
+        trusted_comment: CompactString,
+    },
+
+    /// The package does not publish a signature.
+    NotPublished,
+
+    /// Signature verification is disabled by `--skip-signatures`.
+    Skipped,
+}
+
+impl fmt::Display for SignatureCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Verified {
+                algorithm,
+                key_id,
+                trusted_comment,
+            } => {
+                match algorithm {
+                    SigningAlgorithm::Minisign => write!(f, "verified with minisign key {key_id}")?,
+                    SigningAlgorithm::Sigstore => {
+                        write!(f, "verified with sigstore identity {key_id}")?
+                    }
+                }
+                if !trusted_comment.is_empty() {
+                    write!(f, " ({trusted_comment})")?;
+                }
+                Ok(())
+            }
+            Self::NotPublished => f.write_str("not checked, no verification material published"),
+            Self::Skipped => f.write_str("not checked, verification skipped by flag"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SourceType {
     Git,
     Path,
@@ -81,3 +249,52 @@ impl CrateSource {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_verification_display() {
+        let sha256 = CompactString::from("ab".repeat(32));
+
+        let verification = |signature| Verification {
+            sha256: sha256.clone(),
+            signature,
+        };
+
+        assert_eq!(
+            verification(SignatureCheck::Verified {
+                algorithm: SigningAlgorithm::Minisign,
+                key_id: "3A5C1D76E1D6C2B5".into(),
+                trusted_comment: "timestamp:1700000000".into(),
+            })
+            .to_string(),
+            format!(
+                "sha256 {sha256}, signature verified with minisign key 3A5C1D76E1D6C2B5 \
+(timestamp:1700000000)"
+            )
+        );
+        assert_eq!(
+            verification(SignatureCheck::Verified {
+                algorithm: SigningAlgorithm::Sigstore,
+                key_id: "https://github.com/cargo-bins/cargo-binstall/.github/workflows/release.yml@refs/tags/v1.2.3".into(),
+                trusted_comment: "https://token.actions.githubusercontent.com".into(),
+            })
+            .to_string(),
+            format!(
+                "sha256 {sha256}, signature verified with sigstore identity \
+https://github.com/cargo-bins/cargo-binstall/.github/workflows/release.yml@refs/tags/v1.2.3 \
+(https://token.actions.githubusercontent.com)"
+            )
+        );
+        assert_eq!(
+            verification(SignatureCheck::NotPublished).to_string(),
+            format!("sha256 {sha256}, signature not checked, no verification material published")
+        );
+        assert_eq!(
+            verification(SignatureCheck::Skipped).to_string(),
+            format!("sha256 {sha256}, signature not checked, verification skipped by flag")
+        );
+    }
+}
@@ -1,13 +1,16 @@
 use std::{
     fmt, io, ops,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{ExitCode, ExitStatus, Termination},
+    time::Duration,
 };
 
 use binstalk_downloader::{
-    download::DownloadError, gh_api_client::GhApiError, remote::Error as RemoteError,
+    download::{DownloadError, UnexpectedFormat},
+    gh_api_client::GhApiError,
+    remote::{Error as RemoteError, GeoBlocked},
 };
-use binstalk_fetchers::FetchError;
+use binstalk_fetchers::{FetchError, UrlTemplateError};
 use compact_str::CompactString;
 use itertools::Itertools;
 use miette::{Diagnostic, Report};
@@ -21,7 +24,8 @@ use crate::{
     helpers::{
         cargo_toml::Error as CargoTomlError, cargo_toml_workspace::Error as LoadManifestFromWSError,
     },
-    registry::{InvalidRegistryError, RegistryError},
+    manifests::cargo_toml_binstall::Strategy,
+    registry::{self, InvalidRegistryError, RegistryError},
 };
 
 #[derive(Debug, Error)]
@@ -32,6 +36,93 @@ pub struct VersionParseError {
     pub err: semver::Error,
 }
 
+/// A strategy which failed to provide a prebuilt binary.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+#[error("{strategy} ({target}): {kind}")]
+pub struct StrategyFailure {
+    /// Name of the source of the strategy, e.g. `QuickInstall`.
+    pub strategy: CompactString,
+    pub target: CompactString,
+    pub kind: StrategyFailureKind,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StrategyFailureKind {
+    /// No artifact is found.
+    NotFound,
+    /// The artifact host refused the requests because of the region or
+    /// network of the user.
+    GeoBlocked { host: CompactString },
+    /// The artifact is not cached, in offline mode.
+    OfflineMissing { url: CompactString },
+    /// The artifact does not provide any of the binaries.
+    NoBinaries,
+    /// A server refused the requests because of rate limiting.
+    RateLimited(CompactString),
+    /// Checking or downloading the artifact failed because of the network.
+    Network(CompactString),
+    /// Checking, downloading or extracting the artifact failed.
+    Failed(CompactString),
+}
+
+impl fmt::Display for StrategyFailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => f.write_str("artifact not found"),
+            Self::GeoBlocked { host } => {
+                write!(f, "{host} is unavailable from your region/network")
+            }
+            Self::OfflineMissing { url } => write!(f, "{url} is not cached"),
+            Self::NoBinaries => f.write_str("artifact does not provide any binary"),
+            Self::RateLimited(err) | Self::Network(err) | Self::Failed(err) => f.write_str(err),
+        }
+    }
+}
+
+/// What to suggest instead of a crate which is not found.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CrateSuggestion {
+    /// The name is the one of a binary of this crate.
+    ProvidedBy(&'static str),
+    /// Crates in the registry with a similar name.
+    Similar(Vec<registry::SimilarCrate>),
+    None,
+}
+
+impl CrateSuggestion {
+    fn help(&self, name: &str) -> String {
+        match self {
+            Self::ProvidedBy(crate_name) => format!(
+                "`{name}` is a binary of the crate {crate_name}, install it with `cargo binstall {crate_name}`."
+            ),
+            Self::Similar(similar) => format!(
+                "Did you mean {}?",
+                similar
+                    .iter()
+                    .map(|similar| format!(
+                        "{} ({} downloads)",
+                        similar.name,
+                        format_count(similar.downloads)
+                    ))
+                    .format(", or ")
+            ),
+            Self::None => format!(
+                "Check the spelling of the crate name, or use `:{name}` to search the \
+registry for the crate providing binary `{name}`."
+            ),
+        }
+    }
+}
+
+/// Format `count` with a metric suffix, e.g. `1.5M`.
+fn format_count(count: u64) -> String {
+    match count {
+        0..=999 => count.to_string(),
+        1_000..=999_999 => format!("{:.1}k", count as f64 / 1e3),
+        _ => format!("{:.1}M", count as f64 / 1e6),
+    }
+}
+
 #[derive(Debug, Diagnostic, Error)]
 #[error("For crate {crate_name}: {err}")]
 pub struct CrateContextError {
@@ -125,6 +216,27 @@ impl miette::Diagnostic for CrateErrors {
     }
 }
 
+/// Describe what was found instead of a missing binary, in
+/// [`BinstallError::MissingBinFile`].
+struct MissingBinHint<'a> {
+    found: &'a [CompactString],
+    bin_dir: Option<&'a str>,
+}
+
+impl fmt::Display for MissingBinHint<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.found.is_empty() {
+            f.write_str("which contains no executable")?;
+        } else {
+            write!(f, "which contains {}", self.found.iter().format(", "))?;
+        }
+        if let Some(bin_dir) = self.bin_dir {
+            write!(f, "; bin-dir \"{bin_dir}\" would find it")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("Invalid pkg-url {pkg_url} for {crate_name}@{version} on {target}: {reason}")]
 pub struct InvalidPkgFmtError {
@@ -135,6 +247,125 @@ pub struct InvalidPkgFmtError {
     pub reason: &'static str,
 }
 
+/// Category of a [`BinstallError`], for scripts to tell failures apart with
+/// the exit code, see [`EXIT_CODES`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The installation is cancelled by the user.
+    UserAbort,
+    /// No prebuilt artifact is found and building from source is disabled.
+    NoArtifact,
+    /// The package does not match the checksum published for it.
+    ChecksumMismatch,
+    /// A server refused the requests because too many are sent, e.g. the
+    /// rate limit of the GitHub API.
+    RateLimited,
+    /// A request failed because of the network, or a server error which
+    /// may go away when retried.
+    Network,
+    /// Something is not cached and cannot be downloaded with `--offline`.
+    Offline,
+    /// The binaries would overwrite files not installed by binstall or
+    /// cargo-install, or any file with `--no-track` (exit 88).
+    InstallConflict,
+    /// Any other failure.
+    Other,
+}
+
+impl ErrorKind {
+    fn of_request(rate_limited: bool, network: bool) -> Self {
+        if rate_limited {
+            Self::RateLimited
+        } else if network {
+            Self::Network
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UserAbort => "user-abort",
+            Self::NoArtifact => "no-artifact",
+            Self::ChecksumMismatch => "checksum-mismatch",
+            Self::RateLimited => "rate-limited",
+            Self::Network => "network",
+            Self::Offline => "offline",
+            Self::InstallConflict => "install-conflict",
+            Self::Other => "other",
+        })
+    }
+}
+
+/// The exit codes of cargo-binstall, with their [`ErrorKind`] and a
+/// description, printed by `--list-exit-codes`.
+///
+/// The codes are stable, new ones are only added. 101 is never used since
+/// it is the exit code of a panic.
+#[rustfmt::skip]
+pub const EXIT_CODES: &[(u8, ErrorKind, &str)] = {
+    use ErrorKind::*;
+
+    &[
+        (2, Other, "Invalid command line arguments"),
+        (16, Other, "Fatal error not specific to binstall"),
+        (17, Other, "Internal: a task could not be joined"),
+        (32, UserAbort, "The installation is cancelled by the user"),
+        (40, Other, "The package failed signature verification"),
+        (41, Other, "The package is not signed and the policy requires it"),
+        (42, ChecksumMismatch, "The package does not match its checksum"),
+        (43, Other, "The package has no checksum and the policy requires it"),
+        (44, Other, "The package failed attestation verification"),
+        (65, Other, "A url is invalid"),
+        (67, Other, "A url template is invalid"),
+        (68, Other, "Failed to fetch or download a package"),
+        (70, Other, "A subprocess failed, e.g. cargo-install"),
+        (74, Other, "I/O error"),
+        (75, Other, "Unknown registry name"),
+        (76, Other, "Failed to query the registry, or the crate is not in it"),
+        (77, Other, "The path to the cargo manifest is invalid"),
+        (78, Other, "Failed to parse the cargo manifest"),
+        (79, Other, "Failed to parse the registry index url"),
+        (80, Other, "A version is not valid semver"),
+        (84, Other, "Both crate@version and --version are specified"),
+        (86, Other, "The crate does not specify its binaries"),
+        (87, Other, "No target is detected, specify --target"),
+        (88, Other, "Failed to find or install the binaries"),
+        (89, Other, "Cargo.toml of the crate has no [package]"),
+        (90, Other, "bin-dir generates duplicate source paths"),
+        (94, NoArtifact, "No prebuilt artifact is found and building it is disabled"),
+        (95, Other, "The pkg-fmt or pkg-url of the crate is invalid"),
+        (96, Other, "Request to the GitHub API failed"),
+        (97, Other, "Failed to parse a target triple"),
+        (98, Other, "Failed to clone or check out a git repository"),
+        (99, Other, "Failed to load the manifest from the workspace"),
+        (100, InstallConflict, "A file not installed by binstall is in the way"),
+        (102, Other, "The crate does not provide the binary of crate:bin"),
+        (103, Other, "No crate provides the binary of :bin"),
+        (104, Other, "Several crates may provide the binary of :bin"),
+        (105, Other, "The version is denylisted"),
+        (106, Network, "Artifact hosts are unavailable from your region"),
+        (107, Other, "A url template renders to an unsupported scheme"),
+        (108, Other, "A url template renders to a url with credentials"),
+        (109, Offline, "Not cached and cannot be downloaded with --offline"),
+        (110, Other, "The package downloaded is not in the format expected"),
+        (111, Other, "A url template renders to a plain http url"),
+        (112, Other, "A binary is not where bin-dir points to"),
+        (113, RateLimited, "A server refused the requests because of rate limiting"),
+        (114, Network, "A request failed because of the network"),
+        (115, Network, "Not resolved and downloaded within the --deadline"),
+        (116, Other, "Confirmation is required but stdin is not a terminal"),
+        (117, Other, "Not enough disk space to download or extract the package"),
+        (118, Other, "The lockfile has no entry of the crate for the targets"),
+        (119, Other, "The artifact recorded in the lockfile is missing"),
+        (120, Other, "The version in the artifact is not the resolved one"),
+        (121, NoArtifact, "No prebuilt artifact of the crate to prefetch"),
+    ]
+};
+
 /// Error kinds emitted by cargo-binstall.
 #[derive(Error, Diagnostic, Debug)]
 #[non_exhaustive]
@@ -176,6 +407,38 @@ pub enum BinstallError {
     #[diagnostic(severity(error), code(binstall::signature::missing))]
     MissingSignature(CompactString),
 
+    /// The package does not match the checksum published by the crate.
+    ///
+    /// - Code: `binstall::checksum::mismatch`
+    /// - Exit: 42
+    #[error("Checksum mismatch, expected sha256 {expected} but the package has sha256 {actual}")]
+    #[diagnostic(severity(error), code(binstall::checksum::mismatch))]
+    ChecksumMismatch {
+        expected: CompactString,
+        actual: CompactString,
+    },
+
+    /// Package has no checksum and policy requires it.
+    ///
+    /// - Code: `binstall::checksum::missing`
+    /// - Exit: 43
+    #[error("Package does not have a checksum")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::checksum::missing),
+        help("Remove --require-checksum to install packages without a published checksum.")
+    )]
+    MissingChecksum,
+
+    /// The sigstore bundle of the package failed verification, e.g. it is
+    /// not signed from the repository of the crate.
+    ///
+    /// - Code: `binstall::signature::attestation`
+    /// - Exit: 44
+    #[error("Failed to verify sigstore bundle: {0}")]
+    #[diagnostic(severity(error), code(binstall::signature::attestation))]
+    InvalidAttestation(CompactString),
+
     /// A URL is invalid.
     ///
     /// This may be the result of a template in a Cargo manifest.
@@ -200,10 +463,31 @@ pub enum BinstallError {
         leon::ParseError,
     ),
 
+    /// A url template of the crate metadata uses a variable or a formatter
+    /// which does not exist, or a variable which has no value for the
+    /// package.
+    ///
+    /// - Code: `binstall::template::unknown_key`
+    /// - Exit: 67
+    #[error("template `{template}` uses unknown {kind} `{name}`")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::template::unknown_key),
+        help("The {kind}s available are: {}", .available.join(", "))
+    )]
+    Template {
+        template: CompactString,
+        /// `variable` or `formatter`.
+        kind: &'static str,
+        name: CompactString,
+        /// Sorted.
+        available: Vec<&'static str>,
+    },
+
     /// Failed to fetch pre-built binaries.
     ///
     /// - Code: `binstall::fetch`
-    /// - Exit: 68
+    /// - Exit: 68, or 113 or 114 if it is a rate limiting or network failure
     #[error(transparent)]
     #[diagnostic(severity(error), code(binstall::fetch))]
     #[source_code(transparent)]
@@ -213,10 +497,10 @@ pub enum BinstallError {
     /// Failed to download or failed to decode the body.
     ///
     /// - Code: `binstall::download`
-    /// - Exit: 68
+    /// - Exit: 68, or 113 or 114 if it is a rate limiting or network failure
     #[error(transparent)]
     #[diagnostic(severity(error), code(binstall::download))]
-    Download(#[from] DownloadError),
+    Download(DownloadError),
 
     /// A subprocess failed.
     ///
@@ -252,7 +536,7 @@ pub enum BinstallError {
     /// This could either be a "not found" or a server/transport error.
     ///
     /// - Code: `binstall::cargo_registry`
-    /// - Exit: 76
+    /// - Exit: 76, or 42, 113 or 114 if it is a checksum mismatch, rate limiting or network failure
     #[error(transparent)]
     #[diagnostic(transparent)]
     RegistryError(#[from] Box<RegistryError>),
@@ -379,10 +663,212 @@ pub enum BinstallError {
     /// Fallback to `cargo-install` is disabled.
     ///
     /// - Code: `binstall::no_fallback_to_cargo_install`
-    /// - Exit: 94
-    #[error("Fallback to cargo-install is disabled")]
+    /// - Exit: 94, or 113 or 114 if a strategy failed because of rate limiting or the network
+    #[error(
+        "Fallback to cargo-install is disabled{}{}",
+        reasons.iter().format_with("", |reason, f| f(&format_args!("\n  - {reason}"))),
+        if disabled_strategies.is_empty() {
+            String::new()
+        } else {
+            format!("\n  disabled strategies: {}", disabled_strategies.iter().format(", "))
+        }
+    )]
     #[diagnostic(severity(error), code(binstall::no_fallback_to_cargo_install))]
-    NoFallbackToCargoInstall,
+    NoFallbackToCargoInstall {
+        /// Why no prebuilt binary is available.
+        reasons: Vec<StrategyFailure>,
+        /// Strategies disabled by the user or by the crate.
+        disabled_strategies: Vec<Strategy>,
+    },
+
+    /// Some artifact hosts are unavailable from the region or network of the
+    /// user, and no artifact could be fetched from the other ones.
+    ///
+    /// - Code: `binstall::fetch::geo_blocked`
+    /// - Exit: 106
+    #[error(
+        "artifact host unavailable from your region/network: {}{}",
+        geo_blocked.join(", "),
+        if missing.is_empty() {
+            String::new()
+        } else {
+            format!(" (artifact not found on {})", missing.join(", "))
+        }
+    )]
+    #[diagnostic(
+        severity(error),
+        code(binstall::fetch::geo_blocked),
+        help("Try another network, or route the requests through a proxy with `HTTPS_PROXY`.")
+    )]
+    ArtifactHostUnavailable {
+        /// Hosts which refused the requests because of the region or network.
+        geo_blocked: Vec<CompactString>,
+        /// Sources which do not have the artifact.
+        missing: Vec<CompactString>,
+    },
+
+    /// A url template of the crate metadata rendered to a url with a scheme
+    /// other than http(s).
+    ///
+    /// - Code: `binstall::url_template::scheme`
+    /// - Exit: 107
+    #[error("url template rendered to a url with unsupported scheme `{scheme}`")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::url_template::scheme),
+        help("Only https and http urls are allowed in pkg-url and signing.file.")
+    )]
+    UnsupportedUrlScheme { scheme: CompactString },
+
+    /// A url template of the crate metadata rendered to a url with
+    /// credentials.
+    ///
+    /// - Code: `binstall::url_template::userinfo`
+    /// - Exit: 108
+    #[error("url template rendered to a url with credentials: {url}")]
+    #[diagnostic(severity(error), code(binstall::url_template::userinfo))]
+    UrlWithCredentials {
+        /// The url without its credentials.
+        url: CompactString,
+    },
+
+    /// Something is not cached and cannot be downloaded with `--offline`.
+    ///
+    /// - Code: `binstall::offline`
+    /// - Exit: 109
+    #[error("{url} is not cached, and cannot be downloaded in offline mode")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::offline),
+        help("Run `cargo binstall --prefetch` without `--offline` to cache the crate first.")
+    )]
+    OfflineMissing { url: CompactString },
+
+    /// The package downloaded is not in the format expected, e.g. the host
+    /// served an HTML error or login page instead.
+    ///
+    /// - Code: `binstall::unexpected_format`
+    /// - Exit: 110
+    #[error("{url} is not a package: {err}")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::unexpected_format),
+        help("The host may have returned an error page, e.g. if the release is missing or requires signing in.")
+    )]
+    UnexpectedFormat {
+        url: CompactString,
+        err: Box<UnexpectedFormat>,
+    },
+
+    /// A url template of the crate metadata rendered to a plain http url,
+    /// without `--allow-insecure-http`.
+    ///
+    /// - Code: `binstall::url_template::insecure`
+    /// - Exit: 111
+    #[error("url template rendered to a url which is not https: {url}")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::url_template::insecure),
+        help("Pass --allow-insecure-http to download it anyway, e.g. from an internal mirror.")
+    )]
+    InsecureUrl { url: CompactString },
+
+    /// A binary is not where the bin-dir of the crate points to in the
+    /// package downloaded.
+    ///
+    /// - Code: `binstall::bins::missing`
+    /// - Exit: 112
+    #[error(
+        "bin file {} not found in the package, {}",
+        .path.display(),
+        MissingBinHint { found, bin_dir: bin_dir.as_deref() }
+    )]
+    #[diagnostic(
+        severity(error),
+        code(binstall::bins::missing),
+        help("Set bin-dir in [package.metadata.binstall] or pass --bin-dir to the path of the binaries in the package.")
+    )]
+    MissingBinFile {
+        /// The path of the binary in the package.
+        path: Box<Path>,
+        /// The executables found in the package instead.
+        found: Vec<CompactString>,
+        /// The bin-dir which would have found the binary, if any.
+        bin_dir: Option<CompactString>,
+    },
+
+    /// The crate is not resolved and downloaded before the `--deadline`.
+    ///
+    /// - Code: `binstall::deadline`
+    /// - Exit: 115
+    #[error("not resolved and downloaded within the deadline of {}s", .0.as_secs())]
+    #[diagnostic(
+        severity(error),
+        code(binstall::deadline),
+        help("A mirror or the network might be too slow, raise --deadline to wait longer.")
+    )]
+    DeadlineExceeded(Duration),
+
+    /// The installation has to be confirmed, but stdin is not a terminal
+    /// to ask for it.
+    ///
+    /// - Code: `binstall::confirm::not_interactive`
+    /// - Exit: 116
+    #[error("cannot ask for confirmation since stdin is not a terminal")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::confirm::not_interactive),
+        help("Pass --no-confirm to install without confirmation.")
+    )]
+    NotInteractive,
+
+    /// The package does not fit on the filesystem it is downloaded or
+    /// extracted to.
+    ///
+    /// - Code: `binstall::insufficient_space`
+    /// - Exit: 117
+    #[error(
+        "not enough space on the filesystem of '{}': {} needed, {} available",
+        path.display(),
+        crate::ops::resolve::format_size(*needed),
+        crate::ops::resolve::format_size(*available)
+    )]
+    #[diagnostic(
+        severity(error),
+        code(binstall::insufficient_space),
+        help("Free up some space, or point TMPDIR and --install-path to a filesystem with more of it.")
+    )]
+    InsufficientSpace {
+        needed: u64,
+        available: u64,
+        path: PathBuf,
+    },
+
+    /// The lockfile has no entry of the crate for any of the targets.
+    ///
+    /// - Code: `binstall::lockfile::missing_target`
+    /// - Exit: 118
+    #[error("the lockfile has no entry for any of the targets {targets}")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::lockfile::missing_target),
+        help("Write the lockfile on this target with --write-lockfile to add it, entries of the other targets are kept.")
+    )]
+    NoLockedTarget { targets: CompactString },
+
+    /// The artifact recorded in the lockfile cannot be found anymore.
+    ///
+    /// - Code: `binstall::lockfile::missing_artifact`
+    /// - Exit: 119
+    #[error("the artifact {url} recorded in the lockfile is not found")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::lockfile::missing_artifact),
+        help(
+            "The release might have been deleted, write the lockfile again with --write-lockfile."
+        )
+    )]
+    LockedArtifactMissing { url: CompactString },
 
     /// Fallback to `cargo-install` is disabled.
     ///
@@ -395,7 +881,7 @@ pub enum BinstallError {
     /// Request to GitHub API failed
     ///
     /// - Code: `binstall::gh_api_failure`
-    /// - Exit: 96
+    /// - Exit: 96, or 113 or 114 if it is a rate limiting or network failure
     #[error("Request to GitHub API failed: {0}")]
     #[diagnostic(severity(error), code(binstall::gh_api_failure))]
     GhApiErr(#[source] Box<GhApiError>),
@@ -417,6 +903,15 @@ pub enum BinstallError {
     #[diagnostic(severity(error), code(binstall::git))]
     GitError(#[from] crate::helpers::git::GitError),
 
+    /// Failed to check out `--branch`, `--tag` or `--rev` of a git repository
+    ///
+    /// - Code: `binstall::git`
+    /// - Exit: 98
+    #[cfg(feature = "git")]
+    #[error(transparent)]
+    #[diagnostic(severity(error), code(binstall::git))]
+    GitCheckout(#[from] crate::helpers::git::GitCheckoutError),
+
     /// Failed to load manifest from workspace
     ///
     /// - Code: `binstall::load_manifest_from_workspace`
@@ -425,33 +920,167 @@ pub enum BinstallError {
     #[diagnostic(severity(error), code(binstall::load_manifest_from_workspace))]
     LoadManifestFromWSError(#[from] Box<LoadManifestFromWSError>),
 
+    /// The install destination already contains a file that is tracked by
+    /// neither binstall nor cargo-install.
+    ///
+    /// - Code: `binstall::overwrite_untracked`
+    /// - Exit: 100
+    #[error("refusing to overwrite {} which is not installed by binstall or cargo-install", .0.display())]
+    #[diagnostic(
+        severity(error),
+        code(binstall::overwrite_untracked),
+        help("It might be owned by the system package manager or another tool, use --force to overwrite it anyway.")
+    )]
+    UntrackedDestination(Box<Path>),
+
+    /// The version found in the artifact differs from the resolved one and
+    /// `--strict-version-match` is specified.
+    ///
+    /// - Code: `binstall::version::mismatch`
+    /// - Exit: 120
+    #[error("artifact of version {resolved} contains version {found}")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::version::mismatch),
+        help("The release might have been retagged without being rebuilt.")
+    )]
+    VersionMismatch {
+        resolved: semver::Version,
+        found: CompactString,
+    },
+
+    /// The crate has no prebuilt artifact to save with `--prefetch`.
+    ///
+    /// - Code: `binstall::prefetch::no_artifact`
+    /// - Exit: 121
+    #[error("crate {0} has no prebuilt artifact to prefetch")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::prefetch::no_artifact),
+        help("Crates built from source are not cached, install them instead.")
+    )]
+    NothingToPrefetch(CompactString),
+
+    /// The binary specified with `crate:bin` or `--bin` is not provided by
+    /// the crate.
+    ///
+    /// - Code: `binstall::resolve::bin`
+    /// - Exit: 102
+    #[error(
+        "crate {crate_name} does not provide binary `{bin}`, only: {}",
+        available.join(", ")
+    )]
+    #[diagnostic(
+        severity(error),
+        code(binstall::resolve::bin),
+        help("Use `:{bin}` to search the registry for crates providing it.")
+    )]
+    NoSuchBin {
+        crate_name: CompactString,
+        bin: CompactString,
+        /// The binaries the crate provides.
+        available: Vec<CompactString>,
+    },
+
+    /// The crate is not found in the registry.
+    ///
+    /// - Code: `binstall::resolve::crate_not_found`
+    /// - Exit: 76
+    #[error("crate {name} is not found in the registry, nothing is installed")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::resolve::crate_not_found),
+        help("{}", suggestion.help(name))
+    )]
+    CrateNotFound {
+        name: CompactString,
+        suggestion: CrateSuggestion,
+    },
+
+    /// No crate in the registry provides the binary specified with `:bin`.
+    ///
+    /// - Code: `binstall::resolve::bin_not_found`
+    /// - Exit: 103
+    #[error("no crate providing binary `{0}` found in the registry, nothing is installed")]
+    #[diagnostic(
+        severity(error),
+        code(binstall::resolve::bin_not_found),
+        help("Specify the crate to install it from, e.g. `crate:{0}`.")
+    )]
+    BinNotFound(CompactString),
+
+    /// Several crates in the registry may provide the binary specified with
+    /// `:bin`.
+    ///
+    /// They are only suggested, since it is ambiguous which one to install,
+    /// or the registry does not tell which binaries they provide.
+    ///
+    /// - Code: `binstall::resolve::crates_providing_bin`
+    /// - Exit: 104
+    #[error(
+        "binary `{bin}` may be provided by: {}, nothing is installed",
+        candidates.join(", ")
+    )]
+    #[diagnostic(
+        severity(error),
+        code(binstall::resolve::crates_providing_bin),
+        help("Specify the crate to install it from, e.g. `{}:{bin}`.", candidates[0])
+    )]
+    CratesProvidingBin {
+        bin: CompactString,
+        candidates: Vec<CompactString>,
+    },
+
+    /// The requested version is in the denylist of the user.
+    ///
+    /// - Code: `binstall::resolve::denylisted`
+    /// - Exit: 105
+    #[error(
+        "version {version} is denylisted: {}",
+        reason.as_deref().unwrap_or("no reason recorded")
+    )]
+    #[diagnostic(
+        severity(error),
+        code(binstall::resolve::denylisted),
+        help("Request another version, or remove it from `binstall.denylist` in cargo config.")
+    )]
+    DenylistedVersion {
+        version: CompactString,
+        reason: Option<CompactString>,
+    },
+
     /// A wrapped error providing the context of which crate the error is about.
     #[error(transparent)]
     #[diagnostic(transparent)]
     CrateContext(Box<CrateContextError>),
 
-    /// A wrapped error for failures of multiple crates when `--continue-on-failure` is specified.
+    /// A wrapped error for failures of multiple crates unless `--fail-fast` is specified.
     #[error(transparent)]
     #[diagnostic(transparent)]
     Errors(CrateErrors),
 }
 
 impl BinstallError {
-    fn exit_number(&self) -> u8 {
+    /// The number of [`BinstallError::exit_code`], e.g. for
+    /// [`std::process::exit`].
+    pub fn exit_number(&self) -> u8 {
         use BinstallError::*;
         let code: u8 = match self {
             TaskJoinError(_) => 17,
             UserAbort => 32,
             InvalidSignature { .. } => 40,
             MissingSignature(_) => 41,
+            ChecksumMismatch { .. } => 42,
+            MissingChecksum => 43,
+            InvalidAttestation(_) => 44,
             UrlParse(_) => 65,
             TemplateParseError(..) => 67,
-            FetchError(..) => 68,
-            Download(_) => 68,
+            Template { .. } => 67,
+            FetchError(..) | Download(_) => self.request_exit_number(68),
             SubProcess { .. } => 70,
             Io(_) => 74,
             UnknownRegistryName(_) => 75,
-            RegistryError { .. } => 76,
+            RegistryError { .. } => self.request_exit_number(76),
             CargoManifestPath => 77,
             CargoManifest { .. } => 78,
             RegistryParseError(..) => 79,
@@ -462,23 +1091,109 @@ impl BinstallError {
             BinFile(_) => 88,
             CargoTomlMissingPackage(_) => 89,
             DuplicateSourceFilePath { .. } => 90,
-            NoFallbackToCargoInstall => 94,
+            NoFallbackToCargoInstall { .. } => self.request_exit_number(94),
             InvalidPkgFmt(..) => 95,
-            GhApiErr(..) => 96,
+            GhApiErr(..) => self.request_exit_number(96),
             TargetTripleParseError(..) => 97,
             #[cfg(feature = "git")]
             GitError(_) => 98,
+            #[cfg(feature = "git")]
+            GitCheckout(_) => 98,
             LoadManifestFromWSError(_) => 99,
+            UntrackedDestination(_) => 100,
+            NoSuchBin { .. } => 102,
+            CrateNotFound { .. } => 76,
+            BinNotFound(_) => 103,
+            CratesProvidingBin { .. } => 104,
+            DenylistedVersion { .. } => 105,
+            ArtifactHostUnavailable { .. } => 106,
+            UnsupportedUrlScheme { .. } => 107,
+            UrlWithCredentials { .. } => 108,
+            OfflineMissing { .. } => 109,
+            UnexpectedFormat { .. } => 110,
+            InsecureUrl { .. } => 111,
+            MissingBinFile { .. } => 112,
+            DeadlineExceeded(_) => 115,
+            NotInteractive => 116,
+            InsufficientSpace { .. } => 117,
+            NoLockedTarget { .. } => 118,
+            LockedArtifactMissing { .. } => 119,
+            VersionMismatch { .. } => 120,
+            NothingToPrefetch(_) => 121,
             CrateContext(context) => context.err.exit_number(),
             Errors(errors) => (errors.0)[0].err.exit_number(),
         };
 
-        // reserved codes
-        debug_assert!(code != 64 && code != 16 && code != 1 && code != 2 && code != 0);
+        // reserved codes, 101 being the one of a panic
+        debug_assert!(
+            code != 64 && code != 16 && code != 1 && code != 2 && code != 0 && code != 101
+        );
 
         code
     }
 
+    /// Errors caused by failed requests take the code of their category,
+    /// if it has one, rather than `default`.
+    fn request_exit_number(&self, default: u8) -> u8 {
+        match self.kind() {
+            ErrorKind::ChecksumMismatch => 42,
+            ErrorKind::RateLimited => 113,
+            ErrorKind::Network => 114,
+            _ => default,
+        }
+    }
+
+    /// The category of this error, see [`EXIT_CODES`].
+    pub fn kind(&self) -> ErrorKind {
+        use BinstallError::*;
+        match self {
+            UserAbort => ErrorKind::UserAbort,
+            ChecksumMismatch { .. } => ErrorKind::ChecksumMismatch,
+            OfflineMissing { .. } => ErrorKind::Offline,
+            UntrackedDestination(_) => ErrorKind::InstallConflict,
+            BinFile(bins::Error::Io(err)) if err.kind() == io::ErrorKind::AlreadyExists => {
+                ErrorKind::InstallConflict
+            }
+            ArtifactHostUnavailable { .. } | DeadlineExceeded(_) => ErrorKind::Network,
+            NothingToPrefetch(_) => ErrorKind::NoArtifact,
+            // The artifact might exist if a strategy failed because of
+            // the network, so retrying can help.
+            NoFallbackToCargoInstall { reasons, .. } => {
+                let has = |kind: fn(&StrategyFailureKind) -> bool| {
+                    reasons.iter().any(|reason| kind(&reason.kind))
+                };
+                if has(|kind| matches!(kind, StrategyFailureKind::RateLimited(_))) {
+                    ErrorKind::RateLimited
+                } else if has(|kind| {
+                    matches!(
+                        kind,
+                        StrategyFailureKind::Network(_) | StrategyFailureKind::GeoBlocked { .. }
+                    )
+                }) {
+                    ErrorKind::Network
+                } else {
+                    ErrorKind::NoArtifact
+                }
+            }
+            FetchError(err) => ErrorKind::of_request(err.is_rate_limited(), err.is_network()),
+            Download(DownloadError::Remote(err)) => {
+                ErrorKind::of_request(err.is_rate_limited(), err.is_network())
+            }
+            GhApiErr(err) => ErrorKind::of_request(err.is_rate_limited(), err.is_network()),
+            RegistryError(err) => match &**err {
+                registry::RegistryError::Remote(err)
+                | registry::RegistryError::Download(DownloadError::Remote(err)) => {
+                    ErrorKind::of_request(err.is_rate_limited(), err.is_network())
+                }
+                registry::RegistryError::UnmatchedChecksum { .. } => ErrorKind::ChecksumMismatch,
+                _ => ErrorKind::Other,
+            },
+            CrateContext(context) => context.err.kind(),
+            Errors(errors) => (errors.0)[0].err.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+
     /// The recommended exit code for this error.
     ///
     /// This will never output:
@@ -486,6 +1201,8 @@ impl BinstallError {
     /// - 1 and 2 (catchall and shell)
     /// - 16 (binstall errors not handled here)
     /// - 64 (generic error)
+    ///
+    /// All the codes are listed in [`EXIT_CODES`].
     pub fn exit_code(&self) -> ExitCode {
         self.exit_number().into()
     }
@@ -514,6 +1231,39 @@ impl BinstallError {
             Some(Self::Errors(CrateErrors(errors.into_boxed_slice())))
         }
     }
+
+    /// Split the error into the failure of each crate, with the name of
+    /// the crate if it is about one.
+    pub fn crate_failures(&self) -> Vec<(Option<&str>, &BinstallError)> {
+        match self {
+            Self::CrateContext(context) => vec![(Some(&context.crate_name), &context.err)],
+            Self::Errors(errors) => errors
+                .iter()
+                .map(|context| (Some(&*context.crate_name), &context.err))
+                .collect(),
+            err => vec![(None, err)],
+        }
+    }
+
+    /// Return the geo-block which caused this error, if any.
+    pub fn geo_blocked(&self) -> Option<&GeoBlocked> {
+        match self {
+            Self::FetchError(err) => err.geo_blocked(),
+            Self::Download(DownloadError::Remote(RemoteError::GeoBlocked(geo_blocked))) => {
+                Some(geo_blocked)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the url which could not be downloaded in offline mode, if
+    /// that caused this error.
+    pub fn offline_missing(&self) -> Option<&str> {
+        match self {
+            Self::OfflineMissing { url } => Some(url),
+            _ => None,
+        }
+    }
 }
 
 impl Termination for BinstallError {
@@ -563,6 +1313,27 @@ impl From<RemoteError> for BinstallError {
     }
 }
 
+impl From<DownloadError> for BinstallError {
+    fn from(e: DownloadError) -> Self {
+        match e {
+            DownloadError::Remote(RemoteError::Offline(url)) => BinstallError::OfflineMissing {
+                url: url.as_str().into(),
+            },
+            DownloadError::Remote(RemoteError::Cancelled) => BinstallError::UserAbort,
+            DownloadError::InsufficientSpace {
+                needed,
+                available,
+                path,
+            } => BinstallError::InsufficientSpace {
+                needed,
+                available,
+                path,
+            },
+            e => BinstallError::Download(e),
+        }
+    }
+}
+
 impl From<CargoTomlError> for BinstallError {
     fn from(e: CargoTomlError) -> Self {
         BinstallError::CargoManifest(Box::new(e))
@@ -589,7 +1360,20 @@ impl From<target_lexicon::ParseError> for BinstallError {
 
 impl From<RegistryError> for BinstallError {
     fn from(e: RegistryError) -> Self {
-        BinstallError::RegistryError(Box::new(e))
+        match e {
+            RegistryError::OfflineMissing { url, .. } => BinstallError::OfflineMissing { url },
+            RegistryError::Remote(RemoteError::Offline(url))
+            | RegistryError::Download(DownloadError::Remote(RemoteError::Offline(url))) => {
+                BinstallError::OfflineMissing {
+                    url: url.as_str().into(),
+                }
+            }
+            RegistryError::Remote(RemoteError::Cancelled)
+            | RegistryError::Download(DownloadError::Remote(RemoteError::Cancelled)) => {
+                BinstallError::UserAbort
+            }
+            e => BinstallError::RegistryError(Box::new(e)),
+        }
     }
 }
 
@@ -607,6 +1391,220 @@ impl From<LoadManifestFromWSError> for BinstallError {
 
 impl From<FetchError> for BinstallError {
     fn from(e: FetchError) -> Self {
-        BinstallError::FetchError(Box::new(e))
+        match e {
+            FetchError::UrlTemplate(UrlTemplateError::UnsupportedScheme { scheme }) => {
+                BinstallError::UnsupportedUrlScheme { scheme }
+            }
+            FetchError::UrlTemplate(UrlTemplateError::Userinfo { url }) => {
+                BinstallError::UrlWithCredentials { url }
+            }
+            FetchError::UrlTemplate(UrlTemplateError::Insecure { url }) => {
+                BinstallError::InsecureUrl { url }
+            }
+            FetchError::UrlTemplate(UrlTemplateError::UnknownKey {
+                template,
+                key,
+                available,
+            }) => BinstallError::Template {
+                template,
+                kind: "variable",
+                name: key,
+                available,
+            },
+            FetchError::UrlTemplate(UrlTemplateError::UnknownFormatter {
+                template,
+                formatter,
+                available,
+            }) => BinstallError::Template {
+                template,
+                kind: "formatter",
+                name: formatter,
+                available,
+            },
+            FetchError::ChecksumMismatch { expected, actual } => {
+                BinstallError::ChecksumMismatch { expected, actual }
+            }
+            FetchError::MissingChecksum => BinstallError::MissingChecksum,
+            FetchError::InvalidAttestation(reason) => BinstallError::InvalidAttestation(reason),
+            FetchError::Download(DownloadError::Remote(RemoteError::Offline(url))) => {
+                BinstallError::OfflineMissing {
+                    url: url.as_str().into(),
+                }
+            }
+            FetchError::Download(DownloadError::Remote(RemoteError::Cancelled)) => {
+                BinstallError::UserAbort
+            }
+            FetchError::Download(DownloadError::InsufficientSpace {
+                needed,
+                available,
+                path,
+            }) => BinstallError::InsufficientSpace {
+                needed,
+                available,
+                path,
+            },
+            FetchError::UnexpectedFormat { url, err } => {
+                BinstallError::UnexpectedFormat { url, err }
+            }
+            e => BinstallError::FetchError(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+
+    fn no_fallback(kinds: Vec<StrategyFailureKind>) -> BinstallError {
+        BinstallError::NoFallbackToCargoInstall {
+            reasons: kinds
+                .into_iter()
+                .map(|kind| StrategyFailure {
+                    strategy: "GhCrateMeta".into(),
+                    target: "x86_64-unknown-linux-gnu".into(),
+                    kind,
+                })
+                .collect(),
+            disabled_strategies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_crate_suggestion_help() {
+        assert_eq!(
+            CrateSuggestion::ProvidedBy("ripgrep").help("rg"),
+            "`rg` is a binary of the crate ripgrep, install it with `cargo binstall ripgrep`."
+        );
+
+        let similar = |name: &str, downloads| registry::SimilarCrate {
+            name: name.into(),
+            downloads,
+        };
+        assert_eq!(
+            CrateSuggestion::Similar(vec![
+                similar("ripgrep", 52_345_678),
+                similar("ripgrip", 120),
+                similar("ripgrep_all", 91_500),
+            ])
+            .help("ripgrap"),
+            "Did you mean ripgrep (52.3M downloads), or ripgrip (120 downloads), \
+or ripgrep_all (91.5k downloads)?"
+        );
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let codes: Vec<u8> = EXIT_CODES.iter().map(|(code, ..)| *code).collect();
+        assert!(codes.windows(2).all(|pair| pair[0] < pair[1]), "{codes:?}");
+        assert!(!codes.contains(&101), "101 is the exit code of a panic");
+
+        let errors = [
+            (BinstallError::UserAbort, ErrorKind::UserAbort),
+            (
+                BinstallError::ChecksumMismatch {
+                    expected: "a".into(),
+                    actual: "b".into(),
+                },
+                ErrorKind::ChecksumMismatch,
+            ),
+            (
+                BinstallError::OfflineMissing { url: "url".into() },
+                ErrorKind::Offline,
+            ),
+            (
+                BinstallError::UntrackedDestination(Path::new("/bin/tool").into()),
+                ErrorKind::InstallConflict,
+            ),
+            (
+                no_fallback(vec![
+                    StrategyFailureKind::NotFound,
+                    StrategyFailureKind::NoBinaries,
+                ]),
+                ErrorKind::NoArtifact,
+            ),
+            (
+                no_fallback(vec![
+                    StrategyFailureKind::NotFound,
+                    StrategyFailureKind::Network("timed out".into()),
+                ]),
+                ErrorKind::Network,
+            ),
+            (
+                no_fallback(vec![
+                    StrategyFailureKind::Network("timed out".into()),
+                    StrategyFailureKind::RateLimited("429".into()),
+                ]),
+                ErrorKind::RateLimited,
+            ),
+            (
+                GhApiError::RateLimit {
+                    retry_after: Instant::now(),
+                }
+                .into(),
+                ErrorKind::RateLimited,
+            ),
+            (
+                BinstallError::from(GhApiError::BudgetExhausted).crate_context("tool"),
+                ErrorKind::RateLimited,
+            ),
+            (GhApiError::MalformedResponse.into(), ErrorKind::Other),
+            (BinstallError::MissingChecksum, ErrorKind::Other),
+            (
+                BinstallError::DeadlineExceeded(Duration::from_secs(60)),
+                ErrorKind::Network,
+            ),
+            (BinstallError::NotInteractive, ErrorKind::Other),
+            (
+                BinstallError::InsufficientSpace {
+                    needed: 300_000_000,
+                    available: 1_000_000,
+                    path: PathBuf::from("/tmp"),
+                },
+                ErrorKind::Other,
+            ),
+            (
+                BinstallError::NoLockedTarget {
+                    targets: "aarch64-apple-darwin".into(),
+                },
+                ErrorKind::Other,
+            ),
+            (
+                BinstallError::LockedArtifactMissing {
+                    url: "https://example.com/tool.tgz".into(),
+                },
+                ErrorKind::Other,
+            ),
+            (
+                BinstallError::CrateNotFound {
+                    name: "rg".into(),
+                    suggestion: CrateSuggestion::ProvidedBy("ripgrep"),
+                },
+                ErrorKind::Other,
+            ),
+            (
+                BinstallError::VersionMismatch {
+                    resolved: semver::Version::new(1, 0, 0),
+                    found: "0.9.0".into(),
+                },
+                ErrorKind::Other,
+            ),
+            (
+                BinstallError::NothingToPrefetch("cargo-binstall".into()),
+                ErrorKind::NoArtifact,
+            ),
+        ];
+
+        for (err, kind) in errors {
+            assert_eq!(err.kind(), kind, "{err}");
+
+            let code = err.exit_number();
+            let (_, table_kind, _) = EXIT_CODES
+                .iter()
+                .find(|(table_code, ..)| *table_code == code)
+                .unwrap_or_else(|| panic!("exit code {code} of {err} is not listed"));
+            assert_eq!(*table_kind, kind, "{err}");
+        }
     }
 }
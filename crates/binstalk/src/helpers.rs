@@ -3,12 +3,15 @@ pub mod remote;
 pub(crate) mod target_triple;
 pub mod tasks;
 
-pub(crate) use binstalk_downloader::download;
+pub use binstalk_bins::remove_old_files;
+pub use binstalk_downloader::download;
 pub use binstalk_downloader::gh_api_client;
+pub use binstalk_downloader::gitea_api_client;
+pub use binstalk_downloader::gl_api_client;
 
 pub(crate) use cargo_toml_workspace::{self, cargo_toml};
 #[cfg(feature = "git")]
-pub(crate) use simple_git as git;
+pub mod git;
 
 pub(crate) fn is_universal_macos(target: &str) -> bool {
     ["universal-apple-darwin", "universal2-apple-darwin"].contains(&target)
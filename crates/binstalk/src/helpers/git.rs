@@ -0,0 +1,236 @@
+use std::{
+    error::Error,
+    fmt, fs,
+    num::NonZeroU32,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+};
+
+use compact_str::CompactString;
+use gix::{clone::PrepareFetch, create, open, remote, ObjectId};
+use thiserror::Error as ThisError;
+use tracing::debug;
+
+pub use simple_git::*;
+
+/// Which commit of a git repository to check out, like the `--branch`,
+/// `--tag` and `--rev` options of `cargo install --git`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum GitReference {
+    Branch(CompactString),
+    Tag(CompactString),
+    /// A commit hash, or any revision `git rev-parse` understands.
+    Rev(CompactString),
+}
+
+impl fmt::Display for GitReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Branch(branch) => write!(f, "branch {branch}"),
+            Self::Tag(tag) => write!(f, "tag {tag}"),
+            Self::Rev(rev) => write!(f, "rev {rev}"),
+        }
+    }
+}
+
+/// Set the flag passed to [`checkout_reference`] on drop, to interrupt it.
+#[derive(Debug)]
+pub struct InterruptOnDrop(pub Arc<AtomicBool>);
+
+impl Drop for InterruptOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Relaxed);
+    }
+}
+
+#[derive(Debug, ThisError)]
+#[error("Failed to check out {reference} of {url}: {source}")]
+pub struct GitCheckoutError {
+    url: CompactString,
+    reference: GitReference,
+    #[source]
+    source: Box<dyn Error + Send + Sync>,
+}
+
+/// Clone `reference` of the repository at `url`, and write the files of its
+/// commit to `worktree`.
+///
+/// Branches and tags are cloned shallowly, revisions need the whole history
+/// since they cannot be fetched by themselves.
+///
+/// WARNING: This is a blocking operation.
+pub fn checkout_reference(
+    url: &GitUrl,
+    reference: &GitReference,
+    dir: &Path,
+    worktree: &Path,
+    should_interrupt: &AtomicBool,
+) -> Result<(), GitCheckoutError> {
+    checkout_reference_inner(url, reference, dir, worktree, should_interrupt).map_err(|source| {
+        GitCheckoutError {
+            url: url.to_string().into(),
+            reference: reference.clone(),
+            source,
+        }
+    })
+}
+
+fn checkout_reference_inner(
+    url: &GitUrl,
+    reference: &GitReference,
+    dir: &Path,
+    worktree: &Path,
+    should_interrupt: &AtomicBool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    debug!("Cloning {reference} of {url} to {}", dir.display());
+
+    let (refspecs, local_ref) = match reference {
+        GitReference::Branch(branch) => (
+            vec![format!("+refs/heads/{branch}:refs/remotes/origin/{branch}")],
+            Some(format!("refs/remotes/origin/{branch}")),
+        ),
+        GitReference::Tag(tag) => (
+            vec![format!("+refs/tags/{tag}:refs/tags/{tag}")],
+            Some(format!("refs/tags/{tag}")),
+        ),
+        GitReference::Rev(_) => (
+            vec![
+                "+refs/heads/*:refs/remotes/origin/*".to_string(),
+                "+refs/tags/*:refs/tags/*".to_string(),
+            ],
+            None,
+        ),
+    };
+
+    let mut prepare = PrepareFetch::new(
+        gix::url::parse(url.to_string().as_str().into())?,
+        dir,
+        create::Kind::Bare,
+        create::Options {
+            destination_must_be_empty: true,
+            ..Default::default()
+        },
+        open::Options::isolated(),
+    )?
+    .configure_remote(move |remote| {
+        Ok(remote.with_refspecs(
+            refspecs.iter().map(String::as_str),
+            remote::Direction::Fetch,
+        )?)
+    });
+    if local_ref.is_some() {
+        prepare = prepare.with_shallow(remote::fetch::Shallow::DepthAtRemote(
+            NonZeroU32::new(1).unwrap(),
+        ));
+    }
+
+    let (repo, _) = prepare.fetch_only(gix::progress::Discard, should_interrupt)?;
+
+    let commit: ObjectId = match (local_ref, reference) {
+        (Some(local_ref), _) => repo
+            .find_reference(local_ref.as_str())?
+            .peel_to_id_in_place()?
+            .detach(),
+        (None, GitReference::Rev(rev)) => repo.rev_parse_single(rev.as_str())?.detach(),
+        (None, _) => unreachable!("only revisions have no local reference"),
+    };
+    debug!("Checking out {commit} to {}", worktree.display());
+
+    let tree = repo.find_object(commit)?.peel_to_tree()?;
+    for entry in tree.traverse().breadthfirst.files()? {
+        let path = worktree.join(gix::path::try_from_bstr(entry.filepath.as_ref())?);
+
+        if entry.mode.is_tree() {
+            fs::create_dir_all(&path)?;
+        } else if entry.mode.is_blob() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, &repo.find_object(entry.oid)?.data)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
+
+    #[test]
+    fn test_checkout_reference() {
+        let root = tempfile::tempdir().unwrap();
+        let origin = root.path().join("origin");
+        fs::create_dir_all(origin.join("src")).unwrap();
+
+        git(&origin, &["init", "-q", "-b", "main"]);
+        fs::write(origin.join("src/main.rs"), "v1").unwrap();
+        git(&origin, &["add", "."]);
+        git(&origin, &["commit", "-q", "-m", "v1"]);
+        git(&origin, &["tag", "v1"]);
+        git(&origin, &["branch", "stable"]);
+        fs::write(origin.join("src/main.rs"), "v2").unwrap();
+        git(&origin, &["commit", "-q", "-am", "v2"]);
+
+        let output = Command::new("git")
+            .args(["rev-parse", "HEAD~1"])
+            .current_dir(&origin)
+            .output()
+            .unwrap();
+        let v1_rev = String::from_utf8(output.stdout).unwrap();
+
+        let url: GitUrl = format!("file://{}", origin.display()).parse().unwrap();
+        let checkout = |name: &str, reference: GitReference| {
+            let worktree = root.path().join(name);
+            checkout_reference(
+                &url,
+                &reference,
+                &root.path().join(format!("{name}.git")),
+                &worktree,
+                &AtomicBool::new(false),
+            )
+            .unwrap();
+            fs::read_to_string(worktree.join("src/main.rs")).unwrap()
+        };
+
+        assert_eq!(
+            checkout("branch", GitReference::Branch("main".into())),
+            "v2"
+        );
+        assert_eq!(
+            checkout("stable", GitReference::Branch("stable".into())),
+            "v1"
+        );
+        assert_eq!(checkout("tag", GitReference::Tag("v1".into())), "v1");
+        assert_eq!(
+            checkout("rev", GitReference::Rev(v1_rev.trim().into())),
+            "v1"
+        );
+        assert_eq!(checkout("rev-tag", GitReference::Rev("v1".into())), "v1");
+
+        let err = checkout_reference(
+            &url,
+            &GitReference::Tag("v3".into()),
+            &root.path().join("missing.git"),
+            &root.path().join("missing"),
+            &AtomicBool::new(false),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("tag v3"), "{err}");
+    }
+}
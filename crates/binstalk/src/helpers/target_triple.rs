@@ -1,7 +1,7 @@
 use std::{borrow::Cow, str::FromStr};
 
 use compact_str::{CompactString, ToCompactString};
-use target_lexicon::Triple;
+use target_lexicon::{Environment, Triple};
 
 use crate::{errors::BinstallError, helpers::is_universal_macos};
 
@@ -10,6 +10,8 @@ pub struct TargetTriple {
     pub target_family: Cow<'static, str>,
     pub target_arch: Cow<'static, str>,
     pub target_libc: Cow<'static, str>,
+    /// Same as `target_libc`, but empty if the target has no environment.
+    pub target_env: Cow<'static, str>,
     pub target_vendor: CompactString,
 }
 
@@ -32,6 +34,10 @@ impl FromStr for TargetTriple {
             } else {
                 triple.architecture.into_str()
             },
+            target_env: match triple.environment {
+                Environment::Unknown => Cow::Borrowed(""),
+                environment => environment.into_str(),
+            },
             target_libc: triple.environment.into_str(),
             target_vendor: triple.vendor.to_compact_string(),
         })
@@ -41,12 +47,77 @@ impl FromStr for TargetTriple {
 impl leon::Values for TargetTriple {
     fn get_value<'s>(&'s self, key: &str) -> Option<Cow<'s, str>> {
         match key {
-            "target-family" => Some(Cow::Borrowed(&self.target_family)),
+            "target-family" | "target-os" => Some(Cow::Borrowed(&self.target_family)),
             "target-arch" => Some(Cow::Borrowed(&self.target_arch)),
             "target-libc" => Some(Cow::Borrowed(&self.target_libc)),
+            "target-env" => Some(Cow::Borrowed(&self.target_env)),
             "target-vendor" => Some(Cow::Borrowed(&self.target_vendor)),
 
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use leon::Values;
+
+    #[test]
+    fn test_target_components() {
+        for (target, family, arch, libc, env, vendor) in [
+            (
+                "x86_64-unknown-linux-gnu",
+                "linux",
+                "x86_64",
+                "gnu",
+                "gnu",
+                "unknown",
+            ),
+            (
+                "x86_64-unknown-linux-musl",
+                "linux",
+                "x86_64",
+                "musl",
+                "musl",
+                "unknown",
+            ),
+            (
+                "x86_64-pc-windows-msvc",
+                "windows",
+                "x86_64",
+                "msvc",
+                "msvc",
+                "pc",
+            ),
+            (
+                "aarch64-apple-darwin",
+                "darwin",
+                "aarch64",
+                "unknown",
+                "",
+                "apple",
+            ),
+            (
+                "universal2-apple-darwin",
+                "darwin",
+                "universal",
+                "unknown",
+                "",
+                "apple",
+            ),
+            ("wasm32-wasi", "wasi", "wasm32", "unknown", "", "unknown"),
+        ] {
+            let triple = TargetTriple::from_str(target).unwrap();
+            let value = |key| triple.get_value(key).unwrap().into_owned();
+
+            assert_eq!(value("target-family"), family, "{target}");
+            assert_eq!(value("target-os"), family, "{target}");
+            assert_eq!(value("target-arch"), arch, "{target}");
+            assert_eq!(value("target-libc"), libc, "{target}");
+            assert_eq!(value("target-env"), env, "{target}");
+            assert_eq!(value("target-vendor"), vendor, "{target}");
+            assert_eq!(triple.get_value("target"), None);
+        }
+    }
+}
@@ -1,32 +1,144 @@
 //! Concrete Binstall operations.
 
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    env,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use compact_str::CompactString;
+use maybe_owned::MaybeOwned;
 use semver::VersionReq;
+use url::Url;
 
 use crate::{
-    fetchers::{Data, Fetcher, SignaturePolicy, TargetDataErased},
+    errors::BinstallError,
+    fetchers::{
+        ArtifactCache, ChecksumPolicy, Data, Fetcher, SignaturePolicy, TargetDataErased,
+        UrlRewrites,
+    },
     helpers::{
-        self, gh_api_client::GhApiClient, jobserver_client::LazyJobserverClient, remote::Client,
+        self,
+        gh_api_client::GhApiClient,
+        gitea_api_client::GiteaApiClient,
+        gl_api_client::GlApiClient,
+        jobserver_client::LazyJobserverClient,
+        remote::{CancellationToken, Client},
+    },
+    manifests::{
+        cargo_toml_binstall::{PkgOverride, Strategy},
+        crate_info::{CrateSource, SourceType},
+    },
+    ops::{
+        install_path::InstallPathTemplate,
+        progress::{Progress, ProgressSink},
+        resolve::Denylist,
+        run_report::RunReporter,
+        stats::Stats,
+        usage_report::{Usage, UsageReporter},
     },
-    manifests::cargo_toml_binstall::PkgOverride,
     registry::Registry,
     DesiredTargets,
 };
 
+pub mod install_path;
+pub mod outdated;
+pub mod prefetch;
+pub mod progress;
+pub mod repair;
 pub mod resolve;
+pub mod run_report;
+pub mod stats;
+pub mod uninstall;
+pub mod usage_report;
 
-pub type Resolver =
-    fn(Client, GhApiClient, Arc<Data>, Arc<TargetDataErased>, SignaturePolicy) -> Arc<dyn Fetcher>;
+pub use crate::bins::Shell;
+
+pub type Resolver = fn(
+    Client,
+    GhApiClient,
+    GlApiClient,
+    GiteaApiClient,
+    Arc<Data>,
+    Arc<TargetDataErased>,
+    SignaturePolicy,
+    ChecksumPolicy,
+    Option<ArtifactCache>,
+) -> Arc<dyn Fetcher>;
 
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum CargoTomlFetchOverride {
+    /// Clone the repository at the reference, or at its default branch.
     #[cfg(feature = "git")]
-    Git(helpers::git::GitUrl),
+    Git(helpers::git::GitUrl, Option<helpers::git::GitReference>),
     Path(PathBuf),
 }
 
+impl CargoTomlFetchOverride {
+    /// Where the crates are installed from, recorded in the manifests so
+    /// that they are not mistaken for crates from the registry, `None` if
+    /// it cannot be expressed as an url.
+    pub fn crate_source(&self) -> Option<CrateSource> {
+        match self {
+            #[cfg(feature = "git")]
+            Self::Git(git_url, reference) => {
+                use helpers::git::GitReference::*;
+
+                let mut url = Url::parse(&git_url.to_string()).ok()?;
+                let query = match reference {
+                    Some(Branch(branch)) => Some(("branch", branch)),
+                    Some(Tag(tag)) => Some(("tag", tag)),
+                    Some(Rev(rev)) => Some(("rev", rev)),
+                    None => None,
+                };
+                if let Some((key, value)) = query {
+                    url.query_pairs_mut().append_pair(key, value);
+                }
+
+                Some(CrateSource {
+                    source_type: SourceType::Git,
+                    url: MaybeOwned::Owned(url),
+                })
+            }
+            Self::Path(path) => {
+                let path = if path.is_relative() {
+                    env::current_dir().ok()?.join(path)
+                } else {
+                    path.clone()
+                };
+                let dir = if path.is_file() {
+                    path.parent()?.to_owned()
+                } else {
+                    path
+                };
+
+                Some(CrateSource {
+                    source_type: SourceType::Path,
+                    url: MaybeOwned::Owned(Url::from_directory_path(dir).ok()?),
+                })
+            }
+        }
+    }
+}
+
+/// What to do when the version found in the artifact differs from the
+/// resolved one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VersionMatchPolicy {
+    /// Don't look for the version in the artifact.
+    Ignore,
+
+    /// Warn on mismatch.
+    Warn,
+
+    /// Fail on mismatch.
+    Strict,
+}
+
 #[derive(Debug)]
 pub struct Options {
     pub no_symlinks: bool,
@@ -36,22 +148,142 @@ pub struct Options {
     pub locked: bool,
     pub no_track: bool,
 
+    /// Binaries recorded by binstall or cargo-install, used to refuse to
+    /// overwrite files installed by something else unless `force` is set.
+    ///
+    /// `None` if there is no record to check against.
+    pub tracked_bins: Option<BTreeSet<CompactString>>,
+    /// Ask the system package manager who owns an untracked file before
+    /// refusing to overwrite it.
+    pub query_package_owner: bool,
+
+    /// Shells to install the completions shipped in the package for, empty
+    /// to not install them.
+    pub completion_shells: Vec<Shell>,
+    /// Install man pages shipped in the package.
+    pub install_man: bool,
+    /// Directory to install completions and man pages into.
+    ///
+    /// `None` disables installing them.
+    pub extra_files_dir: Option<PathBuf>,
+    /// Completions and man pages recorded by binstall, which are allowed
+    /// to be overwritten, with the name of the crate they are recorded for.
+    pub tracked_extra_files: Option<BTreeMap<PathBuf, CompactString>>,
+
     pub version_req: Option<VersionReq>,
+    /// Only resolve to the newest version matching the requirement, instead
+    /// of the newest one with an artifact.
+    pub strict: bool,
+    /// Versions known to be broken, skipped when resolving.
+    pub denylist: Denylist,
     pub cargo_toml_fetch_override: Option<CargoTomlFetchOverride>,
     pub cli_overrides: PkgOverride,
 
     pub desired_targets: DesiredTargets,
     pub resolvers: Vec<Resolver>,
     pub cargo_install_fallback: bool,
+    /// Strategies disabled by the user, already left out of `resolvers` and
+    /// `cargo_install_fallback`, listed when no artifact is found.
+    pub disabled_strategies: Vec<Strategy>,
 
     pub temp_dir: PathBuf,
+    /// Where the binaries are installed, the base of
+    /// `install_path_template` if there is one.
     pub install_path: PathBuf,
+    /// `--install-path` with variables in it, see
+    /// [`Options::crate_install_path`].
+    pub install_path_template: Option<InstallPathTemplate>,
+    /// Link the installed binaries into this directory too, e.g. when they
+    /// are installed into a versioned `install_path_template`.
+    pub link_dir: Option<PathBuf>,
+    /// Save the artifacts into this directory instead of installing them.
+    pub download_only: Option<PathBuf>,
     pub cargo_root: Option<PathBuf>,
+    /// Cache of the downloaded packages, if any.
+    pub artifact_cache: Option<ArtifactCache>,
 
     pub client: Client,
     pub gh_api_client: GhApiClient,
+    pub gl_api_client: GlApiClient,
+    pub gitea_api_client: GiteaApiClient,
     pub jobserver_client: LazyJobserverClient,
     pub registry: Registry,
+    /// Cancelled when the user asks to stop, e.g. with ctrl_c, the crates
+    /// being resolved or installed then fail with [`BinstallError::UserAbort`].
+    ///
+    /// It should also be set on [`Options::client`] to stop its downloads.
+    pub cancellation_token: CancellationToken,
 
     pub signature_policy: SignaturePolicy,
+    pub checksum_policy: ChecksumPolicy,
+    pub version_match_policy: VersionMatchPolicy,
+    /// Allow downloading packages from plain http urls.
+    pub allow_insecure_http: bool,
+    /// Rules rewriting where the packages are downloaded from.
+    pub url_rewrites: UrlRewrites,
+
+    /// Receiver of the progress of each crate, if any.
+    pub progress_sink: Option<Arc<dyn ProgressSink>>,
+    /// Counters of this run, see [`stats::RunStats::collect`].
+    pub stats: Stats,
+    /// Collects the report of this run, if requested.
+    pub run_reporter: Option<RunReporter>,
+
+    /// Receiver of the crates installed, if any.
+    pub usage_reporter: Option<Arc<dyn UsageReporter>>,
+    /// Never report anything, neither to `usage_reporter` nor to the
+    /// upstream of the fetchers, e.g. quickinstall.
+    pub disable_telemetry: bool,
+}
+
+impl Options {
+    /// Return the directory the binaries of the crate are installed in.
+    pub fn crate_install_path(
+        &self,
+        name: &str,
+        version: &str,
+        target: &str,
+        binary_ext: &str,
+    ) -> Cow<'_, Path> {
+        match &self.install_path_template {
+            Some(template) => Cow::Owned(template.render(name, version, target, binary_ext)),
+            None => Cow::Borrowed(&self.install_path),
+        }
+    }
+
+    /// Run `fut`, dropping it and failing with [`BinstallError::UserAbort`]
+    /// as soon as [`Options::cancellation_token`] is cancelled.
+    pub(crate) async fn cancellable<T>(
+        &self,
+        fut: impl Future<Output = Result<T, BinstallError>>,
+    ) -> Result<T, BinstallError> {
+        tokio::select! {
+            biased;
+
+            () = self.cancellation_token.cancelled() => Err(BinstallError::UserAbort),
+            res = fut => res,
+        }
+    }
+
+    pub(crate) fn report_progress(&self, crate_name: &str, progress: Progress<'_>) {
+        if let Some(progress_sink) = &self.progress_sink {
+            progress_sink.report(crate_name, progress);
+        }
+    }
+
+    pub(crate) fn report_installed(&self, crate_name: &str) {
+        if let Some(run_reporter) = &self.run_reporter {
+            run_reporter.set_installed(crate_name);
+        }
+    }
+
+    pub(crate) fn report_usage(&self, usage: Usage) {
+        if self.disable_telemetry {
+            return;
+        }
+
+        if let Some(usage_reporter) = &self.usage_reporter {
+            usage_reporter.report(usage);
+        }
+    }
 }
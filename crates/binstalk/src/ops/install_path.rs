@@ -0,0 +1,178 @@
+//! `--install-path` with variables in it, e.g. `/opt/tools/{name}/{version}/bin`,
+//! to install each crate into a directory of its own.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+
+use compact_str::CompactString;
+use leon::Template;
+
+use crate::{
+    errors::BinstallError,
+    manifests::{cargo_toml_binstall::default_binary_ext, crate_info::CrateInfo},
+};
+
+/// Variables available to the install path, sorted.
+const KEYS: &[&str] = &["binary-ext", "name", "target", "version"];
+
+/// An install path with variables in it, rendered for each crate.
+#[derive(Clone, Debug)]
+pub struct InstallPathTemplate {
+    /// The directories before the first one with a variable in it.
+    base: PathBuf,
+    /// The rest of the path, each component of which is a template if it
+    /// is valid UTF-8.
+    ///
+    /// The path is templated by component so that `\` separators on
+    /// windows are not taken for escapes.
+    components: Vec<OsString>,
+}
+
+impl InstallPathTemplate {
+    /// Return `None` if `path` has no variable in it.
+    ///
+    /// Fails if the path uses an unknown variable, so that it is reported
+    /// before anything is installed.
+    pub fn new(path: &Path) -> Result<Option<Self>, BinstallError> {
+        let components: Vec<_> = path.components().collect();
+
+        let Some(pos) = components
+            .iter()
+            .position(|component| as_template(component.as_os_str()).is_some())
+        else {
+            return Ok(None);
+        };
+
+        for source in components[pos..]
+            .iter()
+            .filter_map(|component| as_template(component.as_os_str()))
+        {
+            let template = Template::parse(source)?;
+            let unknown_key = template
+                .keys()
+                .find(|key| !KEYS.contains(key))
+                .map(|key| CompactString::from(*key));
+            if let Some(key) = unknown_key {
+                return Err(BinstallError::Template {
+                    template: path.to_string_lossy().into(),
+                    kind: "variable",
+                    name: key,
+                    available: KEYS.to_vec(),
+                });
+            }
+        }
+
+        let base: PathBuf = components[..pos].iter().collect();
+
+        Ok(Some(Self {
+            base: if base.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                base
+            },
+            components: components[pos..]
+                .iter()
+                .map(|component| component.as_os_str().to_owned())
+                .collect(),
+        }))
+    }
+
+    /// The directory the binaries of every crate are installed under, `.`
+    /// if the path starts with a variable.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Return the directory the binaries of the crate are installed in.
+    pub fn render(&self, name: &str, version: &str, target: &str, binary_ext: &str) -> PathBuf {
+        let values = leon::vals(|key| {
+            let value = match key {
+                "name" => name,
+                "version" => version,
+                "target" => target,
+                "binary-ext" => binary_ext,
+                _ => return None,
+            };
+            Some(Cow::Owned(value.to_owned()))
+        });
+
+        let mut path = self.base.clone();
+        for component in &self.components {
+            match as_template(component) {
+                Some(source) => {
+                    let template = Template::parse(source)
+                        .expect("the template is checked in InstallPathTemplate::new");
+                    path.push(
+                        template
+                            .render(&values)
+                            .expect("the keys are checked in InstallPathTemplate::new"),
+                    );
+                }
+                None => path.push(component),
+            }
+        }
+        path
+    }
+
+    /// Return the directory the binaries of an installed crate are in.
+    pub fn render_for(&self, crate_info: &CrateInfo) -> PathBuf {
+        self.render(
+            &crate_info.name,
+            &crate_info.current_version.to_string(),
+            &crate_info.target,
+            default_binary_ext(&crate_info.target),
+        )
+    }
+}
+
+/// Return the component as a template if it has a variable in it.
+fn as_template(component: &OsStr) -> Option<&str> {
+    component.to_str().filter(|source| source.contains('{'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_variable() {
+        assert!(InstallPathTemplate::new(Path::new("/opt/tools/bin"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn render() {
+        let template = InstallPathTemplate::new(Path::new("/opt/tools/{name}/{ version }/bin"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(template.base(), Path::new("/opt/tools"));
+        assert_eq!(
+            template.render("cargo-binstall", "1.2.3", "x86_64-unknown-linux-gnu", ""),
+            Path::new("/opt/tools/cargo-binstall/1.2.3/bin"),
+        );
+
+        let template = InstallPathTemplate::new(Path::new("tools/{name}-{target}"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            template.render("cargo-binstall", "1.2.3", "x86_64-pc-windows-msvc", ".exe"),
+            Path::new("tools/cargo-binstall-x86_64-pc-windows-msvc"),
+        );
+    }
+
+    #[test]
+    fn unknown_variable() {
+        let err = InstallPathTemplate::new(Path::new("/opt/{nmae}/bin")).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                BinstallError::Template { kind: "variable", name, .. } if *name == "nmae"
+            ),
+            "{err}"
+        );
+    }
+}
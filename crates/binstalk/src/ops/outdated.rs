@@ -0,0 +1,147 @@
+//! Find the installed crates which are behind the latest version in the
+//! registry, for `--upgrade` and `--outdated`.
+
+use std::sync::Arc;
+
+use compact_str::CompactString;
+use semver::{Op, Version, VersionReq};
+
+use crate::{
+    errors::BinstallError,
+    manifests::crate_info::{CrateInfo, CrateSource},
+    ops::{
+        resolve::{resolve, CrateName, Resolution},
+        Options,
+    },
+};
+
+/// An installed crate which is behind the latest version.
+#[derive(Clone, Debug)]
+pub struct OutdatedCrate {
+    pub name: CompactString,
+    pub installed: Version,
+    /// The latest version with a prebuilt artifact, or the latest version
+    /// if it is built from source.
+    pub latest: Version,
+    /// False if no prebuilt artifact is found and it is built from source.
+    pub prebuilt: bool,
+    pub source: CrateSource,
+}
+
+/// Return true if the crate is installed with an exact version, e.g.
+/// `--version 1.2.3`, these are only upgraded if asked to.
+pub fn is_pinned(crate_info: &CrateInfo) -> bool {
+    crate_info
+        .version_req
+        .parse::<VersionReq>()
+        .map_or(false, |version_req| {
+            matches!(
+                &version_req.comparators[..],
+                [comparator] if comparator.op == Op::Exact
+                    && comparator.minor.is_some()
+                    && comparator.patch.is_some()
+            )
+        })
+}
+
+/// The versions the crate can be upgraded to: the ones matching the version
+/// requirement it is installed with, or any version if it is pinned.
+pub fn upgrade_version_req(crate_info: &CrateInfo) -> VersionReq {
+    if is_pinned(crate_info) {
+        VersionReq::STAR
+    } else {
+        crate_info.version_req.parse().unwrap_or(VersionReq::STAR)
+    }
+}
+
+/// Return the latest version of the crate in the index if it is newer than
+/// the installed one.
+///
+/// Only the index is queried, the crate is not resolved.
+pub async fn newer_version(
+    opts: &Options,
+    crate_info: &CrateInfo,
+) -> Result<Option<Version>, BinstallError> {
+    let latest = opts
+        .registry
+        .find_latest_version(
+            opts.client.clone(),
+            &crate_info.name,
+            &upgrade_version_req(crate_info),
+        )
+        .await
+        .map_err(|err| BinstallError::from(err).crate_context(&*crate_info.name))?;
+
+    Ok((latest > crate_info.current_version).then_some(latest))
+}
+
+/// Check whether the crate is behind the latest version.
+///
+/// Crates with a newer version in the index are resolved to confirm an
+/// artifact exists, the others are not.
+pub async fn check(
+    opts: Arc<Options>,
+    crate_info: CrateInfo,
+) -> Result<Option<OutdatedCrate>, BinstallError> {
+    if newer_version(&opts, &crate_info).await?.is_none() {
+        return Ok(None);
+    }
+
+    let crate_name = CrateName {
+        name: crate_info.name.clone(),
+        version_req: Some(upgrade_version_req(&crate_info)),
+        bins: crate_info.selected_bins.clone(),
+    };
+    let installed = crate_info.current_version;
+
+    let (latest, prebuilt) = match resolve(opts, crate_name, Some(installed.clone())).await? {
+        Resolution::Fetch(fetch) => (fetch.new_version, true),
+        Resolution::InstallFromSource(source) => match source.version.parse() {
+            Ok(version) => (version, false),
+            Err(_) => return Ok(None),
+        },
+        // The newer versions have no artifact, and the installed one is
+        // the latest which has.
+        Resolution::AlreadyUpToDate | Resolution::Downloaded(_) => return Ok(None),
+    };
+
+    Ok(Some(OutdatedCrate {
+        name: crate_info.name,
+        installed,
+        latest,
+        prebuilt,
+        source: crate_info.source,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn crate_info(version_req: &str) -> CrateInfo {
+        CrateInfo {
+            version_req: version_req.into(),
+            ..CrateInfo::for_test("cargo-binstall", Version::new(1, 2, 3))
+        }
+    }
+
+    #[test]
+    fn test_is_pinned() {
+        assert!(is_pinned(&crate_info("=1.2.3")));
+
+        assert!(!is_pinned(&crate_info("*")));
+        assert!(!is_pinned(&crate_info("^1.2")));
+        assert!(!is_pinned(&crate_info("=1.2")));
+        assert!(!is_pinned(&crate_info(">=1.2.3, <2")));
+    }
+
+    #[test]
+    fn test_upgrade_version_req() {
+        assert_eq!(upgrade_version_req(&crate_info("=1.2.3")), VersionReq::STAR);
+        assert_eq!(upgrade_version_req(&crate_info("*")), VersionReq::STAR);
+        assert_eq!(
+            upgrade_version_req(&crate_info("~1.2")),
+            VersionReq::parse("~1.2").unwrap()
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Warm the artifact cache without installing anything.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tracing::debug;
+
+use crate::{
+    errors::BinstallError,
+    helpers::download::SavedArtifact,
+    ops::{
+        resolve::{resolve, CrateName, Resolution, ResolutionDownloaded},
+        Options,
+    },
+};
+
+/// Return the path of an artifact named `file_name` with digest `sha256`
+/// in the content-addressed cache at `cache_dir`.
+pub fn cached_artifact_path(cache_dir: &Path, sha256: &str, file_name: &str) -> PathBuf {
+    cache_dir.join("sha256").join(sha256).join(file_name)
+}
+
+/// Resolve `crate_name` and save its artifact into the content-addressed
+/// cache at `cache_dir`.
+///
+/// The artifact is first saved into [`Options::download_only`], which must
+/// be set, and is expected to be a staging directory on the same file
+/// system as `cache_dir`, so that it is moved into the cache atomically.
+///
+/// The index and `.crate` file of the crate are saved to the cache of
+/// [`Options::registry`], and the GitHub releases looked up are kept in the
+/// release cache of [`Options::gh_api_client`], if they have one, so that
+/// the crate can then be installed offline.
+///
+/// Nothing is installed and no receipt is written.
+pub async fn prefetch(
+    opts: Arc<Options>,
+    crate_name: CrateName,
+    cache_dir: &Path,
+) -> Result<ResolutionDownloaded, BinstallError> {
+    let staging_dir = opts
+        .download_only
+        .clone()
+        .expect("prefetch requires Options::download_only");
+
+    let display_name = crate_name.name.clone();
+
+    let mut downloaded = match resolve(opts, crate_name, None).await? {
+        Resolution::Downloaded(downloaded) => downloaded,
+        // E.g. no older version of the crate has an artifact either.
+        _ => {
+            return Err(BinstallError::NothingToPrefetch(display_name.clone())
+                .crate_context(&*display_name))
+        }
+    };
+
+    downloaded.artifact = move_into_cache(cache_dir, &staging_dir, downloaded.artifact)
+        .map_err(|err| BinstallError::from(err).crate_context(&*display_name))?;
+
+    Ok(downloaded)
+}
+
+/// Move the artifact and its digest file to their content-addressed paths.
+///
+/// Both are moved into a new directory in `staging_dir` first, which is
+/// then renamed to the entry of the artifact in the cache, so that the
+/// cache never has an artifact without its digest file. An artifact
+/// already in the cache is kept, since it has the same content.
+fn move_into_cache(
+    cache_dir: &Path,
+    staging_dir: &Path,
+    artifact: SavedArtifact,
+) -> io::Result<SavedArtifact> {
+    let file_name = artifact
+        .path
+        .file_name()
+        .expect("saved artifact must have a file name")
+        .to_owned();
+    let mut digest_file_name = file_name.clone();
+    digest_file_name.push(".sha256");
+
+    let path = cached_artifact_path(cache_dir, &artifact.sha256, &file_name.to_string_lossy());
+    let entry_dir = path.parent().unwrap();
+    let digest_path = entry_dir.join(&digest_file_name);
+
+    debug!(
+        "Moving '{}' into the cache at '{}'",
+        artifact.path.display(),
+        path.display()
+    );
+
+    let staged = tempfile::Builder::new()
+        .prefix(".entry")
+        .tempdir_in(staging_dir)?;
+    fs::rename(&artifact.path, staged.path().join(&file_name))?;
+    fs::rename(&artifact.digest_path, staged.path().join(&digest_file_name))?;
+
+    fs::create_dir_all(entry_dir.parent().unwrap())?;
+    if !entry_dir.exists() {
+        if let Err(err) = fs::rename(staged.path(), entry_dir) {
+            // Created by another run in the meantime.
+            if !entry_dir.is_dir() {
+                return Err(err);
+            }
+        }
+    }
+
+    // The entry may have another file with the same content, the digest
+    // is moved first so that the artifact is only there once it is
+    // complete.
+    if !path.is_file() {
+        fs::rename(staged.path().join(&digest_file_name), &digest_path)?;
+        fs::rename(staged.path().join(&file_name), &path)?;
+    }
+
+    Ok(SavedArtifact {
+        path,
+        sha256: artifact.sha256,
+        digest_path,
+    })
+}
@@ -0,0 +1,353 @@
+//! Hooks for reporting the progress of each crate being installed.
+
+use std::{
+    error,
+    fmt::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    time::Duration,
+};
+
+use compact_str::CompactString;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    errors::BinstallError, helpers::download::DownloadProgress,
+    manifests::crate_info::Verification, ops::Options,
+};
+
+/// Minimum number of bytes downloaded between two [`Progress::Downloading`].
+const DOWNLOADING_STEP: u64 = 256 * 1024;
+
+/// A state transition of a crate being installed.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum Progress<'a> {
+    /// Started resolving the crate.
+    Resolving,
+
+    /// Found an artifact for the crate.
+    Found {
+        /// Name of the source, e.g. domain of the url.
+        source: &'a str,
+        target: &'a str,
+        /// Size of the artifact, if it is known before downloading it.
+        size: Option<u64>,
+    },
+
+    /// Downloading the artifact, reported repeatedly while it is downloaded.
+    Downloading {
+        downloaded: u64,
+        /// Size of the artifact, if the server sent a `Content-Length`.
+        total: Option<u64>,
+    },
+
+    /// Verified the artifact downloaded, against its checksum and signature
+    /// if they are published.
+    Verified { verification: &'a Verification },
+
+    /// Downloaded, verified and extracted the artifact.
+    Downloaded { elapsed: Duration },
+
+    /// Installed the binaries of the crate.
+    Installed { bins: usize, install_path: &'a Path },
+
+    /// Failed to resolve or install the crate.
+    ///
+    /// No more progress is reported for the crate after this.
+    Failed { error: &'a BinstallError },
+}
+
+/// Receiver of [`Progress`] reported during installation.
+///
+/// It is called from multiple tasks concurrently, but progress of the same
+/// crate is always reported in order.
+pub trait ProgressSink: fmt::Debug + Send + Sync {
+    fn report(&self, crate_name: &str, progress: Progress<'_>);
+}
+
+/// Owned version of [`Progress`], sent by [`ChannelProgressSink`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    Resolving,
+    Found {
+        source: CompactString,
+        target: CompactString,
+        size: Option<u64>,
+    },
+    Downloading {
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    Verified {
+        verification: Verification,
+    },
+    Downloaded {
+        elapsed: Duration,
+    },
+    Installed {
+        bins: usize,
+        install_path: PathBuf,
+    },
+    Failed {
+        /// The error, formatted with its source chain.
+        error: String,
+    },
+}
+
+impl From<Progress<'_>> for Event {
+    fn from(progress: Progress<'_>) -> Self {
+        match progress {
+            Progress::Resolving => Self::Resolving,
+            Progress::Found {
+                source,
+                target,
+                size,
+            } => Self::Found {
+                source: source.into(),
+                target: target.into(),
+                size,
+            },
+            Progress::Downloading { downloaded, total } => Self::Downloading { downloaded, total },
+            Progress::Verified { verification } => Self::Verified {
+                verification: verification.clone(),
+            },
+            Progress::Downloaded { elapsed } => Self::Downloaded { elapsed },
+            Progress::Installed { bins, install_path } => Self::Installed {
+                bins,
+                install_path: install_path.to_owned(),
+            },
+            Progress::Failed { error } => {
+                let mut message = error.to_string();
+
+                let mut source = error::Error::source(error);
+                while let Some(err) = source {
+                    write!(message, ": {err}").unwrap();
+                    source = err.source();
+                }
+
+                Self::Failed { error: message }
+            }
+        }
+    }
+}
+
+/// A [`DownloadProgress`] reporting [`Progress::Downloading`] of a crate.
+///
+/// To not flood the [`ProgressSink`], the first update is reported, then
+/// one per [`DOWNLOADING_STEP`] bytes and the last one if the size is known.
+pub(crate) struct DownloadingReporter<'a> {
+    opts: &'a Options,
+    crate_name: &'a str,
+    /// Bytes downloaded at the last report, `u64::MAX` if none.
+    reported: AtomicU64,
+}
+
+impl<'a> DownloadingReporter<'a> {
+    pub(crate) fn new(opts: &'a Options, crate_name: &'a str) -> Self {
+        Self {
+            opts,
+            crate_name,
+            reported: AtomicU64::new(u64::MAX),
+        }
+    }
+}
+
+impl DownloadProgress for DownloadingReporter<'_> {
+    fn update(&self, downloaded: u64, total: Option<u64>) {
+        if self.opts.progress_sink.is_none() {
+            return;
+        }
+
+        let reported = self.reported.load(Relaxed);
+        let due = reported == u64::MAX
+            || downloaded >= reported.saturating_add(DOWNLOADING_STEP)
+            || Some(downloaded) == total;
+
+        if due {
+            self.reported.store(downloaded, Relaxed);
+            self.opts
+                .report_progress(self.crate_name, Progress::Downloading { downloaded, total });
+        }
+    }
+}
+
+/// An [`Event`] of a crate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallEvent {
+    pub crate_name: CompactString,
+    pub event: Event,
+}
+
+#[derive(Debug)]
+enum Sender {
+    Bounded(broadcast::Sender<InstallEvent>),
+    Unbounded(mpsc::UnboundedSender<InstallEvent>),
+}
+
+/// A [`ProgressSink`] sending [`InstallEvent`]s over a tokio channel.
+///
+/// Events of the same crate are received in the order they are reported,
+/// events of different crates may interleave.
+///
+/// # Backpressure
+///
+/// [`ProgressSink::report`] is synchronous and called from within the
+/// installation tasks, so sending never blocks the installation:
+///
+///  - With [`ChannelProgressSink::unbounded`], no event is ever dropped,
+///    but events are buffered without limit if the receiver falls behind.
+///  - With [`ChannelProgressSink::bounded`], the oldest events are dropped
+///    to make room for new ones while the channel is full, and the receiver
+///    gets [`broadcast::error::RecvError::Lagged`] with the number of events
+///    dropped. The channel is shared by all the crates, so the events of the
+///    other crates may push out any event of a crate, including its final
+///    [`Event::Installed`] or [`Event::Failed`]. The events received for
+///    each crate are still a subsequence of the ones reported, in order.
+///
+/// Events reported after the receiver is dropped are discarded silently.
+#[derive(Debug)]
+pub struct ChannelProgressSink {
+    sender: Sender,
+}
+
+impl ChannelProgressSink {
+    /// * `capacity` - must be greater than 0
+    pub fn bounded(capacity: usize) -> (Self, broadcast::Receiver<InstallEvent>) {
+        let (sender, receiver) = broadcast::channel(capacity);
+        (
+            Self {
+                sender: Sender::Bounded(sender),
+            },
+            receiver,
+        )
+    }
+
+    pub fn unbounded() -> (Self, mpsc::UnboundedReceiver<InstallEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (
+            Self {
+                sender: Sender::Unbounded(sender),
+            },
+            receiver,
+        )
+    }
+}
+
+impl ProgressSink for ChannelProgressSink {
+    fn report(&self, crate_name: &str, progress: Progress<'_>) {
+        let event = InstallEvent {
+            crate_name: crate_name.into(),
+            event: progress.into(),
+        };
+
+        match &self.sender {
+            Sender::Bounded(sender) => {
+                sender.send(event).ok();
+            }
+            Sender::Unbounded(sender) => {
+                sender.send(event).ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bounded_channel_drops_oldest_events() {
+        let (sink, mut receiver) = ChannelProgressSink::bounded(2);
+
+        sink.report("a", Progress::Resolving);
+        sink.report(
+            "a",
+            Progress::Found {
+                source: "github.com",
+                target: "x86_64-unknown-linux-gnu",
+                size: None,
+            },
+        );
+        sink.report(
+            "a",
+            Progress::Downloaded {
+                elapsed: Duration::from_secs(1),
+            },
+        );
+
+        assert_eq!(
+            receiver.try_recv().unwrap_err(),
+            broadcast::error::TryRecvError::Lagged(1)
+        );
+        assert_eq!(
+            receiver.try_recv().unwrap().event,
+            Event::Found {
+                source: "github.com".into(),
+                target: "x86_64-unknown-linux-gnu".into(),
+                size: None,
+            }
+        );
+        assert_eq!(
+            receiver.try_recv().unwrap().event,
+            Event::Downloaded {
+                elapsed: Duration::from_secs(1),
+            }
+        );
+        assert!(receiver.try_recv().is_err());
+
+        drop(receiver);
+        sink.report("a", Progress::Resolving);
+    }
+
+    #[test]
+    fn test_bounded_channel_drops_final_events_of_other_crates() {
+        let (sink, mut receiver) = ChannelProgressSink::bounded(2);
+        let install_path = Path::new("bin");
+
+        sink.report("a", Progress::Resolving);
+        sink.report(
+            "a",
+            Progress::Installed {
+                bins: 1,
+                install_path,
+            },
+        );
+        sink.report("b", Progress::Resolving);
+        sink.report("c", Progress::Resolving);
+        sink.report(
+            "b",
+            Progress::Installed {
+                bins: 2,
+                install_path,
+            },
+        );
+
+        assert_eq!(
+            receiver.try_recv().unwrap_err(),
+            broadcast::error::TryRecvError::Lagged(3)
+        );
+        let mut received = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            received.push(event);
+        }
+        // The final event of `a` is pushed out by the ones of `b` and `c`.
+        assert_eq!(
+            received,
+            [
+                InstallEvent {
+                    crate_name: "c".into(),
+                    event: Event::Resolving,
+                },
+                InstallEvent {
+                    crate_name: "b".into(),
+                    event: Event::Installed {
+                        bins: 2,
+                        install_path: install_path.to_owned(),
+                    },
+                },
+            ]
+        );
+    }
+}
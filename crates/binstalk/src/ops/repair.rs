@@ -0,0 +1,117 @@
+//! Check installed crates against their records, and reinstall only the
+//! binaries which are missing or corrupt.
+
+use std::{borrow::Cow, fs, io, path::Path, sync::Arc};
+
+use compact_str::CompactString;
+use semver::VersionReq;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::{
+    errors::BinstallError,
+    manifests::crate_info::CrateInfo,
+    ops::{
+        resolve::{resolve_with_fetch_source, CrateName, Resolution},
+        Options,
+    },
+};
+
+/// Return the hex SHA-256 digest of the file at `path`.
+pub fn sha256_file(path: &Path) -> io::Result<CompactString> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()).into())
+}
+
+/// Return the binaries of `crate_info` which are missing from
+/// `install_path`, or whose content does not match the digest recorded.
+///
+/// Binaries recorded without a digest are only checked for existence.
+pub fn broken_bins(crate_info: &CrateInfo, install_path: &Path) -> Vec<CompactString> {
+    crate_info
+        .bins
+        .iter()
+        .filter(|bin| {
+            // Both checks follow symlinks, so a dangling symlink is missing.
+            let path = install_path.join(bin.as_str());
+
+            let intact = match crate_info.bin_digests.get(*bin) {
+                Some(digest) => sha256_file(&path).map_or(false, |actual| actual == *digest),
+                None => fs::metadata(&path).map_or(false, |metadata| metadata.is_file()),
+            };
+            if !intact {
+                debug!("'{}' is missing or corrupt", path.display());
+            }
+
+            !intact
+        })
+        .cloned()
+        .collect()
+}
+
+/// Check the binaries of the installed crate recorded in `crate_info`.
+///
+/// Return [`Resolution::AlreadyUpToDate`] if they are all intact, without
+/// sending any request. Otherwise, resolve the installed version again and
+/// return a [`Resolution::Fetch`] of the broken binaries only, which
+/// updates `crate_info` once installed.
+pub async fn repair(
+    opts: Arc<Options>,
+    crate_info: CrateInfo,
+) -> Result<Resolution, BinstallError> {
+    let install_path = match &opts.install_path_template {
+        Some(template) => Cow::Owned(template.render_for(&crate_info)),
+        None => Cow::Borrowed(&*opts.install_path),
+    };
+    let broken = broken_bins(&crate_info, &install_path);
+    if broken.is_empty() {
+        debug!(
+            "{} v{} is installed and intact",
+            crate_info.name, crate_info.current_version
+        );
+        return Ok(Resolution::AlreadyUpToDate);
+    }
+
+    warn!(
+        "{} v{} is installed, but {} missing or corrupt, re-fetching {}",
+        crate_info.name,
+        crate_info.current_version,
+        broken.join(", "),
+        if broken.len() == 1 { "it" } else { "them" },
+    );
+
+    let crate_name = CrateName {
+        name: crate_info.name.clone(),
+        // `Options::version_req` cannot be combined with a version in the
+        // crate name, the installed version is the latest it accepts anyway.
+        version_req: opts.version_req.is_none().then(|| {
+            VersionReq::parse(&format!("={}", crate_info.current_version))
+                .expect("version must be a valid requirement")
+        }),
+        bins: crate_info.selected_bins.clone(),
+    };
+
+    let fetch_source = crate_info.fetch_source.clone();
+    match resolve_with_fetch_source(opts, crate_name, None, fetch_source).await? {
+        Resolution::Fetch(mut fetch) => {
+            fetch
+                .bin_files
+                .retain(|file| broken.contains(&file.base_name));
+            // The completions and man pages are left alone.
+            fetch.extra_files.clear();
+            fetch.repaired = Some(crate_info);
+
+            Ok(Resolution::Fetch(fetch))
+        }
+        Resolution::InstallFromSource(source) => {
+            warn!(
+                "{} can only be repaired by building it from source, reinstall it with --force",
+                source.name
+            );
+            Ok(Resolution::AlreadyUpToDate)
+        }
+        resolution => Ok(resolution),
+    }
+}
@@ -1,37 +1,64 @@
 use std::{
     borrow::Cow,
     collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
     iter, mem,
-    path::Path,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
+    time::Instant,
 };
 
-use compact_str::{CompactString, ToCompactString};
+use compact_str::{format_compact, CompactString, ToCompactString};
 use itertools::Itertools;
 use leon::Template;
 use maybe_owned::MaybeOwned;
 use semver::{Version, VersionReq};
 use tempfile::TempDir;
 use tokio::task::spawn_blocking;
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{debug, debug_span, error, field, info, instrument, warn, Instrument, Span};
 
 use crate::{
     bins,
-    errors::{BinstallError, VersionParseError},
-    fetchers::{Data, Fetcher, TargetData},
+    errors::{BinstallError, ErrorKind, VersionParseError},
+    fetchers::{check_url_templates, Data, Fetcher, TargetData},
     helpers::{
-        self, cargo_toml::Manifest, cargo_toml_workspace::load_manifest_from_workspace,
-        download::ExtractedFiles, remote::Client, target_triple::TargetTriple,
+        self,
+        cargo_toml::Manifest,
+        cargo_toml_workspace::load_manifest_and_workspace_metadata,
+        download::{ExtractFilter, ExtractedFiles},
+        gh_api_client::GhRepo,
+        remote::Client,
+        target_triple::TargetTriple,
         tasks::AutoAbortJoinHandle,
     },
-    manifests::cargo_toml_binstall::{Meta, PkgMeta, PkgOverride},
-    ops::{CargoTomlFetchOverride, Options},
+    manifests::{
+        cargo_toml_binstall::{Meta, PkgFmt, PkgMeta, PkgOverride},
+        crate_info::{ArtifactVersion, FetchSource, Verification},
+    },
+    ops::{
+        progress::{DownloadingReporter, Progress},
+        stats::{Phase, COMPILE_STRATEGY},
+        CargoTomlFetchOverride, Options, VersionMatchPolicy,
+    },
+    registry::RegistryError,
 };
 
+mod artifact_version;
+
 mod crate_name;
 #[doc(inline)]
-pub use crate_name::CrateName;
+pub use crate_name::{BinSpec, CrateName, CrateSpec};
+
+mod denylist;
+#[doc(inline)]
+pub use denylist::{DeniedVersion, Denylist};
+
+mod locked;
+pub use locked::resolve_locked;
+
+mod suggest;
+pub use suggest::find_crate_providing_bin;
 
 mod version_ext;
 #[doc(inline)]
@@ -39,18 +66,85 @@ pub use version_ext::VersionReqExt;
 
 mod resolution;
 #[doc(inline)]
-pub use resolution::{Resolution, ResolutionFetch, ResolutionSource};
+pub use resolution::{
+    format_size, BinaryResolution, Resolution, ResolutionDownloaded, ResolutionFetch,
+    ResolutionSource,
+};
+
+#[doc(inline)]
+pub use crate::errors::{StrategyFailure, StrategyFailureKind};
 
-#[instrument(skip_all)]
+/// Resolve `crate_name`, falling back to building from source if
+/// [`Options::cargo_install_fallback`] is set.
 pub async fn resolve(
     opts: Arc<Options>,
     crate_name: CrateName,
     curr_version: Option<Version>,
 ) -> Result<Resolution, BinstallError> {
+    resolve_with_fetch_source(opts, crate_name, curr_version, None).await
+}
+
+/// Resolve `crate_name` like [`resolve`], trying the strategy of
+/// `fetch_source` first, i.e. the one the installed version was fetched
+/// with, before the others.
+#[instrument(skip_all, fields(name = %crate_name.name, version = field::Empty))]
+pub async fn resolve_with_fetch_source(
+    opts: Arc<Options>,
+    crate_name: CrateName,
+    curr_version: Option<Version>,
+    fetch_source: Option<FetchSource>,
+) -> Result<Resolution, BinstallError> {
+    let allow_source_build = opts.cargo_install_fallback && opts.download_only.is_none();
+
     let crate_name_name = crate_name.name.clone();
-    let resolution = resolve_inner(opts, crate_name, curr_version)
-        .await
-        .map_err(|err| err.crate_context(crate_name_name))?;
+    let start = Instant::now();
+    let res = opts
+        .cancellable(resolve_inner(
+            opts.clone(),
+            crate_name,
+            curr_version,
+            fetch_source.as_ref(),
+        ))
+        .await;
+    opts.stats.add_elapsed(Phase::Resolve, start.elapsed());
+
+    let resolution = res
+        .and_then(|resolution| resolution.or_source_build(allow_source_build))
+        .map_err(|err| {
+            let err = err.crate_context(&*crate_name_name);
+            opts.report_progress(&crate_name_name, Progress::Failed { error: &err });
+            err
+        })?;
+
+    if let Resolution::InstallFromSource(_) = resolution {
+        opts.stats.add_strategy(COMPILE_STRATEGY);
+    }
+
+    Ok(resolution)
+}
+
+/// Resolve `crate_name` to a prebuilt binary, leaving the decision of
+/// building from source to the caller.
+///
+/// [`Options::cargo_install_fallback`] is ignored.
+#[instrument(skip_all, fields(name = %crate_name.name, version = field::Empty))]
+pub async fn resolve_binary(
+    opts: Arc<Options>,
+    crate_name: CrateName,
+    curr_version: Option<Version>,
+) -> Result<BinaryResolution, BinstallError> {
+    let crate_name_name = crate_name.name.clone();
+    let start = Instant::now();
+    let res = opts
+        .cancellable(resolve_inner(opts.clone(), crate_name, curr_version, None))
+        .await;
+    opts.stats.add_elapsed(Phase::Resolve, start.elapsed());
+
+    let resolution = res.map_err(|err| {
+        let err = err.crate_context(&*crate_name_name);
+        opts.report_progress(&crate_name_name, Progress::Failed { error: &err });
+        err
+    })?;
 
     Ok(resolution)
 }
@@ -59,8 +153,10 @@ async fn resolve_inner(
     opts: Arc<Options>,
     crate_name: CrateName,
     curr_version: Option<Version>,
-) -> Result<Resolution, BinstallError> {
+    fetch_source: Option<&FetchSource>,
+) -> Result<BinaryResolution, BinstallError> {
     info!("Resolving package: '{}'", crate_name);
+    opts.report_progress(&crate_name.name, Progress::Resolving);
 
     let version_req = match (&crate_name.version_req, &opts.version_req) {
         (Some(version), None) => MaybeOwned::Borrowed(version),
@@ -71,23 +167,218 @@ async fn resolve_inner(
 
     let version_req_str = version_req.to_compact_string();
 
-    let Some(package_info) = PackageInfo::resolve(
-        &opts,
-        crate_name.name,
-        curr_version,
-        &version_req,
-        opts.client.clone(),
-    )
-    .await?
-    else {
-        return Ok(Resolution::AlreadyUpToDate);
+    let bins = crate_name.bins.as_slice();
+
+    let desired_targets = opts.desired_targets.get().await;
+    let targets: Vec<&str> = desired_targets.iter().map(String::as_str).collect();
+
+    let mut name = crate_name.name.clone();
+    let package_info = loop {
+        match PackageInfo::resolve(
+            &opts,
+            name.clone(),
+            bins,
+            curr_version.clone(),
+            &version_req,
+            &targets,
+            opts.client.clone(),
+            &[],
+        )
+        .await
+        {
+            // `crate:bin` where the crate does not exist, install the binary
+            // from the crate providing it instead.
+            Err(BinstallError::RegistryError(err))
+                if bins.len() == 1
+                    && name == crate_name.name
+                    && matches!(*err, RegistryError::NotFound(_)) =>
+            {
+                let found = find_crate_providing_bin(&opts, &bins[0]).await?;
+                if found == name {
+                    return Err(suggest::crate_not_found(&opts, &name).await);
+                }
+                info!(
+                    "Crate {name} is not found, installing binary {} from crate {found}",
+                    bins[0]
+                );
+                name = found;
+            }
+            Err(BinstallError::RegistryError(err))
+                if matches!(*err, RegistryError::NotFound(_)) =>
+            {
+                return Err(suggest::crate_not_found(&opts, &name).await);
+            }
+            res => break res?,
+        }
+    };
+    let Some(package_info) = package_info else {
+        return Ok(BinaryResolution::ResolvedToBinary(
+            Resolution::AlreadyUpToDate,
+        ));
     };
 
-    let desired_targets = opts
-        .desired_targets
-        .get()
+    let name = package_info.name.clone();
+    let newest_version = package_info.version.clone();
+    let repo = package_info.repo.clone();
+
+    let newest = resolve_package(
+        opts.clone(),
+        package_info,
+        desired_targets,
+        version_req_str.clone(),
+        crate_name.bins.clone(),
+        fetch_source,
+    )
+    .await?;
+
+    // An explicit version, or a manifest which is not from the registry,
+    // has no older version to walk down to.
+    if opts.strict
+        || opts.cargo_toml_fetch_override.is_some()
+        || denylist::exact_version(&version_req).is_some()
+        || !has_no_artifact(&newest)
+    {
+        return Ok(newest);
+    }
+
+    // Walk down the older versions matching the requirement, skipping
+    // those without a release of their own.
+    let release_tags = list_release_tags(&opts, repo.as_deref()).await;
+    let mut skipped = vec![newest_version.clone()];
+    while skipped.len() <= MAX_OLDER_VERSIONS {
+        let older = match PackageInfo::resolve(
+            &opts,
+            name.clone(),
+            bins,
+            curr_version.clone(),
+            &version_req,
+            &targets,
+            opts.client.clone(),
+            &skipped,
+        )
+        .await
+        {
+            Ok(Some(older)) => older,
+            Ok(None) => {
+                warn!(
+                    "{name} v{newest_version}, the newest version matching {version_req_str}, \
+                    has no artifact, keeping the installed version"
+                );
+                return Ok(BinaryResolution::ResolvedToBinary(
+                    Resolution::AlreadyUpToDate,
+                ));
+            }
+            Err(err) => {
+                debug!("Stopped looking for older versions of {name}: {err}");
+                break;
+            }
+        };
+
+        let version = older.version.clone();
+        if let Some(tags) = &release_tags {
+            if !tags
+                .iter()
+                .any(|tag| tag_has_version(tag, &older.version_str))
+            {
+                debug!("Skipped {name} v{version}, no release is tagged with it");
+                skipped.push(version);
+                continue;
+            }
+        }
+
+        info!("{name} v{newest_version} has no artifact, trying v{version}");
+        let resolution = match resolve_package(
+            opts.clone(),
+            older,
+            desired_targets,
+            version_req_str.clone(),
+            crate_name.bins.clone(),
+            fetch_source,
+        )
         .await
+        {
+            Ok(resolution) => resolution,
+            Err(err) => {
+                debug!("Stopped looking for older versions of {name}: {err}");
+                break;
+            }
+        };
+
+        if !has_no_artifact(&resolution) {
+            if let BinaryResolution::ResolvedToBinary(_) = resolution {
+                warn!(
+                    "{name} v{version} is installed instead of v{newest_version}, the newest \
+                    version matching {version_req_str}, which has no artifact"
+                );
+                return Ok(resolution);
+            }
+            break;
+        }
+        skipped.push(version);
+    }
+
+    Ok(newest)
+}
+
+/// Maximum number of versions older than the newest one matching the
+/// requirement looked at for an artifact.
+const MAX_OLDER_VERSIONS: usize = 10;
+
+/// Return `true` if `resolution` needs a build from source because no
+/// fetcher found an artifact, rather than because of a failure which would
+/// affect the older versions as well.
+fn has_no_artifact(resolution: &BinaryResolution) -> bool {
+    match resolution {
+        BinaryResolution::ResolvedToBinary(_) => false,
+        BinaryResolution::WouldNeedSourceBuild { reasons, .. } => reasons.iter().all(|reason| {
+            matches!(
+                reason.kind,
+                StrategyFailureKind::NotFound | StrategyFailureKind::NoBinaries
+            )
+        }),
+    }
+}
+
+/// Return the tags of the GitHub releases of `repo`, `None` if they cannot
+/// be listed.
+async fn list_release_tags(opts: &Options, repo: Option<&str>) -> Option<Vec<CompactString>> {
+    let repo = GhRepo::try_extract_from_url(repo?)?;
+    match opts.gh_api_client.list_releases(&repo).await {
+        Ok(releases) if !releases.is_empty() => {
+            Some(releases.into_iter().map(|release| release.tag).collect())
+        }
+        Ok(_) => None,
+        Err(err) => {
+            debug!("Failed to list the releases of {repo:?}: {err}");
+            None
+        }
+    }
+}
+
+/// Return `true` if `tag` is of `version`, e.g. `v1.2.3` or `tool-1.2.3`
+/// but not `1.2.30`.
+fn tag_has_version(tag: &str, version: &str) -> bool {
+    tag.strip_suffix(version).map_or(false, |prefix| {
+        !prefix.ends_with(|c: char| c.is_ascii_digit() || c == '.')
+    })
+}
+
+/// Resolve `package_info` to the artifact of the first fetcher which has
+/// one, or to a build from source.
+async fn resolve_package(
+    opts: Arc<Options>,
+    package_info: PackageInfo,
+    desired_targets: &[String],
+    version_req_str: CompactString,
+    bins: Vec<CompactString>,
+    fetch_source: Option<&FetchSource>,
+) -> Result<BinaryResolution, BinstallError> {
+    Span::current().record("version", field::display(&package_info.version));
+
+    let host_target = desired_targets.first().map(String::as_str);
+    let desired_targets = desired_targets
         .iter()
+        .filter(|target| !package_info.denied_targets.contains(target.as_str()))
         .map(|target| TargetTriple::from_str(target).map(|triple| (triple, target)))
         .collect::<Result<Vec<_>, _>>()?;
     let resolvers = &opts.resolvers;
@@ -95,11 +386,21 @@ async fn resolve_inner(
     let mut handles: Vec<(Arc<dyn Fetcher>, _)> =
         Vec::with_capacity(desired_targets.len() * resolvers.len());
 
-    let data = Arc::new(Data::new(
-        package_info.name.clone(),
-        package_info.version_str.clone(),
-        package_info.repo.clone(),
-    ));
+    let data = Arc::new(
+        Data::new(
+            package_info.name.clone(),
+            package_info.version_str.clone(),
+            package_info.repo.clone(),
+        )
+        .with_bins(
+            package_info
+                .binaries
+                .iter()
+                .map(|bin| bin.name.to_compact_string()),
+        )
+        .with_allow_insecure_http(opts.allow_insecure_http)
+        .with_url_rewrites(opts.url_rewrites.clone()),
+    );
 
     handles.extend(
         desired_targets
@@ -121,57 +422,246 @@ async fn resolve_inner(
             })
             .cartesian_product(resolvers)
             .map(|(target_data, f)| {
-                let fetcher = f(
+                f(
                     opts.client.clone(),
                     opts.gh_api_client.clone(),
+                    opts.gl_api_client.clone(),
+                    opts.gitea_api_client.clone(),
                     data.clone(),
                     target_data,
                     opts.signature_policy,
-                );
-                (fetcher.clone(), AutoAbortJoinHandle::new(fetcher.find()))
+                    opts.checksum_policy,
+                    opts.artifact_cache.clone(),
+                )
+            })
+            .filter(|fetcher| {
+                let disabled = package_info
+                    .meta
+                    .disabled_strategies
+                    .contains(&fetcher.strategy());
+                if disabled {
+                    debug!(
+                        "Skipped {} for {}, {} is disabled by the crate",
+                        fetcher.source_name(),
+                        fetcher.target(),
+                        fetcher.strategy()
+                    );
+                }
+                !disabled
+            })
+            .map(|fetcher| {
+                let handle = fetcher_span(&*fetcher).in_scope(|| fetcher.clone().find());
+                (fetcher, AutoAbortJoinHandle::new(handle))
             }),
     );
 
+    if let Some(fetch_source) = fetch_source {
+        // The sort is stable, so the targets are still tried in order.
+        handles.sort_by_key(|(fetcher, _)| !is_same_strategy(fetch_source, &**fetcher));
+        if let Some((fetcher, _)) = handles
+            .first()
+            .filter(|(fetcher, _)| is_same_strategy(fetch_source, &**fetcher))
+        {
+            debug!(
+                "Trying {} first, {} was installed with it",
+                fetcher.fetcher_name(),
+                package_info.name
+            );
+        }
+    }
+
+    // Why no artifact could be fetched.
+    let mut failures = Vec::new();
+    let failure = |fetcher: &dyn Fetcher, kind| StrategyFailure {
+        strategy: fetcher.source_name(),
+        target: fetcher.target().into(),
+        kind,
+    };
+
     for (fetcher, handle) in handles {
-        fetcher.clone().report_to_upstream();
+        if !opts.disable_telemetry {
+            fetcher.clone().report_to_upstream();
+        }
         match handle.flattened_join().await {
             Ok(true) => {
+                let download_size = match fetcher.report_size().await {
+                    Ok(size) => size,
+                    Err(err) => {
+                        debug!(
+                            "Failed to get the size of the package from {}: {err}",
+                            fetcher.source_name()
+                        );
+                        None
+                    }
+                };
+
+                opts.report_progress(
+                    &package_info.name,
+                    Progress::Found {
+                        source: &fetcher.source_name(),
+                        target: fetcher.target(),
+                        size: download_size,
+                    },
+                );
+                if let Some(host_target) = host_target {
+                    if runs_emulated(host_target, fetcher.target()) {
+                        info!(
+                            "{} binaries run under emulation on {host_target}",
+                            fetcher.target()
+                        );
+                    }
+                }
+                let start = Instant::now();
+
+                if let Some(dir) = &opts.download_only {
+                    let progress = DownloadingReporter::new(&opts, &package_info.name);
+                    match fetcher
+                        .fetch_and_save(dir, &progress)
+                        .instrument(fetcher_span(&*fetcher))
+                        .await
+                    {
+                        Ok((artifact, verification)) => {
+                            let elapsed = start.elapsed();
+                            opts.stats.add_elapsed(Phase::Download, elapsed);
+                            opts.stats.add_strategy(fetcher.fetcher_name());
+                            opts.report_progress(
+                                &package_info.name,
+                                Progress::Verified {
+                                    verification: &verification,
+                                },
+                            );
+                            opts.report_progress(
+                                &package_info.name,
+                                Progress::Downloaded { elapsed },
+                            );
+
+                            return Ok(BinaryResolution::ResolvedToBinary(Resolution::Downloaded(
+                                ResolutionDownloaded {
+                                    name: package_info.name,
+                                    version: package_info.version,
+                                    target: fetcher.target().into(),
+                                    artifact,
+                                    verification,
+                                    denylisted: package_info.denylisted,
+                                },
+                            )));
+                        }
+                        Err(err) => {
+                            warn!(
+                                "Error while downloading from fetcher {}: {}",
+                                fetcher.source_name(),
+                                err
+                            );
+                            let kind = failure_kind(&err.into());
+                            failures.push(failure(&*fetcher, kind));
+                            continue;
+                        }
+                    }
+                }
+
                 // Generate temporary binary path
-                let bin_path = opts.temp_dir.join(format!(
+                let staging_dir = StagingDir(Some(opts.temp_dir.join(format!(
                     "bin-{}-{}-{}",
                     package_info.name,
                     fetcher.target(),
                     fetcher.fetcher_name()
-                ));
+                ))));
+                let bin_path = staging_dir.path();
 
-                match download_extract_and_verify(
-                    fetcher.as_ref(),
-                    &bin_path,
-                    &package_info,
-                    &opts.install_path,
-                    opts.no_symlinks,
-                )
-                .await
+                // Only show what would be installed, the paths of the
+                // binaries in the package are unknown until it is extracted.
+                if opts.dry_run {
+                    let bin_files = collect_bin_files(
+                        fetcher.as_ref(),
+                        &package_info,
+                        fetcher.target_meta(),
+                        bin_path,
+                        &opts,
+                        &ExtractedFiles::new(),
+                    )?;
+                    check_untracked_destinations(&bin_files, &opts)?;
+                    opts.stats.add_strategy(fetcher.fetcher_name());
+
+                    return Ok(BinaryResolution::ResolvedToBinary(Resolution::Fetch(
+                        Box::new(ResolutionFetch {
+                            fetcher,
+                            new_version: package_info.version,
+                            name: package_info.name,
+                            version_req: version_req_str,
+                            bin_files,
+                            selected_bins: bins,
+                            extra_files: Vec::new(),
+                            artifact_version: None,
+                            verification: None,
+                            denylisted: package_info.denylisted,
+                            repaired: None,
+                            download_size,
+                        }),
+                    )));
+                }
+
+                match download_extract_and_verify(fetcher.as_ref(), bin_path, &package_info, &opts)
+                    .instrument(fetcher_span(&*fetcher))
+                    .await
                 {
-                    Ok(bin_files) => {
+                    Ok(Verified {
+                        bin_files,
+                        extra_files,
+                        artifact_version,
+                        verification,
+                    }) => {
+                        let elapsed = start.elapsed();
+                        opts.stats.add_elapsed(Phase::Download, elapsed);
+                        opts.report_progress(
+                            &package_info.name,
+                            Progress::Verified {
+                                verification: &verification,
+                            },
+                        );
+                        opts.report_progress(&package_info.name, Progress::Downloaded { elapsed });
+
                         if !bin_files.is_empty() {
-                            return Ok(Resolution::Fetch(Box::new(ResolutionFetch {
-                                fetcher,
-                                new_version: package_info.version,
-                                name: package_info.name,
-                                version_req: version_req_str,
-                                bin_files,
-                            })));
+                            check_untracked_destinations(&bin_files, &opts)?;
+                            opts.stats.add_strategy(fetcher.fetcher_name());
+                            // It is removed along with `Options::temp_dir`
+                            // once installed.
+                            staging_dir.keep();
+
+                            return Ok(BinaryResolution::ResolvedToBinary(Resolution::Fetch(
+                                Box::new(ResolutionFetch {
+                                    fetcher,
+                                    new_version: package_info.version,
+                                    name: package_info.name,
+                                    version_req: version_req_str,
+                                    bin_files,
+                                    selected_bins: bins,
+                                    extra_files,
+                                    artifact_version,
+                                    verification: Some(verification),
+                                    denylisted: package_info.denylisted,
+                                    repaired: None,
+                                    download_size,
+                                }),
+                            )));
                         } else {
+                            drop(staging_dir);
                             warn!(
                                 "Error when checking binaries provided by fetcher {}: \
                                 The fetcher does not provide any optional binary",
                                 fetcher.source_name(),
                             );
+                            failures.push(failure(&*fetcher, StrategyFailureKind::NoBinaries));
                         }
                     }
                     Err(err) => {
-                        if let BinstallError::UserAbort = err {
+                        drop(staging_dir);
+                        if let BinstallError::UserAbort
+                        | BinstallError::VersionMismatch { .. }
+                        | BinstallError::ChecksumMismatch { .. }
+                        | BinstallError::MissingChecksum
+                        | BinstallError::InvalidAttestation(_)
+                        | BinstallError::InsufficientSpace { .. } = err
+                        {
                             return Err(err);
                         }
                         warn!(
@@ -179,30 +669,151 @@ async fn resolve_inner(
                             fetcher.source_name(),
                             err
                         );
+                        let kind = failure_kind(&err);
+                        failures.push(failure(&*fetcher, kind));
                     }
                 }
             }
-            Ok(false) => (),
+            Ok(false) => {
+                failures.push(failure(&*fetcher, StrategyFailureKind::NotFound));
+            }
+            // Not an issue of this fetcher, the crate needs to be allowed.
+            Err(err @ BinstallError::InsecureUrl { .. }) => return Err(err),
             Err(err) => {
                 warn!(
                     "Error while checking fetcher {}: {}",
                     fetcher.source_name(),
                     err
                 );
+                let kind = failure_kind(&err);
+                failures.push(failure(&*fetcher, kind));
             }
         }
     }
 
-    if opts.cargo_install_fallback {
-        Ok(Resolution::InstallFromSource(ResolutionSource {
+    let geo_blocked: BTreeSet<_> = failures
+        .iter()
+        .filter_map(|failure| match &failure.kind {
+            StrategyFailureKind::GeoBlocked { host } => Some(host),
+            _ => None,
+        })
+        .collect();
+    if !geo_blocked.is_empty() {
+        warn!(
+            "Artifact hosts unavailable from your region/network: {}",
+            geo_blocked.iter().format(", ")
+        );
+    }
+
+    Ok(BinaryResolution::WouldNeedSourceBuild {
+        source: ResolutionSource {
             name: package_info.name,
             version: package_info.version_str,
-        }))
-    } else {
-        Err(BinstallError::NoFallbackToCargoInstall)
+            version_req: version_req_str,
+            bins,
+            denylisted: package_info.denylisted,
+        },
+        reasons: failures,
+        disabled_strategies: opts
+            .disabled_strategies
+            .iter()
+            .chain(&package_info.meta.disabled_strategies)
+            .copied()
+            .sorted()
+            .dedup()
+            .collect(),
+    })
+}
+
+fn failure_kind(err: &BinstallError) -> StrategyFailureKind {
+    if let Some(blocked) = err.geo_blocked() {
+        return StrategyFailureKind::GeoBlocked {
+            host: blocked.host().into(),
+        };
+    }
+    if let Some(url) = err.offline_missing() {
+        return StrategyFailureKind::OfflineMissing { url: url.into() };
+    }
+
+    let err_str = err.to_compact_string();
+    match err.kind() {
+        ErrorKind::RateLimited => StrategyFailureKind::RateLimited(err_str),
+        ErrorKind::Network => StrategyFailureKind::Network(err_str),
+        _ => StrategyFailureKind::Failed(err_str),
+    }
+}
+
+/// The staging directory a fetcher extracts its package to, removed on drop
+/// even if the resolution is cancelled, unless it is kept for installing
+/// from it, it is then removed with [`Options::temp_dir`].
+///
+/// A package of a single binary may be extracted to the path itself.
+struct StagingDir(Option<PathBuf>);
+
+impl StagingDir {
+    fn path(&self) -> &Path {
+        self.0.as_deref().expect("only taken on keep or drop")
+    }
+
+    fn keep(mut self) {
+        self.0.take();
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let Some(path) = self.0.take() else {
+            return;
+        };
+        let res = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match res {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                debug!("Failed to remove '{}': {err}", path.display());
+            }
+            _ => (),
+        }
     }
 }
 
+/// Return true if `fetch_source` is recorded by the strategy of `fetcher`.
+fn is_same_strategy(fetch_source: &FetchSource, fetcher: &dyn Fetcher) -> bool {
+    matches!(
+        (fetch_source, fetcher.fetcher_name()),
+        (FetchSource::GhCrateMeta { .. }, "GhCrateMeta")
+            | (FetchSource::QuickInstall { .. }, "QuickInstall")
+    )
+}
+
+/// Return true if binaries of `target` run under emulation on `host`, i.e.
+/// x86_64 or i686 binaries on Windows on ARM.
+fn runs_emulated(host: &str, target: &str) -> bool {
+    host.starts_with("aarch64-")
+        && host.contains("-windows-")
+        && target.contains("-windows-")
+        && !target.starts_with("aarch64-")
+}
+
+/// Span of what `fetcher` does for the crate being resolved.
+fn fetcher_span(fetcher: &dyn Fetcher) -> Span {
+    debug_span!(
+        "fetcher",
+        fetcher = fetcher.fetcher_name(),
+        target = fetcher.target()
+    )
+}
+
+/// Files downloaded by [`download_extract_and_verify`].
+struct Verified {
+    bin_files: Vec<bins::BinFile>,
+    extra_files: Vec<bins::ExtraFile>,
+    artifact_version: Option<ArtifactVersion>,
+    verification: Verification,
+}
+
 ///  * `fetcher` - `fetcher.find()` must have returned `Ok(true)`.
 ///
 /// Can return empty Vec if all `BinFile` is optional and does not exist
@@ -211,14 +822,20 @@ async fn download_extract_and_verify(
     fetcher: &dyn Fetcher,
     bin_path: &Path,
     package_info: &PackageInfo,
-    install_path: &Path,
-    no_symlinks: bool,
-) -> Result<Vec<bins::BinFile>, BinstallError> {
+    opts: &Options,
+) -> Result<Verified, BinstallError> {
     // Download and extract it.
     // If that fails, then ignore this fetcher.
-    let extracted_files = fetcher.fetch_and_extract(bin_path).await?;
+    let progress = DownloadingReporter::new(opts, &package_info.name);
+    let filter = extract_filter(fetcher, package_info, bin_path, opts);
+    let (extracted_files, verification) = fetcher
+        .fetch_and_extract(bin_path, filter.as_ref(), &progress)
+        .await?;
     debug!("extracted_files = {extracted_files:#?}");
 
+    let artifact_version =
+        check_artifact_version(package_info, bin_path, &extracted_files, opts).await?;
+
     // Build final metadata
     let meta = fetcher.target_meta();
 
@@ -226,16 +843,24 @@ async fn download_extract_and_verify(
     let bin_files = collect_bin_files(
         fetcher,
         package_info,
-        meta,
+        meta.clone(),
         bin_path,
-        install_path,
-        no_symlinks,
+        opts,
         &extracted_files,
     )?;
 
+    let extra_files = collect_extra_files(
+        fetcher,
+        package_info,
+        meta,
+        bin_path,
+        opts,
+        &extracted_files,
+    );
+
     let name = &package_info.name;
 
-    package_info
+    let bin_files = package_info
         .binaries
         .iter()
         .zip(bin_files)
@@ -244,7 +869,7 @@ async fn download_extract_and_verify(
                 Ok(()) => Some(Ok(bin_file)),
 
                 // This binary is optional
-                Err(err) => {
+                Err(_) => {
                     let required_features = &bin.required_features;
                     let bin_name = bin.name.as_str();
 
@@ -255,7 +880,7 @@ This binary is not optional so it must be included in the archive, please contac
 upstream to fix this issue."
                         );
                         // This bin is not optional, error
-                        Some(Err(err))
+                        Some(Err(missing_bin_file(&bin_file, bin_path, &extracted_files)))
                     } else {
                         // Optional, print a warning and continue.
                         let features = required_features.iter().format(",");
@@ -268,8 +893,278 @@ But since it requires features {features}, this bin is ignored."
                 }
             }
         })
-        .collect::<Result<Vec<bins::BinFile>, bins::Error>>()
-        .map_err(BinstallError::from)
+        .collect::<Result<Vec<bins::BinFile>, BinstallError>>()?;
+
+    // Check the binaries before they are installed, e.g. in case the
+    // package is for another architecture, or needs a newer glibc than the
+    // host has in which case a musl target may be tried next.
+    let target = match fetcher.target() {
+        // A universal binary needs a slice for the host.
+        target if target.starts_with("universal") => {
+            format_compact!("{}-apple-darwin", std::env::consts::ARCH)
+        }
+        target => target.into(),
+    };
+    let host_glibc = if target.contains("-linux-gnu") {
+        detect_targets::detect_glibc_version().await
+    } else {
+        None
+    };
+    let bin_files = spawn_blocking(move || {
+        for bin_file in &bin_files {
+            bin_file.check_executable(&target)?;
+            if let Some(host_glibc) = host_glibc {
+                bin_file.check_glibc(host_glibc)?;
+            }
+        }
+        Ok::<_, bins::Error>(bin_files)
+    })
+    .await??;
+
+    Ok(Verified {
+        bin_files,
+        extra_files,
+        artifact_version,
+        verification,
+    })
+}
+
+/// Maximum number of executables listed in [`BinstallError::MissingBinFile`].
+const MAX_LISTED_EXECUTABLES: usize = 10;
+
+/// Return the error for `bin_file` not found in `extracted_files`, with the
+/// executables found instead and the bin-dir which would have found it.
+///
+/// This function uses blocking I/O.
+fn missing_bin_file(
+    bin_file: &bins::BinFile,
+    bin_path: &Path,
+    extracted_files: &ExtractedFiles,
+) -> BinstallError {
+    let file_name = bin_file.archive_source_path.file_name();
+
+    let mut files: Vec<_> = extracted_files.files().collect();
+    files.sort_unstable();
+
+    // The binary in another dir is only found by the default file name
+    // of bin-dir, e.g. `tool` or `tool.exe`.
+    let default_file_name = bin_file.link.as_ref().unwrap_or(&bin_file.dest).file_name();
+    let bin_dir = files
+        .iter()
+        .filter(|path| path.file_name() == file_name && file_name == default_file_name)
+        .find_map(|path| bins::bin_dir_template_for(path.parent()?))
+        .map(CompactString::from);
+
+    let found = files
+        .into_iter()
+        .filter(|path| {
+            path.file_name() == file_name
+                || bins::FileKind::sniff_file(&bin_path.join(path))
+                    .map_or(false, bins::FileKind::is_executable)
+        })
+        .take(MAX_LISTED_EXECUTABLES)
+        .map(|path| path.display().to_compact_string())
+        .collect();
+
+    BinstallError::MissingBinFile {
+        path: (&*bin_file.archive_source_path).into(),
+        found,
+        bin_dir,
+    }
+}
+
+/// Return the filter selecting the entries of the package that could be
+/// installed, so that the rest of it is not extracted: the binaries in any
+/// of the dirs the bin-dir could be inferred from, the extra files requested
+/// and the manifests the version is looked for in.
+///
+/// Return `None` if the whole package is needed.
+fn extract_filter(
+    fetcher: &dyn Fetcher,
+    package_info: &PackageInfo,
+    bin_path: &Path,
+    opts: &Options,
+) -> Option<ExtractFilter> {
+    let meta = fetcher.target_meta();
+    if meta.pkg_fmt.map_or(false, PkgFmt::is_bin) {
+        return None;
+    }
+
+    let bin_data = bins::Data {
+        name: &package_info.name,
+        target: fetcher.target(),
+        version: &package_info.version_str,
+        repo: package_info.repo.as_deref(),
+        meta,
+        bin_path,
+        install_path: &opts.install_path,
+        target_related_info: &fetcher.target_data().target_related_info,
+    };
+
+    let mut paths = Vec::new();
+
+    // Invalid templates are reported by `collect_bin_files` once extracted.
+    for bin_dir in bins::bin_dir_template_candidates(&bin_data) {
+        let template = Template::parse(&bin_dir).ok()?;
+        paths.extend(package_info.binaries.iter().filter_map(|bin| {
+            bins::BinFile::new(&bin_data, bin.name.as_str(), &template, true)
+                .ok()
+                .map(|bin_file| bin_file.archive_source_path)
+        }));
+    }
+
+    // Extra files looked for by their names, as the package does not
+    // specify where they are.
+    let mut detected_kinds = Vec::new();
+
+    if opts.extra_files_dir.is_some() {
+        let dirs = [
+            (
+                bins::ExtraFileKind::Completions,
+                !opts.completion_shells.is_empty(),
+                &bin_data.meta.completions_dir,
+            ),
+            (
+                bins::ExtraFileKind::Man,
+                opts.install_man,
+                &bin_data.meta.man_dir,
+            ),
+        ];
+
+        for (kind, dir) in dirs
+            .into_iter()
+            .filter_map(|(kind, enabled, dir)| enabled.then_some((kind, dir.as_deref())))
+        {
+            let Some(dir) = dir else {
+                detected_kinds.push(kind);
+                continue;
+            };
+            let Ok(template) = Template::parse(dir) else {
+                continue;
+            };
+            let Ok(dir) = bins::ExtraFile::source_dir(&bin_data, &template) else {
+                continue;
+            };
+
+            if dir == Path::new(".") {
+                return None;
+            }
+            paths.push(dir);
+        }
+    }
+
+    let filter = ExtractFilter::paths(paths);
+    let check_version = opts.version_match_policy != VersionMatchPolicy::Ignore;
+    if detected_kinds.is_empty() && !check_version {
+        return Some(filter);
+    }
+
+    let bins: Vec<String> = package_info
+        .binaries
+        .iter()
+        .map(|bin| bin.name.clone())
+        .collect();
+
+    Some(filter.with_extra(move |path| {
+        (check_version && artifact_version::is_manifest_candidate(path))
+            || path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map_or(false, |file_name| {
+                    detected_kinds
+                        .iter()
+                        .any(|kind| kind.is_conventional(file_name, &bins))
+                })
+    }))
+}
+
+/// Compare the resolved version with the version found in the artifact,
+/// unless `opts.version_match_policy` is [`VersionMatchPolicy::Ignore`].
+///
+/// Return `None` if no version is found.
+async fn check_artifact_version(
+    package_info: &PackageInfo,
+    bin_path: &Path,
+    extracted_files: &ExtractedFiles,
+    opts: &Options,
+) -> Result<Option<ArtifactVersion>, BinstallError> {
+    if opts.version_match_policy == VersionMatchPolicy::Ignore {
+        return Ok(None);
+    }
+
+    let manifests = artifact_version::manifest_candidates(bin_path, extracted_files);
+    let name = package_info.name.clone();
+    let Some(found) =
+        spawn_blocking(move || artifact_version::find_version(&manifests, &name)).await?
+    else {
+        debug!("No version found in the artifact of {}", package_info.name);
+        return Ok(None);
+    };
+
+    let resolved = &package_info.version;
+    match artifact_version::versions_match(resolved, &found) {
+        Some(true) => Ok(Some(ArtifactVersion::Matched)),
+        Some(false) if opts.version_match_policy == VersionMatchPolicy::Strict => {
+            Err(BinstallError::VersionMismatch {
+                resolved: resolved.clone(),
+                found,
+            })
+        }
+        Some(false) => {
+            warn!(
+                "The artifact of {} v{resolved} contains version {found}, \
+the release might have been retagged without being rebuilt",
+                package_info.name
+            );
+            Ok(Some(ArtifactVersion::Mismatched { found }))
+        }
+        None => {
+            debug!("Ignoring invalid version '{found}' found in the artifact");
+            Ok(None)
+        }
+    }
+}
+
+/// Refuse to overwrite files in the install path that are not recorded by
+/// binstall or cargo-install, unless `--force` is specified.
+fn check_untracked_destinations(
+    bin_files: &[bins::BinFile],
+    opts: &Options,
+) -> Result<(), BinstallError> {
+    let Some(tracked_bins) = opts.tracked_bins.as_ref() else {
+        return Ok(());
+    };
+
+    if opts.force {
+        return Ok(());
+    }
+
+    let mut first_untracked = None;
+
+    for bin_file in bin_files {
+        if tracked_bins.contains(&bin_file.base_name) {
+            continue;
+        }
+
+        if let Some(existing) = bin_file.existing_dest()? {
+            warn!(
+                "{existing} is not installed by binstall or cargo-install and would be overwritten"
+            );
+
+            if opts.query_package_owner {
+                if let Some(owner) = existing.query_package_owner() {
+                    warn!("It is owned by package {owner}");
+                }
+            }
+
+            first_untracked.get_or_insert_with(|| bin_file.dest.as_path().into());
+        }
+    }
+
+    match first_untracked {
+        Some(path) => Err(BinstallError::UntrackedDestination(path)),
+        None => Ok(()),
+    }
 }
 
 fn collect_bin_files(
@@ -277,10 +1172,17 @@ fn collect_bin_files(
     package_info: &PackageInfo,
     meta: PkgMeta,
     bin_path: &Path,
-    install_path: &Path,
-    no_symlinks: bool,
+    opts: &Options,
     extracted_files: &ExtractedFiles,
 ) -> Result<Vec<bins::BinFile>, BinstallError> {
+    let install_path = opts.crate_install_path(
+        &package_info.name,
+        &package_info.version_str,
+        fetcher.target(),
+        meta.binary_ext(fetcher.target()),
+    );
+    let no_symlinks = opts.no_symlinks;
+
     // List files to be installed
     // based on those found via Cargo.toml
     let bin_data = bins::Data {
@@ -290,7 +1192,7 @@ fn collect_bin_files(
         repo: package_info.repo.as_deref(),
         meta,
         bin_path,
-        install_path,
+        install_path: &install_path,
         target_related_info: &fetcher.target_data().target_related_info,
     };
 
@@ -300,16 +1202,38 @@ fn collect_bin_files(
         .as_deref()
         .map(Cow::Borrowed)
         .unwrap_or_else(|| {
-            bins::infer_bin_dir_template(&bin_data, &mut |p| extracted_files.get_dir(p).is_some())
+            bins::infer_bin_dir_template(
+                &bin_data,
+                &mut |p| extracted_files.get_dir(p).is_some(),
+                extracted_files.single_top_dir().and_then(OsStr::to_str),
+            )
         });
 
     let template = Template::parse(&bin_dir)?;
 
+    // Raw binaries downloaded from one artifact per binary are extracted
+    // under their own name.
+    let bin_per_artifact =
+        fetcher.has_artifact_per_bin() && bin_data.meta.pkg_fmt.map_or(false, PkgFmt::is_bin);
+
     // Create bin_files
     let bin_files = package_info
         .binaries
         .iter()
-        .map(|bin| bins::BinFile::new(&bin_data, bin.name.as_str(), &template, no_symlinks))
+        .map(|bin| {
+            let name = bin.name.as_str();
+            if bin_per_artifact {
+                let bin_path = bin_path.join(name);
+                let bin_data = bins::Data {
+                    bin_path: &bin_path,
+                    meta: bin_data.meta.clone(),
+                    ..bin_data
+                };
+                bins::BinFile::new(&bin_data, name, &template, no_symlinks)
+            } else {
+                bins::BinFile::new(&bin_data, name, &template, no_symlinks)
+            }
+        })
         .collect::<Result<Vec<_>, bins::Error>>()?;
 
     let mut source_set = BTreeSet::new();
@@ -325,6 +1249,128 @@ fn collect_bin_files(
     Ok(bin_files)
 }
 
+/// Collect shell completions and man pages requested by the user.
+///
+/// Extra files are optional, so errors are reported as warnings and the
+/// affected kind of files is skipped.
+fn collect_extra_files(
+    fetcher: &dyn Fetcher,
+    package_info: &PackageInfo,
+    meta: PkgMeta,
+    bin_path: &Path,
+    opts: &Options,
+    extracted_files: &ExtractedFiles,
+) -> Vec<bins::ExtraFile> {
+    let Some(data_dir) = opts.extra_files_dir.as_deref() else {
+        return Vec::new();
+    };
+
+    if meta.pkg_fmt.map_or(false, PkgFmt::is_bin) {
+        return Vec::new();
+    }
+
+    let dirs = [
+        (
+            bins::ExtraFileKind::Completions,
+            !opts.completion_shells.is_empty(),
+            meta.completions_dir.clone(),
+        ),
+        (
+            bins::ExtraFileKind::Man,
+            opts.install_man,
+            meta.man_dir.clone(),
+        ),
+    ];
+
+    let bin_data = bins::Data {
+        name: &package_info.name,
+        target: fetcher.target(),
+        version: &package_info.version_str,
+        repo: package_info.repo.as_deref(),
+        meta,
+        bin_path,
+        install_path: &opts.install_path,
+        target_related_info: &fetcher.target_data().target_related_info,
+    };
+
+    let mut list_files = |dir: &Path| {
+        let file_names = extracted_files.get_dir(dir)?;
+
+        Some(
+            file_names
+                .iter()
+                .map(|file_name| &**file_name)
+                .filter(|file_name| {
+                    let path = if dir == Path::new(".") {
+                        PathBuf::from(file_name)
+                    } else {
+                        dir.join(file_name)
+                    };
+                    extracted_files.has_file(&path)
+                })
+                .filter_map(|file_name| file_name.to_str().map(ToString::to_string))
+                .collect(),
+        )
+    };
+
+    let mut extra_files = Vec::new();
+
+    for (kind, enabled, dir) in dirs {
+        if !enabled {
+            continue;
+        }
+
+        let Some(dir) = dir else {
+            let bins: Vec<&str> = package_info
+                .binaries
+                .iter()
+                .map(|bin| bin.name.as_str())
+                .collect();
+            let files = bins::ExtraFile::detect(
+                kind,
+                &bins,
+                &opts.completion_shells,
+                bin_path,
+                data_dir,
+                extracted_files.files(),
+            );
+
+            if files.is_empty() {
+                info!(
+                    "{} does not specify where its {kind} are in the package and none are named after its binaries, skipping",
+                    package_info.name
+                );
+            }
+            extra_files.extend(files);
+            continue;
+        };
+
+        let res = Template::parse(&dir)
+            .map_err(BinstallError::from)
+            .and_then(|template| {
+                bins::ExtraFile::collect(
+                    &bin_data,
+                    kind,
+                    &template,
+                    &opts.completion_shells,
+                    data_dir,
+                    &mut list_files,
+                )
+                .map_err(BinstallError::from)
+            });
+
+        match res {
+            Ok(files) if files.is_empty() => {
+                warn!("No {kind} found for {} in the package", package_info.name)
+            }
+            Ok(files) => extra_files.extend(files),
+            Err(err) => warn!("Failed to collect {kind} for {}: {err}", package_info.name),
+        }
+    }
+
+    extra_files
+}
+
 struct PackageInfo {
     meta: PkgMeta,
     binaries: Vec<Bin>,
@@ -333,6 +1379,10 @@ struct PackageInfo {
     version: Version,
     repo: Option<String>,
     overrides: BTreeMap<String, PkgOverride>,
+    /// Entries of the denylist which affected the resolution.
+    denylisted: Vec<DeniedVersion>,
+    /// Targets on which this version is denylisted.
+    denied_targets: BTreeSet<CompactString>,
 }
 
 struct Bin {
@@ -342,15 +1392,37 @@ struct Bin {
 
 impl PackageInfo {
     /// Return `None` if already up-to-date.
+    ///
+    ///  * `bins` - if not empty, only these binaries are installed.
+    ///  * `targets` - the desired targets, versions denylisted on all of
+    ///    them are skipped.
+    ///  * `skipped` - versions skipped, e.g. since they have no artifact.
+    #[allow(clippy::too_many_arguments)]
     async fn resolve(
         opts: &Options,
         name: CompactString,
+        bins: &[CompactString],
         curr_version: Option<Version>,
         version_req: &VersionReq,
+        targets: &[&str],
         client: Client,
+        skipped: &[Version],
     ) -> Result<Option<Self>, BinstallError> {
         use CargoTomlFetchOverride::*;
 
+        let denylist = &opts.denylist;
+
+        // Fail before downloading anything if the denylisted version is
+        // explicitly requested.
+        if let Some(version) = denylist::exact_version(version_req) {
+            if let Some(denied) = denylist.denies(&name, &version, targets) {
+                return Err(BinstallError::DenylistedVersion {
+                    version: version.to_compact_string(),
+                    reason: denied.reason.clone(),
+                });
+            }
+        }
+
         // Fetch crate via crates.io, git, or use a local manifest path
         let manifest = match opts.cargo_toml_fetch_override.as_ref() {
             Some(Path(manifest_path)) => {
@@ -360,20 +1432,50 @@ impl PackageInfo {
                 spawn_blocking(move || load_manifest_path(manifest_path, &name)).await??
             }
             #[cfg(feature = "git")]
-            Some(Git(git_url)) => {
-                use helpers::git::{GitCancellationToken, Repository as GitRepository};
+            Some(Git(git_url, reference)) => {
+                use std::sync::atomic::AtomicBool;
+
+                use helpers::git::{
+                    checkout_reference, GitCancellationToken, InterruptOnDrop,
+                    Repository as GitRepository,
+                };
 
                 let git_url = git_url.clone();
+                let reference = reference.clone();
                 let name = name.clone();
                 let cancellation_token = GitCancellationToken::default();
                 // Cancel git operation if the future is cancelled (dropped).
                 let cancel_on_drop = cancellation_token.clone().cancel_on_drop();
+                let interrupt = Arc::new(AtomicBool::new(false));
+                // Interrupt the checkout if the future is cancelled, it is
+                // harmless once the checkout is done.
+                let _interrupt_on_drop = InterruptOnDrop(interrupt.clone());
 
                 let ret = spawn_blocking(move || {
                     let dir = TempDir::new()?;
-                    GitRepository::shallow_clone(git_url, dir.as_ref(), Some(cancellation_token))?;
+                    match reference {
+                        Some(reference) => {
+                            let worktree = dir.path().join("worktree");
+                            checkout_reference(
+                                &git_url,
+                                &reference,
+                                &dir.path().join("repo"),
+                                &worktree,
+                                &interrupt,
+                            )?;
 
-                    load_manifest_from_workspace(dir.as_ref(), &name).map_err(BinstallError::from)
+                            load_manifest_path(&worktree, &name)
+                        }
+                        None => {
+                            GitRepository::shallow_clone(
+                                git_url,
+                                dir.as_ref(),
+                                Some(cancellation_token),
+                            )?;
+
+                            load_manifest_path(dir.as_ref(), &name)
+                        }
+                    }
                 })
                 .await??;
 
@@ -383,9 +1485,11 @@ impl PackageInfo {
                 ret
             }
             None => {
+                let mut excluded = denylist.excluded_versions(&name, targets);
+                excluded.extend_from_slice(skipped);
                 Box::pin(
                     opts.registry
-                        .fetch_crate_matched(client, &name, version_req),
+                        .fetch_crate_matched(client, &name, version_req, &excluded),
                 )
                 .await?
             }
@@ -407,6 +1511,31 @@ impl PackageInfo {
             }
         };
 
+        // The registry already skips the denylisted versions, but the
+        // manifest overrides cannot.
+        if let Some(denied) = denylist.denies(&name, &new_version, targets) {
+            return Err(BinstallError::DenylistedVersion {
+                version: new_version_str,
+                reason: denied.reason.clone(),
+            });
+        }
+
+        let mut denylisted = if opts.cargo_toml_fetch_override.is_none() {
+            denylist.skipped(&name, version_req, &new_version, targets)
+        } else {
+            Vec::new()
+        };
+        let mut denied_targets = BTreeSet::new();
+        for denied in denylist.denied_targets(&name, &new_version) {
+            let target = denied.target.as_deref().unwrap_or_default();
+            if targets.contains(&target) && denied_targets.insert(target.into()) {
+                denylisted.push(denied.clone());
+            }
+        }
+        for denied in &denylisted {
+            debug!("Denylist affected the resolution of {name}: {denied}");
+        }
+
         if let Some(curr_version) = curr_version {
             if new_version == curr_version {
                 info!(
@@ -417,7 +1546,7 @@ impl PackageInfo {
             }
         }
 
-        let (mut meta, binaries): (_, Vec<Bin>) = (
+        let (mut meta, mut binaries): (_, Vec<Bin>) = (
             package
                 .metadata
                 .take()
@@ -435,25 +1564,49 @@ impl PackageInfo {
                 .collect(),
         );
 
+        check_url_templates(&meta)?;
+
         // Check binaries
         if binaries.is_empty() {
-            Err(BinstallError::UnspecifiedBinaries)
-        } else {
-            Ok(Some(Self {
-                overrides: mem::take(&mut meta.overrides),
-                meta,
-                binaries,
-                name,
-                version_str: new_version_str,
-                version: new_version,
-                repo: package.repository().map(ToString::to_string),
-            }))
+            return Err(BinstallError::UnspecifiedBinaries);
+        }
+
+        if let Some(bin) = bins
+            .iter()
+            .find(|bin| !binaries.iter().any(|binary| binary.name == bin.as_str()))
+        {
+            return Err(BinstallError::NoSuchBin {
+                crate_name: name,
+                bin: bin.clone(),
+                available: binaries
+                    .iter()
+                    .map(|binary| binary.name.to_compact_string())
+                    .collect(),
+            });
         }
+        if !bins.is_empty() {
+            binaries.retain(|binary| bins.iter().any(|bin| *bin == binary.name));
+        }
+
+        Ok(Some(Self {
+            overrides: mem::take(&mut meta.overrides),
+            meta,
+            binaries,
+            name,
+            version_str: new_version_str,
+            version: new_version,
+            repo: package.repository().map(ToString::to_string),
+            denylisted,
+            denied_targets,
+        }))
     }
 }
 
 /// Load binstall metadata from the crate `Cargo.toml` at the provided path
 ///
+/// `[workspace.metadata.binstall]` of its workspace is merged under its
+/// `[package.metadata.binstall]`.
+///
 /// This is a blocking function.
 pub fn load_manifest_path<P: AsRef<Path>, N: AsRef<str>>(
     manifest_path: P,
@@ -466,7 +1619,17 @@ pub fn load_manifest_path<P: AsRef<Path>, N: AsRef<str>>(
         );
 
         // Load and parse manifest (this checks file system for binary output names)
-        let manifest = load_manifest_from_workspace(manifest_path, crate_name)?;
+        let (mut manifest, workspace_meta) =
+            load_manifest_and_workspace_metadata::<Meta>(manifest_path, crate_name)?;
+
+        if let Some(workspace_meta) = workspace_meta.and_then(|meta| meta.binstall) {
+            if let Some(package) = &mut manifest.package {
+                let meta = package.metadata.get_or_insert(Meta { binstall: None });
+                meta.binstall
+                    .get_or_insert_with(Default::default)
+                    .inherit_workspace(&workspace_meta);
+            }
+        }
 
         // Return metadata
         Ok(manifest)
@@ -474,3 +1637,153 @@ pub fn load_manifest_path<P: AsRef<Path>, N: AsRef<str>>(
 
     inner(manifest_path.as_ref(), name.as_ref())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_missing_bin_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin_path = dir.path();
+        let mut extracted_files = ExtractedFiles::new();
+        for (path, contents) in [
+            ("tool-v1/bin/tool", "#!/bin/sh\n"),
+            ("tool-v1/bin/helper", "#!/bin/sh\n"),
+            ("tool-v1/README.md", "# tool\n"),
+        ] {
+            let path = Path::new(path);
+            std::fs::create_dir_all(bin_path.join(path.parent().unwrap())).unwrap();
+            std::fs::write(bin_path.join(path), contents).unwrap();
+            extracted_files.add_file(path);
+        }
+
+        let data = bins::Data {
+            name: "tool",
+            target: "x86_64-unknown-linux-gnu",
+            version: "1.0.0",
+            repo: None,
+            meta: PkgMeta::default(),
+            bin_path,
+            install_path: Path::new("bin"),
+            target_related_info: &leon::vals(|_| None),
+        };
+        let template = Template::parse("tool-v1/{ bin }{ binary-ext }").unwrap();
+        let bin_file = bins::BinFile::new(&data, "tool", &template, true).unwrap();
+
+        let err = missing_bin_file(&bin_file, bin_path, &extracted_files);
+        let BinstallError::MissingBinFile {
+            path,
+            found,
+            bin_dir,
+        } = &err
+        else {
+            panic!("Unexpected error {err}");
+        };
+        assert_eq!(&**path, Path::new("tool-v1/tool"));
+        assert_eq!(found, &["tool-v1/bin/helper", "tool-v1/bin/tool"]);
+        assert_eq!(
+            bin_dir.as_deref(),
+            Some("tool-v1/bin/{ bin }{ binary-ext }")
+        );
+        assert_eq!(
+            err.to_string(),
+            "bin file tool-v1/tool not found in the package, which contains \
+tool-v1/bin/helper, tool-v1/bin/tool; bin-dir \"tool-v1/bin/{ bin }{ binary-ext }\" would find it"
+        );
+
+        // The binary is not found under another name.
+        let template = Template::parse("tool-v1/{ bin }-{ target }").unwrap();
+        let bin_file = bins::BinFile::new(&data, "tool", &template, true).unwrap();
+        let BinstallError::MissingBinFile { bin_dir, .. } =
+            missing_bin_file(&bin_file, bin_path, &ExtractedFiles::new())
+        else {
+            panic!("Expected MissingBinFile");
+        };
+        assert_eq!(bin_dir, None);
+    }
+
+    #[test]
+    fn test_tag_has_version() {
+        assert!(tag_has_version("v1.2.3", "1.2.3"));
+        assert!(tag_has_version("1.2.3", "1.2.3"));
+        assert!(tag_has_version("tool-v1.2.3", "1.2.3"));
+        assert!(tag_has_version("tool/1.2.3", "1.2.3"));
+        assert!(!tag_has_version("v11.2.3", "1.2.3"));
+        assert!(!tag_has_version("v0.1.2.3", "1.2.3"));
+        assert!(!tag_has_version("v1.2.30", "1.2.3"));
+    }
+
+    #[test]
+    fn test_has_no_artifact() {
+        let would_need_source_build = |kind| BinaryResolution::WouldNeedSourceBuild {
+            source: ResolutionSource {
+                name: "tool".into(),
+                version: "1.2.3".into(),
+                version_req: "*".into(),
+                bins: Vec::new(),
+                denylisted: Vec::new(),
+            },
+            reasons: vec![
+                StrategyFailure {
+                    strategy: "QuickInstall".into(),
+                    target: "x86_64-unknown-linux-gnu".into(),
+                    kind: StrategyFailureKind::NotFound,
+                },
+                StrategyFailure {
+                    strategy: "GhCrateMeta".into(),
+                    target: "x86_64-unknown-linux-gnu".into(),
+                    kind,
+                },
+            ],
+            disabled_strategies: Vec::new(),
+        };
+
+        assert!(has_no_artifact(&would_need_source_build(
+            StrategyFailureKind::NoBinaries
+        )));
+        // Older versions would be blocked as well.
+        assert!(!has_no_artifact(&would_need_source_build(
+            StrategyFailureKind::GeoBlocked {
+                host: "github.com".into()
+            }
+        )));
+        assert!(!has_no_artifact(&BinaryResolution::ResolvedToBinary(
+            Resolution::AlreadyUpToDate
+        )));
+    }
+
+    #[test]
+    fn test_load_manifest_path_workspace() {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../e2e-tests/manifests/workspace-inheritance");
+
+        let load = |name: &str| {
+            let manifest = load_manifest_path(&workspace, name).unwrap();
+            let package = manifest.package.unwrap();
+            assert_eq!(package.version(), "1.2.3");
+            assert_eq!(
+                package.repository(),
+                Some("https://github.com/cargo-bins/cargo-binstall")
+            );
+            package.metadata.unwrap().binstall.unwrap()
+        };
+
+        // The package takes precedence over the workspace.
+        let meta = load("tool");
+        assert_eq!(
+            meta.pkg_url,
+            ["{ repo }/releases/download/v{ version }/tool-{ target }.tgz"]
+        );
+        assert_eq!(meta.pkg_fmt, Some(PkgFmt::Tgz));
+        assert_eq!(meta.bin_dir.as_deref(), Some("{ bin }{ binary-ext }"));
+
+        // Without its own metadata, the package has the workspace one.
+        let meta = load("plain");
+        assert_eq!(
+            meta.pkg_url,
+            ["{ repo }/releases/download/v{ version }/workspace-{ target }.tgz"]
+        );
+        assert_eq!(meta.pkg_fmt, Some(PkgFmt::Tgz));
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use compact_str::CompactString;
+use semver::Version;
+use tracing::debug;
+
+use crate::helpers::{cargo_toml::Manifest, download::ExtractedFiles};
+
+/// Return paths of `Cargo.toml` at the top-level of the artifact or in one of
+/// its top-level directories.
+/// Return true if `path`, relative to the extracted archive, is one of the
+/// manifests returned by [`manifest_candidates`].
+pub(super) fn is_manifest_candidate(path: &Path) -> bool {
+    path.file_name() == Some(OsStr::new("Cargo.toml")) && path.components().count() <= 2
+}
+
+pub(super) fn manifest_candidates(
+    bin_path: &Path,
+    extracted_files: &ExtractedFiles,
+) -> Vec<PathBuf> {
+    let top_level = Path::new(".");
+
+    let top_level_manifest = Path::new("Cargo.toml");
+    let dir_manifests = extracted_files
+        .get_dir(top_level)
+        .into_iter()
+        .flatten()
+        .map(|name| Path::new(name).join("Cargo.toml"));
+
+    Some(top_level_manifest.to_owned())
+        .into_iter()
+        .chain(dir_manifests)
+        .filter(|path| extracted_files.has_file(path))
+        .map(|path| bin_path.join(path))
+        .collect()
+}
+
+/// Find the version of package `name` in one of the `manifests`.
+///
+/// Manifests of other packages, or whose version is inherited from a
+/// workspace, are skipped.
+///
+/// This is a blocking function.
+pub(super) fn find_version(manifests: &[PathBuf], name: &str) -> Option<CompactString> {
+    manifests.iter().find_map(|path| {
+        let manifest = fs::read(path)
+            .map_err(|err| debug!("Failed to read '{}': {err}", path.display()))
+            .ok()?;
+        let manifest = Manifest::from_slice(&manifest)
+            .map_err(|err| debug!("Failed to parse '{}': {err}", path.display()))
+            .ok()?;
+
+        let package = manifest.package?;
+        if package.name != name {
+            return None;
+        }
+
+        package.version.get().ok().map(CompactString::from)
+    })
+}
+
+/// Compare the resolved version with a version found in the artifact.
+///
+/// The comparison tolerates:
+///  - a leading `v` and surrounding whitespace in `found`,
+///  - build metadata, which is ignored on both sides,
+///  - missing or extra trailing zero segments in `found`, e.g. `1.4` and
+///    `1.4.0.0` both match `1.4.0`.
+///
+/// The pre-release must be identical.
+///
+/// Return `None` if `found` is not a version.
+pub(super) fn versions_match(resolved: &Version, found: &str) -> Option<bool> {
+    let found = found.trim();
+    let found = found.strip_prefix('v').unwrap_or(found);
+    let found = found.split_once('+').map_or(found, |(found, _build)| found);
+    let (core, pre) = found.split_once('-').unwrap_or((found, ""));
+
+    let mut segments = core.split('.').map(|segment| {
+        if segment.is_empty() || !segment.bytes().all(|b| b.is_ascii_digit()) {
+            None
+        } else {
+            segment.parse::<u64>().ok()
+        }
+    });
+
+    let major = segments.next()??;
+    let minor = segments.next().unwrap_or(Some(0))?;
+    let patch = segments.next().unwrap_or(Some(0))?;
+
+    let mut matched = (major, minor, patch) == (resolved.major, resolved.minor, resolved.patch)
+        && pre == resolved.pre.as_str();
+
+    for segment in segments {
+        matched &= segment? == 0;
+    }
+
+    Some(matched)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_versions_match() {
+        let resolved = Version::parse("1.4.0").unwrap();
+
+        for found in ["1.4.0", "v1.4.0", " 1.4.0\n", "1.4", "1.4.0.0", "1.4.0+abc"] {
+            assert_eq!(versions_match(&resolved, found), Some(true), "{found}");
+        }
+
+        for found in ["1.3.9", "1.4.1", "1", "1.4.0.1", "1.4.0-rc.1", "2.4.0"] {
+            assert_eq!(versions_match(&resolved, found), Some(false), "{found}");
+        }
+
+        for found in ["", "abc", "1..0", "1.4.x", "1.4.-1", "vv1.4.0"] {
+            assert_eq!(versions_match(&resolved, found), None, "{found}");
+        }
+
+        let resolved = Version::parse("1.0.0-rc.1+build.5").unwrap();
+        assert_eq!(versions_match(&resolved, "1.0.0-rc.1"), Some(true));
+        assert_eq!(versions_match(&resolved, "1-rc.1+build.6"), Some(true));
+        assert_eq!(versions_match(&resolved, "1.0.0"), Some(false));
+        assert_eq!(versions_match(&resolved, "1.0.0-rc.2"), Some(false));
+    }
+
+    #[test]
+    fn test_is_manifest_candidate() {
+        assert!(is_manifest_candidate(Path::new("Cargo.toml")));
+        assert!(is_manifest_candidate(Path::new(
+            "cargo-binstall-1.4.0/Cargo.toml"
+        )));
+
+        assert!(!is_manifest_candidate(Path::new("vendor/dep/Cargo.toml")));
+        assert!(!is_manifest_candidate(Path::new("Cargo.toml.orig")));
+        assert!(!is_manifest_candidate(Path::new("Cargo.lock")));
+    }
+
+    #[test]
+    fn test_find_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin_path = dir.path();
+
+        fs::create_dir_all(bin_path.join("cargo-binstall-1.4.0")).unwrap();
+        fs::create_dir_all(bin_path.join("vendor")).unwrap();
+        fs::write(
+            bin_path.join("vendor/Cargo.toml"),
+            "[package]\nname = \"dep\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(
+            bin_path.join("cargo-binstall-1.4.0/Cargo.toml"),
+            "[package]\nname = \"cargo-binstall\"\nversion = \"1.3.9\"\n",
+        )
+        .unwrap();
+
+        let mut extracted_files = ExtractedFiles::new();
+        extracted_files.add_file(Path::new("vendor/Cargo.toml"));
+        extracted_files.add_file(Path::new("cargo-binstall-1.4.0/Cargo.toml"));
+
+        let manifests = manifest_candidates(bin_path, &extracted_files);
+        assert_eq!(manifests.len(), 2);
+
+        assert_eq!(
+            find_version(&manifests, "cargo-binstall").as_deref(),
+            Some("1.3.9")
+        );
+        assert_eq!(find_version(&manifests, "cargo-audit"), None);
+    }
+}
@@ -1,8 +1,8 @@
-use std::{fmt, str::FromStr};
+use std::{fmt, mem, str::FromStr};
 
 use compact_str::CompactString;
-use itertools::Itertools;
-use semver::{Error, VersionReq};
+use semver::VersionReq;
+use thiserror::Error as ThisError;
 
 use super::version_ext::VersionReqExt;
 
@@ -10,12 +10,67 @@ use super::version_ext::VersionReqExt;
 pub struct CrateName {
     pub name: CompactString,
     pub version_req: Option<VersionReq>,
+    /// Only install these binaries of the crate, all of them if empty.
+    pub bins: Vec<CompactString>,
+}
+
+/// A binary to install from the crate providing it, e.g. `:sqlx`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BinSpec {
+    pub bin: CompactString,
+    pub version_req: Option<VersionReq>,
+}
+
+/// A crate to install, or only a binary to find the crate providing it
+/// from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CrateSpec {
+    Crate(CrateName),
+    Bin(BinSpec),
+}
+
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum CrateNameParseError {
+    #[error("invalid version requirement: {0}")]
+    Version(#[from] semver::Error),
+
+    #[error("`{0}` looks like a git url, use --git to install from a git repository")]
+    GitUrl(CompactString),
+
+    #[error("invalid crate name `{0}`")]
+    InvalidName(CompactString),
+
+    #[error("invalid binary name `{0}`")]
+    InvalidBin(CompactString),
+
+    #[error("missing crate name, expected `crate[:bin][@version]`")]
+    MissingName,
+
+    #[error("expected `crate[:bin][@version]` or `:bin[@version]`")]
+    Empty,
 }
 
 impl fmt::Display for CrateName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)?;
 
+        if !self.bins.is_empty() {
+            write!(f, ":{}", self.bins.join(","))?;
+        }
+
+        if let Some(version) = &self.version_req {
+            write!(f, "@{version}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for BinSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ":{}", self.bin)?;
+
         if let Some(version) = &self.version_req {
             write!(f, "@{version}")?;
         }
@@ -24,34 +79,113 @@ impl fmt::Display for CrateName {
     }
 }
 
+impl BinSpec {
+    /// Install the binary from crate `name`.
+    pub fn with_crate(self, name: CompactString) -> CrateName {
+        CrateName {
+            name,
+            version_req: self.version_req,
+            bins: vec![self.bin],
+        }
+    }
+}
+
 impl FromStr for CrateName {
-    type Err = Error;
+    type Err = CrateNameParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(if let Some((name, version)) = s.split_once('@') {
-            CrateName {
-                name: name.into(),
-                version_req: Some(VersionReq::parse_from_cli(version)?),
+        match s.parse()? {
+            CrateSpec::Crate(crate_name) => Ok(crate_name),
+            CrateSpec::Bin(_) => Err(CrateNameParseError::MissingName),
+        }
+    }
+}
+
+impl fmt::Display for CrateSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Crate(crate_name) => crate_name.fmt(f),
+            Self::Bin(bin_spec) => bin_spec.fmt(f),
+        }
+    }
+}
+
+impl FromStr for CrateSpec {
+    type Err = CrateNameParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains("://") || s.starts_with("git@") {
+            return Err(CrateNameParseError::GitUrl(s.into()));
+        }
+
+        let (spec, version_req) = match s.split_once('@') {
+            Some((spec, version)) => (spec, Some(VersionReq::parse_from_cli(version)?)),
+            None => (s, None),
+        };
+
+        let (name, bin) = match spec.split_once(':') {
+            Some((name, bin)) => {
+                if !is_valid_name(bin) {
+                    return Err(CrateNameParseError::InvalidBin(bin.into()));
+                }
+                (name, Some(bin.into()))
             }
-        } else {
-            CrateName {
-                name: s.into(),
-                version_req: None,
+            None => (spec, None),
+        };
+
+        if name.is_empty() {
+            return match bin {
+                Some(bin) => Ok(CrateSpec::Bin(BinSpec { bin, version_req })),
+                None => Err(CrateNameParseError::Empty),
+            };
+        } else if !is_valid_name(name) {
+            return Err(CrateNameParseError::InvalidName(name.into()));
+        }
+
+        Ok(CrateSpec::Crate(CrateName {
+            name: name.into(),
+            version_req,
+            bins: bin.into_iter().collect(),
+        }))
+    }
+}
+
+impl CrateSpec {
+    /// Split `specs` into the crates and the binaries only.
+    pub fn partition(specs: Vec<Self>) -> (Vec<CrateName>, Vec<BinSpec>) {
+        let mut crate_names = Vec::new();
+        let mut bins = Vec::new();
+        for spec in specs {
+            match spec {
+                Self::Crate(crate_name) => crate_names.push(crate_name),
+                Self::Bin(bin) => bins.push(bin),
             }
-        })
+        }
+        (crate_names, bins)
     }
 }
 
+/// Check that `name` only contains characters allowed in crate and binary
+/// names.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
 impl CrateName {
     pub fn dedup(mut crate_names: Vec<Self>) -> impl Iterator<Item = Self> {
         crate_names.sort_by(|x, y| x.name.cmp(&y.name));
-        crate_names.into_iter().coalesce(|previous, current| {
-            if previous.name == current.name {
-                Ok(current)
-            } else {
-                Err((previous, current))
+        // Keep the last one of the duplicates.
+        crate_names.dedup_by(|current, previous| {
+            let duplicate = previous.name == current.name;
+            if duplicate {
+                mem::swap(previous, current);
             }
-        })
+            duplicate
+        });
+        crate_names.into_iter()
     }
 }
 
@@ -63,11 +197,13 @@ mod tests {
         ([ $( ( $input_name:expr, $input_version:expr ) ),*  ], [ $( ( $output_name:expr, $output_version:expr ) ),*  ]) => {
             let input_crate_names = vec![$( CrateName {
                 name: $input_name.into(),
-                version_req: Some($input_version.parse().unwrap())
+                version_req: Some($input_version.parse().unwrap()),
+                bins: Vec::new(),
             }, )*];
 
             let mut output_crate_names: Vec<CrateName> = vec![$( CrateName {
-                name: $output_name.into(), version_req: Some($output_version.parse().unwrap())
+                name: $output_name.into(), version_req: Some($output_version.parse().unwrap()),
+                bins: Vec::new(),
             }, )*];
             output_crate_names.sort_by(|x, y| x.name.cmp(&y.name));
 
@@ -108,4 +244,132 @@ mod tests {
             [("a", "1"), ("b", "0"), ("d", "23")]
         );
     }
+
+    fn parse(s: &str) -> (String, Option<String>, Option<String>) {
+        let crate_name: CrateName = s.parse().unwrap();
+        assert_eq!(crate_name.to_string(), s);
+
+        (
+            crate_name.name.into(),
+            crate_name.version_req.map(|req| req.to_string()),
+            crate_name.bins.into_iter().next().map(Into::into),
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let some = |s: &str| Some(s.to_string());
+
+        assert_eq!(parse("sqlx-cli"), ("sqlx-cli".into(), None, None));
+        assert_eq!(
+            parse("sqlx-cli@=0.7.4"),
+            ("sqlx-cli".into(), some("=0.7.4"), None)
+        );
+        assert_eq!(
+            parse("sqlx-cli:sqlx"),
+            ("sqlx-cli".into(), None, some("sqlx"))
+        );
+        assert_eq!(
+            parse("sqlx-cli:sqlx@=0.7.4"),
+            ("sqlx-cli".into(), some("=0.7.4"), some("sqlx"))
+        );
+
+        // A bare version is an exact requirement.
+        let crate_name: CrateName = "sqlx-cli@0.7.4".parse().unwrap();
+        assert_eq!(crate_name.to_string(), "sqlx-cli@=0.7.4");
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        let bin = |s: &str, version: Option<&str>| {
+            CrateSpec::Bin(BinSpec {
+                bin: s.into(),
+                version_req: version.map(|version| version.parse().unwrap()),
+            })
+        };
+
+        assert_eq!(":sqlx".parse::<CrateSpec>().unwrap(), bin("sqlx", None));
+        assert_eq!(
+            ":sqlx@=0.7.4".parse::<CrateSpec>().unwrap(),
+            bin("sqlx", Some("=0.7.4"))
+        );
+        assert_eq!(
+            ":sqlx@0.7.4".parse::<CrateSpec>().unwrap().to_string(),
+            ":sqlx@=0.7.4"
+        );
+        assert_eq!(
+            "sqlx-cli:sqlx".parse::<CrateSpec>().unwrap(),
+            CrateSpec::Crate("sqlx-cli:sqlx".parse().unwrap())
+        );
+        assert!(matches!(
+            ":sqlx@x.y".parse::<CrateSpec>(),
+            Err(CrateNameParseError::Version(_))
+        ));
+
+        // Crate names are never empty.
+        assert!(matches!(
+            ":sqlx".parse::<CrateName>(),
+            Err(CrateNameParseError::MissingName)
+        ));
+
+        let (crate_names, bins) = CrateSpec::partition(vec![
+            ":a".parse().unwrap(),
+            "b".parse().unwrap(),
+            ":c".parse().unwrap(),
+        ]);
+        assert_eq!(crate_names, ["b".parse().unwrap()]);
+        let bins: Vec<_> = bins.into_iter().map(|bin_spec| bin_spec.bin).collect();
+        assert_eq!(bins, ["a", "c"]);
+
+        let crate_name = BinSpec {
+            bin: "sqlx".into(),
+            version_req: Some("=0.7.4".parse().unwrap()),
+        }
+        .with_crate("sqlx-cli".into());
+        assert_eq!(crate_name, "sqlx-cli:sqlx@=0.7.4".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_error() {
+        for s in [
+            "git@github.com:launchbadge/sqlx.git",
+            "https://github.com/launchbadge/sqlx",
+            "https://github.com/launchbadge/sqlx@v0.7.4",
+            "ssh://git@github.com/launchbadge/sqlx",
+        ] {
+            assert!(
+                matches!(s.parse::<CrateName>(), Err(CrateNameParseError::GitUrl(_))),
+                "{s}"
+            );
+        }
+
+        assert!(matches!(
+            "".parse::<CrateName>(),
+            Err(CrateNameParseError::Empty)
+        ));
+        assert!(matches!(
+            "@1.0".parse::<CrateName>(),
+            Err(CrateNameParseError::Empty)
+        ));
+        assert!(matches!(
+            ":".parse::<CrateName>(),
+            Err(CrateNameParseError::InvalidBin(_))
+        ));
+        assert!(matches!(
+            "sqlx-cli:".parse::<CrateName>(),
+            Err(CrateNameParseError::InvalidBin(_))
+        ));
+        assert!(matches!(
+            "sqlx-cli:sqlx:sqlx".parse::<CrateName>(),
+            Err(CrateNameParseError::InvalidBin(_))
+        ));
+        assert!(matches!(
+            "github.com/launchbadge/sqlx".parse::<CrateName>(),
+            Err(CrateNameParseError::InvalidName(_))
+        ));
+        assert!(matches!(
+            "sqlx-cli@x.y".parse::<CrateName>(),
+            Err(CrateNameParseError::Version(_))
+        ));
+    }
 }
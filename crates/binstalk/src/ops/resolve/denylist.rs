@@ -0,0 +1,259 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use compact_str::CompactString;
+use semver::{Op, Version, VersionReq};
+
+/// Versions of crates whose release artifacts are known to be broken.
+///
+/// They are excluded from version resolution as if they were yanked.
+#[derive(Clone, Debug, Default)]
+pub struct Denylist(BTreeMap<CompactString, Vec<DeniedVersion>>);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeniedVersion {
+    pub version: Version,
+    /// Only deny the artifacts of this target.
+    pub target: Option<CompactString>,
+    /// Why the version is denied, shown to the user.
+    pub reason: Option<CompactString>,
+}
+
+impl DeniedVersion {
+    /// Build metadata is ignored, like the registry does.
+    fn matches(&self, version: &Version) -> bool {
+        self.version.cmp_precedence(version).is_eq()
+    }
+}
+
+impl fmt::Display for DeniedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.version)?;
+        if let Some(target) = &self.target {
+            write!(f, " ({target})")?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Denylist {
+    pub fn add(&mut self, crate_name: impl Into<CompactString>, denied: DeniedVersion) {
+        self.0.entry(crate_name.into()).or_default().push(denied);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn get(&self, crate_name: &str) -> &[DeniedVersion] {
+        self.0
+            .get(crate_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Return the entry denying `version` of `crate_name` on all `targets`.
+    pub(super) fn denies(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        targets: &[&str],
+    ) -> Option<&DeniedVersion> {
+        let mut first = None;
+        let mut denied_targets = BTreeSet::new();
+
+        for entry in self.get(crate_name).iter().filter(|e| e.matches(version)) {
+            match &entry.target {
+                None => return Some(entry),
+                Some(target) => {
+                    first.get_or_insert(entry);
+                    denied_targets.insert(target.as_str());
+                }
+            }
+        }
+
+        if !targets.is_empty() && targets.iter().all(|t| denied_targets.contains(t)) {
+            first
+        } else {
+            None
+        }
+    }
+
+    /// Return the versions of `crate_name` denied on all `targets`, which
+    /// are to be excluded from version resolution.
+    pub(super) fn excluded_versions(&self, crate_name: &str, targets: &[&str]) -> Vec<Version> {
+        let mut excluded: Vec<Version> = Vec::new();
+
+        for entry in self.get(crate_name) {
+            if !excluded.iter().any(|version| entry.matches(version))
+                && self.denies(crate_name, &entry.version, targets).is_some()
+            {
+                excluded.push(entry.version.clone());
+            }
+        }
+
+        excluded
+    }
+
+    /// Return the entries denying `version` of `crate_name` on some of the
+    /// targets only.
+    pub(super) fn denied_targets<'a>(
+        &'a self,
+        crate_name: &str,
+        version: &'a Version,
+    ) -> impl Iterator<Item = &'a DeniedVersion> + 'a {
+        self.get(crate_name)
+            .iter()
+            .filter(move |entry| entry.target.is_some() && entry.matches(version))
+    }
+
+    /// Return the entries which made the resolution of `version_req` skip
+    /// versions newer than `resolved`.
+    pub(super) fn skipped(
+        &self,
+        crate_name: &str,
+        version_req: &VersionReq,
+        resolved: &Version,
+        targets: &[&str],
+    ) -> Vec<DeniedVersion> {
+        let mut skipped: Vec<DeniedVersion> = self
+            .excluded_versions(crate_name, targets)
+            .into_iter()
+            .filter(|version| version_req.matches(version) && version > resolved)
+            .filter_map(|version| self.denies(crate_name, &version, targets).cloned())
+            .collect();
+        skipped.sort_by(|x, y| y.version.cmp(&x.version));
+        skipped
+    }
+}
+
+/// Return the version requested by `version_req` if it only matches one.
+pub(super) fn exact_version(version_req: &VersionReq) -> Option<Version> {
+    match &version_req.comparators[..] {
+        [comparator] if comparator.op == Op::Exact => Some(Version {
+            major: comparator.major,
+            minor: comparator.minor?,
+            patch: comparator.patch?,
+            pre: comparator.pre.clone(),
+            build: Default::default(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).unwrap()
+    }
+
+    fn denylist() -> Denylist {
+        let mut denylist = Denylist::default();
+        denylist.add(
+            "cargo-binstall",
+            DeniedVersion {
+                version: version("1.4.1"),
+                target: None,
+                reason: Some("truncated upload".into()),
+            },
+        );
+        denylist.add(
+            "cargo-binstall",
+            DeniedVersion {
+                version: version("1.4.0"),
+                target: Some("x86_64-unknown-linux-musl".into()),
+                reason: None,
+            },
+        );
+        denylist
+    }
+
+    #[test]
+    fn test_denies() {
+        let denylist = denylist();
+        let musl = ["x86_64-unknown-linux-musl"];
+        let both = ["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"];
+
+        let denied = denylist
+            .denies("cargo-binstall", &version("1.4.1"), &both)
+            .unwrap();
+        assert_eq!(denied.reason.as_deref(), Some("truncated upload"));
+        assert!(denylist
+            .denies("cargo-binstall", &version("1.4.1+build"), &musl)
+            .is_some());
+
+        // Only denied on some of the targets.
+        assert!(denylist
+            .denies("cargo-binstall", &version("1.4.0"), &both)
+            .is_none());
+        assert!(denylist
+            .denies("cargo-binstall", &version("1.4.0"), &musl)
+            .is_some());
+        assert_eq!(
+            denylist
+                .denied_targets("cargo-binstall", &version("1.4.0"))
+                .count(),
+            1
+        );
+
+        assert!(denylist
+            .denies("cargo-binstall", &version("1.3.0"), &both)
+            .is_none());
+        assert!(denylist
+            .denies("cargo-audit", &version("1.4.1"), &both)
+            .is_none());
+    }
+
+    #[test]
+    fn test_skipped() {
+        let denylist = denylist();
+        let musl = ["x86_64-unknown-linux-musl"];
+        let gnu = ["x86_64-unknown-linux-gnu"];
+
+        assert_eq!(
+            denylist.excluded_versions("cargo-binstall", &musl),
+            [version("1.4.1"), version("1.4.0")]
+        );
+        assert_eq!(
+            denylist.excluded_versions("cargo-binstall", &gnu),
+            [version("1.4.1")]
+        );
+
+        // `^1.3` skips to 1.3.9 on musl, where both 1.4.1 and 1.4.0 are
+        // denied.
+        let version_req = VersionReq::parse("^1.3").unwrap();
+        let skipped = denylist.skipped("cargo-binstall", &version_req, &version("1.3.9"), &musl);
+        assert_eq!(
+            skipped.iter().map(|s| &s.version).collect::<Vec<_>>(),
+            [&version("1.4.1"), &version("1.4.0")]
+        );
+
+        let skipped = denylist.skipped("cargo-binstall", &version_req, &version("1.4.0"), &gnu);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].to_string(), "v1.4.1: truncated upload");
+
+        // Denied versions outside of the requirement do not count.
+        let version_req = VersionReq::parse("~1.3").unwrap();
+        assert!(denylist
+            .skipped("cargo-binstall", &version_req, &version("1.3.9"), &musl)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_exact_version() {
+        let exact = |req: &str| exact_version(&VersionReq::parse(req).unwrap());
+
+        assert_eq!(exact("=1.4.0"), Some(version("1.4.0")));
+        assert_eq!(exact("=1.4.0-rc.1"), Some(version("1.4.0-rc.1")));
+        assert_eq!(exact("=1.4"), None);
+        assert_eq!(exact("^1.4.0"), None);
+        assert_eq!(exact(">=1.4.0, <2"), None);
+    }
+}
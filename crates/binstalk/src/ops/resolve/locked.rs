@@ -0,0 +1,417 @@
+//! Resolution of the crates to the artifacts recorded in a lockfile, by
+//! `--from-lockfile`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+
+use compact_str::{format_compact, CompactString, ToCompactString};
+use semver::Version;
+use tracing::{debug, field, info, instrument, warn, Instrument, Span};
+
+use super::{
+    check_untracked_destinations, download_extract_and_verify, fetcher_span, Bin, PackageInfo,
+    Resolution, ResolutionFetch, StagingDir, Verified,
+};
+use crate::{
+    bins,
+    errors::BinstallError,
+    fetchers::{ChecksumPolicy, Data, Fetcher, GhCrateMeta, SignaturePolicy, TargetData},
+    helpers::{target_triple::TargetTriple, tasks::AutoAbortJoinHandle},
+    manifests::{
+        cargo_toml_binstall::{default_binary_ext, PkgMeta},
+        crate_info::{FetchSource, LockedCrate, Verification},
+    },
+    ops::{progress::Progress, stats::Phase, Options},
+};
+
+impl ResolutionFetch {
+    /// Return the entry of the lockfile recording the artifact installed,
+    /// `None` if it cannot be installed again from a lockfile, e.g. with
+    /// `--dry-run` which does not download it.
+    pub fn lock(&self) -> Option<LockedCrate> {
+        let name = &self.name;
+        let target = self.fetcher.target();
+
+        if self.repaired.is_some() {
+            debug!("Not locking {name}, only its broken binaries are reinstalled");
+            return None;
+        }
+        let Some(verification) = &self.verification else {
+            warn!("Not locking {name}, its package is not downloaded");
+            return None;
+        };
+        let url = match self.fetcher.fetch_source() {
+            FetchSource::GhCrateMeta { url, .. } | FetchSource::QuickInstall { url } => url,
+            FetchSource::SourceBuild => return None,
+        };
+        let meta = self.fetcher.target_meta();
+        if self.fetcher.has_artifact_per_bin()
+            || meta.binary_ext(target) != default_binary_ext(target)
+        {
+            warn!("Not locking {name}, its binaries cannot be recorded in the lockfile");
+            return None;
+        }
+
+        let pkg_fmt = self.fetcher.pkg_fmt();
+        let bin_dir = if pkg_fmt.is_bin() {
+            None
+        } else {
+            let Some(bin_dir) = common_bin_dir(&self.bin_files) else {
+                warn!(
+                    "Not locking {name}, its binaries are not in the same directory of the package"
+                );
+                return None;
+            };
+            Some(bin_dir)
+        };
+
+        let binary_ext = default_binary_ext(target);
+        let bins = self
+            .bin_files
+            .iter()
+            .map(|bin_file| {
+                bin_file
+                    .base_name
+                    .strip_suffix(binary_ext)
+                    .unwrap_or(&bin_file.base_name)
+                    .into()
+            })
+            .collect();
+
+        Some(LockedCrate {
+            name: name.clone(),
+            version: self.new_version.clone(),
+            target: target.into(),
+            url,
+            sha256: verification.sha256.clone(),
+            pkg_fmt,
+            bin_dir,
+            bins,
+        })
+    }
+}
+
+/// Return the bin-dir finding all of `bin_files` in the package, if they
+/// are in the same directory under their own name.
+fn common_bin_dir(bin_files: &[bins::BinFile]) -> Option<CompactString> {
+    let dir = bin_files.first()?.archive_source_path.parent()?;
+
+    bin_files
+        .iter()
+        .all(|bin_file| {
+            let path = &bin_file.archive_source_path;
+            path.parent() == Some(dir)
+                && path.file_name() == Some(OsStr::new(bin_file.base_name.as_str()))
+        })
+        .then(|| bins::bin_dir_template_for(dir))
+        .flatten()
+        .map(CompactString::from)
+}
+
+/// Fail with [`BinstallError::ChecksumMismatch`] if the package downloaded
+/// is not the one recorded in the lockfile.
+fn check_digest(locked: &LockedCrate, verification: &Verification) -> Result<(), BinstallError> {
+    if locked.sha256.eq_ignore_ascii_case(&verification.sha256) {
+        Ok(())
+    } else {
+        Err(BinstallError::ChecksumMismatch {
+            expected: locked.sha256.clone(),
+            actual: verification.sha256.clone(),
+        })
+    }
+}
+
+/// Return the entry for the first of `targets` which has one, they are in
+/// order of preference.
+fn select<'a>(entries: &'a [LockedCrate], targets: &[&str]) -> Option<&'a LockedCrate> {
+    targets
+        .iter()
+        .find_map(|target| entries.iter().find(|entry| entry.target == *target))
+}
+
+/// Resolve crate `name` to the artifact recorded for it in a lockfile, for
+/// the first of the desired targets one of `entries` is for.
+///
+/// The registry is not queried, and the artifact must match the digest
+/// recorded in the lockfile. It is already up-to-date if `curr_version` is
+/// the version recorded, unless [`Options::force`] is set.
+#[instrument(skip_all, fields(name = %name, version = field::Empty))]
+pub async fn resolve_locked(
+    opts: Arc<Options>,
+    name: CompactString,
+    entries: Vec<LockedCrate>,
+    curr_version: Option<Version>,
+) -> Result<Resolution, BinstallError> {
+    let start = Instant::now();
+    let res = opts
+        .cancellable(resolve_locked_inner(
+            opts.clone(),
+            &name,
+            &entries,
+            curr_version,
+        ))
+        .await;
+    opts.stats.add_elapsed(Phase::Resolve, start.elapsed());
+
+    res.map_err(|err| {
+        let err = err.crate_context(&*name);
+        opts.report_progress(&name, Progress::Failed { error: &err });
+        err
+    })
+}
+
+async fn resolve_locked_inner(
+    opts: Arc<Options>,
+    name: &str,
+    entries: &[LockedCrate],
+    curr_version: Option<Version>,
+) -> Result<Resolution, BinstallError> {
+    info!("Resolving package: '{name}' from the lockfile");
+    opts.report_progress(name, Progress::Resolving);
+
+    let desired_targets = opts.desired_targets.get().await;
+    let targets: Vec<&str> = desired_targets.iter().map(String::as_str).collect();
+    let locked = select(entries, &targets).ok_or_else(|| BinstallError::NoLockedTarget {
+        targets: targets.join(", ").into(),
+    })?;
+    Span::current().record("version", field::display(&locked.version));
+
+    if !opts.force && curr_version.as_ref() == Some(&locked.version) {
+        info!(
+            "{name} v{} is already installed, use --force to override",
+            locked.version
+        );
+        return Ok(Resolution::AlreadyUpToDate);
+    }
+
+    if let Some(denied) = opts
+        .denylist
+        .denies(name, &locked.version, &[locked.target.as_str()])
+    {
+        return Err(BinstallError::DenylistedVersion {
+            version: locked.version.to_compact_string(),
+            reason: denied.reason.clone(),
+        });
+    }
+
+    let package_info = PackageInfo {
+        meta: PkgMeta {
+            pkg_url: vec![locked.url.to_string()],
+            pkg_fmt: Some(locked.pkg_fmt),
+            bin_dir: locked.bin_dir.as_deref().map(String::from),
+            ..Default::default()
+        },
+        binaries: locked
+            .bins
+            .iter()
+            .map(|bin| Bin {
+                name: bin.to_string(),
+                required_features: Vec::new(),
+            })
+            .collect(),
+        name: locked.name.clone(),
+        version_str: locked.version.to_compact_string(),
+        version: locked.version.clone(),
+        repo: None,
+        overrides: BTreeMap::new(),
+        denylisted: Vec::new(),
+        denied_targets: BTreeSet::new(),
+    };
+
+    let data = Arc::new(
+        Data::new(
+            package_info.name.clone(),
+            package_info.version_str.clone(),
+            None,
+        )
+        .with_allow_insecure_http(opts.allow_insecure_http)
+        .with_url_rewrites(opts.url_rewrites.clone()),
+    );
+    let target_data = Arc::new(TargetData {
+        target: locked.target.to_string(),
+        meta: package_info.meta.clone(),
+        target_related_info: TargetTriple::from_str(&locked.target)?,
+    });
+    // The digest recorded pins the artifact, whatever the crate publishes
+    // to verify it.
+    let fetcher = GhCrateMeta::new(
+        opts.client.clone(),
+        opts.gh_api_client.clone(),
+        opts.gl_api_client.clone(),
+        opts.gitea_api_client.clone(),
+        data,
+        target_data,
+        SignaturePolicy::Ignore,
+        ChecksumPolicy::IfPresent,
+        opts.artifact_cache.clone(),
+    );
+
+    let handle = fetcher_span(&*fetcher).in_scope(|| fetcher.clone().find());
+    if !AutoAbortJoinHandle::new(handle).flattened_join().await? {
+        return Err(BinstallError::LockedArtifactMissing {
+            url: locked.url.as_str().into(),
+        });
+    }
+    let download_size = fetcher.report_size().await.unwrap_or_else(|err| {
+        debug!("Failed to get the size of the package: {err}");
+        None
+    });
+    opts.report_progress(
+        name,
+        Progress::Found {
+            source: &fetcher.source_name(),
+            target: fetcher.target(),
+            size: download_size,
+        },
+    );
+
+    let start = Instant::now();
+
+    let staging_dir = StagingDir(Some(
+        opts.temp_dir
+            .join(format!("bin-{name}-{}-locked", fetcher.target())),
+    ));
+    let Verified {
+        bin_files,
+        extra_files,
+        artifact_version,
+        verification,
+    } = download_extract_and_verify(fetcher.as_ref(), staging_dir.path(), &package_info, &opts)
+        .instrument(fetcher_span(&*fetcher))
+        .await?;
+    check_digest(locked, &verification)?;
+
+    let elapsed = start.elapsed();
+    opts.stats.add_elapsed(Phase::Download, elapsed);
+    opts.report_progress(
+        name,
+        Progress::Verified {
+            verification: &verification,
+        },
+    );
+    opts.report_progress(name, Progress::Downloaded { elapsed });
+
+    check_untracked_destinations(&bin_files, &opts)?;
+    opts.stats.add_strategy(fetcher.fetcher_name());
+    // It is removed along with `Options::temp_dir` once installed.
+    staging_dir.keep();
+
+    Ok(Resolution::Fetch(Box::new(ResolutionFetch {
+        fetcher,
+        new_version: package_info.version,
+        name: package_info.name,
+        version_req: format_compact!("={}", locked.version),
+        bin_files,
+        selected_bins: Vec::new(),
+        extra_files,
+        artifact_version,
+        verification: Some(verification),
+        denylisted: Vec::new(),
+        repaired: None,
+        download_size,
+    })))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use url::Url;
+
+    use crate::manifests::{cargo_toml_binstall::PkgFmt, crate_info::SignatureCheck};
+
+    use super::*;
+
+    fn bin_file(base_name: &str, archive_source_path: &str) -> bins::BinFile {
+        bins::BinFile {
+            base_name: base_name.into(),
+            source: PathBuf::from("/tmp/staging").join(archive_source_path),
+            archive_source_path: archive_source_path.into(),
+            dest: PathBuf::from("/usr/local/bin").join(base_name),
+            link: None,
+        }
+    }
+
+    #[test]
+    fn test_common_bin_dir() {
+        assert_eq!(
+            common_bin_dir(&[bin_file("a", "a"), bin_file("b", "b")]).as_deref(),
+            Some("{ bin }{ binary-ext }")
+        );
+        assert_eq!(
+            common_bin_dir(&[
+                bin_file("a", "tool-v1.0.0/a"),
+                bin_file("b", "tool-v1.0.0/b")
+            ])
+            .as_deref(),
+            Some("tool-v1.0.0/{ bin }{ binary-ext }")
+        );
+
+        // Renamed, or in different dirs.
+        assert_eq!(common_bin_dir(&[bin_file("a", "a-x86_64")]), None);
+        assert_eq!(
+            common_bin_dir(&[bin_file("a", "a"), bin_file("b", "bin/b")]),
+            None
+        );
+    }
+
+    fn locked(target: &str) -> LockedCrate {
+        LockedCrate {
+            name: "tool".into(),
+            version: Version::new(1, 0, 0),
+            target: target.into(),
+            url: Url::parse("https://example.com/tool.tgz").unwrap(),
+            sha256: "ABCDEF0123".into(),
+            pkg_fmt: PkgFmt::Tgz,
+            bin_dir: None,
+            bins: vec!["tool".into()],
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let entries = [
+            locked("x86_64-unknown-linux-gnu"),
+            locked("x86_64-unknown-linux-musl"),
+        ];
+        let targets = ["x86_64-unknown-linux-musl", "x86_64-unknown-linux-gnu"];
+
+        assert_eq!(
+            select(&entries, &targets).unwrap().target,
+            "x86_64-unknown-linux-musl"
+        );
+        assert_eq!(
+            select(&entries, &targets[1..]).unwrap().target,
+            "x86_64-unknown-linux-gnu"
+        );
+        assert_eq!(select(&entries, &["aarch64-apple-darwin"]), None);
+    }
+
+    #[test]
+    fn test_check_digest() {
+        let locked = locked("x86_64-unknown-linux-gnu");
+        let verification = |sha256: &str| Verification {
+            sha256: sha256.into(),
+            signature: SignatureCheck::Skipped,
+        };
+
+        check_digest(&locked, &verification("abcdef0123")).unwrap();
+
+        // The artifact is tampered with.
+        let err = check_digest(&locked, &verification("abcdef0124")).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                BinstallError::ChecksumMismatch { expected, actual }
+                    if *expected == "ABCDEF0123" && *actual == "abcdef0124"
+            ),
+            "{err}"
+        );
+        assert_eq!(err.exit_number(), 42);
+    }
+}
@@ -1,4 +1,13 @@
-use std::{borrow::Cow, env, ffi::OsStr, fmt, iter, path::Path, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    env,
+    ffi::OsStr,
+    fmt, fs, io, iter,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
 
 use command_group::AsyncCommandGroup;
 use compact_str::{CompactString, ToCompactString};
@@ -7,13 +16,22 @@ use itertools::Itertools;
 use semver::Version;
 use tokio::process::Command;
 use tracing::{debug, error, info, warn};
+use url::Url;
 
 use crate::{
     bins,
-    errors::BinstallError,
+    errors::{BinstallError, StrategyFailure, StrategyFailureKind},
     fetchers::Fetcher,
-    manifests::crate_info::{CrateInfo, CrateSource},
-    ops::Options,
+    helpers::download::SavedArtifact,
+    manifests::{
+        cargo_toml_binstall::Strategy,
+        crate_info::{ArtifactVersion, CrateInfo, CrateSource, FetchSource, Verification},
+    },
+    ops::{
+        progress::Progress, repair::sha256_file, resolve::DeniedVersion, stats::Phase,
+        usage_report::Usage, CargoTomlFetchOverride, Options,
+    },
+    TARGET,
 };
 
 pub struct ResolutionFetch {
@@ -22,19 +40,124 @@ pub struct ResolutionFetch {
     pub name: CompactString,
     pub version_req: CompactString,
     pub bin_files: Vec<bins::BinFile>,
+    /// Binaries selected with `--bin` or `crate:bin`, empty if all of them
+    /// are installed.
+    pub selected_bins: Vec<CompactString>,
+    pub extra_files: Vec<bins::ExtraFile>,
+    pub artifact_version: Option<ArtifactVersion>,
+    /// `None` with `--dry-run`, the package is not downloaded.
+    pub verification: Option<Verification>,
+    /// Entries of the denylist which affected the resolution.
+    pub denylisted: Vec<DeniedVersion>,
+    /// Record of the installed crate being repaired, only the broken
+    /// binaries of which are in `bin_files`.
+    pub repaired: Option<CrateInfo>,
+    /// Number of bytes downloaded for the package, if known, see
+    /// [`Fetcher::report_size`].
+    pub download_size: Option<u64>,
 }
 
 pub struct ResolutionSource {
     pub name: CompactString,
     pub version: CompactString,
+    pub version_req: CompactString,
+    /// Only install these binaries of the crate, all of them if empty.
+    pub bins: Vec<CompactString>,
+    /// Entries of the denylist which affected the resolution.
+    pub denylisted: Vec<DeniedVersion>,
+}
+
+/// An artifact saved by [`Options::download_only`].
+pub struct ResolutionDownloaded {
+    pub name: CompactString,
+    pub version: Version,
+    pub target: CompactString,
+    pub artifact: SavedArtifact,
+    pub verification: Verification,
+    /// Entries of the denylist which affected the resolution.
+    pub denylisted: Vec<DeniedVersion>,
 }
 
 pub enum Resolution {
     Fetch(Box<ResolutionFetch>),
     InstallFromSource(ResolutionSource),
+    Downloaded(ResolutionDownloaded),
     AlreadyUpToDate,
 }
 
+/// Outcome of looking for a prebuilt binary, before deciding whether to
+/// fall back to building from source.
+pub enum BinaryResolution {
+    /// No build from source is needed: [`Resolution::Fetch`],
+    /// [`Resolution::Downloaded`] or [`Resolution::AlreadyUpToDate`].
+    ResolvedToBinary(Resolution),
+    /// No prebuilt binary is available.
+    WouldNeedSourceBuild {
+        source: ResolutionSource,
+        /// Why each strategy failed.
+        reasons: Vec<StrategyFailure>,
+        /// Strategies disabled by the user or by the crate, it is not built
+        /// from source if [`Strategy::Compile`] is one of them.
+        disabled_strategies: Vec<Strategy>,
+    },
+}
+
+impl BinaryResolution {
+    /// Apply the fallback policy of the cli: build from source only if
+    /// `allow_source_build`, otherwise report why no prebuilt binary is
+    /// available.
+    pub fn or_source_build(self, allow_source_build: bool) -> Result<Resolution, BinstallError> {
+        match self {
+            Self::ResolvedToBinary(resolution) => Ok(resolution),
+            Self::WouldNeedSourceBuild {
+                source,
+                disabled_strategies,
+                ..
+            } if allow_source_build && !disabled_strategies.contains(&Strategy::Compile) => {
+                Ok(Resolution::InstallFromSource(source))
+            }
+            Self::WouldNeedSourceBuild {
+                reasons,
+                disabled_strategies,
+                ..
+            } => {
+                let geo_blocked: BTreeSet<_> = reasons
+                    .iter()
+                    .filter_map(|reason| match &reason.kind {
+                        StrategyFailureKind::GeoBlocked { host } => Some(host.clone()),
+                        _ => None,
+                    })
+                    .collect();
+
+                let offline_missing = reasons.iter().find_map(|reason| match &reason.kind {
+                    StrategyFailureKind::OfflineMissing { url } => Some(url.clone()),
+                    _ => None,
+                });
+
+                if let Some(url) = offline_missing.filter(|_| geo_blocked.is_empty()) {
+                    Err(BinstallError::OfflineMissing { url })
+                } else if geo_blocked.is_empty() {
+                    Err(BinstallError::NoFallbackToCargoInstall {
+                        reasons,
+                        disabled_strategies,
+                    })
+                } else {
+                    let missing: BTreeSet<_> = reasons
+                        .iter()
+                        .filter(|reason| reason.kind == StrategyFailureKind::NotFound)
+                        .map(|reason| reason.strategy.clone())
+                        .collect();
+
+                    Err(BinstallError::ArtifactHostUnavailable {
+                        geo_blocked: geo_blocked.into_iter().collect(),
+                        missing: missing.into_iter().collect(),
+                    })
+                }
+            }
+        }
+    }
+}
+
 impl Resolution {
     pub fn print(&self, opts: &Options) {
         match self {
@@ -44,19 +167,74 @@ impl Resolution {
             Resolution::InstallFromSource(source) => {
                 source.print();
             }
+            Resolution::Downloaded(downloaded) => {
+                downloaded.print();
+            }
             Resolution::AlreadyUpToDate => (),
         }
     }
 }
 
+/// Undo the changes of an install that failed halfway.
+#[derive(Default)]
+struct Rollback {
+    /// Files replaced by the install.
+    backups: Vec<bins::Backup>,
+    /// Paths that did not exist before the install.
+    created: Vec<PathBuf>,
+}
+
+impl Rollback {
+    /// Record that `path` is about to be installed, with `backup` of the
+    /// file it replaces if there is one.
+    fn track(&mut self, path: &Path, backup: Option<bins::Backup>) {
+        match backup {
+            Some(backup) => self.backups.push(backup),
+            None => self.created.push(path.to_owned()),
+        }
+    }
+
+    fn run(self) {
+        for path in self.created {
+            match fs::remove_file(&path) {
+                Ok(()) => debug!("Removed '{}' installed before the failure", path.display()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => warn!("Failed to remove '{}': {err}", path.display()),
+            }
+        }
+
+        // In reverse, in case a path is backed up more than once.
+        for backup in self.backups.into_iter().rev() {
+            let original = backup.original().to_owned();
+            if let Err(restore_err) = backup.restore() {
+                warn!(
+                    "Failed to restore '{}' from backup: {restore_err}",
+                    original.display()
+                );
+            }
+        }
+    }
+}
+
 impl ResolutionFetch {
     pub fn install(self, opts: &Options) -> Result<CrateInfo, BinstallError> {
         let crate_name = self.name.clone();
-        self.install_inner(opts)
-            .map_err(|err| err.crate_context(crate_name))
+        let start = Instant::now();
+        let res = self.install_inner(opts);
+        opts.stats.add_elapsed(Phase::Install, start.elapsed());
+
+        res.map_err(|err| {
+            let err = err.crate_context(&*crate_name);
+            opts.report_progress(&crate_name, Progress::Failed { error: &err });
+            err
+        })
     }
 
     fn install_inner(self, opts: &Options) -> Result<CrateInfo, BinstallError> {
+        if opts.cancellation_token.is_cancelled() {
+            return Err(BinstallError::UserAbort);
+        }
+
         type InstallFp = fn(&bins::BinFile) -> Result<(), bins::Error>;
 
         let (install_bin, install_link): (InstallFp, InstallFp) = match (opts.no_track, opts.force)
@@ -69,31 +247,246 @@ impl ResolutionFetch {
         };
 
         info!("Installing binaries...");
-        for file in &self.bin_files {
-            install_bin(file)?;
+
+        let install_path = self.install_path(opts);
+        if opts.install_path_template.is_some() {
+            fs::create_dir_all(&install_path)?;
         }
 
-        // Generate symlinks
-        if !opts.no_symlinks {
+        // Installation runs on this thread, so degradations recorded after
+        // this point belong to this crate.
+        bins::take_degradations();
+
+        // Keep the files being replaced aside, and the paths created, until
+        // every binary and its links are installed, so that a failure does
+        // not leave a partial install.
+        let mut rollback = Rollback::default();
+        let mut links = Vec::new();
+
+        let res = (|| -> Result<(), bins::Error> {
             for file in &self.bin_files {
-                install_link(file)?;
+                rollback.track(&file.dest, file.backup_dest()?);
+                install_bin(file)?;
+            }
+
+            if !opts.no_symlinks {
+                for file in &self.bin_files {
+                    if let Some(link) = &file.link {
+                        rollback.track(link, file.backup_link()?);
+                    }
+                    install_link(file)?;
+                }
+            }
+
+            if let Some(link_dir) = &opts.link_dir {
+                fs::create_dir_all(link_dir)?;
+                for file in &self.bin_files {
+                    let link = link_dir.join(file.base_name.as_str());
+                    rollback.track(&link, bins::Backup::create(&link)?);
+                    links.push(file.install_link_in(link_dir)?);
+                }
             }
+
+            Ok(())
+        })();
+
+        if let Err(err) = res {
+            rollback.run();
+            return Err(err.into());
+        }
+
+        let extra_files = self.replace_extra_files(opts, self.install_extra_files(opts));
+
+        let bin_digests: BTreeMap<_, _> = self
+            .bin_files
+            .iter()
+            .filter_map(|file| match sha256_file(&file.dest) {
+                Ok(digest) => Some((file.base_name.clone(), digest)),
+                Err(err) => {
+                    warn!(
+                        "Failed to compute the digest of '{}': {err}",
+                        file.dest.display()
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        let degraded = !bins::take_degradations().is_empty();
+        if degraded {
+            warn!(
+                "{} is installed in degraded mode since the filesystem lacks some features",
+                self.name
+            );
+        }
+
+        opts.report_progress(
+            &self.name,
+            Progress::Installed {
+                bins: self.bin_files.len(),
+                install_path: &install_path,
+            },
+        );
+        opts.report_usage(Usage {
+            crate_name: self.name.clone(),
+            version: self.new_version.to_compact_string(),
+            target: self.fetcher.target().to_compact_string(),
+        });
+        opts.report_installed(&self.name);
+
+        if let Some(mut crate_info) = self.repaired {
+            crate_info.bin_digests.extend(bin_digests);
+            crate_info.degraded |= degraded;
+            for link in links {
+                if !crate_info.links.contains(&link) {
+                    crate_info.links.push(link);
+                }
+            }
+            crate_info.fetch_source = Some(self.fetcher.fetch_source());
+            return Ok(crate_info);
         }
 
         Ok(CrateInfo {
             name: self.name,
             version_req: self.version_req,
             current_version: self.new_version,
-            source: CrateSource::cratesio_registry(),
+            source: opts
+                .cargo_toml_fetch_override
+                .as_ref()
+                .and_then(CargoTomlFetchOverride::crate_source)
+                .unwrap_or_else(CrateSource::cratesio_registry),
             target: self.fetcher.target().to_compact_string(),
             bins: self
                 .bin_files
                 .into_iter()
                 .map(|bin| bin.base_name)
                 .collect(),
+            selected_bins: self.selected_bins,
+            bin_digests,
+            extra_files,
+            links,
+            degraded,
+            artifact_version: self.artifact_version,
+            verification: self.verification,
+            fetch_source: Some(self.fetcher.fetch_source()),
         })
     }
 
+    /// Return the directory the binaries are installed in.
+    fn install_path<'a>(&self, opts: &'a Options) -> Cow<'a, Path> {
+        let target = self.fetcher.target();
+        opts.crate_install_path(
+            &self.name,
+            &self.new_version.to_string(),
+            target,
+            self.fetcher.target_meta().binary_ext(target),
+        )
+    }
+
+    /// Return the extra files to record for the crate: `installed`, and the
+    /// ones recorded for the previous install which are of a kind, or for a
+    /// shell, that is not installed this time, e.g. the completions when
+    /// upgrading without `--install-completions`.
+    ///
+    /// The other ones recorded before are replaced, so they are removed if
+    /// the new version does not ship them anymore.
+    fn replace_extra_files(&self, opts: &Options, mut installed: Vec<PathBuf>) -> Vec<PathBuf> {
+        let Some(tracked) = &opts.tracked_extra_files else {
+            return installed;
+        };
+
+        let previous: Vec<&PathBuf> = tracked
+            .iter()
+            .filter(|(file, name)| **name == self.name && !installed.contains(file))
+            .map(|(file, _)| file)
+            .collect();
+
+        for file in previous {
+            let replaced = opts
+                .extra_files_dir
+                .as_deref()
+                .and_then(|data_dir| bins::ExtraFileKind::of_dest(file, data_dir));
+            let replaced = match replaced {
+                Some((bins::ExtraFileKind::Man, _)) => opts.install_man,
+                Some((bins::ExtraFileKind::Completions, shell)) => {
+                    shell.map_or(false, |shell| opts.completion_shells.contains(&shell))
+                }
+                None => false,
+            };
+
+            if !replaced {
+                installed.push(file.clone());
+                continue;
+            }
+
+            match fs::remove_file(file) {
+                Ok(()) => debug!("Removed '{}', not shipped anymore", file.display()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => {
+                    // Kept recorded to be removed on uninstall.
+                    warn!("Failed to remove '{}': {err}", file.display());
+                    installed.push(file.clone());
+                }
+            }
+        }
+
+        installed
+    }
+
+    /// Install completions and man pages, return the installed ones.
+    ///
+    /// Failing to install them does not fail the installation.
+    fn install_extra_files(&self, opts: &Options) -> Vec<PathBuf> {
+        self.extra_files
+            .iter()
+            .filter_map(|file| {
+                let is_tracked = opts
+                    .tracked_extra_files
+                    .as_ref()
+                    .map_or(false, |tracked| tracked.contains_key(&file.dest));
+
+                if !opts.force && !is_tracked && file.dest_exists() {
+                    warn!(
+                        "Skip installing {}: '{}' is not installed by binstall, use --force to overwrite it",
+                        file.kind,
+                        file.dest.display()
+                    );
+                    return None;
+                }
+
+                match file.install() {
+                    Ok(()) => Some(file.dest.clone()),
+                    Err(err) => {
+                        warn!(
+                            "Failed to install {} to '{}': {err}",
+                            file.kind,
+                            file.dest.display()
+                        );
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Return `true` if this only reinstalls the broken binaries of an
+    /// installed crate.
+    pub fn is_repair(&self) -> bool {
+        self.repaired.is_some()
+    }
+
+    /// Return where the package is downloaded from, after the url
+    /// rewrites.
+    pub fn download_url(&self, opts: &Options) -> Option<Url> {
+        match self.fetcher.fetch_source() {
+            FetchSource::GhCrateMeta { url, .. } => {
+                Some(opts.url_rewrites.rewrite(&url).unwrap_or(url))
+            }
+            FetchSource::QuickInstall { url } => Some(url),
+            FetchSource::SourceBuild => None,
+        }
+    }
+
     pub fn print(&self, opts: &Options) {
         let fetcher = &self.fetcher;
         let bin_files = &self.bin_files;
@@ -106,15 +499,28 @@ impl ResolutionFetch {
             fetcher.source_name(),
         );
 
+        if opts.dry_run {
+            self.print_dry_run(opts);
+            return;
+        }
+
         warn!(
-            "The package {name} v{new_version} ({target}) has been downloaded from {}{}",
+            "The package {name} v{new_version} ({target}) has been downloaded from {}{}{}",
             if fetcher.is_third_party() {
                 "third-party source "
             } else {
                 ""
             },
-            fetcher.source_name()
+            fetcher.source_name(),
+            match self.download_size {
+                Some(size) => Cow::Owned(format!(" ({})", format_size(size))),
+                None => Cow::Borrowed(""),
+            }
         );
+        print_denylisted(name, &self.denylisted);
+        if let Some(verification) = &self.verification {
+            print_verification(verification);
+        }
 
         info!("This will install the following binaries:");
         for file in bin_files {
@@ -127,15 +533,68 @@ impl ResolutionFetch {
                 info!("  - {}", file.preview_link());
             }
         }
+
+        if !self.extra_files.is_empty() {
+            info!("And the following extra files:");
+            for file in &self.extra_files {
+                info!("  - {}", file.preview());
+            }
+        }
+    }
+
+    fn print_dry_run(&self, opts: &Options) {
+        let fetcher = &self.fetcher;
+        let name = &self.name;
+        let new_version = &self.new_version;
+        let target = fetcher.target();
+        let strategy = fetcher.source_name();
+        let pkg_fmt = fetcher.pkg_fmt();
+        let url = self.download_url(opts);
+
+        info!(
+            name = %name,
+            version = %new_version,
+            target,
+            strategy = %strategy,
+            url = url.as_ref().map(Url::as_str),
+            pkg_fmt = %pkg_fmt,
+            "Dry-run: {name} v{new_version} ({target}) would be downloaded by {strategy} from {} as {pkg_fmt}",
+            url.as_ref().map_or("an unknown url", Url::as_str),
+        );
+        print_denylisted(name, &self.denylisted);
+
+        info!("Dry-run: it would install the following binaries:");
+        for file in &self.bin_files {
+            info!(
+                bin = %file.base_name,
+                dest = %file.dest.display(),
+                "  - {}",
+                file.preview_bin()
+            );
+        }
+
+        if !opts.no_symlinks {
+            info!("Dry-run: and create (or update) the following symlinks:");
+            for file in &self.bin_files {
+                info!("  - {}", file.preview_link());
+            }
+        }
     }
 }
 
 impl ResolutionSource {
     pub async fn install(self, opts: Arc<Options>) -> Result<(), BinstallError> {
         let crate_name = self.name.clone();
-        self.install_inner(opts)
-            .await
-            .map_err(|err| err.crate_context(crate_name))
+        let start = Instant::now();
+        // Cancelling kills `cargo install`, see `Command::kill_on_drop`.
+        let res = opts.cancellable(self.install_inner(opts.clone())).await;
+        opts.stats.add_elapsed(Phase::Install, start.elapsed());
+
+        res.map_err(|err| {
+            let err = err.crate_context(&*crate_name);
+            opts.report_progress(&crate_name, Progress::Failed { error: &err });
+            err
+        })
     }
 
     async fn install_inner(self, opts: Arc<Options>) -> Result<(), BinstallError> {
@@ -164,6 +623,10 @@ impl ResolutionSource {
             cmd.arg("--target").arg(target);
         }
 
+        for bin in &self.bins {
+            cmd.arg("--bin").arg(bin);
+        }
+
         if opts.quiet {
             cmd.arg("--quiet");
         }
@@ -184,6 +647,10 @@ impl ResolutionSource {
             cmd.arg("--no-track");
         }
 
+        if opts.client.is_offline() {
+            cmd.arg("--offline");
+        }
+
         debug!("Running `{}`", format_cmd(&cmd));
 
         if !opts.dry_run {
@@ -198,6 +665,12 @@ impl ResolutionSource {
             let status = child.wait().await?;
             if status.success() {
                 info!("Cargo finished successfully");
+                opts.report_usage(Usage {
+                    crate_name: name.clone(),
+                    version: version.clone(),
+                    target: target.map_or(TARGET, String::as_str).into(),
+                });
+                opts.report_installed(name);
                 Ok(())
             } else {
                 error!("Cargo errored! {status:?}");
@@ -207,7 +680,11 @@ impl ResolutionSource {
                 })
             }
         } else {
-            info!("Dry-run: running `{}`", format_cmd(&cmd));
+            warn!(
+                "Dry-run: no prebuilt binary of {name} v{version} is found, \
+                it would be built from source by running `{}`",
+                format_cmd(&cmd)
+            );
             Ok(())
         }
     }
@@ -216,10 +693,72 @@ impl ResolutionSource {
         warn!(
             "The package {} v{} will be installed from source (with cargo)",
             self.name, self.version
-        )
+        );
+        print_denylisted(&self.name, &self.denylisted);
     }
 }
 
+impl ResolutionDownloaded {
+    pub fn print(&self) {
+        info!(
+            name = %self.name,
+            version = %self.version,
+            target = %self.target,
+            path = %self.artifact.path.display(),
+            sha256 = %self.artifact.sha256,
+            "Saved {} v{} ({}) to '{}'",
+            self.name,
+            self.version,
+            self.target,
+            self.artifact.path.display()
+        );
+        info!("  digest: '{}'", self.artifact.digest_path.display());
+        print_denylisted(&self.name, &self.denylisted);
+        print_verification(&self.verification);
+    }
+}
+
+fn print_denylisted(name: &str, denylisted: &[DeniedVersion]) {
+    for denied in denylisted {
+        warn!(
+            denylisted_version = %denied.version,
+            denylisted_target = denied.target.as_deref(),
+            denylisted_reason = denied.reason.as_deref(),
+            "Skipped denylisted {name} {denied}"
+        );
+    }
+}
+
+fn print_verification(verification: &Verification) {
+    info!(
+        sha256 = %verification.sha256,
+        signature = ?verification.signature,
+        "Verification: {verification}"
+    );
+}
+
+/// Format `bytes` in decimal units, e.g. `38.4 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["kB", "MB", "GB", "TB"];
+
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        // Compare the rounded value, so that it is never shown as `1000.0`.
+        if (size * 10.0).round() < 10000.0 {
+            break;
+        }
+        size /= 1000.0;
+        unit = next_unit;
+    }
+
+    format!("{size:.1} {unit}")
+}
+
 fn format_cmd(cmd: &Command) -> impl fmt::Display + '_ {
     let cmd = cmd.as_std();
 
@@ -232,3 +771,19 @@ fn format_cmd(cmd: &Command) -> impl fmt::Display + '_ {
 
     iter::once(program).chain(program_args).format(" ")
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(1000), "1.0 kB");
+        assert_eq!(format_size(38_400_000), "38.4 MB");
+        assert_eq!(format_size(999_960), "1.0 MB");
+        assert_eq!(format_size(2_500_000_000), "2.5 GB");
+        assert_eq!(format_size(u64::MAX), "18446744.1 TB");
+    }
+}
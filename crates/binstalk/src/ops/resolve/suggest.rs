@@ -0,0 +1,144 @@
+//! Suggestions for the crates which are not found in the registry.
+
+use compact_str::CompactString;
+use tracing::debug;
+
+use crate::{
+    errors::{BinstallError, CrateSuggestion},
+    ops::Options,
+};
+
+/// Maximum number of crates searched for by [`find_crate_providing_bin`].
+const BIN_SEARCH_LIMIT: usize = 5;
+
+/// Well-known binaries whose crate has another name, sorted by binary.
+const WELL_KNOWN_BINS: &[(&str, &str)] = &[
+    ("btm", "bottom"),
+    ("cargo-add", "cargo-edit"),
+    ("cargo-install-update", "cargo-update"),
+    ("cargo-rm", "cargo-edit"),
+    ("cargo-set-version", "cargo-edit"),
+    ("cargo-sqlx", "sqlx-cli"),
+    ("cargo-upgrade", "cargo-edit"),
+    ("delta", "git-delta"),
+    ("diesel", "diesel_cli"),
+    ("difft", "difftastic"),
+    ("dua", "dua-cli"),
+    ("dust", "du-dust"),
+    ("fd", "fd-find"),
+    ("probe-rs", "probe-rs-tools"),
+    ("rg", "ripgrep"),
+    ("rga", "ripgrep_all"),
+    ("sk", "skim"),
+    ("sqlx", "sqlx-cli"),
+    ("taplo", "taplo-cli"),
+    ("tldr", "tealdeer"),
+    ("typos", "typos-cli"),
+    ("wasm-bindgen", "wasm-bindgen-cli"),
+    ("watchexec", "watchexec-cli"),
+];
+
+/// Return the crate providing the well-known binary `bin`, if its name is
+/// not the one of the binary.
+fn crate_providing(bin: &str) -> Option<&'static str> {
+    WELL_KNOWN_BINS
+        .binary_search_by_key(&bin, |(bin, _)| bin)
+        .ok()
+        .map(|pos| WELL_KNOWN_BINS[pos].1)
+}
+
+/// Return the error for crate `name` not being found, suggesting the crate
+/// providing it if it is a well-known binary, or else crates with a similar
+/// name found in the registry, unless offline.
+///
+/// Failing to search the registry only leaves the suggestions out.
+pub(super) async fn crate_not_found(opts: &Options, name: &str) -> BinstallError {
+    let suggestion = if let Some(crate_name) = crate_providing(name) {
+        CrateSuggestion::ProvidedBy(crate_name)
+    } else if opts.client.is_offline() {
+        debug!("Not searching the registry for crates similar to {name} in offline mode");
+        CrateSuggestion::None
+    } else {
+        match opts
+            .registry
+            .search_similar_crates(&opts.client, name)
+            .await
+        {
+            Ok(similar) if !similar.is_empty() => CrateSuggestion::Similar(similar),
+            Ok(_) => CrateSuggestion::None,
+            Err(err) => {
+                debug!("Failed to search the registry for crates similar to {name}: {err}");
+                CrateSuggestion::None
+            }
+        }
+    };
+
+    BinstallError::CrateNotFound {
+        name: name.into(),
+        suggestion,
+    }
+}
+
+/// Find the crate providing binary `bin` of a `:bin` spec, or of a crate
+/// which is not found: the well-known crate providing it, or else the only
+/// crate found by searching the registry whose latest version provides it.
+///
+/// Otherwise, nothing is installed, and the error lists the crates which
+/// may provide it, i.e. all of those providing it, or those found if the
+/// registry does not tell which binaries they provide. The registry is not
+/// searched offline.
+pub async fn find_crate_providing_bin(
+    opts: &Options,
+    bin: &str,
+) -> Result<CompactString, BinstallError> {
+    if let Some(crate_name) = crate_providing(bin) {
+        return Ok(crate_name.into());
+    }
+
+    if opts.client.is_offline() {
+        debug!("Not searching the registry for crates providing binary `{bin}` in offline mode");
+        return Err(BinstallError::BinNotFound(bin.into()));
+    }
+
+    let found = opts
+        .registry
+        .search_crates_providing_bin(&opts.client, bin, BIN_SEARCH_LIMIT)
+        .await?;
+    let verified: Vec<CompactString> = found
+        .iter()
+        .filter(|found| found.verified)
+        .map(|found| found.name.clone())
+        .collect();
+
+    match &verified[..] {
+        [crate_name] => Ok(crate_name.clone()),
+        [] if found.is_empty() => Err(BinstallError::BinNotFound(bin.into())),
+        [] => Err(BinstallError::CratesProvidingBin {
+            bin: bin.into(),
+            candidates: found.into_iter().map(|found| found.name).collect(),
+        }),
+        _ => Err(BinstallError::CratesProvidingBin {
+            bin: bin.into(),
+            candidates: verified,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn well_known_bins_sorted() {
+        assert!(WELL_KNOWN_BINS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_crate_providing() {
+        assert_eq!(crate_providing("rg"), Some("ripgrep"));
+        assert_eq!(crate_providing("cargo-add"), Some("cargo-edit"));
+        assert_eq!(crate_providing("fd"), Some("fd-find"));
+        assert_eq!(crate_providing("ripgrep"), None);
+        assert_eq!(crate_providing("RG"), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! Machine-readable report of the crates resolved and installed by a run,
+//! for tools driving binstall.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use compact_str::{CompactString, ToCompactString};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    errors::BinstallError,
+    manifests::crate_info::FetchSource,
+    ops::{
+        resolve::{ResolutionFetch, ResolutionSource},
+        Options,
+    },
+    TARGET,
+};
+
+/// Version of the schema of [`RunReport`], bumped on incompatible changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    /// [`SCHEMA_VERSION`] of the report.
+    pub schema_version: u32,
+    /// The crates resolved, in the order they are resolved.
+    pub crates: Vec<CrateReport>,
+    /// The failures of the run, without `--fail-fast` there can be
+    /// one per crate.
+    pub errors: Vec<ErrorReport>,
+}
+
+impl Default for RunReport {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            crates: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// A crate resolved to be installed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CrateReport {
+    pub name: CompactString,
+    /// The version requirement requested, `*` if none is.
+    pub requested_version: CompactString,
+    pub version: CompactString,
+    /// The target of the binaries.
+    pub target: CompactString,
+    /// [`Fetcher::source_name`](crate::fetchers::Fetcher::source_name) of
+    /// the fetcher chosen, `None` if built from source.
+    pub fetcher: Option<CompactString>,
+    pub fetch_source: Option<FetchSource>,
+    /// Where the package is downloaded from, after the url rewrites.
+    pub download_url: Option<Url>,
+    /// The binaries installed, empty if built from source since cargo
+    /// decides where they are installed.
+    pub bins: Vec<PathBuf>,
+    /// True if no prebuilt binary is found and the crate is built from
+    /// source.
+    pub source_build: bool,
+    /// False until the crate is installed, e.g. with `--dry-run`.
+    pub installed: bool,
+}
+
+impl CrateReport {
+    pub fn from_fetch(fetch: &ResolutionFetch, opts: &Options) -> Self {
+        Self {
+            name: fetch.name.clone(),
+            requested_version: fetch.version_req.clone(),
+            version: fetch.new_version.to_compact_string(),
+            target: fetch.fetcher.target().into(),
+            fetcher: Some(fetch.fetcher.source_name()),
+            fetch_source: Some(fetch.fetcher.fetch_source()),
+            download_url: fetch.download_url(opts),
+            bins: fetch
+                .bin_files
+                .iter()
+                .map(|bin_file| bin_file.link.as_ref().unwrap_or(&bin_file.dest).clone())
+                .collect(),
+            source_build: false,
+            installed: false,
+        }
+    }
+
+    pub fn from_source(source: &ResolutionSource, opts: &Options) -> Self {
+        Self {
+            name: source.name.clone(),
+            requested_version: source.version_req.clone(),
+            version: source.version.clone(),
+            target: opts
+                .desired_targets
+                .get_initialized()
+                .and_then(|targets| targets.first())
+                .map_or(TARGET, String::as_str)
+                .into(),
+            fetcher: None,
+            fetch_source: Some(FetchSource::SourceBuild),
+            download_url: None,
+            bins: Vec::new(),
+            source_build: true,
+            installed: false,
+        }
+    }
+}
+
+/// A failure of the run.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// The crate which failed, `None` if the failure is not about a crate.
+    #[serde(rename = "crate")]
+    pub crate_name: Option<CompactString>,
+    pub error: String,
+}
+
+/// Collect the [`RunReport`] of a run from its concurrent tasks.
+#[derive(Debug, Default)]
+pub struct RunReporter(Mutex<RunReport>);
+
+impl RunReporter {
+    pub fn add_crate(&self, report: CrateReport) {
+        self.0.lock().unwrap().crates.push(report);
+    }
+
+    pub(crate) fn set_installed(&self, crate_name: &str) {
+        let mut report = self.0.lock().unwrap();
+        for crate_report in &mut report.crates {
+            if crate_report.name == crate_name {
+                crate_report.installed = true;
+            }
+        }
+    }
+
+    /// Add the failures of `err`, one per crate if it is about crates.
+    pub fn add_error(&self, err: &BinstallError) {
+        let errors = err
+            .crate_failures()
+            .into_iter()
+            .map(|(crate_name, err)| ErrorReport {
+                crate_name: crate_name.map(CompactString::from),
+                error: err.to_string(),
+            });
+        self.0.lock().unwrap().errors.extend(errors);
+    }
+
+    /// Add a failure which is not a [`BinstallError`].
+    pub fn add_other_error(&self, error: String) {
+        self.0.lock().unwrap().errors.push(ErrorReport {
+            crate_name: None,
+            error,
+        });
+    }
+
+    pub fn report(&self) -> RunReport {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl RunReport {
+    /// Write the report as json to `path`, or to stdout if it is `-`.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+
+        if path == Path::new("-") {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&json)?;
+            writeln!(stdout)
+        } else {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, json)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        let report = RunReport {
+            crates: vec![CrateReport {
+                name: "cargo-binstall".into(),
+                requested_version: "*".into(),
+                version: "1.0.0".into(),
+                target: "x86_64-unknown-linux-gnu".into(),
+                fetcher: Some("GhCrateMeta".into()),
+                fetch_source: Some(FetchSource::QuickInstall {
+                    url: Url::parse("https://example.com/a.tgz").unwrap(),
+                }),
+                download_url: Some(Url::parse("https://example.com/a.tgz").unwrap()),
+                bins: vec![PathBuf::from("/bin/cargo-binstall")],
+                source_build: false,
+                installed: true,
+            }],
+            errors: vec![ErrorReport {
+                crate_name: Some("tool".into()),
+                error: "not found".into(),
+            }],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schema_version": 1,
+                "crates": [{
+                    "name": "cargo-binstall",
+                    "requested_version": "*",
+                    "version": "1.0.0",
+                    "target": "x86_64-unknown-linux-gnu",
+                    "fetcher": "GhCrateMeta",
+                    "fetch_source": {
+                        "strategy": "quick-install",
+                        "url": "https://example.com/a.tgz",
+                    },
+                    "download_url": "https://example.com/a.tgz",
+                    "bins": ["/bin/cargo-binstall"],
+                    "source_build": false,
+                    "installed": true,
+                }],
+                "errors": [{ "crate": "tool", "error": "not found" }],
+            })
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        report.write(&path).unwrap();
+        let read: RunReport = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(read, report);
+    }
+}
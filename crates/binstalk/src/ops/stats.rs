@@ -0,0 +1,205 @@
+//! Statistics of a run, to help tune the GitHub token and caching.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use compact_str::CompactString;
+use serde::{Deserialize, Serialize};
+
+use crate::ops::Options;
+
+/// Name of the strategy recorded for crates built from source.
+pub const COMPILE_STRATEGY: &str = "compile";
+
+/// Phase of the installation of a crate.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Phase {
+    /// Resolving the crate, including downloading its artifact.
+    Resolve,
+    /// Downloading, verifying and extracting the artifact.
+    Download,
+    /// Installing the binaries, or building them from source.
+    Install,
+}
+
+/// Counters of a run shared by all the crates, in addition to the ones of
+/// the clients.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// Time spent in each [`Phase`] in nanoseconds, summed over the crates.
+    phases: [AtomicU64; 3],
+    /// Number of crates resolved with each strategy.
+    strategies: Mutex<BTreeMap<CompactString, u32>>,
+}
+
+impl Stats {
+    pub(crate) fn add_elapsed(&self, phase: Phase, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        self.phases[phase as usize].fetch_add(nanos, Relaxed);
+    }
+
+    pub(crate) fn add_strategy(&self, strategy: &str) {
+        *self
+            .strategies
+            .lock()
+            .unwrap()
+            .entry(strategy.into())
+            .or_default() += 1;
+    }
+
+    fn elapsed_ms(&self, phase: Phase) -> u64 {
+        self.phases[phase as usize].load(Relaxed) / 1_000_000
+    }
+}
+
+/// Snapshot of the counters of a run, see [`RunStats::collect`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunStats {
+    /// Number of GitHub API requests sent.
+    pub github_api_requests: u32,
+    /// Number of GitHub API requests sent with a token.
+    pub github_api_authenticated_requests: u32,
+    /// Number of lookups answered by a GitHub release fetched earlier.
+    pub release_metadata_cache_hits: u32,
+    /// Number of `.crate` files loaded from the cache.
+    pub crate_cache_hits: u32,
+    /// Number of bytes of response bodies received.
+    pub bytes_downloaded: u64,
+
+    /// Time spent resolving the crates in milliseconds, including
+    /// [`RunStats::download_ms`].
+    ///
+    /// Phases are summed over the crates, which are processed concurrently,
+    /// so they can exceed the duration of the run.
+    pub resolve_ms: u64,
+    /// Time spent downloading, verifying and extracting artifacts in
+    /// milliseconds.
+    pub download_ms: u64,
+    /// Time spent installing the binaries, or building them from source,
+    /// in milliseconds.
+    pub install_ms: u64,
+
+    /// Number of crates resolved with each strategy, by fetcher name or
+    /// [`COMPILE_STRATEGY`].
+    pub strategies: BTreeMap<CompactString, u32>,
+}
+
+impl RunStats {
+    /// Collect the counters of `opts` and of its clients.
+    pub fn collect(opts: &Options) -> Self {
+        let stats = &opts.stats;
+
+        Self {
+            github_api_requests: opts.gh_api_client.requests_used(),
+            github_api_authenticated_requests: opts.gh_api_client.authenticated_requests(),
+            release_metadata_cache_hits: opts.gh_api_client.release_cache_hits(),
+            crate_cache_hits: opts.registry.crate_cache_hits(),
+            bytes_downloaded: opts.client.bytes_downloaded(),
+
+            resolve_ms: stats.elapsed_ms(Phase::Resolve),
+            download_ms: stats.elapsed_ms(Phase::Download),
+            install_ms: stats.elapsed_ms(Phase::Install),
+
+            strategies: stats.strategies.lock().unwrap().clone(),
+        }
+    }
+
+    /// Load the statistics saved by [`RunStats::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        serde_json::from_slice(&fs::read(path)?).map_err(io::Error::from)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// One line per group of counters.
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = |ms: u64| Duration::from_millis(ms).as_secs_f64();
+
+        writeln!(
+            f,
+            "GitHub API requests: {} ({} authenticated)",
+            self.github_api_requests, self.github_api_authenticated_requests
+        )?;
+        writeln!(
+            f,
+            "Cache hits: {} release metadata, {} .crate files",
+            self.release_metadata_cache_hits, self.crate_cache_hits
+        )?;
+        writeln!(f, "Downloaded: {} bytes", self.bytes_downloaded)?;
+        writeln!(
+            f,
+            "Time spent: resolve {:.2}s, download {:.2}s, install {:.2}s",
+            seconds(self.resolve_ms),
+            seconds(self.download_ms),
+            seconds(self.install_ms)
+        )?;
+
+        write!(f, "Strategies:")?;
+        if self.strategies.is_empty() {
+            write!(f, " none")
+        } else {
+            for (i, (strategy, count)) in self.strategies.iter().enumerate() {
+                let sep = if i == 0 { " " } else { ", " };
+                write!(f, "{sep}{strategy} {count}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display_and_save() {
+        let stats = Stats::default();
+        stats.add_elapsed(Phase::Resolve, Duration::from_millis(1500));
+        stats.add_elapsed(Phase::Resolve, Duration::from_millis(500));
+        stats.add_elapsed(Phase::Install, Duration::from_millis(250));
+        stats.add_strategy("GhCrateMeta");
+        stats.add_strategy(COMPILE_STRATEGY);
+        stats.add_strategy("GhCrateMeta");
+
+        let run_stats = RunStats {
+            github_api_requests: 3,
+            github_api_authenticated_requests: 2,
+            bytes_downloaded: 1024,
+            resolve_ms: stats.elapsed_ms(Phase::Resolve),
+            download_ms: stats.elapsed_ms(Phase::Download),
+            install_ms: stats.elapsed_ms(Phase::Install),
+            strategies: stats.strategies.lock().unwrap().clone(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            run_stats.to_string(),
+            "GitHub API requests: 3 (2 authenticated)\n\
+            Cache hits: 0 release metadata, 0 .crate files\n\
+            Downloaded: 1024 bytes\n\
+            Time spent: resolve 2.00s, download 0.00s, install 0.25s\n\
+            Strategies: GhCrateMeta 2, compile 1"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("binstall/stats.json");
+        run_stats.save(&path).unwrap();
+        assert_eq!(RunStats::load(&path).unwrap(), run_stats);
+    }
+}
@@ -0,0 +1,121 @@
+//! Remove installed crates along with the files recorded for them.
+
+use std::{
+    collections::BTreeSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use compact_str::CompactString;
+use tracing::{debug, warn};
+
+use crate::{
+    errors::BinstallError,
+    manifests::{cargo_toml_binstall::default_binary_ext, crate_info::CrateInfo},
+};
+
+/// Return the files installed for `crate_info` whose binaries are in
+/// `install_path`: the binaries, the versioned binaries they link to, the
+/// links in `--link-dir`, and the completions and man pages.
+///
+/// Binaries also recorded for another crate, in `other_bins`, are kept.
+pub fn installed_files(
+    crate_info: &CrateInfo,
+    install_path: &Path,
+    other_bins: &BTreeSet<CompactString>,
+) -> Vec<PathBuf> {
+    let binary_ext = default_binary_ext(&crate_info.target);
+    let mut files = Vec::new();
+
+    for bin in &crate_info.bins {
+        if other_bins.contains(bin) {
+            warn!(
+                "'{}' is also recorded for another crate, keeping it",
+                install_path.join(bin.as_str()).display()
+            );
+        } else {
+            files.push(install_path.join(bin.as_str()));
+        }
+
+        // Only there if it was installed with symlinks.
+        let stem = bin.strip_suffix(binary_ext).unwrap_or(bin);
+        let versioned = install_path.join(format!(
+            "{stem}-v{}{binary_ext}",
+            crate_info.current_version
+        ));
+        if fs::symlink_metadata(&versioned).is_ok() {
+            files.push(versioned);
+        }
+    }
+
+    files.extend(crate_info.links.iter().cloned());
+    files.extend(crate_info.extra_files.iter().cloned());
+
+    files
+}
+
+/// Remove `files`, the missing ones are warned about.
+///
+/// Fails on the first file which cannot be removed, so that the crate can
+/// still be uninstalled again once fixed.
+pub fn remove_files(files: &[PathBuf]) -> Result<(), BinstallError> {
+    for file in files {
+        match fs::remove_file(file) {
+            Ok(()) => debug!("Removed '{}'", file.display()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                warn!("'{}' is already removed", file.display())
+            }
+            Err(err) => {
+                return Err(BinstallError::Io(io::Error::new(
+                    err.kind(),
+                    format!("Failed to remove '{}': {err}", file.display()),
+                )))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use semver::Version;
+
+    #[test]
+    fn test_installed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let install_path = dir.path();
+        fs::write(install_path.join("a-v1.2.3"), "").unwrap();
+
+        let crate_info = CrateInfo {
+            bins: vec!["a".into(), "b".into(), "c".into()],
+            extra_files: vec!["/data/zsh/site-functions/_a".into()],
+            links: vec!["/links/a".into()],
+            ..CrateInfo::for_test("a", Version::new(1, 2, 3))
+        };
+
+        let files = installed_files(&crate_info, install_path, &["c".into()].into());
+        assert_eq!(
+            files,
+            [
+                install_path.join("a"),
+                install_path.join("a-v1.2.3"),
+                install_path.join("b"),
+                PathBuf::from("/links/a"),
+                PathBuf::from("/data/zsh/site-functions/_a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let present = dir.path().join("a");
+        fs::write(&present, "").unwrap();
+
+        remove_files(&[present.clone(), dir.path().join("missing")]).unwrap();
+        assert!(!present.exists());
+    }
+}
@@ -0,0 +1,184 @@
+//! Opt-in reporting of the installed crates to an endpoint of the user.
+
+use std::{fmt, mem, sync::Mutex, time::Duration};
+
+use compact_str::CompactString;
+use serde::Serialize;
+use tokio::{task::JoinSet, time::timeout};
+use tracing::debug;
+
+use crate::helpers::remote::{Client, Url};
+
+/// A crate installed, as reported to [`UsageReporter`].
+///
+/// It deliberately contains nothing that identifies the user.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Usage {
+    #[serde(rename = "crate")]
+    pub crate_name: CompactString,
+    pub version: CompactString,
+    pub target: CompactString,
+}
+
+/// Receiver of the crates installed.
+///
+/// It is called while installing, so it must neither block nor fail.
+pub trait UsageReporter: fmt::Debug + Send + Sync {
+    fn report(&self, usage: Usage);
+}
+
+/// `POST` of each [`Usage`] as json to an endpoint.
+///
+/// The crates reported are only queued, they are sent by
+/// [`HttpUsageReporter::flush`] once their receipts are written.
+#[derive(Debug)]
+pub struct HttpUsageReporter {
+    client: Client,
+    endpoint: Url,
+    pending: Mutex<Vec<Usage>>,
+}
+
+impl HttpUsageReporter {
+    pub fn new(client: Client, endpoint: Url) -> Self {
+        Self {
+            client,
+            endpoint,
+            pending: Mutex::default(),
+        }
+    }
+
+    /// Send the crates reported so far, giving up on the requests not done
+    /// within `duration`.
+    pub async fn flush(&self, duration: Duration) {
+        let pending = mem::take(&mut *self.pending.lock().unwrap());
+
+        let mut requests = JoinSet::new();
+        for usage in pending {
+            let body = match serde_json::to_vec(&usage) {
+                Ok(body) => body,
+                Err(err) => {
+                    debug!("Failed to serialize usage of {}: {err}", usage.crate_name);
+                    continue;
+                }
+            };
+
+            let request = self
+                .client
+                .post(self.endpoint.clone(), body)
+                .header("Content-Type", "application/json");
+
+            requests.spawn(async move { (usage.crate_name, request.send(true).await) });
+        }
+
+        let all_sent = async {
+            while let Some(res) = requests.join_next().await {
+                if let Ok((crate_name, Err(err))) = res {
+                    debug!("Failed to report usage of {crate_name}: {err}");
+                }
+            }
+        };
+
+        // Dropping `requests` on timeout aborts the ones still in flight.
+        if timeout(duration, all_sent).await.is_err() {
+            debug!("Timed out after {duration:?} reporting usage");
+        }
+    }
+}
+
+impl UsageReporter for HttpUsageReporter {
+    fn report(&self, usage: Usage) {
+        self.pending.lock().unwrap().push(usage);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU16;
+
+    use super::*;
+    use crate::helpers::remote::{
+        test_server::{spawn_server, spawn_stalling_server},
+        Certificate,
+    };
+
+    static REQUESTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn client(cert: &[u8]) -> Client {
+        Client::new(
+            "binstalk-test",
+            None,
+            NonZeroU16::new(10).unwrap(),
+            1.try_into().unwrap(),
+            [Certificate::from_der(cert).unwrap()],
+            Default::default(),
+            None,
+        )
+        .unwrap()
+    }
+
+    fn usage() -> Usage {
+        Usage {
+            crate_name: "cargo-binstall".into(),
+            version: "1.6.8".into(),
+            target: "x86_64-unknown-linux-gnu".into(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_flush() {
+        let (url, cert) = spawn_server(|request| {
+            REQUESTS.lock().unwrap().push(request.to_owned());
+            "HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n"
+        })
+        .await;
+
+        let reporter = HttpUsageReporter::new(client(&cert), url.join("usage").unwrap());
+        reporter.report(usage());
+
+        // Nothing is sent before the flush.
+        assert!(REQUESTS.lock().unwrap().is_empty());
+
+        reporter.flush(Duration::from_secs(10)).await;
+
+        let requests = REQUESTS.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(
+            requests[0].starts_with("POST /usage HTTP/1.1\r\n"),
+            "{requests:?}"
+        );
+        assert!(
+            requests[0].ends_with(
+                "\r\n\r\n{\"crate\":\"cargo-binstall\",\"version\":\"1.6.8\",\"target\":\"x86_64-unknown-linux-gnu\"}"
+            ),
+            "{requests:?}"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_flush_timeout() {
+        // Never answers.
+        let (url, cert) = spawn_stalling_server(|_| "").await;
+
+        let reporter = HttpUsageReporter::new(client(&cert), url);
+        reporter.report(usage());
+
+        let duration = Duration::from_millis(200);
+        timeout(duration * 10, reporter.flush(duration))
+            .await
+            .expect("flush must give up after its timeout");
+    }
+
+    #[test]
+    fn test_usage_json() {
+        let usage = Usage {
+            crate_name: "cargo-binstall".into(),
+            version: "1.6.8".into(),
+            target: "x86_64-unknown-linux-gnu".into(),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&usage).unwrap(),
+            r#"{"crate":"cargo-binstall","version":"1.6.8","target":"x86_64-unknown-linux-gnu"}"#
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    num::{NonZeroU16, NonZeroU64},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use binstalk::{
+    fetchers::Fetcher,
+    helpers::remote::{test_server::spawn_server, Certificate, Client, SpkiPins},
+    ops::{
+        resolve::{find_crate_providing_bin, resolve, CrateName, Resolution},
+        Options,
+    },
+    registry::Registry,
+};
+
+mod common;
+use common::*;
+
+/// Number of searches for `rgrep` served by [`respond_registry`].
+static REGISTRY_SEARCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Respond with `body` as JSON.
+fn json(body: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .into_bytes()
+}
+
+/// A sparse registry with [`CRATE`], whose web API, on the same host:
+/// - finds `ripgrep` and `rg-cli` when searching for `rgrep`, without telling
+///   their binaries;
+/// - finds `cargo-binstall-test` and `cargo-binstall-other` when searching
+///   for `cargo-binstall`, the only one of them providing it being
+///   `cargo-binstall-test`.
+fn respond_registry(request: &str) -> &'static [u8] {
+    let response = if request.contains("/config.json ") {
+        let host = request
+            .lines()
+            .find_map(|line| line.strip_prefix("host: "))
+            .unwrap();
+        json(&format!(
+            "{{\"dl\": \"https://{host}/dl\", \"api\": \"https://{host}\"}}"
+        ))
+    } else if request.starts_with("GET /api/v1/crates?q=rgrep&") {
+        REGISTRY_SEARCHES.fetch_add(1, Ordering::Relaxed);
+        json(r#"{"crates": [{"name": "ripgrep"}, {"name": "rg-cli"}]}"#)
+    } else if request.starts_with("GET /api/v1/crates?q=cargo-binstall&") {
+        json(
+            r#"{"crates": [
+                {"name": "cargo-binstall-test", "max_version": "1.2.3"},
+                {"name": "cargo-binstall-other", "max_version": "0.1.0"}
+            ]}"#,
+        )
+    } else if request.starts_with("GET /api/v1/crates/cargo-binstall-test/1.2.3 ") {
+        json(r#"{"version": {"bin_names": ["cargo-binstall"]}}"#)
+    } else if request.starts_with("GET /api/v1/crates/cargo-binstall-other/0.1.0 ") {
+        json(r#"{"version": {"bin_names": ["other"]}}"#)
+    } else if let Some(response) = respond_crate(request) {
+        response
+    } else {
+        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+    };
+    Box::leak(response.into_boxed_slice())
+}
+
+/// Options getting the manifests from the registry served by
+/// [`respond_registry`].
+async fn registry_options(opts: Arc<Options>) -> Arc<Options> {
+    let (url, cert) = spawn_server(respond_registry).await;
+    let client = Client::new(
+        "binstalk-test",
+        None,
+        NonZeroU16::new(10).unwrap(),
+        NonZeroU64::new(1).unwrap(),
+        [Certificate::from_der(&cert).unwrap()],
+        SpkiPins::default(),
+        None,
+    )
+    .unwrap();
+
+    let Ok(mut opts) = Arc::try_unwrap(opts) else {
+        panic!("The options are not shared yet");
+    };
+    opts.cargo_toml_fetch_override = None;
+    opts.registry = format!("sparse+{url}").parse::<Registry>().unwrap();
+    opts.client = client;
+    Arc::new(opts)
+}
+
+/// `crate:bin` where the crate is not in the registry only suggests the
+/// crates which may provide the binary if the registry does not tell which
+/// one does, found with a single search of the configured registry.
+#[tokio::test(flavor = "multi_thread")]
+async fn bin_of_unknown_crate() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let opts = registry_options(opts).await;
+
+    let crate_name = CrateName {
+        name: "no-such-crate".into(),
+        version_req: None,
+        bins: vec!["rgrep".into()],
+    };
+    let Err(err) = resolve(opts, crate_name, None).await else {
+        panic!("Expected the crate to fail to resolve");
+    };
+    let err = err.to_string();
+    assert!(
+        err.contains("binary `rgrep` may be provided by: ripgrep, rg-cli, nothing is installed"),
+        "{err}"
+    );
+    assert_eq!(REGISTRY_SEARCHES.load(Ordering::Relaxed), 1);
+    assert_eq!(downloads(dir.path()), 0);
+}
+
+/// `crate:bin` where the crate is not in the registry installs the binary
+/// from the only crate found which provides it.
+#[tokio::test(flavor = "multi_thread")]
+async fn bin_of_unknown_crate_is_installed() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let opts = registry_options(opts).await;
+
+    let crate_name = CrateName {
+        name: "no-such-crate".into(),
+        version_req: None,
+        bins: vec!["cargo-binstall".into()],
+    };
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name, None).await else {
+        panic!("Expected the crate providing the binary to be fetched");
+    };
+    assert_eq!(fetch.name, "cargo-binstall-test");
+    fetch.install(&opts).unwrap();
+
+    assert!(dir.path().join("bin/cargo-binstall").is_file());
+}
+
+/// The crate providing a binary is a well-known one, or else the only one
+/// found which provides it, see [`bin_of_unknown_crate`] when ambiguous.
+#[tokio::test(flavor = "multi_thread")]
+async fn crate_providing_bin() {
+    let (_dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let opts = registry_options(opts).await;
+
+    // Well-known binaries are not searched for.
+    assert_eq!(
+        find_crate_providing_bin(&opts, "sqlx").await.unwrap(),
+        "sqlx-cli"
+    );
+    assert_eq!(
+        find_crate_providing_bin(&opts, "cargo-binstall")
+            .await
+            .unwrap(),
+        "cargo-binstall-test"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unknown_bin() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+
+    let crate_name = CrateName {
+        bins: vec!["cargo-binstall".into(), "no-such-bin".into()],
+        ..crate_name()
+    };
+    let Err(err) = resolve(opts, crate_name, None).await else {
+        panic!("Expected the crate to fail to resolve");
+    };
+    let err = err.to_string();
+    assert!(
+        err.contains("does not provide binary `no-such-bin`, only: cargo-binstall"),
+        "{err}"
+    );
+    assert_eq!(downloads(dir.path()), 0);
+}
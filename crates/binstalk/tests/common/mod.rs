@@ -0,0 +1,389 @@
+//! Mocks and setup shared by the integration tests.
+
+// Each test crate uses only some of these.
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    num::{NonZeroU16, NonZeroU64},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use binstalk::{
+    fetchers::{
+        ArtifactCache, ChecksumPolicy, Data, FetchError, Fetcher, SignaturePolicy, TargetDataErased,
+    },
+    get_desired_targets,
+    helpers::{
+        download::{DownloadError, DownloadProgress, ExtractFilter, ExtractedFiles, SavedArtifact},
+        gh_api_client::GhApiClient,
+        gitea_api_client::GiteaApiClient,
+        gl_api_client::GlApiClient,
+        jobserver_client::LazyJobserverClient,
+        remote::{Client, Method, SpkiPins, Url},
+    },
+    manifests::{
+        cargo_toml_binstall::{PkgFmt, PkgMeta, PkgOverride, Strategy},
+        crate_info::{FetchSource, SignatureCheck, Verification},
+    },
+    ops::{
+        progress::{ChannelProgressSink, Event, InstallEvent},
+        resolve::CrateName,
+        usage_report::{Usage, UsageReporter},
+        CargoTomlFetchOverride, Options, Resolver, VersionMatchPolicy,
+    },
+};
+use compact_str::CompactString;
+use tempfile::TempDir;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+pub const TARGET: &str = "x86_64-unknown-linux-gnu";
+
+/// SHA-256 digest of the empty artifacts saved by [`MockFetcher`].
+pub const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// The binary extracted by [`MockFetcher`].
+pub const BINARY: &str = "#!/bin/sh\n";
+
+/// SHA-256 digest of [`BINARY`].
+pub const BINARY_SHA256: &str = "a8076d3d28d21e02012b20eaf7dbf75409a6277134439025f282e368e3305abf";
+
+/// `.crate` file of `cargo-binstall-test` v1.2.3, without a repository,
+/// which would be checked online.
+pub const CRATE: &[u8] = include_bytes!("../cargo-binstall-test-1.2.3.crate");
+
+/// SHA-256 digest of [`CRATE`].
+pub const CRATE_SHA256: &str = "6f004a1110e2376226564db2d07f68e60270bdb87eb1d2f35ae1ffbffe537c81";
+
+/// Serve the index entry and the `.crate` file of [`CRATE`] as a sparse
+/// registry whose `dl` is `/dl`, or `None` for the other requests.
+pub fn respond_crate(request: &str) -> Option<Vec<u8>> {
+    if request.starts_with("GET /ca/rg/cargo-binstall-test ") {
+        let body = format!(
+            "{{\"name\": \"cargo-binstall-test\", \"vers\": \"1.2.3\", \
+            \"cksum\": \"{CRATE_SHA256}\", \"yanked\": false}}\n"
+        );
+        Some(
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes(),
+        )
+    } else if request.starts_with("GET /dl/cargo-binstall-test/1.2.3/download ") {
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            CRATE.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(CRATE);
+        Some(response)
+    } else {
+        None
+    }
+}
+
+/// Size of the chunks "downloaded" by [`MockFetcher::fetch_and_extract`].
+pub const DOWNLOAD_CHUNK: u64 = 100 * 1024;
+
+/// Stats endpoint the [`MockFetcher`]s which report to upstream send their
+/// reports to.
+pub static STATS_URL: Mutex<Option<Url>> = Mutex::new(None);
+
+/// Provide the binaries of `cargo-binstall-test` if `FOUND`, sending the
+/// reports to upstream to [`STATS_URL`] if `REPORTS`.
+///
+/// It poses as the QuickInstall fetcher if `QUICKINSTALL`, and never
+/// finishes the download once it extracted the binary if `STALLS`.
+pub struct MockFetcher<
+    const FOUND: bool,
+    const REPORTS: bool = false,
+    const QUICKINSTALL: bool = false,
+    const STALLS: bool = false,
+> {
+    client: Client,
+    target_data: Arc<TargetDataErased>,
+}
+
+#[async_trait::async_trait]
+impl<const FOUND: bool, const REPORTS: bool, const QUICKINSTALL: bool, const STALLS: bool> Fetcher
+    for MockFetcher<FOUND, REPORTS, QUICKINSTALL, STALLS>
+{
+    fn new(
+        client: Client,
+        _gh_api_client: GhApiClient,
+        _gl_api_client: GlApiClient,
+        _gitea_api_client: GiteaApiClient,
+        _data: Arc<Data>,
+        target_data: Arc<TargetDataErased>,
+        _signature_policy: SignaturePolicy,
+        _checksum_policy: ChecksumPolicy,
+        _artifact_cache: Option<ArtifactCache>,
+    ) -> Arc<dyn Fetcher> {
+        Arc::new(Self {
+            client,
+            target_data,
+        })
+    }
+
+    async fn fetch_and_extract(
+        &self,
+        dst: &Path,
+        _filter: Option<&ExtractFilter>,
+        progress: &dyn DownloadProgress,
+    ) -> Result<(ExtractedFiles, Verification), FetchError> {
+        for downloaded in (1..=6).map(|chunks| chunks * DOWNLOAD_CHUNK) {
+            progress.update(downloaded, Some(6 * DOWNLOAD_CHUNK));
+        }
+
+        fs::create_dir_all(dst).map_err(DownloadError::from)?;
+        fs::write(dst.join("cargo-binstall"), BINARY).map_err(DownloadError::from)?;
+        count_download(dst);
+
+        if STALLS {
+            std::future::pending::<()>().await;
+        }
+
+        let mut extracted_files = ExtractedFiles::new();
+        extracted_files.add_file(Path::new("cargo-binstall"));
+        Ok((extracted_files, verification()))
+    }
+
+    async fn fetch_and_save(
+        &self,
+        dir: &Path,
+        _progress: &dyn DownloadProgress,
+    ) -> Result<(SavedArtifact, Verification), FetchError> {
+        let path = dir.join("cargo-binstall-test.tgz");
+        fs::write(&path, "").map_err(DownloadError::from)?;
+
+        let digest_path = dir.join("cargo-binstall-test.tgz.sha256");
+        fs::write(
+            &digest_path,
+            format!("{EMPTY_SHA256}  cargo-binstall-test.tgz\n"),
+        )
+        .map_err(DownloadError::from)?;
+
+        let artifact = SavedArtifact {
+            digest_path,
+            sha256: EMPTY_SHA256.into(),
+            path,
+        };
+        Ok((artifact, verification()))
+    }
+
+    fn find(self: Arc<Self>) -> JoinHandle<Result<bool, FetchError>> {
+        tokio::spawn(async { Ok(FOUND) })
+    }
+
+    fn report_to_upstream(self: Arc<Self>) {
+        if REPORTS {
+            let url = STATS_URL.lock().unwrap().clone().unwrap();
+            tokio::spawn(async move { self.client.request(Method::HEAD, url).send(true).await });
+        }
+    }
+
+    fn pkg_fmt(&self) -> PkgFmt {
+        PkgFmt::Tgz
+    }
+
+    fn target_meta(&self) -> PkgMeta {
+        let mut meta = self.target_data.meta.clone();
+        meta.pkg_fmt = Some(self.pkg_fmt());
+        meta
+    }
+
+    fn source_name(&self) -> CompactString {
+        "mock".into()
+    }
+
+    fn fetch_source(&self) -> FetchSource {
+        let url = "https://example.com/cargo-binstall-test.tgz"
+            .parse()
+            .unwrap();
+        if QUICKINSTALL {
+            FetchSource::QuickInstall { url }
+        } else {
+            FetchSource::GhCrateMeta { url, repo: None }
+        }
+    }
+
+    fn fetcher_name(&self) -> &'static str {
+        if QUICKINSTALL {
+            "QuickInstall"
+        } else {
+            "MockFetcher"
+        }
+    }
+
+    fn is_third_party(&self) -> bool {
+        false
+    }
+
+    fn strategy(&self) -> Strategy {
+        if QUICKINSTALL {
+            Strategy::QuickInstall
+        } else {
+            Strategy::CrateMetaData
+        }
+    }
+
+    fn target(&self) -> &str {
+        &self.target_data.target
+    }
+
+    fn target_data(&self) -> &Arc<TargetDataErased> {
+        &self.target_data
+    }
+}
+
+/// Count a download in the parent of `dst`, see [`downloads`].
+pub fn count_download(dst: &Path) {
+    let path = dst.parent().unwrap().join("downloads");
+    let mut count = fs::read_to_string(&path).unwrap_or_default();
+    count.push('.');
+    fs::write(path, count).unwrap();
+}
+
+/// Return the number of artifacts downloaded into `dir/tmp`.
+pub fn downloads(dir: &Path) -> usize {
+    fs::read_to_string(dir.join("tmp/downloads"))
+        .unwrap_or_default()
+        .len()
+}
+
+#[derive(Debug, Default)]
+pub struct RecordingReporter(pub Mutex<Vec<Usage>>);
+
+impl UsageReporter for RecordingReporter {
+    fn report(&self, usage: Usage) {
+        self.0.lock().unwrap().push(usage);
+    }
+}
+
+pub fn verification() -> Verification {
+    Verification {
+        sha256: "".into(),
+        signature: SignatureCheck::NotPublished,
+    }
+}
+
+pub fn options(
+    resolver: Resolver,
+    dir: &Path,
+    download_only: bool,
+    progress_sink: Arc<ChannelProgressSink>,
+) -> Arc<Options> {
+    let client = Client::new(
+        "binstalk-test",
+        None,
+        NonZeroU16::new(10).unwrap(),
+        NonZeroU64::new(1).unwrap(),
+        [],
+        SpkiPins::default(),
+        None,
+    )
+    .unwrap();
+
+    let manifest_path =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/parse-meta.Cargo.toml");
+
+    Arc::new(Options {
+        no_symlinks: true,
+        dry_run: false,
+        force: false,
+        quiet: true,
+        locked: false,
+        no_track: false,
+
+        tracked_bins: None,
+        query_package_owner: false,
+
+        completion_shells: Vec::new(),
+        install_man: false,
+        extra_files_dir: None,
+        tracked_extra_files: None,
+
+        version_req: None,
+        strict: false,
+        denylist: Default::default(),
+        cargo_toml_fetch_override: Some(CargoTomlFetchOverride::Path(manifest_path)),
+        cli_overrides: PkgOverride::default(),
+
+        desired_targets: get_desired_targets(Some(vec![TARGET.to_string()])),
+        resolvers: vec![resolver],
+        cargo_install_fallback: false,
+        disabled_strategies: Vec::new(),
+
+        temp_dir: dir.join("tmp"),
+        install_path: dir.join("bin"),
+        install_path_template: None,
+        link_dir: None,
+        download_only: download_only.then(|| dir.join("artifacts")),
+        cargo_root: None,
+        artifact_cache: None,
+
+        gh_api_client: GhApiClient::new(client.clone(), None),
+        gl_api_client: GlApiClient::new(client.clone(), None),
+        gitea_api_client: GiteaApiClient::new(client.clone(), None),
+        client,
+        jobserver_client: LazyJobserverClient::new(),
+        registry: Default::default(),
+        cancellation_token: Default::default(),
+
+        signature_policy: SignaturePolicy::IfPresent,
+        checksum_policy: ChecksumPolicy::IfPresent,
+        version_match_policy: VersionMatchPolicy::Ignore,
+        allow_insecure_http: false,
+        url_rewrites: Default::default(),
+
+        progress_sink: Some(progress_sink),
+        stats: Default::default(),
+        run_reporter: None,
+
+        usage_reporter: None,
+        disable_telemetry: false,
+    })
+}
+
+pub fn crate_name() -> CrateName {
+    CrateName {
+        name: "cargo-binstall-test".into(),
+        version_req: None,
+        bins: Vec::new(),
+    }
+}
+
+pub fn events(mut receiver: mpsc::UnboundedReceiver<InstallEvent>) -> Vec<Event> {
+    let mut events = Vec::new();
+    while let Ok(InstallEvent { crate_name, event }) = receiver.try_recv() {
+        assert_eq!(crate_name, "cargo-binstall-test");
+        events.push(event);
+    }
+    events
+}
+
+/// Create `tmp` and `bin` in a new temporary directory, or `artifacts`
+/// instead of `bin` if `download_only`, with the options installing into
+/// it and the receiver of their [`InstallEvent`]s.
+pub fn setup(
+    resolver: Resolver,
+    download_only: bool,
+) -> (TempDir, Arc<Options>, mpsc::UnboundedReceiver<InstallEvent>) {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("tmp")).unwrap();
+    let out_dir = if download_only { "artifacts" } else { "bin" };
+    fs::create_dir_all(dir.path().join(out_dir)).unwrap();
+
+    let (sink, receiver) = ChannelProgressSink::unbounded();
+    let opts = options(resolver, dir.path(), download_only, Arc::new(sink));
+    (dir, opts, receiver)
+}
+
+/// Options installing into `dir` set up by [`setup`] too, without
+/// receiving their [`InstallEvent`]s.
+pub fn options_in(dir: &Path, resolver: Resolver) -> Arc<Options> {
+    let (sink, _receiver) = ChannelProgressSink::unbounded();
+    options(resolver, dir, false, Arc::new(sink))
+}
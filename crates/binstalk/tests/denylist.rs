@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use binstalk::{
+    fetchers::Fetcher,
+    ops::resolve::{resolve, DeniedVersion, Denylist},
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn denylisted_version() {
+    let (_dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+
+    let mut opts = Arc::try_unwrap(opts).unwrap();
+    opts.denylist = Denylist::default();
+    opts.denylist.add(
+        "cargo-binstall-test",
+        DeniedVersion {
+            version: "1.2.3".parse().unwrap(),
+            target: None,
+            reason: Some("truncated upload".into()),
+        },
+    );
+    let opts = Arc::new(opts);
+
+    let Err(err) = resolve(opts, crate_name(), None).await else {
+        panic!("Expected the denylisted version to be rejected");
+    };
+    let err = err.to_string();
+    assert!(
+        err.contains("version 1.2.3 is denylisted: truncated upload"),
+        "{err}"
+    );
+}
@@ -0,0 +1,54 @@
+use std::{fs, sync::Arc};
+
+use binstalk::{
+    fetchers::Fetcher,
+    ops::{
+        resolve::{resolve, resolve_binary, BinaryResolution, Resolution},
+        Resolver,
+    },
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("tmp")).unwrap();
+
+    let dry_run_options = |resolver: Resolver| {
+        let mut opts = Arc::try_unwrap(options_in(dir.path(), resolver)).unwrap();
+        opts.dry_run = true;
+        Arc::new(opts)
+    };
+
+    let opts = dry_run_options(MockFetcher::<true>::new);
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    fetch.print(&opts);
+
+    assert_eq!(downloads(dir.path()), 0);
+    assert_eq!(fetch.verification, None);
+    assert_eq!(
+        fetch.download_url(&opts).as_ref().map(|url| url.as_str()),
+        Some("https://example.com/cargo-binstall-test.tgz")
+    );
+    let [bin_file] = &fetch.bin_files[..] else {
+        panic!("Expected 1 binary");
+    };
+    assert_eq!(bin_file.dest, dir.path().join("bin").join("cargo-binstall"));
+
+    // The fallback to a build from source is only shown.
+    let opts = dry_run_options(MockFetcher::<false>::new);
+    let Ok(BinaryResolution::WouldNeedSourceBuild { source, .. }) =
+        resolve_binary(opts.clone(), crate_name(), None).await
+    else {
+        panic!("Expected the crate to need a build from source");
+    };
+    source.install(opts).await.unwrap();
+
+    // Nothing is written to the install dir nor downloaded.
+    assert!(!dir.path().join("bin").exists());
+    assert_eq!(fs::read_dir(dir.path().join("tmp")).unwrap().count(), 0);
+}
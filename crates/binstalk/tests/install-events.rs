@@ -0,0 +1,150 @@
+use std::fs;
+
+use binstalk::{
+    errors::ErrorKind,
+    fetchers::Fetcher,
+    ops::{
+        progress::Event,
+        resolve::{resolve, Resolution},
+    },
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn install_events() {
+    let (dir, opts, receiver) = setup(MockFetcher::<true>::new, false);
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    let crate_info = fetch.install(&opts).unwrap();
+    assert_eq!(crate_info.verification, Some(verification()));
+
+    assert!(dir.path().join("bin/cargo-binstall").is_file());
+
+    let mut events = events(receiver);
+
+    let Event::Downloaded { .. } = events.remove(6) else {
+        panic!("Expected Downloaded, found {events:?}");
+    };
+    // Downloading is reported for the first chunk, then at most once per
+    // 256 KiB and for the last chunk.
+    let downloading = |chunks| Event::Downloading {
+        downloaded: chunks * DOWNLOAD_CHUNK,
+        total: Some(6 * DOWNLOAD_CHUNK),
+    };
+    assert_eq!(
+        events,
+        [
+            Event::Resolving,
+            Event::Found {
+                source: "mock".into(),
+                target: TARGET.into(),
+                size: None,
+            },
+            downloading(1),
+            downloading(4),
+            downloading(6),
+            Event::Verified {
+                verification: verification(),
+            },
+            Event::Installed {
+                bins: 1,
+                install_path: dir.path().join("bin"),
+            },
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn install_events_on_failure() {
+    let (_dir, opts, receiver) = setup(MockFetcher::<false>::new, false);
+
+    assert!(resolve(opts, crate_name(), None).await.is_err());
+
+    let events = events(receiver);
+    let [Event::Resolving, Event::Failed { error }] = &events[..] else {
+        panic!("Expected Resolving and Failed, found {events:?}");
+    };
+    assert!(error.contains("cargo-binstall-test"), "{error}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn download_only() {
+    let (dir, opts, receiver) = setup(MockFetcher::<true>::new, true);
+
+    let Ok(Resolution::Downloaded(downloaded)) = resolve(opts, crate_name(), None).await else {
+        panic!("Expected the crate to be downloaded");
+    };
+
+    assert_eq!(
+        downloaded.artifact.path,
+        dir.path().join("artifacts/cargo-binstall-test.tgz")
+    );
+    assert!(downloaded.artifact.path.is_file());
+    assert!(!dir.path().join("bin").exists());
+
+    let events = events(receiver);
+    let [Event::Resolving, Event::Found { .. }, Event::Verified { verification: v }, Event::Downloaded { .. }] =
+        &events[..]
+    else {
+        panic!("Expected Resolving, Found, Verified and Downloaded, found {events:?}");
+    };
+    assert_eq!(v, &verification());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelled() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+
+    opts.cancellation_token.cancel();
+
+    // Neither the resolved crate nor a new one is installed.
+    let err = fetch.install(&opts).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UserAbort, "{err}");
+    assert!(!dir.path().join("bin/cargo-binstall").exists());
+
+    let Err(err) = resolve(opts, crate_name(), None).await else {
+        panic!("Expected the resolution to be cancelled");
+    };
+    assert_eq!(err.kind(), ErrorKind::UserAbort, "{err}");
+    assert_eq!(downloads(dir.path()), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelled_download() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true, false, false, true>::new, false);
+    let staging_dirs = || {
+        fs::read_dir(dir.path().join("tmp"))
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("bin-")
+            })
+            .count()
+    };
+
+    let task = tokio::spawn(resolve(opts.clone(), crate_name(), None));
+    while downloads(dir.path()) == 0 {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    // The fetcher extracted the package to its staging directory.
+    assert_eq!(staging_dirs(), 1);
+
+    opts.cancellation_token.cancel();
+    let res = tokio::time::timeout(std::time::Duration::from_secs(5), task)
+        .await
+        .expect("the resolution stops promptly once cancelled")
+        .unwrap();
+    let Err(err) = res else {
+        panic!("Expected the resolution to be cancelled");
+    };
+    assert_eq!(err.kind(), ErrorKind::UserAbort, "{err}");
+    assert_eq!(staging_dirs(), 0);
+}
@@ -0,0 +1,84 @@
+use std::{fs, sync::Arc};
+
+use binstalk::{
+    fetchers::Fetcher,
+    ops::{
+        install_path::InstallPathTemplate,
+        progress::Event,
+        repair::repair,
+        resolve::{resolve, Resolution},
+    },
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn extra_files_on_upgrade() {
+    let (dir, mut opts, _receiver) = setup(MockFetcher::<true>::new, false);
+
+    let data_dir = dir.path().join("share");
+    let completion = data_dir.join("zsh/site-functions/_cargo-binstall");
+    let man_page = data_dir.join("man/man1/cargo-binstall.1");
+    for file in [&completion, &man_page] {
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, "").unwrap();
+    }
+
+    let opts_mut = Arc::get_mut(&mut opts).unwrap();
+    opts_mut.install_man = true;
+    opts_mut.extra_files_dir = Some(data_dir);
+    opts_mut.tracked_extra_files = Some(
+        [&completion, &man_page]
+            .into_iter()
+            .map(|file| (file.clone(), "cargo-binstall-test".into()))
+            .collect(),
+    );
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    let crate_info = fetch.install(&opts).unwrap();
+
+    // The completions are not installed this time, so they are kept, while
+    // the man page not shipped anymore is removed.
+    assert_eq!(crate_info.extra_files, [completion.as_path()]);
+    assert!(completion.exists());
+    assert!(!man_page.exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn versioned_install_path() {
+    let (dir, mut opts, receiver) = setup(MockFetcher::<true>::new, false);
+    let template = InstallPathTemplate::new(&dir.path().join("tools/{name}/{version}/bin"))
+        .unwrap()
+        .unwrap();
+    let opts_mut = Arc::get_mut(&mut opts).unwrap();
+    opts_mut.install_path = template.base().to_owned();
+    opts_mut.install_path_template = Some(template);
+    opts_mut.link_dir = Some(dir.path().join("bin"));
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    let crate_info = fetch.install(&opts).unwrap();
+
+    let install_path = dir.path().join("tools/cargo-binstall-test/1.2.3/bin");
+    assert_eq!(
+        fs::read_to_string(install_path.join("cargo-binstall")).unwrap(),
+        BINARY
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("bin/cargo-binstall")).unwrap(),
+        BINARY
+    );
+    assert!(events(receiver).contains(&Event::Installed {
+        bins: 1,
+        install_path,
+    }));
+
+    // The binaries are looked for in the versioned directory.
+    let Ok(Resolution::AlreadyUpToDate) = repair(opts, crate_info).await else {
+        panic!("Expected the crate to be up to date");
+    };
+}
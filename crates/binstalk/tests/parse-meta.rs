@@ -15,10 +15,21 @@ fn parse_meta() {
     assert_eq!(&package.name, "cargo-binstall-test");
 
     assert_eq!(
-        meta.pkg_url.as_deref().unwrap(),
-        "{ repo }/releases/download/v{ version }/{ name }-{ target }.{ archive-format }"
+        meta.pkg_url,
+        ["{ repo }/releases/download/v{ version }/{ name }-{ target }.{ archive-format }"]
     );
 
+    // The templates of an array are kept in order.
+    let musl = meta.merge_overrides([&meta.overrides["aarch64-unknown-linux-musl"]]);
+    assert_eq!(
+        musl.pkg_url,
+        [
+            "{ repo }/releases/download/v{ version }/{ name }-{ target }.{ archive-format }",
+            "{ repo }/releases/download/v{ version }/{ name }-musl.{ archive-format }",
+        ]
+    );
+    assert!(meta.overrides["x86_64-apple-darwin"].pkg_url.is_empty());
+
     assert_eq!(
         manifest.bin.as_slice(),
         &[Product {
@@ -0,0 +1,215 @@
+use std::{
+    fs,
+    num::{NonZeroU16, NonZeroU64},
+    path::Path,
+    sync::Arc,
+};
+
+use binstalk::{
+    fetchers::{ArtifactCache, Fetcher, GhCrateMeta},
+    helpers::{
+        gh_api_client::GhApiClient,
+        gitea_api_client::GiteaApiClient,
+        gl_api_client::GlApiClient,
+        remote::{test_server::spawn_server, Certificate, Client, SpkiPins, Url},
+    },
+    manifests::cargo_toml_binstall::{PkgFmt, PkgOverride},
+    ops::{
+        prefetch::{cached_artifact_path, prefetch},
+        progress::ChannelProgressSink,
+        resolve::{resolve, Resolution},
+        CargoTomlFetchOverride, Options,
+    },
+    registry::{CrateCache, Registry},
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn prefetch_into_cache() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, true);
+
+    let cache_dir = dir.path().join("cache");
+    let prefetched = prefetch(opts.clone(), crate_name(), &cache_dir)
+        .await
+        .unwrap();
+
+    let cached = cached_artifact_path(&cache_dir, EMPTY_SHA256, "cargo-binstall-test.tgz");
+    assert_eq!(prefetched.artifact.path, cached);
+    assert_eq!(prefetched.artifact.sha256, EMPTY_SHA256);
+    assert!(cached.is_file());
+    assert!(prefetched.artifact.digest_path.is_file());
+
+    // Nothing is left in the staging directory, and nothing is installed.
+    assert_eq!(
+        fs::read_dir(dir.path().join("artifacts")).unwrap().count(),
+        0
+    );
+    assert!(!dir.path().join("bin").exists());
+
+    // Prefetching again keeps the cached artifact.
+    let prefetched = prefetch(opts, crate_name(), &cache_dir).await.unwrap();
+    assert_eq!(prefetched.artifact.path, cached);
+    assert!(cached.is_file());
+}
+
+/// Serve [`BINARY`] as the artifact of `cargo-binstall-test` for [`TARGET`].
+fn respond_artifact(request: &str) -> &'static [u8] {
+    if request.starts_with("GET /cargo-binstall-test-x86_64-unknown-linux-gnu ") {
+        b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n#!/bin/sh\n"
+    } else if request.starts_with("HEAD /cargo-binstall-test-x86_64-unknown-linux-gnu ") {
+        b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n"
+    } else {
+        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+    }
+}
+
+/// A sparse registry with [`CRATE`], which also serves its artifact with
+/// [`respond_artifact`].
+fn respond_registry(request: &str) -> &'static [u8] {
+    let response = if request.contains("/config.json ") {
+        let host = request
+            .lines()
+            .find_map(|line| line.strip_prefix("host: "))
+            .unwrap();
+        let body = format!("{{\"dl\": \"https://{host}/dl\"}}");
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .into_bytes()
+    } else if let Some(response) = respond_crate(request) {
+        response
+    } else {
+        return respond_artifact(request);
+    };
+    Box::leak(response.into_boxed_slice())
+}
+
+/// Options installing the artifacts served at `url` into `dir`, with the
+/// caches in `dir` too.
+fn cached_options(
+    dir: &Path,
+    url: &Url,
+    cert: &Certificate,
+    offline: bool,
+    download_only: bool,
+) -> Options {
+    let client = Client::new(
+        "binstalk-test",
+        None,
+        NonZeroU16::new(10).unwrap(),
+        NonZeroU64::new(1).unwrap(),
+        [cert.clone()],
+        SpkiPins::default(),
+        None,
+    )
+    .unwrap()
+    .offline(offline);
+
+    let (sink, _receiver) = ChannelProgressSink::unbounded();
+    let opts = options(GhCrateMeta::new, dir, download_only, Arc::new(sink));
+    let Ok(mut opts) = Arc::try_unwrap(opts) else {
+        panic!("The options are not shared yet");
+    };
+    opts.gh_api_client = GhApiClient::new(client.clone(), None);
+    opts.gl_api_client = GlApiClient::new(client.clone(), None);
+    opts.gitea_api_client = GiteaApiClient::new(client.clone(), None);
+    opts.client = client;
+    opts.artifact_cache = Some(ArtifactCache::new(dir.join("artifact-cache")));
+    opts.cli_overrides = PkgOverride {
+        pkg_url: vec![format!("{url}{{ name }}-{{ target }}")],
+        pkg_fmt: Some(PkgFmt::Bin),
+        ..Default::default()
+    };
+    opts
+}
+
+/// Prefetch the crate into `dir` with the options of `options`, then
+/// install it with `--offline`.
+async fn prefetch_then_offline_install_with(dir: &Path, options: impl Fn(bool, bool) -> Options) {
+    fs::create_dir_all(dir.join("tmp")).unwrap();
+    fs::create_dir_all(dir.join("artifacts")).unwrap();
+
+    let cache_dir = dir.join("cache");
+    let prefetched = prefetch(Arc::new(options(false, true)), crate_name(), &cache_dir)
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(prefetched.artifact.path).unwrap(),
+        BINARY
+    );
+    assert!(!dir.join("bin").exists());
+
+    fs::create_dir_all(dir.join("bin")).unwrap();
+    let opts = Arc::new(options(true, false));
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched from the cache");
+    };
+    fetch.install(&opts).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(dir.join("bin/cargo-binstall")).unwrap(),
+        BINARY
+    );
+}
+
+/// A crate prefetched is then installed with `--offline` purely from the
+/// caches.
+#[tokio::test(flavor = "multi_thread")]
+async fn prefetch_then_offline_install() {
+    let dir = tempfile::tempdir().unwrap();
+
+    // Without a repository, which would be checked online.
+    let manifest_path = dir.path().join("Cargo.toml");
+    fs::write(
+        &manifest_path,
+        "[package]\nname = \"cargo-binstall-test\"\nversion = \"1.2.3\"\n\n\
+        [[bin]]\nname = \"cargo-binstall\"\npath = \"src/main.rs\"\n",
+    )
+    .unwrap();
+
+    let (url, cert) = spawn_server(respond_artifact).await;
+    let cert = Certificate::from_der(&cert).unwrap();
+
+    prefetch_then_offline_install_with(dir.path(), |offline, download_only| {
+        let mut opts = cached_options(dir.path(), &url, &cert, offline, download_only);
+        opts.cargo_toml_fetch_override = Some(CargoTomlFetchOverride::Path(manifest_path.clone()));
+        opts
+    })
+    .await;
+}
+
+/// A crate of a sparse registry prefetched is then installed with
+/// `--offline`, resolved from the cached index and `.crate` file.
+#[tokio::test(flavor = "multi_thread")]
+async fn prefetch_then_offline_install_from_registry() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let (url, cert) = spawn_server(respond_registry).await;
+    let cert = Certificate::from_der(&cert).unwrap();
+    let crate_cache_dir = dir.path().join("crate-cache");
+
+    prefetch_then_offline_install_with(dir.path(), |offline, download_only| {
+        let mut opts = cached_options(dir.path(), &url, &cert, offline, download_only);
+        opts.cargo_toml_fetch_override = None;
+        opts.registry = format!("sparse+{url}")
+            .parse::<Registry>()
+            .unwrap()
+            .with_crate_cache(CrateCache::new(crate_cache_dir.clone()).offline(offline));
+        opts
+    })
+    .await;
+
+    // The index and the `.crate` file are cached.
+    assert_eq!(
+        fs::read_dir(crate_cache_dir.join("index")).unwrap().count(),
+        1
+    );
+    assert!(crate_cache_dir
+        .join("sha256")
+        .join(CRATE_SHA256)
+        .join("cargo-binstall-test-1.2.3.crate")
+        .is_file());
+}
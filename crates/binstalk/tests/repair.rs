@@ -0,0 +1,58 @@
+use std::fs;
+
+use binstalk::{
+    fetchers::Fetcher,
+    ops::{
+        repair::repair,
+        resolve::{resolve, Resolution},
+    },
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repair_broken_bins() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let bin = dir.path().join("bin/cargo-binstall");
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    assert!(!fetch.is_repair());
+    let crate_info = fetch.install(&opts).unwrap();
+    assert_eq!(crate_info.bin_digests["cargo-binstall"], BINARY_SHA256);
+    assert_eq!(downloads(dir.path()), 1);
+
+    // Running again downloads nothing.
+    let Ok(Resolution::AlreadyUpToDate) = repair(opts.clone(), crate_info.clone()).await else {
+        panic!("Expected the crate to be up to date");
+    };
+    assert_eq!(downloads(dir.path()), 1);
+
+    // A corrupt binary is fetched again.
+    fs::write(&bin, "corrupt").unwrap();
+    let Ok(Resolution::Fetch(fetch)) = repair(opts.clone(), crate_info.clone()).await else {
+        panic!("Expected the binary to be fetched again");
+    };
+    assert!(fetch.is_repair());
+    assert_eq!(fetch.bin_files.len(), 1);
+    let repaired = fetch.install(&opts).unwrap();
+    assert_eq!(repaired.current_version, crate_info.current_version);
+    assert_eq!(fs::read_to_string(&bin).unwrap(), BINARY);
+    assert_eq!(downloads(dir.path()), 2);
+
+    // So is a missing one.
+    fs::remove_file(&bin).unwrap();
+    let Ok(Resolution::Fetch(fetch)) = repair(opts.clone(), repaired.clone()).await else {
+        panic!("Expected the binary to be fetched again");
+    };
+    fetch.install(&opts).unwrap();
+    assert!(bin.is_file());
+    assert_eq!(downloads(dir.path()), 3);
+
+    let Ok(Resolution::AlreadyUpToDate) = repair(opts, repaired).await else {
+        panic!("Expected the crate to be up to date");
+    };
+    assert_eq!(downloads(dir.path()), 3);
+}
@@ -0,0 +1,69 @@
+use std::{fs, path::Path, sync::Arc};
+
+use binstalk::{
+    fetchers::Fetcher,
+    ops::resolve::{resolve, Resolution},
+};
+
+mod common;
+use common::*;
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn rollback_symlinked_bin() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let bin_dir = dir.path().join("bin");
+
+    // The versioned binary is a symlink to a file of another tool, and the
+    // symlink to it cannot be replaced since a directory is in the way.
+    fs::write(bin_dir.join("other-tool"), "other").unwrap();
+    let bin = bin_dir.join("cargo-binstall-v1.2.3");
+    std::os::unix::fs::symlink("other-tool", &bin).unwrap();
+    fs::create_dir_all(bin_dir.join("cargo-binstall/in-the-way")).unwrap();
+
+    let Ok(mut opts) = Arc::try_unwrap(opts) else {
+        panic!("The options are not shared yet");
+    };
+    opts.no_symlinks = false;
+    opts.force = true;
+    let opts = Arc::new(opts);
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    fetch.install(&opts).unwrap_err();
+
+    // The symlink is restored rather than replaced by a copy of the file
+    // it pointed to.
+    assert_eq!(fs::read_link(&bin).unwrap(), Path::new("other-tool"));
+    assert_eq!(
+        fs::read_to_string(bin_dir.join("other-tool")).unwrap(),
+        "other"
+    );
+    assert!(bin_dir.join("cargo-binstall/in-the-way").is_dir());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rollback_removes_created_bin() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let bin_dir = dir.path().join("bin");
+
+    // The versioned binary is new, and the symlink to it cannot be created
+    // since a directory is in the way.
+    fs::create_dir_all(bin_dir.join("cargo-binstall/in-the-way")).unwrap();
+
+    let Ok(mut opts) = Arc::try_unwrap(opts) else {
+        panic!("The options are not shared yet");
+    };
+    opts.no_symlinks = false;
+    opts.force = true;
+    let opts = Arc::new(opts);
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    fetch.install(&opts).unwrap_err();
+
+    assert!(!bin_dir.join("cargo-binstall-v1.2.3").exists());
+    assert!(bin_dir.join("cargo-binstall/in-the-way").is_dir());
+}
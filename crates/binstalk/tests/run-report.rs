@@ -0,0 +1,106 @@
+use std::{fs, sync::Arc};
+
+use binstalk::{
+    fetchers::Fetcher,
+    manifests::crate_info::FetchSource,
+    ops::{
+        resolve::{resolve, CrateName, Resolution},
+        run_report::{CrateReport, RunReport, RunReporter, SCHEMA_VERSION},
+    },
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn run_report() {
+    let (dir, mut opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    Arc::get_mut(&mut opts).unwrap().run_reporter = Some(RunReporter::default());
+    let run_reporter = || opts.run_reporter.as_ref().unwrap();
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    run_reporter().add_crate(CrateReport::from_fetch(&fetch, &opts));
+    assert!(!run_reporter().report().crates[0].installed);
+    fetch.install(&opts).unwrap();
+
+    // A crate built from source is not installed with `--dry-run`.
+    let mut opts_source = options_in(dir.path(), MockFetcher::<false>::new);
+    let opts_source_mut = Arc::get_mut(&mut opts_source).unwrap();
+    opts_source_mut.cargo_install_fallback = true;
+    opts_source_mut.dry_run = true;
+    let Ok(Resolution::InstallFromSource(source)) =
+        resolve(opts_source.clone(), crate_name(), None).await
+    else {
+        panic!("Expected the crate to be built from source");
+    };
+    run_reporter().add_crate(CrateReport::from_source(&source, &opts_source));
+    source.install(opts_source.clone()).await.unwrap();
+
+    let Err(err) = resolve(
+        opts.clone(),
+        CrateName {
+            bins: vec!["no-such-bin".into()],
+            ..crate_name()
+        },
+        None,
+    )
+    .await
+    else {
+        panic!("Expected the crate to fail to resolve");
+    };
+    run_reporter().add_error(&err.crate_context("cargo-binstall-test"));
+
+    // Parse the json back to lock its schema.
+    let path = dir.path().join("report.json");
+    run_reporter().report().write(&path).unwrap();
+    let json = fs::read_to_string(&path).unwrap();
+    let report: RunReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(report, run_reporter().report());
+    assert_eq!(report.schema_version, SCHEMA_VERSION);
+
+    let [fetched, built] = &report.crates[..] else {
+        panic!("Expected 2 crates in {json}");
+    };
+
+    assert_eq!(fetched.name, "cargo-binstall-test");
+    assert_eq!(fetched.requested_version, "*");
+    assert_eq!(fetched.version, "1.2.3");
+    assert_eq!(fetched.target, TARGET);
+    assert_eq!(fetched.fetcher.as_deref(), Some("mock"));
+    assert!(matches!(
+        fetched.fetch_source,
+        Some(FetchSource::GhCrateMeta { .. })
+    ));
+    assert_eq!(
+        fetched.download_url.as_ref().map(|url| url.as_str()),
+        Some("https://example.com/cargo-binstall-test.tgz")
+    );
+    assert_eq!(
+        fetched.bins,
+        [dir.path().join("bin").join("cargo-binstall")]
+    );
+    assert!(!fetched.source_build);
+    assert!(fetched.installed);
+
+    assert_eq!(built.name, "cargo-binstall-test");
+    assert_eq!(built.version, "1.2.3");
+    assert_eq!(built.fetcher, None);
+    assert_eq!(built.fetch_source, Some(FetchSource::SourceBuild));
+    assert!(built.bins.is_empty());
+    assert!(built.source_build);
+    assert!(!built.installed);
+
+    let [error] = &report.errors[..] else {
+        panic!("Expected 1 error in {json}");
+    };
+    assert_eq!(error.crate_name.as_deref(), Some("cargo-binstall-test"));
+    assert!(
+        error
+            .error
+            .contains("does not provide binary `no-such-bin`"),
+        "{}",
+        error.error
+    );
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use binstalk::{
+    fetchers::Fetcher,
+    ops::{
+        resolve::{resolve, resolve_binary, BinaryResolution, Resolution},
+        stats::{RunStats, COMPILE_STRATEGY},
+    },
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn run_stats() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    fetch.install(&opts).unwrap();
+
+    // A crate without any artifact falls back to building from source.
+    let opts_source = options_in(dir.path(), MockFetcher::<false>::new);
+    let Ok(BinaryResolution::WouldNeedSourceBuild { .. }) =
+        resolve_binary(opts_source.clone(), crate_name(), None).await
+    else {
+        panic!("Expected the crate to need a build from source");
+    };
+
+    let stats = RunStats::collect(&opts);
+    assert_eq!(stats.strategies.len(), 1);
+    assert_eq!(stats.strategies["MockFetcher"], 1);
+    assert!(stats.resolve_ms >= stats.download_ms);
+    // Nothing is sent by the mock fetcher, nor taken from a cache.
+    assert_eq!(stats.github_api_requests, 0);
+    assert_eq!(stats.github_api_authenticated_requests, 0);
+    assert_eq!(stats.release_metadata_cache_hits, 0);
+    assert_eq!(stats.crate_cache_hits, 0);
+    assert_eq!(stats.bytes_downloaded, 0);
+
+    // Strategies are only counted once the fallback is applied.
+    assert!(RunStats::collect(&opts_source).strategies.is_empty());
+    let mut opts_source = options_in(dir.path(), MockFetcher::<false>::new);
+    Arc::get_mut(&mut opts_source)
+        .unwrap()
+        .cargo_install_fallback = true;
+    let Ok(Resolution::InstallFromSource(_)) =
+        resolve(opts_source.clone(), crate_name(), None).await
+    else {
+        panic!("Expected the crate to be built from source");
+    };
+    assert_eq!(
+        RunStats::collect(&opts_source).strategies[COMPILE_STRATEGY],
+        1
+    );
+}
@@ -0,0 +1,148 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use binstalk::{
+    fetchers::Fetcher,
+    manifests::{cargo_toml_binstall::Strategy, crate_info::FetchSource},
+    ops::{
+        resolve::{
+            resolve, resolve_binary, resolve_with_fetch_source, BinaryResolution, Resolution,
+            StrategyFailure, StrategyFailureKind,
+        },
+        CargoTomlFetchOverride,
+    },
+};
+
+mod common;
+use common::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn would_need_source_build() {
+    let (_dir, opts, _receiver) = setup(MockFetcher::<false>::new, false);
+
+    let Ok(BinaryResolution::WouldNeedSourceBuild {
+        source, reasons, ..
+    }) = resolve_binary(opts, crate_name(), None).await
+    else {
+        panic!("Expected the crate to need a build from source");
+    };
+    assert_eq!(source.name, "cargo-binstall-test");
+    assert_eq!(source.version, "1.2.3");
+    assert_eq!(
+        reasons,
+        [StrategyFailure {
+            strategy: "mock".into(),
+            target: TARGET.into(),
+            kind: StrategyFailureKind::NotFound,
+        }]
+    );
+
+    // The policy of the cli is applied on top of it.
+    let Ok(Resolution::InstallFromSource(_)) = (BinaryResolution::WouldNeedSourceBuild {
+        source,
+        reasons: reasons.clone(),
+        disabled_strategies: Vec::new(),
+    })
+    .or_source_build(true) else {
+        panic!("Expected the crate to be built from source");
+    };
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn strategies_disabled_by_crate() {
+    let (dir, mut opts, _receiver) = setup(MockFetcher::<true, false, true>::new, false);
+
+    let manifest_path = dir.path().join("Cargo.toml");
+    let manifest = fs::read_to_string(
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/parse-meta.Cargo.toml"),
+    )
+    .unwrap()
+    .replace(
+        "[package.metadata.binstall]\n",
+        "[package.metadata.binstall]\ndisabled-strategies = [\"quick-install\", \"compile\"]\n",
+    );
+    fs::write(&manifest_path, manifest).unwrap();
+
+    let opts_mut = Arc::get_mut(&mut opts).unwrap();
+    opts_mut.cargo_toml_fetch_override = Some(CargoTomlFetchOverride::Path(manifest_path));
+    opts_mut.cargo_install_fallback = true;
+    opts_mut.disabled_strategies = vec![Strategy::CrateMetaData];
+
+    // The artifact of QuickInstall is not looked at, and the crate is not
+    // built from source either.
+    let Err(err) = resolve(opts, crate_name(), None).await else {
+        panic!("Expected the crate to fail to resolve");
+    };
+    let err = err.to_string();
+    assert!(!err.contains("mock"), "{err}");
+    assert!(
+        err.contains("disabled strategies: crate-meta-data, quick-install, compile"),
+        "{err}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_fallback_to_source_reports_reasons() {
+    let (_dir, opts, _receiver) = setup(MockFetcher::<false>::new, false);
+
+    let Err(err) = resolve(opts, crate_name(), None).await else {
+        panic!("Expected the crate to fail to resolve");
+    };
+    let err = err.to_string();
+    assert!(
+        err.contains(&format!("mock ({TARGET}): artifact not found")),
+        "{err}"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fetch_source_tried_first() {
+    let (_dir, mut opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    Arc::get_mut(&mut opts)
+        .unwrap()
+        .resolvers
+        .push(MockFetcher::<true, false, true>::new);
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    assert_eq!(fetch.fetcher.fetcher_name(), "MockFetcher");
+    let crate_info = fetch.install(&opts).unwrap();
+    assert_eq!(
+        crate_info.fetch_source,
+        Some(FetchSource::GhCrateMeta {
+            url: "https://example.com/cargo-binstall-test.tgz"
+                .parse()
+                .unwrap(),
+            repo: None,
+        })
+    );
+
+    // The strategy recorded is tried first.
+    let fetch_source = FetchSource::QuickInstall {
+        url: "https://example.com/cargo-binstall-test.tgz"
+            .parse()
+            .unwrap(),
+    };
+    let Ok(Resolution::Fetch(fetch)) =
+        resolve_with_fetch_source(opts.clone(), crate_name(), None, Some(fetch_source.clone()))
+            .await
+    else {
+        panic!("Expected the crate to be fetched");
+    };
+    assert_eq!(fetch.fetcher.fetcher_name(), "QuickInstall");
+    let crate_info = fetch.install(&opts).unwrap();
+    assert_eq!(crate_info.fetch_source, Some(fetch_source));
+
+    // The order is kept if no fetcher has the strategy recorded.
+    let Ok(Resolution::Fetch(fetch)) = resolve_with_fetch_source(
+        opts.clone(),
+        crate_name(),
+        None,
+        Some(FetchSource::SourceBuild),
+    )
+    .await
+    else {
+        panic!("Expected the crate to be fetched");
+    };
+    assert_eq!(fetch.fetcher.fetcher_name(), "MockFetcher");
+}
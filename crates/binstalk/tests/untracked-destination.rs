@@ -0,0 +1,54 @@
+use std::{collections::BTreeSet, fs, sync::Arc};
+
+use binstalk::{
+    errors::ErrorKind,
+    fetchers::Fetcher,
+    ops::{
+        resolve::{resolve, Resolution},
+        Options,
+    },
+};
+
+mod common;
+use common::*;
+
+/// Options tracking no binary, so that any file already at the
+/// destination is untracked.
+fn untracked_options(opts: Arc<Options>, force: bool) -> Arc<Options> {
+    let Ok(mut opts) = Arc::try_unwrap(opts) else {
+        panic!("The options are not shared yet");
+    };
+    opts.tracked_bins = Some(BTreeSet::new());
+    opts.force = force;
+    Arc::new(opts)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn untracked_destination_is_refused() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let opts = untracked_options(opts, false);
+    let dest = dir.path().join("bin/cargo-binstall");
+    fs::write(&dest, "untracked").unwrap();
+
+    let Err(err) = resolve(opts, crate_name(), None).await else {
+        panic!("Expected the untracked destination to be refused");
+    };
+    assert_eq!(err.kind(), ErrorKind::InstallConflict, "{err}");
+
+    assert_eq!(fs::read_to_string(&dest).unwrap(), "untracked");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn untracked_destination_is_overwritten_with_force() {
+    let (dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+    let opts = untracked_options(opts, true);
+    let dest = dir.path().join("bin/cargo-binstall");
+    fs::write(&dest, "untracked").unwrap();
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    fetch.install(&opts).unwrap();
+
+    assert_eq!(fs::read_to_string(&dest).unwrap(), BINARY);
+}
@@ -0,0 +1,111 @@
+use std::{
+    num::{NonZeroU16, NonZeroU64},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use binstalk::{
+    fetchers::Fetcher,
+    helpers::remote::{test_server::spawn_server, Certificate, Client, SpkiPins},
+    ops::{
+        resolve::{resolve, Resolution},
+        usage_report::Usage,
+    },
+};
+
+mod common;
+use common::*;
+
+async fn install_with_reporter(disable_telemetry: bool) -> Vec<Usage> {
+    let (_dir, opts, _receiver) = setup(MockFetcher::<true>::new, false);
+
+    let reporter = Arc::new(RecordingReporter::default());
+    let mut opts = Arc::try_unwrap(opts).unwrap();
+    opts.usage_reporter = Some(reporter.clone());
+    opts.disable_telemetry = disable_telemetry;
+    let opts = Arc::new(opts);
+
+    let Ok(Resolution::Fetch(fetch)) = resolve(opts.clone(), crate_name(), None).await else {
+        panic!("Expected the crate to be fetched");
+    };
+    fetch.install(&opts).unwrap();
+
+    let reports = reporter.0.lock().unwrap().clone();
+    reports
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn usage_report() {
+    assert_eq!(
+        install_with_reporter(false).await,
+        [Usage {
+            crate_name: "cargo-binstall-test".into(),
+            version: "1.2.3".into(),
+            target: TARGET.into(),
+        }]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn usage_report_disabled() {
+    assert!(install_with_reporter(true).await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upstream_report_disabled() {
+    static DISABLED_HITS: AtomicUsize = AtomicUsize::new(0);
+    static ENABLED_HITS: AtomicUsize = AtomicUsize::new(0);
+
+    const FAILURE: &str =
+        "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const SUCCESS: &str = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    // No report may reach this one.
+    let disabled_server = spawn_server(|_| {
+        DISABLED_HITS.fetch_add(1, Ordering::Relaxed);
+        FAILURE
+    })
+    .await;
+    let enabled_server = spawn_server(|_| {
+        ENABLED_HITS.fetch_add(1, Ordering::Relaxed);
+        SUCCESS
+    })
+    .await;
+
+    // Both in the same test, since the stats endpoint is set globally.
+    for (disable_telemetry, (url, cert)) in [(true, disabled_server), (false, enabled_server)] {
+        let (_dir, opts, _receiver) = setup(MockFetcher::<true, true>::new, false);
+        let Ok(mut opts) = Arc::try_unwrap(opts) else {
+            panic!("The options are not shared yet");
+        };
+        opts.disable_telemetry = disable_telemetry;
+        opts.client = Client::new(
+            "binstalk-test",
+            None,
+            NonZeroU16::new(10).unwrap(),
+            NonZeroU64::new(1).unwrap(),
+            [Certificate::from_der(&cert).unwrap()],
+            SpkiPins::default(),
+            None,
+        )
+        .unwrap();
+        *STATS_URL.lock().unwrap() = Some(url);
+
+        let Ok(Resolution::Fetch(_)) = resolve(Arc::new(opts), crate_name(), None).await else {
+            panic!("Expected the crate to be fetched");
+        };
+    }
+
+    // The reports are sent in the background.
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while ENABLED_HITS.load(Ordering::Relaxed) == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the report is sent when telemetry is enabled");
+    assert_eq!(ENABLED_HITS.load(Ordering::Relaxed), 1);
+    assert_eq!(DISABLED_HITS.load(Ordering::Relaxed), 0);
+}
@@ -1,5 +1,5 @@
 use std::{
-    io, mem,
+    fs, io, mem,
     path::{Path, PathBuf},
 };
 
@@ -30,7 +30,35 @@ pub fn load_manifest_from_workspace<Metadata: DeserializeOwned>(
         load_manifest_from_workspace_inner(workspace_path, crate_name).map_err(|inner| Error {
             workspace_path: workspace_path.into(),
             crate_name: crate_name.into(),
-            inner,
+            inner: Box::new(inner),
+        })
+    }
+
+    inner(workspace_path.as_ref(), crate_name.as_ref())
+}
+
+/// Like [`load_manifest_from_workspace`], but also return the
+/// `[workspace.metadata]` of the workspace the crate is a member of, if any.
+///
+/// The workspace is the one at `workspace_path` if the crate is found in its
+/// members, otherwise the closest parent dir of the crate whose `Cargo.toml`
+/// has a `[workspace]` table, as cargo finds it.
+///
+/// WARNING: This is a blocking operation.
+pub fn load_manifest_and_workspace_metadata<Metadata: DeserializeOwned + Clone>(
+    workspace_path: impl AsRef<Path>,
+    crate_name: impl AsRef<str>,
+) -> Result<(Manifest<Metadata>, Option<Metadata>), Error> {
+    fn inner<Metadata: DeserializeOwned + Clone>(
+        workspace_path: &Path,
+        crate_name: &str,
+    ) -> Result<(Manifest<Metadata>, Option<Metadata>), Error> {
+        load_manifest_and_workspace_metadata_inner(workspace_path, crate_name).map_err(|inner| {
+            Error {
+                workspace_path: workspace_path.into(),
+                crate_name: crate_name.into(),
+                inner: Box::new(inner),
+            }
         })
     }
 
@@ -43,7 +71,7 @@ pub struct Error {
     workspace_path: Box<Path>,
     crate_name: CompactString,
     #[source]
-    inner: ErrorInner,
+    inner: Box<ErrorInner>,
 }
 
 #[derive(Debug, ThisError)]
@@ -64,11 +92,83 @@ enum ErrorInner {
     NotFound,
 }
 
-#[instrument]
 fn load_manifest_from_workspace_inner<Metadata: DeserializeOwned>(
     workspace_path: &Path,
     crate_name: &str,
 ) -> Result<Manifest<Metadata>, ErrorInner> {
+    find_manifest(workspace_path, crate_name).map(|found| found.manifest)
+}
+
+fn load_manifest_and_workspace_metadata_inner<Metadata: DeserializeOwned + Clone>(
+    workspace_path: &Path,
+    crate_name: &str,
+) -> Result<(Manifest<Metadata>, Option<Metadata>), ErrorInner> {
+    let FoundManifest {
+        manifest,
+        manifest_path,
+        workspace_metadata,
+    } = find_manifest(workspace_path, crate_name)?;
+
+    let workspace_metadata = match (&manifest.workspace, workspace_metadata) {
+        // The crate is the root of its workspace.
+        (Some(ws), _) => ws.metadata.clone(),
+        (None, Some(workspace_metadata)) => workspace_metadata,
+        (None, None) => {
+            let hint = manifest
+                .package
+                .as_ref()
+                .and_then(|package| package.workspace.as_deref());
+            find_workspace_metadata(&manifest_path, hint)?
+        }
+    };
+
+    Ok((manifest, workspace_metadata))
+}
+
+/// Return the metadata of the workspace of the crate at `manifest_path`,
+/// at `hint` relative to it (`package.workspace`) if specified.
+fn find_workspace_metadata<Metadata: DeserializeOwned>(
+    manifest_path: &Path,
+    hint: Option<&str>,
+) -> Result<Option<Metadata>, ErrorInner> {
+    let crate_dir = manifest_path.parent().unwrap();
+    let candidates: Vec<PathBuf> = match hint {
+        Some(hint) => vec![crate_dir.join(hint).join("Cargo.toml")],
+        None => crate_dir
+            .ancestors()
+            .skip(1)
+            .map(|dir| dir.join("Cargo.toml"))
+            .collect(),
+    };
+
+    for path in candidates {
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some(ws) = Manifest::<Metadata>::from_slice_with_metadata(&content)?.workspace {
+            debug!("Found workspace of the crate at {}", path.display());
+            return Ok(ws.metadata);
+        }
+    }
+
+    Ok(None)
+}
+
+struct FoundManifest<Metadata> {
+    manifest: Manifest<Metadata>,
+    manifest_path: PathBuf,
+    /// Metadata of the workspace, if the crate is found among its members.
+    workspace_metadata: Option<Option<Metadata>>,
+}
+
+#[instrument]
+fn find_manifest<Metadata: DeserializeOwned>(
+    workspace_path: &Path,
+    crate_name: &str,
+) -> Result<FoundManifest<Metadata>, ErrorInner> {
     debug!(
         "Loading manifest of crate {crate_name} from workspace: {}",
         workspace_path.display()
@@ -81,6 +181,7 @@ fn load_manifest_from_workspace_inner<Metadata: DeserializeOwned>(
     };
 
     let mut manifest_paths = vec![manifest_path];
+    let mut workspace_metadata = None;
 
     while let Some(manifest_path) = manifest_paths.pop() {
         let manifest = Manifest::<Metadata>::from_path_with_metadata(&manifest_path)?;
@@ -93,7 +194,11 @@ fn load_manifest_from_workspace_inner<Metadata: DeserializeOwned>(
         );
 
         if name == Some(crate_name) {
-            return Ok(manifest);
+            return Ok(FoundManifest {
+                manifest,
+                manifest_path,
+                workspace_metadata,
+            });
         }
 
         if let Some(ws) = manifest.workspace {
@@ -103,6 +208,7 @@ fn load_manifest_from_workspace_inner<Metadata: DeserializeOwned>(
             if members.is_empty() {
                 continue;
             }
+            workspace_metadata = Some(ws.metadata);
 
             let exclude_patterns = excludes
                 .into_iter()
@@ -142,7 +248,8 @@ impl Pattern {
     }
 
     /// * `glob_path` - path to dir to glob for
-    /// return paths relative to `glob_path`.
+    ///
+    /// Return paths relative to `glob_path`.
     fn glob_dirs(&self, glob_path: &Path) -> Result<Vec<PathBuf>, ErrorInner> {
         let mut paths = vec![PathBuf::new()];
 
@@ -268,4 +375,39 @@ mod test {
         assert_eq!(manifest.bin.len(), 1);
         assert_eq!(manifest.bin[0].name.as_deref().unwrap(), "cargo-watch");
     }
+
+    #[test]
+    fn test_load_workspace_metadata() {
+        let p = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join("e2e-tests/manifests/workspace-inheritance");
+
+        let pkg_fmt = |metadata: &cargo_toml::Value| metadata["binstall"]["pkg-fmt"].clone();
+
+        // Found from the workspace root, and from the path of the crate.
+        for path in [p.clone(), p.join("crates/tool/Cargo.toml")] {
+            let (manifest, metadata) =
+                load_manifest_and_workspace_metadata::<cargo_toml::Value>(&path, "tool").unwrap();
+
+            let package = manifest.package.unwrap();
+            assert_eq!(package.version(), "1.2.3");
+            assert_eq!(
+                package.repository(),
+                Some("https://github.com/cargo-bins/cargo-binstall")
+            );
+            assert_eq!(manifest.bin[0].name.as_deref().unwrap(), "tool");
+            assert_eq!(pkg_fmt(&metadata.unwrap()).as_str(), Some("tgz"));
+        }
+
+        // A workspace without `[workspace.metadata]`.
+        let (_, metadata) = load_manifest_and_workspace_metadata::<cargo_toml::Value>(
+            &p.join("../workspace"),
+            "cargo-binstall",
+        )
+        .unwrap();
+        assert!(metadata.is_none());
+    }
 }
@@ -1,6 +1,6 @@
-use crate::detect_targets;
+use crate::{detect_targets, host::host_targets_from_env};
 
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use tokio::sync::OnceCell;
 
@@ -14,7 +14,11 @@ enum DesiredTargetsInner {
 pub struct DesiredTargets(DesiredTargetsInner);
 
 impl DesiredTargets {
-    fn initialized(targets: Vec<String>) -> Self {
+    fn initialized(mut targets: Vec<String>) -> Self {
+        // A target given twice would only be tried again.
+        let mut seen = HashSet::new();
+        targets.retain(|target| seen.insert(target.clone()));
+
         Self(DesiredTargetsInner::Initialized(targets))
     }
 
@@ -55,13 +59,15 @@ impl DesiredTargets {
 }
 
 /// If opts_targets is `Some`, then it will be used.
+/// Otherwise, if [`HOST_TARGETS_ENV`](crate::host::HOST_TARGETS_ENV) is set, the targets it lists will be
+/// used.
 /// Otherwise, call `detect_targets` using `tokio::spawn` to detect targets.
 ///
 /// Since `detect_targets` internally spawns a process and wait for it,
 /// it's pretty costy, it is recommended to run this fn ASAP and
 /// reuse the result.
 pub fn get_desired_targets(opts_targets: Option<Vec<String>>) -> DesiredTargets {
-    if let Some(targets) = opts_targets {
+    if let Some(targets) = opts_targets.or_else(host_targets_from_env) {
         DesiredTargets::initialized(targets)
     } else {
         DesiredTargets::auto_detect()
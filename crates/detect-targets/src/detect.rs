@@ -7,9 +7,15 @@ use std::{
 
 use cfg_if::cfg_if;
 use tokio::process::Command;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use tokio::sync::OnceCell;
 #[cfg(feature = "tracing")]
 use tracing::debug;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::host::is_gnu_or_musl;
+use crate::host::{candidate_targets, HostProbes};
+
 cfg_if! {
     if #[cfg(any(target_os = "linux",  target_os = "android"))] {
         mod linux;
@@ -20,20 +26,21 @@ cfg_if! {
     }
 }
 
+#[cfg(any(target_os = "windows", test))]
+mod windows_arch;
+
 /// Detect the targets supported at runtime,
 /// which might be different from `TARGET` which is detected
 /// at compile-time.
 ///
-/// Return targets supported in the order of preference.
-/// If target_os is linux and it support gnu, then it is preferred
-/// to musl.
-///
-/// If target_os is mac and it is aarch64, then aarch64 is preferred
-/// to x86_64.
-///
-/// Check [this issue](https://github.com/ryankurte/cargo-binstall/issues/155)
-/// for more information.
+/// Return targets supported in the order of preference, see
+/// [`candidate_targets`].
 pub async fn detect_targets() -> Vec<String> {
+    candidate_targets(&probe_host().await)
+}
+
+/// Probe the host for what [`candidate_targets`] needs.
+pub async fn probe_host() -> HostProbes {
     let target = get_target_from_rustc().await;
     #[cfg(feature = "tracing")]
     debug!("get_target_from_rustc()={target:?}");
@@ -44,21 +51,48 @@ pub async fn detect_targets() -> Vec<String> {
         target.unwrap_or(crate::TARGET).to_string()
     });
 
+    #[allow(unused_mut)]
+    let mut probes = HostProbes {
+        target,
+        ..Default::default()
+    };
+
     cfg_if! {
         if #[cfg(target_os = "macos")] {
-            let mut targets = vec![target];
-            targets.extend(macos::detect_alternative_targets(&targets[0]).await);
-            targets
+            probes.runnable_archs = macos::probe_runnable_archs(&probes.target).await;
         } else if #[cfg(target_os = "windows")] {
-            let mut targets = vec![target];
-            targets.extend(windows::detect_alternative_targets(&targets[0]));
-            targets
+            probes.native_arch = windows::native_arch().map(ToString::to_string);
+            probes.runnable_archs = windows::runnable_archs();
         } else if #[cfg(any(target_os = "linux", target_os = "android"))] {
             // Linux is a bit special, since the result from `guess_host_triple`
             // might be wrong about whether glibc or musl is used.
-            linux::detect_targets(target).await
+            if is_gnu_or_musl(&probes.target) {
+                probes.glibc = Some(linux::probe_glibc(&probes.target).await);
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    debug!("probe_host()={probes:?}");
+
+    probes
+}
+
+/// Detect the version of glibc on the host as `(major, minor)`, `None` if
+/// it is not Linux or does not use glibc.
+///
+/// It is only detected once.
+pub async fn detect_glibc_version() -> Option<(u32, u32)> {
+    cfg_if! {
+        if #[cfg(any(target_os = "linux", target_os = "android"))] {
+            static GLIBC_VERSION: OnceCell<Option<(u32, u32)>> = OnceCell::const_new();
+
+            let version = *GLIBC_VERSION.get_or_init(linux::glibc_version).await;
+            #[cfg(feature = "tracing")]
+            debug!("detect_glibc_version()={version:?}");
+            version
         } else {
-            vec![target]
+            None
         }
     }
 }
@@ -1,82 +1,105 @@
-use std::{
-    process::{Output, Stdio},
-    str,
-};
+use std::process::{Output, Stdio};
 
 use tokio::{process::Command, task};
 #[cfg(feature = "tracing")]
 use tracing::debug;
 
-pub(super) async fn detect_targets(target: String) -> Vec<String> {
-    let (prefix, postfix) = target
-        .rsplit_once('-')
-        .expect("unwrap: target always has a -");
-
-    let (abi, libc) = if let Some(abi) = postfix.strip_prefix("musl") {
-        (abi, Libc::Musl)
-    } else if let Some(abi) = postfix.strip_prefix("gnu") {
-        (abi, Libc::Gnu)
-    } else if let Some(abi) = postfix.strip_prefix("android") {
-        (abi, Libc::Android)
-    } else {
-        (postfix, Libc::Unknown)
+/// Return whether the dynamic loader of glibc runs on a host of `target`.
+pub(super) async fn probe_glibc(target: &str) -> bool {
+    let cpu_arch = target
+        .split_once('-')
+        .expect("unwrap: target always has a - for cpu_arch")
+        .0;
+
+    let handles: Vec<_> = {
+        let cpu_arch_suffix = cpu_arch.replace('_', "-");
+        let filename = format!("ld-linux-{cpu_arch_suffix}.so.2");
+        let dirname = format!("{cpu_arch}-linux-gnu");
+
+        [
+            format!("/lib/{filename}"),
+            format!("/lib64/{filename}"),
+            format!("/lib/{dirname}/{filename}"),
+            format!("/lib64/{dirname}/{filename}"),
+            format!("/usr/lib/{dirname}/{filename}"),
+            format!("/usr/lib64/{dirname}/{filename}"),
+        ]
+        .into_iter()
+        .map(|p| AutoAbortHandle(tokio::spawn(is_gnu_ld(p))))
+        .collect()
     };
 
-    let musl_fallback_target = || format!("{prefix}-{}{abi}", "musl");
-
-    match libc {
-        // guess_host_triple cannot detect whether the system is using glibc,
-        // musl libc or other libc.
-        //
-        // On Alpine, you can use `apk add gcompat` to install glibc
-        // and run glibc programs.
-        //
-        // As such, we need to launch the test ourselves.
-        Libc::Gnu | Libc::Musl => {
-            let cpu_arch = target
-                .split_once('-')
-                .expect("unwrap: target always has a - for cpu_arch")
-                .0;
-
-            let handles: Vec<_> = {
-                let cpu_arch_suffix = cpu_arch.replace('_', "-");
-                let filename = format!("ld-linux-{cpu_arch_suffix}.so.2");
-                let dirname = format!("{cpu_arch}-linux-gnu");
-
-                [
-                    format!("/lib/{filename}"),
-                    format!("/lib64/{filename}"),
-                    format!("/lib/{dirname}/{filename}"),
-                    format!("/lib64/{dirname}/{filename}"),
-                    format!("/usr/lib/{dirname}/{filename}"),
-                    format!("/usr/lib64/{dirname}/{filename}"),
-                ]
-                .into_iter()
-                .map(|p| AutoAbortHandle(tokio::spawn(is_gnu_ld(p))))
-                .collect()
-            };
-
-            let has_glibc = async move {
-                for mut handle in handles {
-                    if let Ok(true) = (&mut handle.0).await {
-                        return true;
-                    }
-                }
-
-                false
-            }
-            .await;
-
-            [
-                has_glibc.then(|| format!("{cpu_arch}-unknown-linux-gnu{abi}")),
-                Some(musl_fallback_target()),
-            ]
+    for mut handle in handles {
+        if let Ok(true) = (&mut handle.0).await {
+            return true;
         }
-        Libc::Android | Libc::Unknown => [Some(target.clone()), Some(musl_fallback_target())],
     }
-    .into_iter()
-    .flatten()
-    .collect()
+
+    false
+}
+
+/// Return the version of glibc on the host as `(major, minor)`, `None` if
+/// it does not use glibc.
+pub(super) async fn glibc_version() -> Option<(u32, u32)> {
+    if cfg!(target_env = "gnu") {
+        return parse_glibc_version(gnu_libc_version()?);
+    }
+
+    // Built with musl, so ask the `ldd` of the host.
+    let Output { status, stdout, .. } = Command::new("ldd")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+
+    #[cfg(feature = "tracing")]
+    debug!("`ldd --version`: status={status}, stdout='{stdout}'");
+
+    if status.success() {
+        parse_ldd_version(&stdout)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_env = "gnu")]
+fn gnu_libc_version() -> Option<&'static str> {
+    use std::{ffi::CStr, os::raw::c_char};
+
+    extern "C" {
+        fn gnu_get_libc_version() -> *const c_char;
+    }
+
+    // SAFETY: it returns a static nul-terminated string.
+    unsafe { CStr::from_ptr(gnu_get_libc_version()) }
+        .to_str()
+        .ok()
+}
+
+#[cfg(not(target_env = "gnu"))]
+fn gnu_libc_version() -> Option<&'static str> {
+    None
+}
+
+/// Parse the version of glibc in the output of `ldd --version`, e.g.
+/// `ldd (Ubuntu GLIBC 2.35-0ubuntu3.8) 2.35`.
+fn parse_ldd_version(output: &str) -> Option<(u32, u32)> {
+    let line = output.lines().next()?;
+    if !(line.contains("GLIBC") || line.contains("GNU libc")) {
+        return None;
+    }
+    parse_glibc_version(line.split_whitespace().last()?)
+}
+
+/// Parse a version of glibc such as `2.35`.
+fn parse_glibc_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
 }
 
 async fn is_gnu_ld(cmd: String) -> bool {
@@ -153,8 +176,6 @@ You are not meant to run this directly.
 enum Libc {
     Gnu,
     Musl,
-    Android,
-    Unknown,
 }
 
 struct AutoAbortHandle<T>(task::JoinHandle<T>);
@@ -164,3 +185,23 @@ impl<T> Drop for AutoAbortHandle<T> {
         self.0.abort();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_ldd_version() {
+        assert_eq!(
+            parse_ldd_version(
+                "ldd (Ubuntu GLIBC 2.35-0ubuntu3.8) 2.35\nCopyright (C) 2022 Free Software Foundation, Inc.\n"
+            ),
+            Some((2, 35))
+        );
+        assert_eq!(parse_ldd_version("ldd (GNU libc) 2.39\n"), Some((2, 39)));
+        assert_eq!(
+            parse_ldd_version("musl libc (x86_64)\nVersion 1.2.4\n"),
+            None
+        );
+    }
+}
@@ -1,16 +1,21 @@
-use std::mem;
+use std::{env, mem};
 use windows_sys::Win32::{
-    Foundation::{FreeLibrary, HMODULE, S_OK},
+    Foundation::{FreeLibrary, BOOL, HANDLE, HMODULE, S_OK},
     System::{
         LibraryLoader::{GetProcAddress, LoadLibraryA},
         SystemInformation::{
             IMAGE_FILE_MACHINE, IMAGE_FILE_MACHINE_AMD64, IMAGE_FILE_MACHINE_ARM,
             IMAGE_FILE_MACHINE_ARM64, IMAGE_FILE_MACHINE_I386,
         },
-        Threading::{GetMachineTypeAttributes, UserEnabled, Wow64Container, MACHINE_ATTRIBUTES},
+        Threading::{
+            GetCurrentProcess, GetMachineTypeAttributes, IsWow64Process2, UserEnabled,
+            Wow64Container, MACHINE_ATTRIBUTES,
+        },
     },
 };
 
+use super::windows_arch;
+
 struct LibraryHandle(HMODULE);
 
 impl LibraryHandle {
@@ -69,71 +74,53 @@ fn is_arch_supported(arch: IMAGE_FILE_MACHINE) -> bool {
     is_arch_supported_inner(arch).unwrap_or(false)
 }
 
-pub(super) fn detect_alternative_targets(target: &str) -> impl Iterator<Item = String> {
-    let (prefix, abi) = target
-        .rsplit_once('-')
-        .expect("unwrap: target always has a -");
-
-    let arch = prefix
-        .split_once('-')
-        .expect("unwrap: target always has at least two -")
-        .0;
-
-    let msvc_fallback_target = (abi != "msvc").then(|| format!("{prefix}-msvc"));
-
-    let gnu_fallback_targets = (abi == "msvc")
-        .then(|| [format!("{prefix}-gnu"), format!("{prefix}-gnullvm")])
-        .into_iter()
-        .flatten();
-
-    let x64_fallback_targets = (arch != "x86_64" && is_arch_supported(IMAGE_FILE_MACHINE_AMD64))
-        .then_some([
-            "x86_64-pc-windows-msvc",
-            "x86_64-pc-windows-gnu",
-            "x86_64-pc-windows-gnullvm",
-        ])
-        .into_iter()
-        .flatten()
-        .map(ToString::to_string);
-
-    let x86_fallback_targets = (arch != "x86" && is_arch_supported(IMAGE_FILE_MACHINE_I386))
-        .then_some([
-            "i586-pc-windows-msvc",
-            "i586-pc-windows-gnu",
-            "i586-pc-windows-gnullvm",
-            "i686-pc-windows-msvc",
-            "i686-pc-windows-gnu",
-            "i686-pc-windows-gnullvm",
-        ])
-        .into_iter()
-        .flatten()
-        .map(ToString::to_string);
+type IsWow64Process2FuncType =
+    unsafe extern "system" fn(HANDLE, *mut IMAGE_FILE_MACHINE, *mut IMAGE_FILE_MACHINE) -> BOOL;
+const _: () = {
+    // Ensure that our hand-written signature matches the actual function signature.
+    let _: IsWow64Process2FuncType = IsWow64Process2;
+};
 
-    let arm32_fallback_targets = (arch != "thumbv7a" && is_arch_supported(IMAGE_FILE_MACHINE_ARM))
-        .then_some([
-            "thumbv7a-pc-windows-msvc",
-            "thumbv7a-pc-windows-gnu",
-            "thumbv7a-pc-windows-gnullvm",
-        ])
-        .into_iter()
-        .flatten()
-        .map(ToString::to_string);
+fn native_machine() -> Option<IMAGE_FILE_MACHINE> {
+    // IsWow64Process2 is only available on Win10 1709+, so dynamically load it.
+    let kernel32 = LibraryHandle::new(b"kernel32.dll\0")?;
+    // SAFETY: IsWow64Process2FuncType is checked to match the real function signature.
+    let is_wow64_process2 =
+        unsafe { kernel32.get_proc_address::<IsWow64Process2FuncType>(b"IsWow64Process2\0") }?;
+
+    let mut process_machine = 0;
+    let mut native_machine = 0;
+    let succeeded = unsafe {
+        is_wow64_process2(
+            GetCurrentProcess(),
+            &mut process_machine,
+            &mut native_machine,
+        )
+    } != 0;
+
+    succeeded.then_some(native_machine)
+}
 
-    let arm64_fallback_targets = (arch != "aarch64" && is_arch_supported(IMAGE_FILE_MACHINE_ARM64))
-        .then_some([
-            "aarch64-pc-windows-msvc",
-            "aarch64-pc-windows-gnu",
-            "aarch64-pc-windows-gnullvm",
-        ])
-        .into_iter()
-        .flatten()
-        .map(ToString::to_string);
+/// Return the native architecture of the host, which is different from the
+/// one binstall is built for if it runs emulated.
+pub(super) fn native_arch() -> Option<&'static str> {
+    windows_arch::native_arch(
+        native_machine(),
+        env::var("PROCESSOR_ARCHITEW6432").ok().as_deref(),
+        env::var("PROCESSOR_ARCHITECTURE").ok().as_deref(),
+    )
+}
 
-    msvc_fallback_target
-        .into_iter()
-        .chain(gnu_fallback_targets)
-        .chain(x64_fallback_targets)
-        .chain(x86_fallback_targets)
-        .chain(arm32_fallback_targets)
-        .chain(arm64_fallback_targets)
+/// Return the architectures the machine runs, natively or emulated.
+pub(super) fn runnable_archs() -> Vec<String> {
+    [
+        (IMAGE_FILE_MACHINE_AMD64, "x86_64"),
+        (IMAGE_FILE_MACHINE_I386, "i686"),
+        (IMAGE_FILE_MACHINE_ARM, "thumbv7a"),
+        (IMAGE_FILE_MACHINE_ARM64, "aarch64"),
+    ]
+    .into_iter()
+    .filter(|(machine, _)| is_arch_supported(*machine))
+    .map(|(_, arch)| arch.to_string())
+    .collect()
 }
@@ -0,0 +1,90 @@
+//! Native architecture of a Windows host.
+//!
+//! It might be different from the one binstall is built for, e.g. a x86_64
+//! binstall running emulated on Windows on ARM.
+//!
+//! Kept free of windows API calls so that it can be tested on any platform.
+
+// `IMAGE_FILE_MACHINE_*` returned by `IsWow64Process2`.
+const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
+const IMAGE_FILE_MACHINE_ARMNT: u16 = 0x01c4;
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+
+/// Return the native architecture, as used in target triples.
+///
+///  * `native_machine` - native machine returned by `IsWow64Process2`, which
+///    is correct even in an emulated process.
+///  * `processor_architew6432` - `PROCESSOR_ARCHITEW6432`, which is only set
+///    in WOW64 processes.
+///  * `processor_architecture` - `PROCESSOR_ARCHITECTURE`, which is the
+///    architecture of the current process.
+pub(super) fn native_arch(
+    native_machine: Option<u16>,
+    processor_architew6432: Option<&str>,
+    processor_architecture: Option<&str>,
+) -> Option<&'static str> {
+    if let Some(arch) = native_machine.and_then(arch_from_machine) {
+        return Some(arch);
+    }
+
+    processor_architew6432
+        .or(processor_architecture)
+        .and_then(arch_from_env)
+}
+
+fn arch_from_machine(machine: u16) -> Option<&'static str> {
+    match machine {
+        IMAGE_FILE_MACHINE_ARM64 => Some("aarch64"),
+        IMAGE_FILE_MACHINE_AMD64 => Some("x86_64"),
+        IMAGE_FILE_MACHINE_I386 => Some("i686"),
+        IMAGE_FILE_MACHINE_ARMNT => Some("thumbv7a"),
+        _ => None,
+    }
+}
+
+fn arch_from_env(processor_architecture: &str) -> Option<&'static str> {
+    match processor_architecture.trim().to_ascii_uppercase().as_str() {
+        "ARM64" => Some("aarch64"),
+        "AMD64" => Some("x86_64"),
+        "X86" => Some("i686"),
+        "ARM" => Some("thumbv7a"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_native_arch() {
+        // x86_64 binstall emulated on Windows on ARM, where
+        // PROCESSOR_ARCHITECTURE is the one of the process.
+        assert_eq!(
+            native_arch(Some(IMAGE_FILE_MACHINE_ARM64), None, Some("AMD64")),
+            Some("aarch64")
+        );
+        assert_eq!(
+            native_arch(Some(IMAGE_FILE_MACHINE_AMD64), None, Some("AMD64")),
+            Some("x86_64")
+        );
+
+        // IsWow64Process2 unavailable, i686 binstall in WOW64.
+        assert_eq!(
+            native_arch(None, Some("ARM64"), Some("x86")),
+            Some("aarch64")
+        );
+        assert_eq!(
+            native_arch(None, Some("AMD64"), Some("x86")),
+            Some("x86_64")
+        );
+        assert_eq!(native_arch(None, None, Some("x86")), Some("i686"));
+
+        // Unknown machine falls back to the environment.
+        assert_eq!(native_arch(Some(0), None, Some("ARM64")), Some("aarch64"));
+
+        assert_eq!(native_arch(None, None, Some("IA64")), None);
+        assert_eq!(native_arch(None, None, None), None);
+    }
+}
@@ -0,0 +1,406 @@
+//! Targets supported by the host, derived from what was probed at runtime.
+//!
+//! Kept free of any system call so that any host can be simulated by
+//! constructing [`HostProbes`] by hand, on any platform.
+
+use std::{collections::HashSet, env};
+
+/// Environment variable overriding the detected targets, a comma-separated
+/// list of target triples in the order of preference.
+pub const HOST_TARGETS_ENV: &str = "BINSTALL_HOST_TARGETS";
+
+const AARCH64_DARWIN: &str = "aarch64-apple-darwin";
+const X86_DARWIN: &str = "x86_64-apple-darwin";
+/// https://doc.rust-lang.org/nightly/rustc/platform-support/x86_64h-apple-darwin.html
+///
+/// This target is an x86_64 target that only supports Apple's late-gen
+/// (Haswell-compatible) Intel chips.
+///
+/// It enables a set of target features available on these chips (AVX2 and similar),
+/// and MachO binaries built with this target may be used as the x86_64h entry in
+/// universal binaries ("fat" MachO binaries), and will fail to load on machines
+/// that do not support this.
+///
+/// It is similar to x86_64-apple-darwin in nearly all respects, although
+/// the minimum supported OS version is slightly higher (it requires 10.8
+/// rather than x86_64-apple-darwin's 10.7).
+const X86H_DARWIN: &str = "x86_64h-apple-darwin";
+const UNIVERSAL_DARWIN: &str = "universal-apple-darwin";
+const UNIVERSAL2_DARWIN: &str = "universal2-apple-darwin";
+
+/// What was probed about the host at runtime.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostProbes {
+    /// Target reported by `rustc -vV`, or guessed from the system.
+    pub target: String,
+    /// Linux: whether the dynamic loader of glibc runs, `None` if not probed.
+    pub glibc: Option<bool>,
+    /// macOS: architectures `arch` can run, e.g. `x86_64` under Rosetta.
+    ///
+    /// Windows: architectures the machine runs natively or emulated,
+    /// according to `GetMachineTypeAttributes`.
+    pub runnable_archs: Vec<String>,
+    /// Windows: native architecture of the machine, which differs from the
+    /// one of `target` if binstall runs emulated.
+    pub native_arch: Option<String>,
+}
+
+impl HostProbes {
+    fn can_run(&self, arch: &str) -> bool {
+        self.runnable_archs.iter().any(|runnable| runnable == arch)
+    }
+}
+
+/// Return the targets supported by the host described by `probes`, in the
+/// order of preference.
+///
+/// If target_os is linux and it support gnu, then it is preferred
+/// to musl.
+///
+/// If target_os is mac and it is aarch64, then aarch64 is preferred
+/// to x86_64.
+///
+/// Check [this issue](https://github.com/ryankurte/cargo-binstall/issues/155)
+/// for more information.
+pub fn candidate_targets(probes: &HostProbes) -> Vec<String> {
+    let target = &probes.target;
+
+    let mut targets = if target.ends_with("-apple-darwin") {
+        darwin_targets(probes)
+    } else if target.contains("-windows") {
+        windows_targets(probes)
+    } else if target.contains("-linux-") {
+        linux_targets(probes)
+    } else {
+        vec![target.clone()]
+    };
+
+    let mut seen = HashSet::new();
+    targets.retain(|target| seen.insert(target.clone()));
+    targets
+}
+
+/// Return the targets set in [`HOST_TARGETS_ENV`], if any.
+pub fn host_targets_from_env() -> Option<Vec<String>> {
+    parse_host_targets(&env::var(HOST_TARGETS_ENV).ok()?)
+}
+
+fn parse_host_targets(value: &str) -> Option<Vec<String>> {
+    let targets: Vec<_> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+        .map(ToString::to_string)
+        .collect();
+
+    (!targets.is_empty()).then_some(targets)
+}
+
+fn darwin_targets(probes: &HostProbes) -> Vec<String> {
+    let target = probes.target.as_str();
+
+    match target {
+        AARCH64_DARWIN => vec![
+            Some(target),
+            // Prefer universal as it provides native arm executable
+            Some(UNIVERSAL_DARWIN),
+            Some(UNIVERSAL2_DARWIN),
+            // Prefer x86h since it is more optimized
+            probes.can_run("x86_64h").then_some(X86H_DARWIN),
+            probes.can_run("x86_64").then_some(X86_DARWIN),
+        ],
+        X86_DARWIN => vec![
+            Some(target),
+            probes.can_run("x86_64h").then_some(X86H_DARWIN),
+            Some(UNIVERSAL_DARWIN),
+            Some(UNIVERSAL2_DARWIN),
+        ],
+        X86H_DARWIN => vec![
+            Some(target),
+            Some(X86_DARWIN),
+            Some(UNIVERSAL_DARWIN),
+            Some(UNIVERSAL2_DARWIN),
+        ],
+        _ => vec![Some(target)],
+    }
+    .into_iter()
+    .flatten()
+    .map(ToString::to_string)
+    .collect()
+}
+
+fn windows_targets(probes: &HostProbes) -> Vec<String> {
+    let target = &probes.target;
+
+    // binstall might run emulated, e.g. x86_64 on Windows on ARM,
+    // so prefer the targets of the native architecture.
+    let mut targets = match probes.native_arch.as_deref() {
+        Some(arch) if should_prefer(arch, target) => preferred_windows_targets(arch),
+        _ => Vec::new(),
+    };
+    targets.push(target.clone());
+
+    let (prefix, abi) = match target.rsplit_once('-') {
+        Some(split) => split,
+        None => return targets,
+    };
+    let arch = prefix.split('-').next().unwrap_or_default();
+
+    if abi == "msvc" {
+        targets.extend([format!("{prefix}-gnu"), format!("{prefix}-gnullvm")]);
+    } else {
+        targets.push(format!("{prefix}-msvc"));
+    }
+
+    let fallbacks: [(&str, &[&str]); 4] = [
+        ("x86_64", &["x86_64"]),
+        ("i686", &["i586", "i686"]),
+        ("thumbv7a", &["thumbv7a"]),
+        ("aarch64", &["aarch64"]),
+    ];
+    for (runnable_arch, archs) in fallbacks {
+        if !archs.contains(&arch) && probes.can_run(runnable_arch) {
+            targets.extend(archs.iter().flat_map(|arch| {
+                ["msvc", "gnu", "gnullvm"].map(|abi| format!("{arch}-pc-windows-{abi}"))
+            }));
+        }
+    }
+
+    targets
+}
+
+/// Return the targets to prefer on a Windows host of `native_arch`, in
+/// order.
+///
+/// Few crates publish for Windows on ARM, but x86_64 and i686 binaries run
+/// there under emulation.
+fn preferred_windows_targets(native_arch: &str) -> Vec<String> {
+    match native_arch {
+        "aarch64" => vec![
+            "aarch64-pc-windows-msvc".to_string(),
+            "x86_64-pc-windows-msvc".to_string(),
+            "i686-pc-windows-msvc".to_string(),
+        ],
+        arch => vec![format!("{arch}-pc-windows-msvc")],
+    }
+}
+
+/// Return `true` if the preferred targets of `native_arch` should be put in
+/// front of `target`, which is not the case for a native non-msvc
+/// toolchain.
+fn should_prefer(native_arch: &str, target: &str) -> bool {
+    let arch = target.split('-').next().unwrap_or_default();
+    arch != native_arch || target.ends_with("-msvc")
+}
+
+fn linux_targets(probes: &HostProbes) -> Vec<String> {
+    let target = &probes.target;
+    let (prefix, postfix) = match target.rsplit_once('-') {
+        Some(split) => split,
+        None => return vec![target.clone()],
+    };
+
+    let musl_fallback_target = format!("{prefix}-musl{}", linux_abi(postfix));
+
+    // guess_host_triple cannot detect whether the system is using glibc,
+    // musl libc or other libc.
+    //
+    // On Alpine, you can use `apk add gcompat` to install glibc
+    // and run glibc programs.
+    //
+    // As such, the dynamic loader of glibc is probed.
+    if is_gnu_or_musl(target) {
+        let cpu_arch = target.split('-').next().unwrap_or_default();
+
+        [
+            (probes.glibc == Some(true))
+                .then(|| format!("{cpu_arch}-unknown-linux-gnu{}", linux_abi(postfix))),
+            Some(musl_fallback_target),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    } else {
+        vec![target.clone(), musl_fallback_target]
+    }
+}
+
+/// Return `true` if `target` is a linux target using glibc or musl, on
+/// which the libc has to be probed.
+pub(crate) fn is_gnu_or_musl(target: &str) -> bool {
+    target.rsplit_once('-').map_or(false, |(_, postfix)| {
+        postfix.starts_with("gnu") || postfix.starts_with("musl")
+    })
+}
+
+/// Return the abi suffix of a linux target, e.g. `eabihf` for `gnueabihf`.
+fn linux_abi(postfix: &str) -> &str {
+    ["musl", "gnu", "android"]
+        .iter()
+        .find_map(|libc| postfix.strip_prefix(libc))
+        .unwrap_or(postfix)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn probes(target: &str) -> HostProbes {
+        HostProbes {
+            target: target.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn archs(archs: &[&str]) -> Vec<String> {
+        archs.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_linux() {
+        let glibc = HostProbes {
+            glibc: Some(true),
+            ..probes("x86_64-unknown-linux-gnu")
+        };
+        assert_eq!(
+            candidate_targets(&glibc),
+            ["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"]
+        );
+
+        // Alpine, where rustc reports the host as musl.
+        let musl = HostProbes {
+            glibc: Some(false),
+            ..probes("x86_64-unknown-linux-musl")
+        };
+        assert_eq!(candidate_targets(&musl), ["x86_64-unknown-linux-musl"]);
+
+        // Alpine with gcompat.
+        let gcompat = HostProbes {
+            glibc: Some(true),
+            ..probes("aarch64-unknown-linux-musl")
+        };
+        assert_eq!(
+            candidate_targets(&gcompat),
+            ["aarch64-unknown-linux-gnu", "aarch64-unknown-linux-musl"]
+        );
+
+        let armhf = HostProbes {
+            glibc: Some(true),
+            ..probes("armv7-unknown-linux-gnueabihf")
+        };
+        assert_eq!(
+            candidate_targets(&armhf),
+            [
+                "armv7-unknown-linux-gnueabihf",
+                "armv7-unknown-linux-musleabihf"
+            ]
+        );
+
+        assert_eq!(
+            candidate_targets(&probes("aarch64-linux-android")),
+            ["aarch64-linux-android", "aarch64-linux-musl"]
+        );
+    }
+
+    #[test]
+    fn test_darwin() {
+        // Apple silicon with Rosetta.
+        let rosetta = HostProbes {
+            runnable_archs: archs(&["x86_64"]),
+            ..probes(AARCH64_DARWIN)
+        };
+        assert_eq!(
+            candidate_targets(&rosetta),
+            [
+                AARCH64_DARWIN,
+                UNIVERSAL_DARWIN,
+                UNIVERSAL2_DARWIN,
+                X86_DARWIN
+            ]
+        );
+
+        // Apple silicon without Rosetta.
+        assert_eq!(
+            candidate_targets(&probes(AARCH64_DARWIN)),
+            [AARCH64_DARWIN, UNIVERSAL_DARWIN, UNIVERSAL2_DARWIN]
+        );
+
+        // Intel before Haswell.
+        assert_eq!(
+            candidate_targets(&probes(X86_DARWIN)),
+            [X86_DARWIN, UNIVERSAL_DARWIN, UNIVERSAL2_DARWIN]
+        );
+
+        // Haswell or later Intel.
+        let haswell = HostProbes {
+            runnable_archs: archs(&["x86_64h"]),
+            ..probes(X86_DARWIN)
+        };
+        assert_eq!(
+            candidate_targets(&haswell),
+            [X86_DARWIN, X86H_DARWIN, UNIVERSAL_DARWIN, UNIVERSAL2_DARWIN]
+        );
+    }
+
+    #[test]
+    fn test_windows() {
+        // x86_64 binstall emulated on Windows on ARM.
+        let emulated = HostProbes {
+            native_arch: Some("aarch64".to_string()),
+            runnable_archs: archs(&["x86_64", "i686", "aarch64"]),
+            ..probes("x86_64-pc-windows-msvc")
+        };
+        let targets = candidate_targets(&emulated);
+        assert_eq!(
+            targets[..5],
+            [
+                "aarch64-pc-windows-msvc",
+                "x86_64-pc-windows-msvc",
+                "i686-pc-windows-msvc",
+                "x86_64-pc-windows-gnu",
+                "x86_64-pc-windows-gnullvm",
+            ]
+        );
+        assert!(targets.contains(&"i586-pc-windows-msvc".to_string()));
+        assert!(targets.contains(&"aarch64-pc-windows-gnullvm".to_string()));
+        assert!(!targets.contains(&"thumbv7a-pc-windows-msvc".to_string()));
+
+        // Native gnu toolchain on x86_64.
+        let gnu = HostProbes {
+            native_arch: Some("x86_64".to_string()),
+            ..probes("x86_64-pc-windows-gnu")
+        };
+        assert_eq!(
+            candidate_targets(&gnu),
+            ["x86_64-pc-windows-gnu", "x86_64-pc-windows-msvc"]
+        );
+    }
+
+    #[test]
+    fn test_other() {
+        assert_eq!(
+            candidate_targets(&probes("x86_64-unknown-freebsd")),
+            ["x86_64-unknown-freebsd"]
+        );
+    }
+
+    #[test]
+    fn test_should_prefer() {
+        assert!(should_prefer("aarch64", "x86_64-pc-windows-msvc"));
+        assert!(should_prefer("aarch64", "x86_64-pc-windows-gnu"));
+        assert!(should_prefer("aarch64", "aarch64-pc-windows-msvc"));
+        assert!(!should_prefer("x86_64", "x86_64-pc-windows-gnu"));
+    }
+
+    #[test]
+    fn test_parse_host_targets() {
+        assert_eq!(
+            parse_host_targets("x86_64-unknown-linux-musl, aarch64-unknown-linux-musl,"),
+            Some(archs(&[
+                "x86_64-unknown-linux-musl",
+                "aarch64-unknown-linux-musl"
+            ]))
+        );
+        assert_eq!(parse_host_targets(" , "), None);
+    }
+}
@@ -12,12 +12,17 @@
 //!  - The binary is built for x86_64-apple-darwin, but run on
 //!    aarch64-apple-darwin.
 //!
-//! This crate provides two API:
+//! This crate provides these API:
 //!  - [`detect_targets`] provides the API to get the target
 //!    at runtime, but the code is run on the current thread.
 //!  - [`get_desired_targets`] provides the API to either
-//!    use override provided by the users, or run [`detect_targets`]
-//!    in the background using [`tokio::spawn`].
+//!    use override provided by the users or [`host::HOST_TARGETS_ENV`],
+//!    or run [`detect_targets`] in the background using [`tokio::spawn`].
+//!  - [`host::candidate_targets`] derives the targets from the
+//!    [`host::HostProbes`] returned by [`probe_host`], or constructed by
+//!    hand to simulate another host.
+//!  - [`detect_glibc_version`] detects the version of glibc on Linux, to
+//!    tell whether binaries linked to glibc can run.
 //!
 //! # Example
 //!
@@ -65,7 +70,9 @@
 //! ```
 
 mod detect;
-pub use detect::detect_targets;
+pub use detect::{detect_glibc_version, detect_targets, probe_host};
+
+pub mod host;
 
 mod desired_targets;
 pub use desired_targets::{get_desired_targets, DesiredTargets};
@@ -14,6 +14,24 @@ mkdir -p "$othertmpdir/bin"
 cp "./$1" "$othertmpdir/bin/"
 
 
+## Test --fail-fast
+set +e
+cargo binstall --no-confirm --fail-fast cargo-watch@8.4.0 non-existent-clippy
+exit_code="$?"
+
+set -e
+
+if [ "$exit_code" != 76 ]; then
+    echo "Expected exit code 76, but actual exit code $exit_code"
+    exit 1
+fi
+
+if command -v cargo-watch; then
+    echo "Expected cargo-watch not to be installed with --fail-fast"
+    exit 1
+fi
+
+
 ## Test --continue-on-failure
 set +e
 cargo binstall --no-confirm --continue-on-failure cargo-watch@8.4.0 non-existent-clippy
@@ -45,6 +63,18 @@ if [ "$exit_code" != 76 ]; then
     exit 1
 fi
 
+## Test that it is the default
+set +e
+cargo binstall --no-confirm --force cargo-watch@8.4.0 non-existent-clippy
+exit_code="$?"
+
+set -e
+
+if [ "$exit_code" != 76 ]; then
+    echo "Expected exit code 76, but actual exit code $exit_code"
+    exit 1
+fi
+
 # Test if both crates are invalid
 set +e
 cargo binstall --no-confirm --continue-on-failure non-existent-clippy non-existent-clippy2
@@ -0,0 +1,68 @@
+#!/bin/bash
+
+set -euxo pipefail
+
+unset CARGO_INSTALL_ROOT
+
+CARGO_HOME=$(mktemp -d 2>/dev/null || mktemp -d -t 'cargo-home')
+export CARGO_HOME
+export PATH="$CARGO_HOME/bin:$PATH"
+
+expect_exit_code() {
+    expected="$1"
+    shift
+
+    set +e
+    "$@"
+    exit_code="$?"
+    set -e
+
+    if [ "$exit_code" != "$expected" ]; then
+        echo "Expected exit code $expected, but actual exit code $exit_code"
+        exit 1
+    fi
+}
+
+# Test the exit codes are listed
+"./$1" binstall --list-exit-codes | grep -E '^114 +network '
+
+# Test invalid arguments
+expect_exit_code 2 "./$1" binstall --upgrade --version 1
+
+# Test nothing can be downloaded in offline mode
+expect_exit_code 109 "./$1" binstall --no-confirm --offline cargo-binstall
+
+# Test a crate which is not found is reported with nothing installed
+set +e
+output="$("./$1" binstall --no-confirm cargo-binstall-no-such-crate 2>&1)"
+exit_code="$?"
+set -e
+[ "$exit_code" = 76 ]
+echo "$output" | grep -q 'nothing is installed'
+
+# Test the artifact cannot be downloaded because of the network
+expect_exit_code 114 "./$1" binstall --no-confirm \
+    --manifest-path manifests/workspace-inheritance/crates/tool \
+    --strategies crate-meta-data \
+    --pkg-url 'https://127.0.0.1:1/tool.tgz' \
+    tool
+
+# Test the confirmation is not waited for when stdin is not a terminal
+expect_exit_code 116 "./$1" binstall \
+    --manifest-path manifests/workspace-inheritance/crates/tool \
+    --strategies compile \
+    tool </dev/null
+
+# Test the deadline cancels the download from a mirror which never answers
+python3 exit-codes/hung-server.py &
+server_pid=$!
+trap 'kill $server_pid' ERR INT TERM
+sleep 1
+
+expect_exit_code 115 "./$1" binstall --no-confirm \
+    --manifest-path manifests/hung-mirror-Cargo.toml \
+    --strategies crate-meta-data \
+    --deadline 5 \
+    hung-mirror-test
+
+kill $server_pid || true
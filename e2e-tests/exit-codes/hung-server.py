@@ -0,0 +1,7 @@
+# Accept connections without ever answering, like a hung mirror.
+import socket
+
+server = socket.create_server(("127.0.0.1", 4444))
+connections = []
+while True:
+    connections.append(server.accept())
@@ -0,0 +1,53 @@
+#!/bin/bash
+
+set -euxo pipefail
+
+unset CARGO_INSTALL_ROOT
+
+CARGO_HOME=$(mktemp -d 2>/dev/null || mktemp -d -t 'cargo-home')
+export CARGO_HOME
+export PATH="$CARGO_HOME/bin:$PATH"
+
+lockfile="$CARGO_HOME/binstall.lock"
+
+expect_exit_code() {
+    expected="$1"
+    shift
+
+    set +e
+    "$@"
+    exit_code="$?"
+    set -e
+
+    if [ "$exit_code" != "$expected" ]; then
+        echo "Expected exit code $expected, but actual exit code $exit_code"
+        exit 1
+    fi
+}
+
+# Test the artifact installed is recorded
+"./$1" binstall --no-confirm --no-cache --write-lockfile "$lockfile" cargo-binstall@0.20.1
+grep -E '^version = 1$' "$lockfile"
+grep -E '^name = "cargo-binstall"$' "$lockfile"
+grep -E '^version = "0.20.1"$' "$lockfile"
+grep -E '^sha256 = "[0-9a-f]{64}"$' "$lockfile"
+
+# Test the crate is installed again from the lockfile
+cargo uninstall cargo-binstall
+"./$1" binstall --no-confirm --no-cache --from-lockfile "$lockfile"
+cargo-binstall --help >/dev/null
+cargo_binstall_version="$(cargo-binstall -V)"
+[ "$cargo_binstall_version" = "cargo-binstall 0.20.1" ]
+
+# Test the crate already installed with the version recorded is skipped
+"./$1" binstall --no-confirm --no-cache --from-lockfile "$lockfile"
+
+# Test a tampered digest fails the installation
+sed -i.bak -E 's/^sha256 = "[0-9a-f]/sha256 = "x/' "$lockfile"
+expect_exit_code 42 "./$1" binstall --no-confirm --no-cache --force --from-lockfile "$lockfile"
+
+# Test a lockfile without an entry for the target
+mv "$lockfile.bak" "$lockfile"
+expect_exit_code 118 "./$1" binstall --no-confirm --no-cache --force \
+    --targets riscv64gc-unknown-linux-gnu \
+    --from-lockfile "$lockfile"
@@ -0,0 +1,33 @@
+#!/bin/bash
+
+set -euxo pipefail
+
+unset CARGO_INSTALL_ROOT
+
+CARGO_HOME=$(mktemp -d 2>/dev/null || mktemp -d -t 'cargo-home')
+export CARGO_HOME
+export PATH="$CARGO_HOME/bin:$PATH"
+
+bin="$CARGO_HOME/bin/cargo-watch"
+if [ -e "$bin.exe" ] || [ "${OS:-}" = "Windows_NT" ]; then
+    bin="$bin.exe"
+fi
+
+# Running again with the binaries intact does nothing
+"./$1" binstall --no-confirm cargo-watch@8.4.0
+"./$1" binstall --log-level=info --no-confirm cargo-watch@8.4.0 | grep -q 'cargo-watch v8.4.0 is already installed'
+
+# A corrupt binary is fetched again
+echo corrupt > "$bin"
+"./$1" binstall --log-level=info --no-confirm cargo-watch@8.4.0 | grep -q 'repaired: 1'
+cargo_watch_version="$(cargo watch -V)"
+[ "$cargo_watch_version" = "cargo-watch 8.4.0" ]
+
+# So is a missing one
+rm "$bin"
+"./$1" binstall --log-level=info --no-confirm cargo-watch@8.4.0 | grep -q 'repaired: 1'
+cargo_watch_version="$(cargo watch -V)"
+[ "$cargo_watch_version" = "cargo-watch 8.4.0" ]
+
+# And then it is intact again
+"./$1" binstall --log-level=info --no-confirm cargo-watch@8.4.0 | grep -q 'cargo-watch v8.4.0 is already installed'
@@ -15,4 +15,37 @@ cp "./$1" "$othertmpdir/bin/"
 
 
 cargo binstall --no-confirm cargo-watch@8.4.0
+cargo install --list | grep -q '^cargo-watch v8.4.0:$'
+grep -q '"cargo-watch 8.4.0 (registry+https://github.com/rust-lang/crates.io-index)"' "$CARGO_HOME/.crates2.json"
+
 cargo uninstall cargo-watch
+test ! -e "$CARGO_HOME/bin/cargo-watch"
+if grep -q cargo-watch "$CARGO_HOME/.crates2.json"; then
+    echo "cargo-watch is still recorded in .crates2.json"
+    exit 1
+fi
+
+# Test uninstalling with binstall's records, including the links in --link-dir
+linkdir="$othertmpdir/links"
+cargo binstall --no-confirm --link-dir "$linkdir" cargo-watch@8.4.0
+test -e "$linkdir/cargo-watch"
+
+cargo binstall --uninstall --dry-run cargo-watch
+test -e "$CARGO_HOME/bin/cargo-watch"
+test -e "$linkdir/cargo-watch"
+
+cargo binstall --uninstall cargo-watch
+test ! -e "$CARGO_HOME/bin/cargo-watch"
+test ! -e "$linkdir/cargo-watch"
+for manifest in .crates.toml .crates2.json binstall/crates-v1.json; do
+    if grep -q cargo-watch "$CARGO_HOME/$manifest"; then
+        echo "cargo-watch is still recorded in $manifest"
+        exit 1
+    fi
+done
+
+# Test crates not installed by binstall are rejected
+if cargo binstall --uninstall cargo-watch; then
+    echo "Uninstalling a crate which is not installed should fail"
+    exit 1
+fi
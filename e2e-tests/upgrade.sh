@@ -8,6 +8,10 @@ CARGO_HOME=$(mktemp -d 2>/dev/null || mktemp -d -t 'cargo-home')
 export CARGO_HOME
 export PATH="$CARGO_HOME/bin:$PATH"
 
+# Test --upgrade and --outdated without crate names and nothing installed
+"./$1" binstall --no-confirm --upgrade
+"./$1" binstall --outdated
+
 # Test skip when installed
 "./$1" binstall --no-confirm --force cargo-binstall@0.11.1
 "./$1" binstall --log-level=info --no-confirm cargo-binstall@0.11.1 | grep -q 'cargo-binstall v0.11.1 is already installed'
@@ -16,3 +20,6 @@ export PATH="$CARGO_HOME/bin:$PATH"
 "./$1" binstall --no-confirm cargo-binstall@0.12.0
 "./$1" binstall --log-level=info --no-confirm cargo-binstall@0.12.0 | grep -q 'cargo-binstall v0.12.0 is already installed'
 "./$1" binstall --log-level=info --no-confirm cargo-binstall@^0.12.0 | grep -q -v 'cargo-binstall v0.12.0 is already installed'
+
+# Test --outdated without crate names checks the installed crates
+"./$1" binstall --outdated